@@ -0,0 +1,98 @@
+//! Decoding of records of a 30 field template with and without a decode plan.
+//!
+//! Without a plan every record locates its fields and resolves their parsers
+//! again, with the plan compiled when the template is registered the offsets
+//! and parsers of the fields are looked up once.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use fluss::ipfix::parser::{DataSet, FieldSpecifier};
+use fluss::ipfix::{FieldParser, Parser};
+use fluss::produce::IpfixParser;
+use fluss::testing::field;
+
+/// The fat template of `fluss bench`.
+fn fat_fields() -> Vec<FieldSpecifier> {
+    [
+        (1, 8),
+        (2, 8),
+        (4, 1),
+        (5, 1),
+        (6, 2),
+        (7, 2),
+        (8, 4),
+        (9, 1),
+        (10, 4),
+        (11, 2),
+        (12, 4),
+        (13, 1),
+        (14, 4),
+        (15, 4),
+        (16, 4),
+        (17, 4),
+        (21, 4),
+        (22, 4),
+        (23, 8),
+        (24, 8),
+        (56, 6),
+        (58, 2),
+        (59, 2),
+        (61, 1),
+        (81, 6),
+        (136, 1),
+        (225, 4),
+        (226, 4),
+        (227, 2),
+        (228, 2),
+    ]
+    .iter()
+    .map(|&(id, length)| field(id, length))
+    .collect()
+}
+
+fn bench<P: Parser>(
+    c: &mut Criterion,
+    name: &str,
+    parser: P,
+    fields: &[FieldSpecifier],
+    data: &[u8],
+) {
+    let set = DataSet { id: 256, data };
+    let plan = parser.compile(fields);
+
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("unplanned", |b| {
+        b.iter(|| {
+            parser
+                .parse_in_domain(1, black_box(fields), black_box(&set))
+                .is_some()
+        })
+    });
+    group.bench_function("planned", |b| {
+        b.iter(|| {
+            parser
+                .parse_planned(&plan, 1, black_box(fields), black_box(&set))
+                .is_some()
+        })
+    });
+    group.finish();
+}
+
+fn decode_plan(c: &mut Criterion) {
+    let fields = fat_fields();
+    assert_eq!(fields.len(), 30);
+    let length = fields.iter().map(|field| field.length as usize).sum();
+    let data = (0..length).map(|i| (i * 31 + 7) as u8).collect::<Vec<_>>();
+
+    bench(c, "decode_plan/ipfix", IpfixParser::new(), &fields, &data);
+    bench(
+        c,
+        "decode_plan/fields",
+        FieldParser::default(),
+        &fields,
+        &data,
+    );
+}
+
+criterion_group!(benches, decode_plan);
+criterion_main!(benches);
@@ -0,0 +1,108 @@
+//! Decode throughput of traffic of many exporters sharded across workers.
+//!
+//! Like the decode workers of `collect`, every worker owns the sessions of
+//! its exporters and decodes them without sharing state with other workers,
+//! the throughput should scale with the number of workers up to the number
+//! of cores.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use fluss::ipfix::parser::TemplateRecord;
+use fluss::ipfix::{parse, Session};
+use fluss::produce::IpfixParser;
+use fluss::testing::{field, DataRecord, MessageBuilder};
+use std::net::Ipv4Addr;
+
+const EXPORTERS: usize = 16;
+const MESSAGES_PER_EXPORTER: usize = 100;
+const RECORDS_PER_MESSAGE: usize = 10;
+const TEMPLATE_ID: u16 = 256;
+
+/// Messages of a single exporter, the first one announces the template.
+struct Exporter {
+    messages: Vec<Vec<u8>>,
+}
+
+impl Exporter {
+    fn new(index: usize) -> Self {
+        let template = TemplateRecord {
+            id: TEMPLATE_ID,
+            fields: vec![
+                field(8, 4),
+                field(12, 4),
+                field(7, 2),
+                field(11, 2),
+                field(4, 1),
+                field(1, 8),
+                field(2, 8),
+            ],
+        };
+
+        let mut builder = MessageBuilder::new(index as u32);
+        let mut messages = vec![builder.templates(&[template])];
+        for message in 0..MESSAGES_PER_EXPORTER {
+            let records = (0..RECORDS_PER_MESSAGE)
+                .map(|record| {
+                    let n = (message * RECORDS_PER_MESSAGE + record) as u32;
+                    DataRecord::new()
+                        .addr(Ipv4Addr::from(0x0a00_0000 | n).into())
+                        .addr(Ipv4Addr::from(0xc000_0200 | index as u32).into())
+                        .u16(1024 + n as u16)
+                        .u16(443)
+                        .u8(6)
+                        .u64(u64::from(n) * 100)
+                        .u64(u64::from(n % 50))
+                })
+                .collect::<Vec<_>>();
+            messages.push(builder.data(TEMPLATE_ID, &records));
+        }
+
+        Self { messages }
+    }
+}
+
+/// Decodes the traffic of all exporters with `workers` threads, exporters are
+/// assigned to workers by their index. Returns the number of decoded flows.
+fn decode_sharded(exporters: &[Exporter], workers: usize) -> usize {
+    std::thread::scope(|scope| {
+        let handles = (0..workers)
+            .map(|worker| {
+                scope.spawn(move || {
+                    let mut flows = 0;
+                    for exporter in exporters.iter().skip(worker).step_by(workers) {
+                        let session = Session::new(IpfixParser::new());
+                        for message in &exporter.messages {
+                            let packet = parse(message).unwrap();
+                            flows += session.parse(&packet).unwrap().len();
+                        }
+                    }
+                    flows
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .sum()
+    })
+}
+
+fn decode_workers(c: &mut Criterion) {
+    let exporters = (0..EXPORTERS).map(Exporter::new).collect::<Vec<_>>();
+    let flows = EXPORTERS * MESSAGES_PER_EXPORTER * RECORDS_PER_MESSAGE;
+    assert_eq!(decode_sharded(&exporters, 1), flows);
+
+    let mut group = c.benchmark_group("decode_workers");
+    group.throughput(Throughput::Elements(flows as u64));
+    for workers in [1, 2, 4, 8] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(workers),
+            &workers,
+            |b, &workers| b.iter(|| decode_sharded(&exporters, workers)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode_workers);
+criterion_main!(benches);
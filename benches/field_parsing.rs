@@ -0,0 +1,203 @@
+//! Parsers of single fields, records and whole packets on fixed inputs.
+//!
+//! The throughput of `session` is in decoded flows, the elements per second
+//! criterion reports are flows per second.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use fluss::ipfix::parser::{parse, DataSet, FieldSpecifier, TemplateRecord};
+use fluss::ipfix::{FieldParser, Parser, Session};
+use fluss::produce::IpfixParser;
+use fluss::protocol::{parse_ipv4, parse_ipv6, parse_mac6, parse_number, Value};
+use fluss::testing::{field, DataRecord, MessageBuilder};
+use std::net::Ipv4Addr;
+
+/// A template with addresses, ports, counters, timestamps and interfaces of both directions.
+fn flow_template() -> TemplateRecord {
+    TemplateRecord {
+        id: 256,
+        fields: vec![
+            field(8, 4),
+            field(12, 4),
+            field(7, 2),
+            field(11, 2),
+            field(4, 1),
+            field(6, 1),
+            field(5, 1),
+            field(10, 4),
+            field(14, 4),
+            field(58, 2),
+            field(1, 8),
+            field(2, 8),
+            field(152, 8),
+            field(153, 8),
+        ],
+    }
+}
+
+/// A HTTPS download of 1.5 MB in one second.
+fn flow_record(i: u32) -> DataRecord {
+    DataRecord::new()
+        .addr(Ipv4Addr::from(0x0a00_0000 + i).into())
+        .addr(Ipv4Addr::new(142, 250, 1, 2).into())
+        .u16(49152 + i as u16)
+        .u16(443)
+        .u8(6)
+        .u8(0x1b)
+        .u8(0)
+        .u32(3)
+        .u32(7)
+        .u16(120)
+        .u64(1_500_000)
+        .u64(1100)
+        .u64(1_714_564_800_000)
+        .u64(1_714_564_801_000)
+}
+
+/// 40 fields, IPv6 addresses, MACs and timestamps among them.
+fn wide_fields() -> Vec<FieldSpecifier> {
+    [
+        (1, 8),
+        (2, 8),
+        (4, 1),
+        (5, 1),
+        (6, 2),
+        (7, 2),
+        (8, 4),
+        (9, 1),
+        (10, 4),
+        (11, 2),
+        (12, 4),
+        (13, 1),
+        (14, 4),
+        (15, 4),
+        (16, 4),
+        (17, 4),
+        (21, 4),
+        (22, 4),
+        (23, 8),
+        (24, 8),
+        (27, 16),
+        (28, 16),
+        (29, 1),
+        (30, 1),
+        (32, 2),
+        (56, 6),
+        (58, 2),
+        (59, 2),
+        (61, 1),
+        (80, 6),
+        (81, 6),
+        (136, 1),
+        (150, 4),
+        (151, 4),
+        (152, 8),
+        (153, 8),
+        (225, 4),
+        (226, 4),
+        (227, 2),
+        (228, 2),
+    ]
+    .iter()
+    .map(|&(id, length)| field(id, length))
+    .collect()
+}
+
+type FieldExtractor = fn(&[u8]) -> Value;
+
+fn fields(c: &mut Criterion) {
+    let inputs: [(&str, FieldExtractor, &[u8]); 7] = [
+        ("parse_number/1", parse_number, &[0x2a]),
+        ("parse_number/2", parse_number, &[0x01, 0xbb]),
+        ("parse_number/4", parse_number, &[0x00, 0x01, 0x86, 0xa0]),
+        (
+            "parse_number/8",
+            parse_number,
+            &[0, 0, 0, 0x02, 0x54, 0x0b, 0xe4, 0x00],
+        ),
+        ("parse_ipv4", parse_ipv4, &[10, 0, 0, 1]),
+        (
+            "parse_ipv6",
+            parse_ipv6,
+            &[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+        ),
+        (
+            "parse_mac6",
+            parse_mac6,
+            &[0x02, 0x42, 0xac, 0x11, 0x00, 0x02],
+        ),
+    ];
+
+    let mut group = c.benchmark_group("fields");
+    for (name, parse, input) in inputs {
+        group.bench_function(name, |b| b.iter(|| parse(black_box(input))));
+    }
+    group.finish();
+}
+
+fn records(c: &mut Criterion) {
+    let fields = wide_fields();
+    assert_eq!(fields.len(), 40);
+    let length = fields.iter().map(|field| field.length as usize).sum();
+    let data = (0..length).map(|i| (i * 31 + 7) as u8).collect::<Vec<_>>();
+    let parser = FieldParser::default();
+    let set = DataSet {
+        id: 256,
+        data: &data,
+    };
+    assert_eq!(parser.parse(&fields, &set).unwrap().records.len(), 40);
+
+    let template = flow_template();
+    let data = flow_record(1).into_bytes();
+    let flow = DataSet {
+        id: 256,
+        data: &data,
+    };
+    let ipfix = IpfixParser::new();
+    assert_eq!(
+        ipfix.parse(&template.fields, &flow).unwrap().bytes,
+        1_500_000
+    );
+
+    let mut group = c.benchmark_group("records");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("FieldParser::parse/40 fields", |b| {
+        b.iter(|| parser.parse(black_box(&fields), black_box(&set)))
+    });
+    group.bench_function("IpfixParser::parse/flow", |b| {
+        b.iter(|| ipfix.parse(black_box(&template.fields), black_box(&flow)))
+    });
+    group.finish();
+}
+
+fn session(c: &mut Criterion) {
+    // a template refresh and two data sets, like routers send them
+    let template = flow_template();
+    let mut builder = MessageBuilder::new(1);
+    let mut packet = builder.templates(std::slice::from_ref(&template));
+    for records in [0..20, 20..30] {
+        let records = records.map(flow_record).collect::<Vec<_>>();
+        let data = builder.data(template.id, &records);
+        packet.extend_from_slice(&data[16..]);
+    }
+    let length = packet.len() as u16;
+    packet[2..4].copy_from_slice(&length.to_be_bytes());
+
+    let packet = parse(&packet).unwrap();
+    let session = Session::new(IpfixParser::new());
+    assert_eq!(session.parse(&packet).unwrap().len(), 30);
+
+    let mut group = c.benchmark_group("session");
+    group.throughput(Throughput::Elements(30));
+    group.bench_function("Session::parse/3 sets", |b| {
+        b.iter(|| {
+            let flows = session.parse(black_box(&packet));
+            // the same packet over and over is a sequence gap every time
+            session.events().for_each(drop);
+            flows
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, fields, records, session);
+criterion_main!(benches);
@@ -0,0 +1,88 @@
+//! Lookups in prefix tables of 10 and 10,000 networks.
+//!
+//! The trie visits at most one node per address bit, lookups in the large
+//! table take about as long as in the small one. The linear scan over the
+//! same prefixes is the baseline a trie has to beat.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use fluss::enrich::prefix::Network;
+use fluss::enrich::PrefixEnricher;
+use fluss::exporters::Cidr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Pseudo random prefixes, half IPv4 with 8 to 32 bits and half IPv6 with 16 to 64 bits.
+fn prefixes(count: u32) -> Vec<Cidr> {
+    (0..count)
+        .map(|i| {
+            let random = i.wrapping_mul(2_654_435_761);
+            if i % 2 == 0 {
+                let addr = Ipv4Addr::from(random);
+                Cidr::new(addr.into(), 8 + (random % 25) as u8).unwrap()
+            } else {
+                let addr = Ipv6Addr::from(0x2001_0db8_u128 << 96 | u128::from(random) << 64);
+                Cidr::new(addr.into(), 16 + (random % 49) as u8).unwrap()
+            }
+        })
+        .collect()
+}
+
+fn addresses() -> Vec<IpAddr> {
+    (0..256u32)
+        .map(|i| {
+            let random = i.wrapping_mul(2_246_822_519);
+            if i % 2 == 0 {
+                Ipv4Addr::from(random).into()
+            } else {
+                Ipv6Addr::from(0x2001_0db8_u128 << 96 | u128::from(random) << 64).into()
+            }
+        })
+        .collect()
+}
+
+fn prefix_lookup(c: &mut Criterion) {
+    let addresses = addresses();
+
+    let mut group = c.benchmark_group("prefix_lookup");
+    for count in [10, 10_000] {
+        let prefixes = prefixes(count);
+        let mut enricher = PrefixEnricher::new();
+        for (i, prefix) in prefixes.iter().enumerate() {
+            let network = Network {
+                name: Some(format!("network-{}", i)),
+                ..Network::default()
+            };
+            enricher.insert(*prefix, network).unwrap();
+        }
+
+        group.bench_with_input(BenchmarkId::new("trie", count), &enricher, |b, enricher| {
+            b.iter(|| {
+                addresses
+                    .iter()
+                    .filter(|&&addr| enricher.lookup(black_box(addr)).is_some())
+                    .count()
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("linear", count),
+            &prefixes,
+            |b, prefixes| {
+                b.iter(|| {
+                    addresses
+                        .iter()
+                        .filter(|&&addr| {
+                            prefixes
+                                .iter()
+                                .filter(|prefix| prefix.contains(black_box(addr)))
+                                .max_by_key(|prefix| prefix.prefix_len())
+                                .is_some()
+                        })
+                        .count()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, prefix_lookup);
+criterion_main!(benches);
@@ -0,0 +1,59 @@
+//! Publishes a user defined payload instead of flows through the generic `Publisher` trait.
+
+use async_trait::async_trait;
+use fluss::protocol::{Record, RecordSet, RecordSetOwned, Value};
+use fluss::publish::{ConsolePublisher, Publisher};
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// An aggregate computed from flows, published instead of the flows themselves.
+struct BandwidthAlert {
+    interface: u32,
+    bytes_per_second: u64,
+}
+
+struct AlertLog {
+    alerts: AtomicU64,
+}
+
+#[async_trait]
+impl Publisher<BandwidthAlert> for AlertLog {
+    async fn publish(&self, alert: &BandwidthAlert) -> anyhow::Result<()> {
+        let count = self.alerts.fetch_add(1, Ordering::Relaxed) + 1;
+        println!(
+            "alert #{}: interface {} at {}B/s",
+            count, alert.interface, alert.bytes_per_second
+        );
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let alerts: Arc<dyn Publisher<BandwidthAlert> + Send + Sync> = Arc::new(AlertLog {
+        alerts: AtomicU64::new(0),
+    });
+    alerts
+        .publish(&BandwidthAlert {
+            interface: 3,
+            bytes_per_second: 125_000_000,
+        })
+        .await?;
+
+    // the built-in publishers also accept raw record sets
+    let record_set: RecordSetOwned = RecordSet::new(
+        256,
+        vec![
+            Record::new(8, Value::Ipv4Addr(Ipv4Addr::new(10, 0, 0, 1))),
+            Record::new(1, Value::U64(1500)),
+        ],
+    );
+    let console: Arc<dyn Publisher<RecordSetOwned> + Send + Sync> =
+        Arc::new(ConsolePublisher::new());
+    console.publish(&record_set).await?;
+
+    Ok(())
+}
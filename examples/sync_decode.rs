@@ -0,0 +1,65 @@
+//! Decodes IPFIX messages from a file without an async runtime.
+//!
+//! The file contains IPFIX messages back to back, e.g. the payloads of
+//! captured datagrams. Messages are read on the main thread and decoded on a
+//! worker thread, like in an application which owns its sockets.
+//!
+//! ```text
+//! cargo run --example sync_decode -- messages.bin 192.0.2.1:4739
+//! ```
+
+use fluss::ipfix::{Decoder, PacketContext};
+use std::net::SocketAddr;
+use std::sync::{mpsc, Arc};
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let path = args
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("usage: sync_decode <file> [exporter]"))?;
+    let exporter: SocketAddr = match args.next() {
+        Some(exporter) => exporter.parse()?,
+        None => ([127, 0, 0, 1], 4739).into(),
+    };
+    let data = std::fs::read(path)?;
+
+    // the decoder is shared, every thread could decode datagrams of other exporters
+    let decoder = Arc::new(Decoder::new());
+    let (tx, rx) = mpsc::sync_channel::<Vec<u8>>(64);
+
+    let worker = {
+        let decoder = Arc::clone(&decoder);
+        std::thread::spawn(move || {
+            let mut flows = 0;
+            for datagram in rx {
+                match decoder.decode_with(&datagram, PacketContext::new(exporter), |fluss| {
+                    println!("{}", fluss)
+                }) {
+                    Ok(count) => flows += count,
+                    Err(err) => eprintln!("failed to decode message: {}", err),
+                }
+            }
+            flows
+        })
+    };
+
+    let mut rest = &data[..];
+    while rest.len() >= 4 {
+        // the length of the message is part of its header
+        let len = u16::from_be_bytes([rest[2], rest[3]]) as usize;
+        if len < 16 || len > rest.len() {
+            anyhow::bail!("truncated message of {} bytes", len);
+        }
+        tx.send(rest[..len].to_vec())?;
+        rest = &rest[len..];
+    }
+    drop(tx);
+
+    let flows = worker.join().expect("the worker panicked");
+    eprintln!("decoded {} flows", flows);
+
+    if let Some(session) = decoder.session(exporter) {
+        eprintln!("learned {} templates", session.templates().len());
+    }
+    Ok(())
+}
@@ -0,0 +1,161 @@
+//! Generates the built-in tables of IANA registries.
+//!
+//! `data/service-names-port-numbers.csv` contains the service name and
+//! transport protocol port number registry and can be replaced with the full
+//! registry from
+//! <https://www.iana.org/assignments/service-names-port-numbers/service-names-port-numbers.csv>.
+//!
+//! `assets/ipfix-information-elements.csv` contains the IPFIX information
+//! elements and can be replaced with the full registry from
+//! <https://www.iana.org/assignments/ipfix/ipfix-information-elements.csv>.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+const SERVICE_NAMES: &str = "data/service-names-port-numbers.csv";
+const INFORMATION_ELEMENTS: &str = "assets/ipfix-information-elements.csv";
+
+fn main() {
+    generate_service_names();
+    generate_information_elements();
+}
+
+fn generate_service_names() {
+    println!("cargo:rerun-if-changed={}", SERVICE_NAMES);
+
+    let csv = fs::read_to_string(SERVICE_NAMES).expect("failed to read service names");
+
+    let mut services = Vec::new();
+    // first row is the header: Service Name,Port Number,Transport Protocol,...
+    for record in parse_csv(&csv).into_iter().skip(1) {
+        let (name, ports, protocol) = match record.as_slice() {
+            [name, ports, protocol, ..] if !name.is_empty() && !ports.is_empty() => {
+                (name, ports, protocol)
+            }
+            // reserved and unassigned ports have no service name
+            _ => continue,
+        };
+
+        let protocol: u8 = match protocol.as_str() {
+            "tcp" => 6,
+            "udp" => 17,
+            "dccp" => 33,
+            "sctp" => 132,
+            _ => continue,
+        };
+
+        let (start, end) = ports.split_once('-').unwrap_or((ports, ports));
+        let (start, end): (u16, u16) = match (start.parse(), end.parse()) {
+            (Ok(start), Ok(end)) => (start, end),
+            _ => panic!("invalid port number {:?} for service {:?}", ports, name),
+        };
+
+        for port in start..=end {
+            services.push((protocol, port, name.clone()));
+        }
+    }
+
+    // the registry lists the primary name of a port first, the sort is stable
+    services.sort_by_key(|&(protocol, port, _)| (protocol, port));
+    services.dedup_by_key(|&mut (protocol, port, _)| (protocol, port));
+
+    let mut out = String::from("static SERVICE_NAMES: &[(u8, u16, &str)] = &[\n");
+    for (protocol, port, name) in services {
+        writeln!(out, "    ({}, {}, {:?}),", protocol, port, name).unwrap();
+    }
+    out.push_str("];\n");
+
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("service_names.rs");
+    fs::write(path, out).expect("failed to write service name table");
+}
+
+fn generate_information_elements() {
+    println!("cargo:rerun-if-changed={}", INFORMATION_ELEMENTS);
+
+    let csv =
+        fs::read_to_string(INFORMATION_ELEMENTS).expect("failed to read information elements");
+
+    let mut elements = Vec::new();
+    // first row is the header: ElementID,Name,Abstract Data Type,Data Type Semantics,Status,...
+    for record in parse_csv(&csv).into_iter().skip(1) {
+        let (id, name, data_type, semantics, status) = match record.as_slice() {
+            [id, name, data_type, semantics, status, ..] if !data_type.is_empty() => {
+                (id, name, data_type, semantics, status)
+            }
+            // reserved and unassigned ranges have no data type
+            _ => continue,
+        };
+
+        let id: u16 = match id.parse() {
+            Ok(id) if id < 0x8000 => id,
+            _ => panic!(
+                "invalid element id {:?} of information element {:?}",
+                id, name
+            ),
+        };
+        elements.push((
+            id,
+            name.clone(),
+            data_type.clone(),
+            semantics.clone(),
+            status == "deprecated",
+        ));
+    }
+
+    elements.sort_by_key(|&(id, ..)| id);
+    if let Some(window) = elements
+        .windows(2)
+        .find(|window| window[0].0 == window[1].0)
+    {
+        panic!("duplicate information element {}", window[0].0);
+    }
+
+    let mut out = String::from("static INFORMATION_ELEMENTS: &[InformationElement] = &[\n");
+    for (id, name, data_type, semantics, deprecated) in elements {
+        writeln!(
+            out,
+            "    InformationElement {{ id: {}, name: {:?}, data_type: {:?}, semantics: {:?}, deprecated: {} }},",
+            id, name, data_type, semantics, deprecated
+        )
+        .unwrap();
+    }
+    out.push_str("];\n");
+
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("information_elements.rs");
+    fs::write(path, out).expect("failed to write information element table");
+}
+
+/// Minimal RFC 4180 parser, quoted fields may contain separators and line breaks.
+fn parse_csv(input: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => record.push(std::mem::take(&mut field)),
+            ('\r', false) => (),
+            ('\n', false) => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (c, _) => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    records
+}
@@ -0,0 +1,526 @@
+//! Conversion of flows to Apache Arrow record batches.
+//!
+//! The columns follow the serialized [`Fluss`], addresses and enums are stored
+//! as strings. Labels and extra fields differ between exporters and are not
+//! part of the fixed schema.
+
+use crate::fluss::Fluss;
+use ::arrow::array::{
+    ArrayRef, BooleanBuilder, Int64Builder, StringBuilder, TimestampMillisecondBuilder,
+    UInt16Builder, UInt32Builder, UInt64Builder, UInt8Builder,
+};
+use ::arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use ::arrow::error::ArrowError;
+use ::arrow::record_batch::RecordBatch;
+use std::sync::Arc;
+
+/// The schema of the record batches built by [`FlussArrowBuilder`].
+pub fn schema() -> Schema {
+    let field =
+        |name: &str, data_type: DataType, nullable: bool| Field::new(name, data_type, nullable);
+
+    Schema::new(vec![
+        field("type", DataType::Utf8, false),
+        field(
+            "time_received",
+            DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+            false,
+        ),
+        field("exporter", DataType::Utf8, true),
+        field("flow_age", DataType::UInt64, false),
+        field(
+            "flow_start",
+            DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+            true,
+        ),
+        field(
+            "flow_end",
+            DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+            true,
+        ),
+        field("clock_skew_ms", DataType::Int64, true),
+        field("flow_direction", DataType::Utf8, false),
+        field("is_bidirectional", DataType::Boolean, false),
+        field("ingress_interface", DataType::UInt32, false),
+        field("egress_interface", DataType::UInt32, false),
+        field("ingress_interface_name", DataType::Utf8, true),
+        field("egress_interface_name", DataType::Utf8, true),
+        field("bytes", DataType::UInt64, false),
+        field("packets", DataType::UInt64, false),
+        field("bytes_in", DataType::UInt64, false),
+        field("bytes_out", DataType::UInt64, false),
+        field("packets_in", DataType::UInt64, false),
+        field("packets_out", DataType::UInt64, false),
+        field("sampling_interval", DataType::UInt32, true),
+        field("dscp", DataType::UInt8, false),
+        field("ethernet_type", DataType::UInt16, false),
+        field("protocol", DataType::Utf8, false),
+        field("src_mac", DataType::Utf8, true),
+        field("dst_mac", DataType::Utf8, true),
+        field("src_addr", DataType::Utf8, false),
+        field("dst_addr", DataType::Utf8, false),
+        field("src_net", DataType::UInt8, false),
+        field("dst_net", DataType::UInt8, false),
+        field("src_port", DataType::UInt16, false),
+        field("dst_port", DataType::UInt16, false),
+        field("icmp_type", DataType::UInt8, true),
+        field("icmp_code", DataType::UInt8, true),
+        field("vlan_id", DataType::UInt16, false),
+        field("post_vlan_id", DataType::UInt16, false),
+        field("post_nat_src_addr", DataType::Utf8, false),
+        field("post_nat_dst_addr", DataType::Utf8, false),
+        field("post_napt_src_port", DataType::UInt16, false),
+        field("post_napt_dst_port", DataType::UInt16, false),
+        field("next_hop_addr", DataType::Utf8, false),
+        field("tunnel_type", DataType::Utf8, true),
+        field("tunnel_id", DataType::UInt64, true),
+        field("inner_src_addr", DataType::Utf8, true),
+        field("inner_dst_addr", DataType::Utf8, true),
+        field("inner_src_port", DataType::UInt16, true),
+        field("inner_dst_port", DataType::UInt16, true),
+        field("outer_src_addr", DataType::Utf8, true),
+        field("outer_dst_addr", DataType::Utf8, true),
+        field("outer_src_port", DataType::UInt16, true),
+        field("outer_dst_port", DataType::UInt16, true),
+        field("tcp_flags", DataType::UInt16, false),
+        field("reverse_tcp_flags", DataType::UInt16, false),
+        field("flow_end_reason", DataType::Utf8, true),
+        field("flow_state", DataType::Utf8, true),
+        field("flow_class", DataType::Utf8, true),
+        field("service", DataType::Utf8, true),
+        field("tenant", DataType::Utf8, true),
+        field("src_net_name", DataType::Utf8, true),
+        field("dst_net_name", DataType::Utf8, true),
+        field("dns_query", DataType::Utf8, true),
+        field("dns_qtype", DataType::UInt16, true),
+        field("http_host", DataType::Utf8, true),
+        field("http_url", DataType::Utf8, true),
+        field("http_status", DataType::UInt16, true),
+        field("tls_sni", DataType::Utf8, true),
+        field("lossy_utf8", DataType::Boolean, false),
+        field("suspect", DataType::Boolean, false),
+    ])
+}
+
+/// Accumulates flows column by column into record batches.
+///
+/// ```ignore
+/// let mut builder = FlussArrowBuilder::new();
+/// for flow in &flows {
+///     builder.push(flow);
+/// }
+/// writer.write(&builder.finish()?)?;
+/// ```
+#[derive(Debug, Default)]
+pub struct FlussArrowBuilder {
+    r#type: StringBuilder,
+    time_received: TimestampMillisecondBuilder,
+    exporter: StringBuilder,
+    flow_age: UInt64Builder,
+    flow_start: TimestampMillisecondBuilder,
+    flow_end: TimestampMillisecondBuilder,
+    clock_skew_ms: Int64Builder,
+    flow_direction: StringBuilder,
+    is_bidirectional: BooleanBuilder,
+    ingress_interface: UInt32Builder,
+    egress_interface: UInt32Builder,
+    ingress_interface_name: StringBuilder,
+    egress_interface_name: StringBuilder,
+    bytes: UInt64Builder,
+    packets: UInt64Builder,
+    bytes_in: UInt64Builder,
+    bytes_out: UInt64Builder,
+    packets_in: UInt64Builder,
+    packets_out: UInt64Builder,
+    sampling_interval: UInt32Builder,
+    dscp: UInt8Builder,
+    ethernet_type: UInt16Builder,
+    protocol: StringBuilder,
+    src_mac: StringBuilder,
+    dst_mac: StringBuilder,
+    src_addr: StringBuilder,
+    dst_addr: StringBuilder,
+    src_net: UInt8Builder,
+    dst_net: UInt8Builder,
+    src_port: UInt16Builder,
+    dst_port: UInt16Builder,
+    icmp_type: UInt8Builder,
+    icmp_code: UInt8Builder,
+    vlan_id: UInt16Builder,
+    post_vlan_id: UInt16Builder,
+    post_nat_src_addr: StringBuilder,
+    post_nat_dst_addr: StringBuilder,
+    post_napt_src_port: UInt16Builder,
+    post_napt_dst_port: UInt16Builder,
+    next_hop_addr: StringBuilder,
+    tunnel_type: StringBuilder,
+    tunnel_id: UInt64Builder,
+    inner_src_addr: StringBuilder,
+    inner_dst_addr: StringBuilder,
+    inner_src_port: UInt16Builder,
+    inner_dst_port: UInt16Builder,
+    outer_src_addr: StringBuilder,
+    outer_dst_addr: StringBuilder,
+    outer_src_port: UInt16Builder,
+    outer_dst_port: UInt16Builder,
+    tcp_flags: UInt16Builder,
+    reverse_tcp_flags: UInt16Builder,
+    flow_end_reason: StringBuilder,
+    flow_state: StringBuilder,
+    flow_class: StringBuilder,
+    service: StringBuilder,
+    tenant: StringBuilder,
+    src_net_name: StringBuilder,
+    dst_net_name: StringBuilder,
+    dns_query: StringBuilder,
+    dns_qtype: UInt16Builder,
+    http_host: StringBuilder,
+    http_url: StringBuilder,
+    http_status: UInt16Builder,
+    tls_sni: StringBuilder,
+    lossy_utf8: BooleanBuilder,
+    suspect: BooleanBuilder,
+    len: usize,
+}
+
+impl FlussArrowBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of flows pushed since the last batch was finished.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Appends a flow as a new row.
+    pub fn push(&mut self, flow: &Fluss) {
+        self.r#type.append_value(flow.r#type.to_string());
+        self.time_received
+            .append_value(flow.time_received.timestamp_millis());
+        self.exporter
+            .append_option(flow.exporter.map(|exporter| exporter.to_string()));
+        self.flow_age.append_value(flow.flow_age.as_millis() as u64);
+        self.flow_start
+            .append_option(flow.flow_start.map(|start| start.timestamp_millis()));
+        self.flow_end
+            .append_option(flow.flow_end.map(|end| end.timestamp_millis()));
+        self.clock_skew_ms.append_option(flow.clock_skew_ms);
+        self.flow_direction
+            .append_value(flow.flow_direction.to_string());
+        self.is_bidirectional.append_value(flow.is_bidirectional);
+        self.ingress_interface.append_value(flow.ingress_interface);
+        self.egress_interface.append_value(flow.egress_interface);
+        self.ingress_interface_name
+            .append_option(flow.ingress_interface_name.as_deref());
+        self.egress_interface_name
+            .append_option(flow.egress_interface_name.as_deref());
+        self.bytes.append_value(flow.bytes);
+        self.packets.append_value(flow.packets);
+        self.bytes_in.append_value(flow.bytes_in);
+        self.bytes_out.append_value(flow.bytes_out);
+        self.packets_in.append_value(flow.packets_in);
+        self.packets_out.append_value(flow.packets_out);
+        self.sampling_interval.append_option(flow.sampling_interval);
+        self.dscp.append_value(flow.dscp);
+        self.ethernet_type.append_value(flow.ethernet_type);
+        self.protocol.append_value(flow.protocol.to_string());
+        self.src_mac
+            .append_option(flow.src_mac.map(|mac| mac.to_string()));
+        self.dst_mac
+            .append_option(flow.dst_mac.map(|mac| mac.to_string()));
+        self.src_addr.append_value(flow.src_addr.to_string());
+        self.dst_addr.append_value(flow.dst_addr.to_string());
+        self.src_net.append_value(flow.src_net);
+        self.dst_net.append_value(flow.dst_net);
+        self.src_port.append_value(flow.src_port);
+        self.dst_port.append_value(flow.dst_port);
+        self.icmp_type.append_option(flow.icmp_type);
+        self.icmp_code.append_option(flow.icmp_code);
+        self.vlan_id.append_value(flow.vlan_id);
+        self.post_vlan_id.append_value(flow.post_vlan_id);
+        self.post_nat_src_addr
+            .append_value(flow.post_nat_src_addr.to_string());
+        self.post_nat_dst_addr
+            .append_value(flow.post_nat_dst_addr.to_string());
+        self.post_napt_src_port
+            .append_value(flow.post_napt_src_port);
+        self.post_napt_dst_port
+            .append_value(flow.post_napt_dst_port);
+        self.next_hop_addr
+            .append_value(flow.next_hop_addr.to_string());
+        self.tunnel_type.append_option(flow.tunnel_type.as_deref());
+        self.tunnel_id.append_option(flow.tunnel_id);
+        self.inner_src_addr
+            .append_option(flow.inner_src_addr.map(|addr| addr.to_string()));
+        self.inner_dst_addr
+            .append_option(flow.inner_dst_addr.map(|addr| addr.to_string()));
+        self.inner_src_port.append_option(flow.inner_src_port);
+        self.inner_dst_port.append_option(flow.inner_dst_port);
+        self.outer_src_addr
+            .append_option(flow.outer_src_addr.map(|addr| addr.to_string()));
+        self.outer_dst_addr
+            .append_option(flow.outer_dst_addr.map(|addr| addr.to_string()));
+        self.outer_src_port.append_option(flow.outer_src_port);
+        self.outer_dst_port.append_option(flow.outer_dst_port);
+        self.tcp_flags.append_value(flow.tcp_flags);
+        self.reverse_tcp_flags.append_value(flow.reverse_tcp_flags);
+        self.flow_end_reason
+            .append_option(flow.flow_end_reason.map(|reason| reason.to_string()));
+        self.flow_state
+            .append_option(flow.flow_state.map(|state| state.to_string()));
+        self.flow_class
+            .append_option(flow.flow_class.map(|class| class.to_string()));
+        self.service.append_option(flow.service.as_deref());
+        self.tenant.append_option(flow.tenant.as_deref());
+        self.src_net_name
+            .append_option(flow.src_net_name.as_deref());
+        self.dst_net_name
+            .append_option(flow.dst_net_name.as_deref());
+        self.dns_query.append_option(flow.dns_query.as_deref());
+        self.dns_qtype.append_option(flow.dns_qtype);
+        self.http_host.append_option(flow.http_host.as_deref());
+        self.http_url.append_option(flow.http_url.as_deref());
+        self.http_status.append_option(flow.http_status);
+        self.tls_sni.append_option(flow.tls_sni.as_deref());
+        self.lossy_utf8.append_value(flow.lossy_utf8);
+        self.suspect.append_value(flow.suspect);
+        self.len += 1;
+    }
+
+    /// Builds a record batch of all pushed flows and resets the builder.
+    pub fn finish(&mut self) -> Result<RecordBatch, ArrowError> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.r#type.finish()),
+            Arc::new(self.time_received.finish().with_timezone("UTC")),
+            Arc::new(self.exporter.finish()),
+            Arc::new(self.flow_age.finish()),
+            Arc::new(self.flow_start.finish().with_timezone("UTC")),
+            Arc::new(self.flow_end.finish().with_timezone("UTC")),
+            Arc::new(self.clock_skew_ms.finish()),
+            Arc::new(self.flow_direction.finish()),
+            Arc::new(self.is_bidirectional.finish()),
+            Arc::new(self.ingress_interface.finish()),
+            Arc::new(self.egress_interface.finish()),
+            Arc::new(self.ingress_interface_name.finish()),
+            Arc::new(self.egress_interface_name.finish()),
+            Arc::new(self.bytes.finish()),
+            Arc::new(self.packets.finish()),
+            Arc::new(self.bytes_in.finish()),
+            Arc::new(self.bytes_out.finish()),
+            Arc::new(self.packets_in.finish()),
+            Arc::new(self.packets_out.finish()),
+            Arc::new(self.sampling_interval.finish()),
+            Arc::new(self.dscp.finish()),
+            Arc::new(self.ethernet_type.finish()),
+            Arc::new(self.protocol.finish()),
+            Arc::new(self.src_mac.finish()),
+            Arc::new(self.dst_mac.finish()),
+            Arc::new(self.src_addr.finish()),
+            Arc::new(self.dst_addr.finish()),
+            Arc::new(self.src_net.finish()),
+            Arc::new(self.dst_net.finish()),
+            Arc::new(self.src_port.finish()),
+            Arc::new(self.dst_port.finish()),
+            Arc::new(self.icmp_type.finish()),
+            Arc::new(self.icmp_code.finish()),
+            Arc::new(self.vlan_id.finish()),
+            Arc::new(self.post_vlan_id.finish()),
+            Arc::new(self.post_nat_src_addr.finish()),
+            Arc::new(self.post_nat_dst_addr.finish()),
+            Arc::new(self.post_napt_src_port.finish()),
+            Arc::new(self.post_napt_dst_port.finish()),
+            Arc::new(self.next_hop_addr.finish()),
+            Arc::new(self.tunnel_type.finish()),
+            Arc::new(self.tunnel_id.finish()),
+            Arc::new(self.inner_src_addr.finish()),
+            Arc::new(self.inner_dst_addr.finish()),
+            Arc::new(self.inner_src_port.finish()),
+            Arc::new(self.inner_dst_port.finish()),
+            Arc::new(self.outer_src_addr.finish()),
+            Arc::new(self.outer_dst_addr.finish()),
+            Arc::new(self.outer_src_port.finish()),
+            Arc::new(self.outer_dst_port.finish()),
+            Arc::new(self.tcp_flags.finish()),
+            Arc::new(self.reverse_tcp_flags.finish()),
+            Arc::new(self.flow_end_reason.finish()),
+            Arc::new(self.flow_state.finish()),
+            Arc::new(self.flow_class.finish()),
+            Arc::new(self.service.finish()),
+            Arc::new(self.tenant.finish()),
+            Arc::new(self.src_net_name.finish()),
+            Arc::new(self.dst_net_name.finish()),
+            Arc::new(self.dns_query.finish()),
+            Arc::new(self.dns_qtype.finish()),
+            Arc::new(self.http_host.finish()),
+            Arc::new(self.http_url.finish()),
+            Arc::new(self.http_status.finish()),
+            Arc::new(self.tls_sni.finish()),
+            Arc::new(self.lossy_utf8.finish()),
+            Arc::new(self.suspect.finish()),
+        ];
+        self.len = 0;
+
+        RecordBatch::try_new(Arc::new(schema()), columns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fluss::{FlowClass, FlowEndReason, FlowState};
+    use crate::testing::flow;
+    use ::arrow::array::{
+        Array, AsArray, BooleanArray, Int64Array, StringArray, TimestampMillisecondArray,
+    };
+    use ::arrow::datatypes::{UInt16Type, UInt32Type, UInt64Type, UInt8Type};
+    use chrono::{TimeZone, Utc};
+    use serde_json::{json, Value};
+    use std::net::Ipv4Addr;
+
+    fn time(millis: i64) -> chrono::DateTime<Utc> {
+        Utc.timestamp_millis_opt(millis).unwrap()
+    }
+
+    /// A flow without any of the optional fields.
+    fn sparse() -> Fluss {
+        let mut fluss = flow(
+            Ipv4Addr::new(192, 0, 2, 1),
+            Ipv4Addr::new(198, 51, 100, 1),
+            443,
+            1500,
+            3,
+        );
+        fluss.time_received = time(1_600_000_000_123);
+        fluss
+    }
+
+    /// A flow with all optional fields set.
+    fn full() -> Fluss {
+        let mut fluss = sparse();
+        fluss.exporter = Some(Ipv4Addr::new(10, 0, 0, 1).into());
+        fluss.flow_start = Some(time(1_599_999_940_000));
+        fluss.flow_end = Some(time(1_600_000_000_000));
+        fluss.clock_skew_ms = Some(-250);
+        fluss.ingress_interface_name = Some("eth0".to_owned());
+        fluss.egress_interface_name = Some("eth1".to_owned());
+        fluss.sampling_interval = Some(1000);
+        fluss.src_mac = Some([0x02, 0, 0, 0, 0, 1].into());
+        fluss.dst_mac = Some([0x02, 0, 0, 0, 0, 2].into());
+        fluss.icmp_type = Some(8);
+        fluss.icmp_code = Some(0);
+        fluss.tunnel_type = Some("vxlan".to_owned());
+        fluss.tunnel_id = Some(4096);
+        fluss.inner_src_addr = Some(Ipv4Addr::new(172, 16, 0, 1).into());
+        fluss.inner_dst_addr = Some(Ipv4Addr::new(172, 16, 0, 2).into());
+        fluss.inner_src_port = Some(50000);
+        fluss.inner_dst_port = Some(80);
+        fluss.outer_src_addr = Some(Ipv4Addr::new(10, 1, 0, 1).into());
+        fluss.outer_dst_addr = Some(Ipv4Addr::new(10, 1, 0, 2).into());
+        fluss.outer_src_port = Some(49152);
+        fluss.outer_dst_port = Some(4789);
+        fluss.flow_end_reason = Some(FlowEndReason::EndOfFlow);
+        fluss.flow_state = Some(FlowState::FinClosed);
+        fluss.flow_class = Some(FlowClass::Elephant);
+        fluss.service = Some("https".to_owned());
+        fluss.tenant = Some("acme".to_owned());
+        fluss.src_net_name = Some("office".to_owned());
+        fluss.dst_net_name = Some("datacenter".to_owned());
+        fluss.dns_query = Some("example.com".to_owned());
+        fluss.dns_qtype = Some(28);
+        fluss.http_host = Some("example.com".to_owned());
+        fluss.http_url = Some("/index.html".to_owned());
+        fluss.http_status = Some(200);
+        fluss.tls_sni = Some("example.com".to_owned());
+        fluss
+    }
+
+    /// Returns the value of a cell in the serialized form of its flow field.
+    fn cell(column: &dyn Array, row: usize) -> Value {
+        if column.is_null(row) {
+            return Value::Null;
+        }
+
+        match column.data_type() {
+            DataType::Utf8 => json!(column
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .value(row)),
+            DataType::Boolean => json!(column
+                .as_any()
+                .downcast_ref::<BooleanArray>()
+                .unwrap()
+                .value(row)),
+            DataType::Int64 => json!(column
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .unwrap()
+                .value(row)),
+            DataType::UInt8 => json!(column.as_primitive::<UInt8Type>().value(row)),
+            DataType::UInt16 => json!(column.as_primitive::<UInt16Type>().value(row)),
+            DataType::UInt32 => json!(column.as_primitive::<UInt32Type>().value(row)),
+            DataType::UInt64 => json!(column.as_primitive::<UInt64Type>().value(row)),
+            DataType::Timestamp(TimeUnit::Millisecond, _) => {
+                let millis = column
+                    .as_any()
+                    .downcast_ref::<TimestampMillisecondArray>()
+                    .unwrap()
+                    .value(row);
+                json!(time(millis))
+            }
+            data_type => panic!("unexpected column type {}", data_type),
+        }
+    }
+
+    #[test]
+    fn flows_round_trip_through_record_batches() {
+        let flows = [full(), sparse()];
+        let mut builder = FlussArrowBuilder::new();
+        for flow in &flows {
+            builder.push(flow);
+        }
+        let batch = builder.finish().unwrap();
+
+        assert_eq!(batch.schema().as_ref(), &schema());
+        assert_eq!(batch.num_rows(), 2);
+        for (row, flow) in flows.iter().enumerate() {
+            let serialized = serde_json::to_value(flow).unwrap();
+            for (field, column) in schema().fields().iter().zip(batch.columns()) {
+                assert_eq!(
+                    cell(column, row),
+                    serialized[field.name()],
+                    "column {} of row {}",
+                    field.name(),
+                    row
+                );
+            }
+        }
+
+        // only the optional fields of the sparse flow are null
+        for (field, column) in schema().fields().iter().zip(batch.columns()) {
+            assert!(!column.is_null(0), "{} of the full flow", field.name());
+            assert_eq!(
+                column.is_null(1),
+                field.is_nullable(),
+                "{} of the sparse flow",
+                field.name()
+            );
+        }
+    }
+
+    #[test]
+    fn builder_is_reset_by_finish() {
+        let mut builder = FlussArrowBuilder::new();
+        builder.push(&sparse());
+        assert_eq!(builder.finish().unwrap().num_rows(), 1);
+
+        let batch = builder.finish().unwrap();
+        assert_eq!(batch.num_rows(), 0);
+        assert_eq!(batch.schema().as_ref(), &schema());
+    }
+}
@@ -0,0 +1,19 @@
+//! Current time of the decode path.
+//!
+//! `SystemTime::now` panics on `wasm32-unknown-unknown`, with the `wasm`
+//! feature the time is read from JavaScript instead.
+
+use std::time::SystemTime;
+
+/// Returns the current time of the system.
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub(crate) fn now() -> SystemTime {
+    SystemTime::now()
+}
+
+/// Returns the current time of the JavaScript runtime.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub(crate) fn now() -> SystemTime {
+    let millis = js_sys::Date::now();
+    std::time::UNIX_EPOCH + std::time::Duration::from_micros((millis * 1000.0) as u64)
+}
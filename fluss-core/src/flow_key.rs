@@ -0,0 +1,37 @@
+use crate::fluss::{Fluss, Protocol};
+use std::net::IpAddr;
+
+/// The 5-tuple identifying the connection of a flow.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FlowKey {
+    pub src_addr: IpAddr,
+    pub dst_addr: IpAddr,
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub protocol: Protocol,
+}
+
+impl FlowKey {
+    /// Returns the key of the opposite direction of the connection.
+    pub fn reversed(&self) -> Self {
+        Self {
+            src_addr: self.dst_addr,
+            dst_addr: self.src_addr,
+            src_port: self.dst_port,
+            dst_port: self.src_port,
+            protocol: self.protocol,
+        }
+    }
+}
+
+impl From<&Fluss> for FlowKey {
+    fn from(fluss: &Fluss) -> Self {
+        Self {
+            src_addr: fluss.src_addr,
+            dst_addr: fluss.dst_addr,
+            src_port: fluss.src_port,
+            dst_port: fluss.dst_port,
+            protocol: fluss.protocol,
+        }
+    }
+}
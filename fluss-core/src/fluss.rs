@@ -0,0 +1,1295 @@
+use crate::icmp;
+use crate::services::service_name;
+use chrono::{DateTime, SecondsFormat, Utc};
+use macaddr::MacAddr6;
+use serde::Serialize;
+use serde_with::{serde_as, DisplayFromStr, DurationMilliSeconds};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize)]
+pub enum FlowType {
+    IPFIX,
+}
+
+impl fmt::Display for FlowType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IPFIX => write!(f, "IPFIX"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Icmp,
+    Tcp,
+    Udp,
+    Gre,
+    Icmpv6,
+    Other(u8),
+}
+
+impl From<u8> for Protocol {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::Icmp,
+            6 => Self::Tcp,
+            17 => Self::Udp,
+            47 => Self::Gre,
+            58 => Self::Icmpv6,
+            other => Self::Other(other),
+        }
+    }
+}
+
+impl From<Protocol> for u8 {
+    fn from(value: Protocol) -> Self {
+        match value {
+            Protocol::Icmp => 1,
+            Protocol::Tcp => 6,
+            Protocol::Udp => 17,
+            Protocol::Gre => 47,
+            Protocol::Icmpv6 => 58,
+            Protocol::Other(other) => other,
+        }
+    }
+}
+
+impl Default for Protocol {
+    fn default() -> Self {
+        Self::Other(0)
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Icmp => write!(f, "icmp"),
+            Self::Tcp => write!(f, "tcp"),
+            Self::Udp => write!(f, "udp"),
+            Self::Gre => write!(f, "gre"),
+            Self::Icmpv6 => write!(f, "icmpv6"),
+            Self::Other(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlowDirection {
+    Ingress,
+    Egress,
+    #[default]
+    Unknown,
+}
+
+impl fmt::Display for FlowDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ingress => write!(f, "ingress"),
+            Self::Egress => write!(f, "egress"),
+            Self::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// TCP control bits as reported in `tcpControlBits` (RFC 7125).
+pub mod tcp_flags {
+    pub const FIN: u16 = 0x01;
+    pub const SYN: u16 = 0x02;
+    pub const RST: u16 = 0x04;
+    pub const PSH: u16 = 0x08;
+    pub const ACK: u16 = 0x10;
+    pub const URG: u16 = 0x20;
+    pub const ECE: u16 = 0x40;
+    pub const CWR: u16 = 0x80;
+    pub const NS: u16 = 0x100;
+}
+
+/// Reason why the exporter ended the flow, `flowEndReason` in IPFIX.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FlowEndReason {
+    IdleTimeout,
+    ActiveTimeout,
+    EndOfFlow,
+    ForcedEnd,
+    LackOfResources,
+    Unknown(u8),
+}
+
+impl From<u8> for FlowEndReason {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::IdleTimeout,
+            2 => Self::ActiveTimeout,
+            3 => Self::EndOfFlow,
+            4 => Self::ForcedEnd,
+            5 => Self::LackOfResources,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl fmt::Display for FlowEndReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IdleTimeout => write!(f, "idle-timeout"),
+            Self::ActiveTimeout => write!(f, "active-timeout"),
+            Self::EndOfFlow => write!(f, "end-of-flow"),
+            Self::ForcedEnd => write!(f, "forced-end"),
+            Self::LackOfResources => write!(f, "lack-of-resources"),
+            Self::Unknown(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Connection state of a TCP flow derived from its control bits.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FlowState {
+    /// Only connection attempts were seen, typical for scans.
+    SynOnly,
+    Established,
+    Reset,
+    FinClosed,
+}
+
+impl FlowState {
+    /// Classifies a flow, returns `None` for non TCP flows or flows without control bits.
+    pub fn classify(
+        protocol: Protocol,
+        tcp_flags: u16,
+        end_reason: Option<FlowEndReason>,
+    ) -> Option<Self> {
+        use self::tcp_flags::{ACK, FIN, RST, SYN};
+
+        if !matches!(protocol, Protocol::Tcp) {
+            return None;
+        }
+
+        if tcp_flags & RST != 0 {
+            Some(Self::Reset)
+        } else if tcp_flags & FIN != 0 {
+            Some(Self::FinClosed)
+        } else if tcp_flags & ACK != 0 {
+            // the exporter detected the end of the connection without reporting a FIN
+            match end_reason {
+                Some(FlowEndReason::EndOfFlow) => Some(Self::FinClosed),
+                _ => Some(Self::Established),
+            }
+        } else if tcp_flags & SYN != 0 {
+            Some(Self::SynOnly)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for FlowState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SynOnly => write!(f, "syn-only"),
+            Self::Established => write!(f, "established"),
+            Self::Reset => write!(f, "reset"),
+            Self::FinClosed => write!(f, "fin-closed"),
+        }
+    }
+}
+
+/// Size class of a flow, see [`FlowClass::classify`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FlowClass {
+    Mouse,
+    /// Below the byte threshold but active for at least the duration threshold.
+    LongLivedSmall,
+    Elephant,
+}
+
+impl FlowClass {
+    /// Classifies a flow by its size and age, returns `None` for empty flows.
+    ///
+    /// A flow of at least `elephant_bytes` is an elephant, regardless of its
+    /// age. Smaller flows active for at least `elephant_duration` are
+    /// long-lived-small, all other flows are mice.
+    pub fn classify(
+        bytes: u64,
+        packets: u64,
+        age: Duration,
+        elephant_bytes: u64,
+        elephant_duration: Duration,
+    ) -> Option<Self> {
+        // templates without byte counts report only packets
+        if bytes == 0 && packets == 0 {
+            return None;
+        }
+
+        if bytes >= elephant_bytes {
+            Some(Self::Elephant)
+        } else if age >= elephant_duration {
+            Some(Self::LongLivedSmall)
+        } else {
+            Some(Self::Mouse)
+        }
+    }
+}
+
+impl fmt::Display for FlowClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mouse => write!(f, "mouse"),
+            Self::LongLivedSmall => write!(f, "long-lived-small"),
+            Self::Elephant => write!(f, "elephant"),
+        }
+    }
+}
+
+/// A flow record decoded from an exporter.
+///
+/// Flows are equal if all their fields are equal. [`Hash`] only hashes the
+/// fields identifying the flow, the 5-tuple and the timestamps of the
+/// exporter, but not `time_received`.
+// TODO: make fields optional
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Fluss {
+    // TODO: receive metadata, actual timestamp at receive time not parse time, source addr
+    pub r#type: FlowType,
+    pub time_received: DateTime<Utc>,
+    /// Address of the exporter which sent the flow, filled in by the collector.
+    pub exporter: Option<IpAddr>,
+
+    #[serde_as(as = "DurationMilliSeconds")]
+    pub flow_age: Duration,
+    /// Absolute time of the first packet, if reported by the exporter.
+    pub flow_start: Option<DateTime<Utc>>,
+    /// Absolute time of the last packet, if reported by the exporter.
+    pub flow_end: Option<DateTime<Utc>>,
+    /// Milliseconds added to `flow_start` and `flow_end` to correct the clock
+    /// of the exporter, `None` if the timestamps were not corrected.
+    pub clock_skew_ms: Option<i64>,
+    pub flow_direction: FlowDirection,
+    /// Whether the flow contains both directions of the connection.
+    pub is_bidirectional: bool,
+
+    pub ingress_interface: u32,
+    pub egress_interface: u32,
+    /// Name of the ingress interface, filled in by the `InterfaceNames` enricher.
+    pub ingress_interface_name: Option<String>,
+    /// Name of the egress interface, filled in by the `InterfaceNames` enricher.
+    pub egress_interface_name: Option<String>,
+
+    pub bytes: u64,
+    pub packets: u64,
+
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub packets_in: u64,
+    pub packets_out: u64,
+
+    /// One in `sampling_interval` packets was sampled, the counters are not normalized.
+    pub sampling_interval: Option<u32>,
+
+    pub dscp: u8,
+
+    pub ethernet_type: u16,
+    #[serde_as(as = "DisplayFromStr")]
+    pub protocol: Protocol,
+
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub src_mac: Option<MacAddr6>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub dst_mac: Option<MacAddr6>,
+
+    pub src_addr: IpAddr,
+    pub dst_addr: IpAddr,
+
+    pub src_net: u8,
+    pub dst_net: u8,
+
+    pub src_port: u16,
+    pub dst_port: u16,
+
+    pub icmp_type: Option<u8>,
+    pub icmp_code: Option<u8>,
+
+    pub vlan_id: u16,
+    pub post_vlan_id: u16,
+
+    pub post_nat_src_addr: IpAddr,
+    pub post_nat_dst_addr: IpAddr,
+
+    pub post_napt_src_port: u16,
+    pub post_napt_dst_port: u16,
+
+    pub next_hop_addr: IpAddr,
+
+    /// Encapsulation of a tunneled flow, e.g. `vxlan` or `gre`.
+    pub tunnel_type: Option<String>,
+    /// Identifier of the tunnel, e.g. the VXLAN network identifier or the GRE key.
+    pub tunnel_id: Option<u64>,
+    pub inner_src_addr: Option<IpAddr>,
+    pub inner_dst_addr: Option<IpAddr>,
+    pub inner_src_port: Option<u16>,
+    pub inner_dst_port: Option<u16>,
+    /// Outer header of a tunneled flow, only set if the inner header was moved
+    /// into the primary addresses, see [`Fluss::prefer_inner`].
+    pub outer_src_addr: Option<IpAddr>,
+    pub outer_dst_addr: Option<IpAddr>,
+    pub outer_src_port: Option<u16>,
+    pub outer_dst_port: Option<u16>,
+
+    /// Union of all TCP control bits seen in the flow, only of the forward
+    /// direction for bidirectional flows.
+    pub tcp_flags: u16,
+    /// Union of all TCP control bits seen in the reverse direction of a bidirectional flow.
+    pub reverse_tcp_flags: u16,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub flow_end_reason: Option<FlowEndReason>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub flow_state: Option<FlowState>,
+    /// Size class of the flow, filled in by the `FlowClassifier`.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub flow_class: Option<FlowClass>,
+
+    /// Name of the service, e.g. `https`, filled in by the `ServiceEnricher`.
+    pub service: Option<String>,
+
+    /// Site or tenant of the exporter, from the settings of the exporter.
+    pub tenant: Option<String>,
+
+    /// Names of the networks containing the addresses, filled in by the `PrefixEnricher`.
+    pub src_net_name: Option<String>,
+    pub dst_net_name: Option<String>,
+
+    /// Application layer metadata of exporters inspecting the payload, e.g. nProbe.
+    ///
+    /// Strings are truncated to the limit of the parser, see
+    /// `IpfixParser::with_max_metadata_length`.
+    pub dns_query: Option<String>,
+    /// Type of the DNS query, e.g. 1 for `A` or 28 for `AAAA`.
+    pub dns_qtype: Option<u16>,
+    pub http_host: Option<String>,
+    /// Target of the HTTP request, usually the path and query.
+    pub http_url: Option<String>,
+    pub http_status: Option<u16>,
+    /// Server name indication of the TLS handshake.
+    pub tls_sni: Option<String>,
+    /// Invalid UTF-8 of the metadata strings was replaced by `U+FFFD`.
+    pub lossy_utf8: bool,
+
+    /// The flow holds implausible values, e.g. because the template of the
+    /// exporter changed while the flow was in flight, see `SanityChecks`.
+    pub suspect: bool,
+
+    /// Static labels of the exporter, e.g. the site of the router.
+    #[serde(flatten)]
+    pub labels: BTreeMap<String, String>,
+
+    /// Additional fields configured through custom field mappings.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+impl Hash for Fluss {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // a subset of the fields compared by `PartialEq`, equal flows hash the same
+        self.exporter.hash(state);
+        self.src_addr.hash(state);
+        self.dst_addr.hash(state);
+        self.src_port.hash(state);
+        self.dst_port.hash(state);
+        self.protocol.hash(state);
+        self.flow_start.hash(state);
+        self.flow_end.hash(state);
+        self.flow_age.hash(state);
+    }
+}
+
+impl Fluss {
+    /// Names of all serialized fields, custom fields must not use these names.
+    pub const FIELDS: &'static [&'static str] = &[
+        "type",
+        "time_received",
+        "exporter",
+        "flow_age",
+        "flow_start",
+        "flow_end",
+        "clock_skew_ms",
+        "flow_direction",
+        "is_bidirectional",
+        "ingress_interface",
+        "egress_interface",
+        "ingress_interface_name",
+        "egress_interface_name",
+        "bytes",
+        "packets",
+        "bytes_in",
+        "bytes_out",
+        "packets_in",
+        "packets_out",
+        "sampling_interval",
+        "dscp",
+        "ethernet_type",
+        "protocol",
+        "src_mac",
+        "dst_mac",
+        "src_addr",
+        "dst_addr",
+        "src_net",
+        "dst_net",
+        "src_port",
+        "dst_port",
+        "icmp_type",
+        "icmp_code",
+        "vlan_id",
+        "post_vlan_id",
+        "post_nat_src_addr",
+        "post_nat_dst_addr",
+        "post_napt_src_port",
+        "post_napt_dst_port",
+        "next_hop_addr",
+        "tunnel_type",
+        "tunnel_id",
+        "inner_src_addr",
+        "inner_dst_addr",
+        "inner_src_port",
+        "inner_dst_port",
+        "outer_src_addr",
+        "outer_dst_addr",
+        "outer_src_port",
+        "outer_dst_port",
+        "tcp_flags",
+        "reverse_tcp_flags",
+        "flow_end_reason",
+        "flow_state",
+        "flow_class",
+        "service",
+        "tenant",
+        "src_net_name",
+        "dst_net_name",
+        "dns_query",
+        "dns_qtype",
+        "http_host",
+        "http_url",
+        "http_status",
+        "tls_sni",
+        "lossy_utf8",
+        "suspect",
+    ];
+
+    /// Returns the bytes scaled up by the sampling interval.
+    pub fn normalized_bytes(&self) -> u64 {
+        self.bytes.saturating_mul(self.sampling_factor())
+    }
+
+    /// Returns the packets scaled up by the sampling interval.
+    pub fn normalized_packets(&self) -> u64 {
+        self.packets.saturating_mul(self.sampling_factor())
+    }
+
+    fn sampling_factor(&self) -> u64 {
+        self.sampling_interval.unwrap_or(1).max(1) as u64
+    }
+
+    /// Returns the registered service name of the source port.
+    pub fn src_service(&self) -> Option<&'static str> {
+        service_name(self.src_port, self.protocol)
+    }
+
+    /// Returns the registered service name of the destination port.
+    pub fn dst_service(&self) -> Option<&'static str> {
+        service_name(self.dst_port, self.protocol)
+    }
+
+    /// Moves the inner header of a tunneled flow into the primary addresses and
+    /// ports, the outer header is kept in the `outer_*` fields.
+    ///
+    /// Returns `false` and leaves the flow unchanged if the inner addresses are unknown.
+    pub fn prefer_inner(&mut self) -> bool {
+        let (inner_src_addr, inner_dst_addr) = match (self.inner_src_addr, self.inner_dst_addr) {
+            (Some(src_addr), Some(dst_addr)) => (src_addr, dst_addr),
+            _ => return false,
+        };
+
+        // the ports of the outer header, e.g. of VXLAN, do not belong to the inner flow
+        self.outer_src_addr = Some(std::mem::replace(&mut self.src_addr, inner_src_addr));
+        self.outer_dst_addr = Some(std::mem::replace(&mut self.dst_addr, inner_dst_addr));
+        self.outer_src_port = Some(std::mem::replace(
+            &mut self.src_port,
+            self.inner_src_port.unwrap_or_default(),
+        ));
+        self.outer_dst_port = Some(std::mem::replace(
+            &mut self.dst_port,
+            self.inner_dst_port.unwrap_or_default(),
+        ));
+
+        true
+    }
+
+    /// Returns a one-line summary of the flow, with `verbose` additional
+    /// layer 2 information is included.
+    pub fn display(&self, verbose: bool) -> FlussDisplay<'_> {
+        FlussDisplay {
+            fluss: self,
+            verbose,
+            service_names: false,
+            normalize_sampling: false,
+        }
+    }
+
+    /// Returns a compact line of the [`CompactField::DEFAULT`] fields with
+    /// human-readable units, see [`DisplayCompact`].
+    pub fn display_compact(&self) -> DisplayCompact<'_> {
+        DisplayCompact {
+            fluss: self,
+            fields: CompactField::DEFAULT,
+            color: false,
+            normalize_sampling: false,
+        }
+    }
+}
+
+impl fmt::Display for Fluss {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display(false).fmt(f)
+    }
+}
+
+pub struct FlussDisplay<'a> {
+    fluss: &'a Fluss,
+    pub verbose: bool,
+    /// Appends the service names to the ports, e.g. `443 (https)`.
+    pub service_names: bool,
+    /// Shows the counters scaled up by the sampling interval.
+    pub normalize_sampling: bool,
+}
+
+impl<'a> FlussDisplay<'a> {
+    pub fn service_names(mut self, service_names: bool) -> Self {
+        self.service_names = service_names;
+        self
+    }
+
+    pub fn normalize_sampling(mut self, normalize_sampling: bool) -> Self {
+        self.normalize_sampling = normalize_sampling;
+        self
+    }
+
+    fn endpoint(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        addr: IpAddr,
+        port: u16,
+        service: Option<&str>,
+    ) -> fmt::Result {
+        write!(f, "{}", SocketAddr::new(addr, port))?;
+        match service {
+            Some(service) if self.service_names => write!(f, " ({})", service),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<'a> fmt::Display for FlussDisplay<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fluss = self.fluss;
+
+        write!(
+            f,
+            "{} {} {} ",
+            fluss
+                .time_received
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            fluss.r#type,
+            fluss.protocol,
+        )?;
+        self.endpoint(f, fluss.src_addr, fluss.src_port, fluss.src_service())?;
+        match fluss.is_bidirectional {
+            true => write!(f, " \u{2194} ")?,
+            false => write!(f, " \u{2192} ")?,
+        }
+        self.endpoint(f, fluss.dst_addr, fluss.dst_port, fluss.dst_service())?;
+        let (bytes, packets) = match self.normalize_sampling {
+            true => (fluss.normalized_bytes(), fluss.normalized_packets()),
+            false => (fluss.bytes, fluss.packets),
+        };
+        write!(
+            f,
+            " {}B/{}pkts {}ms",
+            bytes,
+            packets,
+            fluss.flow_age.as_millis(),
+        )?;
+
+        if let Some(sampling_interval) = fluss.sampling_interval {
+            write!(f, " sampling=1:{}", sampling_interval)?;
+        }
+
+        if let (Some(icmp_type), Some(icmp_code)) = (fluss.icmp_type, fluss.icmp_code) {
+            write!(
+                f,
+                " icmp={}/{} ({})",
+                icmp_type,
+                icmp_code,
+                icmp::description(icmp_type, icmp_code)
+            )?;
+        }
+
+        if let Some(flow_state) = fluss.flow_state {
+            write!(f, " state={}", flow_state)?;
+        }
+
+        if let Some(flow_class) = fluss.flow_class {
+            write!(f, " class={}", flow_class)?;
+        }
+
+        if let Some(flow_end_reason) = fluss.flow_end_reason {
+            write!(f, " end={}", flow_end_reason)?;
+        }
+
+        if let Some(service) = &fluss.service {
+            write!(f, " service={}", service)?;
+        }
+
+        if let Some(tenant) = &fluss.tenant {
+            write!(f, " tenant={}", tenant)?;
+        }
+
+        if let Some(src_net_name) = &fluss.src_net_name {
+            write!(f, " src_net={}", src_net_name)?;
+        }
+
+        if let Some(dst_net_name) = &fluss.dst_net_name {
+            write!(f, " dst_net={}", dst_net_name)?;
+        }
+
+        if let Some(dns_query) = &fluss.dns_query {
+            write!(f, " dns={:?}", dns_query)?;
+        }
+
+        if let Some(dns_qtype) = fluss.dns_qtype {
+            write!(f, " qtype={}", dns_qtype)?;
+        }
+
+        if let Some(http_host) = &fluss.http_host {
+            write!(f, " host={:?}", http_host)?;
+        }
+
+        if let Some(http_url) = &fluss.http_url {
+            write!(f, " url={:?}", http_url)?;
+        }
+
+        if let Some(http_status) = fluss.http_status {
+            write!(f, " status={}", http_status)?;
+        }
+
+        if let Some(tls_sni) = &fluss.tls_sni {
+            write!(f, " sni={:?}", tls_sni)?;
+        }
+
+        if fluss.suspect {
+            write!(f, " suspect")?;
+        }
+
+        if let Some(tunnel_type) = &fluss.tunnel_type {
+            write!(f, " tunnel={}", tunnel_type)?;
+            if let Some(tunnel_id) = fluss.tunnel_id {
+                write!(f, "/{}", tunnel_id)?;
+            }
+        }
+
+        if let Some(clock_skew_ms) = fluss.clock_skew_ms {
+            write!(f, " clock_skew={}ms", clock_skew_ms)?;
+        }
+
+        for (name, value) in &fluss.labels {
+            write!(f, " {}={}", name, value)?;
+        }
+
+        for (name, value) in &fluss.extra {
+            write!(f, " {}={}", name, value)?;
+        }
+
+        if self.verbose {
+            if let Some(src_mac) = fluss.src_mac {
+                write!(f, " src_mac={}", src_mac)?;
+            }
+            if let Some(dst_mac) = fluss.dst_mac {
+                write!(f, " dst_mac={}", dst_mac)?;
+            }
+            if let Some(flow_start) = fluss.flow_start {
+                write!(
+                    f,
+                    " flow_start={}",
+                    flow_start.to_rfc3339_opts(SecondsFormat::Millis, true)
+                )?;
+            }
+            if let Some(flow_end) = fluss.flow_end {
+                write!(
+                    f,
+                    " flow_end={}",
+                    flow_end.to_rfc3339_opts(SecondsFormat::Millis, true)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A column of the [`DisplayCompact`] line.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CompactField {
+    /// Time the flow was received.
+    Time,
+    /// Type of the flow, e.g. `IPFIX`.
+    Type,
+    /// Source and destination address and port.
+    Addrs,
+    Protocol,
+    Packets,
+    Bytes,
+    /// Age of the flow.
+    Duration,
+    Vlan,
+    Exporter,
+    Service,
+    Class,
+    Tenant,
+    Sampling,
+    /// Direction of the flow relative to the observation point.
+    Direction,
+}
+
+impl CompactField {
+    /// Fields shown unless others are selected.
+    pub const DEFAULT: &'static [Self] = &[
+        Self::Time,
+        Self::Type,
+        Self::Addrs,
+        Self::Protocol,
+        Self::Packets,
+        Self::Bytes,
+        Self::Duration,
+        Self::Vlan,
+        Self::Exporter,
+    ];
+
+    /// Parses a comma separated list of field names, e.g. `time,addrs,bytes`.
+    pub fn parse_list(s: &str) -> anyhow::Result<Vec<Self>> {
+        let fields = s
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::parse)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if fields.is_empty() {
+            anyhow::bail!("no console fields selected");
+        }
+        Ok(fields)
+    }
+
+    /// Name of the field as accepted by [`CompactField::parse_list`].
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Time => "time",
+            Self::Type => "type",
+            Self::Addrs => "addrs",
+            Self::Protocol => "proto",
+            Self::Packets => "packets",
+            Self::Bytes => "bytes",
+            Self::Duration => "duration",
+            Self::Vlan => "vlan",
+            Self::Exporter => "exporter",
+            Self::Service => "service",
+            Self::Class => "class",
+            Self::Tenant => "tenant",
+            Self::Sampling => "sampling",
+            Self::Direction => "direction",
+        }
+    }
+}
+
+impl std::str::FromStr for CompactField {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "time" => Self::Time,
+            "type" => Self::Type,
+            "addrs" => Self::Addrs,
+            "proto" | "protocol" => Self::Protocol,
+            "packets" => Self::Packets,
+            "bytes" => Self::Bytes,
+            "duration" => Self::Duration,
+            "vlan" => Self::Vlan,
+            "exporter" => Self::Exporter,
+            "service" => Self::Service,
+            "class" => Self::Class,
+            "tenant" => Self::Tenant,
+            "sampling" => Self::Sampling,
+            "direction" => Self::Direction,
+            _ => anyhow::bail!("unknown console field {}", s),
+        })
+    }
+}
+
+/// Compact line of selected fields of a flow, e.g.
+/// `2024-05-01T12:03:44Z IPFIX 10.0.0.5:53421 -> 142.250.1.2:443 tcp 14 pkts 9.1KB 3.2s vlan 120 [exporter 192.0.2.1]`.
+///
+/// Fields without a value are left out, bidirectional flows are shown with `<->`.
+pub struct DisplayCompact<'a> {
+    fluss: &'a Fluss,
+    fields: &'a [CompactField],
+    color: bool,
+    normalize_sampling: bool,
+}
+
+impl<'a> DisplayCompact<'a> {
+    pub fn fields(mut self, fields: &'a [CompactField]) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    /// Highlights the protocol with ANSI colors.
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Shows the counters scaled up by the sampling interval.
+    pub fn normalize_sampling(mut self, normalize_sampling: bool) -> Self {
+        self.normalize_sampling = normalize_sampling;
+        self
+    }
+
+    /// Whether the flow has a value for `field`, optional fields are left out.
+    fn has_value(&self, field: CompactField) -> bool {
+        let fluss = self.fluss;
+        match field {
+            CompactField::Vlan => fluss.vlan_id != 0,
+            CompactField::Exporter => fluss.exporter.is_some(),
+            CompactField::Service => fluss.service.is_some(),
+            CompactField::Class => fluss.flow_class.is_some(),
+            CompactField::Tenant => fluss.tenant.is_some(),
+            CompactField::Sampling => fluss.sampling_interval.is_some(),
+            CompactField::Direction => !matches!(fluss.flow_direction, FlowDirection::Unknown),
+            _ => true,
+        }
+    }
+
+    fn write_field(&self, f: &mut fmt::Formatter<'_>, field: CompactField) -> fmt::Result {
+        let fluss = self.fluss;
+        let (bytes, packets) = match self.normalize_sampling {
+            true => (fluss.normalized_bytes(), fluss.normalized_packets()),
+            false => (fluss.bytes, fluss.packets),
+        };
+
+        match field {
+            CompactField::Time => write!(
+                f,
+                "{}",
+                fluss
+                    .time_received
+                    .to_rfc3339_opts(SecondsFormat::Secs, true)
+            ),
+            CompactField::Type => write!(f, "{}", fluss.r#type),
+            CompactField::Addrs => write!(
+                f,
+                "{} {} {}",
+                SocketAddr::new(fluss.src_addr, fluss.src_port),
+                if fluss.is_bidirectional { "<->" } else { "->" },
+                SocketAddr::new(fluss.dst_addr, fluss.dst_port)
+            ),
+            CompactField::Protocol => match (self.color, protocol_color(fluss.protocol)) {
+                (true, Some(color)) => write!(f, "\x1b[{}m{}\x1b[0m", color, fluss.protocol),
+                _ => write!(f, "{}", fluss.protocol),
+            },
+            CompactField::Packets => write!(f, "{} pkts", packets),
+            CompactField::Bytes => write!(f, "{}", HumanBytes(bytes)),
+            CompactField::Duration => write!(f, "{}", HumanDuration(fluss.flow_age)),
+            CompactField::Vlan => write!(f, "vlan {}", fluss.vlan_id),
+            CompactField::Exporter => match fluss.exporter {
+                Some(exporter) => write!(f, "[exporter {}]", exporter),
+                None => Ok(()),
+            },
+            CompactField::Service => match &fluss.service {
+                Some(service) => write!(f, "({})", service),
+                None => Ok(()),
+            },
+            CompactField::Class => match fluss.flow_class {
+                Some(class) => write!(f, "{}", class),
+                None => Ok(()),
+            },
+            CompactField::Tenant => match &fluss.tenant {
+                Some(tenant) => write!(f, "tenant {}", tenant),
+                None => Ok(()),
+            },
+            CompactField::Sampling => match fluss.sampling_interval {
+                Some(interval) => write!(f, "1:{}", interval),
+                None => Ok(()),
+            },
+            CompactField::Direction => write!(f, "{}", fluss.flow_direction),
+        }
+    }
+}
+
+impl<'a> fmt::Display for DisplayCompact<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields = self.fields.iter().filter(|&&field| self.has_value(field));
+        for (i, &field) in fields.enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            self.write_field(f, field)?;
+        }
+        Ok(())
+    }
+}
+
+/// ANSI color code of the protocol, common protocols are highlighted.
+fn protocol_color(protocol: Protocol) -> Option<u8> {
+    match protocol {
+        Protocol::Tcp => Some(36),
+        Protocol::Udp => Some(33),
+        Protocol::Icmp | Protocol::Icmpv6 => Some(35),
+        _ => None,
+    }
+}
+
+/// An amount of bytes with a decimal unit, e.g. `999B`, `1.0KB` or `9.1MB`.
+pub struct HumanBytes(pub u64);
+
+impl fmt::Display for HumanBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: &[&str] = &["KB", "MB", "GB", "TB", "PB", "EB"];
+
+        if self.0 < 1000 {
+            return write!(f, "{}B", self.0);
+        }
+
+        let mut value = self.0 as f64;
+        let mut unit = 0;
+        value /= 1000.0;
+        // rounding to one decimal must not show 1000.0 of a unit
+        while value >= 999.95 && unit + 1 < UNITS.len() {
+            value /= 1000.0;
+            unit += 1;
+        }
+        write!(f, "{:.1}{}", value, UNITS[unit])
+    }
+}
+
+/// A duration with the largest fitting unit, e.g. `850ms`, `3.2s`, `4.5m` or `2.0h`.
+pub struct HumanDuration(pub Duration);
+
+impl fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.0.as_secs_f64();
+        match self.0.as_millis() {
+            millis if millis < 1000 => write!(f, "{}ms", millis),
+            _ if secs < 59.95 => write!(f, "{:.1}s", secs),
+            _ if secs < 59.95 * 60.0 => write!(f, "{:.1}m", secs / 60.0),
+            _ => write!(f, "{:.1}h", secs / 3600.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tcp_flags::*;
+    use super::*;
+
+    const END_REASONS: [Option<FlowEndReason>; 8] = [
+        None,
+        Some(FlowEndReason::IdleTimeout),
+        Some(FlowEndReason::ActiveTimeout),
+        Some(FlowEndReason::EndOfFlow),
+        Some(FlowEndReason::ForcedEnd),
+        Some(FlowEndReason::LackOfResources),
+        Some(FlowEndReason::Unknown(0)),
+        Some(FlowEndReason::Unknown(255)),
+    ];
+
+    /// All combinations of the 9 TCP control bits.
+    fn all_flags() -> impl Iterator<Item = u16> {
+        0..0x200
+    }
+
+    #[test]
+    fn flow_state_cases() {
+        use FlowState::*;
+
+        let cases = [
+            (SYN, None, Some(SynOnly)),
+            (SYN | ECE | CWR, None, Some(SynOnly)),
+            (SYN | ACK, None, Some(Established)),
+            (
+                SYN | ACK | PSH,
+                Some(FlowEndReason::ActiveTimeout),
+                Some(Established),
+            ),
+            (
+                ACK | PSH,
+                Some(FlowEndReason::IdleTimeout),
+                Some(Established),
+            ),
+            (ACK | PSH, Some(FlowEndReason::EndOfFlow), Some(FinClosed)),
+            (SYN | ACK | FIN, None, Some(FinClosed)),
+            (FIN, Some(FlowEndReason::ForcedEnd), Some(FinClosed)),
+            (SYN | RST, None, Some(Reset)),
+            (
+                SYN | ACK | FIN | RST,
+                Some(FlowEndReason::EndOfFlow),
+                Some(Reset),
+            ),
+            (RST, None, Some(Reset)),
+            (PSH | URG, None, None),
+            (0, Some(FlowEndReason::EndOfFlow), None),
+        ];
+
+        for &(flags, end_reason, expected) in &cases {
+            assert_eq!(
+                FlowState::classify(Protocol::Tcp, flags, end_reason),
+                expected,
+                "flags {:#x}, end reason {:?}",
+                flags,
+                end_reason
+            );
+        }
+    }
+
+    #[test]
+    fn flow_state_only_for_tcp() {
+        for protocol in [
+            Protocol::Icmp,
+            Protocol::Udp,
+            Protocol::Gre,
+            Protocol::Other(132),
+        ] {
+            for flags in all_flags() {
+                for &end_reason in &END_REASONS {
+                    assert_eq!(FlowState::classify(protocol, flags, end_reason), None);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn flow_state_exhaustive() {
+        for flags in all_flags() {
+            for &end_reason in &END_REASONS {
+                let state = FlowState::classify(Protocol::Tcp, flags, end_reason);
+                // flags other than SYN, ACK, FIN and RST do not matter
+                let significant = flags & (SYN | ACK | FIN | RST);
+                assert_eq!(
+                    state,
+                    FlowState::classify(Protocol::Tcp, significant, end_reason),
+                    "flags {:#x}",
+                    flags
+                );
+
+                let expected = if flags & RST != 0 {
+                    Some(FlowState::Reset)
+                } else if flags & FIN != 0 {
+                    Some(FlowState::FinClosed)
+                } else if flags & ACK != 0 {
+                    if end_reason == Some(FlowEndReason::EndOfFlow) {
+                        Some(FlowState::FinClosed)
+                    } else {
+                        Some(FlowState::Established)
+                    }
+                } else if flags & SYN != 0 {
+                    Some(FlowState::SynOnly)
+                } else {
+                    None
+                };
+                assert_eq!(
+                    state, expected,
+                    "flags {:#x}, end reason {:?}",
+                    flags, end_reason
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn flow_end_reason_from_u8() {
+        let names: Vec<_> = (0..=6)
+            .map(|value| FlowEndReason::from(value).to_string())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "0",
+                "idle-timeout",
+                "active-timeout",
+                "end-of-flow",
+                "forced-end",
+                "lack-of-resources",
+                "6"
+            ]
+        );
+        assert_eq!(FlowEndReason::from(255), FlowEndReason::Unknown(255));
+    }
+
+    #[test]
+    fn flow_state_names() {
+        let names: Vec<_> = [
+            FlowState::SynOnly,
+            FlowState::Established,
+            FlowState::Reset,
+            FlowState::FinClosed,
+        ]
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+        assert_eq!(names, ["syn-only", "established", "reset", "fin-closed"]);
+    }
+
+    #[test]
+    fn flow_class_thresholds() {
+        use FlowClass::*;
+
+        let minute = Duration::from_secs(60);
+        let classify = |bytes, packets, age| FlowClass::classify(bytes, packets, age, 1000, minute);
+        let second = Duration::from_secs(1);
+
+        assert_eq!(classify(0, 0, Duration::ZERO), None);
+        assert_eq!(classify(0, 0, minute), None);
+        // templates without byte counts
+        assert_eq!(classify(0, 10, second), Some(Mouse));
+        assert_eq!(classify(0, 10, minute), Some(LongLivedSmall));
+
+        assert_eq!(classify(999, 1, second), Some(Mouse));
+        assert_eq!(classify(1000, 1, second), Some(Elephant));
+        assert_eq!(classify(u64::MAX, 1, Duration::ZERO), Some(Elephant));
+        assert_eq!(
+            classify(999, 1, minute - Duration::from_millis(1)),
+            Some(Mouse)
+        );
+        assert_eq!(classify(999, 1, minute), Some(LongLivedSmall));
+        // elephants regardless of their age
+        assert_eq!(classify(1000, 1, minute * 60), Some(Elephant));
+    }
+
+    #[test]
+    fn flow_class_names() {
+        let names: Vec<_> = [
+            FlowClass::Mouse,
+            FlowClass::LongLivedSmall,
+            FlowClass::Elephant,
+        ]
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+        assert_eq!(names, ["mouse", "long-lived-small", "elephant"]);
+    }
+
+    #[test]
+    fn human_bytes() {
+        let cases = [
+            (0, "0B"),
+            (999, "999B"),
+            (1000, "1.0KB"),
+            (1049, "1.0KB"),
+            (1050, "1.1KB"),
+            (9_100, "9.1KB"),
+            (999_900, "999.9KB"),
+            // rounds up to the next unit instead of 1000.0KB
+            (999_950, "1.0MB"),
+            (999_999, "1.0MB"),
+            (1_000_000, "1.0MB"),
+            (1_000_000_000, "1.0GB"),
+            (1_500_000_000_000, "1.5TB"),
+            (u64::MAX, "18.4EB"),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(HumanBytes(bytes).to_string(), expected, "{} bytes", bytes);
+        }
+    }
+
+    #[test]
+    fn human_durations() {
+        let cases = [
+            (Duration::ZERO, "0ms"),
+            (Duration::from_millis(850), "850ms"),
+            (Duration::from_millis(999), "999ms"),
+            (Duration::from_millis(1000), "1.0s"),
+            (Duration::from_millis(3200), "3.2s"),
+            (Duration::from_millis(59_900), "59.9s"),
+            (Duration::from_millis(59_950), "1.0m"),
+            (Duration::from_secs(270), "4.5m"),
+            (Duration::from_secs(3597), "1.0h"),
+            (Duration::from_secs(7200), "2.0h"),
+        ];
+        for (duration, expected) in cases {
+            assert_eq!(
+                HumanDuration(duration).to_string(),
+                expected,
+                "{:?}",
+                duration
+            );
+        }
+    }
+
+    #[test]
+    fn compact_field_lists() {
+        use CompactField::*;
+
+        let fields = CompactField::parse_list("time, addrs,protocol,,bytes").unwrap();
+        assert_eq!(fields, [Time, Addrs, Protocol, Bytes]);
+        assert_eq!(CompactField::parse_list("tenant").unwrap(), [Tenant]);
+
+        let err = CompactField::parse_list("time,port").unwrap_err();
+        assert_eq!(err.to_string(), "unknown console field port");
+        assert!(CompactField::parse_list("").is_err());
+        assert!(CompactField::parse_list(" , ").is_err());
+
+        // names round trip
+        let all = [
+            Time, Type, Addrs, Protocol, Packets, Bytes, Duration, Vlan, Exporter, Service, Class,
+            Tenant, Sampling, Direction,
+        ];
+        let names: Vec<_> = all.iter().map(|field| field.name()).collect();
+        assert_eq!(CompactField::parse_list(&names.join(",")).unwrap(), all);
+    }
+
+    #[test]
+    fn compact_lines() {
+        let mut fluss = crate::testing::flow(
+            [10, 0, 0, 5].into(),
+            [142, 250, 1, 2].into(),
+            443,
+            9_100,
+            14,
+        );
+        fluss.time_received = "2024-05-01T12:03:44Z".parse().unwrap();
+        fluss.src_port = 53421;
+        fluss.flow_age = Duration::from_millis(3200);
+        fluss.vlan_id = 120;
+        fluss.exporter = Some([192, 0, 2, 1].into());
+        assert_eq!(
+            fluss.display_compact().to_string(),
+            "2024-05-01T12:03:44Z IPFIX 10.0.0.5:53421 -> 142.250.1.2:443 tcp 14 pkts 9.1KB 3.2s vlan 120 [exporter 192.0.2.1]"
+        );
+
+        // fields without a value are left out
+        fluss.vlan_id = 0;
+        fluss.exporter = None;
+        let fields = [
+            CompactField::Addrs,
+            CompactField::Vlan,
+            CompactField::Protocol,
+            CompactField::Tenant,
+        ];
+        let line = fluss.display_compact().fields(&fields);
+        assert_eq!(line.to_string(), "10.0.0.5:53421 -> 142.250.1.2:443 tcp");
+
+        let line = fluss.display_compact().fields(&fields[2..3]).color(true);
+        assert_eq!(line.to_string(), "\x1b[36mtcp\x1b[0m");
+    }
+}
@@ -0,0 +1,42 @@
+/// Returns a description of an ICMP (IPv4) message type and code.
+pub fn description(icmp_type: u8, code: u8) -> &'static str {
+    match (icmp_type, code) {
+        (0, _) => "Echo Reply",
+        (3, 0) => "Destination Unreachable / Net Unreachable",
+        (3, 1) => "Destination Unreachable / Host Unreachable",
+        (3, 2) => "Destination Unreachable / Protocol Unreachable",
+        (3, 3) => "Destination Unreachable / Port Unreachable",
+        (3, 4) => "Destination Unreachable / Fragmentation Needed",
+        (3, 5) => "Destination Unreachable / Source Route Failed",
+        (3, 6) => "Destination Unreachable / Destination Network Unknown",
+        (3, 7) => "Destination Unreachable / Destination Host Unknown",
+        (3, 8) => "Destination Unreachable / Source Host Isolated",
+        (3, 9) => "Destination Unreachable / Network Administratively Prohibited",
+        (3, 10) => "Destination Unreachable / Host Administratively Prohibited",
+        (3, 11) => "Destination Unreachable / Network Unreachable for Type of Service",
+        (3, 12) => "Destination Unreachable / Host Unreachable for Type of Service",
+        (3, 13) => "Destination Unreachable / Communication Administratively Prohibited",
+        (3, 14) => "Destination Unreachable / Host Precedence Violation",
+        (3, 15) => "Destination Unreachable / Precedence Cutoff in Effect",
+        (3, _) => "Destination Unreachable",
+        (4, _) => "Source Quench",
+        (5, 0) => "Redirect / Network",
+        (5, 1) => "Redirect / Host",
+        (5, 2) => "Redirect / Type of Service and Network",
+        (5, 3) => "Redirect / Type of Service and Host",
+        (5, _) => "Redirect",
+        (8, _) => "Echo Request",
+        (9, _) => "Router Advertisement",
+        (10, _) => "Router Solicitation",
+        (11, 0) => "Time Exceeded / Time to Live Exceeded in Transit",
+        (11, 1) => "Time Exceeded / Fragment Reassembly Time Exceeded",
+        (11, _) => "Time Exceeded",
+        (12, 0) => "Parameter Problem / Pointer Indicates the Error",
+        (12, 1) => "Parameter Problem / Missing a Required Option",
+        (12, 2) => "Parameter Problem / Bad Length",
+        (12, _) => "Parameter Problem",
+        (13, _) => "Timestamp",
+        (14, _) => "Timestamp Reply",
+        _ => "Unknown",
+    }
+}
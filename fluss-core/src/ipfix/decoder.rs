@@ -0,0 +1,369 @@
+//! Synchronous decoding of received datagrams, for applications which own their sockets.
+//!
+//! ```no_run
+//! use fluss_core::ipfix::{Decoder, PacketContext};
+//!
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let decoder = Decoder::new();
+//!     let socket = std::net::UdpSocket::bind("0.0.0.0:4739")?;
+//!     let mut buf = [0; 65535];
+//!     loop {
+//!         let (len, exporter) = socket.recv_from(&mut buf)?;
+//!         for fluss in decoder.decode(&buf[..len], PacketContext::new(exporter))? {
+//!             println!("{}", fluss);
+//!         }
+//!     }
+//! }
+//! ```
+
+use super::parser::{parse, ParseError};
+use super::session::{Compile, FieldParser, OptionsContext, Session, SessionError};
+use crate::fluss::Fluss;
+use crate::produce::IpfixParser;
+use crate::protocol::RecordSetOwned;
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Metadata of a datagram which is not part of the IPFIX message.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PacketContext {
+    /// Address the datagram was received from, templates are kept per exporter.
+    pub exporter: SocketAddr,
+    /// Time the datagram was received, the time of decoding if not set.
+    pub received: Option<DateTime<Utc>>,
+}
+
+impl PacketContext {
+    pub fn new(exporter: SocketAddr) -> Self {
+        Self {
+            exporter,
+            received: None,
+        }
+    }
+
+    pub fn with_received(mut self, received: DateTime<Utc>) -> Self {
+        self.received = Some(received);
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The datagram is not a valid IPFIX message.
+    Parse(ParseError),
+    /// The message was rejected by the session of the exporter.
+    Session(SessionError),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(err) => write!(f, "invalid message: {}", err),
+            Self::Session(err) => write!(f, "rejected message: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Parse(err) => Some(err),
+            Self::Session(err) => Some(err),
+        }
+    }
+}
+
+impl From<ParseError> for DecodeError {
+    fn from(err: ParseError) -> Self {
+        Self::Parse(err)
+    }
+}
+
+impl From<SessionError> for DecodeError {
+    fn from(err: SessionError) -> Self {
+        Self::Session(err)
+    }
+}
+
+/// A [`Session`] per exporter, created on the first datagram of the exporter.
+struct Sessions<P: Compile> {
+    sessions: RwLock<HashMap<SocketAddr, Arc<Session<P>>>>,
+    new_session: Box<dyn Fn() -> Session<P> + Send + Sync>,
+}
+
+impl<P: Compile> Sessions<P> {
+    fn new(new_session: impl Fn() -> Session<P> + Send + Sync + 'static) -> Self {
+        Self {
+            sessions: RwLock::new(HashMap::new()),
+            new_session: Box::new(new_session),
+        }
+    }
+
+    fn get(&self, exporter: SocketAddr) -> Arc<Session<P>> {
+        if let Some(session) = self.sessions.read().get(&exporter) {
+            return Arc::clone(session);
+        }
+        let mut sessions = self.sessions.write();
+        let session = sessions
+            .entry(exporter)
+            .or_insert_with(|| Arc::new((self.new_session)()));
+        Arc::clone(session)
+    }
+}
+
+/// Decodes datagrams of any number of exporters into flows, without an async runtime.
+///
+/// Templates and options are learned per exporter, the first datagram of an
+/// exporter creates its [`Session`]. All methods take `&self`, a decoder can
+/// be shared between threads, e.g. in an [`Arc`]. Datagrams of an exporter
+/// should be decoded in the order they were received, otherwise data sets
+/// arriving before their template are skipped and sequence gaps are reported.
+pub struct Decoder {
+    sessions: Sessions<IpfixParser>,
+}
+
+impl Decoder {
+    /// Decodes the IPFIX information elements known to [`IpfixParser`].
+    pub fn new() -> Self {
+        Self::with_session(|| {
+            // the parser completes flows with the options recorded by the session
+            let options = OptionsContext::new();
+            Session::new(IpfixParser::new().with_options(options.clone())).with_options(options)
+        })
+    }
+
+    /// Creates the session of every new exporter with `new_session`, e.g. to
+    /// use custom fields or limit the accepted templates.
+    pub fn with_session(
+        new_session: impl Fn() -> Session<IpfixParser> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            sessions: Sessions::new(new_session),
+        }
+    }
+
+    /// Decodes the flows of a datagram containing a single IPFIX message.
+    pub fn decode(&self, datagram: &[u8], ctx: PacketContext) -> Result<Vec<Fluss>, DecodeError> {
+        let mut flows = Vec::new();
+        self.decode_with(datagram, ctx, |fluss| flows.push(fluss))?;
+        Ok(flows)
+    }
+
+    /// Like [`Decoder::decode`] but passes every flow to `f` instead of collecting them.
+    ///
+    /// Returns the amount of decoded flows.
+    pub fn decode_with<F>(
+        &self,
+        datagram: &[u8],
+        ctx: PacketContext,
+        mut f: F,
+    ) -> Result<usize, DecodeError>
+    where
+        F: FnMut(Fluss),
+    {
+        let packet = parse(datagram)?;
+        let session = self.sessions.get(ctx.exporter);
+
+        let mut count = 0;
+        for mut fluss in session.parse(&packet)? {
+            fluss.exporter = Some(ctx.exporter.ip());
+            if let Some(received) = ctx.received {
+                fluss.time_received = received;
+            }
+            f(fluss);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// The session of `exporter`, if it sent a datagram.
+    pub fn session(&self, exporter: SocketAddr) -> Option<Arc<Session<IpfixParser>>> {
+        self.sessions.sessions.read().get(&exporter).cloned()
+    }
+
+    /// Forgets the templates and options of `exporter`.
+    pub fn remove(&self, exporter: SocketAddr) {
+        self.sessions.sessions.write().remove(&exporter);
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decodes datagrams into record sets of the raw fields, see [`Decoder`].
+///
+/// Unlike flows record sets keep every field of the template, fields without
+/// a name are kept by their information element id.
+pub struct RawDecoder {
+    sessions: Sessions<FieldParser>,
+}
+
+impl RawDecoder {
+    /// Names the fields known to the default [`FieldParser`].
+    pub fn new() -> Self {
+        Self::with_session(|| Session::new(FieldParser::default()))
+    }
+
+    pub fn with_session(
+        new_session: impl Fn() -> Session<FieldParser> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            sessions: Sessions::new(new_session),
+        }
+    }
+
+    /// Decodes the data records of a datagram containing a single IPFIX message.
+    pub fn decode(
+        &self,
+        datagram: &[u8],
+        ctx: PacketContext,
+    ) -> Result<Vec<RecordSetOwned>, DecodeError> {
+        let packet = parse(datagram)?;
+        let session = self.sessions.get(ctx.exporter);
+
+        let record_sets = session
+            .parse(&packet)?
+            .into_iter()
+            .map(|record_set| record_set.into_owned())
+            .collect();
+        Ok(record_sets)
+    }
+
+    /// The session of `exporter`, if it sent a datagram.
+    pub fn session(&self, exporter: SocketAddr) -> Option<Arc<Session<FieldParser>>> {
+        self.sessions.sessions.read().get(&exporter).cloned()
+    }
+
+    /// Forgets the templates of `exporter`.
+    pub fn remove(&self, exporter: SocketAddr) {
+        self.sessions.sessions.write().remove(&exporter);
+    }
+}
+
+impl Default for RawDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipfix::parser::TemplateRecord;
+    use crate::testing::{field, DataRecord, MessageBuilder};
+    use chrono::TimeZone;
+    use std::net::Ipv4Addr;
+
+    fn template() -> TemplateRecord {
+        TemplateRecord {
+            id: 256,
+            fields: vec![field(8, 4), field(12, 4), field(4, 1), field(1, 8)],
+        }
+    }
+
+    fn record(bytes: u64) -> DataRecord {
+        DataRecord::new()
+            .addr([10, 0, 0, 1].into())
+            .addr([10, 0, 0, 2].into())
+            .u8(17)
+            .u64(bytes)
+    }
+
+    fn exporter(host: u8) -> PacketContext {
+        PacketContext::new((Ipv4Addr::new(192, 0, 2, host), 4739).into())
+    }
+
+    #[test]
+    fn flows_of_exporters() {
+        let decoder = Decoder::new();
+        let mut builder = MessageBuilder::new(1);
+        let templates = builder.templates(&[template()]);
+        let data = builder.data(256, &[record(1500), record(80)]);
+
+        assert!(decoder.decode(&templates, exporter(1)).unwrap().is_empty());
+        let received = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let flows = decoder
+            .decode(&data, exporter(1).with_received(received))
+            .unwrap();
+        assert_eq!(flows.len(), 2);
+        assert_eq!(flows[0].bytes, 1500);
+        assert_eq!(flows[1].bytes, 80);
+        assert_eq!(flows[0].exporter, Some(Ipv4Addr::new(192, 0, 2, 1).into()));
+        assert_eq!(flows[0].time_received, received);
+
+        // templates are kept per exporter
+        assert!(decoder.decode(&data, exporter(2)).unwrap().is_empty());
+        assert!(decoder.session(exporter(2).exporter).is_some());
+
+        let mut bytes = Vec::new();
+        let count = decoder
+            .decode_with(&data, exporter(1), |fluss| bytes.push(fluss.bytes))
+            .unwrap();
+        assert_eq!((count, bytes), (2, vec![1500, 80]));
+
+        decoder.remove(exporter(1).exporter);
+        assert!(decoder.session(exporter(1).exporter).is_none());
+        assert!(decoder.decode(&data, exporter(1)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_datagrams() {
+        let decoder = Decoder::new();
+        let err = decoder.decode(&[0, 10, 0, 4], exporter(1)).unwrap_err();
+        assert!(matches!(err, DecodeError::Parse(_)), "{}", err);
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn raw_record_sets() {
+        let decoder = RawDecoder::new();
+        let mut builder = MessageBuilder::new(1);
+        decoder
+            .decode(&builder.templates(&[template()]), exporter(1))
+            .unwrap();
+        let record_sets = decoder
+            .decode(&builder.data(256, &[record(1500)]), exporter(1))
+            .unwrap();
+
+        assert_eq!(record_sets.len(), 1);
+        let records = &record_sets[0].records;
+        let ids: Vec<_> = records.iter().map(|record| record.id).collect();
+        assert_eq!(ids, [8, 12, 4, 1]);
+        assert_eq!(records[3].value.as_u64(), Some(1500));
+    }
+
+    #[test]
+    fn decoders_are_shared_between_threads() {
+        fn shared<T: Send + Sync>() {}
+        shared::<Decoder>();
+        shared::<RawDecoder>();
+
+        let decoder = Arc::new(Decoder::new());
+        let mut builder = MessageBuilder::new(1);
+        let templates = builder.templates(&[template()]);
+        let data = builder.data(256, &[record(1500)]);
+
+        let handles: Vec<_> = (1..=4)
+            .map(|host| {
+                let decoder = Arc::clone(&decoder);
+                let (templates, data) = (templates.clone(), data.clone());
+                std::thread::spawn(move || {
+                    decoder.decode(&templates, exporter(host)).unwrap();
+                    decoder.decode(&data, exporter(host)).unwrap().len()
+                })
+            })
+            .collect();
+        let flows: usize = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .sum();
+        assert_eq!(flows, 4);
+    }
+}
@@ -0,0 +1,84 @@
+//! IPFIX information elements registered with IANA.
+
+/// An information element of the IANA registry.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct InformationElement {
+    pub id: u16,
+    pub name: &'static str,
+    /// Abstract data type, e.g. `unsigned32` or `ipv4Address` (RFC 7011).
+    pub data_type: &'static str,
+    /// Data type semantics, e.g. `deltaCounter`, empty if unspecified.
+    pub semantics: &'static str,
+    pub deprecated: bool,
+}
+
+// generated by the build script from the IANA IPFIX information element
+// registry, sorted by id
+include!(concat!(env!("OUT_DIR"), "/information_elements.rs"));
+
+/// Returns the IANA information element with `id`.
+pub fn lookup(id: u16) -> Option<&'static InformationElement> {
+    INFORMATION_ELEMENTS
+        .binary_search_by_key(&id, |element| element.id)
+        .ok()
+        .map(|index| &INFORMATION_ELEMENTS[index])
+}
+
+/// Returns all known information elements ordered by id.
+pub fn all() -> &'static [InformationElement] {
+    INFORMATION_ELEMENTS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spot_checks() {
+        let cases = [
+            (1, "octetDeltaCount", "unsigned64", "deltaCounter"),
+            (8, "sourceIPv4Address", "ipv4Address", "default"),
+            (18, "bgpNextHopIPv4Address", "ipv4Address", "default"),
+            (95, "applicationId", "octetArray", "identifier"),
+            (96, "applicationName", "string", ""),
+            (
+                152,
+                "flowStartMilliseconds",
+                "dateTimeMilliseconds",
+                "default",
+            ),
+            (222, "tcpAckTotalCount", "unsigned64", "totalCounter"),
+            (223, "tcpUrgTotalCount", "unsigned64", "totalCounter"),
+            (434, "mibObjectValueInteger", "signed32", "quantity"),
+            (460, "httpRequestHost", "string", "default"),
+        ];
+        for (id, name, data_type, semantics) in cases {
+            let element = lookup(id).unwrap();
+            assert_eq!(
+                (element.name, element.data_type, element.semantics),
+                (name, data_type, semantics),
+                "element {}",
+                id
+            );
+        }
+
+        assert!(lookup(84).unwrap().deprecated);
+        assert!(!lookup(1).unwrap().deprecated);
+        assert!(lookup(0).is_none());
+    }
+
+    #[test]
+    fn ids_are_unique() {
+        // strictly ascending, which the binary search of `lookup` relies on
+        for window in all().windows(2) {
+            assert!(
+                window[0].id < window[1].id,
+                "element {} follows {}",
+                window[1].id,
+                window[0].id
+            );
+        }
+        assert!(all().iter().all(|element| element.id < 0x8000));
+        assert!(all().len() > 400);
+    }
+}
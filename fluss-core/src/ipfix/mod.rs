@@ -0,0 +1,22 @@
+pub mod decoder;
+pub mod elements;
+pub mod parser;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+pub mod router;
+pub mod sanity;
+pub mod session;
+
+pub use decoder::{DecodeError, Decoder, PacketContext, RawDecoder};
+pub use elements::InformationElement;
+pub use parser::{
+    parse, parse_all, parse_owned, Context, FieldError, Message, OwnedPacket, Packet, ParseError,
+    ParseErrorKind,
+};
+pub use router::{Route, Routed, RoutedPlan, TemplateRouter, TemplateRule};
+pub use sanity::{SanityChecks, Suspicion};
+pub use session::{
+    Compile, DebugCallback, DebugParser, DecodePlan, Decoded, DomainTemplate, Extractor,
+    FieldParser, OptionsContext, OptionsRecord, Parser, Session, SessionError, SessionEvent,
+    TemplateError, TemplateStats, TemplateVersion, Templates,
+};
@@ -0,0 +1,771 @@
+use bytes::Bytes;
+use nom::bytes::complete::take;
+use nom::combinator::cond;
+use nom::number::complete::{be_u16, be_u32, be_u8};
+use nom::IResult;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+
+/// IPFIX version number, NetFlow v5 and v9 use 5 and 9 respectively.
+const IPFIX_VERSION: u16 = 10;
+
+// set ids 0, 1 and 4 to 255 are reserved (RFC 7011 3.3.2)
+const TEMPLATE_SET_ID: u16 = 2;
+const OPTIONS_TEMPLATE_SET_ID: u16 = 3;
+const MIN_DATA_SET_ID: u16 = 256;
+
+const MESSAGE_HEADER_LENGTH: u16 = 16;
+
+#[derive(Debug)]
+pub struct Packet<'a> {
+    pub version: u16,
+    pub export_time: u32,
+    pub sequence_number: u32,
+    pub observation_domain_id: u32,
+    pub sets: Vec<Set<'a>>,
+}
+
+/// A packet owning its data, unlike [`Packet`] it can be queued or sent across threads.
+///
+/// Cloning is cheap, the data is reference counted.
+#[derive(Debug, Clone)]
+pub struct OwnedPacket {
+    pub version: u16,
+    pub export_time: u32,
+    pub sequence_number: u32,
+    pub observation_domain_id: u32,
+    sets: Arc<[OwnedSet]>,
+}
+
+impl OwnedPacket {
+    pub fn sets(&self) -> &[OwnedSet] {
+        &self.sets
+    }
+}
+
+#[derive(Debug)]
+pub enum OwnedSet {
+    DataSet { id: u16, data: Bytes },
+    OptionsTemplateSet(Vec<OptionsTemplateRecord>),
+    TemplateSet(Vec<TemplateRecord>),
+}
+
+/// A borrowed set of either a [`Packet`] or an [`OwnedPacket`].
+#[derive(Debug)]
+pub enum SetRef<'a> {
+    DataSet(DataSet<'a>),
+    OptionsTemplateSet(&'a [OptionsTemplateRecord]),
+    TemplateSet(&'a [TemplateRecord]),
+}
+
+/// Common interface of [`Packet`] and [`OwnedPacket`].
+pub trait Message {
+    fn export_time(&self) -> u32;
+    fn sequence_number(&self) -> u32;
+    fn observation_domain_id(&self) -> u32;
+    fn set_refs(&self) -> impl Iterator<Item = SetRef<'_>>;
+}
+
+impl<'p> Message for Packet<'p> {
+    fn export_time(&self) -> u32 {
+        self.export_time
+    }
+
+    fn sequence_number(&self) -> u32 {
+        self.sequence_number
+    }
+
+    fn observation_domain_id(&self) -> u32 {
+        self.observation_domain_id
+    }
+
+    fn set_refs(&self) -> impl Iterator<Item = SetRef<'_>> {
+        self.sets.iter().map(|set| match set {
+            Set::DataSet(data) => SetRef::DataSet(DataSet {
+                id: data.id,
+                data: data.data,
+            }),
+            Set::OptionsTemplateSet(records) => SetRef::OptionsTemplateSet(records),
+            Set::TemplateSet(records) => SetRef::TemplateSet(records),
+        })
+    }
+}
+
+impl Message for OwnedPacket {
+    fn export_time(&self) -> u32 {
+        self.export_time
+    }
+
+    fn sequence_number(&self) -> u32 {
+        self.sequence_number
+    }
+
+    fn observation_domain_id(&self) -> u32 {
+        self.observation_domain_id
+    }
+
+    fn set_refs(&self) -> impl Iterator<Item = SetRef<'_>> {
+        self.sets.iter().map(|set| match set {
+            OwnedSet::DataSet { id, data } => SetRef::DataSet(DataSet {
+                id: *id,
+                data: &data[..],
+            }),
+            OwnedSet::OptionsTemplateSet(records) => SetRef::OptionsTemplateSet(records),
+            OwnedSet::TemplateSet(records) => SetRef::TemplateSet(records),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct DataSet<'a> {
+    pub id: u16,
+    pub data: &'a [u8],
+}
+
+impl<'a> DataSet<'a> {
+    /// Splits the record into the data of its `fields`.
+    ///
+    /// A field exceeding the remaining data yields an error and ends the iteration.
+    pub fn with_fields<'f>(
+        &self,
+        fields: &'f [FieldSpecifier],
+    ) -> impl Iterator<Item = Result<(&'f FieldSpecifier, &'a [u8]), FieldError>> + 'f
+    where
+        'a: 'f,
+    {
+        fields.iter().scan(Some(self.data), |input, field| {
+            let remaining = (*input)?;
+            match field.read_field(remaining) {
+                Ok((remaining, data)) => {
+                    *input = Some(remaining);
+                    Some(Ok((field, data)))
+                }
+                Err(err) => {
+                    *input = None;
+                    Some(Err(err))
+                }
+            }
+        })
+    }
+}
+
+/// A field of a data record could not be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldError {
+    /// The record ended before the field, `needed` includes the length
+    /// prefix of variable length fields.
+    Truncated {
+        field_id: u16,
+        needed: usize,
+        available: usize,
+    },
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated {
+                field_id,
+                needed,
+                available,
+            } => write!(
+                f,
+                "record truncated at field {}, needed {} bytes but {} are left",
+                field_id, needed, available
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FieldError {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateRecord {
+    pub id: u16,
+    pub fields: Vec<FieldSpecifier>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OptionsTemplateRecord {
+    pub id: u16,
+    /// The first `scope_field_count` fields are scope fields.
+    pub scope_field_count: u16,
+    pub fields: Vec<FieldSpecifier>,
+}
+
+#[derive(Debug)]
+pub enum Set<'a> {
+    DataSet(DataSet<'a>),
+    OptionsTemplateSet(Vec<OptionsTemplateRecord>),
+    TemplateSet(Vec<TemplateRecord>),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldSpecifier {
+    pub id: u16,
+    pub length: u16,
+    pub enterprise_id: Option<u32>,
+}
+
+impl FieldSpecifier {
+    pub fn read<'a>(&self, input: &'a [u8]) -> IResult<&'a [u8], &'a [u8]> {
+        // TODO make this prettier / use named!(pub read ...)
+        if self.length < u16::MAX {
+            return take(self.length)(input);
+        }
+
+        let (input, length) = be_u8(input)?;
+        if length < u8::MAX {
+            return take(length)(input);
+        }
+
+        let (input, length) = be_u16(input)?;
+        take(length)(input)
+    }
+
+    /// Like [`FieldSpecifier::read`] but reports how much data the field needs.
+    pub fn read_field<'a>(&self, input: &'a [u8]) -> Result<(&'a [u8], &'a [u8]), FieldError> {
+        self.read(input).map_err(|_| FieldError::Truncated {
+            field_id: self.id,
+            needed: self.encoded_length(input),
+            available: input.len(),
+        })
+    }
+
+    /// Encoded length of the field at the start of `input`, as far as it is known.
+    fn encoded_length(&self, input: &[u8]) -> usize {
+        if self.length < u16::MAX {
+            return self.length as usize;
+        }
+
+        match input {
+            [] => 1,
+            [length, ..] if *length < u8::MAX => 1 + *length as usize,
+            [_, high, low, ..] => 3 + u16::from_be_bytes([*high, *low]) as usize,
+            _ => 3,
+        }
+    }
+}
+
+fn parse_field_specifier(input: &[u8]) -> IResult<&[u8], FieldSpecifier> {
+    let (input, id) = be_u16(input)?;
+    let (input, length) = be_u16(input)?;
+    // the enterprise bit indicates a following enterprise number
+    let (input, enterprise_id) = cond(id & 0x8000 != 0, be_u32)(input)?;
+
+    Ok((
+        input,
+        FieldSpecifier {
+            id: id & 0x7fff,
+            length,
+            enterprise_id,
+        },
+    ))
+}
+
+/// The part of the message which was parsed when an error occurred.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Context {
+    /// The message header.
+    Header,
+    /// The header of a set, sets are counted from 0 within the message.
+    Set(usize),
+    /// A record of a template or options template set.
+    TemplateRecord { set: usize, record: usize },
+    /// A field specifier of a template record.
+    FieldSpecifier {
+        set: usize,
+        record: usize,
+        field: usize,
+    },
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Header => write!(f, "message header"),
+            Self::Set(set) => write!(f, "set {}", set),
+            Self::TemplateRecord { set, record } => {
+                write!(f, "set {} template record {}", set, record)
+            }
+            Self::FieldSpecifier { set, record, field } => write!(
+                f,
+                "set {} template record {} field specifier {}",
+                set, record, field
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The message is not an IPFIX message.
+    InvalidVersion(u16),
+    /// The length of the message or a set is shorter than its header.
+    InvalidLength(u16),
+    /// The message contains a set with a reserved set id.
+    ReservedSetId(u16),
+    /// The input contains bytes after the end of the message.
+    TrailingBytes(usize),
+    /// The input ended before the message or one of its sets was complete.
+    UnexpectedEnd,
+    /// The message is malformed.
+    Malformed(nom::error::ErrorKind),
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidVersion(version) => {
+                write!(f, "invalid version {}, expected {}", version, IPFIX_VERSION)
+            }
+            Self::InvalidLength(length) => write!(f, "invalid length {}", length),
+            Self::ReservedSetId(id) => write!(f, "reserved set id {}", id),
+            Self::TrailingBytes(count) => write!(f, "{} trailing bytes after message", count),
+            Self::UnexpectedEnd => write!(f, "unexpected end of message"),
+            Self::Malformed(kind) => write!(f, "malformed message: {}", kind.description()),
+        }
+    }
+}
+
+/// Error of a malformed message, `offset` is the position of the error in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub offset: usize,
+    pub context: Context,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at byte {} ({})",
+            self.kind, self.offset, self.context
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A parse error which still has to be located in the input.
+struct Failure<'a> {
+    kind: ParseErrorKind,
+    // the remaining input at the position of the error
+    input: &'a [u8],
+    context: Context,
+}
+
+impl<'a> Failure<'a> {
+    fn new(kind: ParseErrorKind, input: &'a [u8], context: Context) -> Self {
+        Self {
+            kind,
+            input,
+            context,
+        }
+    }
+
+    /// Resolves the offset of the error relative to `base`, the complete input.
+    fn locate(self, base: &[u8]) -> ParseError {
+        ParseError {
+            kind: self.kind,
+            offset: self.input.as_ptr() as usize - base.as_ptr() as usize,
+            context: self.context,
+        }
+    }
+}
+
+type PResult<'a, T> = Result<(&'a [u8], T), Failure<'a>>;
+
+/// Attaches the context to the result of a nom parser which was applied to `input`.
+fn at<'a, T>(result: IResult<&'a [u8], T>, input: &'a [u8], context: Context) -> PResult<'a, T> {
+    result.map_err(|err| match err {
+        nom::Err::Incomplete(_) => Failure::new(ParseErrorKind::UnexpectedEnd, input, context),
+        nom::Err::Error(err) | nom::Err::Failure(err) => {
+            let kind = match err.code {
+                nom::error::ErrorKind::Eof => ParseErrorKind::UnexpectedEnd,
+                kind => ParseErrorKind::Malformed(kind),
+            };
+            Failure::new(kind, err.input, context)
+        }
+    })
+}
+
+fn parse_field_specifiers<'a>(
+    mut input: &'a [u8],
+    count: u16,
+    set: usize,
+    record: usize,
+) -> PResult<'a, Vec<FieldSpecifier>> {
+    // every field specifier is at least 4 bytes, do not trust the count
+    let mut fields = Vec::with_capacity((count as usize).min(input.len() / 4));
+    for field in 0..count as usize {
+        let context = Context::FieldSpecifier { set, record, field };
+        let (remaining, specifier) = at(parse_field_specifier(input), input, context)?;
+        fields.push(specifier);
+        input = remaining;
+    }
+
+    Ok((input, fields))
+}
+
+fn parse_template_record(input: &[u8], set: usize, record: usize) -> PResult<'_, TemplateRecord> {
+    let context = Context::TemplateRecord { set, record };
+    let (input, id) = at(be_u16(input), input, context)?;
+    let (input, field_count) = at(be_u16(input), input, context)?;
+    let (input, fields) = parse_field_specifiers(input, field_count, set, record)?;
+
+    Ok((input, TemplateRecord { id, fields }))
+}
+
+fn parse_options_template_record(
+    input: &[u8],
+    set: usize,
+    record: usize,
+) -> PResult<'_, OptionsTemplateRecord> {
+    let context = Context::TemplateRecord { set, record };
+    let (input, id) = at(be_u16(input), input, context)?;
+    let (input, field_count) = at(be_u16(input), input, context)?;
+    let (input, scope_field_count) = at(be_u16(input), input, context)?;
+    let (input, fields) = parse_field_specifiers(input, field_count, set, record)?;
+
+    Ok((
+        input,
+        OptionsTemplateRecord {
+            id,
+            scope_field_count,
+            fields,
+        },
+    ))
+}
+
+/// Sets may be padded with zeros, a record never consists of zeros only.
+fn is_padding(input: &[u8]) -> bool {
+    input.iter().all(|&b| b == 0)
+}
+
+/// Parses records until the end of the set or the padding is reached.
+fn parse_records<'a, O>(
+    mut input: &'a [u8],
+    set: usize,
+    parser: impl Fn(&'a [u8], usize, usize) -> PResult<'a, O>,
+) -> Result<Vec<O>, Failure<'a>> {
+    let mut records = Vec::new();
+    while !is_padding(input) {
+        let (remaining, record) = parser(input, set, records.len())?;
+        records.push(record);
+        input = remaining;
+    }
+
+    Ok(records)
+}
+
+/// Parses the set header, returns the set id and the contents of the set.
+///
+/// Reserved set ids are rejected.
+fn parse_set_header(input: &[u8], set: usize) -> PResult<'_, (u16, &[u8])> {
+    let context = Context::Set(set);
+    let (rest, id) = at(be_u16(input), input, context)?;
+    if id < MIN_DATA_SET_ID && id != TEMPLATE_SET_ID && id != OPTIONS_TEMPLATE_SET_ID {
+        return Err(Failure::new(
+            ParseErrorKind::ReservedSetId(id),
+            input,
+            context,
+        ));
+    }
+
+    let input = rest;
+    let (remaining, length) = at(be_u16(input), input, context)?;
+    // the length includes the 4 byte set header
+    if length < 4 {
+        return Err(Failure::new(
+            ParseErrorKind::InvalidLength(length),
+            input,
+            context,
+        ));
+    }
+    let (remaining, data) = at(take(length - 4)(remaining), remaining, context)?;
+
+    Ok((remaining, (id, data)))
+}
+
+fn parse_set(input: &[u8], set: usize) -> PResult<'_, Set<'_>> {
+    let (remaining, (id, data)) = parse_set_header(input, set)?;
+
+    let set = match id {
+        TEMPLATE_SET_ID => Set::TemplateSet(parse_records(data, set, parse_template_record)?),
+        OPTIONS_TEMPLATE_SET_ID => {
+            Set::OptionsTemplateSet(parse_records(data, set, parse_options_template_record)?)
+        }
+        // padding at the end of the set is skipped by the session, it knows the record length
+        id => Set::DataSet(DataSet { id, data }),
+    };
+
+    Ok((remaining, set))
+}
+
+fn parse_message(input: &[u8]) -> PResult<'_, Packet<'_>> {
+    let context = Context::Header;
+    let message = input;
+
+    let (input, version) = at(be_u16(input), input, context)?;
+    if version != IPFIX_VERSION {
+        return Err(Failure::new(
+            ParseErrorKind::InvalidVersion(version),
+            message,
+            context,
+        ));
+    }
+
+    let (rest, length) = at(be_u16(input), input, context)?;
+    if length < MESSAGE_HEADER_LENGTH {
+        return Err(Failure::new(
+            ParseErrorKind::InvalidLength(length),
+            input,
+            context,
+        ));
+    }
+    // the version and length were already read
+    let (remaining, input) = at(take(length - 4)(rest), rest, context)?;
+    let (input, export_time) = at(be_u32(input), input, context)?;
+    let (input, sequence_number) = at(be_u32(input), input, context)?;
+    let (input, observation_domain_id) = at(be_u32(input), input, context)?;
+
+    let mut input = input;
+    let mut sets = Vec::new();
+    while !input.is_empty() {
+        // some exporters pad the message, too short for another set header
+        if input.len() < 4 && is_padding(input) {
+            break;
+        }
+
+        let (remaining, set) = parse_set(input, sets.len())?;
+        sets.push(set);
+        input = remaining;
+    }
+
+    Ok((
+        remaining,
+        Packet {
+            version,
+            export_time,
+            sequence_number,
+            observation_domain_id,
+            sets,
+        },
+    ))
+}
+
+fn do_parse<'a>(input: &'a [u8], base: &[u8]) -> Result<(&'a [u8], Packet<'a>), ParseError> {
+    parse_message(input).map_err(|failure| failure.locate(base))
+}
+
+pub fn parse(input: &[u8]) -> Result<Packet<'_>, ParseError> {
+    match do_parse(input, input)? {
+        (remaining, _) if !remaining.is_empty() => Err(Failure::new(
+            ParseErrorKind::TrailingBytes(remaining.len()),
+            remaining,
+            Context::Header,
+        )
+        .locate(input)),
+        (_, packet) => Ok(packet),
+    }
+}
+
+/// Parses a single IPFIX message into a packet owning the data of `buf`.
+pub fn parse_owned(buf: Bytes) -> Result<OwnedPacket, ParseError> {
+    let packet = parse(&buf)?;
+
+    let sets = packet
+        .sets
+        .into_iter()
+        .map(|set| match set {
+            Set::DataSet(data) => OwnedSet::DataSet {
+                id: data.id,
+                data: buf.slice_ref(data.data),
+            },
+            Set::OptionsTemplateSet(records) => OwnedSet::OptionsTemplateSet(records),
+            Set::TemplateSet(records) => OwnedSet::TemplateSet(records),
+        })
+        .collect();
+
+    Ok(OwnedPacket {
+        version: packet.version,
+        export_time: packet.export_time,
+        sequence_number: packet.sequence_number,
+        observation_domain_id: packet.observation_domain_id,
+        sets,
+    })
+}
+
+/// Parses all consecutive IPFIX messages contained in `input`.
+pub fn parse_all(input: &[u8]) -> Result<Vec<Packet<'_>>, ParseError> {
+    let base = input;
+    let mut input = input;
+    let mut packets = Vec::new();
+
+    while !input.is_empty() {
+        let (remaining, packet) = do_parse(input, base)?;
+        packets.push(packet);
+        input = remaining;
+    }
+
+    Ok(packets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A set with the header for `id` and the length of `data`.
+    fn set(id: u16, data: &[u8]) -> Vec<u8> {
+        let mut set = id.to_be_bytes().to_vec();
+        set.extend_from_slice(&(data.len() as u16 + 4).to_be_bytes());
+        set.extend_from_slice(data);
+        set
+    }
+
+    /// A message with the header for `sets`, the sets start at offset 16.
+    fn message(sets: &[u8]) -> Vec<u8> {
+        let mut message = vec![0, 10];
+        message.extend_from_slice(&(sets.len() as u16 + MESSAGE_HEADER_LENGTH).to_be_bytes());
+        message.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 3]);
+        message.extend_from_slice(sets);
+        message
+    }
+
+    fn error(input: &[u8]) -> ParseError {
+        parse(input).unwrap_err()
+    }
+
+    #[test]
+    fn truncated_message_header() {
+        let message = message(&[]);
+
+        let err = error(&message[..3]);
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedEnd);
+        assert_eq!(err.offset, 2);
+        assert_eq!(err.context, Context::Header);
+
+        // the length is read, the rest of the header is missing
+        let err = error(&message[..10]);
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedEnd);
+        assert_eq!(err.offset, 4);
+        assert_eq!(err.context, Context::Header);
+    }
+
+    #[test]
+    fn truncated_set_header() {
+        let mut sets = set(256, &[1, 2, 3, 4]);
+        // the set id of the second set, its length is missing
+        sets.extend_from_slice(&[1, 0, 0]);
+
+        let err = error(&message(&sets));
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedEnd);
+        assert_eq!(err.offset, 16 + 8 + 2);
+        assert_eq!(err.context, Context::Set(1));
+    }
+
+    #[test]
+    fn truncated_template_record() {
+        // template 256 with a single field, then the id of the next record
+        let records = [1, 0, 0, 1, 0, 8, 0, 4, 1, 1, 0];
+
+        let err = error(&message(&set(TEMPLATE_SET_ID, &records)));
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedEnd);
+        assert_eq!(err.offset, 16 + 4 + 10);
+        assert_eq!(err.context, Context::TemplateRecord { set: 0, record: 1 });
+    }
+
+    #[test]
+    fn truncated_field_specifier() {
+        // template 256 announces two fields, the length of the second is missing
+        let records = [1, 0, 0, 2, 0, 8, 0, 4, 0, 12];
+
+        let err = error(&message(&set(TEMPLATE_SET_ID, &records)));
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedEnd);
+        assert_eq!(err.offset, 16 + 4 + 10);
+        assert_eq!(
+            err.context,
+            Context::FieldSpecifier {
+                set: 0,
+                record: 0,
+                field: 1
+            }
+        );
+
+        // the enterprise number of an enterprise specific field is missing
+        let records = [1, 0, 0, 1, 0x80, 1, 0, 4, 0, 0];
+        let err = error(&message(&set(TEMPLATE_SET_ID, &records)));
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedEnd);
+        assert_eq!(err.offset, 16 + 4 + 8);
+        assert_eq!(
+            err.context,
+            Context::FieldSpecifier {
+                set: 0,
+                record: 0,
+                field: 0
+            }
+        );
+    }
+
+    #[test]
+    fn netflow_v9_is_rejected() {
+        // version, count, system uptime, unix seconds, sequence number and source id
+        let header = [0, 9, 0, 1, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 4];
+
+        let err = error(&header);
+        assert_eq!(err.kind, ParseErrorKind::InvalidVersion(9));
+        assert_eq!(err.offset, 0);
+        assert_eq!(err.context, Context::Header);
+    }
+
+    #[test]
+    fn bytes_after_the_message_length_are_trailing() {
+        let mut input = message(&set(256, &[1, 2, 3, 4]));
+        input.extend_from_slice(&[5, 6, 7]);
+
+        let err = error(&input);
+        assert_eq!(err.kind, ParseErrorKind::TrailingBytes(3));
+        assert_eq!(err.offset, 16 + 8);
+        assert_eq!(err.context, Context::Header);
+    }
+
+    #[test]
+    fn cut_off_set() {
+        // the set announces 8 bytes of data, the message ends after 4
+        let mut sets = set(256, &[1, 2, 3, 4, 5, 6, 7, 8]);
+        sets.truncate(8);
+
+        let err = error(&message(&sets));
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedEnd);
+        assert_eq!(err.offset, 16 + 4);
+        assert_eq!(err.context, Context::Set(0));
+    }
+
+    #[test]
+    fn reserved_set_ids_are_rejected() {
+        for id in [0, 1, 4, 255] {
+            let err = error(&message(&set(id, &[1, 2, 3, 4])));
+            assert_eq!(err.kind, ParseErrorKind::ReservedSetId(id));
+            assert_eq!(err.offset, 16);
+            assert_eq!(err.context, Context::Set(0));
+        }
+    }
+
+    #[test]
+    fn first_data_set_id_is_accepted() {
+        let input = message(&set(256, &[1, 2, 3, 4]));
+        let packet = parse(&input).unwrap();
+
+        assert_eq!(packet.sets.len(), 1);
+        match &packet.sets[0] {
+            Set::DataSet(data) => {
+                assert_eq!(data.id, 256);
+                assert_eq!(data.data, [1, 2, 3, 4]);
+            }
+            set => panic!("expected a data set, got {:?}", set),
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Field extractors loaded from shared libraries.
+//!
+//! A plugin exports a C function which decodes the data of a field:
+//!
+//! ```c
+//! ParsedValue parse_vendor_field(const uint8_t *data, size_t len);
+//! ```
+//!
+//! The returned [`ParsedValue`] does not own any memory, strings and bytes
+//! are returned as a range of the field data.
+
+use crate::protocol::Value;
+use anyhow::Context as _;
+use chrono::{TimeZone, Utc};
+use libloading::Library;
+use std::convert::TryFrom;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+/// The field could not be decoded, it is kept as raw bytes.
+pub const PARSED_UNKNOWN: u32 = 0;
+/// An unsigned number in `number`.
+pub const PARSED_NUMBER: u32 = 1;
+/// An IPv4 address in the first 4 bytes of `addr`.
+pub const PARSED_IPV4: u32 = 2;
+/// An IPv6 address in `addr`.
+pub const PARSED_IPV6: u32 = 3;
+/// A MAC address in the first 6 bytes of `addr`.
+pub const PARSED_MAC: u32 = 4;
+/// UTF-8 text, the range `offset..offset + len` of the field data.
+pub const PARSED_STRING: u32 = 5;
+/// Bytes, the range `offset..offset + len` of the field data.
+pub const PARSED_BYTES: u32 = 6;
+/// Milliseconds since the unix epoch in `number`.
+pub const PARSED_DATETIME_MILLIS: u32 = 7;
+/// Milliseconds in `number`.
+pub const PARSED_DURATION_MILLIS: u32 = 8;
+
+/// Value decoded by a plugin, the `kind` selects the used members.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParsedValue {
+    /// One of the `PARSED_*` constants.
+    pub kind: u32,
+    pub number: u64,
+    pub addr: [u8; 16],
+    pub offset: usize,
+    pub len: usize,
+}
+
+impl ParsedValue {
+    /// Converts the value, values with an invalid kind or range are unknown.
+    pub fn into_value(self, data: &[u8]) -> Value<'_> {
+        let range = self
+            .offset
+            .checked_add(self.len)
+            .and_then(|end| data.get(self.offset..end));
+        let addr = self.addr;
+
+        let value = match (self.kind, range) {
+            (PARSED_NUMBER, _) => Some(Value::U64(self.number)),
+            (PARSED_IPV4, _) => Some(Value::Ipv4Addr(Ipv4Addr::new(
+                addr[0], addr[1], addr[2], addr[3],
+            ))),
+            (PARSED_IPV6, _) => Some(Value::Ipv6Addr(Ipv6Addr::from(addr))),
+            (PARSED_MAC, _) => Some(Value::MacAddr6(macaddr::MacAddr6::new(
+                addr[0], addr[1], addr[2], addr[3], addr[4], addr[5],
+            ))),
+            (PARSED_STRING, Some(range)) => std::str::from_utf8(range)
+                .ok()
+                .map(|string| Value::String(string.to_owned())),
+            (PARSED_BYTES, Some(range)) => Some(Value::Bytes(range.into())),
+            (PARSED_DATETIME_MILLIS, _) => i64::try_from(self.number)
+                .ok()
+                .and_then(|millis| Utc.timestamp_millis_opt(millis).single())
+                .map(Value::DateTime),
+            (PARSED_DURATION_MILLIS, _) => {
+                Some(Value::Duration(Duration::from_millis(self.number)))
+            }
+            _ => None,
+        };
+
+        value.unwrap_or_else(|| Value::Unknown(data.into()))
+    }
+}
+
+type ParseFn = unsafe extern "C" fn(data: *const u8, len: usize) -> ParsedValue;
+
+/// A field extractor exported by a shared library.
+pub struct Plugin {
+    function: ParseFn,
+    // the function is only valid as long as the library is loaded
+    _library: Library,
+}
+
+impl Plugin {
+    /// Loads the function `symbol` from the shared library at `path`.
+    ///
+    /// Loading runs the initialization code of the library and the function
+    /// is trusted to have the expected signature and to only read `len` bytes.
+    pub fn load(path: &str, symbol: &str) -> anyhow::Result<Self> {
+        // SAFETY: plugins are trusted like the binary itself, see above
+        unsafe {
+            let library =
+                Library::new(path).with_context(|| format!("failed to load plugin {}", path))?;
+            let function = *library
+                .get::<ParseFn>(symbol.as_bytes())
+                .with_context(|| format!("plugin {} does not export {}", path, symbol))?;
+
+            Ok(Self {
+                function,
+                _library: library,
+            })
+        }
+    }
+
+    pub fn extract<'a>(&self, data: &'a [u8]) -> Value<'a> {
+        // SAFETY: the function reads at most `len` bytes of `data`
+        let parsed = unsafe { (self.function)(data.as_ptr(), data.len()) };
+        parsed.into_value(data)
+    }
+}
@@ -0,0 +1,426 @@
+//! Selects the parser of a template by the information elements it contains.
+//!
+//! Exporters may send templates which do not describe flows, e.g. interface
+//! statistics. A [`TemplateRouter`] decodes flow-like templates with its flow
+//! parser and all others with a second parser, or skips them.
+//!
+//! ```ignore
+//! use fluss_core::ipfix::{FieldParser, Routed, Session, TemplateRouter};
+//! use fluss_core::produce::IpfixParser;
+//!
+//! let router = TemplateRouter::new(IpfixParser::new()).with_other(FieldParser::default());
+//! let session = Session::new(router);
+//! for output in session.parse(&packet)? {
+//!     match output {
+//!         Routed::Flow(fluss) => println!("{}", fluss),
+//!         Routed::Other(record_set) => println!("template {}", record_set.id),
+//!     }
+//! }
+//! ```
+
+use super::parser::{DataSet, FieldSpecifier};
+use super::sanity::SanityChecks;
+use super::session::{Compile, FieldParser, Parser};
+use serde::Deserialize;
+
+// sourceIPv4Address and destinationIPv4Address
+const IPV4_ADDRS: &[u16] = &[8, 12];
+// sourceIPv6Address and destinationIPv6Address
+const IPV6_ADDRS: &[u16] = &[27, 28];
+// octetDeltaCount, packetDeltaCount, octetTotalCount and packetTotalCount
+const COUNTERS: &[u16] = &[1, 2, 85, 86];
+
+/// Parser of the records of a template.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Route {
+    /// Decoded by the flow parser of the router.
+    Flow,
+    /// Decoded by the other parser of the router, skipped without one.
+    Other,
+    /// Skipped.
+    Drop,
+}
+
+/// Routes templates with the required and without the forbidden information elements.
+///
+/// Only IANA information elements are matched, enterprise specific fields are ignored.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TemplateRule {
+    /// The template contains all of these elements.
+    #[serde(default)]
+    pub require: Vec<u16>,
+    /// The template contains at least one of these elements, if any.
+    #[serde(default)]
+    pub require_any: Vec<u16>,
+    /// The template contains none of these elements.
+    #[serde(default)]
+    pub forbid: Vec<u16>,
+    pub route: Route,
+}
+
+impl TemplateRule {
+    pub fn new(route: Route) -> Self {
+        Self {
+            require: Vec::new(),
+            require_any: Vec::new(),
+            forbid: Vec::new(),
+            route,
+        }
+    }
+
+    pub fn with_require(mut self, ids: &[u16]) -> Self {
+        self.require.extend_from_slice(ids);
+        self
+    }
+
+    pub fn with_require_any(mut self, ids: &[u16]) -> Self {
+        self.require_any.extend_from_slice(ids);
+        self
+    }
+
+    pub fn with_forbid(mut self, ids: &[u16]) -> Self {
+        self.forbid.extend_from_slice(ids);
+        self
+    }
+
+    /// Whether a template of `fields` matches the rule.
+    pub fn matches(&self, fields: &[FieldSpecifier]) -> bool {
+        let contains = |id: &u16| {
+            fields
+                .iter()
+                .any(|field| field.enterprise_id.is_none() && field.id == *id)
+        };
+
+        self.require.iter().all(contains)
+            && (self.require_any.is_empty() || self.require_any.iter().any(contains))
+            && !self.forbid.iter().any(contains)
+    }
+}
+
+/// Output of a [`TemplateRouter`].
+#[derive(Debug)]
+pub enum Routed<F, O> {
+    Flow(F),
+    Other(O),
+}
+
+impl<F, O> Routed<F, O> {
+    /// The output of the flow parser.
+    pub fn into_flow(self) -> Option<F> {
+        match self {
+            Self::Flow(flow) => Some(flow),
+            Self::Other(_) => None,
+        }
+    }
+}
+
+/// Plan of a template, compiled by the parser it was routed to.
+#[derive(Debug, Clone)]
+pub enum RoutedPlan<F, O> {
+    Flow(F),
+    Other(O),
+    Drop,
+}
+
+/// Decodes every template with the parser selected by its [`TemplateRule`]s.
+///
+/// The rules are checked in order, the first matching rule routes the
+/// template. Templates matching no rule take the default route, by default
+/// [`Route::Other`]. The route is chosen once when the template is registered.
+///
+/// Without rules, templates with both source and destination addresses and
+/// at least one byte or packet counter are flows.
+#[derive(Debug, Clone)]
+pub struct TemplateRouter<F, O = FieldParser> {
+    flows: F,
+    other: Option<O>,
+    rules: Vec<TemplateRule>,
+    default_route: Route,
+}
+
+impl<F> TemplateRouter<F> {
+    /// Decodes flows with `flows` and skips all other templates.
+    pub fn new(flows: F) -> Self {
+        Self {
+            flows,
+            other: None,
+            rules: vec![
+                TemplateRule::new(Route::Flow)
+                    .with_require(IPV4_ADDRS)
+                    .with_require_any(COUNTERS),
+                TemplateRule::new(Route::Flow)
+                    .with_require(IPV6_ADDRS)
+                    .with_require_any(COUNTERS),
+            ],
+            default_route: Route::Other,
+        }
+    }
+}
+
+impl<F, O> TemplateRouter<F, O> {
+    /// Decodes templates routed to [`Route::Other`] with `other`.
+    pub fn with_other<T>(self, other: T) -> TemplateRouter<F, T> {
+        TemplateRouter {
+            flows: self.flows,
+            other: Some(other),
+            rules: self.rules,
+            default_route: self.default_route,
+        }
+    }
+
+    /// Replaces the rules, e.g. loaded from a configuration file.
+    pub fn with_rules(mut self, rules: Vec<TemplateRule>) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Route of templates matching no rule.
+    pub fn with_default_route(mut self, route: Route) -> Self {
+        self.default_route = route;
+        self
+    }
+
+    /// Returns the route of a template of `fields`.
+    pub fn route(&self, fields: &[FieldSpecifier]) -> Route {
+        let route = self
+            .rules
+            .iter()
+            .find(|rule| rule.matches(fields))
+            .map_or(self.default_route, |rule| rule.route);
+
+        match (route, &self.other) {
+            (Route::Other, None) => Route::Drop,
+            (route, _) => route,
+        }
+    }
+}
+
+impl<F: Compile, O: Compile> Compile for TemplateRouter<F, O> {
+    type Plan = RoutedPlan<F::Plan, O::Plan>;
+
+    fn compile(&self, fields: &[FieldSpecifier]) -> Self::Plan {
+        match (self.route(fields), &self.other) {
+            (Route::Flow, _) => RoutedPlan::Flow(self.flows.compile(fields)),
+            (Route::Other, Some(other)) => RoutedPlan::Other(other.compile(fields)),
+            _ => RoutedPlan::Drop,
+        }
+    }
+}
+
+impl<F: Parser, O: Parser> Parser for TemplateRouter<F, O> {
+    type Output<'a> = Routed<F::Output<'a>, O::Output<'a>>;
+
+    fn parse<'a>(&self, fields: &[FieldSpecifier], set: &DataSet<'a>) -> Option<Self::Output<'a>> {
+        match (self.route(fields), &self.other) {
+            (Route::Flow, _) => self.flows.parse(fields, set).map(Routed::Flow),
+            (Route::Other, Some(other)) => other.parse(fields, set).map(Routed::Other),
+            _ => None,
+        }
+    }
+
+    fn parse_in_domain<'a>(
+        &self,
+        domain_id: u32,
+        fields: &[FieldSpecifier],
+        set: &DataSet<'a>,
+    ) -> Option<Self::Output<'a>> {
+        match (self.route(fields), &self.other) {
+            (Route::Flow, _) => self
+                .flows
+                .parse_in_domain(domain_id, fields, set)
+                .map(Routed::Flow),
+            (Route::Other, Some(other)) => other
+                .parse_in_domain(domain_id, fields, set)
+                .map(Routed::Other),
+            _ => None,
+        }
+    }
+
+    fn parse_planned<'a>(
+        &self,
+        plan: &Self::Plan,
+        domain_id: u32,
+        fields: &[FieldSpecifier],
+        set: &DataSet<'a>,
+    ) -> Option<Self::Output<'a>> {
+        match (plan, &self.other) {
+            (RoutedPlan::Flow(plan), _) => self
+                .flows
+                .parse_planned(plan, domain_id, fields, set)
+                .map(Routed::Flow),
+            (RoutedPlan::Other(plan), Some(other)) => other
+                .parse_planned(plan, domain_id, fields, set)
+                .map(Routed::Other),
+            _ => None,
+        }
+    }
+
+    fn check_sanity(
+        &self,
+        checks: &SanityChecks,
+        fields: &[FieldSpecifier],
+        output: &mut Self::Output<'_>,
+    ) -> bool {
+        match (output, &self.other) {
+            (Routed::Flow(flow), _) => self.flows.check_sanity(checks, fields, flow),
+            (Routed::Other(output), Some(other)) => other.check_sanity(checks, fields, output),
+            (Routed::Other(_), None) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fluss::Fluss;
+    use crate::ipfix::parser::{parse, TemplateRecord};
+    use crate::ipfix::Session;
+    use crate::produce::IpfixParser;
+    use crate::testing::{field, DataRecord, MessageBuilder};
+
+    fn flow_template() -> TemplateRecord {
+        TemplateRecord {
+            id: 256,
+            fields: vec![field(8, 4), field(12, 4), field(11, 2), field(1, 8)],
+        }
+    }
+
+    // interface statistics: ingressInterface, octetTotalCount and packetTotalCount
+    fn stats_template() -> TemplateRecord {
+        TemplateRecord {
+            id: 257,
+            fields: vec![field(10, 4), field(85, 8), field(86, 8)],
+        }
+    }
+
+    /// Decodes a flow template and an interface statistics template with a
+    /// record each, returns the flows and the template id and record count of
+    /// all other outputs.
+    fn decode(router: TemplateRouter<IpfixParser>) -> (Vec<Fluss>, Vec<(u16, usize)>) {
+        let session = Session::new(router);
+        let mut builder = MessageBuilder::new(1);
+        let flow = DataRecord::new()
+            .addr([10, 0, 0, 1].into())
+            .addr([192, 0, 2, 1].into())
+            .u16(443)
+            .u64(1500);
+        let stats = DataRecord::new().u32(3).u64(9_000_000).u64(7000);
+        let messages = [
+            builder.templates(&[flow_template(), stats_template()]),
+            builder.data(256, &[flow]),
+            builder.data(257, &[stats]),
+        ];
+
+        let mut flows = Vec::new();
+        let mut other = Vec::new();
+        for message in &messages {
+            let packet = parse(message).unwrap();
+            for output in session.parse(&packet).unwrap() {
+                match output {
+                    Routed::Flow(fluss) => flows.push(fluss),
+                    Routed::Other(set) => other.push((set.id, set.records.len())),
+                }
+            }
+        }
+        (flows, other)
+    }
+
+    fn fields(ids: &[u16]) -> Vec<FieldSpecifier> {
+        ids.iter().map(|&id| field(id, 4)).collect()
+    }
+
+    #[test]
+    fn flow_and_stats_templates() {
+        let router = TemplateRouter::new(IpfixParser::new()).with_other(FieldParser::default());
+        let (flows, other) = decode(router);
+        assert_eq!(flows.len(), 1);
+        assert_eq!((flows[0].dst_port, flows[0].bytes), (443, 1500));
+        assert_eq!(other, [(257, 3)]);
+    }
+
+    #[test]
+    fn other_templates_are_skipped_without_a_parser() {
+        let (flows, other) = decode(TemplateRouter::new(IpfixParser::new()));
+        assert_eq!(flows.len(), 1);
+        assert!(other.is_empty());
+    }
+
+    #[test]
+    fn default_rules() {
+        let router = TemplateRouter::new(IpfixParser::new()).with_other(FieldParser::default());
+        assert_eq!(router.route(&flow_template().fields), Route::Flow);
+        assert_eq!(router.route(&stats_template().fields), Route::Other);
+        // IPv6 addresses and a packet counter
+        assert_eq!(router.route(&fields(&[27, 28, 2])), Route::Flow);
+        // addresses without counters
+        assert_eq!(router.route(&fields(&[8, 12, 7, 11])), Route::Other);
+        // a source address only
+        assert_eq!(router.route(&fields(&[8, 1])), Route::Other);
+
+        // enterprise specific elements are not matched
+        let mut enterprise = fields(&[8, 12, 1]);
+        enterprise[2].enterprise_id = Some(29305);
+        assert_eq!(router.route(&enterprise), Route::Other);
+    }
+
+    #[test]
+    fn rules_are_checked_in_order() {
+        let router = TemplateRouter::new(IpfixParser::new())
+            .with_other(FieldParser::default())
+            .with_rules(vec![
+                TemplateRule::new(Route::Drop).with_require(&[10]),
+                TemplateRule::new(Route::Flow)
+                    .with_require_any(&[8, 27])
+                    .with_forbid(&[10]),
+            ])
+            .with_default_route(Route::Drop);
+
+        assert_eq!(router.route(&fields(&[10, 85])), Route::Drop);
+        assert_eq!(router.route(&fields(&[8, 1])), Route::Flow);
+        assert_eq!(router.route(&fields(&[27])), Route::Flow);
+        assert_eq!(router.route(&fields(&[12, 1])), Route::Drop);
+
+        // the statistics template is dropped
+        let (flows, other) = decode(
+            router
+                .with_other(FieldParser::default())
+                .with_rules(vec![TemplateRule::new(Route::Flow).with_require(&[8, 12])]),
+        );
+        assert_eq!(flows.len(), 1);
+        assert!(other.is_empty());
+    }
+
+    #[test]
+    fn rules_of_configuration_files() {
+        #[derive(Deserialize)]
+        struct Config {
+            rules: Vec<TemplateRule>,
+        }
+
+        let config: Config = toml::from_str(
+            r#"
+            [[rules]]
+            require = [10]
+            require_any = [85, 86]
+            route = "other"
+
+            [[rules]]
+            forbid = [8]
+            route = "drop"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.rules,
+            [
+                TemplateRule::new(Route::Other)
+                    .with_require(&[10])
+                    .with_require_any(&[85, 86]),
+                TemplateRule::new(Route::Drop).with_forbid(&[8]),
+            ]
+        );
+
+        assert!(toml::from_str::<Config>("[[rules]]\nroute = \"stats\"").is_err());
+    }
+}
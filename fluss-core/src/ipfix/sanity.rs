@@ -0,0 +1,250 @@
+//! Detection of records decoded with the wrong layout of their template.
+//!
+//! Exporters may change the layout of a template without changing its id,
+//! e.g. after a firmware update. Records sent around the change are decoded
+//! with the other layout and produce absurd values. The [`Session`](super::Session)
+//! counts suspect records per template and reports templates producing many
+//! of them shortly after their layout changed.
+
+use super::parser::FieldSpecifier;
+use crate::fluss::Fluss;
+use std::fmt;
+use std::time::Duration;
+
+/// Suggested limit of [`SanityChecks::with_max_bytes`], 1 PiB.
+pub const DEFAULT_MAX_BYTES: u64 = 1 << 50;
+/// Default amount of suspect records after which a template is reported.
+pub const DEFAULT_SUSPECT_THRESHOLD: u64 = 10;
+/// Default time after a layout change in which suspect records are reported.
+pub const DEFAULT_SUSPECT_WINDOW: Duration = Duration::from_secs(300);
+
+// transport ports of the forward and post NAPT headers, always encoded as unsigned16
+const PORT_FIELDS: &[u16] = &[7, 11, 180, 181, 182, 183, 227, 228];
+
+/// Heuristics flagging decoded flows as suspect, all checks are disabled by default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SanityChecks {
+    max_bytes: Option<u64>,
+    port_lengths: bool,
+    max_time_reversal: Option<Duration>,
+    threshold: u64,
+    window: Duration,
+    quarantine: bool,
+}
+
+/// Reason why a flow is suspect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Suspicion {
+    /// The flow has more bytes than the limit.
+    Bytes(u64),
+    /// A port field of the template is not two bytes long.
+    PortLength { field: u16, length: u16 },
+    /// The flow ended before it started.
+    TimeReversal(Duration),
+}
+
+impl fmt::Display for Suspicion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bytes(bytes) => write!(f, "implausible byte count {}", bytes),
+            Self::PortLength { field, length } => {
+                write!(f, "port field {} has a length of {}", field, length)
+            }
+            Self::TimeReversal(reversal) => {
+                write!(f, "flow ended {:?} before it started", reversal)
+            }
+        }
+    }
+}
+
+impl SanityChecks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Flags flows with more than `bytes` bytes, see [`DEFAULT_MAX_BYTES`].
+    pub fn with_max_bytes(mut self, bytes: u64) -> Self {
+        self.max_bytes = Some(bytes);
+        self
+    }
+
+    /// Flags flows of templates with port fields which are not two bytes long.
+    ///
+    /// Reduced-size encoding allows one byte ports, no known exporter uses them.
+    pub fn with_port_lengths(mut self) -> Self {
+        self.port_lengths = true;
+        self
+    }
+
+    /// Flags flows ending more than `reversal` before they started.
+    pub fn with_max_time_reversal(mut self, reversal: Duration) -> Self {
+        self.max_time_reversal = Some(reversal);
+        self
+    }
+
+    /// Reports a template once it produced `count` suspect records shortly
+    /// after its layout changed, defaults to [`DEFAULT_SUSPECT_THRESHOLD`].
+    pub fn with_threshold(mut self, count: u64) -> Self {
+        self.threshold = count;
+        self
+    }
+
+    /// Only reports templates within `window` after their layout changed,
+    /// defaults to [`DEFAULT_SUSPECT_WINDOW`].
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Drops the records of a reported template until the template is announced again.
+    pub fn with_quarantine(mut self) -> Self {
+        self.quarantine = true;
+        self
+    }
+
+    /// Whether any heuristic is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.max_bytes.is_some() || self.port_lengths || self.max_time_reversal.is_some()
+    }
+
+    pub fn threshold(&self) -> u64 {
+        self.threshold
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    pub fn quarantine(&self) -> bool {
+        self.quarantine
+    }
+
+    /// Returns the first reason why `fluss`, decoded with `fields`, is suspect.
+    pub fn check(&self, fields: &[FieldSpecifier], fluss: &Fluss) -> Option<Suspicion> {
+        if let Some(max_bytes) = self.max_bytes {
+            if fluss.bytes > max_bytes {
+                return Some(Suspicion::Bytes(fluss.bytes));
+            }
+        }
+
+        if self.port_lengths {
+            let port = fields.iter().find(|field| {
+                field.enterprise_id.is_none()
+                    && PORT_FIELDS.contains(&field.id)
+                    && field.length != 2
+            });
+            if let Some(field) = port {
+                return Some(Suspicion::PortLength {
+                    field: field.id,
+                    length: field.length,
+                });
+            }
+        }
+
+        if let (Some(max), Some(start), Some(end)) =
+            (self.max_time_reversal, fluss.flow_start, fluss.flow_end)
+        {
+            let reversal = (start - end).to_std().unwrap_or_default();
+            if reversal > max {
+                return Some(Suspicion::TimeReversal(reversal));
+            }
+        }
+
+        None
+    }
+}
+
+impl Default for SanityChecks {
+    fn default() -> Self {
+        Self {
+            max_bytes: None,
+            port_lengths: false,
+            max_time_reversal: None,
+            threshold: DEFAULT_SUSPECT_THRESHOLD,
+            window: DEFAULT_SUSPECT_WINDOW,
+            quarantine: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{field, flow};
+
+    fn fields(port_length: u16) -> Vec<FieldSpecifier> {
+        vec![
+            field(8, 4),
+            field(12, 4),
+            field(11, port_length),
+            field(1, 8),
+        ]
+    }
+
+    #[test]
+    fn checks_are_disabled_by_default() {
+        let checks = SanityChecks::new();
+        assert!(!checks.is_enabled());
+        let fluss = flow([10, 0, 0, 1].into(), [10, 0, 0, 2].into(), 443, u64::MAX, 1);
+        assert_eq!(checks.check(&fields(1), &fluss), None);
+    }
+
+    #[test]
+    fn implausible_byte_counts() {
+        let checks = SanityChecks::new().with_max_bytes(DEFAULT_MAX_BYTES);
+        let mut fluss = flow([10, 0, 0, 1].into(), [10, 0, 0, 2].into(), 443, 1 << 50, 1);
+        assert_eq!(checks.check(&fields(2), &fluss), None);
+        fluss.bytes += 1;
+        assert_eq!(
+            checks.check(&fields(2), &fluss),
+            Some(Suspicion::Bytes((1 << 50) + 1))
+        );
+    }
+
+    #[test]
+    fn port_lengths() {
+        let checks = SanityChecks::new().with_port_lengths();
+        let fluss = flow([10, 0, 0, 1].into(), [10, 0, 0, 2].into(), 443, 1500, 1);
+        assert_eq!(checks.check(&fields(2), &fluss), None);
+        assert_eq!(
+            checks.check(&fields(1), &fluss),
+            Some(Suspicion::PortLength {
+                field: 11,
+                length: 1
+            })
+        );
+
+        // enterprise specific elements with the same id are no ports
+        let mut fields = fields(2);
+        fields.push(FieldSpecifier {
+            id: 7,
+            length: 4,
+            enterprise_id: Some(9),
+        });
+        assert_eq!(checks.check(&fields, &fluss), None);
+    }
+
+    #[test]
+    fn time_reversals() {
+        let checks = SanityChecks::new().with_max_time_reversal(Duration::from_secs(60));
+        let mut fluss = flow([10, 0, 0, 1].into(), [10, 0, 0, 2].into(), 443, 1500, 1);
+        let start: chrono::DateTime<chrono::Utc> = "2024-05-01T12:00:00Z".parse().unwrap();
+        fluss.flow_start = Some(start);
+
+        fluss.flow_end = Some(start - chrono::Duration::seconds(60));
+        assert_eq!(checks.check(&fields(2), &fluss), None);
+        fluss.flow_end = Some(start - chrono::Duration::seconds(61));
+        assert_eq!(
+            checks.check(&fields(2), &fluss),
+            Some(Suspicion::TimeReversal(Duration::from_secs(61)))
+        );
+        assert_eq!(
+            Suspicion::TimeReversal(Duration::from_secs(61)).to_string(),
+            "flow ended 61s before it started"
+        );
+
+        // flows without timestamps are not checked
+        fluss.flow_start = None;
+        assert_eq!(checks.check(&fields(2), &fluss), None);
+    }
+}
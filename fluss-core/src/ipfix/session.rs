@@ -0,0 +1,2451 @@
+use super::elements::{self, InformationElement};
+use super::parser::{
+    DataSet, FieldError, FieldSpecifier, Message, OptionsTemplateRecord, TemplateRecord,
+};
+use super::sanity::SanityChecks;
+use crate::clock;
+use crate::protocol::{
+    parse_boolean, parse_bytes, parse_datetime_millis, parse_datetime_ntp_micro,
+    parse_datetime_ntp_nano, parse_datetime_seconds, parse_duration_micros, parse_duration_millis,
+    parse_ipv4, parse_ipv6, parse_mac, parse_number, parse_signed, parse_string, Record, RecordSet,
+    Value,
+};
+use anyhow::Context as _;
+use parking_lot::{Mutex, RwLock, RwLockReadGuard};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::iter::Iterator;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const IPFIX_SAMPLING_INTERVAL: u16 = 34;
+const IPFIX_SAMPLER_RANDOM_INTERVAL: u16 = 50;
+const IPFIX_SAMPLING_PACKET_INTERVAL: u16 = 305;
+const IPFIX_SAMPLING_PACKET_SPACE: u16 = 306;
+
+/// Number of previous layouts kept per template, see [`Session::template_history`].
+pub const TEMPLATE_HISTORY: usize = 4;
+/// Default maximum number of fields of a template.
+pub const DEFAULT_MAX_TEMPLATE_FIELDS: usize = 128;
+/// Largest data record fitting into a message, the message and set headers take 20 bytes.
+const MAX_RECORD_LENGTH: usize = u16::MAX as usize - 20;
+/// Field length announcing a variable length field.
+const VARIABLE_LENGTH: u16 = u16::MAX;
+
+/// Compiles a template into a plan once when it is registered, the plan is
+/// passed to [`Parser::parse_planned`] for every record of the template.
+pub trait Compile {
+    type Plan: Send + Sync;
+
+    fn compile(&self, fields: &[FieldSpecifier]) -> Self::Plan;
+}
+
+/// Decodes the records of data sets.
+///
+/// The output may borrow the data of the set but never the parser, e.g.
+/// [`FieldParser`] outputs a [`RecordSet`] referencing the packet while the
+/// flows of [`IpfixParser`](crate::produce::IpfixParser) are owned.
+pub trait Parser: Compile {
+    type Output<'a>;
+
+    fn parse<'a>(&self, fields: &[FieldSpecifier], set: &DataSet<'a>) -> Option<Self::Output<'a>>;
+
+    /// Like [`Parser::parse`] for a data set of the observation domain `domain_id`,
+    /// used by the [`Session`] to give parsers access to the [`OptionsContext`].
+    fn parse_in_domain<'a>(
+        &self,
+        domain_id: u32,
+        fields: &[FieldSpecifier],
+        set: &DataSet<'a>,
+    ) -> Option<Self::Output<'a>> {
+        let _ = domain_id;
+        self.parse(fields, set)
+    }
+
+    /// Like [`Parser::parse_in_domain`] with the `plan` compiled for `fields`.
+    fn parse_planned<'a>(
+        &self,
+        plan: &Self::Plan,
+        domain_id: u32,
+        fields: &[FieldSpecifier],
+        set: &DataSet<'a>,
+    ) -> Option<Self::Output<'a>> {
+        let _ = plan;
+        self.parse_in_domain(domain_id, fields, set)
+    }
+
+    /// Returns whether `output`, decoded with `fields`, fails the `checks`.
+    ///
+    /// Parsers should flag suspect outputs, e.g. with [`Fluss::suspect`](crate::fluss::Fluss::suspect).
+    /// By default no output is suspect.
+    fn check_sanity(
+        &self,
+        checks: &SanityChecks,
+        fields: &[FieldSpecifier],
+        output: &mut Self::Output<'_>,
+    ) -> bool {
+        let _ = (checks, fields, output);
+        false
+    }
+}
+
+/// Offsets of the fields of a template within its records, resolved to a
+/// parser specific target.
+#[derive(Debug, Clone)]
+pub struct DecodePlan<T> {
+    entries: Vec<PlanEntry<T>>,
+    // `None` if the template has variable length fields, their offsets differ per record
+    record_length: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct PlanEntry<T> {
+    field: FieldSpecifier,
+    offset: usize,
+    target: T,
+}
+
+impl<T> DecodePlan<T> {
+    /// Resolves the target of every field, fields without a target are skipped.
+    pub fn new(
+        fields: &[FieldSpecifier],
+        mut resolve: impl FnMut(&FieldSpecifier) -> Option<T>,
+    ) -> Self {
+        if fields.iter().any(|field| field.length == VARIABLE_LENGTH) {
+            return Self {
+                entries: Vec::new(),
+                record_length: None,
+            };
+        }
+
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        for field in fields {
+            if let Some(target) = resolve(field) {
+                entries.push(PlanEntry {
+                    field: *field,
+                    offset,
+                    target,
+                });
+            }
+            offset += field.length as usize;
+        }
+
+        Self {
+            entries,
+            record_length: Some(offset),
+        }
+    }
+
+    /// Returns the data and target of all resolved fields of the record `set`.
+    ///
+    /// Returns `None` if the template has variable length fields or the record
+    /// is too short, the record has to be read field by field then.
+    pub fn fields<'p, 'a: 'p>(
+        &'p self,
+        set: &DataSet<'a>,
+    ) -> Option<impl Iterator<Item = (&'p FieldSpecifier, &'a [u8], &'p T)> + 'p> {
+        let data = set.data;
+        if data.len() < self.record_length? {
+            return None;
+        }
+
+        Some(self.entries.iter().map(move |entry| {
+            let end = entry.offset + entry.field.length as usize;
+            (&entry.field, &data[entry.offset..end], &entry.target)
+        }))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionEvent {
+    /// The sequence number of a packet did not match the expected sequence number,
+    /// data records were lost or reordered.
+    SequenceGap {
+        expected: u32,
+        got: u32,
+        domain_id: u32,
+    },
+    /// A template was not registered, data sets of the template are skipped.
+    TemplateRejected {
+        domain_id: u32,
+        template_id: u16,
+        error: TemplateError,
+    },
+    /// Many records of a template were suspect shortly after its layout
+    /// changed, see [`SanityChecks`].
+    TemplateFlapping {
+        domain_id: u32,
+        template_id: u16,
+        version: u32,
+        suspect: u64,
+        /// Records of the template are dropped until it is announced again.
+        quarantined: bool,
+    },
+}
+
+/// Reasons why a template is rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    /// A fixed length field has a length of zero.
+    ZeroLength { field: u16 },
+    /// The records of the template do not fit into a message.
+    RecordTooLong(usize),
+    /// The template has more fields than allowed.
+    TooManyFields(usize),
+    /// An options template has no scope fields or more scope fields than fields.
+    ScopeFieldCount { scope_fields: usize, fields: usize },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroLength { field } => write!(f, "field {} has a length of zero", field),
+            Self::RecordTooLong(length) => write!(
+                f,
+                "record length of {} bytes exceeds the maximum of {} bytes",
+                length, MAX_RECORD_LENGTH
+            ),
+            Self::TooManyFields(count) => write!(f, "template has too many fields: {}", count),
+            Self::ScopeFieldCount {
+                scope_fields,
+                fields,
+            } => write!(
+                f,
+                "options template has {} scope fields and {} fields",
+                scope_fields, fields
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionError {
+    /// The export time of the packet deviates more than `skew` from the current time.
+    ClockSkew { export_time: u32, skew: Duration },
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ClockSkew { export_time, skew } => write!(
+                f,
+                "export time {} exceeds the maximum clock skew of {:?}",
+                export_time, skew
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+/// A data record described by an options template.
+#[derive(Debug, Serialize)]
+pub struct OptionsRecord<'a> {
+    pub template_id: u16,
+    pub scope: Vec<Record<'a>>,
+    pub options: Vec<Record<'a>>,
+}
+
+impl<'a> OptionsRecord<'a> {
+    /// Returns the value of the scope or option field `id`.
+    pub fn get(&self, id: u16) -> Option<&Value<'a>> {
+        self.scope
+            .iter()
+            .chain(self.options.iter())
+            .find(|record| record.id == id)
+            .map(|record| &record.value)
+    }
+
+    /// Returns the sampling interval announced by the record, 1 in `interval` packets is sampled.
+    pub fn sampling_interval(&self) -> Option<u64> {
+        let value = |id| self.get(id).and_then(|value| value.as_u64());
+
+        let interval = match (
+            value(IPFIX_SAMPLING_PACKET_INTERVAL),
+            value(IPFIX_SAMPLING_PACKET_SPACE),
+        ) {
+            // `interval` packets are selected, the following `space` packets are skipped
+            (Some(interval), Some(space)) if interval > 0 => (interval + space) / interval,
+            _ => value(IPFIX_SAMPLING_INTERVAL).or_else(|| value(IPFIX_SAMPLER_RANDOM_INTERVAL))?,
+        };
+
+        Some(interval.max(1))
+    }
+
+    pub fn into_owned(self) -> OptionsRecord<'static> {
+        OptionsRecord {
+            template_id: self.template_id,
+            scope: self.scope.into_iter().map(Record::into_owned).collect(),
+            options: self.options.into_iter().map(Record::into_owned).collect(),
+        }
+    }
+}
+
+/// Options announced through options records, per observation domain.
+///
+/// The context is shared between a [`Session`], which records the options,
+/// and its parser, which can use them to complete flow records.
+/// Values of later records replace the values of earlier records.
+#[derive(Debug, Clone, Default)]
+pub struct OptionsContext(Arc<RwLock<HashMap<u32, OptionsRecord<'static>>>>);
+
+impl OptionsContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the latest value of the scope or option field `id` of the domain.
+    pub fn get(&self, domain_id: u32, id: u16) -> Option<Value<'static>> {
+        self.0
+            .read()
+            .get(&domain_id)
+            .and_then(|record| record.get(id))
+            .cloned()
+    }
+
+    /// Returns the sampling interval announced for the domain.
+    pub fn sampling_interval(&self, domain_id: u32) -> Option<u64> {
+        self.0
+            .read()
+            .get(&domain_id)
+            .and_then(|record| record.sampling_interval())
+    }
+
+    fn update(&self, domain_id: u32, record: &OptionsRecord) {
+        let mut domains = self.0.write();
+        let stored = domains.entry(domain_id).or_insert_with(|| OptionsRecord {
+            template_id: record.template_id,
+            scope: Vec::new(),
+            options: Vec::new(),
+        });
+
+        stored.template_id = record.template_id;
+        stored.scope = record
+            .scope
+            .iter()
+            .cloned()
+            .map(Record::into_owned)
+            .collect();
+        for option in &record.options {
+            let option = option.clone().into_owned();
+            match stored
+                .options
+                .iter_mut()
+                .find(|stored| stored.id == option.id)
+            {
+                Some(stored) => *stored = option,
+                None => stored.options.push(option),
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Decoded<'a, T> {
+    Flow(T),
+    Options(OptionsRecord<'a>),
+}
+
+struct Template<T> {
+    fields: Vec<FieldSpecifier>,
+    // only options templates have scope fields
+    scope_field_count: usize,
+    last_seen: SystemTime,
+    // number of records decoded with this template
+    records: AtomicU64,
+    // incremented whenever the layout of the template id changes
+    version: u32,
+    // last change of the layout, not set for the first layout
+    changed: Option<SystemTime>,
+    // number of suspect records since the layout changed
+    suspect: AtomicU64,
+    // set once the template was reported as flapping
+    flapping: AtomicBool,
+    // records are dropped until the template is announced again
+    quarantined: AtomicBool,
+    plan: Arc<TemplatePlan<T>>,
+}
+
+impl<T> Clone for Template<T> {
+    fn clone(&self) -> Self {
+        Self {
+            fields: self.fields.clone(),
+            scope_field_count: self.scope_field_count,
+            last_seen: self.last_seen,
+            records: AtomicU64::new(self.records.load(Ordering::Relaxed)),
+            version: self.version,
+            changed: self.changed,
+            suspect: AtomicU64::new(self.suspect.load(Ordering::Relaxed)),
+            flapping: AtomicBool::new(self.flapping.load(Ordering::Relaxed)),
+            quarantined: AtomicBool::new(self.quarantined.load(Ordering::Relaxed)),
+            plan: Arc::clone(&self.plan),
+        }
+    }
+}
+
+// templates by observation domain and template id
+type TemplateMap<T> = HashMap<(u32, u16), Template<T>>;
+
+/// Plan of a template, compiled by the parser of its records.
+enum TemplatePlan<T> {
+    Data(T),
+    Options(<FieldParser as Compile>::Plan),
+}
+
+/// A template of an observation domain, see [`Session::dump_templates_json`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DomainTemplate {
+    pub domain_id: u32,
+    /// Only options templates have scope fields.
+    #[serde(default)]
+    pub scope_field_count: usize,
+    #[serde(flatten)]
+    pub template: TemplateRecord,
+}
+
+/// Snapshot of a template and its usage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateStats {
+    pub domain_id: u32,
+    pub id: u16,
+    pub fields: Vec<FieldSpecifier>,
+    pub scope_field_count: usize,
+    /// Last time the template was announced by the exporter.
+    pub last_seen: SystemTime,
+    /// Number of data records decoded with the template.
+    pub records: u64,
+    /// Version of the layout, incremented whenever the layout of the template id changes.
+    #[serde(default)]
+    pub version: u32,
+    /// Number of suspect records since the layout changed, see [`SanityChecks`].
+    #[serde(default)]
+    pub suspect_records: u64,
+}
+
+/// A previous layout of a template, see [`Session::template_history`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateVersion {
+    pub version: u32,
+    pub fields: Vec<FieldSpecifier>,
+    pub scope_field_count: usize,
+    /// Time the layout was replaced or withdrawn.
+    pub replaced: SystemTime,
+}
+
+pub struct Session<P: Compile> {
+    templates: RwLock<TemplateMap<P::Plan>>,
+    // next expected sequence number per observation domain
+    sequences: Mutex<HashMap<u32, u32>>,
+    events: Mutex<Vec<SessionEvent>>,
+    max_clock_skew: Duration,
+    allowed_templates: Option<HashSet<u16>>,
+    max_template_fields: usize,
+    // templates which were rejected, their data sets are skipped without further notice
+    rejected_templates: Mutex<HashSet<(u32, u16)>>,
+    // previous layouts by observation domain and template id, oldest first
+    history: Mutex<HashMap<(u32, u16), Vec<TemplateVersion>>>,
+    sanity: SanityChecks,
+    parser: P,
+    options_parser: FieldParser,
+    options: OptionsContext,
+}
+
+impl<P: Compile> Session<P> {
+    pub fn new(parser: P) -> Self {
+        Self {
+            templates: RwLock::new(HashMap::new()),
+            sequences: Mutex::new(HashMap::new()),
+            events: Mutex::new(Vec::new()),
+            max_clock_skew: Duration::MAX,
+            allowed_templates: None,
+            max_template_fields: DEFAULT_MAX_TEMPLATE_FIELDS,
+            rejected_templates: Mutex::new(HashSet::new()),
+            history: Mutex::new(HashMap::new()),
+            sanity: SanityChecks::default(),
+            parser,
+            options_parser: FieldParser::builder().with_default_fields().build(),
+            options: OptionsContext::new(),
+        }
+    }
+
+    /// Records announced options into `options`, which may be shared with the parser.
+    pub fn with_options(mut self, options: OptionsContext) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the options announced through options records.
+    pub fn options(&self) -> &OptionsContext {
+        &self.options
+    }
+
+    /// Rejects packets with an export time deviating more than `skew`
+    /// from the current system time, by default this check is disabled.
+    pub fn with_max_clock_skew(mut self, skew: Duration) -> Self {
+        self.max_clock_skew = skew;
+        self
+    }
+
+    /// Rejects templates with more than `count` fields, defaults to [`DEFAULT_MAX_TEMPLATE_FIELDS`].
+    pub fn with_max_template_fields(mut self, count: usize) -> Self {
+        self.max_template_fields = count;
+        self
+    }
+
+    /// Only decodes data sets of the templates `ids`, data sets of other templates are skipped.
+    pub fn with_allowed_templates(mut self, ids: impl IntoIterator<Item = u16>) -> Self {
+        self.allowed_templates = Some(ids.into_iter().collect());
+        self
+    }
+
+    /// Flags decoded flows with the heuristics of `checks`, by default all heuristics are disabled.
+    pub fn with_sanity_checks(mut self, checks: SanityChecks) -> Self {
+        self.sanity = checks;
+        self
+    }
+
+    /// Drains all events which occurred since the last call.
+    pub fn events(&self) -> impl Iterator<Item = SessionEvent> {
+        std::mem::take(&mut *self.events.lock()).into_iter()
+    }
+
+    pub fn get_parser(&self) -> &P {
+        &self.parser
+    }
+
+    /// Returns the name of the information element `id`.
+    pub fn field_name(&self, id: u16) -> Option<&str> {
+        self.options_parser.field_name(id)
+    }
+
+    /// Converts `record_set` into a JSON object keyed by field names,
+    /// fields without a known name are keyed by their id.
+    pub fn to_json(&self, record_set: &RecordSet) -> serde_json::Value {
+        record_set
+            .records
+            .iter()
+            .map(|record| {
+                let name = match self.field_name(record.id) {
+                    Some(name) => name.to_owned(),
+                    None => record.id.to_string(),
+                };
+                (name, serde_json::Value::from(&record.value))
+            })
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    }
+
+    /// Returns a read only view of all currently known templates.
+    ///
+    /// The view holds a read lock on the templates, new templates can
+    /// not be registered until it is dropped.
+    pub fn templates(&self) -> Templates<'_, P> {
+        Templates(self.templates.read())
+    }
+
+    pub fn has_template(&self, domain_id: u32, id: u16) -> bool {
+        self.templates.read().contains_key(&(domain_id, id))
+    }
+
+    pub fn template_field_count(&self, domain_id: u32, id: u16) -> Option<usize> {
+        self.templates
+            .read()
+            .get(&(domain_id, id))
+            .map(|template| template.fields.len())
+    }
+
+    /// Returns a snapshot of all templates and how often they were used.
+    pub fn template_stats(&self) -> Vec<TemplateStats> {
+        self.templates
+            .read()
+            .iter()
+            .map(|(&(domain_id, id), template)| TemplateStats {
+                domain_id,
+                id,
+                fields: template.fields.clone(),
+                scope_field_count: template.scope_field_count,
+                last_seen: template.last_seen,
+                records: template.records.load(Ordering::Relaxed),
+                version: template.version,
+                suspect_records: template.suspect.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    /// Returns the previous layouts of a template, oldest first.
+    ///
+    /// Up to [`TEMPLATE_HISTORY`] layouts are kept after they were replaced or
+    /// withdrawn, the current layout is not part of the history.
+    pub fn template_history(&self, domain_id: u32, id: u16) -> Vec<TemplateVersion> {
+        self.history
+            .lock()
+            .get(&(domain_id, id))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Returns all templates ordered by observation domain and template id.
+    pub fn dump_templates(&self) -> Vec<DomainTemplate> {
+        let mut templates = self
+            .templates
+            .read()
+            .iter()
+            .map(|(&(domain_id, id), template)| DomainTemplate {
+                domain_id,
+                scope_field_count: template.scope_field_count,
+                template: TemplateRecord {
+                    id,
+                    fields: template.fields.clone(),
+                },
+            })
+            .collect::<Vec<_>>();
+        templates.sort_by_key(|template| (template.domain_id, template.template.id));
+        templates
+    }
+
+    /// Returns all templates as pretty printed JSON, see [`Session::load_templates_json`].
+    pub fn dump_templates_json(&self) -> String {
+        serde_json::to_string_pretty(&self.dump_templates()).expect("templates can be serialized")
+    }
+
+    /// Registers the templates of a [`Session::dump_templates_json`] dump,
+    /// e.g. to decode captured data sets without their templates.
+    ///
+    /// The templates are validated like announced templates.
+    pub fn load_templates_json(&self, json: &str) -> anyhow::Result<()> {
+        let templates: Vec<DomainTemplate> =
+            serde_json::from_str(json).context("invalid template dump")?;
+        for template in templates {
+            if template.scope_field_count > template.template.fields.len() {
+                anyhow::bail!(
+                    "template {} has more scope fields than fields",
+                    template.template.id
+                );
+            }
+            // dumps of data templates have no scope fields
+            let scope_field_count = Some(template.scope_field_count).filter(|&count| count > 0);
+            self.insert_template(
+                template.domain_id,
+                template.template.id,
+                &template.template.fields,
+                scope_field_count,
+            );
+        }
+        Ok(())
+    }
+
+    fn validate_template(
+        &self,
+        fields: &[FieldSpecifier],
+        scope_field_count: Option<usize>,
+    ) -> Result<(), TemplateError> {
+        if fields.len() > self.max_template_fields {
+            return Err(TemplateError::TooManyFields(fields.len()));
+        }
+
+        // the scope fields are split off every record of an options template
+        if let Some(count) = scope_field_count {
+            if count == 0 || count > fields.len() {
+                return Err(TemplateError::ScopeFieldCount {
+                    scope_fields: count,
+                    fields: fields.len(),
+                });
+            }
+        }
+
+        if let Some(field) = fields.iter().find(|field| field.length == 0) {
+            return Err(TemplateError::ZeroLength { field: field.id });
+        }
+
+        // variable length fields take at least their one byte length prefix
+        let length = fields
+            .iter()
+            .map(|field| match field.length {
+                VARIABLE_LENGTH => 1,
+                length => length as usize,
+            })
+            .sum::<usize>();
+        if length > MAX_RECORD_LENGTH {
+            return Err(TemplateError::RecordTooLong(length));
+        }
+
+        Ok(())
+    }
+
+    /// Registers a template, options templates have a scope field count.
+    fn insert_template(
+        &self,
+        domain_id: u32,
+        id: u16,
+        fields: &[FieldSpecifier],
+        scope_field_count: Option<usize>,
+    ) {
+        let mut templates = self.templates.write();
+        let was_rejected = self.rejected_templates.lock().remove(&(domain_id, id));
+        let unchanged = templates
+            .get(&(domain_id, id))
+            .is_some_and(|template| template.fields == fields);
+
+        if fields.is_empty() {
+            // a template without fields withdraws the template (RFC 7011 8.1)
+            tracing::trace!(domain_id, template = id, "template withdrawn");
+            if let Some(template) = templates.remove(&(domain_id, id)) {
+                self.archive_template(domain_id, id, &template);
+            }
+            return;
+        }
+
+        if let Err(error) = self.validate_template(fields, scope_field_count) {
+            // only reported once, refreshes of the template are rejected silently
+            if !was_rejected {
+                tracing::warn!(domain_id, template = id, %error, "template rejected");
+                self.events.lock().push(SessionEvent::TemplateRejected {
+                    domain_id,
+                    template_id: id,
+                    error,
+                });
+            }
+            if let Some(template) = templates.remove(&(domain_id, id)) {
+                self.archive_template(domain_id, id, &template);
+            }
+            self.rejected_templates.lock().insert((domain_id, id));
+            return;
+        }
+
+        if !unchanged {
+            for field in unexpected_lengths(fields) {
+                tracing::warn!(
+                    domain_id,
+                    template = id,
+                    field = field.id,
+                    length = field.length,
+                    "unexpected length of field"
+                );
+            }
+        }
+
+        let scope_field_count = scope_field_count.unwrap_or(0);
+
+        // template refreshes keep counting records and the compiled plan
+        let existing = templates.get(&(domain_id, id));
+        let records = existing.map_or(0, |template| template.records.load(Ordering::Relaxed));
+        let refreshed = existing
+            .filter(|template| unchanged && template.scope_field_count == scope_field_count);
+        let plan = match refreshed {
+            Some(template) => Arc::clone(&template.plan),
+            None => Arc::new(match scope_field_count {
+                0 => TemplatePlan::Data(self.parser.compile(fields)),
+                _ => TemplatePlan::Options(self.options_parser.compile(fields)),
+            }),
+        };
+
+        // refreshes keep the version and the suspicion, only the quarantine ends
+        let (version, changed, suspect, flapping) = match (refreshed, existing) {
+            (Some(template), _) => (
+                template.version,
+                template.changed,
+                template.suspect.load(Ordering::Relaxed),
+                template.flapping.load(Ordering::Relaxed),
+            ),
+            (None, Some(template)) => {
+                // records in flight are decoded with the new layout
+                tracing::warn!(
+                    domain_id,
+                    template = id,
+                    version = template.version + 1,
+                    "template layout changed without withdrawing the template"
+                );
+                self.archive_template(domain_id, id, template);
+                (template.version + 1, Some(clock::now()), 0, false)
+            }
+            (None, None) => {
+                let version = self
+                    .history
+                    .lock()
+                    .get(&(domain_id, id))
+                    .and_then(|history| history.last())
+                    .map_or(1, |previous| previous.version + 1);
+                (version, None, 0, false)
+            }
+        };
+
+        templates.insert(
+            (domain_id, id),
+            Template {
+                fields: fields.to_vec(),
+                scope_field_count,
+                last_seen: clock::now(),
+                records: AtomicU64::new(records),
+                version,
+                changed,
+                suspect: AtomicU64::new(suspect),
+                flapping: AtomicBool::new(flapping),
+                quarantined: AtomicBool::new(false),
+                plan,
+            },
+        );
+    }
+
+    /// Adds the layout of a replaced or withdrawn template to its history.
+    fn archive_template(&self, domain_id: u32, id: u16, template: &Template<P::Plan>) {
+        let mut history = self.history.lock();
+        let versions = history.entry((domain_id, id)).or_default();
+        if versions.len() == TEMPLATE_HISTORY {
+            versions.remove(0);
+        }
+        versions.push(TemplateVersion {
+            version: template.version,
+            fields: template.fields.clone(),
+            scope_field_count: template.scope_field_count,
+            replaced: clock::now(),
+        });
+    }
+}
+
+impl<P: Compile + Default> Default for Session<P> {
+    fn default() -> Self {
+        Self::new(P::default())
+    }
+}
+
+/// Clones the session with a snapshot of its templates and sequence numbers.
+///
+/// Both sessions diverge independently afterwards, templates learned by one
+/// are not known to the other. Pending events are not cloned and the
+/// [`OptionsContext`] stays shared, just like it is shared with a cloned parser.
+impl<P: Compile + Clone> Clone for Session<P> {
+    fn clone(&self) -> Self {
+        Self {
+            templates: RwLock::new(self.templates.read().clone()),
+            sequences: Mutex::new(self.sequences.lock().clone()),
+            events: Mutex::new(Vec::new()),
+            max_clock_skew: self.max_clock_skew,
+            allowed_templates: self.allowed_templates.clone(),
+            max_template_fields: self.max_template_fields,
+            rejected_templates: Mutex::new(self.rejected_templates.lock().clone()),
+            history: Mutex::new(self.history.lock().clone()),
+            sanity: self.sanity.clone(),
+            parser: self.parser.clone(),
+            options_parser: self.options_parser.clone(),
+            options: self.options.clone(),
+        }
+    }
+}
+
+/// Encoded length of the abstract data type of well known information elements.
+enum ElementLength {
+    /// Addresses and timestamps have a fixed length.
+    Exact(u16),
+    /// Numbers may use reduced-size encoding (RFC 7011 6.2).
+    AtMost(u16),
+}
+
+fn element_length(id: u16) -> Option<ElementLength> {
+    use ElementLength::*;
+
+    Some(match id {
+        // addresses
+        8 | 12 | 15 | 18 | 225 | 226 => Exact(4),
+        27 | 28 | 62 | 63 | 281 | 282 => Exact(16),
+        56 | 57 | 80 | 81 => Exact(6),
+        // timestamps
+        150 | 151 => Exact(4),
+        152..=157 => Exact(8),
+        // numbers
+        1 | 2 | 3 | 85 | 86 | 231 | 232 | 298 | 299 => AtMost(8),
+        10 | 14 | 34 | 50 | 305 | 306 => AtMost(4),
+        6 | 7 | 11 | 58 | 59 | 256 => AtMost(2),
+        4 | 5 | 9 | 13 | 29 | 30 | 136 => AtMost(1),
+        _ => return None,
+    })
+}
+
+/// Returns the fields of well known elements with a length not matching their type.
+///
+/// The template is still used, the affected fields may decode to unexpected values.
+fn unexpected_lengths(fields: &[FieldSpecifier]) -> impl Iterator<Item = &FieldSpecifier> {
+    let iana_fields = fields.iter().filter(|field| field.enterprise_id.is_none());
+    iana_fields.filter(|field| match element_length(field.id) {
+        Some(ElementLength::Exact(length)) => field.length != length,
+        Some(ElementLength::AtMost(length)) => field.length > length,
+        None => false,
+    })
+}
+
+/// Splits the data of a set into its records and returns the length of the padding.
+///
+/// The records of templates with variable length fields differ in length,
+/// they are split by reading the fields of every record.
+fn split_records<'a>(fields: &[FieldSpecifier], data: &'a [u8]) -> (Vec<&'a [u8]>, usize) {
+    if !fields.iter().any(|field| field.length == VARIABLE_LENGTH) {
+        let length = fields.iter().map(|f| f.length as usize).sum::<usize>();
+        let records = data.chunks_exact(length.max(1));
+        let padding = records.remainder().len();
+        return (records.collect(), padding);
+    }
+
+    let mut records = Vec::new();
+    let mut remaining = data;
+    'records: while !remaining.is_empty() {
+        let mut input = remaining;
+        for field in fields {
+            match field.read(input) {
+                Ok((rest, _)) => input = rest,
+                // too short for another record
+                Err(_) => break 'records,
+            }
+        }
+
+        let (record, rest) = remaining.split_at(remaining.len() - input.len());
+        records.push(record);
+        remaining = rest;
+    }
+
+    (records, remaining.len())
+}
+
+pub struct Templates<'a, P: Compile>(RwLockReadGuard<'a, TemplateMap<P::Plan>>);
+
+impl<'a, P: Compile> Templates<'a, P> {
+    /// Iterates over `(observation_domain_id, template_id, fields)` of all templates.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, u16, &[FieldSpecifier])> {
+        self.0
+            .iter()
+            .map(|(&(domain_id, id), template)| (domain_id, id, template.fields.as_slice()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<P: Parser> Session<P> {
+    /// Decodes the flows of a [`Packet`](super::Packet) or an [`OwnedPacket`](super::OwnedPacket).
+    ///
+    /// The flows only borrow from the packet, never from the session. Owned
+    /// outputs like [`Fluss`](crate::fluss::Fluss) can be sent to other tasks
+    /// while the session keeps decoding.
+    pub fn parse<'a, M: Message>(&self, packet: &'a M) -> Result<Vec<P::Output<'a>>, SessionError> {
+        let mut flows = Vec::new();
+        self.for_each_decoded(packet, |decoded| {
+            if let Decoded::Flow(flow) = decoded {
+                flows.push(flow);
+            }
+        })?;
+        Ok(flows)
+    }
+
+    /// Like [`Session::parse`] but additionally yields records of options templates.
+    pub fn parse_with_options<'a, M: Message>(
+        &self,
+        packet: &'a M,
+    ) -> Result<Vec<Decoded<'a, P::Output<'a>>>, SessionError> {
+        let mut decoded = Vec::new();
+        self.for_each_decoded(packet, |record| decoded.push(record))?;
+        Ok(decoded)
+    }
+
+    /// Like [`Session::parse_with_options`] but passes every record to `f`
+    /// instead of collecting them.
+    ///
+    /// The records of a data set are passed once the set is decoded. All
+    /// templates of the message are registered before its data sets are
+    /// decoded, data sets may precede the template set defining them.
+    pub fn for_each_decoded<'a, M, F>(&self, packet: &'a M, mut f: F) -> Result<(), SessionError>
+    where
+        M: Message,
+        F: FnMut(Decoded<'a, P::Output<'a>>),
+    {
+        use super::parser::SetRef::*;
+        self.check_export_time(packet.export_time())?;
+
+        let domain_id = packet.observation_domain_id();
+        self.check_sequence(domain_id, packet.sequence_number());
+
+        // RFC 7011 does not require template sets to come first
+        for set in packet.set_refs() {
+            match set {
+                TemplateSet(records) => self.add_records(domain_id, records),
+                OptionsTemplateSet(records) => self.add_options_records(domain_id, records),
+                DataSet(_) => (),
+            }
+        }
+
+        for set in packet.set_refs() {
+            if let DataSet(data) = set {
+                self.parse_data_set(domain_id, &data)
+                    .into_iter()
+                    .for_each(&mut f);
+            }
+        }
+        Ok(())
+    }
+
+    fn check_export_time(&self, export_time: u32) -> Result<(), SessionError> {
+        if self.max_clock_skew == Duration::MAX {
+            return Ok(());
+        }
+
+        let export_time_since_epoch = Duration::from_secs(export_time.into());
+        let now = clock::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+
+        let skew = match now > export_time_since_epoch {
+            true => now - export_time_since_epoch,
+            false => export_time_since_epoch - now,
+        };
+
+        if skew > self.max_clock_skew {
+            tracing::warn!(export_time, ?skew, "export time exceeds maximum clock skew");
+            return Err(SessionError::ClockSkew {
+                export_time,
+                skew: self.max_clock_skew,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn check_sequence(&self, domain_id: u32, got: u32) {
+        // the sequence number counts data records, not packets,
+        // the expected value is advanced by `parse_data_set`
+        let mut sequences = self.sequences.lock();
+        if let Some(&expected) = sequences.get(&domain_id) {
+            if expected != got {
+                tracing::warn!(expected, got, domain_id, "sequence number gap detected");
+                self.events.lock().push(SessionEvent::SequenceGap {
+                    expected,
+                    got,
+                    domain_id,
+                });
+            }
+        }
+        sequences.insert(domain_id, got);
+    }
+
+    fn add_records(&self, domain_id: u32, records: &[TemplateRecord]) {
+        for record in records {
+            tracing::trace!(
+                domain_id,
+                template = record.id,
+                fields = ?record.fields,
+                "template registered"
+            );
+            self.insert_template(domain_id, record.id, &record.fields, None);
+        }
+    }
+
+    fn add_options_records(&self, domain_id: u32, records: &[OptionsTemplateRecord]) {
+        for record in records {
+            tracing::trace!(
+                domain_id,
+                template = record.id,
+                scope_field_count = record.scope_field_count,
+                fields = ?record.fields,
+                "options template registered"
+            );
+            self.insert_template(
+                domain_id,
+                record.id,
+                &record.fields,
+                Some(record.scope_field_count as usize),
+            );
+        }
+    }
+
+    pub fn parse_data_set<'a>(
+        &self,
+        domain_id: u32,
+        set: &DataSet<'a>,
+    ) -> Vec<Decoded<'a, P::Output<'a>>> {
+        let templates = self.templates.read();
+        let template = match templates.get(&(domain_id, set.id)) {
+            Some(v) => v,
+            None => {
+                if !self
+                    .rejected_templates
+                    .lock()
+                    .contains(&(domain_id, set.id))
+                {
+                    tracing::debug!(domain_id, template = set.id, "data set of unknown template");
+                }
+                // the records can not be counted without the template, the
+                // sequence number of the next message is not checked
+                self.sequences.lock().remove(&domain_id);
+                return vec![];
+            }
+        };
+        let fields = &template.fields;
+
+        // the set may be padded, the padding is shorter than a record
+        let (records, padding) = split_records(fields, set.data);
+        if let Some(expected) = self.sequences.lock().get_mut(&domain_id) {
+            *expected = expected.wrapping_add(records.len() as u32);
+        }
+
+        if let Some(allowed) = &self.allowed_templates {
+            if !allowed.contains(&set.id) {
+                tracing::trace!(
+                    template = set.id,
+                    "skipping data set of a template not allowed"
+                );
+                return vec![];
+            }
+        }
+
+        if template.quarantined.load(Ordering::Relaxed) {
+            tracing::trace!(
+                template = set.id,
+                "skipping data set of a quarantined template"
+            );
+            return vec![];
+        }
+
+        if padding > 0 {
+            tracing::trace!(template = set.id, padding, "skipping data set padding");
+        }
+
+        // collected while holding the read lock, the records must not borrow the session
+        let check_sanity = self.sanity.is_enabled();
+        let mut decoded = records
+            .into_iter()
+            .filter_map(move |data| {
+                let _span = tracing::trace_span!("record", template = set.id).entered();
+                let set = DataSet { id: set.id, data };
+                match &*template.plan {
+                    TemplatePlan::Data(plan) => self
+                        .parser
+                        .parse_planned(plan, domain_id, fields, &set)
+                        .map(|mut flow| {
+                            if check_sanity
+                                && self.parser.check_sanity(&self.sanity, fields, &mut flow)
+                            {
+                                template.suspect.fetch_add(1, Ordering::Relaxed);
+                            }
+                            Decoded::Flow(flow)
+                        }),
+                    TemplatePlan::Options(plan) => self
+                        .options_parser
+                        .parse_planned(plan, domain_id, fields, &set)
+                        .map(|mut record_set| {
+                            let options = record_set.records.split_off(template.scope_field_count);
+                            let record = OptionsRecord {
+                                template_id: set.id,
+                                scope: record_set.records,
+                                options,
+                            };
+                            self.options.update(domain_id, &record);
+                            Decoded::Options(record)
+                        }),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        template
+            .records
+            .fetch_add(decoded.len() as u64, Ordering::Relaxed);
+
+        if check_sanity && self.check_flapping(domain_id, set.id, template) {
+            decoded.clear();
+        }
+
+        decoded
+    }
+
+    /// Reports the template once it produced too many suspect records shortly
+    /// after its layout changed, returns whether its records are dropped.
+    fn check_flapping(&self, domain_id: u32, id: u16, template: &Template<P::Plan>) -> bool {
+        let suspect = template.suspect.load(Ordering::Relaxed);
+        let recently_changed = template
+            .changed
+            .and_then(|changed| clock::now().duration_since(changed).ok())
+            .is_some_and(|elapsed| elapsed <= self.sanity.window());
+        if suspect < self.sanity.threshold().max(1)
+            || !recently_changed
+            || template.flapping.swap(true, Ordering::Relaxed)
+        {
+            return template.quarantined.load(Ordering::Relaxed);
+        }
+
+        let quarantined = self.sanity.quarantine();
+        template.quarantined.store(quarantined, Ordering::Relaxed);
+        tracing::debug!(
+            domain_id,
+            template = id,
+            suspect,
+            quarantined,
+            "template flapping"
+        );
+        self.events.lock().push(SessionEvent::TemplateFlapping {
+            domain_id,
+            template_id: id,
+            version: template.version,
+            suspect,
+            quarantined,
+        });
+        quarantined
+    }
+}
+
+pub type FieldExtractor = fn(&[u8]) -> Value;
+
+/// Decodes the data of a field, with a built-in function or a plugin.
+#[derive(Clone)]
+pub enum Extractor {
+    Fn(FieldExtractor),
+    #[cfg(feature = "plugins")]
+    Plugin(Arc<super::plugin::Plugin>),
+}
+
+impl Extractor {
+    pub fn extract<'a>(&self, data: &'a [u8]) -> Value<'a> {
+        match self {
+            Self::Fn(extractor) => extractor(data),
+            #[cfg(feature = "plugins")]
+            Self::Plugin(plugin) => plugin.extract(data),
+        }
+    }
+}
+
+impl From<FieldExtractor> for Extractor {
+    fn from(extractor: FieldExtractor) -> Self {
+        Self::Fn(extractor)
+    }
+}
+
+#[derive(Clone)]
+struct NameFn(String, Extractor);
+
+/// Receives the id, name and value of every decoded field,
+/// the name of fields without a registered parser is empty.
+pub type DebugCallback = Box<dyn Fn(u16, &str, &Value) + Send + Sync>;
+
+pub struct DebugParser<T> {
+    parsers: HashMap<u16, NameFn>,
+    callback: DebugCallback,
+    delegate: T,
+}
+
+impl<T> DebugParser<T> {
+    /// Creates a parser which logs all decoded fields with `tracing`.
+    pub fn new(parser: T) -> Self {
+        Self::with_callback(parser, |id, name, value| match value {
+            Value::Unknown(data) => tracing::info!(field = id, value = ?data, "unknown field"),
+            value => tracing::info!(field = id, name, ?value, "decoded field"),
+        })
+    }
+
+    /// Creates a parser which passes all decoded fields to `callback`.
+    pub fn with_callback(
+        parser: T,
+        callback: impl Fn(u16, &str, &Value) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            parsers: get_default_field_parsers(),
+            callback: Box::new(callback),
+            delegate: parser,
+        }
+    }
+
+    pub fn set_parser(
+        &mut self,
+        id: u16,
+        name: impl Into<String>,
+        extractor: FieldExtractor,
+    ) -> &mut Self {
+        self.parsers
+            .insert(id, NameFn(name.into(), extractor.into()));
+        self
+    }
+
+    fn log_fields(&self, fields: &[FieldSpecifier], set: &DataSet) {
+        for field in set.with_fields(fields) {
+            let (field, data) = match field {
+                Ok(field) => field,
+                Err(err) => {
+                    tracing::debug!(template = set.id, %err, "truncated record");
+                    break;
+                }
+            };
+            match self.parsers.get(&field.id) {
+                Some(NameFn(name, parser)) => {
+                    (self.callback)(field.id, name, &parser.extract(data))
+                }
+                None => (self.callback)(field.id, "", &Value::Unknown(data.into())),
+            }
+        }
+    }
+}
+
+impl<T: Compile> Compile for DebugParser<T> {
+    type Plan = T::Plan;
+
+    fn compile(&self, fields: &[FieldSpecifier]) -> Self::Plan {
+        self.delegate.compile(fields)
+    }
+}
+
+impl<T: Parser> Parser for DebugParser<T> {
+    type Output<'a> = T::Output<'a>;
+
+    fn parse<'a>(&self, fields: &[FieldSpecifier], set: &DataSet<'a>) -> Option<Self::Output<'a>> {
+        self.log_fields(fields, set);
+        self.delegate.parse(fields, set)
+    }
+
+    fn parse_in_domain<'a>(
+        &self,
+        domain_id: u32,
+        fields: &[FieldSpecifier],
+        set: &DataSet<'a>,
+    ) -> Option<Self::Output<'a>> {
+        self.log_fields(fields, set);
+        self.delegate.parse_in_domain(domain_id, fields, set)
+    }
+
+    fn parse_planned<'a>(
+        &self,
+        plan: &Self::Plan,
+        domain_id: u32,
+        fields: &[FieldSpecifier],
+        set: &DataSet<'a>,
+    ) -> Option<Self::Output<'a>> {
+        self.log_fields(fields, set);
+        self.delegate.parse_planned(plan, domain_id, fields, set)
+    }
+
+    fn check_sanity(
+        &self,
+        checks: &SanityChecks,
+        fields: &[FieldSpecifier],
+        output: &mut Self::Output<'_>,
+    ) -> bool {
+        self.delegate.check_sanity(checks, fields, output)
+    }
+}
+
+#[derive(Clone)]
+pub struct FieldParser {
+    parsers: HashMap<u16, NameFn>,
+    // only these fields are decoded if set, all others are skipped
+    selected: Option<HashSet<u16>>,
+}
+
+impl FieldParser {
+    pub fn builder() -> FieldParserBuilder {
+        FieldParserBuilder::new()
+    }
+
+    /// Returns the name of the information element registered for `id`.
+    pub fn field_name(&self, id: u16) -> Option<&str> {
+        self.parsers.get(&id).map(|NameFn(name, _)| name.as_str())
+    }
+
+    /// Lazily decodes the fields of a record, one field at a time.
+    ///
+    /// Fields which are not selected are skipped.
+    /// The iterator ends early if the record is too short for all `fields`.
+    pub fn parse_iter<'p, 'a: 'p>(
+        &'p self,
+        fields: &'p [FieldSpecifier],
+        set: &DataSet<'a>,
+    ) -> impl Iterator<Item = Record<'a>> + 'p {
+        self.read_fields(fields, set)
+            .map_while(|field| match field {
+                Ok(record) => Some(record),
+                Err(err) => {
+                    tracing::trace!(%err, "record too short for field");
+                    None
+                }
+            })
+            .flatten()
+    }
+
+    /// Reads one field after the other, only selected fields are decoded.
+    fn read_fields<'p, 'a: 'p>(
+        &'p self,
+        fields: &'p [FieldSpecifier],
+        set: &DataSet<'a>,
+    ) -> impl Iterator<Item = Result<Option<Record<'a>>, FieldError>> + 'p {
+        set.with_fields(fields).map(move |field| {
+            let (field, data) = field?;
+            let selected = match &self.selected {
+                Some(selected) => selected.contains(&field.id),
+                None => true,
+            };
+            Ok(selected.then(|| self.parse_field(field, data)))
+        })
+    }
+
+    fn parse_field<'a>(&self, field: &FieldSpecifier, data: &'a [u8]) -> Record<'a> {
+        if let Some(NameFn(name, parser)) = self.parsers.get(&field.id) {
+            tracing::trace!(parser = name.as_str(), ?field, ?data, "pre parse");
+            let value = parser.extract(data);
+            tracing::trace!(parser = name.as_str(), ?field, ?value, "post parse");
+
+            Record::new(field.id, value)
+        } else {
+            tracing::trace!(?field, "no parser registered for field");
+            Record::new(field.id, Value::Unknown(data.into()))
+        }
+    }
+}
+
+/// A parser for all fields of the default information elements.
+impl Default for FieldParser {
+    fn default() -> Self {
+        Self::builder().with_default_fields().build()
+    }
+}
+
+/// Resolves the extractors of the selected fields, `None` for fields without a registered parser.
+impl Compile for FieldParser {
+    type Plan = DecodePlan<Option<Extractor>>;
+
+    fn compile(&self, fields: &[FieldSpecifier]) -> Self::Plan {
+        DecodePlan::new(fields, |field| {
+            let selected = match &self.selected {
+                Some(selected) => selected.contains(&field.id),
+                None => true,
+            };
+            selected.then(|| {
+                self.parsers
+                    .get(&field.id)
+                    .map(|NameFn(_, parser)| parser.clone())
+            })
+        })
+    }
+}
+
+impl Parser for FieldParser {
+    type Output<'a> = RecordSet<'a>;
+
+    fn parse<'a>(&self, fields: &[FieldSpecifier], set: &DataSet<'a>) -> Option<Self::Output<'a>> {
+        // incomplete records are dropped, finding the truncation only slices the fields
+        if let Some(Err(err)) = set.with_fields(fields).find(Result::is_err) {
+            tracing::debug!(template = set.id, %err, "dropping truncated record");
+            return None;
+        }
+        Some(RecordSet::new(
+            set.id,
+            self.parse_iter(fields, set).collect(),
+        ))
+    }
+
+    fn parse_planned<'a>(
+        &self,
+        plan: &Self::Plan,
+        _domain_id: u32,
+        fields: &[FieldSpecifier],
+        set: &DataSet<'a>,
+    ) -> Option<Self::Output<'a>> {
+        let planned = match plan.fields(set) {
+            Some(planned) => planned,
+            None => return self.parse(fields, set),
+        };
+
+        let records = planned
+            .map(|(field, data, parser)| match parser {
+                Some(parser) => Record::new(field.id, parser.extract(data)),
+                None => Record::new(field.id, Value::Unknown(data.into())),
+            })
+            .collect();
+        Some(RecordSet::new(set.id, records))
+    }
+}
+
+pub struct FieldParserBuilder {
+    parsers: HashMap<u16, NameFn>,
+    selected: Option<HashSet<u16>>,
+}
+
+impl FieldParserBuilder {
+    fn new() -> Self {
+        Self {
+            parsers: HashMap::new(),
+            selected: None,
+        }
+    }
+
+    /// Registers all IANA information elements, fields which were already
+    /// registered are kept.
+    pub fn with_default_fields(mut self) -> Self {
+        for (id, parser) in get_default_field_parsers() {
+            self.parsers.entry(id).or_insert(parser);
+        }
+        self
+    }
+
+    pub fn with_field(mut self, id: u16, name: impl Into<String>, fe: FieldExtractor) -> Self {
+        self.parsers.insert(id, NameFn(name.into(), fe.into()));
+        self
+    }
+
+    /// Decodes the field `id` with the function `symbol` of the shared library at `path`,
+    /// the field is named after the function.
+    ///
+    /// See [`plugin`](super::plugin) for the signature of the function.
+    #[cfg(feature = "plugins")]
+    pub fn with_plugin(mut self, id: u16, path: &str, symbol: &str) -> anyhow::Result<Self> {
+        let plugin = super::plugin::Plugin::load(path, symbol)?;
+        self.parsers.insert(
+            id,
+            NameFn(symbol.to_owned(), Extractor::Plugin(Arc::new(plugin))),
+        );
+        Ok(self)
+    }
+
+    /// Creates a builder with the fields of a JSON file, see [`Self::with_json`].
+    pub fn from_json(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::new().with_json(path)
+    }
+
+    /// Registers the fields of a JSON file, replacing already registered fields with the same id.
+    ///
+    /// The file contains a list of fields like `[{"id": 256, "name": "ethernetType", "type": "number"}]`,
+    /// the type selects one of the built-in extractors.
+    pub fn with_json(mut self, path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|| format!("failed to open field definitions {}", path.display()))?;
+        let fields: Vec<FieldDefinition> = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("invalid field definitions in {}", path.display()))?;
+
+        for field in fields {
+            let extractor = field.kind.extractor();
+            self.parsers
+                .insert(field.id, NameFn(field.name, extractor.into()));
+        }
+
+        Ok(self)
+    }
+
+    /// Only decodes the fields `ids`, all other fields are skipped and not part of the output.
+    pub fn select_fields(mut self, ids: &[u16]) -> Self {
+        self.selected = Some(ids.iter().copied().collect());
+        self
+    }
+
+    pub fn build(self) -> FieldParser {
+        FieldParser {
+            parsers: self.parsers,
+            selected: self.selected,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct FieldDefinition {
+    id: u16,
+    name: String,
+    #[serde(rename = "type")]
+    kind: ExtractorType,
+}
+
+/// Built-in extractors selectable from a field definition file.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ExtractorType {
+    Number,
+    String,
+    Bytes,
+    Ipv4,
+    Ipv6,
+    Mac,
+    DatetimeSeconds,
+    DatetimeMillis,
+    DatetimeMicros,
+    DatetimeNanos,
+    DurationMillis,
+    DurationMicros,
+}
+
+impl ExtractorType {
+    fn extractor(self) -> FieldExtractor {
+        match self {
+            Self::Number => parse_number,
+            Self::String => parse_string,
+            Self::Bytes => parse_bytes,
+            Self::Ipv4 => parse_ipv4,
+            Self::Ipv6 => parse_ipv6,
+            Self::Mac => parse_mac,
+            Self::DatetimeSeconds => parse_datetime_seconds,
+            Self::DatetimeMillis => parse_datetime_millis,
+            Self::DatetimeMicros => parse_datetime_ntp_micro,
+            Self::DatetimeNanos => parse_datetime_ntp_nano,
+            Self::DurationMillis => parse_duration_millis,
+            Self::DurationMicros => parse_duration_micros,
+        }
+    }
+}
+
+/// Parsers of all IANA information elements, derived from their abstract data type.
+fn get_default_field_parsers() -> HashMap<u16, NameFn> {
+    elements::all()
+        .iter()
+        .map(|element| {
+            let parser = default_extractor(element);
+            (element.id, NameFn(element.name.to_owned(), parser.into()))
+        })
+        .collect()
+}
+
+fn default_extractor(element: &InformationElement) -> FieldExtractor {
+    // durations are plain unsigned numbers in the registry
+    match element.name {
+        "flowStartDeltaMicroseconds" | "flowEndDeltaMicroseconds" | "flowDurationMicroseconds" => {
+            return parse_duration_micros
+        }
+        "flowDurationMilliseconds" => return parse_duration_millis,
+        _ => (),
+    }
+
+    match element.data_type {
+        "unsigned8" | "unsigned16" | "unsigned32" | "unsigned64" | "unsigned128" => parse_number,
+        "signed8" | "signed16" | "signed32" | "signed64" => parse_signed,
+        "boolean" => parse_boolean,
+        "ipv4Address" => parse_ipv4,
+        "ipv6Address" => parse_ipv6,
+        "macAddress" => parse_mac,
+        "string" => parse_string,
+        "dateTimeSeconds" => parse_datetime_seconds,
+        "dateTimeMilliseconds" => parse_datetime_millis,
+        "dateTimeMicroseconds" => parse_datetime_ntp_micro,
+        "dateTimeNanoseconds" => parse_datetime_ntp_nano,
+        // floats, octet arrays and structured data
+        _ => parse_bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipfix::parser::parse;
+    use crate::produce::IpfixParser;
+    use crate::testing::{field, DataRecord, MessageBuilder};
+
+    fn template() -> TemplateRecord {
+        TemplateRecord {
+            id: 256,
+            fields: vec![field(8, 4), field(12, 4), field(1, 8)],
+        }
+    }
+
+    fn record(bytes: u64) -> DataRecord {
+        DataRecord::new()
+            .addr([10, 0, 0, 1].into())
+            .addr([10, 0, 0, 2].into())
+            .u64(bytes)
+    }
+
+    fn feed<P: Parser>(session: &Session<P>, message: &[u8]) {
+        session.parse(&parse(message).unwrap()).unwrap();
+    }
+
+    fn gaps<P: Compile>(session: &Session<P>) -> Vec<(u32, u32)> {
+        session
+            .events()
+            .filter_map(|event| match event {
+                SessionEvent::SequenceGap { expected, got, .. } => Some((expected, got)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn decoded_flows_are_sent_to_other_tasks() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<crate::fluss::Fluss>(16);
+        let consumer = tokio::spawn(async move {
+            let mut bytes = Vec::new();
+            while let Some(fluss) = rx.recv().await {
+                bytes.push(fluss.bytes);
+            }
+            bytes
+        });
+
+        let session = Session::new(IpfixParser::new());
+        let mut builder = MessageBuilder::new(1);
+        feed(&session, &builder.templates(&[template()]));
+        for records in [&[record(1), record(2)][..], &[record(3)]] {
+            let message = builder.data(256, records);
+            let packet = parse(&message).unwrap();
+            for fluss in session.parse(&packet).unwrap() {
+                tx.send(fluss).await.unwrap();
+            }
+            // the flows borrow neither the packet nor its buffer
+            drop(packet);
+            drop(message);
+        }
+        // nor the session
+        drop(session);
+        drop(tx);
+
+        assert_eq!(consumer.await.unwrap(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn in_order_messages_have_no_gap() {
+        let session = Session::new(IpfixParser::new());
+        let mut builder = MessageBuilder::new(1);
+        feed(&session, &builder.templates(&[template()]));
+        for _ in 0..3 {
+            feed(&session, &builder.data(256, &[record(1), record(2)]));
+        }
+        assert!(gaps(&session).is_empty());
+    }
+
+    #[test]
+    fn lost_message_is_a_gap() {
+        let session = Session::new(IpfixParser::new());
+        let mut builder = MessageBuilder::new(1);
+        feed(&session, &builder.templates(&[template()]));
+        feed(&session, &builder.data(256, &[record(1), record(2)]));
+        // lost on the way
+        builder.data(256, &[record(3)]);
+        feed(&session, &builder.data(256, &[record(4)]));
+        assert_eq!(gaps(&session), vec![(2, 3)]);
+    }
+
+    #[test]
+    fn sequence_number_wraps_around() {
+        let session = Session::new(IpfixParser::new());
+        let mut builder = MessageBuilder::new(1);
+        builder.set_sequence_number(u32::MAX - 1);
+        feed(&session, &builder.templates(&[template()]));
+        feed(
+            &session,
+            &builder.data(256, &[record(1), record(2), record(3)]),
+        );
+        feed(&session, &builder.data(256, &[record(4)]));
+        assert!(gaps(&session).is_empty());
+    }
+
+    #[test]
+    fn data_before_template_is_no_gap() {
+        let session = Session::new(IpfixParser::new());
+        let mut builder = MessageBuilder::new(1);
+        builder.set_sequence_number(1000);
+        // the collector restarted, the exporter keeps sending data
+        feed(&session, &builder.data(256, &[record(1), record(2)]));
+        feed(&session, &builder.data(256, &[record(3)]));
+        feed(&session, &builder.templates(&[template()]));
+        feed(&session, &builder.data(256, &[record(4)]));
+        feed(&session, &builder.data(256, &[record(5)]));
+        assert!(gaps(&session).is_empty());
+    }
+
+    #[test]
+    fn templates_after_data_in_one_message() {
+        let session = Session::new(IpfixParser::new());
+        let mut builder = MessageBuilder::new(1);
+        let message = builder.data_before_template(&template(), &[record(1500), record(3000)]);
+
+        let flows = session.parse(&parse(&message).unwrap()).unwrap();
+        let bytes: Vec<_> = flows.iter().map(|fluss| fluss.bytes).collect();
+        assert_eq!(bytes, [1500, 3000]);
+        assert_eq!(session.templates().len(), 1);
+        assert!(gaps(&session).is_empty());
+    }
+
+    /// Collects the JSON log lines of a closure.
+    fn json_logs(f: impl FnOnce()) -> Vec<serde_json::Value> {
+        #[derive(Clone, Default)]
+        struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+        impl std::io::Write for Buffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_max_level(tracing::Level::TRACE)
+            .with_writer(move || writer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, f);
+
+        let logs = buffer.0.lock();
+        String::from_utf8_lossy(&logs)
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn record_logs_carry_the_template() {
+        let session = Session::new(IpfixParser::new());
+        let mut builder = MessageBuilder::new(7);
+        let template = TemplateRecord {
+            id: 300,
+            // a three byte IPv4 address is malformed
+            fields: vec![field(8, 3), field(12, 4)],
+        };
+        let templates = builder.templates(&[template]);
+        let data = builder.data(300, &[DataRecord::new().bytes(&[10, 0, 0]).u32(1)]);
+
+        let logs = json_logs(|| {
+            feed(&session, &templates);
+            feed(&session, &data);
+        });
+
+        let malformed = logs
+            .iter()
+            .find(|log| log["fields"]["message"] == "skipping malformed field")
+            .expect("malformed field is logged");
+        assert_eq!(malformed["span"]["name"], "record");
+        assert_eq!(malformed["span"]["template"], 300);
+        assert!(malformed["fields"]["field"].is_string());
+    }
+
+    #[test]
+    fn unknown_template_logs_fields() {
+        let session = Session::new(IpfixParser::new());
+        let data = MessageBuilder::new(7).data(400, &[record(1)]);
+
+        let logs = json_logs(|| feed(&session, &data));
+
+        let unknown = logs
+            .iter()
+            .find(|log| log["fields"]["message"] == "data set of unknown template")
+            .expect("unknown template is logged");
+        assert_eq!(unknown["fields"]["domain_id"], 7);
+        assert_eq!(unknown["fields"]["template"], 400);
+    }
+
+    fn summary(records: &[Record]) -> Vec<(u16, Option<u64>)> {
+        records
+            .iter()
+            .map(|record| (record.id, record.value.as_u64()))
+            .collect()
+    }
+
+    #[test]
+    fn field_parser_parse_matches_parse_iter() {
+        let parser = FieldParser::default();
+        let fields = template().fields;
+        let data = record(1500).into_bytes();
+        let set = DataSet {
+            id: 256,
+            data: &data,
+        };
+
+        let records = parser.parse(&fields, &set).unwrap().records;
+        let lazy: Vec<_> = parser.parse_iter(&fields, &set).collect();
+        assert_eq!(summary(&records), summary(&lazy));
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2].value.as_u64(), Some(1500));
+    }
+
+    #[test]
+    fn field_parser_skips_unselected_fields() {
+        let parser = FieldParser::builder()
+            .with_default_fields()
+            .select_fields(&[1])
+            .build();
+        let fields = template().fields;
+        let data = record(1500).into_bytes();
+        let set = DataSet {
+            id: 256,
+            data: &data,
+        };
+
+        let records = parser.parse(&fields, &set).unwrap().records;
+        assert_eq!(summary(&records), [(1, Some(1500))]);
+    }
+
+    #[test]
+    fn field_parser_drops_truncated_records() {
+        let parser = FieldParser::default();
+        let fields = template().fields;
+        let data = record(1500).into_bytes();
+        let set = DataSet {
+            id: 256,
+            data: &data[..12],
+        };
+
+        assert!(parser.parse(&fields, &set).is_none());
+        // the lazy iterator yields the fields before the truncation
+        assert_eq!(parser.parse_iter(&fields, &set).count(), 2);
+    }
+
+    fn rejections<P: Compile>(session: &Session<P>) -> Vec<(u16, TemplateError)> {
+        session
+            .events()
+            .filter_map(|event| match event {
+                SessionEvent::TemplateRejected {
+                    template_id, error, ..
+                } => Some((template_id, error)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Announces `fields` as template 256 followed by a data set of the template,
+    /// returns the rejections and the number of decoded flows.
+    fn announce(
+        session: &Session<IpfixParser>,
+        fields: Vec<FieldSpecifier>,
+    ) -> (Vec<(u16, TemplateError)>, usize) {
+        let mut builder = MessageBuilder::new(1);
+        let template = TemplateRecord { id: 256, fields };
+        feed(session, &builder.templates(std::slice::from_ref(&template)));
+        let data = builder.data(256, &[record(1)]);
+        let flows = session.parse(&parse(&data).unwrap()).unwrap();
+        (rejections(session), flows.len())
+    }
+
+    #[test]
+    fn zero_length_fields_are_rejected() {
+        let session = Session::new(IpfixParser::new());
+        let (rejected, flows) = announce(&session, vec![field(8, 4), field(12, 0), field(1, 8)]);
+        assert_eq!(rejected, [(256, TemplateError::ZeroLength { field: 12 })]);
+        assert_eq!(flows, 0);
+        assert!(session.dump_templates().is_empty());
+    }
+
+    #[test]
+    fn oversized_records_are_rejected() {
+        let session = Session::new(IpfixParser::new());
+        let fields = vec![field(8, 4), field(12, 4), field(1, 40000), field(2, 40000)];
+        let (rejected, flows) = announce(&session, fields);
+        assert_eq!(rejected, [(256, TemplateError::RecordTooLong(80008))]);
+        assert_eq!(flows, 0);
+
+        // variable length fields count with their length prefix
+        let fields = vec![field(1, 32000), field(2, 33515), field(82, VARIABLE_LENGTH)];
+        assert_eq!(
+            Session::new(IpfixParser::new()).validate_template(&fields, None),
+            Err(TemplateError::RecordTooLong(MAX_RECORD_LENGTH + 1))
+        );
+    }
+
+    #[test]
+    fn templates_are_capped_by_field_count() {
+        let session = Session::new(IpfixParser::new()).with_max_template_fields(2);
+        let (rejected, flows) = announce(&session, vec![field(8, 4), field(12, 4), field(1, 8)]);
+        assert_eq!(rejected, [(256, TemplateError::TooManyFields(3))]);
+        assert_eq!(flows, 0);
+
+        let session = Session::new(IpfixParser::new()).with_max_template_fields(3);
+        let (rejected, flows) = announce(&session, vec![field(8, 4), field(12, 4), field(1, 8)]);
+        assert!(rejected.is_empty());
+        assert_eq!(flows, 1);
+    }
+
+    #[test]
+    fn invalid_scope_field_counts_are_rejected() {
+        for scope_field_count in [0, 5] {
+            let session = Session::new(IpfixParser::new());
+            let mut builder = MessageBuilder::new(1);
+            let template = OptionsTemplateRecord {
+                id: 512,
+                scope_field_count,
+                fields: vec![field(149, 4), field(34, 4)],
+            };
+            feed(&session, &builder.options_templates(&[template]));
+            let data = builder.data(512, &[DataRecord::new().u32(7).u32(100)]);
+            let packet = parse(&data).unwrap();
+            let decoded = session.parse_with_options(&packet).unwrap();
+
+            // no panic on splitting off the scope fields
+            assert!(decoded.is_empty());
+            assert_eq!(
+                rejections(&session),
+                [(
+                    512,
+                    TemplateError::ScopeFieldCount {
+                        scope_fields: scope_field_count.into(),
+                        fields: 2
+                    }
+                )]
+            );
+            assert!(session.dump_templates().is_empty());
+        }
+    }
+
+    #[test]
+    fn refreshes_of_rejected_templates_are_reported_once() {
+        let session = Session::new(IpfixParser::new());
+        let fields = vec![field(8, 4), field(12, 0), field(1, 8)];
+        assert_eq!(announce(&session, fields.clone()).0.len(), 1);
+        assert!(announce(&session, fields).0.is_empty());
+
+        // a valid template replaces the rejected one, a later rejection is reported again
+        let (rejected, flows) = announce(&session, template().fields);
+        assert!(rejected.is_empty());
+        assert_eq!(flows, 1);
+        let (rejected, _) = announce(&session, vec![field(8, 0)]);
+        assert_eq!(rejected, [(256, TemplateError::ZeroLength { field: 8 })]);
+    }
+
+    #[test]
+    fn unexpected_lengths_are_only_warnings() {
+        // a reduced size encoding of a counter is legal, of an address it is not
+        let fields = vec![
+            field(8, 3),
+            field(12, 4),
+            field(1, 4),
+            field(4, 2),
+            field(82, 16),
+        ];
+        let unexpected: Vec<_> = unexpected_lengths(&fields)
+            .map(|field| (field.id, field.length))
+            .collect();
+        assert_eq!(unexpected, [(8, 3), (4, 2)]);
+
+        let session = Session::new(IpfixParser::new());
+        let mut builder = MessageBuilder::new(1);
+        let template = TemplateRecord {
+            id: 256,
+            fields: vec![field(8, 3), field(12, 4), field(1, 8)],
+        };
+        feed(&session, &builder.templates(&[template]));
+        let record = DataRecord::new()
+            .bytes(&[10, 0, 0])
+            .addr([10, 0, 0, 2].into())
+            .u64(100);
+        let flows = session
+            .parse(&parse(&builder.data(256, &[record])).unwrap())
+            .unwrap();
+        assert!(rejections(&session).is_empty());
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].bytes, 100);
+    }
+
+    #[test]
+    fn default_parsers_follow_the_data_type() {
+        let parser = FieldParser::builder().with_default_fields().build();
+        let fields = [
+            field(1, 8),
+            field(8, 4),
+            field(96, 5),
+            field(434, 4),
+            field(434, 2),
+            field(95, 3),
+        ];
+        let record = DataRecord::new()
+            .u64(1500)
+            .addr([10, 0, 0, 1].into())
+            .bytes(b"https")
+            .u32(0xffff_fffe)
+            .u16(0x8000)
+            .bytes(&[3, 0, 80]);
+        let data = record.into_bytes();
+        let set = DataSet {
+            id: 256,
+            data: &data,
+        };
+
+        let records = parser.parse(&fields, &set).unwrap().records;
+        let values: Vec<_> = records.iter().map(|record| &record.value).collect();
+        assert_eq!(values[0].as_u64(), Some(1500));
+        assert_eq!(values[1].as_ipv4(), Some(&[10, 0, 0, 1].into()));
+        assert_eq!(values[2].as_string().map(String::as_str), Some("https"));
+        // signed numbers are sign extended from their encoded length
+        assert_eq!(values[3].as_i64(), Some(-2));
+        assert_eq!(values[4].as_i64(), Some(-32768));
+        assert_eq!(values[3].as_u64(), None);
+        assert_eq!(values[5].as_bytes(), Some(&[3, 0, 80][..]));
+
+        assert_eq!(parser.field_name(1), Some("octetDeltaCount"));
+        assert_eq!(parser.field_name(434), Some("mibObjectValueInteger"));
+    }
+
+    #[test]
+    fn interface_and_sampler_names_are_strings() {
+        let parser = FieldParser::default();
+        let fields = [field(82, 4), field(83, 7), field(84, 3)];
+        let record = DataRecord::new()
+            .bytes(b"eth0")
+            .bytes(b"uplink\xff")
+            .bytes(b"s-1");
+        let data = record.into_bytes();
+        let set = DataSet {
+            id: 256,
+            data: &data,
+        };
+
+        let records = parser.parse(&fields, &set).unwrap().records;
+        assert_eq!(
+            records[0].value.as_string().map(String::as_str),
+            Some("eth0")
+        );
+        // invalid UTF-8 is kept as raw bytes instead of a lossy string
+        assert!(matches!(&records[1].value, Value::Unknown(data) if **data == b"uplink\xff"[..]));
+        assert_eq!(
+            records[2].value.as_string().map(String::as_str),
+            Some("s-1")
+        );
+        assert_eq!(parser.field_name(83), Some("interfaceDescription"));
+    }
+
+    #[test]
+    fn overrides_take_precedence_over_default_fields() {
+        let fields = [field(8, 4)];
+        let data = [10, 0, 0, 1];
+        let set = DataSet {
+            id: 256,
+            data: &data,
+        };
+
+        for parser in [
+            FieldParser::builder()
+                .with_field(8, "source", parse_bytes)
+                .with_default_fields()
+                .build(),
+            FieldParser::builder()
+                .with_default_fields()
+                .with_field(8, "source", parse_bytes)
+                .build(),
+        ] {
+            assert_eq!(parser.field_name(8), Some("source"));
+            let records = parser.parse(&fields, &set).unwrap().records;
+            assert_eq!(records[0].value.as_bytes(), Some(&data[..]));
+        }
+    }
+
+    #[test]
+    fn signed_numbers() {
+        assert_eq!(parse_signed(&[0xff]).as_i64(), Some(-1));
+        assert_eq!(parse_signed(&[0x7f]).as_i64(), Some(127));
+        assert_eq!(parse_signed(&[0xff, 0x00, 0x00]).as_i64(), Some(-65536));
+        assert_eq!(parse_signed(&[0x00, 0x80, 0x00]).as_i64(), Some(32768));
+        assert_eq!(
+            parse_signed(&i64::MIN.to_be_bytes()).as_i64(),
+            Some(i64::MIN)
+        );
+        assert!(matches!(parse_signed(&[]), Value::Unknown(_)));
+        assert!(matches!(parse_signed(&[0; 9]), Value::Unknown(_)));
+        assert_eq!(serde_json::Value::from(&parse_signed(&[0xfe])), -2);
+    }
+
+    /// The fields of the fat template of `fluss bench`.
+    fn fat_fields() -> Vec<FieldSpecifier> {
+        [
+            (1, 8),
+            (2, 8),
+            (4, 1),
+            (5, 1),
+            (6, 2),
+            (7, 2),
+            (8, 4),
+            (9, 1),
+            (10, 4),
+            (11, 2),
+            (12, 4),
+            (13, 1),
+            (14, 4),
+            (15, 4),
+            (16, 4),
+            (17, 4),
+            (21, 4),
+            (22, 4),
+            (23, 8),
+            (24, 8),
+            (56, 6),
+            (58, 2),
+            (59, 2),
+            (61, 1),
+            (81, 6),
+            (136, 1),
+            (225, 4),
+            (226, 4),
+            (227, 2),
+            (228, 2),
+        ]
+        .iter()
+        .map(|&(id, length)| field(id, length))
+        .collect()
+    }
+
+    /// A record of `fields` without repeating bytes.
+    fn pattern(fields: &[FieldSpecifier]) -> Vec<u8> {
+        let length: usize = fields.iter().map(|field| field.length as usize).sum();
+        (0..length).map(|i| (i * 31 + 7) as u8).collect()
+    }
+
+    /// Decodes the record `data` with and without a compiled plan.
+    fn both(
+        parser: &FieldParser,
+        fields: &[FieldSpecifier],
+        data: &[u8],
+    ) -> (Option<serde_json::Value>, Option<serde_json::Value>) {
+        let set = DataSet { id: 256, data };
+        let plan = parser.compile(fields);
+        let planned = parser.parse_planned(&plan, 1, fields, &set);
+        let unplanned = parser.parse_in_domain(1, fields, &set);
+        let json = |records: RecordSet<'_>| serde_json::to_value(&records.records).unwrap();
+        (planned.map(json), unplanned.map(json))
+    }
+
+    #[test]
+    fn planned_records_match_unplanned() {
+        let mut fields = fat_fields();
+        assert_eq!(fields.len(), 30);
+        let data = pattern(&fields);
+
+        let parsers = [
+            FieldParser::default(),
+            FieldParser::builder()
+                .with_default_fields()
+                .select_fields(&[1, 8, 12, 225])
+                .build(),
+            // every field is unknown
+            FieldParser::builder().build(),
+        ];
+        for parser in &parsers {
+            let (planned, unplanned) = both(parser, &fields, &data);
+            assert!(planned.is_some());
+            assert_eq!(planned, unplanned);
+        }
+        let (planned, _) = both(&parsers[1], &fields, &data);
+        assert_eq!(planned.unwrap().as_array().unwrap().len(), 4);
+
+        // unassigned and enterprise fields
+        fields.push(field(32000, 2));
+        fields.push(FieldSpecifier {
+            id: 1,
+            length: 4,
+            enterprise_id: Some(6871),
+        });
+        let data = pattern(&fields);
+        for parser in &parsers {
+            let (planned, unplanned) = both(parser, &fields, &data);
+            assert_eq!(planned, unplanned);
+        }
+    }
+
+    #[test]
+    fn planned_records_of_truncated_and_variable_length_records() {
+        let parser = FieldParser::default();
+        let fields = fat_fields();
+        let data = pattern(&fields);
+        assert_eq!(both(&parser, &fields, &data[..50]), (None, None));
+
+        // offsets differ per record, the plan falls back to reading field by field
+        let fields = vec![field(8, 4), field(82, VARIABLE_LENGTH), field(1, 8)];
+        let mut data = vec![10, 0, 0, 1, 4];
+        data.extend_from_slice(b"eth0");
+        data.extend_from_slice(&1500u64.to_be_bytes());
+        let (planned, unplanned) = both(&parser, &fields, &data);
+        assert_eq!(planned, unplanned);
+        let planned = planned.unwrap();
+        assert_eq!(planned[1]["value"], "eth0");
+        assert_eq!(planned[2]["value"], 1500);
+    }
+
+    #[test]
+    fn replaced_templates_are_compiled_again() {
+        let session = Session::new(IpfixParser::new());
+        let mut builder = MessageBuilder::new(1);
+        feed(&session, &builder.templates(&[template()]));
+        let flows = session
+            .parse(&parse(&builder.data(256, &[record(1500)])).unwrap())
+            .unwrap();
+        assert_eq!(flows[0].bytes, 1500);
+
+        // the same fields in a different order
+        let replaced = TemplateRecord {
+            id: 256,
+            fields: vec![field(1, 8), field(8, 4), field(12, 4)],
+        };
+        feed(&session, &builder.templates(&[replaced]));
+        let record = DataRecord::new()
+            .u64(9000)
+            .addr([10, 0, 0, 3].into())
+            .addr([10, 0, 0, 4].into());
+        let flows = session
+            .parse(&parse(&builder.data(256, &[record])).unwrap())
+            .unwrap();
+        assert_eq!(flows[0].bytes, 9000);
+        assert_eq!(flows[0].src_addr, std::net::IpAddr::from([10, 0, 0, 3]));
+    }
+
+    /// Source and destination port before the byte count.
+    fn ports_first() -> TemplateRecord {
+        TemplateRecord {
+            id: 256,
+            fields: vec![
+                field(8, 4),
+                field(12, 4),
+                field(7, 2),
+                field(11, 2),
+                field(1, 8),
+            ],
+        }
+    }
+
+    /// The layout of [`ports_first`] after a firmware update, the byte count moved up.
+    fn bytes_first() -> TemplateRecord {
+        TemplateRecord {
+            id: 256,
+            fields: vec![
+                field(8, 4),
+                field(12, 4),
+                field(1, 8),
+                field(7, 2),
+                field(11, 2),
+            ],
+        }
+    }
+
+    fn ports_first_record(bytes: u64) -> DataRecord {
+        DataRecord::new()
+            .addr([10, 0, 0, 1].into())
+            .addr([10, 0, 0, 2].into())
+            .u16(49152)
+            .u16(443)
+            .u64(bytes)
+    }
+
+    fn flapping<P: Compile>(session: &Session<P>) -> Vec<(u16, u32, u64, bool)> {
+        session
+            .events()
+            .filter_map(|event| match event {
+                SessionEvent::TemplateFlapping {
+                    template_id,
+                    version,
+                    suspect,
+                    quarantined,
+                    ..
+                } => Some((template_id, version, suspect, quarantined)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn sanity_checks() -> SanityChecks {
+        SanityChecks::new()
+            .with_max_bytes(crate::ipfix::sanity::DEFAULT_MAX_BYTES)
+            .with_threshold(3)
+    }
+
+    #[test]
+    fn layout_changes_are_detected() {
+        let session =
+            Session::new(IpfixParser::new()).with_sanity_checks(sanity_checks().with_quarantine());
+        let mut builder = MessageBuilder::new(1);
+        feed(&session, &builder.templates(&[ports_first()]));
+        let flows = session
+            .parse(&parse(&builder.data(256, &[ports_first_record(1500)])).unwrap())
+            .unwrap();
+        assert_eq!((flows[0].bytes, flows[0].suspect), (1500, false));
+
+        // the exporter changes the layout, records in flight still use the old one
+        feed(&session, &builder.templates(&[bytes_first()]));
+        let history = session.template_history(1, 256);
+        assert_eq!(history.len(), 1);
+        assert_eq!(
+            (history[0].version, &history[0].fields),
+            (1, &ports_first().fields)
+        );
+
+        let in_flight = [ports_first_record(1500), ports_first_record(3000)];
+        let flows = session
+            .parse(&parse(&builder.data(256, &in_flight)).unwrap())
+            .unwrap();
+        // the ports are read as the upper bytes of the byte count
+        assert!(flows
+            .iter()
+            .all(|flow| flow.suspect && flow.bytes > 1 << 50));
+        assert!(flapping(&session).is_empty());
+
+        // the threshold is reached, the records of the template are quarantined
+        let flows = session
+            .parse(&parse(&builder.data(256, &in_flight)).unwrap())
+            .unwrap();
+        assert!(flows.is_empty());
+        assert_eq!(flapping(&session), [(256, 2, 4, true)]);
+        let stats = session.template_stats();
+        assert_eq!((stats[0].version, stats[0].suspect_records), (2, 4));
+
+        // records of the new layout are dropped as well until the template is announced again
+        let record = DataRecord::new()
+            .addr([10, 0, 0, 1].into())
+            .addr([10, 0, 0, 2].into())
+            .u64(1500)
+            .u16(49152)
+            .u16(443);
+        let message = builder.data(256, std::slice::from_ref(&record));
+        assert!(session.parse(&parse(&message).unwrap()).unwrap().is_empty());
+
+        feed(&session, &builder.templates(&[bytes_first()]));
+        let flows = session
+            .parse(&parse(&builder.data(256, &[record])).unwrap())
+            .unwrap();
+        assert_eq!((flows[0].bytes, flows[0].suspect), (1500, false));
+        assert_eq!(flows[0].src_port, 49152);
+        // a template is reported once
+        assert!(flapping(&session).is_empty());
+    }
+
+    #[test]
+    fn layout_changes_are_only_reported_by_default() {
+        let session = Session::new(IpfixParser::new()).with_sanity_checks(sanity_checks());
+        let mut builder = MessageBuilder::new(1);
+        feed(&session, &builder.templates(&[ports_first()]));
+        feed(&session, &builder.templates(&[bytes_first()]));
+
+        let in_flight = vec![ports_first_record(1500); 3];
+        let flows = session
+            .parse(&parse(&builder.data(256, &in_flight)).unwrap())
+            .unwrap();
+        assert_eq!(flows.len(), 3);
+        assert!(flows.iter().all(|flow| flow.suspect));
+        assert_eq!(flapping(&session), [(256, 2, 3, false)]);
+    }
+
+    #[test]
+    fn suspect_records_of_unchanged_templates_are_not_flapping() {
+        let session = Session::new(IpfixParser::new()).with_sanity_checks(sanity_checks());
+        let mut builder = MessageBuilder::new(1);
+        feed(&session, &builder.templates(&[ports_first()]));
+        // refreshing the template does not change its layout
+        feed(&session, &builder.templates(&[ports_first()]));
+
+        let records = vec![ports_first_record(u64::MAX); 5];
+        let flows = session
+            .parse(&parse(&builder.data(256, &records)).unwrap())
+            .unwrap();
+        assert!(flows.iter().all(|flow| flow.suspect));
+        assert!(flapping(&session).is_empty());
+        assert!(session.template_history(1, 256).is_empty());
+        assert_eq!(session.template_stats()[0].version, 1);
+    }
+
+    #[test]
+    fn sanity_checks_are_off_by_default() {
+        let session = Session::new(IpfixParser::new());
+        let mut builder = MessageBuilder::new(1);
+        feed(&session, &builder.templates(&[ports_first()]));
+        feed(&session, &builder.templates(&[bytes_first()]));
+
+        let in_flight = vec![ports_first_record(1500); 20];
+        let flows = session
+            .parse(&parse(&builder.data(256, &in_flight)).unwrap())
+            .unwrap();
+        assert_eq!(flows.len(), 20);
+        assert!(flows.iter().all(|flow| !flow.suspect));
+        assert!(flapping(&session).is_empty());
+        assert_eq!(session.template_stats()[0].suspect_records, 0);
+    }
+}
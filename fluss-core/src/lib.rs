@@ -0,0 +1,13 @@
+#[cfg(feature = "arrow")]
+pub mod arrow;
+mod clock;
+pub mod flow_key;
+pub mod fluss;
+pub mod icmp;
+pub mod ipfix;
+pub mod produce;
+pub mod protocol;
+pub mod services;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod transport;
@@ -0,0 +1,430 @@
+use crate::fluss::Fluss;
+use crate::protocol::{
+    parse_ipv4, parse_ipv6, parse_mac, parse_number, parse_string, to_hex, Value,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// How the data of a custom field is decoded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FieldType {
+    String,
+    Number,
+    Ipv4,
+    Ipv6,
+    Mac,
+    BytesHex,
+}
+
+impl FieldType {
+    /// Decodes `data`, returns `None` if the data does not match the type.
+    pub fn decode(self, data: &[u8]) -> Option<serde_json::Value> {
+        let value = match self {
+            Self::String => parse_string(data),
+            Self::Number => parse_number(data),
+            Self::Ipv4 => parse_ipv4(data),
+            Self::Ipv6 => parse_ipv6(data),
+            Self::Mac => parse_mac(data),
+            Self::BytesHex => return Some(to_hex(data).into()),
+        };
+
+        match value {
+            Value::Unknown(_) => None,
+            Value::String(value) => Some(value.into()),
+            value => match value.as_u64() {
+                Some(number) => Some(number.into()),
+                None => Some(value.to_string().into()),
+            },
+        }
+    }
+}
+
+/// Built-in field of a [`Fluss`] which can be filled by a custom field.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MappedField {
+    TunnelType,
+    TunnelId,
+    InnerSrcAddr,
+    InnerDstAddr,
+    InnerSrcPort,
+    InnerDstPort,
+    DnsQuery,
+    DnsQtype,
+    HttpHost,
+    HttpUrl,
+    HttpStatus,
+    TlsSni,
+}
+
+impl MappedField {
+    /// Whether the field is application layer metadata, decoded like the
+    /// built-in metadata elements regardless of the type of the custom field.
+    pub fn is_metadata(self) -> bool {
+        matches!(
+            self,
+            Self::DnsQuery
+                | Self::DnsQtype
+                | Self::HttpHost
+                | Self::HttpUrl
+                | Self::HttpStatus
+                | Self::TlsSni
+        )
+    }
+
+    /// The type a custom field mapped to this field has to declare.
+    fn expected_type(self) -> Option<FieldType> {
+        match self {
+            Self::DnsQuery | Self::HttpHost | Self::HttpUrl | Self::TlsSni => {
+                Some(FieldType::String)
+            }
+            Self::DnsQtype | Self::HttpStatus => Some(FieldType::Number),
+            _ => None,
+        }
+    }
+}
+
+/// Maps an information element to an additional field of a [`Fluss`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CustomField {
+    /// Private enterprise number, `None` for IANA information elements.
+    pub pen: Option<u32>,
+    pub id: u16,
+    /// Name of the field in [`Fluss::extra`], not required for mapped fields.
+    #[serde(default)]
+    pub name: String,
+    pub r#type: FieldType,
+    /// Stores the value in a built-in field instead of [`Fluss::extra`],
+    /// e.g. vendor specific inner addresses of tunneled flows.
+    #[serde(default)]
+    pub map_to: Option<MappedField>,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    custom_field: Vec<CustomField>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct CustomFields {
+    fields: HashMap<(Option<u32>, u16), CustomField>,
+}
+
+impl CustomFields {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the `[[custom_field]]` entries of a TOML file.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let config: Config = toml::from_str(&content)?;
+
+        let mut fields = Self::new();
+        for field in config.custom_field {
+            fields.add(field)?;
+        }
+
+        Ok(fields)
+    }
+
+    /// Adds a custom field, fails if the name is already taken by another field.
+    pub fn add(&mut self, field: CustomField) -> anyhow::Result<()> {
+        // the most significant bit of an element id is the enterprise bit
+        if field.id >= 0x8000 {
+            anyhow::bail!(
+                "information element id {} of custom field {:?} is out of range",
+                field.id,
+                field.name
+            );
+        }
+        // mapped fields are stored in the built-in field, the name is not used
+        if field.map_to.is_none() {
+            if field.name.is_empty() {
+                anyhow::bail!(
+                    "custom field for information element {} (pen {:?}) needs a name or map_to",
+                    field.id,
+                    field.pen
+                );
+            }
+            if Fluss::FIELDS.contains(&field.name.as_str()) {
+                anyhow::bail!(
+                    "custom field {:?} conflicts with a built-in field",
+                    field.name
+                );
+            }
+            if self
+                .fields
+                .values()
+                .any(|other| other.map_to.is_none() && other.name == field.name)
+            {
+                anyhow::bail!("duplicate custom field {:?}", field.name);
+            }
+        }
+        if let Some(target) = field.map_to {
+            match target.expected_type() {
+                Some(expected) if expected != field.r#type => anyhow::bail!(
+                    "information element {} (pen {:?}) is mapped to {:?} and needs the type {:?}",
+                    field.id,
+                    field.pen,
+                    target,
+                    expected
+                ),
+                _ => (),
+            }
+        }
+        if self.fields.contains_key(&(field.pen, field.id)) {
+            anyhow::bail!(
+                "information element {} (pen {:?}) is mapped more than once",
+                field.id,
+                field.pen
+            );
+        }
+
+        self.fields.insert((field.pen, field.id), field);
+        Ok(())
+    }
+
+    pub fn get(&self, enterprise_id: Option<u32>, id: u16) -> Option<&CustomField> {
+        self.fields.get(&(enterprise_id, id))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipfix::parser::FieldSpecifier;
+    use crate::ipfix::parser::{parse, TemplateRecord};
+    use crate::ipfix::Session;
+    use crate::produce::IpfixParser;
+    use crate::testing::{field, DataRecord, MessageBuilder};
+    use std::io::Write;
+    use std::sync::Arc;
+
+    const PEN: u32 = 25461;
+
+    fn load(config: &str) -> anyhow::Result<CustomFields> {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+        CustomFields::load(file.path())
+    }
+
+    fn vendor(id: u16, length: u16) -> FieldSpecifier {
+        FieldSpecifier {
+            id,
+            length,
+            enterprise_id: Some(PEN),
+        }
+    }
+
+    #[test]
+    fn custom_fields_are_serialized() {
+        let fields = load(
+            r#"
+            [[custom_field]]
+            pen = 25461
+            id = 5671
+            name = "rule_name"
+            type = "string"
+
+            [[custom_field]]
+            pen = 25461
+            id = 5672
+            name = "rule_id"
+            type = "number"
+
+            [[custom_field]]
+            pen = 25461
+            id = 5673
+            name = "policy_hash"
+            type = "bytes-hex"
+            "#,
+        )
+        .unwrap();
+
+        let template = TemplateRecord {
+            id: 256,
+            fields: vec![
+                field(8, 4),
+                field(12, 4),
+                field(4, 1),
+                vendor(5671, 9),
+                vendor(5672, 4),
+                vendor(5673, 2),
+            ],
+        };
+        let record = DataRecord::new()
+            .addr([10, 0, 0, 1].into())
+            .addr([10, 0, 0, 2].into())
+            .u8(6)
+            .bytes(b"allow-web")
+            .u32(17)
+            .bytes(&[0xbe, 0xef]);
+
+        let session = Session::new(IpfixParser::with_custom_fields(Arc::new(fields)));
+        let mut builder = MessageBuilder::new(1);
+        let templates = builder.templates(std::slice::from_ref(&template));
+        session.parse(&parse(&templates).unwrap()).unwrap();
+        let data = builder.data(template.id, &[record]);
+        let flows = session.parse(&parse(&data).unwrap()).unwrap();
+
+        let json = serde_json::to_value(&flows[0]).unwrap();
+        assert_eq!(json["rule_name"], "allow-web");
+        assert_eq!(json["rule_id"], 17);
+        assert_eq!(json["policy_hash"], "beef");
+        assert!(json.get("extra").is_none());
+    }
+
+    #[test]
+    fn conflicting_names_are_rejected() {
+        let err = load(
+            r#"
+            [[custom_field]]
+            pen = 25461
+            id = 5671
+            name = "bytes"
+            type = "number"
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("built-in"), "{}", err);
+
+        let err = load(
+            r#"
+            [[custom_field]]
+            pen = 25461
+            id = 5671
+            name = "zone"
+            type = "string"
+
+            [[custom_field]]
+            pen = 25461
+            id = 5672
+            name = "zone"
+            type = "string"
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("duplicate"), "{}", err);
+
+        let err = load(
+            r#"
+            [[custom_field]]
+            pen = 25461
+            id = 56701
+            name = "zone"
+            type = "string"
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("out of range"), "{}", err);
+    }
+
+    #[test]
+    fn decode_rejects_mismatched_data() {
+        assert_eq!(FieldType::Ipv4.decode(&[10, 0, 0]), None);
+        assert_eq!(
+            FieldType::Ipv4.decode(&[10, 0, 0, 1]),
+            Some("10.0.0.1".into())
+        );
+        assert_eq!(FieldType::Number.decode(&[1, 0]), Some(256.into()));
+    }
+
+    #[test]
+    fn metadata_of_mapped_fields() {
+        // Flowmon application layer elements
+        let fields = load(
+            r#"
+            [[custom_field]]
+            pen = 39499
+            id = 1
+            type = "string"
+            map_to = "dns_query"
+
+            [[custom_field]]
+            pen = 39499
+            id = 2
+            type = "number"
+            map_to = "dns_qtype"
+
+            [[custom_field]]
+            pen = 39499
+            id = 20
+            type = "string"
+            map_to = "tls_sni"
+            "#,
+        )
+        .unwrap();
+
+        let flowmon = |id, length| FieldSpecifier {
+            id,
+            length,
+            enterprise_id: Some(39499),
+        };
+        let template = TemplateRecord {
+            id: 256,
+            fields: vec![
+                field(8, 4),
+                field(12, 4),
+                flowmon(1, 16),
+                flowmon(2, 2),
+                flowmon(20, 4),
+            ],
+        };
+        let record = DataRecord::new()
+            .addr([10, 0, 0, 1].into())
+            .addr([10, 0, 0, 2].into())
+            .bytes(b"mail.example\xff\0\0\0")
+            .u16(15)
+            .bytes(&[0, 0, 0, 0]);
+
+        let session = Session::new(IpfixParser::with_custom_fields(Arc::new(fields)));
+        let mut builder = MessageBuilder::new(1);
+        let templates = builder.templates(std::slice::from_ref(&template));
+        session.parse(&parse(&templates).unwrap()).unwrap();
+        let data = builder.data(template.id, &[record]);
+        let flows = session.parse(&parse(&data).unwrap()).unwrap();
+
+        assert_eq!(flows[0].dns_query.as_deref(), Some("mail.example\u{fffd}"));
+        assert_eq!(flows[0].dns_qtype, Some(15));
+        assert_eq!(flows[0].tls_sni, None);
+        assert!(flows[0].lossy_utf8);
+        let json = serde_json::to_value(&flows[0]).unwrap();
+        assert_eq!(json["dns_query"], "mail.example\u{fffd}");
+        assert!(json.get("extra").is_none());
+    }
+
+    #[test]
+    fn mapped_metadata_needs_its_type() {
+        let err = load(
+            r#"
+            [[custom_field]]
+            pen = 39499
+            id = 3
+            type = "string"
+            map_to = "http_status"
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("needs the type"), "{}", err);
+
+        let err = load(
+            r#"
+            [[custom_field]]
+            pen = 39499
+            id = 4
+            type = "number"
+            map_to = "http_host"
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("needs the type"), "{}", err);
+    }
+}
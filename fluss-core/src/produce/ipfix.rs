@@ -0,0 +1,1382 @@
+use super::{CustomField, CustomFields, MappedField};
+use crate::fluss::{tcp_flags, FlowDirection, FlowEndReason, FlowState, FlowType, Fluss, Protocol};
+use crate::ipfix::parser::{DataSet, FieldSpecifier};
+use crate::ipfix::sanity::SanityChecks;
+use crate::ipfix::session::{Compile, DecodePlan, OptionsContext, Parser};
+use crate::protocol::{
+    parse_datetime_ntp_micro, parse_datetime_ntp_nano, parse_icmp_type_code, parse_ipv4, parse_mac,
+    parse_number, Value,
+};
+use chrono::{DateTime, TimeZone, Utc};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::Duration;
+
+const IPFIX_BYTES_IN: u16 = 1;
+const IPFIX_PACKETS_IN: u16 = 2;
+const IPFIX_PROTOCOL: u16 = 4;
+const IPFIX_TCP_CONTROL_BITS: u16 = 6;
+const IPFIX_SRC_PORT: u16 = 7;
+const IPFIX_IPV4_SRC_ADDR: u16 = 8;
+const IPFIX_IPV4_SRC_MASK: u16 = 9;
+const IPFIX_INGRESS_INTERFACE: u16 = 10;
+const IPFIX_DST_PORT: u16 = 11;
+const IPFIX_IPV4_DST_ADDR: u16 = 12;
+const IPFIX_IPV4_DST_MASK: u16 = 13;
+const IPFIX_EGRESS_INTERFACE: u16 = 14;
+const IPFIX_IPV4_NEXT_HOP: u16 = 15;
+const IPFIX_FLOW_END_SYSUPTIME: u16 = 21;
+const IPFIX_FLOW_START_SYSUPTIME: u16 = 22;
+const IPFIX_BYTES_OUT: u16 = 23;
+const IPFIX_PACKETS_OUT: u16 = 24;
+const IPFIX_ICMP_TYPE_CODE_IPV4: u16 = 32;
+const IPFIX_SAMPLING_INTERVAL: u16 = 34;
+const IPFIX_MAC_SRC: u16 = 56;
+const IPFIX_VLAN_ID: u16 = 58;
+const IPFIX_POST_VLAN_ID: u16 = 59;
+const IPFIX_FLOW_DIRECTION: u16 = 61;
+const IPFIX_MAC_DST: u16 = 81;
+const IPFIX_OCTET_TOTAL_COUNT: u16 = 85;
+const IPFIX_PACKET_TOTAL_COUNT: u16 = 86;
+const IPFIX_FLOW_END_REASON: u16 = 136;
+const IPFIX_FLOW_START_SECONDS: u16 = 150;
+const IPFIX_FLOW_END_SECONDS: u16 = 151;
+const IPFIX_FLOW_START_MILLISECONDS: u16 = 152;
+const IPFIX_FLOW_END_MILLISECONDS: u16 = 153;
+const IPFIX_FLOW_START_MICROSECONDS: u16 = 154;
+const IPFIX_FLOW_END_MICROSECONDS: u16 = 155;
+const IPFIX_FLOW_START_NANOSECONDS: u16 = 156;
+const IPFIX_FLOW_END_NANOSECONDS: u16 = 157;
+const IPFIX_DSCP: u16 = 195;
+const IPFIX_TCP_SYN_TOTAL_COUNT: u16 = 218;
+const IPFIX_TCP_FIN_TOTAL_COUNT: u16 = 219;
+const IPFIX_TCP_RST_TOTAL_COUNT: u16 = 220;
+const IPFIX_TCP_PSH_TOTAL_COUNT: u16 = 221;
+const IPFIX_TCP_ACK_TOTAL_COUNT: u16 = 222;
+const IPFIX_TCP_URG_TOTAL_COUNT: u16 = 223;
+const IPFIX_POST_NAT_IPV4_SRC_ADDR: u16 = 225;
+const IPFIX_POST_NAT_IPV4_DST_ADDR: u16 = 226;
+const IPFIX_POST_NAPT_SRC_PORT: u16 = 227;
+const IPFIX_POST_NAPT_DST_PORT: u16 = 228;
+const IPFIX_ETHERNET_TYPE: u16 = 256;
+const IPFIX_SAMPLING_PACKET_INTERVAL: u16 = 305;
+const IPFIX_SAMPLING_PACKET_SPACE: u16 = 306;
+const IPFIX_LAYER2_SEGMENT_ID: u16 = 351;
+const IPFIX_HTTP_STATUS_CODE: u16 = 457;
+const IPFIX_HTTP_REQUEST_HOST: u16 = 460;
+const IPFIX_HTTP_REQUEST_TARGET: u16 = 461;
+
+/// Private enterprise number used for reverse information elements (RFC 5103).
+const IPFIX_REVERSE_PEN: u32 = 29305;
+
+/// Private enterprise number of CERT, used by YAF for the TCP flags of biflows.
+const CERT_PEN: u32 = 6871;
+const CERT_INITIAL_TCP_FLAGS: u16 = 14;
+const CERT_UNION_TCP_FLAGS: u16 = 15;
+const CERT_REVERSE_INITIAL_TCP_FLAGS: u16 = 16;
+const CERT_REVERSE_UNION_TCP_FLAGS: u16 = 17;
+
+/// Private enterprise number of ntop, used by nProbe for its plugin elements.
+const NTOP_PEN: u32 = 35632;
+const NTOP_HTTP_URL: u16 = 180;
+const NTOP_HTTP_RET_CODE: u16 = 181;
+const NTOP_HTTP_HOST: u16 = 187;
+const NTOP_TLS_SERVER_NAME: u16 = 188;
+const NTOP_DNS_QUERY: u16 = 205;
+const NTOP_DNS_QUERY_TYPE: u16 = 207;
+
+/// Default limit of [`IpfixParser::with_max_metadata_length`] in bytes.
+pub const DEFAULT_MAX_METADATA_LENGTH: usize = 512;
+
+/// Tunnel metadata of a record, from `layer2SegmentId` or mapped custom fields.
+#[derive(Default)]
+struct Tunnel {
+    r#type: Option<String>,
+    id: Option<u64>,
+    src_addr: Option<IpAddr>,
+    dst_addr: Option<IpAddr>,
+    src_port: Option<u16>,
+    dst_port: Option<u16>,
+}
+
+impl Tunnel {
+    /// Stores the decoded value of a mapped custom field, returns `None` if
+    /// the value does not fit the field.
+    fn set(&mut self, field: MappedField, value: serde_json::Value) -> Option<()> {
+        let addr = || value.as_str()?.parse().ok();
+        let port = || u16::try_from(value.as_u64()?).ok();
+
+        match field {
+            MappedField::TunnelType => {
+                self.r#type = Some(match &value {
+                    serde_json::Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                })
+            }
+            MappedField::TunnelId => self.id = Some(value.as_u64()?),
+            MappedField::InnerSrcAddr => self.src_addr = Some(addr()?),
+            MappedField::InnerDstAddr => self.dst_addr = Some(addr()?),
+            MappedField::InnerSrcPort => self.src_port = Some(port()?),
+            MappedField::InnerDstPort => self.dst_port = Some(port()?),
+            // decoded from the raw data by `AppMetadata`
+            _ => return None,
+        }
+
+        Some(())
+    }
+
+    /// Fills in type and identifier of a `layer2SegmentId` unless they were mapped.
+    fn set_layer2_segment(&mut self, segment: u64) {
+        // the upper 8 bits are the segment type, the lower 56 bits the identifier
+        let r#type = match segment >> 56 {
+            0x01 => "vxlan",
+            0x02 => "nvgre",
+            _ => return,
+        };
+
+        self.r#type.get_or_insert_with(|| r#type.to_owned());
+        self.id.get_or_insert(segment & 0x00ff_ffff_ffff_ffff);
+    }
+}
+
+/// Application layer metadata of a record, from built-in or mapped custom fields.
+struct AppMetadata {
+    max_length: usize,
+    dns_query: Option<String>,
+    dns_qtype: Option<u16>,
+    http_host: Option<String>,
+    http_url: Option<String>,
+    http_status: Option<u16>,
+    tls_sni: Option<String>,
+    lossy_utf8: bool,
+}
+
+impl AppMetadata {
+    fn new(max_length: usize) -> Self {
+        Self {
+            max_length,
+            dns_query: None,
+            dns_qtype: None,
+            http_host: None,
+            http_url: None,
+            http_status: None,
+            tls_sni: None,
+            lossy_utf8: false,
+        }
+    }
+
+    /// Stores the raw data of a metadata field, returns `None` if the data
+    /// does not fit the field.
+    ///
+    /// Zero numbers are `None`, probes export them for flows of other protocols.
+    fn set(&mut self, field: MappedField, data: &[u8]) -> Option<()> {
+        let number = || {
+            parse_number(data)
+                .as_u16()
+                .map(|number| Some(number).filter(|&n| n != 0))
+        };
+
+        match field {
+            MappedField::DnsQuery => self.dns_query = self.text(data),
+            MappedField::DnsQtype => self.dns_qtype = number()?,
+            MappedField::HttpHost => self.http_host = self.text(data),
+            MappedField::HttpUrl => self.http_url = self.text(data),
+            MappedField::HttpStatus => self.http_status = number()?,
+            MappedField::TlsSni => self.tls_sni = self.text(data),
+            _ => return None,
+        }
+
+        Some(())
+    }
+
+    /// Decodes a string of up to `max_length` bytes, invalid UTF-8 is replaced.
+    ///
+    /// Fixed length fields are padded with zeros, empty strings are `None`.
+    fn text(&mut self, data: &[u8]) -> Option<String> {
+        let data = data.split(|&byte| byte == 0).next().unwrap_or_default();
+        if data.is_empty() {
+            return None;
+        }
+
+        let mut text = match String::from_utf8_lossy(data) {
+            Cow::Borrowed(text) => text.to_owned(),
+            Cow::Owned(text) => {
+                self.lossy_utf8 = true;
+                text
+            }
+        };
+        if text.len() > self.max_length {
+            let mut end = self.max_length;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            text.truncate(end);
+        }
+
+        Some(text)
+    }
+}
+
+/// Transformation of a decoded field value, see [`IpfixParser::with_transform`].
+pub type Transform = dyn for<'a> Fn(Value<'a>) -> Value<'a> + Send + Sync;
+
+#[derive(Clone)]
+pub struct IpfixParser {
+    custom_fields: Arc<CustomFields>,
+    options: Option<OptionsContext>,
+    prefer_inner: bool,
+    transforms: HashMap<u16, Arc<Transform>>,
+    max_metadata_length: usize,
+}
+
+impl IpfixParser {
+    pub fn new() -> Self {
+        Self::with_custom_fields(Arc::new(CustomFields::new()))
+    }
+
+    /// Creates a parser which additionally decodes `custom_fields` into [`Fluss::extra`].
+    pub fn with_custom_fields(custom_fields: Arc<CustomFields>) -> Self {
+        Self {
+            custom_fields,
+            options: None,
+            prefer_inner: false,
+            transforms: HashMap::new(),
+            max_metadata_length: DEFAULT_MAX_METADATA_LENGTH,
+        }
+    }
+
+    /// Completes flows with the options announced for their observation domain,
+    /// e.g. the sampling interval, `options` has to be shared with the session.
+    pub fn with_options(mut self, options: OptionsContext) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    /// Uses the inner header of tunneled flows as source and destination,
+    /// see [`Fluss::prefer_inner`].
+    pub fn with_prefer_inner(mut self, prefer_inner: bool) -> Self {
+        self.prefer_inner = prefer_inner;
+        self
+    }
+
+    /// Truncates the metadata strings of flows, e.g. [`Fluss::http_url`], to
+    /// `length` bytes, defaults to [`DEFAULT_MAX_METADATA_LENGTH`].
+    pub fn with_max_metadata_length(mut self, length: usize) -> Self {
+        self.max_metadata_length = length;
+        self
+    }
+
+    /// Transforms the decoded value of the IANA field `field_id` before it is
+    /// stored in the flow, e.g. to anonymize addresses or to bucket ports.
+    ///
+    /// A transformation replaces a previous one of the same field. Values which
+    /// do not fit the flow field anymore are skipped like malformed fields.
+    ///
+    /// ```ignore
+    /// // sourceTransportPort, everything except well known ports becomes 0
+    /// let parser = IpfixParser::new().with_transform(7, |value| match value.as_u16() {
+    ///     Some(port) if port < 1024 => value,
+    ///     _ => Value::U16(0),
+    /// });
+    /// ```
+    pub fn with_transform<F>(mut self, field_id: u16, transform: F) -> Self
+    where
+        F: for<'a> Fn(Value<'a>) -> Value<'a> + Send + Sync + 'static,
+    {
+        self.transforms.insert(field_id, Arc::new(transform));
+        self
+    }
+}
+
+impl Default for IpfixParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Resolves the custom field of every field and drops fields of other
+/// enterprises which are neither custom, reverse, ntop nor CERT fields.
+impl Compile for IpfixParser {
+    type Plan = DecodePlan<Option<CustomField>>;
+
+    fn compile(&self, fields: &[FieldSpecifier]) -> Self::Plan {
+        DecodePlan::new(fields, |field| {
+            let custom = self.custom_fields.get(field.enterprise_id, field.id);
+            match field.enterprise_id {
+                None | Some(IPFIX_REVERSE_PEN) | Some(NTOP_PEN) | Some(CERT_PEN) => {
+                    Some(custom.cloned())
+                }
+                Some(_) => custom.map(|custom| Some(custom.clone())),
+            }
+        })
+    }
+}
+
+impl Parser for IpfixParser {
+    type Output<'a> = Fluss;
+
+    fn parse_in_domain<'a>(
+        &self,
+        domain_id: u32,
+        fields: &[FieldSpecifier],
+        set: &DataSet<'a>,
+    ) -> Option<Self::Output<'a>> {
+        let fluss = self.parse(fields, set)?;
+        Some(self.complete(domain_id, fluss))
+    }
+
+    fn parse_planned<'a>(
+        &self,
+        plan: &Self::Plan,
+        domain_id: u32,
+        fields: &[FieldSpecifier],
+        set: &DataSet<'a>,
+    ) -> Option<Self::Output<'a>> {
+        let fluss = match plan.fields(set) {
+            Some(planned) => {
+                self.decode(planned.map(|(field, data, custom)| (field, data, custom.as_ref())))?
+            }
+            None => self.parse(fields, set)?,
+        };
+        Some(self.complete(domain_id, fluss))
+    }
+
+    fn parse<'a>(&self, fields: &[FieldSpecifier], set: &DataSet<'a>) -> Option<Self::Output<'a>> {
+        let mut truncated = None;
+        let fluss = self.decode(set.with_fields(fields).map_while(|field| match field {
+            Ok((field, data)) => {
+                let custom = self.custom_fields.get(field.enterprise_id, field.id);
+                Some((field, data, custom))
+            }
+            Err(err) => {
+                truncated = Some(err);
+                None
+            }
+        }));
+
+        // a flow of a partial record would lack the fields after the truncation
+        if let Some(err) = truncated {
+            tracing::debug!(template = set.id, %err, "dropping truncated record");
+            return None;
+        }
+        fluss
+    }
+
+    fn check_sanity(
+        &self,
+        checks: &SanityChecks,
+        fields: &[FieldSpecifier],
+        fluss: &mut Fluss,
+    ) -> bool {
+        match checks.check(fields, fluss) {
+            Some(suspicion) => {
+                tracing::debug!(%suspicion, "suspect flow");
+                fluss.suspect = true;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl IpfixParser {
+    /// Applies the transformation registered for the field `field_id` to `value`.
+    fn transform<'v>(&self, field_id: u16, value: Value<'v>) -> Value<'v> {
+        if self.transforms.is_empty() {
+            return value;
+        }
+        match self.transforms.get(&field_id) {
+            Some(transform) => transform(value),
+            None => value,
+        }
+    }
+
+    /// Completes `fluss` with the options announced for the observation domain `domain_id`.
+    fn complete(&self, domain_id: u32, mut fluss: Fluss) -> Fluss {
+        // the sampling interval of the record takes precedence over the announced one
+        if fluss.sampling_interval.is_none() {
+            fluss.sampling_interval = self
+                .options
+                .as_ref()
+                .and_then(|options| options.sampling_interval(domain_id))
+                .filter(|&interval| interval > 1)
+                .map(|interval| u32::try_from(interval).unwrap_or(u32::MAX));
+        }
+
+        fluss
+    }
+
+    /// Decodes a flow from the fields of a record and their custom field definitions.
+    fn decode<'f>(
+        &self,
+        fields: impl Iterator<Item = (&'f FieldSpecifier, &'f [u8], Option<&'f CustomField>)>,
+    ) -> Option<Fluss> {
+        let mut bytes_in = 0;
+        let mut bytes_out = 0;
+        let mut packets_in = 0;
+        let mut packets_out = 0;
+        // total counts of exporters reporting a flow once, e.g. YAF, used without delta counts
+        let (mut total_bytes, mut total_packets) = (None, None);
+        let (mut reverse_total_bytes, mut reverse_total_packets) = (None, None);
+        let (mut delta_counts, mut reverse_delta_counts) = (false, false);
+        let mut dscp = 0;
+        let mut flow_direction = FlowDirection::Unknown;
+        let mut ingress_interface = 0;
+        let mut egress_interface = 0;
+        let mut ethernet_type = 0;
+        let mut protocol = None;
+        let mut src_mac = None;
+        let mut dst_mac = None;
+        let mut src_addr = None;
+        let mut dst_addr = None;
+        let mut src_net = 0;
+        let mut dst_net = 0;
+        let mut src_port = None;
+        let mut dst_port = None;
+        let mut vlan_id = 0;
+        let mut post_vlan_id = 0;
+        let mut post_nat_src_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let mut post_nat_dst_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let mut post_napt_src_port = 0;
+        let mut post_napt_dst_port = 0;
+        let mut next_hop_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let mut icmp_type_code = None;
+        let mut sampling_interval = None;
+        let mut sampling_packet_interval = None;
+        let mut sampling_packet_space = None;
+        let mut tcp_flags = 0;
+        let mut tcp_flag_counts = 0;
+        let mut reverse_tcp_flags = 0;
+        let mut reverse_tcp_flag_counts = 0;
+        let mut biflow = false;
+        let mut flow_end_reason = None;
+        let mut layer2_segment = None;
+        let mut tunnel = Tunnel::default();
+        let mut metadata = AppMetadata::new(self.max_metadata_length);
+
+        let mut start_uptime = None;
+        let mut end_uptime = None;
+        let (mut start_seconds, mut end_seconds) = (None, None);
+        let (mut start_millis, mut end_millis) = (None, None);
+        let (mut start_micros, mut end_micros) = (None, None);
+        let (mut start_nanos, mut end_nanos) = (None, None);
+
+        let mut extra = BTreeMap::new();
+
+        for (field, data, custom) in fields {
+            // assigns the value if the field could be parsed, malformed fields are skipped
+            macro_rules! set {
+                ($target:ident = $value:expr) => {
+                    match $value {
+                        Some(value) => $target = value,
+                        None => tracing::trace!(?field, ?data, "skipping malformed field"),
+                    }
+                };
+                ($value:expr) => {
+                    if $value.is_none() {
+                        tracing::trace!(?field, ?data, "skipping malformed field");
+                    }
+                };
+            }
+
+            if let Some(target) = custom
+                .and_then(|custom| custom.map_to)
+                .filter(|target| target.is_metadata())
+            {
+                if metadata.set(target, data).is_none() {
+                    tracing::trace!(?field, ?data, ?target, "skipping mismatched custom field");
+                }
+            } else if let Some(custom) = custom {
+                let value = custom.r#type.decode(data);
+                match (value, custom.map_to) {
+                    (Some(value), Some(target)) => {
+                        if tunnel.set(target, value).is_none() {
+                            tracing::trace!(
+                                ?field,
+                                ?data,
+                                ?target,
+                                "skipping mismatched custom field"
+                            );
+                        }
+                    }
+                    (Some(value), None) => {
+                        extra.insert(custom.name.clone(), value);
+                    }
+                    (None, _) => tracing::trace!(?field, ?data, "skipping malformed custom field"),
+                }
+            }
+
+            match field.enterprise_id {
+                None => (),
+                Some(IPFIX_REVERSE_PEN) => {
+                    // reverse direction of a biflow, counts towards the out counters
+                    biflow = true;
+                    match field.id {
+                        IPFIX_BYTES_IN => {
+                            reverse_delta_counts = true;
+                            set!(bytes_out = parse_number(data).as_u64())
+                        }
+                        IPFIX_PACKETS_IN => {
+                            reverse_delta_counts = true;
+                            set!(packets_out = parse_number(data).as_u64())
+                        }
+                        IPFIX_OCTET_TOTAL_COUNT => {
+                            set!(reverse_total_bytes = parse_number(data).as_u64().map(Some))
+                        }
+                        IPFIX_PACKET_TOTAL_COUNT => {
+                            set!(reverse_total_packets = parse_number(data).as_u64().map(Some))
+                        }
+                        IPFIX_TCP_CONTROL_BITS => {
+                            set!(reverse_tcp_flags = parse_number(data).as_u16())
+                        }
+                        IPFIX_TCP_SYN_TOTAL_COUNT
+                        | IPFIX_TCP_FIN_TOTAL_COUNT
+                        | IPFIX_TCP_RST_TOTAL_COUNT
+                        | IPFIX_TCP_PSH_TOTAL_COUNT
+                        | IPFIX_TCP_ACK_TOTAL_COUNT
+                        | IPFIX_TCP_URG_TOTAL_COUNT => match parse_number(data).as_u64() {
+                            Some(0) => (),
+                            Some(_) => reverse_tcp_flag_counts |= tcp_flag_for_counter(field.id),
+                            None => tracing::trace!(?field, ?data, "skipping malformed field"),
+                        },
+                        _ => (),
+                    }
+                    continue;
+                }
+                Some(NTOP_PEN) => {
+                    let target = match field.id {
+                        NTOP_HTTP_URL => MappedField::HttpUrl,
+                        NTOP_HTTP_RET_CODE => MappedField::HttpStatus,
+                        NTOP_HTTP_HOST => MappedField::HttpHost,
+                        NTOP_TLS_SERVER_NAME => MappedField::TlsSni,
+                        NTOP_DNS_QUERY => MappedField::DnsQuery,
+                        NTOP_DNS_QUERY_TYPE => MappedField::DnsQtype,
+                        _ => continue,
+                    };
+                    set!(metadata.set(target, data));
+                    continue;
+                }
+                Some(CERT_PEN) => {
+                    let flags = parse_number(data).as_u16();
+                    match field.id {
+                        CERT_INITIAL_TCP_FLAGS | CERT_UNION_TCP_FLAGS => {
+                            set!(flags.map(|flags| tcp_flags |= flags))
+                        }
+                        CERT_REVERSE_INITIAL_TCP_FLAGS | CERT_REVERSE_UNION_TCP_FLAGS => {
+                            set!(flags.map(|flags| reverse_tcp_flags |= flags))
+                        }
+                        _ => (),
+                    }
+                    continue;
+                }
+                Some(_) => continue,
+            }
+
+            // TODO: better parsing to get rid of value wrapper
+            let transform = |value| self.transform(field.id, value);
+            match field.id {
+                IPFIX_BYTES_IN => {
+                    delta_counts = true;
+                    set!(bytes_in = transform(parse_number(data)).as_u64())
+                }
+                IPFIX_PACKETS_IN => {
+                    delta_counts = true;
+                    set!(packets_in = transform(parse_number(data)).as_u64())
+                }
+                IPFIX_OCTET_TOTAL_COUNT => {
+                    set!(total_bytes = transform(parse_number(data)).as_u64().map(Some))
+                }
+                IPFIX_PACKET_TOTAL_COUNT => {
+                    set!(total_packets = transform(parse_number(data)).as_u64().map(Some))
+                }
+                IPFIX_BYTES_OUT => set!(bytes_out = transform(parse_number(data)).as_u64()),
+                IPFIX_PACKETS_OUT => set!(packets_out = transform(parse_number(data)).as_u64()),
+
+                IPFIX_DSCP => set!(dscp = transform(parse_number(data)).as_u8()),
+
+                IPFIX_SAMPLING_INTERVAL => {
+                    set!(sampling_interval = transform(parse_number(data)).as_u32().map(Some))
+                }
+                IPFIX_SAMPLING_PACKET_INTERVAL => {
+                    set!(
+                        sampling_packet_interval = transform(parse_number(data)).as_u32().map(Some)
+                    )
+                }
+                IPFIX_SAMPLING_PACKET_SPACE => {
+                    set!(sampling_packet_space = transform(parse_number(data)).as_u32().map(Some))
+                }
+
+                IPFIX_FLOW_DIRECTION => set!(
+                    flow_direction =
+                        transform(parse_number(data))
+                            .as_u16()
+                            .map(|value| match value {
+                                0 => FlowDirection::Ingress,
+                                1 => FlowDirection::Egress,
+                                _ => FlowDirection::Unknown,
+                            })
+                ),
+
+                IPFIX_INGRESS_INTERFACE => {
+                    set!(ingress_interface = transform(parse_number(data)).as_u32())
+                }
+                IPFIX_EGRESS_INTERFACE => {
+                    set!(egress_interface = transform(parse_number(data)).as_u32())
+                }
+
+                IPFIX_ETHERNET_TYPE => set!(ethernet_type = transform(parse_number(data)).as_u16()),
+
+                IPFIX_FLOW_END_SYSUPTIME => {
+                    set!(
+                        end_uptime = transform(parse_number(data))
+                            .as_u64()
+                            .map(|millis| Some(Duration::from_millis(millis)))
+                    )
+                }
+                IPFIX_FLOW_START_SYSUPTIME => {
+                    set!(
+                        start_uptime = transform(parse_number(data))
+                            .as_u64()
+                            .map(|millis| Some(Duration::from_millis(millis)))
+                    )
+                }
+                IPFIX_FLOW_START_SECONDS => {
+                    set!(
+                        start_seconds = transform(parse_number(data))
+                            .as_u64()
+                            .and_then(from_secs)
+                            .map(Some)
+                    )
+                }
+                IPFIX_FLOW_END_SECONDS => {
+                    set!(
+                        end_seconds = transform(parse_number(data))
+                            .as_u64()
+                            .and_then(from_secs)
+                            .map(Some)
+                    )
+                }
+                IPFIX_FLOW_START_MILLISECONDS => {
+                    set!(
+                        start_millis = transform(parse_number(data))
+                            .as_u64()
+                            .and_then(from_millis)
+                            .map(Some)
+                    )
+                }
+                IPFIX_FLOW_END_MILLISECONDS => {
+                    set!(
+                        end_millis = transform(parse_number(data))
+                            .as_u64()
+                            .and_then(from_millis)
+                            .map(Some)
+                    )
+                }
+                IPFIX_FLOW_START_MICROSECONDS => {
+                    set!(
+                        start_micros = transform(parse_datetime_ntp_micro(data))
+                            .as_datetime()
+                            .map(|time| Some(*time))
+                    )
+                }
+                IPFIX_FLOW_END_MICROSECONDS => {
+                    set!(
+                        end_micros = transform(parse_datetime_ntp_micro(data))
+                            .as_datetime()
+                            .map(|time| Some(*time))
+                    )
+                }
+                IPFIX_FLOW_START_NANOSECONDS => {
+                    set!(
+                        start_nanos = transform(parse_datetime_ntp_nano(data))
+                            .as_datetime()
+                            .map(|time| Some(*time))
+                    )
+                }
+                IPFIX_FLOW_END_NANOSECONDS => {
+                    set!(
+                        end_nanos = transform(parse_datetime_ntp_nano(data))
+                            .as_datetime()
+                            .map(|time| Some(*time))
+                    )
+                }
+
+                IPFIX_PROTOCOL => {
+                    set!(
+                        protocol = transform(parse_number(data))
+                            .as_u8()
+                            .map(|p| Some(Protocol::from(p)))
+                    )
+                }
+
+                IPFIX_MAC_SRC => src_mac = transform(parse_mac(data)).as_mac6().copied(),
+                IPFIX_MAC_DST => dst_mac = transform(parse_mac(data)).as_mac6().copied(),
+
+                IPFIX_IPV4_SRC_ADDR => set!(
+                    src_addr = transform(parse_ipv4(data))
+                        .as_ipv4()
+                        .map(|addr| Some(IpAddr::V4(*addr)))
+                ),
+                IPFIX_IPV4_DST_ADDR => set!(
+                    dst_addr = transform(parse_ipv4(data))
+                        .as_ipv4()
+                        .map(|addr| Some(IpAddr::V4(*addr)))
+                ),
+
+                IPFIX_IPV4_SRC_MASK => set!(src_net = transform(parse_number(data)).as_u8()),
+                IPFIX_IPV4_DST_MASK => set!(dst_net = transform(parse_number(data)).as_u8()),
+
+                IPFIX_ICMP_TYPE_CODE_IPV4 => {
+                    set!(icmp_type_code = parse_icmp_type_code(data).map(Some))
+                }
+
+                // exporters send either the 1 byte (RFC 5102) or 2 byte (RFC 7125) encoding
+                IPFIX_TCP_CONTROL_BITS => set!(tcp_flags = transform(parse_number(data)).as_u16()),
+                IPFIX_TCP_SYN_TOTAL_COUNT
+                | IPFIX_TCP_FIN_TOTAL_COUNT
+                | IPFIX_TCP_RST_TOTAL_COUNT
+                | IPFIX_TCP_PSH_TOTAL_COUNT
+                | IPFIX_TCP_ACK_TOTAL_COUNT
+                | IPFIX_TCP_URG_TOTAL_COUNT => match transform(parse_number(data)).as_u64() {
+                    Some(0) => (),
+                    Some(_) => tcp_flag_counts |= tcp_flag_for_counter(field.id),
+                    None => tracing::trace!(?field, ?data, "skipping malformed field"),
+                },
+                IPFIX_FLOW_END_REASON => set!(
+                    flow_end_reason = transform(parse_number(data))
+                        .as_u8()
+                        .map(|reason| Some(FlowEndReason::from(reason)))
+                ),
+
+                IPFIX_SRC_PORT => set!(src_port = transform(parse_number(data)).as_u16().map(Some)),
+                IPFIX_DST_PORT => set!(dst_port = transform(parse_number(data)).as_u16().map(Some)),
+
+                IPFIX_VLAN_ID => set!(vlan_id = transform(parse_number(data)).as_u16()),
+                IPFIX_POST_VLAN_ID => set!(post_vlan_id = transform(parse_number(data)).as_u16()),
+
+                IPFIX_POST_NAT_IPV4_SRC_ADDR => set!(
+                    post_nat_src_addr = transform(parse_ipv4(data))
+                        .as_ipv4()
+                        .map(|addr| IpAddr::V4(*addr))
+                ),
+                IPFIX_POST_NAT_IPV4_DST_ADDR => set!(
+                    post_nat_dst_addr = transform(parse_ipv4(data))
+                        .as_ipv4()
+                        .map(|addr| IpAddr::V4(*addr))
+                ),
+
+                IPFIX_POST_NAPT_SRC_PORT => {
+                    set!(post_napt_src_port = transform(parse_number(data)).as_u16())
+                }
+                IPFIX_POST_NAPT_DST_PORT => {
+                    set!(post_napt_dst_port = transform(parse_number(data)).as_u16())
+                }
+
+                IPFIX_IPV4_NEXT_HOP => {
+                    set!(
+                        next_hop_addr = transform(parse_ipv4(data))
+                            .as_ipv4()
+                            .map(|addr| IpAddr::V4(*addr))
+                    )
+                }
+
+                IPFIX_LAYER2_SEGMENT_ID => {
+                    set!(layer2_segment = transform(parse_number(data)).as_u64().map(Some))
+                }
+
+                IPFIX_HTTP_STATUS_CODE => set!(metadata.set(MappedField::HttpStatus, data)),
+                IPFIX_HTTP_REQUEST_HOST => set!(metadata.set(MappedField::HttpHost, data)),
+                IPFIX_HTTP_REQUEST_TARGET => set!(metadata.set(MappedField::HttpUrl, data)),
+
+                _ => (),
+            }
+        }
+
+        // without any of the flow keys the record does not describe a flow
+        if protocol.is_none()
+            && src_addr.is_none()
+            && dst_addr.is_none()
+            && src_port.is_none()
+            && dst_port.is_none()
+        {
+            tracing::trace!("record without flow keys");
+            return None;
+        }
+
+        let protocol = protocol.unwrap_or_default();
+        let sampling_interval = match (sampling_packet_interval, sampling_packet_space) {
+            // `interval` packets are selected, the following `space` packets are skipped
+            (Some(interval), Some(space)) if interval > 0 => {
+                Some(interval.saturating_add(space) / interval)
+            }
+            _ => sampling_interval.or(sampling_packet_interval),
+        }
+        .filter(|&interval| interval > 1);
+        if !delta_counts {
+            bytes_in = total_bytes.unwrap_or(bytes_in);
+            packets_in = total_packets.unwrap_or(packets_in);
+        }
+        if !reverse_delta_counts {
+            bytes_out = reverse_total_bytes.unwrap_or(bytes_out);
+            packets_out = reverse_total_packets.unwrap_or(packets_out);
+        }
+        let tcp_flags = tcp_flags | tcp_flag_counts;
+        let reverse_tcp_flags = reverse_tcp_flags | reverse_tcp_flag_counts;
+        if let Some(segment) = layer2_segment {
+            tunnel.set_layer2_segment(segment);
+        }
+        let (icmp_type, icmp_code) = match (protocol, icmp_type_code) {
+            (Protocol::Icmp, Some((icmp_type, icmp_code))) => (Some(icmp_type), Some(icmp_code)),
+            _ => (None, None),
+        };
+
+        let times = FlowTimes {
+            start: [start_seconds, start_millis, start_micros, start_nanos],
+            end: [end_seconds, end_millis, end_micros, end_nanos],
+            start_uptime,
+            end_uptime,
+        };
+
+        let mut fluss = Fluss {
+            r#type: FlowType::IPFIX,
+            // the time of decoding, callers knowing the time of reception replace it
+            time_received: crate::clock::now().into(),
+            exporter: None,
+
+            flow_age: times.age(),
+            flow_start: times.flow_start(),
+            flow_end: times.flow_end(),
+            clock_skew_ms: None,
+            flow_direction,
+            is_bidirectional: biflow,
+
+            bytes: bytes_in.saturating_add(bytes_out),
+            packets: packets_in.saturating_add(packets_out),
+
+            bytes_in,
+            bytes_out,
+            packets_in,
+            packets_out,
+
+            sampling_interval,
+
+            dscp,
+
+            ingress_interface,
+            egress_interface,
+            ingress_interface_name: None,
+            egress_interface_name: None,
+
+            ethernet_type,
+            protocol,
+
+            src_mac,
+            dst_mac,
+
+            src_addr: src_addr.unwrap_or(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))),
+            dst_addr: dst_addr.unwrap_or(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))),
+
+            src_net,
+            dst_net,
+
+            src_port: src_port.unwrap_or_default(),
+            dst_port: dst_port.unwrap_or_default(),
+
+            icmp_type,
+            icmp_code,
+
+            vlan_id,
+            post_vlan_id,
+
+            post_nat_src_addr,
+            post_nat_dst_addr,
+
+            post_napt_src_port,
+            post_napt_dst_port,
+
+            next_hop_addr,
+
+            tunnel_type: tunnel.r#type,
+            tunnel_id: tunnel.id,
+            inner_src_addr: tunnel.src_addr,
+            inner_dst_addr: tunnel.dst_addr,
+            inner_src_port: tunnel.src_port,
+            inner_dst_port: tunnel.dst_port,
+            outer_src_addr: None,
+            outer_dst_addr: None,
+            outer_src_port: None,
+            outer_dst_port: None,
+
+            tcp_flags,
+            reverse_tcp_flags,
+            flow_end_reason,
+            flow_state: FlowState::classify(
+                protocol,
+                tcp_flags | reverse_tcp_flags,
+                flow_end_reason,
+            ),
+            flow_class: None,
+
+            service: None,
+            tenant: None,
+            src_net_name: None,
+            dst_net_name: None,
+
+            dns_query: metadata.dns_query,
+            dns_qtype: metadata.dns_qtype,
+            http_host: metadata.http_host,
+            http_url: metadata.http_url,
+            http_status: metadata.http_status,
+            tls_sni: metadata.tls_sni,
+            lossy_utf8: metadata.lossy_utf8,
+
+            suspect: false,
+
+            labels: BTreeMap::new(),
+            extra,
+        };
+
+        if self.prefer_inner {
+            fluss.prefer_inner();
+        }
+
+        Some(fluss)
+    }
+}
+
+/// Converts a `dateTimeSeconds` value, `None` if it is out of range.
+fn from_secs(secs: u64) -> Option<DateTime<Utc>> {
+    Utc.timestamp_opt(i64::try_from(secs).ok()?, 0).single()
+}
+
+/// Start and end timestamps of a record in all reported precisions.
+///
+/// The most precise timestamps are used, in the order nanoseconds,
+/// microseconds, milliseconds, seconds and sysUpTime. The age is computed
+/// from the most precise start and end of the same precision, sysUpTime is
+/// only used without absolute timestamps.
+#[derive(Debug, Default)]
+struct FlowTimes {
+    // seconds, milliseconds, microseconds and nanoseconds
+    start: [Option<DateTime<Utc>>; 4],
+    end: [Option<DateTime<Utc>>; 4],
+    start_uptime: Option<Duration>,
+    end_uptime: Option<Duration>,
+}
+
+impl FlowTimes {
+    fn flow_start(&self) -> Option<DateTime<Utc>> {
+        self.start.iter().rev().find_map(|&time| time)
+    }
+
+    fn flow_end(&self) -> Option<DateTime<Utc>> {
+        self.end.iter().rev().find_map(|&time| time)
+    }
+
+    fn age(&self) -> Duration {
+        let pair = self
+            .start
+            .iter()
+            .zip(&self.end)
+            .rev()
+            .find_map(|(&start, &end)| start.zip(end));
+
+        // exporters are not guaranteed to report a start before the end
+        match (pair, self.flow_start().zip(self.flow_end())) {
+            (Some((start, end)), _) | (None, Some((start, end))) => {
+                (end - start).to_std().unwrap_or_default()
+            }
+            (None, None) => match (self.start_uptime, self.end_uptime) {
+                (Some(start), Some(end)) => end.saturating_sub(start),
+                _ => Duration::ZERO,
+            },
+        }
+    }
+}
+
+/// Converts a `dateTimeMilliseconds` value, `None` if it is out of range.
+fn from_millis(millis: u64) -> Option<DateTime<Utc>> {
+    Utc.timestamp_millis_opt(i64::try_from(millis).ok()?)
+        .single()
+}
+
+/// Maps a per flag counter information element to the flag it counts.
+fn tcp_flag_for_counter(id: u16) -> u16 {
+    match id {
+        IPFIX_TCP_SYN_TOTAL_COUNT => tcp_flags::SYN,
+        IPFIX_TCP_FIN_TOTAL_COUNT => tcp_flags::FIN,
+        IPFIX_TCP_RST_TOTAL_COUNT => tcp_flags::RST,
+        IPFIX_TCP_PSH_TOTAL_COUNT => tcp_flags::PSH,
+        IPFIX_TCP_ACK_TOTAL_COUNT => tcp_flags::ACK,
+        IPFIX_TCP_URG_TOTAL_COUNT => tcp_flags::URG,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipfix::parser::{parse, TemplateRecord};
+    use crate::ipfix::Session;
+    use crate::produce::FieldType;
+    use crate::testing::{field, DataRecord, MessageBuilder};
+
+    fn reverse(id: u16, length: u16) -> FieldSpecifier {
+        FieldSpecifier {
+            id,
+            length,
+            enterprise_id: Some(IPFIX_REVERSE_PEN),
+        }
+    }
+
+    fn decode(parser: IpfixParser, template: TemplateRecord, records: &[DataRecord]) -> Vec<Fluss> {
+        let mut builder = MessageBuilder::new(1);
+        let session = Session::new(parser);
+        let templates = builder.templates(std::slice::from_ref(&template));
+        assert!(session
+            .parse(&parse(&templates).unwrap())
+            .unwrap()
+            .is_empty());
+        let data = builder.data(template.id, records);
+        session.parse(&parse(&data).unwrap()).unwrap()
+    }
+
+    fn flow_keys() -> DataRecord {
+        DataRecord::new()
+            .addr([10, 0, 0, 1].into())
+            .addr([10, 0, 0, 2].into())
+            .u8(6)
+    }
+
+    #[test]
+    fn biflow_fills_out_counters_from_reverse_fields() {
+        let template = TemplateRecord {
+            id: 256,
+            fields: vec![
+                field(IPFIX_IPV4_SRC_ADDR, 4),
+                field(IPFIX_IPV4_DST_ADDR, 4),
+                field(IPFIX_PROTOCOL, 1),
+                field(IPFIX_BYTES_IN, 8),
+                field(IPFIX_PACKETS_IN, 8),
+                reverse(IPFIX_BYTES_IN, 8),
+                reverse(IPFIX_PACKETS_IN, 8),
+                field(IPFIX_DSCP, 1),
+            ],
+        };
+        let record = flow_keys().u64(1000).u64(10).u64(500).u64(5).u8(46);
+
+        let flows = decode(IpfixParser::new(), template, &[record]);
+        assert_eq!(flows.len(), 1);
+        let fluss = &flows[0];
+        assert!(fluss.is_bidirectional);
+        assert_eq!((fluss.bytes_in, fluss.packets_in), (1000, 10));
+        assert_eq!((fluss.bytes_out, fluss.packets_out), (500, 5));
+        assert_eq!((fluss.bytes, fluss.packets), (1500, 15));
+        assert_eq!(fluss.dscp, 46);
+    }
+
+    #[test]
+    fn delta_counts_take_precedence_over_total_counts() {
+        let template = TemplateRecord {
+            id: 256,
+            fields: vec![
+                field(IPFIX_IPV4_SRC_ADDR, 4),
+                field(IPFIX_IPV4_DST_ADDR, 4),
+                field(IPFIX_PROTOCOL, 1),
+                field(IPFIX_OCTET_TOTAL_COUNT, 8),
+                field(IPFIX_BYTES_IN, 8),
+                field(IPFIX_PACKET_TOTAL_COUNT, 8),
+                field(IPFIX_PACKETS_IN, 8),
+                reverse(IPFIX_OCTET_TOTAL_COUNT, 8),
+                reverse(IPFIX_PACKET_TOTAL_COUNT, 8),
+            ],
+        };
+        // an idle interval of a long lived flow, the delta count is zero
+        let record = flow_keys()
+            .u64(90_000)
+            .u64(0)
+            .u64(70)
+            .u64(0)
+            .u64(40_000)
+            .u64(30);
+
+        let flows = decode(IpfixParser::new(), template, &[record]);
+        let fluss = &flows[0];
+        assert_eq!((fluss.bytes_in, fluss.packets_in), (0, 0));
+        assert_eq!((fluss.bytes_out, fluss.packets_out), (40_000, 30));
+    }
+
+    #[test]
+    fn out_counters_do_not_overwrite_in_counters() {
+        let template = TemplateRecord {
+            id: 256,
+            fields: vec![
+                field(IPFIX_IPV4_SRC_ADDR, 4),
+                field(IPFIX_IPV4_DST_ADDR, 4),
+                field(IPFIX_PROTOCOL, 1),
+                field(IPFIX_BYTES_IN, 4),
+                field(IPFIX_PACKETS_IN, 4),
+                field(IPFIX_BYTES_OUT, 4),
+                field(IPFIX_PACKETS_OUT, 4),
+            ],
+        };
+        let record = flow_keys().u32(1200).u32(12).u32(300).u32(3);
+
+        let flows = decode(IpfixParser::new(), template, &[record]);
+        let fluss = &flows[0];
+        assert!(!fluss.is_bidirectional);
+        assert_eq!((fluss.bytes_in, fluss.packets_in), (1200, 12));
+        assert_eq!((fluss.bytes_out, fluss.packets_out), (300, 3));
+        assert_eq!((fluss.bytes, fluss.packets), (1500, 15));
+        assert_eq!(fluss.dscp, 0);
+    }
+
+    #[test]
+    fn split_counters_are_serialized() {
+        let template = TemplateRecord {
+            id: 256,
+            fields: vec![
+                field(IPFIX_IPV4_SRC_ADDR, 4),
+                field(IPFIX_IPV4_DST_ADDR, 4),
+                field(IPFIX_PROTOCOL, 1),
+                field(IPFIX_BYTES_IN, 8),
+                reverse(IPFIX_BYTES_IN, 8),
+                field(IPFIX_DSCP, 1),
+            ],
+        };
+        let record = flow_keys().u64(100).u64(40).u8(10);
+
+        let flows = decode(IpfixParser::new(), template, &[record]);
+        let json = serde_json::to_value(&flows[0]).unwrap();
+        assert_eq!(json["bytes"], 140);
+        assert_eq!(json["bytes_in"], 100);
+        assert_eq!(json["bytes_out"], 40);
+        assert_eq!(json["dscp"], 10);
+    }
+
+    fn key_fields() -> Vec<FieldSpecifier> {
+        vec![
+            field(IPFIX_IPV4_SRC_ADDR, 4),
+            field(IPFIX_IPV4_DST_ADDR, 4),
+            field(IPFIX_PROTOCOL, 1),
+        ]
+    }
+
+    #[test]
+    fn short_record_is_dropped() {
+        let mut fields = key_fields();
+        fields.push(field(IPFIX_BYTES_IN, 8));
+        let record = flow_keys().u32(1000).into_bytes();
+
+        let parser = IpfixParser::new();
+        for length in 0..record.len() {
+            let set = DataSet {
+                id: 256,
+                data: &record[..length],
+            };
+            assert!(parser.parse(&fields, &set).is_none(), "{} bytes", length);
+        }
+    }
+
+    #[test]
+    fn three_byte_counters() {
+        let mut fields = key_fields();
+        fields.push(field(IPFIX_BYTES_IN, 3));
+        fields.push(field(IPFIX_PACKETS_IN, 3));
+        let template = TemplateRecord { id: 256, fields };
+        let record = flow_keys().bytes(&[0x01, 0x02, 0x03]).bytes(&[0, 0, 7]);
+
+        let flows = decode(IpfixParser::new(), template, &[record]);
+        assert_eq!(flows.len(), 1);
+        assert_eq!((flows[0].bytes, flows[0].packets), (0x010203, 7));
+    }
+
+    #[test]
+    fn reduced_size_counters() {
+        let cases: [(u16, &[u8], u64); 5] = [
+            (4, &[0xff; 4], 4294967295),
+            (5, &[0x01, 0, 0, 0, 0x02], 0x01_0000_0002),
+            (6, &[0xff; 6], 0xffff_ffff_ffff),
+            (7, &[0x01, 2, 3, 4, 5, 6, 7], 0x01_0203_0405_0607),
+            (8, &[0xff; 8], u64::MAX),
+        ];
+        for (length, data, expected) in cases {
+            let mut fields = key_fields();
+            fields.push(field(IPFIX_BYTES_IN, length));
+            fields.push(field(IPFIX_PACKETS_IN, length));
+            let template = TemplateRecord { id: 256, fields };
+            let record = flow_keys().bytes(data).bytes(data);
+
+            let flows = decode(IpfixParser::new(), template, &[record]);
+            assert_eq!(flows.len(), 1, "{} bytes", length);
+            assert_eq!((flows[0].bytes, flows[0].packets), (expected, expected));
+        }
+    }
+
+    #[test]
+    fn oversized_counters_are_skipped() {
+        let mut fields = key_fields();
+        fields.push(field(IPFIX_BYTES_IN, 9));
+        fields.push(field(IPFIX_PACKETS_IN, 4));
+        let template = TemplateRecord { id: 256, fields };
+        let record = flow_keys().bytes(&[1; 9]).u32(7);
+
+        let flows = decode(IpfixParser::new(), template, &[record]);
+        assert_eq!(flows.len(), 1);
+        assert_eq!((flows[0].bytes, flows[0].packets), (0, 7));
+    }
+
+    #[test]
+    fn malformed_field_is_skipped() {
+        let mut fields = key_fields();
+        fields.push(field(IPFIX_MAC_SRC, 4));
+        fields.push(field(IPFIX_BYTES_IN, 4));
+        let template = TemplateRecord { id: 256, fields };
+        let record = flow_keys().bytes(&[0xaa; 4]).u32(1000);
+
+        let flows = decode(IpfixParser::new(), template, &[record]);
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].src_mac, None);
+        assert_eq!(flows[0].bytes, 1000);
+    }
+
+    #[test]
+    fn reversed_timestamps() {
+        let mut fields = key_fields();
+        fields.push(field(IPFIX_FLOW_START_MILLISECONDS, 8));
+        fields.push(field(IPFIX_FLOW_END_MILLISECONDS, 8));
+        fields.push(field(IPFIX_FLOW_START_SYSUPTIME, 4));
+        fields.push(field(IPFIX_FLOW_END_SYSUPTIME, 4));
+        let template = TemplateRecord { id: 256, fields };
+        let start = 1_700_000_005_000;
+        let end = 1_700_000_000_000;
+        let record = flow_keys().u64(start).u64(end).u32(9000).u32(4000);
+
+        let flows = decode(IpfixParser::new(), template, &[record]);
+        assert_eq!(flows.len(), 1);
+        let fluss = &flows[0];
+        assert_eq!(fluss.flow_age, Duration::ZERO);
+        assert_eq!(fluss.flow_start, from_millis(start));
+        assert_eq!(fluss.flow_end, from_millis(end));
+    }
+
+    #[test]
+    fn tcp_control_bits_of_both_widths() {
+        for length in [1, 2] {
+            let mut fields = key_fields();
+            fields.push(field(IPFIX_TCP_CONTROL_BITS, length));
+            fields.push(field(IPFIX_FLOW_END_REASON, 1));
+            let template = TemplateRecord { id: 256, fields };
+            let flags = match length {
+                1 => DataRecord::new().u8(0x12),
+                _ => DataRecord::new().u16(0x0112),
+            };
+            let record = flow_keys().bytes(&flags.into_bytes()).u8(1);
+
+            let flows = decode(IpfixParser::new(), template, &[record]);
+            let fluss = &flows[0];
+            let expected = if length == 1 { 0x12 } else { 0x0112 };
+            assert_eq!(fluss.tcp_flags, expected);
+            assert_eq!(fluss.flow_end_reason, Some(FlowEndReason::IdleTimeout));
+            assert_eq!(fluss.flow_state, Some(FlowState::Established));
+
+            let json = serde_json::to_value(fluss).unwrap();
+            assert_eq!(json["flow_end_reason"], "idle-timeout");
+            assert_eq!(json["flow_state"], "established");
+        }
+    }
+
+    #[test]
+    fn tcp_flag_counters() {
+        let mut fields = key_fields();
+        fields.push(field(IPFIX_TCP_SYN_TOTAL_COUNT, 8));
+        fields.push(field(IPFIX_TCP_FIN_TOTAL_COUNT, 8));
+        fields.push(field(IPFIX_TCP_RST_TOTAL_COUNT, 8));
+        let template = TemplateRecord { id: 256, fields };
+        let record = flow_keys().u64(1).u64(0).u64(3);
+
+        let flows = decode(IpfixParser::new(), template, &[record]);
+        assert_eq!(flows[0].tcp_flags, tcp_flags::SYN | tcp_flags::RST);
+        assert_eq!(flows[0].flow_state, Some(FlowState::Reset));
+    }
+
+    #[test]
+    fn planned_flows_match_unplanned() {
+        let mut custom_fields = CustomFields::new();
+        custom_fields
+            .add(CustomField {
+                pen: Some(29305),
+                id: 40,
+                name: "vendor".to_owned(),
+                r#type: FieldType::Number,
+                map_to: None,
+            })
+            .unwrap();
+        let parser = IpfixParser::with_custom_fields(Arc::new(custom_fields));
+
+        let pen = |enterprise_id, id, length| FieldSpecifier {
+            id,
+            length,
+            enterprise_id: Some(enterprise_id),
+        };
+        let mut fields = key_fields();
+        fields.extend([
+            field(IPFIX_BYTES_IN, 8),
+            field(IPFIX_PACKETS_IN, 8),
+            field(IPFIX_SRC_PORT, 2),
+            field(IPFIX_DST_PORT, 2),
+            field(IPFIX_TCP_CONTROL_BITS, 1),
+            field(IPFIX_FLOW_START_MILLISECONDS, 8),
+            field(IPFIX_FLOW_END_MILLISECONDS, 8),
+            field(IPFIX_FLOW_END_REASON, 1),
+            // unassigned
+            field(32000, 4),
+            reverse(IPFIX_BYTES_IN, 8),
+            reverse(IPFIX_PACKETS_IN, 8),
+            pen(CERT_PEN, 14, 1),
+            pen(CERT_PEN, 16, 1),
+            // custom and unrelated enterprise fields
+            pen(29305, 40, 4),
+            pen(29305, 41, 4),
+        ]);
+        let record = flow_keys()
+            .u64(1500)
+            .u64(3)
+            .u16(52000)
+            .u16(443)
+            .u8(0x1b)
+            .u64(1_700_000_000_000)
+            .u64(1_700_000_005_000)
+            .u8(3)
+            .u32(7)
+            .u64(9000)
+            .u64(8)
+            .u8(0x02)
+            .u8(0x12)
+            .u32(42)
+            .u32(43)
+            .into_bytes();
+
+        let plan = parser.compile(&fields);
+        let decode = |data: &[u8]| {
+            let set = DataSet { id: 256, data };
+            let planned = parser.parse_planned(&plan, 1, &fields, &set);
+            let unplanned = parser.parse_in_domain(1, &fields, &set);
+            (planned, unplanned)
+        };
+
+        let (planned, unplanned) = decode(&record);
+        let (mut planned, unplanned) = (planned.unwrap(), unplanned.unwrap());
+        planned.time_received = unplanned.time_received;
+        assert_eq!(planned, unplanned);
+        assert_eq!((planned.bytes_out, planned.packets_out), (9000, 8));
+        assert_eq!(planned.extra["vendor"], 42);
+
+        for length in [0, 8, record.len() - 1] {
+            assert_eq!(decode(&record[..length]), (None, None), "{} bytes", length);
+        }
+    }
+}
@@ -0,0 +1,5 @@
+mod custom;
+mod ipfix;
+
+pub use self::custom::{CustomField, CustomFields, FieldType, MappedField};
+pub use self::ipfix::{IpfixParser, Transform, DEFAULT_MAX_METADATA_LENGTH};
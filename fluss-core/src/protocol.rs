@@ -0,0 +1,637 @@
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use macaddr::{MacAddr6, MacAddr8};
+use nom::number::complete::{be_u16, be_u32, be_u64, be_u8};
+use nom::IResult;
+use serde::Serialize;
+use serde_with::rust::display_fromstr;
+use serde_with::{serde_as, DurationMilliSeconds};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::{self, Write};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+/// Seconds between the NTP epoch (1900-01-01) and the unix epoch.
+const NTP_UNIX_OFFSET: i64 = 2_208_988_800;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Record<'a> {
+    pub id: u16,
+    pub value: Value<'a>,
+}
+
+impl<'a> Record<'a> {
+    pub fn new(id: u16, value: Value<'a>) -> Self {
+        Self { id, value }
+    }
+
+    /// Copies borrowed data, the returned record no longer borrows the packet.
+    pub fn into_owned(self) -> Record<'static> {
+        Record::new(self.id, self.value.into_owned())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordSet<'a> {
+    pub id: u16,
+    pub records: Vec<Record<'a>>,
+}
+
+/// A [`RecordSet`] which does not borrow from the packet.
+pub type RecordSetOwned = RecordSet<'static>;
+
+impl<'a> RecordSet<'a> {
+    pub fn new(id: u16, records: Vec<Record<'a>>) -> Self {
+        Self { id, records }
+    }
+
+    /// Copies borrowed data, the returned record set no longer borrows the packet.
+    pub fn into_owned(self) -> RecordSetOwned {
+        RecordSet::new(
+            self.id,
+            self.records.into_iter().map(Record::into_owned).collect(),
+        )
+    }
+}
+
+#[serde_as]
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum Value<'a> {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    /// Serialized as a number if it fits into 64 bits, otherwise as a decimal string.
+    #[serde(serialize_with = "serialize_u128")]
+    U128(u128),
+    I64(i64),
+    Boolean(bool),
+    Bytes(Cow<'a, [u8]>),
+    String(String),
+    Ipv4Addr(Ipv4Addr),
+    Ipv6Addr(Ipv6Addr),
+    #[serde(with = "display_fromstr")]
+    MacAddr6(MacAddr6),
+    #[serde(with = "display_fromstr")]
+    MacAddr8(MacAddr8),
+    DateTime(DateTime<Utc>),
+    Duration(#[serde_as(as = "DurationMilliSeconds")] Duration),
+    Unknown(Cow<'a, [u8]>),
+}
+
+impl<'a> fmt::Display for Value<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::U8(val) => write!(f, "{}", val),
+            Self::U16(val) => write!(f, "{}", val),
+            Self::U32(val) => write!(f, "{}", val),
+            Self::U64(val) => write!(f, "{}", val),
+            Self::U128(val) => write!(f, "{}", val),
+            Self::I64(val) => write!(f, "{}", val),
+            Self::Boolean(val) => write!(f, "{}", val),
+            Self::Bytes(val) => write!(f, "{:?}", val),
+            Self::String(val) => write!(f, "{}", val),
+            Self::Ipv4Addr(val) => write!(f, "{}", val),
+            Self::Ipv6Addr(val) => write!(f, "{}", val),
+            Self::MacAddr6(val) => write!(f, "{}", val),
+            Self::MacAddr8(val) => write!(f, "{}", val),
+            Self::DateTime(val) => write!(f, "{}", to_rfc3339(val)),
+            Self::Duration(val) => write!(f, "{}ms", val.as_millis()),
+            Self::Unknown(val) => write!(f, "{:?}", val),
+        }
+    }
+}
+
+/// Reason why data can not be parsed as a value of its type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// The data is not as long as values of the type.
+    InvalidLength { expected: usize, got: usize },
+    /// A boolean is neither 1 (true) nor 2 (false).
+    InvalidBoolean(u8),
+    /// A string is not valid UTF-8.
+    InvalidUtf8(std::str::Utf8Error),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLength { expected, got } => {
+                write!(f, "invalid length {}, expected {}", got, expected)
+            }
+            Self::InvalidBoolean(val) => write!(f, "invalid boolean {}", val),
+            Self::InvalidUtf8(err) => write!(f, "invalid string: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+macro_rules! val_as {
+    ($name:ident, $type:ident) => {
+        val_as!($name, $type, $type);
+    };
+    ($name:ident, $type:ty, $ident:ident) => {
+        pub fn $name(&self) -> Option<&$type> {
+            match self {
+                Self::$ident(val) => Some(val),
+                _ => None,
+            }
+        }
+    };
+}
+
+impl<'a> Value<'a> {
+    /// The number if it fits, regardless of the length it was encoded with.
+    pub fn as_u8(&self) -> Option<u8> {
+        self.as_u64().and_then(|val| u8::try_from(val).ok())
+    }
+
+    /// The number if it fits, regardless of the length it was encoded with.
+    pub fn as_u16(&self) -> Option<u16> {
+        self.as_u64().and_then(|val| u16::try_from(val).ok())
+    }
+
+    /// The number if it fits, regardless of the length it was encoded with.
+    pub fn as_u32(&self) -> Option<u32> {
+        self.as_u64().and_then(|val| u32::try_from(val).ok())
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Self::U8(val) => Some(*val as u64),
+            Self::U16(val) => Some(*val as u64),
+            Self::U32(val) => Some(*val as u64),
+            Self::U64(val) => Some(*val),
+            Self::U128(val) => u64::try_from(*val).ok(),
+            Self::I64(val) => u64::try_from(*val).ok(),
+            _ => None,
+        }
+    }
+
+    /// The number if it fits, signed or not.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::I64(val) => Some(*val),
+            value => value.as_u128().and_then(|val| i64::try_from(val).ok()),
+        }
+    }
+
+    /// The number, regardless of the length it was encoded with.
+    pub fn as_u128(&self) -> Option<u128> {
+        match self {
+            Self::U128(val) => Some(*val),
+            value => value.as_u64().map(u128::from),
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Boolean(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Bytes(val) => Some(val),
+            Self::Unknown(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    /// Copies borrowed data, the returned value no longer borrows the packet.
+    pub fn into_owned(self) -> Value<'static> {
+        match self {
+            Self::U8(val) => Value::U8(val),
+            Self::U16(val) => Value::U16(val),
+            Self::U32(val) => Value::U32(val),
+            Self::U64(val) => Value::U64(val),
+            Self::U128(val) => Value::U128(val),
+            Self::I64(val) => Value::I64(val),
+            Self::Boolean(val) => Value::Boolean(val),
+            Self::Bytes(val) => Value::Bytes(Cow::Owned(val.into_owned())),
+            Self::String(val) => Value::String(val),
+            Self::Ipv4Addr(val) => Value::Ipv4Addr(val),
+            Self::Ipv6Addr(val) => Value::Ipv6Addr(val),
+            Self::MacAddr6(val) => Value::MacAddr6(val),
+            Self::MacAddr8(val) => Value::MacAddr8(val),
+            Self::DateTime(val) => Value::DateTime(val),
+            Self::Duration(val) => Value::Duration(val),
+            Self::Unknown(val) => Value::Unknown(Cow::Owned(val.into_owned())),
+        }
+    }
+
+    val_as!(as_string, String);
+    val_as!(as_ipv4, Ipv4Addr);
+    val_as!(as_ipv6, Ipv6Addr);
+    val_as!(as_mac6, MacAddr6);
+    val_as!(as_mac8, MacAddr8);
+    val_as!(as_datetime, DateTime<Utc>, DateTime);
+    val_as!(as_duration, Duration);
+}
+
+impl<'a> From<&Value<'a>> for serde_json::Value {
+    fn from(value: &Value<'a>) -> Self {
+        match value {
+            Value::U8(val) => (*val).into(),
+            Value::U16(val) => (*val).into(),
+            Value::U32(val) => (*val).into(),
+            Value::U64(val) => (*val).into(),
+            // without arbitrary precision JSON numbers hold at most 64 bits
+            Value::U128(val) => match u64::try_from(*val) {
+                Ok(val) => val.into(),
+                Err(_) => val.to_string().into(),
+            },
+            Value::I64(val) => (*val).into(),
+            Value::Boolean(val) => (*val).into(),
+            Value::String(val) => val.as_str().into(),
+            Value::Bytes(val) | Value::Unknown(val) => to_hex(val).into(),
+            Value::DateTime(val) => to_rfc3339(val).into(),
+            Value::Duration(val) => (val.as_millis() as u64).into(),
+            value => value.to_string().into(),
+        }
+    }
+}
+
+impl<'a> From<&RecordSet<'a>> for HashMap<String, serde_json::Value> {
+    /// Keys are the ids of the fields, use [`crate::ipfix::Session::to_json`] for named keys.
+    fn from(record_set: &RecordSet<'a>) -> Self {
+        record_set
+            .records
+            .iter()
+            .map(|record| (record.id.to_string(), (&record.value).into()))
+            .collect()
+    }
+}
+
+impl<'a> From<RecordSet<'a>> for HashMap<String, serde_json::Value> {
+    fn from(record_set: RecordSet<'a>) -> Self {
+        (&record_set).into()
+    }
+}
+
+// `serde_json::to_value` fails on numbers beyond 64 bits
+fn serialize_u128<S: serde::Serializer>(val: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+    match u64::try_from(*val) {
+        Ok(val) => serializer.serialize_u64(val),
+        Err(_) => serializer.collect_str(val),
+    }
+}
+
+/// Formats `data` as lowercase hex string.
+pub fn to_hex(data: &[u8]) -> String {
+    let mut hex = String::with_capacity(data.len() * 2);
+    data.iter().for_each(|b| write!(hex, "{:02x}", b).unwrap());
+    hex
+}
+
+macro_rules! val_from {
+    ($type:ty, $ident:ident) => {
+        impl<'a> From<$type> for Value<'a> {
+            fn from(value: $type) -> Self {
+                Self::$ident(value)
+            }
+        }
+    };
+}
+
+val_from!(u8, U8);
+val_from!(u16, U16);
+val_from!(u32, U32);
+val_from!(u64, U64);
+val_from!(u128, U128);
+val_from!(bool, Boolean);
+
+impl<'a> From<&'a [u8]> for Value<'a> {
+    fn from(value: &'a [u8]) -> Self {
+        Self::Bytes(Cow::Borrowed(value))
+    }
+}
+
+val_from!(String, String);
+val_from!(Ipv4Addr, Ipv4Addr);
+val_from!(Ipv6Addr, Ipv6Addr);
+val_from!(DateTime<Utc>, DateTime);
+val_from!(Duration, Duration);
+
+fn read_u8(input: &[u8]) -> IResult<&[u8], u8> {
+    be_u8(input)
+}
+
+fn read_u16(input: &[u8]) -> IResult<&[u8], u16> {
+    be_u16(input)
+}
+
+fn read_u32(input: &[u8]) -> IResult<&[u8], u32> {
+    be_u32(input)
+}
+
+fn read_u64(input: &[u8]) -> IResult<&[u8], u64> {
+    be_u64(input)
+}
+
+// TODO: parse errors and remaining data
+pub fn parse_u8(input: &[u8]) -> Value<'_> {
+    read_u8(input).map_or(Value::Unknown(input.into()), |val| val.1.into())
+}
+
+pub fn parse_u16(input: &[u8]) -> Value<'_> {
+    read_u16(input).map_or(Value::Unknown(input.into()), |val| val.1.into())
+}
+
+pub fn parse_u32(input: &[u8]) -> Value<'_> {
+    read_u32(input).map_or(Value::Unknown(input.into()), |val| val.1.into())
+}
+
+pub fn parse_u64(input: &[u8]) -> Value<'_> {
+    read_u64(input).map_or(Value::Unknown(input.into()), |val| val.1.into())
+}
+
+/// Parses an unsigned integer of exactly 16 bytes, e.g. an `unsigned128` element.
+pub fn parse_u128(input: &[u8]) -> Value<'_> {
+    match <[u8; 16]>::try_from(input) {
+        Ok(bytes) => Value::U128(u128::from_be_bytes(bytes)),
+        Err(_) => Value::Unknown(input.into()),
+    }
+}
+
+/// Reads an unsigned integer of 1 to 8 bytes in network byte order.
+///
+/// Covers the reduced size encoding (RFC 7011 6.2), e.g. an 8 byte counter
+/// sent in 3 bytes. Returns `None` for empty or longer input.
+pub fn read_unsigned(input: &[u8]) -> Option<u64> {
+    match input.len() {
+        1..=8 => Some(input.iter().fold(0, |acc, &b| acc << 8 | b as u64)),
+        _ => None,
+    }
+}
+
+/// Parses an unsigned integer of any length [`read_unsigned`] accepts, into
+/// the smallest value which holds all numbers of that length. 16 bytes are
+/// parsed by [`parse_u128`].
+pub fn parse_number(input: &[u8]) -> Value<'_> {
+    if input.len() == 16 {
+        return parse_u128(input);
+    }
+
+    match read_unsigned(input) {
+        Some(val) => match input.len() {
+            1 => Value::U8(val as u8),
+            2 => Value::U16(val as u16),
+            3 | 4 => Value::U32(val as u32),
+            _ => Value::U64(val),
+        },
+        None => Value::Unknown(input.into()),
+    }
+}
+
+/// Parses a signed integer of 1 to 8 bytes in two's complement, shorter
+/// encodings are sign extended (RFC 7011 6.2).
+pub fn parse_signed(input: &[u8]) -> Value<'_> {
+    match read_unsigned(input) {
+        Some(val) => {
+            let shift = 64 - 8 * input.len() as u32;
+            // the sign bit of the encoded length is moved to the top and back
+            Value::I64(((val << shift) as i64) >> shift)
+        }
+        None => Value::Unknown(input.into()),
+    }
+}
+
+/// Parses a `boolean` (RFC 7011 6.1.5), invalid values are unknown values.
+pub fn parse_boolean(input: &[u8]) -> Value<'_> {
+    try_parse_boolean(input).unwrap_or_else(|_| Value::Unknown(input.into()))
+}
+
+/// Like [`parse_boolean`] but returns why the value is invalid.
+///
+/// True is encoded as 1 and false as 2, other values are invalid.
+pub fn try_parse_boolean(input: &[u8]) -> Result<Value<'_>, ParseError> {
+    match input {
+        [1] => Ok(Value::Boolean(true)),
+        [2] => Ok(Value::Boolean(false)),
+        &[val] => Err(ParseError::InvalidBoolean(val)),
+        _ => Err(ParseError::InvalidLength {
+            expected: 1,
+            got: input.len(),
+        }),
+    }
+}
+
+pub fn parse_bytes(input: &[u8]) -> Value<'_> {
+    Value::Bytes(input.into())
+}
+
+/// Parses an IPv4 address, other lengths than 4 bytes are unknown values.
+pub fn parse_ipv4(input: &[u8]) -> Value<'_> {
+    match <[u8; 4]>::try_from(input) {
+        Ok(octets) => Value::Ipv4Addr(octets.into()),
+        Err(_) => Value::Unknown(input.into()),
+    }
+}
+
+/// Parses an IPv6 address, other lengths than 16 bytes are unknown values.
+pub fn parse_ipv6(input: &[u8]) -> Value<'_> {
+    match <[u8; 16]>::try_from(input) {
+        Ok(octets) => Value::Ipv6Addr(octets.into()),
+        Err(_) => Value::Unknown(input.into()),
+    }
+}
+
+pub fn parse_mac6(input: &[u8]) -> Value<'_> {
+    match <[u8; 6]>::try_from(input) {
+        Ok(octets) => Value::MacAddr6(octets.into()),
+        Err(_) => Value::Unknown(input.into()),
+    }
+}
+
+pub fn parse_mac8(input: &[u8]) -> Value<'_> {
+    match <[u8; 8]>::try_from(input) {
+        Ok(octets) => Value::MacAddr8(octets.into()),
+        Err(_) => Value::Unknown(input.into()),
+    }
+}
+
+pub fn parse_mac(input: &[u8]) -> Value<'_> {
+    match input.len() {
+        6 => parse_mac6(input),
+        8 => parse_mac8(input),
+        _ => Value::Unknown(input.into()),
+    }
+}
+
+/// Parses a `dateTimeSeconds`, seconds since the unix epoch.
+pub fn parse_datetime_seconds(input: &[u8]) -> Value<'_> {
+    read_u32(input)
+        .ok()
+        .and_then(|(_, secs)| Utc.timestamp_opt(secs.into(), 0).single())
+        .map_or(Value::Unknown(input.into()), Value::DateTime)
+}
+
+/// Parses a `dateTimeMilliseconds`, milliseconds since the unix epoch.
+pub fn parse_datetime_millis(input: &[u8]) -> Value<'_> {
+    read_u64(input)
+        .ok()
+        .and_then(|(_, millis)| i64::try_from(millis).ok())
+        .and_then(|millis| Utc.timestamp_millis_opt(millis).single())
+        .map_or(Value::Unknown(input.into()), Value::DateTime)
+}
+
+/// Parses a `dateTimeMicroseconds` in the NTP 32.32 fixed point format.
+///
+/// The lowest 11 bits of the fraction are ignored (RFC 7011 6.1.9).
+pub fn parse_datetime_ntp_micro(input: &[u8]) -> Value<'_> {
+    parse_ntp(input, !0x7ff)
+}
+
+/// Parses a `dateTimeNanoseconds` in the NTP 32.32 fixed point format.
+pub fn parse_datetime_ntp_nano(input: &[u8]) -> Value<'_> {
+    parse_ntp(input, !0)
+}
+
+fn parse_ntp(input: &[u8], fraction_mask: u32) -> Value<'_> {
+    let ntp = match read_u64(input) {
+        Ok((_, ntp)) => ntp,
+        Err(_) => return Value::Unknown(input.into()),
+    };
+
+    let secs = (ntp >> 32) as i64 - NTP_UNIX_OFFSET;
+    let fraction = (ntp as u32 & fraction_mask) as u64;
+    // the fraction is in units of 2^-32 seconds
+    let nanos = (fraction * 1_000_000_000) >> 32;
+
+    Utc.timestamp_opt(secs, nanos as u32)
+        .single()
+        .map_or(Value::Unknown(input.into()), Value::DateTime)
+}
+
+/// Parses a duration in milliseconds.
+pub fn parse_duration_millis(input: &[u8]) -> Value<'_> {
+    parse_number(input)
+        .as_u64()
+        .map_or(Value::Unknown(input.into()), |millis| {
+            Value::Duration(Duration::from_millis(millis))
+        })
+}
+
+/// Parses a duration in microseconds.
+pub fn parse_duration_micros(input: &[u8]) -> Value<'_> {
+    parse_number(input)
+        .as_u64()
+        .map_or(Value::Unknown(input.into()), |micros| {
+            Value::Duration(Duration::from_micros(micros))
+        })
+}
+
+fn to_rfc3339(datetime: &DateTime<Utc>) -> String {
+    datetime.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+/// Splits an `icmpTypeCodeIPv4`/`icmpTypeCodeIPv6` into type and code.
+pub fn parse_icmp_type_code(input: &[u8]) -> Option<(u8, u8)> {
+    read_u16(input)
+        .ok()
+        .map(|(_, type_code)| ((type_code >> 8) as u8, type_code as u8))
+}
+
+/// Parses a UTF-8 string, invalid strings are unknown values.
+pub fn parse_string(input: &[u8]) -> Value<'_> {
+    try_parse_string(input).unwrap_or_else(|_| Value::Unknown(input.into()))
+}
+
+/// Like [`parse_string`] but returns why the string is invalid.
+pub fn try_parse_string(input: &[u8]) -> Result<Value<'_>, ParseError> {
+    match std::str::from_utf8(input) {
+        Ok(string) => Ok(Value::String(string.to_owned())),
+        Err(err) => Err(ParseError::InvalidUtf8(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsigned_numbers_of_every_length() {
+        assert_eq!(read_unsigned(&[]), None);
+        assert_eq!(read_unsigned(&[0x12]), Some(0x12));
+        assert_eq!(read_unsigned(&[0x01, 0x02, 0x03]), Some(0x010203));
+        assert_eq!(read_unsigned(&[0xff; 4]), Some(4294967295));
+        assert_eq!(read_unsigned(&[0x01, 0, 0, 0, 0]), Some(1 << 32));
+        assert_eq!(read_unsigned(&[0xff; 6]), Some((1 << 48) - 1));
+        assert_eq!(
+            read_unsigned(&[0x7f, 0, 0, 0, 0, 0, 1]),
+            Some(0x7f << 48 | 1)
+        );
+        assert_eq!(read_unsigned(&[0xff; 8]), Some(u64::MAX));
+        assert_eq!(read_unsigned(&[0; 9]), None);
+    }
+
+    #[test]
+    fn reduced_size_numbers() {
+        assert!(matches!(
+            parse_number(&[0x01, 0x02, 0x03]),
+            Value::U32(0x010203)
+        ));
+        assert!(matches!(parse_number(&[0xff; 4]), Value::U32(u32::MAX)));
+        assert_eq!(parse_number(&[0xff; 4]).as_u64(), Some(4294967295));
+        assert!(matches!(
+            parse_number(&[0x01, 0, 0, 0, 0]),
+            Value::U64(0x01_0000_0000)
+        ));
+        assert!(matches!(
+            parse_number(&[0xff; 6]),
+            Value::U64(0xffff_ffff_ffff)
+        ));
+        assert!(matches!(
+            parse_number(&[0x01, 2, 3, 4, 5, 6, 7]),
+            Value::U64(0x01_0203_0405_0607)
+        ));
+        assert!(matches!(parse_number(&[0xff; 16]), Value::U128(u128::MAX)));
+        // other lengths are kept as bytes instead of panicking
+        assert!(matches!(parse_number(&[]), Value::Unknown(_)));
+        assert!(matches!(parse_number(&[0; 9]), Value::Unknown(_)));
+    }
+
+    #[test]
+    fn addresses_of_the_wrong_length() {
+        assert!(matches!(parse_ipv4(&[10, 0, 0, 1]), Value::Ipv4Addr(_)));
+        assert!(matches!(parse_ipv6(&[0; 16]), Value::Ipv6Addr(_)));
+        assert!(matches!(parse_mac(&[0; 6]), Value::MacAddr6(_)));
+        assert!(matches!(parse_mac(&[0; 8]), Value::MacAddr8(_)));
+        for length in [0, 3, 5, 15, 17] {
+            let data = vec![1; length];
+            assert!(matches!(parse_ipv4(&data), Value::Unknown(_)), "{}", length);
+            assert!(matches!(parse_ipv6(&data), Value::Unknown(_)), "{}", length);
+            assert!(matches!(parse_mac(&data), Value::Unknown(_)), "{}", length);
+        }
+    }
+
+    #[test]
+    fn strings() {
+        let value = try_parse_string(b"eth0").unwrap();
+        assert_eq!(value.to_string(), "eth0");
+        assert_eq!(try_parse_string(b"").unwrap().to_string(), "");
+        let value = try_parse_string("Gig0/1 → core".as_bytes()).unwrap();
+        assert_eq!(value.to_string(), "Gig0/1 → core");
+
+        let err = try_parse_string(b"eth\xff0").unwrap_err();
+        match err {
+            ParseError::InvalidUtf8(err) => assert_eq!(err.valid_up_to(), 3),
+            err => panic!("unexpected error {:?}", err),
+        }
+        assert_eq!(
+            err.to_string(),
+            "invalid string: invalid utf-8 sequence of 1 bytes from index 3"
+        );
+
+        // the extractor keeps the raw bytes of invalid strings
+        assert!(matches!(parse_string(b"eth0"), Value::String(string) if string == "eth0"));
+        assert!(
+            matches!(parse_string(b"eth\xff0"), Value::Unknown(data) if *data == b"eth\xff0"[..])
+        );
+    }
+}
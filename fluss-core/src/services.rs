@@ -0,0 +1,83 @@
+use crate::fluss::Protocol;
+
+// generated by the build script from the IANA service name registry,
+// sorted by `(protocol, port)`
+include!(concat!(env!("OUT_DIR"), "/service_names.rs"));
+
+/// Returns the IANA service name registered for `port` and `protocol`.
+pub fn service_name(port: u16, protocol: Protocol) -> Option<&'static str> {
+    let protocol = u8::from(protocol);
+
+    SERVICE_NAMES
+        .binary_search_by_key(&(protocol, port), |&(protocol, port, _)| (protocol, port))
+        .ok()
+        .map(|index| SERVICE_NAMES[index].2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const REGISTRY: &str = include_str!("../data/service-names-port-numbers.csv");
+
+    /// Reads the primary service name of every port from the registry the table is generated from.
+    fn registry() -> HashMap<(u8, u16), &'static str> {
+        let mut services = HashMap::new();
+        for line in REGISTRY.lines().skip(1) {
+            // name, port and protocol are never quoted
+            let mut columns = line.splitn(4, ',');
+            let (name, ports, protocol) = match (columns.next(), columns.next(), columns.next()) {
+                (Some(name), Some(ports), Some(protocol)) if !name.is_empty() => {
+                    (name, ports, protocol)
+                }
+                _ => continue,
+            };
+            let protocol = match protocol {
+                "tcp" => 6,
+                "udp" => 17,
+                "dccp" => 33,
+                "sctp" => 132,
+                _ => continue,
+            };
+
+            let (start, end) = ports.split_once('-').unwrap_or((ports, ports));
+            for port in start.parse().unwrap()..=end.parse().unwrap() {
+                services.entry((protocol, port)).or_insert(name);
+            }
+        }
+        services
+    }
+
+    #[test]
+    fn table_matches_registry() {
+        let registry = registry();
+        assert_eq!(SERVICE_NAMES.len(), registry.len());
+        for (&(protocol, port), &name) in &registry {
+            assert_eq!(
+                service_name(port, Protocol::from(protocol)),
+                Some(name),
+                "{}/{}",
+                port,
+                protocol
+            );
+        }
+    }
+
+    #[test]
+    fn table_is_sorted() {
+        assert!(SERVICE_NAMES
+            .windows(2)
+            .all(|window| (window[0].0, window[0].1) < (window[1].0, window[1].1)));
+    }
+
+    #[test]
+    fn lookup() {
+        assert_eq!(service_name(443, Protocol::Tcp), Some("https"));
+        assert_eq!(service_name(53, Protocol::Udp), Some("domain"));
+        // ranges are expanded
+        assert_eq!(service_name(6063, Protocol::Tcp), Some("x11"));
+        // reserved ports have no name
+        assert_eq!(service_name(0, Protocol::Tcp), None);
+    }
+}
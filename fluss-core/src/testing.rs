@@ -0,0 +1,302 @@
+//! A minimal IPFIX exporter, to exercise a collector end to end.
+//!
+//! Messages are built from [`TemplateRecord`]s and records of raw field values
+//! and sent from a real UDP socket, so they pass through the same path as
+//! messages of a real exporter.
+
+use crate::fluss::Fluss;
+use crate::ipfix::parser::{parse, FieldSpecifier, OptionsTemplateRecord, TemplateRecord};
+use crate::ipfix::Session;
+use crate::produce::IpfixParser;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+
+const IPFIX_VERSION: u16 = 10;
+const TEMPLATE_SET_ID: u16 = 2;
+const OPTIONS_TEMPLATE_SET_ID: u16 = 3;
+
+/// The values of a data record in template order, encoded in network byte order.
+#[derive(Debug, Clone, Default)]
+pub struct DataRecord {
+    data: Vec<u8>,
+}
+
+impl DataRecord {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn u8(mut self, value: u8) -> Self {
+        self.data.push(value);
+        self
+    }
+
+    pub fn u16(self, value: u16) -> Self {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    pub fn u32(self, value: u32) -> Self {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    pub fn u64(self, value: u64) -> Self {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    pub fn addr(self, addr: IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(addr) => self.bytes(&addr.octets()),
+            IpAddr::V6(addr) => self.bytes(&addr.octets()),
+        }
+    }
+
+    /// Appends the bytes as is, e.g. for fields of an unusual length.
+    pub fn bytes(mut self, value: &[u8]) -> Self {
+        self.data.extend_from_slice(value);
+        self
+    }
+    /// The encoded record, e.g. for a [`DataSet`](crate::ipfix::parser::DataSet) of a single record.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Builds the messages of a single exporter and observation domain.
+///
+/// The sequence number counts the exported data records like required by RFC 7011.
+#[derive(Debug, Clone)]
+pub struct MessageBuilder {
+    observation_domain_id: u32,
+    sequence_number: u32,
+    export_time: u32,
+}
+
+impl MessageBuilder {
+    pub fn new(observation_domain_id: u32) -> Self {
+        Self {
+            observation_domain_id,
+            sequence_number: 0,
+            export_time: 0,
+        }
+    }
+
+    /// Sets the export time in seconds since the epoch, defaults to 0.
+    pub fn set_export_time(&mut self, export_time: u32) {
+        self.export_time = export_time;
+    }
+
+    pub fn set_sequence_number(&mut self, sequence_number: u32) {
+        self.sequence_number = sequence_number;
+    }
+
+    /// Builds a message with a template set containing all templates.
+    pub fn templates(&mut self, templates: &[TemplateRecord]) -> Vec<u8> {
+        let mut set = Vec::new();
+        for template in templates {
+            put_u16(&mut set, template.id);
+            put_u16(&mut set, template.fields.len() as u16);
+            for field in &template.fields {
+                put_field_specifier(&mut set, field);
+            }
+        }
+
+        self.message(&[(TEMPLATE_SET_ID, set)], 0)
+    }
+
+    /// Builds a message with an options template set containing all templates.
+    pub fn options_templates(&mut self, templates: &[OptionsTemplateRecord]) -> Vec<u8> {
+        let mut set = Vec::new();
+        for template in templates {
+            put_u16(&mut set, template.id);
+            put_u16(&mut set, template.fields.len() as u16);
+            put_u16(&mut set, template.scope_field_count);
+            for field in &template.fields {
+                put_field_specifier(&mut set, field);
+            }
+        }
+
+        self.message(&[(OPTIONS_TEMPLATE_SET_ID, set)], 0)
+    }
+
+    /// Builds a message with a data set of the template containing all records.
+    pub fn data(&mut self, template_id: u16, records: &[DataRecord]) -> Vec<u8> {
+        let set = records
+            .iter()
+            .flat_map(|record| record.data.iter())
+            .copied();
+
+        self.message(&[(template_id, set.collect())], records.len() as u32)
+    }
+
+    /// Builds a message with a data set followed by the template set defining
+    /// it, like sent by some Arista devices.
+    pub fn data_before_template(
+        &mut self,
+        template: &TemplateRecord,
+        records: &[DataRecord],
+    ) -> Vec<u8> {
+        let data = records
+            .iter()
+            .flat_map(|record| record.data.iter())
+            .copied()
+            .collect();
+
+        let mut set = Vec::new();
+        put_u16(&mut set, template.id);
+        put_u16(&mut set, template.fields.len() as u16);
+        for field in &template.fields {
+            put_field_specifier(&mut set, field);
+        }
+
+        self.message(
+            &[(template.id, data), (TEMPLATE_SET_ID, set)],
+            records.len() as u32,
+        )
+    }
+
+    fn message(&mut self, sets: &[(u16, Vec<u8>)], record_count: u32) -> Vec<u8> {
+        let length = 16 + sets.iter().map(|(_, data)| 4 + data.len()).sum::<usize>();
+
+        let mut message = Vec::with_capacity(length);
+        put_u16(&mut message, IPFIX_VERSION);
+        put_u16(&mut message, length as u16);
+        put_u32(&mut message, self.export_time);
+        put_u32(&mut message, self.sequence_number);
+        put_u32(&mut message, self.observation_domain_id);
+        for (id, data) in sets {
+            put_u16(&mut message, *id);
+            put_u16(&mut message, 4 + data.len() as u16);
+            message.extend_from_slice(data);
+        }
+
+        self.sequence_number = self.sequence_number.wrapping_add(record_count);
+        message
+    }
+}
+
+/// Sends messages from its own UDP socket, every simulator appears as a separate exporter.
+pub struct ExporterSimulator {
+    socket: UdpSocket,
+    collector: SocketAddr,
+    builder: MessageBuilder,
+}
+
+impl ExporterSimulator {
+    /// Binds an ephemeral port on the loopback interface matching the collector address.
+    pub fn new(collector: SocketAddr, observation_domain_id: u32) -> io::Result<Self> {
+        let local: SocketAddr = match collector {
+            SocketAddr::V4(_) => ([127, 0, 0, 1], 0).into(),
+            SocketAddr::V6(_) => (std::net::Ipv6Addr::LOCALHOST, 0).into(),
+        };
+
+        Self::bind(local, collector, observation_domain_id)
+    }
+
+    /// Binds `local`, e.g. another loopback address to appear as a different host.
+    pub fn bind(
+        local: SocketAddr,
+        collector: SocketAddr,
+        observation_domain_id: u32,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind(local)?,
+            collector,
+            builder: MessageBuilder::new(observation_domain_id),
+        })
+    }
+
+    /// Address the collector sees as the exporter address.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    pub fn builder(&mut self) -> &mut MessageBuilder {
+        &mut self.builder
+    }
+
+    pub fn send_templates(&mut self, templates: &[TemplateRecord]) -> io::Result<()> {
+        let message = self.builder.templates(templates);
+        self.send_raw(&message)
+    }
+
+    pub fn send_options_templates(
+        &mut self,
+        templates: &[OptionsTemplateRecord],
+    ) -> io::Result<()> {
+        let message = self.builder.options_templates(templates);
+        self.send_raw(&message)
+    }
+
+    pub fn send_data(&mut self, template_id: u16, records: &[DataRecord]) -> io::Result<()> {
+        let message = self.builder.data(template_id, records);
+        self.send_raw(&message)
+    }
+
+    /// Sends the datagram unchanged, e.g. a malformed message.
+    pub fn send_raw(&self, datagram: &[u8]) -> io::Result<()> {
+        self.socket.send_to(datagram, self.collector).map(|_| ())
+    }
+}
+
+/// Creates a field specifier of an IANA information element.
+pub fn field(id: u16, length: u16) -> FieldSpecifier {
+    FieldSpecifier {
+        id,
+        length,
+        enterprise_id: None,
+    }
+}
+
+/// Decodes a TCP flow from `src` to `dst:dst_port`, like a flow received from an exporter.
+///
+/// Other fields of the flow are at their defaults and can be changed by the caller.
+pub fn flow(src: Ipv4Addr, dst: Ipv4Addr, dst_port: u16, bytes: u64, packets: u64) -> Fluss {
+    let template = TemplateRecord {
+        id: 256,
+        fields: vec![
+            field(8, 4),
+            field(12, 4),
+            field(4, 1),
+            field(11, 2),
+            field(1, 8),
+            field(2, 8),
+        ],
+    };
+    let record = DataRecord::new()
+        .addr(src.into())
+        .addr(dst.into())
+        .u8(6)
+        .u16(dst_port)
+        .u64(bytes)
+        .u64(packets);
+
+    let session = Session::new(IpfixParser::new());
+    let mut builder = MessageBuilder::new(0);
+    let templates = builder.templates(std::slice::from_ref(&template));
+    session.parse(&parse(&templates).unwrap()).unwrap();
+    let data = builder.data(template.id, &[record]);
+    let mut flows = session.parse(&parse(&data).unwrap()).unwrap();
+    flows.pop().expect("flow of the record")
+}
+
+fn put_field_specifier(buf: &mut Vec<u8>, field: &FieldSpecifier) {
+    match field.enterprise_id {
+        Some(enterprise_id) => {
+            put_u16(buf, field.id | 0x8000);
+            put_u16(buf, field.length);
+            put_u32(buf, enterprise_id);
+        }
+        None => {
+            put_u16(buf, field.id);
+            put_u16(buf, field.length);
+        }
+    }
+}
+
+fn put_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_be_bytes());
+}
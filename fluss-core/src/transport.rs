@@ -0,0 +1,18 @@
+//! Hooks into the reception of datagrams.
+
+use std::net::SocketAddr;
+
+/// Receives every datagram before it is parsed, e.g. to record the traffic
+/// of exporters for a later replay.
+///
+/// Datagrams are passed in the order they were received, including
+/// datagrams which turn out to be malformed.
+pub trait PacketSink {
+    /// Handles a datagram received from `addr`.
+    fn raw_packet(&self, addr: SocketAddr, data: &[u8]) -> anyhow::Result<()>;
+
+    /// Writes out buffered datagrams, called before the collector exits.
+    fn flush(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
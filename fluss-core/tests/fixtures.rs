@@ -0,0 +1,357 @@
+//! Decodes the messages of `tests/fixtures`, see its README for their origin.
+
+use fluss_core::fluss::{FlowEndReason, FlowState, Fluss};
+use fluss_core::ipfix::parser::Set;
+use fluss_core::ipfix::{parse_all, Decoded, OptionsContext, Session};
+use fluss_core::produce::IpfixParser;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::Duration;
+
+/// Reads a hexdump of one message per line.
+fn fixture(name: &str) -> Vec<Vec<u8>> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    let content = std::fs::read_to_string(&path).unwrap();
+
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let line = line.trim();
+            (0..line.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&line[i..i + 2], 16).unwrap())
+                .collect()
+        })
+        .collect()
+}
+
+/// Decodes all messages of a fixture in order with a single session.
+fn decode_flows(name: &str, parser: IpfixParser) -> Vec<Fluss> {
+    let session = Session::new(parser);
+    let mut flows = Vec::new();
+    for message in fixture(name) {
+        for packet in parse_all(&message).unwrap() {
+            flows.extend(session.parse(&packet).unwrap());
+        }
+    }
+    flows
+}
+
+/// The number of sets of every message and the field count of every template of a fixture.
+fn layout(name: &str) -> (Vec<usize>, Vec<(u16, usize)>) {
+    let mut sets = Vec::new();
+    let mut templates = Vec::new();
+    for message in fixture(name) {
+        for packet in parse_all(&message).unwrap() {
+            sets.push(packet.sets.len());
+            for set in &packet.sets {
+                if let Set::TemplateSet(records) = set {
+                    templates.extend(
+                        records
+                            .iter()
+                            .map(|record| (record.id, record.fields.len())),
+                    );
+                }
+            }
+        }
+    }
+    (sets, templates)
+}
+
+fn addr(addr: &str) -> IpAddr {
+    addr.parse().unwrap()
+}
+
+#[test]
+fn biflow() {
+    let flows = decode_flows("biflow.hex", IpfixParser::new());
+    assert_eq!(flows.len(), 2);
+
+    let https = &flows[0];
+    assert!(https.is_bidirectional);
+    assert_eq!(https.dst_port, 443);
+    assert_eq!((https.bytes_in, https.packets_in), (4200, 12));
+    assert_eq!((https.bytes_out, https.packets_out), (98000, 70));
+    assert_eq!((https.bytes, https.packets), (102_200, 82));
+    assert_eq!(https.dscp, 46);
+
+    let dns = &flows[1];
+    assert_eq!(dns.dst_port, 53);
+    assert_eq!((dns.bytes_in, dns.bytes_out), (64, 180));
+    assert_eq!(dns.dscp, 0);
+}
+
+#[test]
+fn yaf() {
+    let (sets, templates) = layout("yaf.hex");
+    assert_eq!(sets, [1, 1]);
+    assert_eq!(templates, [(0xb800, 27)]);
+
+    let flows = decode_flows("yaf.hex", IpfixParser::new());
+    assert_eq!(flows.len(), 2);
+
+    // total counts of both directions, the flags of a direction are the initial and the union flags
+    let https = &flows[0];
+    assert!(https.is_bidirectional);
+    assert_eq!(https.src_addr, addr("192.0.2.30"));
+    assert_eq!(https.dst_port, 443);
+    assert_eq!((https.bytes_in, https.packets_in), (4200, 12));
+    assert_eq!((https.bytes_out, https.packets_out), (98000, 70));
+    assert_eq!((https.bytes, https.packets), (102_200, 82));
+    assert_eq!(https.tcp_flags, 0x1b);
+    assert_eq!(https.reverse_tcp_flags, 0x1b);
+    assert_eq!(https.flow_end_reason, Some(FlowEndReason::EndOfFlow));
+    assert_eq!(https.flow_state, Some(FlowState::FinClosed));
+    assert_eq!(https.flow_age, Duration::from_secs(18));
+
+    let dns = &flows[1];
+    assert!(dns.is_bidirectional);
+    assert_eq!(dns.dst_port, 53);
+    assert_eq!((dns.bytes_in, dns.bytes_out), (64, 180));
+    assert_eq!((dns.packets_in, dns.packets_out), (1, 1));
+    assert_eq!(dns.flow_state, None);
+}
+
+#[test]
+fn options() {
+    let options = OptionsContext::new();
+    let session =
+        Session::new(IpfixParser::new().with_options(options.clone())).with_options(options);
+
+    let messages = fixture("options.hex");
+    let packets: Vec<_> = messages
+        .iter()
+        .flat_map(|message| parse_all(message).unwrap())
+        .collect();
+    let decoded: Vec<_> = packets
+        .iter()
+        .flat_map(|packet| session.parse_with_options(packet).unwrap())
+        .collect();
+    assert_eq!(decoded.len(), 2);
+
+    let record = match &decoded[0] {
+        Decoded::Options(record) => record,
+        Decoded::Flow(flow) => panic!("expected an options record, got {:?}", flow),
+    };
+    assert_eq!(record.template_id, 512);
+    let scope: Vec<_> = record.scope.iter().map(|record| record.id).collect();
+    let values: Vec<_> = record.options.iter().map(|record| record.id).collect();
+    assert_eq!(scope, [149]);
+    assert_eq!(values, [34, 35, 42]);
+    assert_eq!(record.get(149).and_then(|value| value.as_u64()), Some(7));
+    assert_eq!(
+        record.get(42).and_then(|value| value.as_u64()),
+        Some(123_456)
+    );
+    assert_eq!(record.sampling_interval(), Some(100));
+
+    // the flow template has no sampling fields, the interval comes from the options record
+    let flow = match &decoded[1] {
+        Decoded::Flow(flow) => flow,
+        Decoded::Options(record) => panic!("expected a flow, got {:?}", record),
+    };
+    assert_eq!(flow.dst_port, 443);
+    assert_eq!(flow.bytes, 1500);
+    assert_eq!(flow.sampling_interval, Some(100));
+}
+
+#[test]
+fn cisco() {
+    let (sets, templates) = layout("cisco.hex");
+    assert_eq!(sets, [2, 1, 1]);
+    assert_eq!(templates, [(256, 13)]);
+
+    let session = Session::new(IpfixParser::new());
+    let messages = fixture("cisco.hex");
+    let packets: Vec<_> = messages
+        .iter()
+        .flat_map(|message| parse_all(message).unwrap())
+        .collect();
+    let mut interfaces = Vec::new();
+    let mut flows = Vec::new();
+    for packet in &packets {
+        for decoded in session.parse_with_options(packet).unwrap() {
+            match decoded {
+                Decoded::Options(record) => {
+                    assert_eq!(record.template_id, 261);
+                    let scope: Vec<_> = record.scope.iter().map(|record| record.id).collect();
+                    let values: Vec<_> = record.options.iter().map(|record| record.id).collect();
+                    assert_eq!((scope, values), (vec![10], vec![82, 83]));
+                    interfaces.push(record.scope[0].value.as_u32().unwrap());
+                }
+                Decoded::Flow(flow) => flows.push(flow),
+            }
+        }
+    }
+
+    assert_eq!(interfaces, [1, 2, 7]);
+    assert_eq!(flows.len(), 3);
+    let interfaces: Vec<_> = flows
+        .iter()
+        .map(|flow| (flow.ingress_interface, flow.egress_interface))
+        .collect();
+    assert_eq!(interfaces, [(2, 1), (1, 2), (7, 3)]);
+    assert_eq!((flows[1].bytes, flows[1].packets), (64000, 48));
+}
+
+#[test]
+fn softflowd() {
+    let (sets, templates) = layout("softflowd.hex");
+    assert_eq!(sets, [1, 2]);
+    assert_eq!(templates, [(1024, 13), (2048, 13)]);
+
+    let flows = decode_flows("softflowd.hex", IpfixParser::new());
+    assert_eq!(flows.len(), 2);
+    assert_eq!(flows[0].src_addr, addr("192.0.2.10"));
+    assert_eq!(flows[0].dst_port, 22);
+    assert_eq!((flows[0].bytes, flows[0].packets), (5120, 40));
+    assert_eq!(flows[0].flow_age, Duration::from_secs(60));
+    assert_eq!(flows[1].dst_port, 443);
+    assert_eq!((flows[1].bytes, flows[1].packets), (900, 6));
+}
+
+#[test]
+fn nprobe() {
+    // the padding of the template set is no template
+    let (sets, templates) = layout("nprobe.hex");
+    assert_eq!(sets, [2]);
+    assert_eq!(templates, [(257, 18)]);
+
+    let flows = decode_flows("nprobe.hex", IpfixParser::new());
+    assert_eq!(flows.len(), 2);
+    assert_eq!(flows[0].dst_addr, addr("203.0.113.5"));
+    assert_eq!(flows[0].dst_port, 80);
+    assert_eq!((flows[0].bytes, flows[0].packets), (12000, 14));
+    assert_eq!(flows[1].dst_port, 443);
+}
+
+#[test]
+fn fortigate() {
+    // two padded template sets and a padded message
+    let (sets, templates) = layout("fortigate.hex");
+    assert_eq!(sets, [3]);
+    assert_eq!(templates, [(262, 17), (263, 13)]);
+
+    let flows = decode_flows("fortigate.hex", IpfixParser::new());
+    assert_eq!(flows.len(), 1);
+    assert_eq!(flows[0].src_addr, addr("10.1.1.20"));
+    assert_eq!(flows[0].post_nat_src_addr, addr("203.0.113.2"));
+    assert_eq!((flows[0].bytes, flows[0].packets), (64000, 80));
+    assert_eq!(flows[0].flow_age, Duration::from_secs(29));
+}
+
+#[test]
+fn mikrotik() {
+    // the padding of the data set is no record
+    let (sets, templates) = layout("mikrotik.hex");
+    assert_eq!(sets, [1, 1]);
+    assert_eq!(templates, [(256, 20)]);
+
+    let flows = decode_flows("mikrotik.hex", IpfixParser::new());
+    assert_eq!(flows.len(), 2);
+    assert_eq!(flows[0].dst_addr, addr("1.1.1.1"));
+    assert_eq!(flows[0].dst_port, 53);
+    assert_eq!((flows[0].bytes, flows[0].packets), (120, 2));
+    assert_eq!(flows[1].bytes, 240);
+}
+
+#[test]
+fn arista() {
+    // the data set comes before the template set defining it
+    let messages = fixture("arista.hex");
+    let packet = &parse_all(&messages[0]).unwrap()[0];
+    assert!(matches!(packet.sets[0], Set::DataSet(_)));
+    assert!(matches!(packet.sets[1], Set::TemplateSet(_)));
+    let (sets, templates) = layout("arista.hex");
+    assert_eq!(sets, [2, 1]);
+    assert_eq!(templates, [(256, 15)]);
+
+    let flows = decode_flows("arista.hex", IpfixParser::new());
+    assert_eq!(flows.len(), 3);
+    assert_eq!(flows[0].src_addr, addr("10.20.0.11"));
+    assert_eq!(flows[0].dst_port, 443);
+    assert_eq!((flows[0].bytes, flows[0].packets), (48000, 40));
+    assert_eq!(flows[0].vlan_id, 200);
+    assert_eq!(
+        (flows[0].ingress_interface, flows[0].egress_interface),
+        (17, 49)
+    );
+    assert_eq!(flows[0].flow_end_reason, Some(FlowEndReason::EndOfFlow));
+    assert_eq!(flows[1].dst_addr, addr("198.51.100.53"));
+    assert_eq!(flows[1].dst_port, 53);
+    assert_eq!(flows[1].flow_end_reason, Some(FlowEndReason::IdleTimeout));
+    // the template of the first message decodes the following ones
+    assert_eq!((flows[2].bytes, flows[2].packets), (9000, 8));
+    assert_eq!(flows[2].flow_age, Duration::from_secs(5));
+}
+
+#[test]
+fn nprobe_metadata() {
+    let (sets, templates) = layout("nprobe_metadata.hex");
+    assert_eq!(sets, [1, 1, 1]);
+    assert_eq!(templates, [(258, 13), (259, 11)]);
+
+    let flows = decode_flows("nprobe_metadata.hex", IpfixParser::new());
+    assert_eq!(flows.len(), 6);
+
+    let dns = &flows[0];
+    assert_eq!(dns.dns_query.as_deref(), Some("www.example.com"));
+    assert_eq!(dns.dns_qtype, Some(1));
+    assert_eq!(
+        (dns.http_host.as_ref(), dns.http_url.as_ref()),
+        (None, None)
+    );
+    assert_eq!(dns.tls_sni, None);
+    assert!(!dns.lossy_utf8);
+
+    // the invalid byte is replaced and the flow is flagged
+    let lossy = &flows[1];
+    assert_eq!(lossy.dns_query.as_deref(), Some("b\u{fffd}cher.example"));
+    assert_eq!(lossy.dns_qtype, Some(28));
+    assert!(lossy.lossy_utf8);
+
+    let http = &flows[2];
+    assert_eq!(http.dns_query, None);
+    assert_eq!(http.http_host.as_deref(), Some("www.example.org"));
+    assert_eq!(http.http_url.as_deref(), Some("/index.html?q=1"));
+    assert_eq!(http.http_status, Some(200));
+    assert!(!http.lossy_utf8);
+
+    // a URL of 600 bytes with a three byte length prefix is truncated
+    let long = &flows[3];
+    let url = long.http_url.as_deref().unwrap();
+    assert_eq!(url.len(), 512);
+    assert!(url.starts_with("/search?q=aaa"));
+    assert_eq!(long.http_status, Some(414));
+    assert_eq!(long.bytes, 1800);
+
+    let tls = &flows[4];
+    assert_eq!(tls.dst_port, 443);
+    assert_eq!(tls.tls_sni.as_deref(), Some("api.example.net"));
+    assert_eq!(tls.http_status, None);
+
+    // IANA HTTP elements and a zero padded fixed length server name
+    let iana = &flows[5];
+    assert_eq!(iana.http_status, Some(404));
+    assert_eq!(iana.http_host.as_deref(), Some("shop.example.com"));
+    assert_eq!(iana.http_url.as_deref(), Some("/cart"));
+    assert_eq!(iana.tls_sni.as_deref(), Some("cdn.example.com"));
+
+    let json = serde_json::to_value(lossy).unwrap();
+    assert_eq!(json["dns_query"], "b\u{fffd}cher.example");
+    assert_eq!(json["lossy_utf8"], true);
+
+    let flows = decode_flows(
+        "nprobe_metadata.hex",
+        IpfixParser::new().with_max_metadata_length(16),
+    );
+    assert_eq!(flows[3].http_url.as_deref(), Some("/search?q=aaaaaa"));
+    // truncated at a char boundary, the replacement character takes 3 bytes
+    let lossy = IpfixParser::new().with_max_metadata_length(3);
+    let flows = decode_flows("nprobe_metadata.hex", lossy);
+    assert_eq!(flows[1].dns_query.as_deref(), Some("b"));
+    assert_eq!(flows[0].dns_query.as_deref(), Some("www"));
+}
@@ -0,0 +1,398 @@
+use crate::Publisher;
+use async_trait::async_trait;
+use fluss_core::fluss::Fluss;
+use lapin::options::{BasicPublishOptions, ConfirmSelectOptions};
+use lapin::{BasicProperties, Channel, Connection, ConnectionProperties};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::{Mutex, Semaphore};
+
+const DEFAULT_MAX_IN_FLIGHT: usize = 1000;
+/// Delivery mode of messages which survive a broker restart.
+const PERSISTENT: u8 = 2;
+
+/// Publishes flows as persistent JSON messages to an AMQP exchange, e.g. of RabbitMQ.
+///
+/// The routing key of every message is rendered from a [`RoutingKey`] template.
+/// The exchange has to exist. Messages are published in confirm mode, at most
+/// `max_in_flight` messages wait for their confirmation at a time. Messages
+/// rejected by the broker or lost with the connection are logged.
+///
+/// The connection is established on first use and again after it was lost.
+/// Call [`AmqpPublisher::shutdown`] to wait for all confirmations before exiting.
+pub struct AmqpPublisher {
+    url: String,
+    exchange: String,
+    routing_key: RoutingKey,
+    max_in_flight: usize,
+    in_flight: Arc<Semaphore>,
+    connection: Mutex<Option<(Connection, Channel)>>,
+}
+
+impl AmqpPublisher {
+    pub fn new(url: &str, exchange: &str, routing_key: RoutingKey) -> Self {
+        Self {
+            url: url.to_owned(),
+            exchange: exchange.to_owned(),
+            routing_key,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            in_flight: Arc::new(Semaphore::new(DEFAULT_MAX_IN_FLIGHT)),
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Maximum amount of messages waiting for their confirmation,
+    /// publishing waits until older messages are confirmed.
+    pub fn set_max_in_flight(&mut self, max_in_flight: usize) {
+        // waiting for all confirmations acquires all permits at once, at most `u32::MAX`
+        self.max_in_flight = max_in_flight.clamp(1, u32::MAX as usize);
+        self.in_flight = Arc::new(Semaphore::new(self.max_in_flight));
+    }
+
+    /// Waits until all published messages are confirmed.
+    pub async fn wait_for_confirms(&self) -> anyhow::Result<()> {
+        // all permits are available again once every confirmation arrived
+        let _permits = self
+            .in_flight
+            .acquire_many(self.max_in_flight as u32)
+            .await?;
+        Ok(())
+    }
+
+    /// Waits for all confirmations and closes the connection.
+    pub async fn shutdown(&self) -> anyhow::Result<()> {
+        self.wait_for_confirms().await?;
+        if let Some((connection, _)) = self.connection.lock().await.take() {
+            connection.close(200, "shutdown").await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the channel of the current connection, connects if the connection was lost.
+    async fn channel(&self) -> anyhow::Result<Channel> {
+        let mut connection = self.connection.lock().await;
+        if let Some((_, channel)) = &*connection {
+            if channel.status().connected() {
+                return Ok(channel.clone());
+            }
+            tracing::info!(
+                exchange = self.exchange.as_str(),
+                "reconnecting to amqp broker"
+            );
+        }
+
+        let established = Connection::connect(&self.url, ConnectionProperties::default()).await?;
+        let channel = established.create_channel().await?;
+        channel
+            .confirm_select(ConfirmSelectOptions::default())
+            .await?;
+        *connection = Some((established, channel.clone()));
+
+        Ok(channel)
+    }
+
+    /// Returns the routing key and the JSON payload of the message of `fluss`.
+    fn message(&self, fluss: &Fluss) -> anyhow::Result<(String, Vec<u8>)> {
+        Ok((self.routing_key.render(fluss), serde_json::to_vec(fluss)?))
+    }
+}
+
+/// Properties of all published messages.
+fn properties() -> BasicProperties {
+    BasicProperties::default()
+        .with_content_type("application/json".into())
+        .with_delivery_mode(PERSISTENT)
+}
+
+#[async_trait]
+impl Publisher for AmqpPublisher {
+    async fn publish(&self, fluss: &Fluss) -> anyhow::Result<()> {
+        let (routing_key, payload) = self.message(fluss)?;
+
+        let permit = Arc::clone(&self.in_flight).acquire_owned().await?;
+        let confirm = self
+            .channel()
+            .await?
+            .basic_publish(
+                &self.exchange,
+                &routing_key,
+                BasicPublishOptions::default(),
+                &payload,
+                properties(),
+            )
+            .await?;
+
+        tokio::spawn(async move {
+            match confirm.await {
+                Ok(confirmation) if confirmation.is_nack() => {
+                    tracing::warn!(
+                        routing_key = routing_key.as_str(),
+                        "flow rejected by the broker"
+                    )
+                }
+                Ok(_) => (),
+                Err(err) => tracing::warn!(error = %err, "flow was not confirmed by the broker"),
+            }
+            drop(permit);
+        });
+
+        Ok(())
+    }
+
+    async fn health_check(&self) -> anyhow::Result<()> {
+        self.channel().await?;
+        Ok(())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        self.wait_for_confirms().await
+    }
+}
+
+/// Routing key of a message with placeholders for values of the flow.
+///
+/// Supports `{exporter}`, `{protocol}` and `{direction}`, e.g. `flows.{exporter}.{protocol}`.
+/// Dots in values are replaced by underscores, they separate the words of
+/// routing keys of topic exchanges.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutingKey(Vec<Segment>);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Exporter,
+    Protocol,
+    Direction,
+}
+
+impl RoutingKey {
+    pub fn render(&self, fluss: &Fluss) -> String {
+        let mut key = String::new();
+        for segment in &self.0 {
+            let value = match segment {
+                Segment::Literal(literal) => {
+                    key.push_str(literal);
+                    continue;
+                }
+                Segment::Exporter => fluss
+                    .exporter
+                    .map_or_else(|| "unknown".to_owned(), |exporter| exporter.to_string()),
+                Segment::Protocol => fluss.protocol.to_string(),
+                Segment::Direction => fluss.flow_direction.to_string(),
+            };
+            key.push_str(&value.replace('.', "_"));
+        }
+        key
+    }
+}
+
+impl FromStr for RoutingKey {
+    type Err = anyhow::Error;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_owned()));
+            }
+            let end = rest[start..].find('}').ok_or_else(|| {
+                anyhow::anyhow!("unclosed placeholder in routing key {}", template)
+            })?;
+            segments.push(match &rest[start + 1..start + end] {
+                "exporter" => Segment::Exporter,
+                "protocol" => Segment::Protocol,
+                "direction" => Segment::Direction,
+                name => anyhow::bail!("unknown placeholder {{{}}} in routing key", name),
+            });
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_owned()));
+        }
+
+        Ok(Self(segments))
+    }
+}
+
+impl fmt::Display for RoutingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for segment in &self.0 {
+            match segment {
+                Segment::Literal(literal) => f.write_str(literal)?,
+                Segment::Exporter => f.write_str("{exporter}")?,
+                Segment::Protocol => f.write_str("{protocol}")?,
+                Segment::Direction => f.write_str("{direction}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fluss_core::fluss::{FlowDirection, Protocol};
+    use fluss_core::testing::flow;
+    use std::net::Ipv4Addr;
+
+    fn fluss() -> Fluss {
+        let mut fluss = flow(
+            Ipv4Addr::new(10, 0, 0, 1),
+            Ipv4Addr::new(10, 0, 0, 2),
+            443,
+            1500,
+            3,
+        );
+        fluss.exporter = Some(Ipv4Addr::new(192, 0, 2, 1).into());
+        fluss.flow_direction = FlowDirection::Egress;
+        fluss
+    }
+
+    fn render(template: &str, fluss: &Fluss) -> String {
+        template.parse::<RoutingKey>().unwrap().render(fluss)
+    }
+
+    #[test]
+    fn routing_keys_are_rendered() {
+        let mut fluss = fluss();
+        assert_eq!(render("flows", &fluss), "flows");
+        assert_eq!(render("flows.{protocol}", &fluss), "flows.tcp");
+        assert_eq!(
+            render("{direction}.flows.{exporter}.{protocol}", &fluss),
+            "egress.flows.192_0_2_1.tcp"
+        );
+        assert_eq!(render("{protocol}{protocol}", &fluss), "tcptcp");
+
+        fluss.exporter = Some("2001:db8::1".parse().unwrap());
+        fluss.protocol = Protocol::Other(132);
+        assert_eq!(
+            render("flows.{exporter}.{protocol}", &fluss),
+            "flows.2001:db8::1.132"
+        );
+        fluss.exporter = None;
+        assert_eq!(render("flows.{exporter}", &fluss), "flows.unknown");
+    }
+
+    #[test]
+    fn routing_key_templates_round_trip() {
+        for template in ["", "flows", "flows.{exporter}.{protocol}", "{direction}#"] {
+            assert_eq!(
+                template.parse::<RoutingKey>().unwrap().to_string(),
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_routing_key_templates_are_rejected() {
+        for template in [
+            "flows.{protocol",
+            "flows.{}",
+            "flows.{src_addr}",
+            "{Protocol}",
+        ] {
+            assert!(template.parse::<RoutingKey>().is_err(), "{}", template);
+        }
+    }
+
+    #[test]
+    fn flows_are_persistent_json_messages() {
+        let publisher = AmqpPublisher::new(
+            "amqp://127.0.0.1:1",
+            "flows",
+            "flows.{protocol}".parse().unwrap(),
+        );
+        let fluss = fluss();
+
+        let (routing_key, payload) = publisher.message(&fluss).unwrap();
+        assert_eq!(routing_key, "flows.tcp");
+        let json: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+        assert_eq!(json, serde_json::to_value(&fluss).unwrap());
+        assert_eq!(json["bytes"], 1500);
+
+        let properties = properties();
+        assert_eq!(properties.delivery_mode(), &Some(PERSISTENT));
+        assert_eq!(
+            properties.content_type().as_ref().map(|ty| ty.as_str()),
+            Some("application/json")
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_publishes_release_their_slot() {
+        // nothing listens on the port, connecting fails
+        let mut publisher =
+            AmqpPublisher::new("amqp://127.0.0.1:1", "flows", "flows".parse().unwrap());
+        publisher.set_max_in_flight(1);
+
+        for _ in 0..3 {
+            assert!(publisher.publish(&fluss()).await.is_err());
+        }
+        publisher.flush().await.unwrap();
+        publisher.shutdown().await.unwrap();
+    }
+
+    /// Publishes to a local RabbitMQ, the broker URL is read from `FLUSS_TEST_AMQP_URL`.
+    #[tokio::test]
+    async fn publish_to_broker() {
+        use lapin::options::{BasicGetOptions, QueueBindOptions, QueueDeclareOptions};
+        use lapin::types::FieldTable;
+
+        let url = match std::env::var("FLUSS_TEST_AMQP_URL") {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+
+        let connection = Connection::connect(&url, ConnectionProperties::default())
+            .await
+            .unwrap();
+        let channel = connection.create_channel().await.unwrap();
+        let queue = channel
+            .queue_declare(
+                "",
+                QueueDeclareOptions {
+                    exclusive: true,
+                    ..Default::default()
+                },
+                FieldTable::default(),
+            )
+            .await
+            .unwrap();
+        channel
+            .queue_bind(
+                queue.name().as_str(),
+                "amq.topic",
+                "fluss-test.#",
+                QueueBindOptions::default(),
+                FieldTable::default(),
+            )
+            .await
+            .unwrap();
+
+        let publisher = AmqpPublisher::new(
+            &url,
+            "amq.topic",
+            "fluss-test.{direction}.{protocol}".parse().unwrap(),
+        );
+        publisher.publish(&fluss()).await.unwrap();
+        publisher.shutdown().await.unwrap();
+
+        let message = channel
+            .basic_get(queue.name().as_str(), BasicGetOptions { no_ack: true })
+            .await
+            .unwrap()
+            .expect("the flow was routed to the queue");
+        assert_eq!(
+            message.delivery.routing_key.as_str(),
+            "fluss-test.egress.tcp"
+        );
+        assert_eq!(
+            message.delivery.properties.delivery_mode(),
+            &Some(PERSISTENT)
+        );
+        let json: serde_json::Value = serde_json::from_slice(&message.delivery.data).unwrap();
+        assert_eq!(json["bytes"], 1500);
+    }
+}
@@ -0,0 +1,66 @@
+use crate::Publisher;
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::time::{Duration, Instant};
+
+/// Keeps a copy of every published item, to inspect the output of a pipeline.
+pub struct CapturingPublisher<T> {
+    items: Mutex<Vec<T>>,
+}
+
+impl<T: Clone> CapturingPublisher<T> {
+    pub fn new() -> Self {
+        Self {
+            items: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.lock().is_empty()
+    }
+
+    /// Returns a copy of all items published so far.
+    pub fn items(&self) -> Vec<T> {
+        self.items.lock().clone()
+    }
+
+    /// Removes and returns all items published so far.
+    pub fn take(&self) -> Vec<T> {
+        std::mem::take(&mut *self.items.lock())
+    }
+
+    /// Waits until at least `count` items were published or the timeout expired,
+    /// returns whether enough items were published.
+    pub async fn wait_for(&self, count: usize, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.len() < count {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        true
+    }
+}
+
+impl<T: Clone> Default for CapturingPublisher<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl<T> Publisher<T> for CapturingPublisher<T>
+where
+    T: Clone + Send + Sync,
+{
+    async fn publish(&self, item: &T) -> anyhow::Result<()> {
+        self.items.lock().push(item.clone());
+        Ok(())
+    }
+}
@@ -0,0 +1,336 @@
+use crate::Publisher;
+use async_trait::async_trait;
+use fluss_core::fluss::Fluss;
+use parking_lot::Mutex;
+use std::time::{Duration, Instant};
+
+const DEFAULT_BATCH_SIZE: usize = 1000;
+const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Table layout matching the serialized [`Fluss`], `{table}` is replaced with the table name.
+///
+/// Custom fields and labels are not part of the table and skipped on insert.
+pub const TABLE_DDL: &str = "CREATE TABLE IF NOT EXISTS {table} (
+    type LowCardinality(String),
+    time_received DateTime64(3, 'UTC'),
+    exporter Nullable(String),
+    flow_age UInt64,
+    flow_start Nullable(DateTime64(3, 'UTC')),
+    flow_end Nullable(DateTime64(3, 'UTC')),
+    clock_skew_ms Nullable(Int64),
+    flow_direction LowCardinality(String),
+    is_bidirectional Bool,
+    ingress_interface UInt32,
+    egress_interface UInt32,
+    ingress_interface_name Nullable(String),
+    egress_interface_name Nullable(String),
+    bytes UInt64,
+    packets UInt64,
+    bytes_in UInt64,
+    bytes_out UInt64,
+    packets_in UInt64,
+    packets_out UInt64,
+    sampling_interval Nullable(UInt32),
+    dscp UInt8,
+    ethernet_type UInt16,
+    protocol LowCardinality(String),
+    src_mac Nullable(String),
+    dst_mac Nullable(String),
+    src_addr String,
+    dst_addr String,
+    src_net UInt8,
+    dst_net UInt8,
+    src_port UInt16,
+    dst_port UInt16,
+    icmp_type Nullable(UInt8),
+    icmp_code Nullable(UInt8),
+    vlan_id UInt16,
+    post_vlan_id UInt16,
+    post_nat_src_addr String,
+    post_nat_dst_addr String,
+    post_napt_src_port UInt16,
+    post_napt_dst_port UInt16,
+    next_hop_addr String,
+    tunnel_type Nullable(String),
+    tunnel_id Nullable(UInt64),
+    inner_src_addr Nullable(String),
+    inner_dst_addr Nullable(String),
+    inner_src_port Nullable(UInt16),
+    inner_dst_port Nullable(UInt16),
+    outer_src_addr Nullable(String),
+    outer_dst_addr Nullable(String),
+    outer_src_port Nullable(UInt16),
+    outer_dst_port Nullable(UInt16),
+    tcp_flags UInt16,
+    reverse_tcp_flags UInt16,
+    flow_end_reason Nullable(String),
+    flow_state Nullable(String),
+    flow_class LowCardinality(Nullable(String)),
+    service Nullable(String),
+    tenant LowCardinality(Nullable(String)),
+    src_net_name LowCardinality(Nullable(String)),
+    dst_net_name LowCardinality(Nullable(String)),
+    dns_query Nullable(String),
+    dns_qtype Nullable(UInt16),
+    http_host Nullable(String),
+    http_url Nullable(String),
+    http_status Nullable(UInt16),
+    tls_sni Nullable(String),
+    lossy_utf8 Bool,
+    suspect Bool
+)
+ENGINE = MergeTree
+PARTITION BY toDate(time_received)
+ORDER BY time_received";
+
+/// Inserts flows in batches through the ClickHouse HTTP interface.
+///
+/// A batch is sent once it reaches the batch size or a flow arrives after the
+/// flush interval passed, [`ClickHousePublisher::flush`] sends it unconditionally.
+pub struct ClickHousePublisher {
+    client: reqwest::Client,
+    url: String,
+    table: String,
+    batch_size: usize,
+    flush_interval: Duration,
+    batch: Mutex<Batch>,
+}
+
+struct Batch {
+    // rows in the JSONEachRow format
+    rows: Vec<u8>,
+    len: usize,
+    started: Instant,
+}
+
+impl Batch {
+    fn new() -> Self {
+        Self {
+            rows: Vec::new(),
+            len: 0,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl ClickHousePublisher {
+    pub fn new(url: &str, table: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.to_owned(),
+            table: table.to_owned(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            batch: Mutex::new(Batch::new()),
+        }
+    }
+
+    /// Maximum amount of flows sent in a single insert.
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = batch_size.max(1);
+    }
+
+    /// Maximum time a flow is held back before the batch is sent.
+    pub fn set_flush_interval(&mut self, flush_interval: Duration) {
+        self.flush_interval = flush_interval;
+    }
+
+    /// Creates the table with [`TABLE_DDL`] if it does not exist yet.
+    pub async fn ensure_table(&self) -> anyhow::Result<()> {
+        let ddl = TABLE_DDL.replace("{table}", &self.table);
+        self.execute(&[], ddl.into_bytes()).await
+    }
+
+    /// Sends all pending flows.
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        let batch = std::mem::replace(&mut *self.batch.lock(), Batch::new());
+        self.insert(batch).await
+    }
+
+    async fn insert(&self, batch: Batch) -> anyhow::Result<()> {
+        if batch.len == 0 {
+            return Ok(());
+        }
+
+        tracing::debug!(
+            rows = batch.len,
+            table = self.table.as_str(),
+            "inserting batch"
+        );
+        let query = format!("INSERT INTO {} FORMAT JSONEachRow", self.table);
+        self.execute(
+            &[
+                ("query", query.as_str()),
+                ("date_time_input_format", "best_effort"),
+                ("input_format_skip_unknown_fields", "1"),
+            ],
+            batch.rows,
+        )
+        .await
+    }
+
+    async fn execute(&self, params: &[(&str, &str)], body: Vec<u8>) -> anyhow::Result<()> {
+        let response = self
+            .client
+            .post(&self.url)
+            .query(params)
+            .body(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!("clickhouse request failed: {}: {}", status, body.trim());
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Publisher for ClickHousePublisher {
+    async fn publish(&self, fluss: &Fluss) -> anyhow::Result<()> {
+        let mut row = serde_json::to_vec(fluss)?;
+        row.push(b'\n');
+
+        let full = {
+            let mut batch = self.batch.lock();
+            batch.rows.extend_from_slice(&row);
+            batch.len += 1;
+
+            if batch.len >= self.batch_size || batch.started.elapsed() >= self.flush_interval {
+                Some(std::mem::replace(&mut *batch, Batch::new()))
+            } else {
+                None
+            }
+        };
+
+        match full {
+            Some(batch) => self.insert(batch).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn health_check(&self) -> anyhow::Result<()> {
+        self.execute(&[("query", "SELECT 1")], Vec::new()).await
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        ClickHousePublisher::flush(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fluss_core::testing::flow;
+    use std::net::Ipv4Addr;
+    use wiremock::matchers::{method, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn flows(count: u8) -> Vec<Fluss> {
+        (0..count)
+            .map(|i| {
+                flow(
+                    Ipv4Addr::new(192, 0, 2, i),
+                    Ipv4Addr::new(198, 51, 100, 1),
+                    1000 + u16::from(i),
+                    1500,
+                    3,
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the rows of an insert request.
+    fn rows(body: &[u8]) -> Vec<serde_json::Value> {
+        std::str::from_utf8(body)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn flows_are_inserted_in_batches() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(query_param("query", "INSERT INTO flows FORMAT JSONEachRow"))
+            .and(query_param("date_time_input_format", "best_effort"))
+            .and(query_param("input_format_skip_unknown_fields", "1"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let mut publisher = ClickHousePublisher::new(&server.uri(), "flows");
+        publisher.set_batch_size(2);
+        for flow in &flows(3) {
+            publisher.publish(flow).await.unwrap();
+        }
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+        publisher.flush().await.unwrap();
+        // nothing is pending, flushing again does not send a request
+        publisher.flush().await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 2);
+        let batches: Vec<_> = requests.iter().map(|request| rows(&request.body)).collect();
+        assert_eq!(batches[0].len(), 2);
+        assert_eq!(batches[1].len(), 1);
+
+        let rows: Vec<_> = batches.concat();
+        for (i, row) in rows.iter().enumerate() {
+            assert_eq!(row["src_addr"], format!("192.0.2.{}", i));
+            assert_eq!(row["dst_port"], 1000 + i);
+            assert_eq!(row["bytes"], 1500);
+        }
+        assert!(requests[0].body.ends_with(b"\n"));
+    }
+
+    #[tokio::test]
+    async fn failed_inserts_return_the_response() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(404).set_body_string(
+                    "Code: 60. DB::Exception: Table default.flows does not exist.\n",
+                ),
+            )
+            .mount(&server)
+            .await;
+
+        let publisher = ClickHousePublisher::new(&server.uri(), "flows");
+        publisher.publish(&flows(1)[0]).await.unwrap();
+        let err = publisher.flush().await.unwrap_err().to_string();
+        assert!(err.contains("404"), "{}", err);
+        assert!(
+            err.ends_with("Table default.flows does not exist."),
+            "{}",
+            err
+        );
+
+        assert!(publisher.health_check().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn table_is_created_with_its_name() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let publisher = ClickHousePublisher::new(&server.uri(), "netflow.flows");
+        publisher.ensure_table().await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let ddl = std::str::from_utf8(&requests[0].body).unwrap();
+        assert!(
+            ddl.starts_with("CREATE TABLE IF NOT EXISTS netflow.flows ("),
+            "{}",
+            ddl
+        );
+        assert!(!ddl.contains("{table}"));
+    }
+}
@@ -0,0 +1,141 @@
+use crate::rollup::Rollup;
+use crate::Publisher;
+use async_trait::async_trait;
+use fluss_core::fluss::{CompactField, Fluss};
+use fluss_core::protocol::RecordSet;
+use std::collections::HashMap;
+use std::io::Write as _;
+
+/// How the console publisher renders flows.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConsoleFormat {
+    /// Selected fields with human-readable units, see [`Fluss::display_compact`].
+    ///
+    /// Lines are written to stdout without the prefix of log messages.
+    Compact,
+    /// All fields of the flow as `key=value` pairs, see [`Fluss::display`].
+    Line,
+    /// The debug representation of the flow.
+    Debug,
+}
+
+impl std::str::FromStr for ConsoleFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "compact" => Ok(Self::Compact),
+            "line" => Ok(Self::Line),
+            "debug" => Ok(Self::Debug),
+            _ => anyhow::bail!("unknown console format {}", s),
+        }
+    }
+}
+
+pub struct ConsolePublisher {
+    format: ConsoleFormat,
+    fields: Vec<CompactField>,
+    color: bool,
+    verbose: bool,
+    service_names: bool,
+    normalize_sampling: bool,
+}
+
+impl ConsolePublisher {
+    pub fn new() -> Self {
+        Self {
+            format: ConsoleFormat::Compact,
+            fields: CompactField::DEFAULT.to_vec(),
+            color: false,
+            verbose: false,
+            service_names: false,
+            normalize_sampling: false,
+        }
+    }
+
+    pub fn set_format(&mut self, format: ConsoleFormat) {
+        self.format = format;
+    }
+
+    /// Fields of the compact format, in the order they are shown.
+    pub fn set_fields(&mut self, fields: Vec<CompactField>) {
+        self.fields = fields;
+    }
+
+    /// Highlights the protocol in the compact format with ANSI colors.
+    pub fn set_color(&mut self, color: bool) {
+        self.color = color;
+    }
+
+    pub fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+
+    /// Renders ports with their registered service name, e.g. `443 (https)`.
+    pub fn set_service_names(&mut self, service_names: bool) {
+        self.service_names = service_names;
+    }
+
+    /// Shows the counters of sampled flows scaled up by the sampling interval.
+    pub fn set_normalize_sampling(&mut self, normalize_sampling: bool) {
+        self.normalize_sampling = normalize_sampling;
+    }
+}
+
+#[async_trait]
+impl Publisher for ConsolePublisher {
+    async fn publish(&self, fluss: &Fluss) -> anyhow::Result<()> {
+        match self.format {
+            // written directly, log messages can not contain colors
+            ConsoleFormat::Compact => writeln!(
+                std::io::stdout().lock(),
+                "{}",
+                fluss
+                    .display_compact()
+                    .fields(&self.fields)
+                    .color(self.color)
+                    .normalize_sampling(self.normalize_sampling)
+            )?,
+            ConsoleFormat::Line => tracing::info!(
+                "{}",
+                fluss
+                    .display(self.verbose)
+                    .service_names(self.service_names)
+                    .normalize_sampling(self.normalize_sampling)
+            ),
+            ConsoleFormat::Debug => tracing::info!("{:?}", fluss),
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<'a> Publisher<RecordSet<'a>> for ConsolePublisher {
+    async fn publish(&self, record_set: &RecordSet<'a>) -> anyhow::Result<()> {
+        let fields: HashMap<String, serde_json::Value> = record_set.into();
+        tracing::info!(
+            "template={} {}",
+            record_set.id,
+            serde_json::to_string(&fields)?
+        );
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Publisher<Rollup> for ConsolePublisher {
+    async fn publish(&self, rollup: &Rollup) -> anyhow::Result<()> {
+        match self.format {
+            ConsoleFormat::Compact => writeln!(std::io::stdout().lock(), "{}", rollup)?,
+            ConsoleFormat::Line => tracing::info!("{}", rollup),
+            ConsoleFormat::Debug => tracing::info!("{:?}", rollup),
+        }
+        Ok(())
+    }
+}
+
+impl Default for ConsolePublisher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
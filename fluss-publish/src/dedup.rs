@@ -0,0 +1,154 @@
+use crate::Publisher;
+use async_trait::async_trait;
+use fluss_core::flow_key::FlowKey;
+use fluss_core::fluss::Fluss;
+use parking_lot::Mutex;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Drops flows which were already published for the same [`FlowKey`]
+/// within the dedup window, e.g. when multiple exporters report the same connection.
+pub struct DeduplicatingPublisher<P> {
+    publisher: P,
+    dedup_window: Duration,
+    seen: Mutex<Seen>,
+}
+
+struct Seen {
+    keys: HashMap<FlowKey, Instant>,
+    last_cleanup: Instant,
+}
+
+impl<P> DeduplicatingPublisher<P> {
+    pub fn new(publisher: P, dedup_window: Duration) -> Self {
+        Self {
+            publisher,
+            dedup_window,
+            seen: Mutex::new(Seen {
+                keys: HashMap::new(),
+                last_cleanup: Instant::now(),
+            }),
+        }
+    }
+
+    /// Records the key and returns whether it was already seen within the window.
+    fn is_duplicate(&self, key: FlowKey) -> bool {
+        self.is_duplicate_at(key, Instant::now())
+    }
+
+    /// The window starts when a key is first seen, duplicates do not extend it.
+    /// Otherwise the interim records of a long-lived flow would never be published.
+    fn is_duplicate_at(&self, key: FlowKey, now: Instant) -> bool {
+        let window = self.dedup_window;
+        let mut seen = self.seen.lock();
+
+        // expired keys are removed at most once per window to keep the map bounded
+        if now.duration_since(seen.last_cleanup) >= window {
+            seen.keys
+                .retain(|_, first_seen| now.duration_since(*first_seen) < window);
+            seen.last_cleanup = now;
+        }
+
+        match seen.keys.entry(key) {
+            Entry::Occupied(entry) if now.duration_since(*entry.get()) < window => true,
+            Entry::Occupied(mut entry) => {
+                entry.insert(now);
+                false
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(now);
+                false
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<P> Publisher for DeduplicatingPublisher<P>
+where
+    P: Publisher + Send + Sync,
+{
+    async fn publish(&self, fluss: &Fluss) -> anyhow::Result<()> {
+        if self.is_duplicate(FlowKey::from(fluss)) {
+            tracing::trace!(%fluss, "skipping duplicate flow");
+            return Ok(());
+        }
+
+        self.publisher.publish(fluss).await
+    }
+
+    async fn health_check(&self) -> anyhow::Result<()> {
+        self.publisher.health_check().await
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        self.publisher.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CapturingPublisher;
+    use fluss_core::testing::flow;
+    use std::net::Ipv4Addr;
+
+    fn fluss() -> Fluss {
+        flow(
+            Ipv4Addr::new(192, 0, 2, 1),
+            Ipv4Addr::new(198, 51, 100, 1),
+            443,
+            1500,
+            3,
+        )
+    }
+
+    fn dedup(window: u64) -> DeduplicatingPublisher<CapturingPublisher<Fluss>> {
+        DeduplicatingPublisher::new(CapturingPublisher::new(), Duration::from_secs(window))
+    }
+
+    #[tokio::test]
+    async fn duplicates_within_the_window_are_dropped() {
+        let publisher = dedup(60);
+        let fluss = fluss();
+        publisher.publish(&fluss).await.unwrap();
+        publisher.publish(&fluss).await.unwrap();
+
+        let mut other = fluss.clone();
+        other.dst_port = 80;
+        publisher.publish(&other).await.unwrap();
+
+        let published = publisher.publisher.items();
+        assert_eq!(published.len(), 2);
+        assert_eq!(published[0].dst_port, 443);
+        assert_eq!(published[1].dst_port, 80);
+    }
+
+    #[test]
+    fn flows_are_published_again_after_the_window() {
+        let publisher = dedup(10);
+        let key = FlowKey::from(&fluss());
+        let start = Instant::now();
+
+        assert!(!publisher.is_duplicate_at(key, start));
+        assert!(publisher.is_duplicate_at(key, start + Duration::from_secs(9)));
+        assert!(!publisher.is_duplicate_at(key, start + Duration::from_secs(10)));
+        assert!(publisher.is_duplicate_at(key, start + Duration::from_secs(11)));
+    }
+
+    #[test]
+    fn interim_records_are_published_once_per_window() {
+        let publisher = dedup(10);
+        let key = FlowKey::from(&fluss());
+        let start = Instant::now();
+
+        // a long-lived flow reported every 4 seconds, more often than the window
+        let published: Vec<_> = (0..8)
+            .map(|i| start + Duration::from_secs(4 * i))
+            .filter(|&now| !publisher.is_duplicate_at(key, now))
+            .map(|now| now.duration_since(start).as_secs())
+            .collect();
+        assert_eq!(published, [0, 12, 24]);
+    }
+}
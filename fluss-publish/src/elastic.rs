@@ -0,0 +1,1099 @@
+use crate::rollup::{KeyValue, Rollup};
+use crate::Publisher;
+use async_trait::async_trait;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Utc};
+use elasticsearch::cluster::ClusterHealthParts;
+use elasticsearch::http::request::JsonBody;
+use elasticsearch::http::response::Response;
+use elasticsearch::http::StatusCode;
+use elasticsearch::ilm::IlmPutLifecycleParts;
+use elasticsearch::indices::IndicesPutIndexTemplateParts;
+use elasticsearch::{BulkParts, Elasticsearch};
+use fluss_core::fluss::Fluss;
+use fluss_core::protocol::RecordSet;
+use parking_lot::Mutex;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+const DEFAULT_BATCH_SIZE: usize = 1000;
+const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+const DEFAULT_INDEX_PATTERN: &str = "fluss-%d.%m.%Y";
+
+/// Mappings of the index template created by [`ElasticPublisher::setup`].
+///
+/// The application layer metadata is also searchable by its ECS name, e.g.
+/// `dns.question.name`, through field aliases.
+pub const MAPPINGS: &str = include_str!("elastic_mappings.json");
+
+/// Items which can be indexed by the [`ElasticPublisher`].
+pub trait Indexable: Serialize {
+    /// Value of the `@timestamp` field of the document.
+    fn timestamp(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    /// Address of the exporter, used for the `{exporter}` placeholder of index names.
+    fn exporter(&self) -> Option<IpAddr> {
+        None
+    }
+
+    /// Tenant of the item, used for the `{tenant}` placeholder of index names.
+    fn tenant(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl Indexable for Fluss {
+    fn timestamp(&self) -> DateTime<Utc> {
+        self.time_received
+    }
+
+    fn exporter(&self) -> Option<IpAddr> {
+        self.exporter
+    }
+
+    fn tenant(&self) -> Option<&str> {
+        self.tenant.as_deref()
+    }
+}
+
+impl<'a> Indexable for RecordSet<'a> {}
+
+impl Indexable for Rollup {
+    fn timestamp(&self) -> DateTime<Utc> {
+        self.window_end
+    }
+
+    fn exporter(&self) -> Option<IpAddr> {
+        match self.key.get("exporter") {
+            Some(&KeyValue::Addr(exporter)) => Some(exporter),
+            _ => None,
+        }
+    }
+
+    fn tenant(&self) -> Option<&str> {
+        match self.key.get("tenant") {
+            Some(KeyValue::Text(tenant)) => Some(tenant),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Document<'a, T> {
+    #[serde(rename = "@timestamp")]
+    timestamp: DateTime<Utc>,
+
+    #[serde(flatten)]
+    item: &'a T,
+}
+
+impl<'a, T: Indexable> Document<'a, T> {
+    fn new(item: &'a T) -> Self {
+        Self {
+            timestamp: item.timestamp(),
+            item,
+        }
+    }
+}
+
+/// A document which could not be indexed, stored as one line of the dead-letter file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub index: String,
+    pub error: String,
+    pub failed_at: DateTime<Utc>,
+    pub document: serde_json::Value,
+}
+
+/// The pattern of an index name, a strftime format string with optional
+/// `{exporter}` and `{tenant}` placeholders, e.g. `fluss-{tenant}-%Y.%m.%d`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexPattern(String);
+
+impl IndexPattern {
+    pub fn new(pattern: impl Into<String>) -> anyhow::Result<Self> {
+        let pattern = pattern.into();
+        if StrftimeItems::new(&pattern).any(|item| matches!(item, Item::Error)) {
+            anyhow::bail!("invalid index pattern: {:?}", pattern);
+        }
+
+        Ok(Self(pattern))
+    }
+
+    /// Returns the name of the index for documents of `exporter` and `tenant` at `time`.
+    ///
+    /// Documents without a tenant are written to the index of the tenant `default`.
+    pub fn render(
+        &self,
+        time: DateTime<Utc>,
+        exporter: Option<IpAddr>,
+        tenant: Option<&str>,
+    ) -> String {
+        // index names must not contain colons
+        let exporter = match exporter {
+            Some(exporter) => exporter.to_string().replace(':', "-"),
+            None => "unknown".to_owned(),
+        };
+        // and have to be lowercase
+        let tenant = tenant.unwrap_or("default").to_lowercase();
+
+        time.format(&self.0)
+            .to_string()
+            .replace("{exporter}", &exporter)
+            .replace("{tenant}", &tenant)
+    }
+
+    /// The static prefix of all index names, e.g. `fluss` for `fluss-%Y.%m.%d`.
+    fn prefix(&self) -> &str {
+        let end = self.0.find(['%', '{']).unwrap_or(self.0.len());
+        self.0[..end].trim_end_matches(['-', '.', '_'])
+    }
+}
+
+impl FromStr for IndexPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        Self::new(pattern)
+    }
+}
+
+impl fmt::Display for IndexPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Where documents are written to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexStrategy {
+    /// Indices named after a pattern, the publisher rolls over to a new index
+    /// when the rendered name changes.
+    Pattern(IndexPattern),
+    /// A data stream, documents are appended with the `create` action and
+    /// Elasticsearch rolls over the backing indices itself.
+    DataStream(String),
+}
+
+impl IndexStrategy {
+    /// Name of the ILM policy and index template created by [`ElasticPublisher::setup`].
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Pattern(pattern) if pattern.prefix().is_empty() => "fluss",
+            Self::Pattern(pattern) => pattern.prefix(),
+            Self::DataStream(name) => name,
+        }
+    }
+
+    fn index(&self, time: DateTime<Utc>, exporter: Option<IpAddr>, tenant: Option<&str>) -> String {
+        match self {
+            Self::Pattern(pattern) => pattern.render(time, exporter, tenant),
+            Self::DataStream(name) => name.clone(),
+        }
+    }
+
+    fn index_patterns(&self) -> String {
+        match self {
+            Self::Pattern(pattern) => format!("{}*", pattern.prefix()),
+            Self::DataStream(name) => name.clone(),
+        }
+    }
+
+    /// The bulk action, data streams only accept `create`.
+    fn action(&self) -> &'static str {
+        match self {
+            Self::Pattern(_) => "index",
+            Self::DataStream(_) => "create",
+        }
+    }
+}
+
+impl Default for IndexStrategy {
+    fn default() -> Self {
+        Self::Pattern(IndexPattern(DEFAULT_INDEX_PATTERN.to_owned()))
+    }
+}
+
+#[derive(Clone)]
+enum IndexError {
+    /// Elasticsearch is unavailable or overloaded, the request can be retried.
+    Transient(String),
+    /// The document was rejected, e.g. because of a mapping error.
+    Permanent(String),
+}
+
+impl IndexError {
+    fn from_status(status: StatusCode, error: String) -> Self {
+        match status {
+            StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT => Self::Transient(error),
+            _ => Self::Permanent(error),
+        }
+    }
+
+    fn into_message(self) -> String {
+        match self {
+            Self::Transient(error) | Self::Permanent(error) => error,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct BulkResponse {
+    errors: bool,
+    // every item is keyed by its action
+    items: Vec<HashMap<String, BulkItem>>,
+}
+
+#[derive(Deserialize)]
+struct BulkItem {
+    status: u16,
+    #[serde(default)]
+    error: Option<serde_json::Value>,
+}
+
+/// Documents indexed for a tenant since the publisher was created.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TenantStats {
+    /// `None` for documents without a tenant.
+    pub tenant: Option<String>,
+    pub documents: u64,
+}
+
+struct Batch {
+    // documents grouped by exporter and tenant, the index names are rendered once per batch
+    documents: HashMap<(Option<IpAddr>, Option<String>), Vec<serde_json::Value>>,
+    len: usize,
+    created: DateTime<Utc>,
+    started: Instant,
+}
+
+impl Batch {
+    fn new() -> Self {
+        Self {
+            documents: HashMap::new(),
+            len: 0,
+            created: Utc::now(),
+            started: Instant::now(),
+        }
+    }
+}
+
+/// Indexes documents in batches through the bulk API.
+///
+/// A batch is sent once it reaches the batch size or a document arrives after the
+/// flush interval passed, [`ElasticPublisher::flush`] sends it unconditionally.
+/// All documents of a batch are written to the index of the time the batch was started.
+pub struct ElasticPublisher {
+    client: Elasticsearch,
+    strategy: IndexStrategy,
+    retry_budget: Duration,
+    dead_letter: Option<tokio::sync::Mutex<File>>,
+    batch_size: usize,
+    flush_interval: Duration,
+    batch: Mutex<Batch>,
+    tenants: Mutex<HashMap<Option<String>, u64>>,
+}
+
+impl ElasticPublisher {
+    pub fn new(client: Elasticsearch) -> Self {
+        Self {
+            client,
+            strategy: IndexStrategy::default(),
+            retry_budget: Duration::from_secs(60),
+            dead_letter: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            batch: Mutex::new(Batch::new()),
+            tenants: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Documents indexed per tenant, ordered by tenant.
+    pub fn tenant_stats(&self) -> Vec<TenantStats> {
+        let mut stats = self
+            .tenants
+            .lock()
+            .iter()
+            .map(|(tenant, &documents)| TenantStats {
+                tenant: tenant.clone(),
+                documents,
+            })
+            .collect::<Vec<_>>();
+        stats.sort_by(|a, b| a.tenant.cmp(&b.tenant));
+        stats
+    }
+
+    /// Sets the indices documents are written to, defaults to daily `fluss-%d.%m.%Y` indices.
+    pub fn set_index_strategy(&mut self, strategy: IndexStrategy) {
+        self.strategy = strategy;
+    }
+
+    /// Maximum time spent retrying a document before it is given up.
+    pub fn set_retry_budget(&mut self, budget: Duration) {
+        self.retry_budget = budget;
+    }
+
+    /// Maximum amount of documents sent in a single bulk request.
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = batch_size.max(1);
+    }
+
+    /// Maximum time a document is held back before the batch is sent.
+    pub fn set_flush_interval(&mut self, flush_interval: Duration) {
+        self.flush_interval = flush_interval;
+    }
+
+    /// Appends documents which could not be indexed to `path` instead of failing.
+    pub async fn set_dead_letter_file(&mut self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        self.dead_letter = Some(tokio::sync::Mutex::new(file));
+        Ok(())
+    }
+
+    /// Creates or updates the ILM policy and the index template with the bundled [`MAPPINGS`].
+    ///
+    /// Indices of a pattern are deleted after `retention`, data streams are
+    /// additionally rolled over daily.
+    pub async fn setup(&self, retention: Duration) -> anyhow::Result<()> {
+        let name = self.strategy.name();
+        let delete = json!({
+            "min_age": format!("{}d", (retention.as_secs() / 86400).max(1)),
+            "actions": { "delete": {} }
+        });
+        let phases = match self.strategy {
+            IndexStrategy::Pattern(_) => json!({ "delete": delete }),
+            IndexStrategy::DataStream(_) => json!({
+                "hot": { "actions": { "rollover": { "max_age": "1d", "max_size": "50gb" } } },
+                "delete": delete,
+            }),
+        };
+
+        let response = self
+            .client
+            .ilm()
+            .put_lifecycle(IlmPutLifecycleParts::Policy(name))
+            .body(json!({ "policy": { "phases": phases } }))
+            .send()
+            .await?;
+        ensure_success(response, "create ILM policy").await?;
+
+        let mut template = json!({
+            "index_patterns": [self.strategy.index_patterns()],
+            "priority": 200,
+            "template": {
+                "settings": { "index.lifecycle.name": name },
+                "mappings": serde_json::from_str::<serde_json::Value>(MAPPINGS)?,
+            },
+        });
+        if let IndexStrategy::DataStream(_) = self.strategy {
+            template["data_stream"] = json!({});
+        }
+
+        let response = self
+            .client
+            .indices()
+            .put_index_template(IndicesPutIndexTemplateParts::Name(name))
+            .body(template)
+            .send()
+            .await?;
+        ensure_success(response, "create index template").await?;
+
+        tracing::info!(name, "created ILM policy and index template");
+        Ok(())
+    }
+
+    /// Sends all pending documents.
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        let batch = std::mem::replace(&mut *self.batch.lock(), Batch::new());
+        self.send(batch).await
+    }
+
+    /// Indexes a document of the dead-letter file again, without retries.
+    pub async fn replay(&self, dead_letter: &DeadLetter) -> anyhow::Result<()> {
+        // `create` works for indices and data streams alike
+        let documents = [(dead_letter.index.clone(), dead_letter.document.clone())];
+        let error = match self.bulk("create", &documents).await {
+            Ok(mut failed) => failed.pop().map(|(_, error)| error),
+            Err(error) => Some(error),
+        };
+
+        match error {
+            Some(error) => Err(anyhow::anyhow!(error.into_message())),
+            None => Ok(()),
+        }
+    }
+
+    async fn send(&self, batch: Batch) -> anyhow::Result<()> {
+        if batch.len == 0 {
+            return Ok(());
+        }
+
+        let created = batch.created;
+        let mut pending = Vec::with_capacity(batch.len);
+        let mut indexed = HashMap::<Option<String>, u64>::new();
+        for ((exporter, tenant), documents) in batch.documents {
+            let index = self.strategy.index(created, exporter, tenant.as_deref());
+            *indexed.entry(tenant).or_default() += documents.len() as u64;
+            pending.extend(documents.into_iter().map(|doc| (index.clone(), doc)));
+        }
+        tracing::debug!(documents = pending.len(), "sending bulk request");
+
+        let start = Instant::now();
+        let mut attempt = 0;
+        let mut rejected = Vec::new();
+        loop {
+            let mut failed: HashMap<_, _> = match self.bulk(self.strategy.action(), &pending).await
+            {
+                Ok(failed) => failed.into_iter().collect(),
+                // the whole request failed
+                Err(error) => (0..pending.len()).map(|i| (i, error.clone())).collect(),
+            };
+
+            let backoff = backoff(attempt);
+            let exhausted = start.elapsed() + backoff > self.retry_budget;
+
+            let mut retry = Vec::new();
+            for (i, document) in pending.into_iter().enumerate() {
+                match failed.remove(&i) {
+                    None => {}
+                    Some(IndexError::Transient(_)) if !exhausted => retry.push(document),
+                    Some(error) => rejected.push((document, error.into_message())),
+                }
+            }
+
+            if retry.is_empty() {
+                break;
+            }
+
+            tracing::debug!(
+                attempt,
+                ?backoff,
+                documents = retry.len(),
+                "retrying documents"
+            );
+            tokio::time::sleep(backoff).await;
+            attempt += 1;
+            pending = retry;
+        }
+
+        for ((_, document), _) in &rejected {
+            let tenant = document["tenant"].as_str().map(str::to_owned);
+            if let Some(count) = indexed.get_mut(&tenant) {
+                *count -= 1;
+            }
+        }
+        {
+            let mut tenants = self.tenants.lock();
+            for (tenant, count) in indexed {
+                *tenants.entry(tenant).or_default() += count;
+            }
+        }
+
+        self.write_dead_letters(rejected).await
+    }
+
+    /// Sends a single bulk request, returns the positions of the documents which failed.
+    async fn bulk(
+        &self,
+        action: &str,
+        documents: &[(String, serde_json::Value)],
+    ) -> Result<Vec<(usize, IndexError)>, IndexError> {
+        let mut body: Vec<JsonBody<serde_json::Value>> = Vec::with_capacity(documents.len() * 2);
+        for (index, document) in documents {
+            body.push(JsonBody::new(bulk_action(action, index)));
+            body.push(JsonBody::new(document.clone()));
+        }
+
+        let response = self
+            .client
+            .bulk(BulkParts::None)
+            .body(body)
+            .send()
+            .await
+            .map_err(|err| IndexError::Transient(err.to_string()))?;
+
+        let status = response.status_code();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(IndexError::from_status(
+                status,
+                format!("{}: {}", status, body),
+            ));
+        }
+
+        let response = response
+            .json::<BulkResponse>()
+            .await
+            .map_err(|err| IndexError::Transient(err.to_string()))?;
+        if !response.errors {
+            return Ok(Vec::new());
+        }
+
+        Ok(response
+            .items
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, mut item)| {
+                let item = item.remove(action)?;
+                let error = item.error?;
+                let status = StatusCode::from_u16(item.status).ok()?;
+                Some((
+                    i,
+                    IndexError::from_status(status, format!("{}: {}", status, error)),
+                ))
+            })
+            .collect())
+    }
+
+    async fn write_dead_letters(
+        &self,
+        rejected: Vec<((String, serde_json::Value), String)>,
+    ) -> anyhow::Result<()> {
+        let (index, error) = match rejected.first() {
+            Some(((index, _), error)) => (index.clone(), error.clone()),
+            None => return Ok(()),
+        };
+        let file = match &self.dead_letter {
+            Some(file) => file,
+            None => anyhow::bail!(
+                "failed to index {} documents into {}: {}",
+                rejected.len(),
+                index,
+                error
+            ),
+        };
+
+        tracing::warn!(
+            documents = rejected.len(),
+            index = index.as_str(),
+            error = error.as_str(),
+            "writing documents to dead-letter file"
+        );
+
+        let mut lines = Vec::new();
+        for ((index, document), error) in rejected {
+            let dead_letter = DeadLetter {
+                index,
+                error,
+                failed_at: Utc::now(),
+                document,
+            };
+            serde_json::to_writer(&mut lines, &dead_letter)?;
+            lines.push(b'\n');
+        }
+        // the write of a tokio file completes in the background, unless flushed
+        let mut file = file.lock().await;
+        file.write_all(&lines).await?;
+        file.flush().await?;
+
+        Ok(())
+    }
+}
+
+/// The action line of a document in a bulk request, e.g. `{"create":{"_index":"flows"}}`.
+fn bulk_action(action: &str, index: &str) -> serde_json::Value {
+    json!({ action: { "_index": index } })
+}
+
+async fn ensure_success(response: Response, request: &str) -> anyhow::Result<()> {
+    let status = response.status_code();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!("failed to {}: {}: {}", request, status, body);
+    }
+
+    Ok(())
+}
+
+/// Exponential backoff with full jitter.
+fn backoff(attempt: u32) -> Duration {
+    let max = INITIAL_BACKOFF
+        .checked_mul(1 << attempt.min(16))
+        .unwrap_or(MAX_BACKOFF)
+        .min(MAX_BACKOFF);
+
+    rand::thread_rng().gen_range(Duration::ZERO..=max)
+}
+
+#[async_trait]
+impl<T> Publisher<T> for ElasticPublisher
+where
+    T: Indexable + Sync,
+{
+    async fn publish(&self, item: &T) -> anyhow::Result<()> {
+        let document = serde_json::to_value(Document::new(item))?;
+
+        let full = {
+            let mut batch = self.batch.lock();
+            batch
+                .documents
+                .entry((item.exporter(), item.tenant().map(str::to_owned)))
+                .or_default()
+                .push(document);
+            batch.len += 1;
+
+            if batch.len >= self.batch_size || batch.started.elapsed() >= self.flush_interval {
+                Some(std::mem::replace(&mut *batch, Batch::new()))
+            } else {
+                None
+            }
+        };
+
+        match full {
+            Some(batch) => self.send(batch).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Fails if the cluster is unreachable or its health is red.
+    async fn health_check(&self) -> anyhow::Result<()> {
+        let response = self
+            .client
+            .cluster()
+            .health(ClusterHealthParts::None)
+            .send()
+            .await?;
+        let status = response.status_code();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!("failed to check cluster health: {}: {}", status, body);
+        }
+
+        let health: serde_json::Value = response.json().await?;
+        if health["status"] == "red" {
+            anyhow::bail!("elasticsearch cluster health is red");
+        }
+
+        Ok(())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        ElasticPublisher::flush(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elasticsearch::http::transport::Transport;
+    use fluss_core::testing::flow;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[derive(Serialize)]
+    struct Flow {
+        id: u32,
+    }
+
+    impl Indexable for Flow {}
+
+    async fn publisher(server: &MockServer, dead_letter: &Path) -> ElasticPublisher {
+        let transport = Transport::single_node(&server.uri()).unwrap();
+        let mut publisher = ElasticPublisher::new(Elasticsearch::new(transport));
+        publisher.set_batch_size(2);
+        publisher.set_retry_budget(Duration::from_millis(300));
+        publisher.set_dead_letter_file(dead_letter).await.unwrap();
+        publisher
+    }
+
+    async fn publish(publisher: &ElasticPublisher, ids: &[u32]) {
+        for &id in ids {
+            Publisher::publish(publisher, &Flow { id }).await.unwrap();
+        }
+    }
+
+    fn dead_letters(path: &Path) -> Vec<DeadLetter> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn bulk_response(items: serde_json::Value) -> ResponseTemplate {
+        let errors = items
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|item| item["index"]["error"].is_object());
+        ResponseTemplate::new(200).set_body_json(json!({ "errors": errors, "items": items }))
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/_bulk"))
+            .respond_with(ResponseTemplate::new(429))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/_bulk"))
+            .respond_with(bulk_response(
+                json!([{ "index": { "status": 201 } }, { "index": { "status": 201 } }]),
+            ))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let dead_letter = dir.path().join("dlq.ndjson");
+        let publisher = publisher(&server, &dead_letter).await;
+        publish(&publisher, &[1, 2]).await;
+
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+        assert!(dead_letters(&dead_letter).is_empty());
+        assert_eq!(publisher.tenant_stats()[0].documents, 2);
+    }
+
+    #[tokio::test]
+    async fn exhausted_retries_are_dead_lettered_and_replayed() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/_bulk"))
+            .respond_with(ResponseTemplate::new(503).set_body_string("maintenance"))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let dead_letter = dir.path().join("dlq.ndjson");
+        let publisher = publisher(&server, &dead_letter).await;
+        publish(&publisher, &[1, 2]).await;
+
+        assert!(server.received_requests().await.unwrap().len() > 1);
+        let letters = dead_letters(&dead_letter);
+        let ids: Vec<_> = letters
+            .iter()
+            .map(|letter| &letter.document["id"])
+            .collect();
+        assert_eq!(ids, [1, 2]);
+        for letter in &letters {
+            assert!(letter.index.starts_with("fluss-"), "{}", letter.index);
+            assert!(letter.error.contains("503"), "{}", letter.error);
+            assert!(letter.document["@timestamp"].is_string());
+        }
+
+        // elasticsearch is healthy again
+        server.reset().await;
+        Mock::given(method("POST"))
+            .and(path("/_bulk"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(
+                json!({ "errors": false, "items": [{ "create": { "status": 201 } }] }),
+            ))
+            .mount(&server)
+            .await;
+        for letter in &letters {
+            publisher.replay(letter).await.unwrap();
+        }
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 2);
+        for (request, letter) in requests.iter().zip(&letters) {
+            let lines: Vec<serde_json::Value> = std::str::from_utf8(&request.body)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+            assert_eq!(lines[0], bulk_action("create", &letter.index));
+            assert_eq!(lines[1], letter.document);
+        }
+    }
+
+    #[tokio::test]
+    async fn permanent_failures_are_dead_lettered_without_retry() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/_bulk"))
+            .respond_with(bulk_response(json!([
+                {
+                    "index": {
+                        "status": 400,
+                        "error": { "type": "mapper_parsing_exception", "reason": "failed to parse field [id]" }
+                    }
+                },
+                { "index": { "status": 201 } }
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let dead_letter = dir.path().join("dlq.ndjson");
+        let publisher = publisher(&server, &dead_letter).await;
+        publish(&publisher, &[1, 2]).await;
+
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+        let letters = dead_letters(&dead_letter);
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0].document["id"], 1);
+        assert!(
+            letters[0].error.contains("mapper_parsing_exception"),
+            "{}",
+            letters[0].error
+        );
+        assert_eq!(publisher.tenant_stats()[0].documents, 1);
+    }
+
+    #[tokio::test]
+    async fn failures_without_dead_letter_file_are_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/_bulk"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("bad request"))
+            .mount(&server)
+            .await;
+
+        let transport = Transport::single_node(&server.uri()).unwrap();
+        let publisher = ElasticPublisher::new(Elasticsearch::new(transport));
+        Publisher::publish(&publisher, &Flow { id: 1 })
+            .await
+            .unwrap();
+        let err = publisher.flush().await.unwrap_err();
+        assert!(err.to_string().contains("bad request"), "{}", err);
+    }
+
+    fn bulk_lines(request: &wiremock::Request) -> Vec<serde_json::Value> {
+        std::str::from_utf8(&request.body)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn utc(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn index_names_are_rendered() {
+        let pattern = IndexPattern::new("fluss-{tenant}-{exporter}-%Y.%m.%d").unwrap();
+        let time = utc("2024-03-01T12:00:00Z");
+
+        let exporter = Some(IpAddr::from([192, 0, 2, 1]));
+        assert_eq!(
+            pattern.render(time, exporter, Some("Acme")),
+            "fluss-acme-192.0.2.1-2024.03.01"
+        );
+        let exporter = Some("2001:db8::1".parse().unwrap());
+        assert_eq!(
+            pattern.render(time, exporter, None),
+            "fluss-default-2001-db8--1-2024.03.01"
+        );
+        assert_eq!(
+            pattern.render(time, None, None),
+            "fluss-default-unknown-2024.03.01"
+        );
+    }
+
+    #[test]
+    fn index_names_roll_over_at_utc_midnight() {
+        let strategy = IndexStrategy::Pattern("fluss-%Y.%m.%d".parse().unwrap());
+        let before = utc("2024-02-29T23:59:59.999Z");
+        let after = utc("2024-03-01T00:00:00Z");
+        assert_eq!(strategy.index(before, None, None), "fluss-2024.02.29");
+        assert_eq!(strategy.index(after, None, None), "fluss-2024.03.01");
+
+        // local time zones do not matter, the time is always UTC
+        let local = "2024-03-01T01:30:00+02:00".parse::<DateTime<chrono::FixedOffset>>();
+        let local = local.unwrap().with_timezone(&Utc);
+        assert_eq!(strategy.index(local, None, None), "fluss-2024.02.29");
+
+        // the default pattern keeps the previous index names
+        let time = utc("2024-03-01T00:00:00Z");
+        assert_eq!(
+            IndexStrategy::default().index(time, None, None),
+            "fluss-01.03.2024"
+        );
+    }
+
+    #[test]
+    fn data_streams_are_never_rolled() {
+        let strategy = IndexStrategy::DataStream("flows".to_owned());
+        for time in ["2024-02-29T23:59:59Z", "2024-03-01T00:00:00Z"] {
+            let exporter = Some(IpAddr::from([192, 0, 2, 1]));
+            assert_eq!(strategy.index(utc(time), exporter, Some("acme")), "flows");
+        }
+        assert_eq!(strategy.action(), "create");
+        assert_eq!(strategy.name(), "flows");
+        assert_eq!(strategy.index_patterns(), "flows");
+    }
+
+    #[test]
+    fn pattern_names() {
+        let strategy = IndexStrategy::Pattern("fluss-{tenant}-%Y.%m.%d".parse().unwrap());
+        assert_eq!(strategy.name(), "fluss");
+        assert_eq!(strategy.index_patterns(), "fluss*");
+        assert_eq!(strategy.action(), "index");
+
+        let strategy = IndexStrategy::Pattern("%Y.%m.%d".parse().unwrap());
+        assert_eq!(strategy.name(), "fluss");
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        assert!(IndexPattern::new("fluss-%Q").is_err());
+        assert!(IndexPattern::new("fluss-%").is_err());
+    }
+
+    #[tokio::test]
+    async fn data_stream_documents_are_created() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/_bulk"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "errors": false,
+                "items": [{ "create": { "status": 201 } }, { "create": { "status": 201 } }]
+            })))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut publisher = publisher(&server, &dir.path().join("dlq.ndjson")).await;
+        publisher.set_index_strategy(IndexStrategy::DataStream("flows".to_owned()));
+        publish(&publisher, &[1, 2]).await;
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        let lines = bulk_lines(&requests[0]);
+        assert_eq!(lines.len(), 4);
+        for (i, id) in [1, 2].iter().enumerate() {
+            assert_eq!(lines[i * 2], json!({ "create": { "_index": "flows" } }));
+            assert_eq!(lines[i * 2 + 1]["id"], *id);
+        }
+    }
+
+    #[tokio::test]
+    async fn pattern_documents_are_indexed() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/_bulk"))
+            .respond_with(bulk_response(
+                json!([{ "index": { "status": 201 } }, { "index": { "status": 201 } }]),
+            ))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut publisher = publisher(&server, &dir.path().join("dlq.ndjson")).await;
+        publisher.set_index_strategy(IndexStrategy::Pattern(
+            "flows-{exporter}-%Y".parse().unwrap(),
+        ));
+        publish(&publisher, &[1, 2]).await;
+
+        // all documents of a batch go to the index of the time the batch was started
+        let requests = server.received_requests().await.unwrap();
+        let lines = bulk_lines(&requests[0]);
+        let index = format!("flows-unknown-{}", Utc::now().format("%Y"));
+        assert_eq!(lines[0], json!({ "index": { "_index": index } }));
+        assert_eq!(lines[2], lines[0]);
+    }
+
+    #[tokio::test]
+    async fn tenants_are_indexed_separately() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/_bulk"))
+            .respond_with(bulk_response(json!([
+                { "index": { "status": 201 } },
+                { "index": { "status": 201 } },
+                { "index": { "status": 201 } }
+            ])))
+            .mount(&server)
+            .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut publisher = publisher(&server, &dir.path().join("dlq.ndjson")).await;
+        publisher.set_batch_size(3);
+        publisher.set_index_strategy(IndexStrategy::Pattern(
+            "fluss-{tenant}-%Y.%m.%d".parse().unwrap(),
+        ));
+
+        let exporters = [
+            ([10, 1, 0, 1], Some("Berlin")),
+            ([10, 2, 0, 1], Some("lab")),
+            ([10, 1, 0, 1], Some("Berlin")),
+        ];
+        for (exporter, tenant) in exporters {
+            let mut fluss = flow([10, 0, 0, 1].into(), [192, 0, 2, 1].into(), 443, 1000, 10);
+            fluss.exporter = Some(IpAddr::from(exporter));
+            fluss.tenant = tenant.map(str::to_owned);
+            Publisher::publish(&publisher, &fluss).await.unwrap();
+        }
+
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        let lines = bulk_lines(&requests[0]);
+        let mut indices: Vec<_> = lines
+            .chunks(2)
+            .map(|lines| {
+                let index = lines[0]["index"]["_index"].as_str().unwrap().to_owned();
+                (index, lines[1]["tenant"].clone())
+            })
+            .collect();
+        indices.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let date = Utc::now().format("%Y.%m.%d");
+        let berlin = format!("fluss-berlin-{}", date);
+        let lab = format!("fluss-lab-{}", date);
+        assert_eq!(
+            indices,
+            [
+                (berlin.clone(), json!("Berlin")),
+                (berlin, json!("Berlin")),
+                (lab, json!("lab")),
+            ]
+        );
+
+        let stats: Vec<_> = publisher
+            .tenant_stats()
+            .into_iter()
+            .map(|stats| (stats.tenant, stats.documents))
+            .collect();
+        assert_eq!(
+            stats,
+            [(Some("Berlin".to_owned()), 2), (Some("lab".to_owned()), 1)]
+        );
+    }
+
+    #[test]
+    fn metadata_fields_have_ecs_aliases() {
+        let mappings: serde_json::Value = serde_json::from_str(MAPPINGS).unwrap();
+        let properties = &mappings["properties"];
+        for (alias, field) in [
+            ("dns.question.name", "dns_query"),
+            ("url.domain", "http_host"),
+            ("url.original", "http_url"),
+            ("http.response.status_code", "http_status"),
+            ("tls.client.server_name", "tls_sni"),
+        ] {
+            assert_eq!(
+                properties[alias],
+                json!({ "type": "alias", "path": field }),
+                "{}",
+                alias
+            );
+            assert!(properties[field]["type"].is_string(), "{}", field);
+        }
+        assert_eq!(properties["lossy_utf8"]["type"], "boolean");
+    }
+}
@@ -0,0 +1,550 @@
+use crate::Publisher;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use fluss_core::fluss::Fluss;
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tonic::codec::ProstCodec;
+use tonic::transport::{Channel, Endpoint};
+
+const DEFAULT_BATCH_SIZE: usize = 100;
+const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+const PUBLISH_FLOWS: &str = "/fluss.v1.FlowService/PublishFlows";
+
+/// A flow as sent to the collector, `FlowRequest` of `proto/fluss.proto`.
+///
+/// The messages are maintained by hand to not require `protoc` at build time,
+/// they have to be kept in sync with the schema.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FlowRequest {
+    #[prost(string, tag = "1")]
+    pub r#type: String,
+    #[prost(uint64, tag = "2")]
+    pub time_received: u64,
+    #[prost(string, optional, tag = "3")]
+    pub exporter: Option<String>,
+    #[prost(uint64, tag = "4")]
+    pub flow_age: u64,
+    #[prost(uint64, optional, tag = "55")]
+    pub flow_start: Option<u64>,
+    #[prost(uint64, optional, tag = "56")]
+    pub flow_end: Option<u64>,
+    #[prost(int64, optional, tag = "57")]
+    pub clock_skew_ms: Option<i64>,
+    #[prost(string, tag = "5")]
+    pub flow_direction: String,
+    #[prost(bool, tag = "6")]
+    pub is_bidirectional: bool,
+    #[prost(uint32, tag = "7")]
+    pub ingress_interface: u32,
+    #[prost(uint32, tag = "8")]
+    pub egress_interface: u32,
+    #[prost(string, optional, tag = "9")]
+    pub ingress_interface_name: Option<String>,
+    #[prost(string, optional, tag = "10")]
+    pub egress_interface_name: Option<String>,
+    #[prost(uint64, tag = "11")]
+    pub bytes: u64,
+    #[prost(uint64, tag = "12")]
+    pub packets: u64,
+    #[prost(uint64, tag = "13")]
+    pub bytes_in: u64,
+    #[prost(uint64, tag = "14")]
+    pub bytes_out: u64,
+    #[prost(uint64, tag = "15")]
+    pub packets_in: u64,
+    #[prost(uint64, tag = "16")]
+    pub packets_out: u64,
+    #[prost(uint32, optional, tag = "17")]
+    pub sampling_interval: Option<u32>,
+    #[prost(uint32, tag = "18")]
+    pub dscp: u32,
+    #[prost(uint32, tag = "19")]
+    pub ethernet_type: u32,
+    #[prost(string, tag = "20")]
+    pub protocol: String,
+    #[prost(string, optional, tag = "21")]
+    pub src_mac: Option<String>,
+    #[prost(string, optional, tag = "22")]
+    pub dst_mac: Option<String>,
+    #[prost(string, tag = "23")]
+    pub src_addr: String,
+    #[prost(string, tag = "24")]
+    pub dst_addr: String,
+    #[prost(uint32, tag = "25")]
+    pub src_net: u32,
+    #[prost(uint32, tag = "26")]
+    pub dst_net: u32,
+    #[prost(uint32, tag = "27")]
+    pub src_port: u32,
+    #[prost(uint32, tag = "28")]
+    pub dst_port: u32,
+    #[prost(uint32, optional, tag = "29")]
+    pub icmp_type: Option<u32>,
+    #[prost(uint32, optional, tag = "30")]
+    pub icmp_code: Option<u32>,
+    #[prost(uint32, tag = "31")]
+    pub vlan_id: u32,
+    #[prost(uint32, tag = "32")]
+    pub post_vlan_id: u32,
+    #[prost(string, tag = "33")]
+    pub post_nat_src_addr: String,
+    #[prost(string, tag = "34")]
+    pub post_nat_dst_addr: String,
+    #[prost(uint32, tag = "35")]
+    pub post_napt_src_port: u32,
+    #[prost(uint32, tag = "36")]
+    pub post_napt_dst_port: u32,
+    #[prost(string, tag = "37")]
+    pub next_hop_addr: String,
+    #[prost(string, optional, tag = "38")]
+    pub tunnel_type: Option<String>,
+    #[prost(uint64, optional, tag = "39")]
+    pub tunnel_id: Option<u64>,
+    #[prost(string, optional, tag = "40")]
+    pub inner_src_addr: Option<String>,
+    #[prost(string, optional, tag = "41")]
+    pub inner_dst_addr: Option<String>,
+    #[prost(uint32, optional, tag = "42")]
+    pub inner_src_port: Option<u32>,
+    #[prost(uint32, optional, tag = "43")]
+    pub inner_dst_port: Option<u32>,
+    #[prost(string, optional, tag = "44")]
+    pub outer_src_addr: Option<String>,
+    #[prost(string, optional, tag = "45")]
+    pub outer_dst_addr: Option<String>,
+    #[prost(uint32, optional, tag = "46")]
+    pub outer_src_port: Option<u32>,
+    #[prost(uint32, optional, tag = "47")]
+    pub outer_dst_port: Option<u32>,
+    #[prost(uint32, tag = "48")]
+    pub tcp_flags: u32,
+    #[prost(uint32, tag = "49")]
+    pub reverse_tcp_flags: u32,
+    #[prost(string, optional, tag = "50")]
+    pub flow_end_reason: Option<String>,
+    #[prost(string, optional, tag = "51")]
+    pub flow_state: Option<String>,
+    #[prost(string, optional, tag = "52")]
+    pub service: Option<String>,
+    #[prost(btree_map = "string, string", tag = "53")]
+    pub labels: BTreeMap<String, String>,
+    #[prost(btree_map = "string, string", tag = "54")]
+    pub extra: BTreeMap<String, String>,
+    #[prost(string, optional, tag = "67")]
+    pub flow_class: Option<String>,
+    #[prost(string, optional, tag = "68")]
+    pub tenant: Option<String>,
+    #[prost(string, optional, tag = "69")]
+    pub src_net_name: Option<String>,
+    #[prost(string, optional, tag = "70")]
+    pub dst_net_name: Option<String>,
+    #[prost(bool, tag = "59")]
+    pub suspect: bool,
+    #[prost(string, optional, tag = "60")]
+    pub dns_query: Option<String>,
+    #[prost(uint32, optional, tag = "61")]
+    pub dns_qtype: Option<u32>,
+    #[prost(string, optional, tag = "62")]
+    pub http_host: Option<String>,
+    #[prost(string, optional, tag = "63")]
+    pub http_url: Option<String>,
+    #[prost(uint32, optional, tag = "64")]
+    pub http_status: Option<u32>,
+    #[prost(string, optional, tag = "65")]
+    pub tls_sni: Option<String>,
+    #[prost(bool, tag = "66")]
+    pub lossy_utf8: bool,
+}
+
+/// Reply of the collector, `FlowResponse` of `proto/fluss.proto`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct FlowResponse {
+    #[prost(uint64, tag = "1")]
+    pub accepted: u64,
+}
+
+impl From<&Fluss> for FlowRequest {
+    fn from(fluss: &Fluss) -> Self {
+        Self {
+            r#type: fluss.r#type.to_string(),
+            time_received: unix_millis(fluss.time_received),
+            exporter: fluss.exporter.as_ref().map(ToString::to_string),
+            flow_age: fluss.flow_age.as_millis() as u64,
+            flow_start: fluss.flow_start.map(unix_millis),
+            flow_end: fluss.flow_end.map(unix_millis),
+            clock_skew_ms: fluss.clock_skew_ms,
+            flow_direction: fluss.flow_direction.to_string(),
+            is_bidirectional: fluss.is_bidirectional,
+            ingress_interface: fluss.ingress_interface,
+            egress_interface: fluss.egress_interface,
+            ingress_interface_name: fluss.ingress_interface_name.clone(),
+            egress_interface_name: fluss.egress_interface_name.clone(),
+            bytes: fluss.bytes,
+            packets: fluss.packets,
+            bytes_in: fluss.bytes_in,
+            bytes_out: fluss.bytes_out,
+            packets_in: fluss.packets_in,
+            packets_out: fluss.packets_out,
+            sampling_interval: fluss.sampling_interval,
+            dscp: fluss.dscp.into(),
+            ethernet_type: fluss.ethernet_type.into(),
+            protocol: fluss.protocol.to_string(),
+            src_mac: fluss.src_mac.as_ref().map(ToString::to_string),
+            dst_mac: fluss.dst_mac.as_ref().map(ToString::to_string),
+            src_addr: fluss.src_addr.to_string(),
+            dst_addr: fluss.dst_addr.to_string(),
+            src_net: fluss.src_net.into(),
+            dst_net: fluss.dst_net.into(),
+            src_port: fluss.src_port.into(),
+            dst_port: fluss.dst_port.into(),
+            icmp_type: fluss.icmp_type.map(Into::into),
+            icmp_code: fluss.icmp_code.map(Into::into),
+            vlan_id: fluss.vlan_id.into(),
+            post_vlan_id: fluss.post_vlan_id.into(),
+            post_nat_src_addr: fluss.post_nat_src_addr.to_string(),
+            post_nat_dst_addr: fluss.post_nat_dst_addr.to_string(),
+            post_napt_src_port: fluss.post_napt_src_port.into(),
+            post_napt_dst_port: fluss.post_napt_dst_port.into(),
+            next_hop_addr: fluss.next_hop_addr.to_string(),
+            tunnel_type: fluss.tunnel_type.clone(),
+            tunnel_id: fluss.tunnel_id,
+            inner_src_addr: fluss.inner_src_addr.as_ref().map(ToString::to_string),
+            inner_dst_addr: fluss.inner_dst_addr.as_ref().map(ToString::to_string),
+            inner_src_port: fluss.inner_src_port.map(Into::into),
+            inner_dst_port: fluss.inner_dst_port.map(Into::into),
+            outer_src_addr: fluss.outer_src_addr.as_ref().map(ToString::to_string),
+            outer_dst_addr: fluss.outer_dst_addr.as_ref().map(ToString::to_string),
+            outer_src_port: fluss.outer_src_port.map(Into::into),
+            outer_dst_port: fluss.outer_dst_port.map(Into::into),
+            tcp_flags: fluss.tcp_flags.into(),
+            reverse_tcp_flags: fluss.reverse_tcp_flags.into(),
+            flow_end_reason: fluss.flow_end_reason.as_ref().map(ToString::to_string),
+            flow_state: fluss.flow_state.as_ref().map(ToString::to_string),
+            flow_class: fluss.flow_class.as_ref().map(ToString::to_string),
+            service: fluss.service.clone(),
+            tenant: fluss.tenant.clone(),
+            src_net_name: fluss.src_net_name.clone(),
+            dst_net_name: fluss.dst_net_name.clone(),
+            suspect: fluss.suspect,
+            dns_query: fluss.dns_query.clone(),
+            dns_qtype: fluss.dns_qtype.map(Into::into),
+            http_host: fluss.http_host.clone(),
+            http_url: fluss.http_url.clone(),
+            http_status: fluss.http_status.map(Into::into),
+            tls_sni: fluss.tls_sni.clone(),
+            lossy_utf8: fluss.lossy_utf8,
+            labels: fluss.labels.clone(),
+            extra: fluss
+                .extra
+                .iter()
+                .map(|(key, value)| (key.clone(), value.to_string()))
+                .collect(),
+        }
+    }
+}
+
+/// Milliseconds since the unix epoch, earlier times are clamped to the epoch.
+fn unix_millis(time: DateTime<Utc>) -> u64 {
+    time.timestamp_millis().max(0) as u64
+}
+
+/// Forwards flows to a collector implementing the `FlowService` of `proto/fluss.proto`.
+///
+/// Flows are sent in batches through the client streaming `PublishFlows` call.
+/// A batch is sent once it reaches the batch size or a flow arrives after the
+/// flush interval passed, [`GrpcPublisher::flush`] sends it unconditionally.
+pub struct GrpcPublisher {
+    channel: Channel,
+    batch_size: usize,
+    flush_interval: Duration,
+    batch: Mutex<Batch>,
+}
+
+struct Batch {
+    flows: Vec<FlowRequest>,
+    started: Instant,
+}
+
+impl Batch {
+    fn new() -> Self {
+        Self {
+            flows: Vec::new(),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl GrpcPublisher {
+    /// Creates the channel to `endpoint`, e.g. `http://collector:50051`, the
+    /// connection is established on first use.
+    pub fn new(endpoint: &str) -> anyhow::Result<Self> {
+        let channel = Endpoint::from_shared(endpoint.to_owned())?.connect_lazy();
+
+        Ok(Self {
+            channel,
+            batch_size: DEFAULT_BATCH_SIZE,
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            batch: Mutex::new(Batch::new()),
+        })
+    }
+
+    /// Maximum amount of flows sent in a single call.
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = batch_size.max(1);
+    }
+
+    /// Maximum time a flow is held back before the batch is sent.
+    pub fn set_flush_interval(&mut self, flush_interval: Duration) {
+        self.flush_interval = flush_interval;
+    }
+
+    /// Sends all pending flows.
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        let batch = std::mem::replace(&mut *self.batch.lock(), Batch::new());
+        self.send(batch).await
+    }
+
+    async fn send(&self, batch: Batch) -> anyhow::Result<()> {
+        if batch.flows.is_empty() {
+            return Ok(());
+        }
+
+        let flows = batch.flows.len();
+        tracing::debug!(flows, "sending batch");
+
+        let accepted = self.publish_flows(batch.flows).await?;
+        if accepted < flows as u64 {
+            tracing::warn!(flows, accepted, "collector did not accept all flows");
+        }
+
+        Ok(())
+    }
+
+    /// Streams `flows` in a single `PublishFlows` call, returns the accepted flows.
+    async fn publish_flows(&self, flows: Vec<FlowRequest>) -> anyhow::Result<u64> {
+        let mut client = tonic::client::Grpc::new(self.channel.clone());
+        client.ready().await?;
+        let response = client
+            .client_streaming(
+                tonic::Request::new(tokio_stream::iter(flows)),
+                http::uri::PathAndQuery::from_static(PUBLISH_FLOWS),
+                ProstCodec::<FlowRequest, FlowResponse>::default(),
+            )
+            .await?;
+
+        Ok(response.into_inner().accepted)
+    }
+}
+
+#[async_trait]
+impl Publisher for GrpcPublisher {
+    async fn publish(&self, fluss: &Fluss) -> anyhow::Result<()> {
+        let request = FlowRequest::from(fluss);
+
+        let full = {
+            let mut batch = self.batch.lock();
+            batch.flows.push(request);
+
+            if batch.flows.len() >= self.batch_size
+                || batch.started.elapsed() >= self.flush_interval
+            {
+                Some(std::mem::replace(&mut *batch, Batch::new()))
+            } else {
+                None
+            }
+        };
+
+        match full {
+            Some(batch) => self.send(batch).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Calls `PublishFlows` without any flows.
+    async fn health_check(&self) -> anyhow::Result<()> {
+        self.publish_flows(Vec::new()).await?;
+        Ok(())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        GrpcPublisher::flush(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use fluss_core::fluss::FlowClass;
+    use fluss_core::testing::flow;
+    use prost::Message;
+    use std::collections::HashSet;
+    use std::convert::Infallible;
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+    use tonic::body::BoxBody;
+    use tonic::codegen::{BoxFuture, Context, Poll, Service};
+    use tonic::server::{ClientStreamingService, NamedService};
+    use tonic::transport::server::TcpIncoming;
+    use tonic::transport::{Body, Server};
+    use tonic::{Request, Response, Status, Streaming};
+
+    /// A flow with its timestamps, classification and metadata set.
+    fn populated() -> Fluss {
+        let mut fluss = flow(
+            Ipv4Addr::new(192, 0, 2, 1),
+            Ipv4Addr::new(198, 51, 100, 1),
+            443,
+            1500,
+            3,
+        );
+        fluss.flow_start = Some(Utc.timestamp_millis_opt(1_600_000_000_000).unwrap());
+        fluss.flow_end = Some(Utc.timestamp_millis_opt(1_600_000_060_000).unwrap());
+        fluss.clock_skew_ms = Some(-250);
+        fluss.flow_class = Some(FlowClass::Elephant);
+        fluss.service = Some("https".to_owned());
+        fluss.tenant = Some("acme".to_owned());
+        fluss.src_net_name = Some("office".to_owned());
+        fluss.dst_net_name = Some("datacenter".to_owned());
+        fluss.dns_query = Some("example.com".to_owned());
+        fluss.dns_qtype = Some(28);
+        fluss.tls_sni = Some("example.com".to_owned());
+        fluss.labels.insert("site".to_owned(), "fra1".to_owned());
+        fluss.extra.insert("vrf".to_owned(), serde_json::json!(7));
+        fluss
+    }
+
+    #[test]
+    fn flow_request_round_trips() {
+        let request = FlowRequest::from(&populated());
+        let decoded = FlowRequest::decode(request.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(decoded, request);
+
+        assert_eq!(decoded.src_addr, "192.0.2.1");
+        assert_eq!(decoded.dst_port, 443);
+        assert_eq!(decoded.bytes, 1500);
+        assert_eq!(decoded.flow_start, Some(1_600_000_000_000));
+        assert_eq!(decoded.flow_end, Some(1_600_000_060_000));
+        assert_eq!(decoded.clock_skew_ms, Some(-250));
+        assert_eq!(decoded.flow_class.as_deref(), Some("elephant"));
+        assert_eq!(decoded.tenant.as_deref(), Some("acme"));
+        assert_eq!(decoded.src_net_name.as_deref(), Some("office"));
+        assert_eq!(decoded.dst_net_name.as_deref(), Some("datacenter"));
+        assert_eq!(decoded.dns_qtype, Some(28));
+        assert_eq!(decoded.labels["site"], "fra1");
+        assert_eq!(decoded.extra["vrf"], "7");
+    }
+
+    #[test]
+    fn proto_field_numbers_are_unique() {
+        let proto = include_str!("../proto/fluss.proto");
+        let message = &proto[proto.find("message FlowRequest {").unwrap()..];
+        let message = &message[..message.find('}').unwrap()];
+
+        let mut numbers = HashSet::new();
+        for line in message.lines().map(str::trim) {
+            if let Some(number) = line.strip_suffix(';').and_then(|l| l.rsplit(" = ").next()) {
+                assert!(
+                    numbers.insert(number.to_owned()),
+                    "{} is reused: {}",
+                    number,
+                    line
+                );
+            }
+        }
+        assert!(
+            numbers.contains("1") && numbers.contains("70"),
+            "{:?}",
+            numbers
+        );
+    }
+
+    /// A `FlowService` which records the flows it receives.
+    #[derive(Clone, Default)]
+    struct Collector {
+        flows: Arc<Mutex<Vec<FlowRequest>>>,
+    }
+
+    impl NamedService for Collector {
+        const NAME: &'static str = "fluss.v1.FlowService";
+    }
+
+    impl ClientStreamingService<FlowRequest> for Collector {
+        type Response = FlowResponse;
+        type Future = BoxFuture<Response<FlowResponse>, Status>;
+
+        fn call(&mut self, request: Request<Streaming<FlowRequest>>) -> Self::Future {
+            let flows = Arc::clone(&self.flows);
+            Box::pin(async move {
+                let mut stream = request.into_inner();
+                let mut accepted = 0;
+                while let Some(flow) = stream.message().await? {
+                    flows.lock().push(flow);
+                    accepted += 1;
+                }
+                Ok(Response::new(FlowResponse { accepted }))
+            })
+        }
+    }
+
+    impl Service<http::Request<Body>> for Collector {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+            assert_eq!(request.uri().path(), PUBLISH_FLOWS);
+            let collector = self.clone();
+            Box::pin(async move {
+                let codec = ProstCodec::<FlowResponse, FlowRequest>::default();
+                Ok(tonic::server::Grpc::new(codec)
+                    .client_streaming(collector, request)
+                    .await)
+            })
+        }
+    }
+
+    /// Serves a [`Collector`] on an ephemeral port, returns it with its endpoint.
+    async fn serve() -> (Collector, String) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+
+        let collector = Collector::default();
+        let server = Server::builder()
+            .add_service(collector.clone())
+            .serve_with_incoming(incoming);
+        tokio::spawn(server);
+
+        (collector, endpoint)
+    }
+
+    #[tokio::test]
+    async fn batches_are_streamed_to_the_collector() {
+        let (collector, endpoint) = serve().await;
+        let mut publisher = GrpcPublisher::new(&endpoint).unwrap();
+        publisher.set_batch_size(2);
+
+        publisher.health_check().await.unwrap();
+        assert!(collector.flows.lock().is_empty());
+
+        let fluss = populated();
+        for _ in 0..3 {
+            publisher.publish(&fluss).await.unwrap();
+        }
+        // the first batch is full, the third flow waits for the next one
+        assert_eq!(collector.flows.lock().len(), 2);
+
+        publisher.flush().await.unwrap();
+        let flows = collector.flows.lock();
+        assert_eq!(flows.len(), 3);
+        assert!(flows.iter().all(|flow| *flow == FlowRequest::from(&fluss)));
+    }
+}
@@ -0,0 +1,99 @@
+#[cfg(feature = "amqp")]
+pub mod amqp;
+#[cfg(feature = "testing")]
+pub mod capture;
+#[cfg(feature = "clickhouse")]
+pub mod clickhouse;
+pub mod console;
+pub mod dedup;
+#[cfg(feature = "elastic")]
+pub mod elastic;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod merge;
+pub mod null;
+#[cfg(feature = "otel")]
+pub mod otel;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod raw;
+#[cfg(feature = "redis")]
+pub mod redis;
+pub mod rollup;
+pub mod route;
+pub mod summary;
+#[cfg(feature = "syslog")]
+pub mod syslog;
+
+#[cfg(feature = "amqp")]
+pub use self::amqp::AmqpPublisher;
+#[cfg(feature = "testing")]
+pub use self::capture::CapturingPublisher;
+#[cfg(feature = "clickhouse")]
+pub use self::clickhouse::ClickHousePublisher;
+pub use self::console::{ConsoleFormat, ConsolePublisher};
+pub use self::dedup::DeduplicatingPublisher;
+#[cfg(feature = "elastic")]
+pub use self::elastic::ElasticPublisher;
+#[cfg(feature = "grpc")]
+pub use self::grpc::GrpcPublisher;
+pub use self::merge::FlowMerger;
+pub use self::null::NullPublisher;
+#[cfg(feature = "otel")]
+pub use self::otel::OtelPublisher;
+#[cfg(feature = "parquet")]
+pub use self::parquet::ParquetPublisher;
+#[cfg(feature = "postgres")]
+pub use self::postgres::PostgresPublisher;
+pub use self::raw::RawPublisher;
+#[cfg(feature = "redis")]
+pub use self::redis::RedisPublisher;
+pub use self::rollup::RollupPublisher;
+pub use self::route::RoutingPublisher;
+pub use self::summary::SummaryPublisher;
+#[cfg(feature = "syslog")]
+pub use self::syslog::{SyslogPublisher, SyslogTransport};
+
+use async_trait::async_trait;
+use fluss_core::fluss::Fluss;
+use std::sync::Arc;
+
+/// Publishes items, by default flows.
+///
+/// Publishers are generic over the published item so the same output can be
+/// used for flows, raw record sets or aggregates.
+#[async_trait]
+pub trait Publisher<T: ?Sized + Sync = Fluss> {
+    async fn publish(&self, item: &T) -> anyhow::Result<()>;
+
+    /// Checks whether the backend of the publisher is reachable without publishing anything.
+    async fn health_check(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Publishes all buffered items, called before the collector exits.
+    async fn flush(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T, P> Publisher<T> for Arc<P>
+where
+    T: ?Sized + Sync,
+    P: Publisher<T> + Send + Sync + ?Sized,
+{
+    async fn publish(&self, item: &T) -> anyhow::Result<()> {
+        (**self).publish(item).await
+    }
+
+    async fn health_check(&self) -> anyhow::Result<()> {
+        (**self).health_check().await
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        (**self).flush().await
+    }
+}
@@ -0,0 +1,161 @@
+use crate::Publisher;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use fluss_core::flow_key::FlowKey;
+use fluss_core::fluss::{FlowState, Fluss};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Combines the two directions of a connection into a single bidirectional flow.
+///
+/// Flows are held back until the flow of the opposite direction arrives, flows
+/// without a counterpart within the window are published unchanged.
+/// [`FlowMerger::flush_expired`] has to be called periodically to publish
+/// expired flows while no new flows arrive.
+pub struct FlowMerger {
+    window: Duration,
+    inner: Box<dyn Publisher + Send + Sync>,
+    pending: Mutex<HashMap<FlowKey, (Instant, Fluss)>>,
+}
+
+impl FlowMerger {
+    pub fn new(window: Duration, inner: Box<dyn Publisher + Send + Sync>) -> Self {
+        Self {
+            window,
+            inner,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Publishes all flows which did not find their counterpart within the window.
+    pub async fn flush_expired(&self) -> anyhow::Result<()> {
+        let expired = self.take_expired(&mut self.pending.lock(), Instant::now());
+        self.publish_all(expired).await
+    }
+
+    /// Publishes all pending flows.
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        let pending = std::mem::take(&mut *self.pending.lock());
+        self.publish_all(pending.into_iter().map(|(_, (_, fluss))| fluss).collect())
+            .await
+    }
+
+    fn take_expired(
+        &self,
+        pending: &mut HashMap<FlowKey, (Instant, Fluss)>,
+        now: Instant,
+    ) -> Vec<Fluss> {
+        let expired: Vec<_> = pending
+            .iter()
+            .filter(|(_, (received, _))| now.duration_since(*received) >= self.window)
+            .map(|(key, _)| *key)
+            .collect();
+
+        expired
+            .into_iter()
+            .filter_map(|key| pending.remove(&key))
+            .map(|(_, fluss)| fluss)
+            .collect()
+    }
+
+    /// Publishes every flow, the first error is returned after all flows were published.
+    async fn publish_all(&self, flows: Vec<Fluss>) -> anyhow::Result<()> {
+        let mut result = Ok(());
+        for fluss in flows {
+            if let Err(err) = self.inner.publish(&fluss).await {
+                result = result.and(Err(err));
+            }
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl Publisher for FlowMerger {
+    async fn publish(&self, fluss: &Fluss) -> anyhow::Result<()> {
+        // biflows already contain both directions
+        if fluss.is_bidirectional {
+            return self.inner.publish(fluss).await;
+        }
+
+        let key = FlowKey::from(fluss);
+        let now = Instant::now();
+
+        let flows = {
+            let mut pending = self.pending.lock();
+            let mut flows = self.take_expired(&mut pending, now);
+
+            match pending.remove(&key.reversed()) {
+                Some((_, forward)) => flows.push(merge(forward, fluss)),
+                // a second flow in the same direction replaces the pending one
+                None => flows.extend(
+                    pending
+                        .insert(key, (now, fluss.clone()))
+                        .map(|(_, fluss)| fluss),
+                ),
+            }
+
+            flows
+        };
+
+        self.publish_all(flows).await
+    }
+
+    async fn health_check(&self) -> anyhow::Result<()> {
+        self.inner.health_check().await
+    }
+
+    /// Publishes all pending flows, even if their counterpart may still arrive.
+    async fn flush(&self) -> anyhow::Result<()> {
+        let result = FlowMerger::flush(self).await;
+        result.and(self.inner.flush().await)
+    }
+}
+
+/// Combines two flows of opposite directions, `forward` determines the direction.
+fn merge(forward: Fluss, reverse: &Fluss) -> Fluss {
+    // the state depends on the control bits of both directions
+    let tcp_flags = forward.tcp_flags | reverse.tcp_flags;
+    let flow_end_reason = forward.flow_end_reason.or(reverse.flow_end_reason);
+
+    Fluss {
+        is_bidirectional: true,
+        flow_age: forward.flow_age.max(reverse.flow_age),
+        flow_start: min_time(forward.flow_start, reverse.flow_start),
+        flow_end: forward.flow_end.max(reverse.flow_end),
+
+        bytes: forward.bytes.saturating_add(reverse.bytes),
+        packets: forward.packets.saturating_add(reverse.packets),
+        bytes_in: forward.bytes,
+        bytes_out: reverse.bytes,
+        packets_in: forward.packets,
+        packets_out: reverse.packets,
+
+        tcp_flags: forward.tcp_flags,
+        reverse_tcp_flags: reverse.tcp_flags,
+        flow_end_reason,
+        flow_state: FlowState::classify(forward.protocol, tcp_flags, flow_end_reason),
+        // the class of the larger direction, it is not known how the directions were classified
+        flow_class: forward.flow_class.max(reverse.flow_class),
+        // usually only the direction of the request carries the metadata
+        dns_query: forward.dns_query.or_else(|| reverse.dns_query.clone()),
+        dns_qtype: forward.dns_qtype.or(reverse.dns_qtype),
+        http_host: forward.http_host.or_else(|| reverse.http_host.clone()),
+        http_url: forward.http_url.or_else(|| reverse.http_url.clone()),
+        http_status: forward.http_status.or(reverse.http_status),
+        tls_sni: forward.tls_sni.or_else(|| reverse.tls_sni.clone()),
+        lossy_utf8: forward.lossy_utf8 || reverse.lossy_utf8,
+        suspect: forward.suspect || reverse.suspect,
+
+        ..forward
+    }
+}
+
+/// Returns the earlier of two optional timestamps.
+fn min_time(a: Option<DateTime<Utc>>, b: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
@@ -0,0 +1,28 @@
+use crate::Publisher;
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Drops all items, only counts how many were published.
+#[derive(Debug, Default)]
+pub struct NullPublisher {
+    published: AtomicU64,
+}
+
+impl NullPublisher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of items published so far.
+    pub fn published(&self) -> u64 {
+        self.published.load(Ordering::Relaxed)
+    }
+}
+
+#[async_trait]
+impl<T: ?Sized + Sync> Publisher<T> for NullPublisher {
+    async fn publish(&self, _item: &T) -> anyhow::Result<()> {
+        self.published.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
@@ -0,0 +1,269 @@
+use crate::Publisher;
+use async_trait::async_trait;
+use fluss_core::fluss::Fluss;
+use opentelemetry::logs::{AnyValue, LogRecord as _, Logger as _, LoggerProvider as _};
+use opentelemetry::metrics::{Counter, MeterProvider as _};
+use opentelemetry::{Key, KeyValue};
+use opentelemetry_otlp::{LogExporter, MetricExporter, WithExportConfig};
+use opentelemetry_sdk::logs::{SdkLogger, SdkLoggerProvider};
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use std::time::SystemTime;
+
+const SCOPE: &str = "fluss";
+
+/// Exports flows as OpenTelemetry log records and traffic counters through OTLP/gRPC.
+///
+/// Every serialized field of a flow becomes an attribute of its log record.
+/// Bytes and packets are additionally counted in `fluss.bytes.total` and
+/// `fluss.packets.total`, labeled by source, destination and protocol. The
+/// address labels are of high cardinality, collectors may need to drop them.
+///
+/// Records and metrics are exported in the background, call
+/// [`OtelPublisher::shutdown`] to export the pending data before exiting.
+pub struct OtelPublisher {
+    logger_provider: SdkLoggerProvider,
+    meter_provider: SdkMeterProvider,
+    logger: SdkLogger,
+    bytes: Counter<u64>,
+    packets: Counter<u64>,
+}
+
+impl OtelPublisher {
+    /// Creates the exporters for the collector at `endpoint`, e.g. `http://collector:4317`.
+    ///
+    /// Has to be called within a tokio runtime.
+    pub fn new(endpoint: &str) -> anyhow::Result<Self> {
+        let logs = LogExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()?;
+        let logger_provider = SdkLoggerProvider::builder()
+            .with_batch_exporter(logs)
+            .build();
+
+        let metrics = MetricExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()?;
+        let meter_provider = SdkMeterProvider::builder()
+            .with_periodic_exporter(metrics)
+            .build();
+
+        Ok(Self::with_providers(logger_provider, meter_provider))
+    }
+
+    fn with_providers(
+        logger_provider: SdkLoggerProvider,
+        meter_provider: SdkMeterProvider,
+    ) -> Self {
+        let meter = meter_provider.meter(SCOPE);
+        let bytes = meter
+            .u64_counter("fluss.bytes.total")
+            .with_unit("By")
+            .with_description("Bytes of all published flows")
+            .build();
+        let packets = meter
+            .u64_counter("fluss.packets.total")
+            .with_description("Packets of all published flows")
+            .build();
+
+        Self {
+            logger: logger_provider.logger(SCOPE),
+            logger_provider,
+            meter_provider,
+            bytes,
+            packets,
+        }
+    }
+
+    /// Exports all pending records and metrics and stops the exporters.
+    pub fn shutdown(&self) -> anyhow::Result<()> {
+        self.logger_provider.shutdown()?;
+        self.meter_provider.shutdown()?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Publisher for OtelPublisher {
+    async fn publish(&self, fluss: &Fluss) -> anyhow::Result<()> {
+        let fields = match serde_json::to_value(fluss)? {
+            serde_json::Value::Object(fields) => fields,
+            _ => unreachable!("flows are serialized as objects"),
+        };
+
+        let mut record = self.logger.create_log_record();
+        record.set_timestamp(SystemTime::from(fluss.time_received));
+        record.set_observed_timestamp(SystemTime::now());
+        record.set_event_name("fluss.flow");
+        record.set_body(AnyValue::from(fluss.display(false).to_string()));
+        record.add_attributes(
+            fields
+                .into_iter()
+                .filter_map(|(key, value)| Some((Key::new(key), any_value(value)?))),
+        );
+        self.logger.emit(record);
+
+        let attributes = [
+            KeyValue::new("src_addr", fluss.src_addr.to_string()),
+            KeyValue::new("dst_addr", fluss.dst_addr.to_string()),
+            KeyValue::new("protocol", fluss.protocol.to_string()),
+        ];
+        self.bytes.add(fluss.normalized_bytes(), &attributes);
+        self.packets.add(fluss.normalized_packets(), &attributes);
+
+        Ok(())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        self.logger_provider.force_flush()?;
+        self.meter_provider.force_flush()?;
+        Ok(())
+    }
+}
+
+/// Converts a serialized field into an attribute value, `None` for missing values.
+fn any_value(value: serde_json::Value) -> Option<AnyValue> {
+    use serde_json::Value;
+
+    let value = match value {
+        Value::Null => return None,
+        Value::Bool(value) => AnyValue::from(value),
+        Value::Number(number) => match number.as_i64() {
+            Some(value) => AnyValue::from(value),
+            None => AnyValue::from(number.as_f64()?),
+        },
+        Value::String(value) => AnyValue::from(value),
+        // custom fields can be of any structure, nested values are kept as JSON
+        value @ (Value::Array(_) | Value::Object(_)) => AnyValue::from(value.to_string()),
+    };
+
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fluss_core::testing::flow;
+    use opentelemetry_sdk::logs::InMemoryLogExporter;
+    use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+    use opentelemetry_sdk::metrics::InMemoryMetricExporter;
+    use std::collections::BTreeMap;
+    use std::net::Ipv4Addr;
+
+    fn publisher() -> (OtelPublisher, InMemoryLogExporter, InMemoryMetricExporter) {
+        let logs = InMemoryLogExporter::default();
+        let metrics = InMemoryMetricExporter::default();
+        let logger_provider = SdkLoggerProvider::builder()
+            .with_simple_exporter(logs.clone())
+            .build();
+        let meter_provider = SdkMeterProvider::builder()
+            .with_periodic_exporter(metrics.clone())
+            .build();
+
+        let publisher = OtelPublisher::with_providers(logger_provider, meter_provider);
+        (publisher, logs, metrics)
+    }
+
+    fn fluss(src: u8, bytes: u64, packets: u64) -> Fluss {
+        flow(
+            Ipv4Addr::new(192, 0, 2, src),
+            Ipv4Addr::new(198, 51, 100, 1),
+            443,
+            bytes,
+            packets,
+        )
+    }
+
+    /// Returns the sorted labels and the values of all data points of the counter `name`.
+    fn counter(metrics: &InMemoryMetricExporter, name: &str) -> BTreeMap<Vec<String>, u64> {
+        let mut points = BTreeMap::new();
+        for resource in metrics.get_finished_metrics().unwrap() {
+            let metrics = resource.scope_metrics().flat_map(|scope| scope.metrics());
+            for metric in metrics.filter(|metric| metric.name() == name) {
+                let sum = match metric.data() {
+                    AggregatedMetrics::U64(MetricData::Sum(sum)) => sum,
+                    data => panic!("{} is not a counter: {:?}", name, data),
+                };
+                for point in sum.data_points() {
+                    let mut labels: Vec<_> = point
+                        .attributes()
+                        .map(|kv| format!("{}={}", kv.key, kv.value))
+                        .collect();
+                    labels.sort();
+                    points.insert(labels, point.value());
+                }
+            }
+        }
+        points
+    }
+
+    #[tokio::test]
+    async fn flows_are_log_records_with_their_fields() {
+        let (publisher, logs, _) = publisher();
+        let mut fluss = fluss(1, 1500, 3);
+        fluss.is_bidirectional = true;
+        fluss.labels.insert("site".to_owned(), "fra1".to_owned());
+        fluss
+            .extra
+            .insert("rules".to_owned(), serde_json::json!(["allow", "log"]));
+        publisher.publish(&fluss).await.unwrap();
+        publisher.flush().await.unwrap();
+
+        let logs = logs.get_emitted_logs().unwrap();
+        assert_eq!(logs.len(), 1);
+        let record = &logs[0].record;
+        assert_eq!(logs[0].instrumentation.name(), SCOPE);
+        assert_eq!(record.event_name(), Some("fluss.flow"));
+        assert_eq!(
+            record.timestamp(),
+            Some(SystemTime::from(fluss.time_received))
+        );
+        assert_eq!(
+            record.body(),
+            Some(&AnyValue::from(fluss.display(false).to_string()))
+        );
+
+        let attributes: BTreeMap<_, _> = record
+            .attributes_iter()
+            .map(|(key, value)| (key.as_str(), value))
+            .collect();
+        assert_eq!(attributes["src_addr"], &AnyValue::from("192.0.2.1"));
+        assert_eq!(attributes["protocol"], &AnyValue::from("tcp"));
+        assert_eq!(attributes["dst_port"], &AnyValue::from(443i64));
+        assert_eq!(attributes["bytes"], &AnyValue::from(1500i64));
+        assert_eq!(attributes["is_bidirectional"], &AnyValue::from(true));
+        assert_eq!(attributes["site"], &AnyValue::from("fra1"));
+        // nested custom fields are kept as JSON
+        assert_eq!(attributes["rules"], &AnyValue::from(r#"["allow","log"]"#));
+        // fields without a value are left out
+        assert!(!attributes.contains_key("icmp_type"));
+        assert!(!attributes.contains_key("tenant"));
+    }
+
+    #[tokio::test]
+    async fn traffic_is_counted_by_addresses_and_protocol() {
+        let (publisher, _, metrics) = publisher();
+        for fluss in [fluss(1, 1500, 3), fluss(1, 500, 1), fluss(2, 100, 2)] {
+            publisher.publish(&fluss).await.unwrap();
+        }
+        publisher.flush().await.unwrap();
+
+        let labels = |src: &str| {
+            vec![
+                "dst_addr=198.51.100.1".to_owned(),
+                "protocol=tcp".to_owned(),
+                format!("src_addr={}", src),
+            ]
+        };
+        let bytes = counter(&metrics, "fluss.bytes.total");
+        assert_eq!(bytes.len(), 2);
+        assert_eq!(bytes[&labels("192.0.2.1")], 2000);
+        assert_eq!(bytes[&labels("192.0.2.2")], 100);
+
+        let packets = counter(&metrics, "fluss.packets.total");
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[&labels("192.0.2.1")], 4);
+        assert_eq!(packets[&labels("192.0.2.2")], 2);
+    }
+}
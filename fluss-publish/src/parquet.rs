@@ -0,0 +1,356 @@
+use crate::Publisher;
+use ::parquet::arrow::ArrowWriter;
+use ::parquet::basic::{Compression, ZstdLevel};
+use ::parquet::file::properties::WriterProperties;
+use async_trait::async_trait;
+use chrono::Utc;
+use fluss_core::arrow::{schema, FlussArrowBuilder};
+use fluss_core::fluss::Fluss;
+use parking_lot::Mutex;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const DEFAULT_BATCH_SIZE: usize = 10_000;
+const DEFAULT_ROTATE: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_MAX_FILE_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Writes flows to Apache Parquet files for offline analysis, e.g. with DuckDB or Spark.
+///
+/// Rows follow the [`schema`] of the Arrow conversion, string columns are
+/// dictionary encoded. Flows are buffered and converted into record batches
+/// on a blocking thread. A file is finished once it is older than the rotation
+/// interval or larger than the maximum size, the next flows start a new file.
+///
+/// Files are written as `flows-<time>.parquet.part` and renamed to
+/// `flows-<time>.parquet` once they are finished, so readers of the directory
+/// never see a file without its footer. Call [`ParquetPublisher::close`]
+/// before exiting to finish the current file.
+pub struct ParquetPublisher {
+    settings: Settings,
+    batch_size: usize,
+    batch: Mutex<Vec<Fluss>>,
+    // taken by the blocking writes, the lock keeps the order of the batches
+    file: tokio::sync::Mutex<Option<ParquetFile>>,
+}
+
+#[derive(Debug, Clone)]
+struct Settings {
+    dir: PathBuf,
+    compression: Compression,
+    rotate: Duration,
+    max_file_size: u64,
+}
+
+impl ParquetPublisher {
+    /// Writes the files to `dir`, which is created if it does not exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            settings: Settings {
+                dir: dir.into(),
+                compression: Compression::ZSTD(ZstdLevel::default()),
+                rotate: DEFAULT_ROTATE,
+                max_file_size: DEFAULT_MAX_FILE_SIZE,
+            },
+            batch_size: DEFAULT_BATCH_SIZE,
+            batch: Mutex::new(Vec::new()),
+            file: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// Compression of the column chunks, zstd by default.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.settings.compression = compression;
+    }
+
+    /// Maximum time flows are written to the same file.
+    pub fn set_rotate(&mut self, rotate: Duration) {
+        self.settings.rotate = rotate;
+    }
+
+    /// Size in bytes after which a file is finished, including the buffered row group.
+    pub fn set_max_file_size(&mut self, max_file_size: u64) {
+        self.settings.max_file_size = max_file_size.max(1);
+    }
+
+    /// Amount of flows converted into a single record batch.
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = batch_size.max(1);
+    }
+
+    /// Writes all pending flows and finishes the current file if it is due for rotation.
+    ///
+    /// Has to be called periodically, otherwise files are only rotated when flows are written.
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        let flows = std::mem::take(&mut *self.batch.lock());
+        self.write(flows, false).await
+    }
+
+    /// Writes all pending flows and finishes the current file.
+    pub async fn close(&self) -> anyhow::Result<()> {
+        let flows = std::mem::take(&mut *self.batch.lock());
+        self.write(flows, true).await
+    }
+
+    async fn write(&self, flows: Vec<Fluss>, finish: bool) -> anyhow::Result<()> {
+        let mut file = self.file.lock().await;
+        let mut current = file.take();
+        let settings = self.settings.clone();
+
+        let (current, result) = tokio::task::spawn_blocking(move || {
+            let result = write_flows(&settings, &mut current, &flows, finish);
+            (current, result)
+        })
+        .await?;
+
+        *file = current;
+        result
+    }
+}
+
+/// Appends `flows` to the current file and rotates it if it is due or `finish` is set.
+fn write_flows(
+    settings: &Settings,
+    file: &mut Option<ParquetFile>,
+    flows: &[Fluss],
+    finish: bool,
+) -> anyhow::Result<()> {
+    if !flows.is_empty() {
+        let mut builder = FlussArrowBuilder::new();
+        for flow in flows {
+            builder.push(flow);
+        }
+
+        if file.is_none() {
+            *file = Some(ParquetFile::create(settings)?);
+        }
+        let current = file.as_mut().expect("the file was just created");
+        current.writer.write(&builder.finish()?)?;
+    }
+
+    let rotate = file.as_ref().is_some_and(|current| {
+        finish
+            || current.opened.elapsed() >= settings.rotate
+            || current.size() >= settings.max_file_size
+    });
+    if rotate {
+        let path = file.take().expect("only open files are rotated").finish()?;
+        tracing::info!(path = %path.display(), "finished parquet file");
+    }
+
+    Ok(())
+}
+
+struct ParquetFile {
+    // path of the unfinished file
+    path: PathBuf,
+    writer: ArrowWriter<File>,
+    opened: Instant,
+}
+
+impl ParquetFile {
+    fn create(settings: &Settings) -> anyhow::Result<Self> {
+        fs::create_dir_all(&settings.dir)?;
+        let name = format!(
+            "flows-{}.parquet.part",
+            Utc::now().format("%Y%m%dT%H%M%S%.3fZ")
+        );
+        let path = settings.dir.join(name);
+
+        let properties = WriterProperties::builder()
+            .set_compression(settings.compression)
+            .build();
+        let writer =
+            ArrowWriter::try_new(File::create(&path)?, Arc::new(schema()), Some(properties))?;
+
+        tracing::debug!(path = %path.display(), "created parquet file");
+
+        Ok(Self {
+            path,
+            writer,
+            opened: Instant::now(),
+        })
+    }
+
+    fn size(&self) -> u64 {
+        (self.writer.bytes_written() + self.writer.in_progress_size()) as u64
+    }
+
+    /// Writes the buffered rows and the footer, returns the path of the finished file.
+    fn finish(self) -> anyhow::Result<PathBuf> {
+        self.writer.close()?;
+        // strips the `.part` extension
+        let finished = self.path.with_extension("");
+        fs::rename(&self.path, &finished)?;
+        Ok(finished)
+    }
+}
+
+#[async_trait]
+impl Publisher for ParquetPublisher {
+    async fn publish(&self, fluss: &Fluss) -> anyhow::Result<()> {
+        let full = {
+            let mut batch = self.batch.lock();
+            batch.push(fluss.clone());
+
+            if batch.len() >= self.batch_size {
+                Some(std::mem::take(&mut *batch))
+            } else {
+                None
+            }
+        };
+
+        match full {
+            Some(flows) => self.write(flows, false).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn health_check(&self) -> anyhow::Result<()> {
+        tokio::fs::create_dir_all(&self.settings.dir).await?;
+        Ok(())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        self.close().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use arrow::array::{Array, StringArray, TimestampMillisecondArray, UInt16Array, UInt64Array};
+    use arrow::record_batch::RecordBatch;
+    use fluss_core::testing::flow;
+    use std::net::Ipv4Addr;
+    use std::path::Path;
+
+    fn flows(count: u8) -> Vec<Fluss> {
+        (0..count)
+            .map(|i| {
+                flow(
+                    Ipv4Addr::new(192, 0, 2, i),
+                    Ipv4Addr::new(198, 51, 100, 1),
+                    1000 + u16::from(i),
+                    1500 * u64::from(i),
+                    u64::from(i),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the names of the files in `dir`, sorted.
+    fn files(dir: &Path) -> Vec<String> {
+        let mut files: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn read(path: &Path) -> Vec<RecordBatch> {
+        ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> &'a T {
+        batch
+            .column_by_name(name)
+            .unwrap()
+            .as_any()
+            .downcast_ref()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn written_flows_are_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut publisher = ParquetPublisher::new(dir.path());
+        publisher.set_batch_size(3);
+        let flows = flows(10);
+        for flow in &flows {
+            publisher.publish(flow).await.unwrap();
+        }
+        // full batches are written to an unfinished file
+        let unfinished = files(dir.path());
+        assert_eq!(unfinished.len(), 1);
+        assert!(unfinished[0].ends_with(".parquet.part"), "{:?}", unfinished);
+
+        publisher.close().await.unwrap();
+        let finished = files(dir.path());
+        assert_eq!(finished.len(), 1);
+        assert!(finished[0].ends_with(".parquet"), "{:?}", finished);
+
+        let batches = read(&dir.path().join(&finished[0]));
+        assert_eq!(batches.len(), 1);
+        let batch = &batches[0];
+        assert_eq!(batch.schema().as_ref(), &schema());
+        assert_eq!(batch.num_rows(), flows.len());
+
+        let src_addr: &StringArray = column(batch, "src_addr");
+        let dst_port: &UInt16Array = column(batch, "dst_port");
+        let bytes: &UInt64Array = column(batch, "bytes");
+        let packets: &UInt64Array = column(batch, "packets");
+        let protocol: &StringArray = column(batch, "protocol");
+        let time_received: &TimestampMillisecondArray = column(batch, "time_received");
+        let flow_start: &TimestampMillisecondArray = column(batch, "flow_start");
+        for row in [0, 4, 9] {
+            let flow = &flows[row];
+            assert_eq!(src_addr.value(row), flow.src_addr.to_string());
+            assert_eq!(dst_port.value(row), flow.dst_port);
+            assert_eq!(bytes.value(row), flow.bytes);
+            assert_eq!(packets.value(row), flow.packets);
+            assert_eq!(protocol.value(row), "tcp");
+            assert_eq!(
+                time_received.value(row),
+                flow.time_received.timestamp_millis()
+            );
+            assert_eq!(flow_start.is_null(row), flow.flow_start.is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn files_are_rotated_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut publisher = ParquetPublisher::new(dir.path());
+        publisher.set_batch_size(2);
+        publisher.set_max_file_size(1);
+        publisher.set_compression(Compression::UNCOMPRESSED);
+
+        for flow in &flows(4) {
+            publisher.publish(flow).await.unwrap();
+            // file names have millisecond precision
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        publisher.close().await.unwrap();
+
+        // every batch exceeds the size, nothing is left to finish
+        let files = files(dir.path());
+        assert_eq!(files.len(), 2, "{:?}", files);
+        let rows: Vec<_> = files
+            .iter()
+            .map(|file| {
+                assert!(file.ends_with(".parquet"), "{}", file);
+                let batches = read(&dir.path().join(file));
+                batches.iter().map(RecordBatch::num_rows).sum::<usize>()
+            })
+            .collect();
+        assert_eq!(rows, [2, 2]);
+    }
+
+    #[tokio::test]
+    async fn closing_without_flows_creates_no_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let publisher = ParquetPublisher::new(dir.path().join("flows"));
+        publisher.health_check().await.unwrap();
+        publisher.close().await.unwrap();
+        assert!(files(&dir.path().join("flows")).is_empty());
+    }
+}
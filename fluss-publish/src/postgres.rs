@@ -0,0 +1,346 @@
+use crate::Publisher;
+use async_trait::async_trait;
+use fluss_core::fluss::Fluss;
+use parking_lot::Mutex;
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use std::time::{Duration, Instant};
+
+const DEFAULT_TABLE: &str = "flows";
+const DEFAULT_BATCH_SIZE: usize = 100;
+const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Table layout matching the serialized [`Fluss`], `{table}` is replaced with the table name.
+///
+/// Custom fields and labels are not part of the table and skipped on insert.
+pub const TABLE_DDL: &str = "CREATE TABLE IF NOT EXISTS {table} (
+    type TEXT NOT NULL,
+    time_received TIMESTAMPTZ NOT NULL,
+    exporter INET,
+    flow_age BIGINT NOT NULL,
+    flow_start TIMESTAMPTZ,
+    flow_end TIMESTAMPTZ,
+    clock_skew_ms BIGINT,
+    flow_direction TEXT NOT NULL,
+    is_bidirectional BOOLEAN NOT NULL,
+    ingress_interface BIGINT NOT NULL,
+    egress_interface BIGINT NOT NULL,
+    ingress_interface_name TEXT,
+    egress_interface_name TEXT,
+    bytes BIGINT NOT NULL,
+    packets BIGINT NOT NULL,
+    bytes_in BIGINT NOT NULL,
+    bytes_out BIGINT NOT NULL,
+    packets_in BIGINT NOT NULL,
+    packets_out BIGINT NOT NULL,
+    sampling_interval BIGINT,
+    dscp SMALLINT NOT NULL,
+    ethernet_type INTEGER NOT NULL,
+    protocol TEXT NOT NULL,
+    src_mac MACADDR,
+    dst_mac MACADDR,
+    src_addr INET NOT NULL,
+    dst_addr INET NOT NULL,
+    src_net SMALLINT NOT NULL,
+    dst_net SMALLINT NOT NULL,
+    src_port INTEGER NOT NULL,
+    dst_port INTEGER NOT NULL,
+    icmp_type SMALLINT,
+    icmp_code SMALLINT,
+    vlan_id INTEGER NOT NULL,
+    post_vlan_id INTEGER NOT NULL,
+    post_nat_src_addr INET NOT NULL,
+    post_nat_dst_addr INET NOT NULL,
+    post_napt_src_port INTEGER NOT NULL,
+    post_napt_dst_port INTEGER NOT NULL,
+    next_hop_addr INET NOT NULL,
+    tunnel_type TEXT,
+    tunnel_id BIGINT,
+    inner_src_addr INET,
+    inner_dst_addr INET,
+    inner_src_port INTEGER,
+    inner_dst_port INTEGER,
+    outer_src_addr INET,
+    outer_dst_addr INET,
+    outer_src_port INTEGER,
+    outer_dst_port INTEGER,
+    tcp_flags INTEGER NOT NULL,
+    reverse_tcp_flags INTEGER NOT NULL,
+    flow_end_reason TEXT,
+    flow_state TEXT,
+    flow_class TEXT,
+    service TEXT,
+    tenant TEXT,
+    src_net_name TEXT,
+    dst_net_name TEXT,
+    dns_query TEXT,
+    dns_qtype INTEGER,
+    http_host TEXT,
+    http_url TEXT,
+    http_status INTEGER,
+    tls_sni TEXT,
+    lossy_utf8 BOOLEAN NOT NULL,
+    suspect BOOLEAN NOT NULL
+)";
+
+/// Inserts flows in batches into a PostgreSQL table, with TimescaleDB the
+/// table is a hypertable partitioned by `time_received`.
+///
+/// A batch is sent once it reaches the batch size or a flow arrives after the
+/// flush interval passed, [`PostgresPublisher::flush`] sends it unconditionally.
+pub struct PostgresPublisher {
+    pool: PgPool,
+    table: String,
+    batch_size: usize,
+    flush_interval: Duration,
+    batch: Mutex<Batch>,
+}
+
+struct Batch {
+    rows: Vec<serde_json::Value>,
+    started: Instant,
+}
+
+impl Batch {
+    fn new() -> Self {
+        Self {
+            rows: Vec::new(),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl PostgresPublisher {
+    /// Creates the connection pool, connections are established on first use.
+    pub fn new(url: &str) -> anyhow::Result<Self> {
+        let pool = PgPoolOptions::new().connect_lazy(url)?;
+
+        Ok(Self {
+            pool,
+            table: DEFAULT_TABLE.to_owned(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            batch: Mutex::new(Batch::new()),
+        })
+    }
+
+    /// Table flows are inserted into, defaults to `flows`.
+    pub fn set_table(&mut self, table: &str) {
+        self.table = table.to_owned();
+    }
+
+    /// Maximum amount of flows sent in a single insert.
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = batch_size.max(1);
+    }
+
+    /// Maximum time a flow is held back before the batch is sent.
+    pub fn set_flush_interval(&mut self, flush_interval: Duration) {
+        self.flush_interval = flush_interval;
+    }
+
+    /// Creates the table with [`TABLE_DDL`] if it does not exist yet and turns
+    /// it into a TimescaleDB hypertable.
+    pub async fn ensure_schema(&self) -> anyhow::Result<()> {
+        sqlx::query(&self.ddl()).execute(&self.pool).await?;
+
+        sqlx::query(
+            "SELECT create_hypertable($1::regclass, 'time_received', if_not_exists => TRUE)",
+        )
+        .bind(&self.table)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    fn ddl(&self) -> String {
+        TABLE_DDL.replace("{table}", &self.table)
+    }
+
+    /// The whole batch is a single parameter, postgres converts the serialized
+    /// flows into rows of the table and ignores fields without a column.
+    fn insert_query(&self) -> String {
+        format!(
+            "INSERT INTO {table} SELECT * FROM jsonb_populate_recordset(NULL::{table}, $1)",
+            table = self.table
+        )
+    }
+
+    /// Sends all pending flows.
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        let batch = std::mem::replace(&mut *self.batch.lock(), Batch::new());
+        self.insert(batch).await
+    }
+
+    async fn insert(&self, batch: Batch) -> anyhow::Result<()> {
+        if batch.rows.is_empty() {
+            return Ok(());
+        }
+
+        tracing::debug!(
+            rows = batch.rows.len(),
+            table = self.table.as_str(),
+            "inserting batch"
+        );
+        sqlx::query(&self.insert_query())
+            .bind(serde_json::Value::Array(batch.rows))
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Publisher for PostgresPublisher {
+    async fn publish(&self, fluss: &Fluss) -> anyhow::Result<()> {
+        let row = serde_json::to_value(fluss)?;
+
+        let full = {
+            let mut batch = self.batch.lock();
+            batch.rows.push(row);
+
+            if batch.rows.len() >= self.batch_size || batch.started.elapsed() >= self.flush_interval
+            {
+                Some(std::mem::replace(&mut *batch, Batch::new()))
+            } else {
+                None
+            }
+        };
+
+        match full {
+            Some(batch) => self.insert(batch).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn health_check(&self) -> anyhow::Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        PostgresPublisher::flush(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use fluss_core::testing::flow;
+    use std::net::Ipv4Addr;
+
+    fn publisher(table: &str) -> PostgresPublisher {
+        let mut publisher = PostgresPublisher::new("postgres://localhost/fluss").unwrap();
+        publisher.set_table(table);
+        publisher
+    }
+
+    fn fluss(dst_port: u16) -> Fluss {
+        let mut fluss = flow(
+            Ipv4Addr::new(192, 0, 2, 1),
+            Ipv4Addr::new(198, 51, 100, 1),
+            dst_port,
+            1500,
+            3,
+        );
+        fluss.time_received = Utc.timestamp_millis_opt(1_600_000_000_123).unwrap();
+        fluss
+    }
+
+    /// Names of the columns of [`TABLE_DDL`].
+    fn columns() -> Vec<&'static str> {
+        TABLE_DDL
+            .lines()
+            .skip(1)
+            .take_while(|line| !line.starts_with(')'))
+            .map(|line| line.split_whitespace().next().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn statements_use_the_table() {
+        let publisher = publisher("netflow.flows");
+
+        let ddl = publisher.ddl();
+        assert!(
+            ddl.starts_with("CREATE TABLE IF NOT EXISTS netflow.flows (\n    type TEXT NOT NULL,"),
+            "{}",
+            ddl
+        );
+        assert!(ddl.ends_with("suspect BOOLEAN NOT NULL\n)"), "{}", ddl);
+        assert_eq!(
+            publisher.insert_query(),
+            "INSERT INTO netflow.flows SELECT * FROM \
+             jsonb_populate_recordset(NULL::netflow.flows, $1)"
+        );
+    }
+
+    #[test]
+    fn rows_have_a_field_for_every_column() {
+        let row = serde_json::to_value(fluss(443)).unwrap();
+        let row = row.as_object().unwrap();
+
+        let columns = columns();
+        assert_eq!(columns.len(), 67);
+        for column in columns {
+            assert!(row.contains_key(column), "no field for column {}", column);
+        }
+    }
+
+    #[test]
+    fn rows_hold_values_postgres_can_convert() {
+        let row = serde_json::to_value(fluss(443)).unwrap();
+
+        assert_eq!(row["time_received"], "2020-09-13T12:26:40.123Z");
+        assert_eq!(row["src_addr"], "192.0.2.1");
+        assert_eq!(row["dst_port"], 443);
+        assert_eq!(row["bytes"], 1500);
+        assert_eq!(row["protocol"], "tcp");
+        assert_eq!(row["is_bidirectional"], false);
+        // absent values become NULL
+        assert!(row["icmp_type"].is_null());
+        assert!(row["tenant"].is_null());
+    }
+
+    /// Needs a PostgreSQL server, e.g.
+    /// `FLUSS_POSTGRES_URL=postgres://postgres@localhost/postgres cargo test -- --ignored`.
+    #[tokio::test]
+    #[ignore]
+    async fn flows_are_inserted() {
+        let url = std::env::var("FLUSS_POSTGRES_URL").expect("FLUSS_POSTGRES_URL is not set");
+        let table = format!("fluss_test_{}", std::process::id());
+        let mut publisher = PostgresPublisher::new(&url).unwrap();
+        publisher.set_table(&table);
+        publisher.set_batch_size(2);
+
+        // without TimescaleDB, the hypertable can not be created
+        sqlx::query(&publisher.ddl())
+            .execute(&publisher.pool)
+            .await
+            .unwrap();
+        for dst_port in [443, 80, 22] {
+            publisher.publish(&fluss(dst_port)).await.unwrap();
+        }
+        publisher.flush().await.unwrap();
+
+        let rows: Vec<(String, i32, i64)> = sqlx::query_as(&format!(
+            "SELECT host(src_addr), dst_port, bytes FROM {} ORDER BY dst_port",
+            table
+        ))
+        .fetch_all(&publisher.pool)
+        .await
+        .unwrap();
+        sqlx::query(&format!("DROP TABLE {}", table))
+            .execute(&publisher.pool)
+            .await
+            .unwrap();
+
+        let expected: Vec<_> = [22, 80, 443]
+            .iter()
+            .map(|&port| ("192.0.2.1".to_owned(), port, 1500))
+            .collect();
+        assert_eq!(rows, expected);
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::Context as _;
+use chrono::Utc;
+use fluss_core::transport::PacketSink;
+use parking_lot::Mutex;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Records the raw bytes of every received datagram, e.g. to replay the
+/// traffic of an exporter while debugging.
+///
+/// Every datagram is written as a record of big-endian fields:
+///
+/// | bytes | field                                          |
+/// |-------|------------------------------------------------|
+/// | 8     | time received, microseconds since the epoch    |
+/// | 4     | IPv4 address of the exporter                   |
+/// | 2     | port of the exporter                           |
+/// | 2     | length of the datagram                         |
+/// | n     | the datagram                                   |
+///
+/// IPv4 mapped addresses are written as IPv4, datagrams of IPv6 exporters
+/// can not be recorded and are skipped. Writes to a file are buffered, call
+/// [`PacketSink::flush`] before exiting.
+pub struct RawPublisher {
+    output: Mutex<Output>,
+    skipped: AtomicU64,
+}
+
+enum Output {
+    File(BufWriter<File>),
+    Memory(Vec<u8>),
+}
+
+impl RawPublisher {
+    /// Creates the file, an existing file is truncated.
+    pub fn new(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .with_context(|| format!("failed to create raw capture {}", path.display()))?;
+        Ok(Self::with_output(Output::File(BufWriter::new(file))))
+    }
+
+    /// Records the datagrams into a buffer, see [`RawPublisher::take_buffer`].
+    pub fn in_memory() -> Self {
+        Self::with_output(Output::Memory(Vec::new()))
+    }
+
+    fn with_output(output: Output) -> Self {
+        Self {
+            output: Mutex::new(output),
+            skipped: AtomicU64::new(0),
+        }
+    }
+
+    /// Removes and returns the records written so far, empty for a file.
+    pub fn take_buffer(&self) -> Vec<u8> {
+        match &mut *self.output.lock() {
+            Output::File(_) => Vec::new(),
+            Output::Memory(buffer) => std::mem::take(buffer),
+        }
+    }
+
+    /// Amount of datagrams of IPv6 exporters which were not recorded.
+    pub fn skipped(&self) -> u64 {
+        self.skipped.load(Ordering::Relaxed)
+    }
+}
+
+impl PacketSink for RawPublisher {
+    fn raw_packet(&self, addr: SocketAddr, data: &[u8]) -> anyhow::Result<()> {
+        let ip = match addr.ip() {
+            IpAddr::V4(ip) => ip,
+            IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+                Some(ip) => ip,
+                None => {
+                    if self.skipped.fetch_add(1, Ordering::Relaxed) == 0 {
+                        tracing::warn!(exporter = %addr, "raw captures only record datagrams of IPv4 exporters");
+                    }
+                    return Ok(());
+                }
+            },
+        };
+        let length = u16::try_from(data.len())
+            .map_err(|_| anyhow::anyhow!("datagram of {} bytes is too long", data.len()))?;
+
+        let mut record = Vec::with_capacity(16 + data.len());
+        record.extend_from_slice(&Utc::now().timestamp_micros().to_be_bytes());
+        record.extend_from_slice(&ip.octets());
+        record.extend_from_slice(&addr.port().to_be_bytes());
+        record.extend_from_slice(&length.to_be_bytes());
+        record.extend_from_slice(data);
+
+        match &mut *self.output.lock() {
+            Output::File(writer) => writer.write_all(&record)?,
+            Output::Memory(buffer) => buffer.extend_from_slice(&record),
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        if let Output::File(writer) = &mut *self.output.lock() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    /// A record read back from a capture.
+    #[derive(Debug, PartialEq)]
+    struct Record {
+        micros: i64,
+        addr: SocketAddr,
+        data: Vec<u8>,
+    }
+
+    fn read(mut input: &[u8]) -> Vec<Record> {
+        let mut records = Vec::new();
+        while !input.is_empty() {
+            let micros = i64::from_be_bytes(input[..8].try_into().unwrap());
+            let ip = Ipv4Addr::from(<[u8; 4]>::try_from(&input[8..12]).unwrap());
+            let port = u16::from_be_bytes(input[12..14].try_into().unwrap());
+            let length = u16::from_be_bytes(input[14..16].try_into().unwrap()) as usize;
+            records.push(Record {
+                micros,
+                addr: SocketAddr::new(ip.into(), port),
+                data: input[16..16 + length].to_vec(),
+            });
+            input = &input[16 + length..];
+        }
+        records
+    }
+
+    fn exporter(port: u16) -> SocketAddr {
+        SocketAddr::new(Ipv4Addr::new(192, 0, 2, 1).into(), port)
+    }
+
+    #[test]
+    fn datagrams_are_written_to_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capture.raw");
+        let raw = RawPublisher::new(&path).unwrap();
+
+        let before = Utc::now().timestamp_micros();
+        raw.raw_packet(exporter(4739), &[0, 10, 0, 16]).unwrap();
+        raw.raw_packet(exporter(2055), &[]).unwrap();
+        raw.raw_packet(exporter(4739), &[0xff; 300]).unwrap();
+        raw.flush().unwrap();
+        let after = Utc::now().timestamp_micros();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes.len(), 3 * 16 + 4 + 300);
+        // the header of the first record, field by field
+        assert_eq!(&bytes[8..16], &[192, 0, 2, 1, 0x12, 0x83, 0, 4]);
+        assert_eq!(&bytes[16..20], &[0, 10, 0, 16]);
+
+        let records = read(&bytes);
+        assert_eq!(records.len(), 3);
+        for record in &records {
+            assert!((before..=after).contains(&record.micros));
+        }
+        assert!(records.windows(2).all(|w| w[0].micros <= w[1].micros));
+        assert_eq!(records[0].addr, exporter(4739));
+        assert_eq!(records[0].data, [0, 10, 0, 16]);
+        assert_eq!(records[1].addr, exporter(2055));
+        assert!(records[1].data.is_empty());
+        assert_eq!(records[2].data, vec![0xff; 300]);
+    }
+
+    #[test]
+    fn existing_files_are_truncated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("capture.raw");
+        std::fs::write(&path, b"previous capture").unwrap();
+
+        let raw = RawPublisher::new(&path).unwrap();
+        raw.raw_packet(exporter(4739), &[1, 2, 3]).unwrap();
+        raw.flush().unwrap();
+
+        let records = read(&std::fs::read(&path).unwrap());
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].data, [1, 2, 3]);
+    }
+
+    #[test]
+    fn in_memory_records_use_the_file_layout() {
+        let raw = RawPublisher::in_memory();
+        raw.raw_packet(exporter(4739), &[1, 2, 3]).unwrap();
+        raw.raw_packet(exporter(4740), &[4]).unwrap();
+
+        let records = read(&raw.take_buffer());
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            (records[0].addr, &records[0].data[..]),
+            (exporter(4739), &[1, 2, 3][..])
+        );
+        assert_eq!(
+            (records[1].addr, &records[1].data[..]),
+            (exporter(4740), &[4][..])
+        );
+        assert!(raw.take_buffer().is_empty());
+    }
+
+    #[test]
+    fn ipv6_exporters_are_skipped() {
+        let raw = RawPublisher::in_memory();
+        let mapped = Ipv4Addr::new(192, 0, 2, 1).to_ipv6_mapped();
+        raw.raw_packet(SocketAddr::new(mapped.into(), 4739), &[1])
+            .unwrap();
+        raw.raw_packet(SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 4739), &[2])
+            .unwrap();
+        raw.raw_packet(SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 4739), &[3])
+            .unwrap();
+
+        let records = read(&raw.take_buffer());
+        assert_eq!(records.len(), 1);
+        // mapped addresses are recorded as IPv4
+        assert_eq!(records[0].addr, exporter(4739));
+        assert_eq!(records[0].data, [1]);
+        assert_eq!(raw.skipped(), 2);
+    }
+
+    #[test]
+    fn oversized_datagrams_are_rejected() {
+        let raw = RawPublisher::in_memory();
+        assert!(raw.raw_packet(exporter(4739), &vec![0; 65536]).is_err());
+        raw.raw_packet(exporter(4739), &vec![0; 65535]).unwrap();
+        assert_eq!(read(&raw.take_buffer())[0].data.len(), 65535);
+    }
+}
@@ -0,0 +1,267 @@
+use crate::Publisher;
+use ::redis::aio::ConnectionManager;
+use async_trait::async_trait;
+use fluss_core::fluss::Fluss;
+use parking_lot::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+const DEFAULT_BATCH_SIZE: usize = 1000;
+const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Appends flows as entries to a Redis stream with `XADD`.
+///
+/// Every field of the flow is stored as its string representation, absent optional
+/// fields are omitted. Entries are batched and sent as a single pipeline, the
+/// same way as in the [`ClickHousePublisher`](crate::ClickHousePublisher).
+///
+/// The stream is not trimmed unless a maximum length is set.
+pub struct RedisPublisher {
+    client: ::redis::Client,
+    // connected on first use, reconnects on its own afterwards
+    connection: OnceCell<ConnectionManager>,
+    stream: String,
+    max_len: Option<usize>,
+    batch_size: usize,
+    flush_interval: Duration,
+    batch: Mutex<Batch>,
+}
+
+type Entry = Vec<(String, String)>;
+
+struct Batch {
+    entries: Vec<Entry>,
+    started: Instant,
+}
+
+impl Batch {
+    fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl RedisPublisher {
+    pub fn new(url: &str, stream: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: ::redis::Client::open(url)?,
+            connection: OnceCell::new(),
+            stream: stream.to_owned(),
+            max_len: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            batch: Mutex::new(Batch::new()),
+        })
+    }
+
+    /// Maximum amount of flows sent in a single pipeline.
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = batch_size.max(1);
+    }
+
+    /// Maximum time a flow is held back before the batch is sent.
+    pub fn set_flush_interval(&mut self, flush_interval: Duration) {
+        self.flush_interval = flush_interval;
+    }
+
+    /// Trims the stream to about `max_len` entries with every `XADD`.
+    ///
+    /// The trimming is approximate (`MAXLEN ~`), Redis only removes whole
+    /// nodes of the stream which is a lot cheaper than exact trimming.
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.max_len = Some(max_len);
+    }
+
+    /// Sends all pending flows.
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        let batch = std::mem::replace(&mut *self.batch.lock(), Batch::new());
+        self.send(batch).await
+    }
+
+    async fn send(&self, batch: Batch) -> anyhow::Result<()> {
+        if batch.entries.is_empty() {
+            return Ok(());
+        }
+
+        tracing::debug!(
+            entries = batch.entries.len(),
+            stream = self.stream.as_str(),
+            "adding batch to stream"
+        );
+
+        let mut pipe = ::redis::pipe();
+        for entry in &batch.entries {
+            pipe.add_command(self.xadd(entry)).ignore();
+        }
+
+        pipe.query_async::<_, ()>(&mut self.connection().await?)
+            .await?;
+
+        Ok(())
+    }
+
+    /// `XADD <stream> [MAXLEN ~ <max len>] * <field> <value> ...`
+    fn xadd(&self, entry: &Entry) -> ::redis::Cmd {
+        let mut cmd = ::redis::cmd("XADD");
+        cmd.arg(&self.stream);
+        if let Some(max_len) = self.max_len {
+            cmd.arg("MAXLEN").arg("~").arg(max_len);
+        }
+        cmd.arg("*");
+        for (field, value) in entry {
+            cmd.arg(field).arg(value);
+        }
+        cmd
+    }
+
+    async fn connection(&self) -> anyhow::Result<ConnectionManager> {
+        let connection = self
+            .connection
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await?;
+        Ok(connection.clone())
+    }
+}
+
+/// Converts the flow into stream fields, labels are flattened into the entry.
+fn to_entry(fluss: &Fluss) -> anyhow::Result<Entry> {
+    let fields = match serde_json::to_value(fluss)? {
+        serde_json::Value::Object(fields) => fields,
+        _ => unreachable!("flows serialize into a map"),
+    };
+
+    Ok(fields
+        .into_iter()
+        .filter_map(|(field, value)| match value {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(value) => Some((field, value)),
+            value => Some((field, value.to_string())),
+        })
+        .collect())
+}
+
+#[async_trait]
+impl Publisher for RedisPublisher {
+    async fn publish(&self, fluss: &Fluss) -> anyhow::Result<()> {
+        let entry = to_entry(fluss)?;
+
+        let full = {
+            let mut batch = self.batch.lock();
+            batch.entries.push(entry);
+
+            if batch.entries.len() >= self.batch_size
+                || batch.started.elapsed() >= self.flush_interval
+            {
+                Some(std::mem::replace(&mut *batch, Batch::new()))
+            } else {
+                None
+            }
+        };
+
+        match full {
+            Some(batch) => self.send(batch).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn health_check(&self) -> anyhow::Result<()> {
+        ::redis::cmd("PING")
+            .query_async::<_, ()>(&mut self.connection().await?)
+            .await?;
+        Ok(())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        RedisPublisher::flush(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::redis::Arg;
+    use fluss_core::testing::flow;
+    use std::net::Ipv4Addr;
+
+    fn fluss() -> Fluss {
+        flow(
+            Ipv4Addr::new(192, 0, 2, 1),
+            Ipv4Addr::new(198, 51, 100, 1),
+            443,
+            1500,
+            3,
+        )
+    }
+
+    fn args(cmd: &::redis::Cmd) -> Vec<String> {
+        cmd.args_iter()
+            .map(|arg| match arg {
+                Arg::Simple(arg) => String::from_utf8(arg.to_vec()).unwrap(),
+                Arg::Cursor => panic!("unexpected cursor"),
+            })
+            .collect()
+    }
+
+    fn value<'a>(entry: &'a Entry, field: &str) -> Option<&'a str> {
+        entry
+            .iter()
+            .find(|(name, _)| name == field)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn flows_are_mapped_to_string_fields() {
+        let mut fluss = fluss();
+        fluss.is_bidirectional = true;
+        fluss.labels.insert("site".to_owned(), "fra1".to_owned());
+        let entry = to_entry(&fluss).unwrap();
+
+        // strings are not quoted, other values use their JSON representation
+        assert_eq!(value(&entry, "src_addr"), Some("192.0.2.1"));
+        assert_eq!(value(&entry, "protocol"), Some("tcp"));
+        assert_eq!(value(&entry, "dst_port"), Some("443"));
+        assert_eq!(value(&entry, "bytes"), Some("1500"));
+        assert_eq!(value(&entry, "is_bidirectional"), Some("true"));
+        assert_eq!(value(&entry, "site"), Some("fra1"));
+        assert_eq!(value(&entry, "labels"), None);
+        // absent optional fields are omitted
+        assert_eq!(value(&entry, "icmp_type"), None);
+        assert_eq!(value(&entry, "tenant"), None);
+    }
+
+    #[test]
+    fn xadd_lists_the_fields_after_the_id() {
+        let publisher = RedisPublisher::new("redis://127.0.0.1:6379", "flows").unwrap();
+        let entry = vec![
+            ("src_addr".to_owned(), "192.0.2.1".to_owned()),
+            ("dst_port".to_owned(), "443".to_owned()),
+        ];
+
+        assert_eq!(
+            args(&publisher.xadd(&entry)),
+            [
+                "XADD",
+                "flows",
+                "*",
+                "src_addr",
+                "192.0.2.1",
+                "dst_port",
+                "443"
+            ]
+        );
+    }
+
+    #[test]
+    fn xadd_trims_the_stream_to_the_max_len() {
+        let mut publisher = RedisPublisher::new("redis://127.0.0.1:6379", "flows").unwrap();
+        publisher.set_max_len(100_000);
+        let entry = vec![("dst_port".to_owned(), "443".to_owned())];
+
+        assert_eq!(
+            args(&publisher.xadd(&entry)),
+            ["XADD", "flows", "MAXLEN", "~", "100000", "*", "dst_port", "443"]
+        );
+    }
+}
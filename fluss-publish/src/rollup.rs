@@ -0,0 +1,542 @@
+use crate::summary::Totals;
+use crate::Publisher;
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use fluss_core::fluss::{CompactField, Fluss, HumanBytes};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::net::IpAddr;
+
+/// Default amount of distinct keys of a window, see [`RollupPublisher::set_max_keys`].
+pub const DEFAULT_MAX_KEYS: usize = 100_000;
+
+/// Returns the current time, the start and end of windows.
+pub type Clock = Box<dyn Fn() -> DateTime<Utc> + Send + Sync>;
+
+/// Sums up the bytes and packets of flows by a key of selected fields, e.g.
+/// per tenant and VLAN for billing.
+///
+/// The rollups of the current window are published to the inner publisher by
+/// [`RollupPublisher::flush_window`], which has to be called periodically and
+/// starts a new window. Flushing the publisher publishes the partial window.
+pub struct RollupPublisher {
+    fields: Vec<CompactField>,
+    max_keys: usize,
+    normalize_sampling: bool,
+    inner: Box<dyn Publisher<Rollup> + Send + Sync>,
+    clock: Clock,
+    window: Mutex<Window>,
+}
+
+struct Window {
+    start: DateTime<Utc>,
+    keys: HashMap<Vec<KeyValue>, Totals>,
+    // flows of keys beyond the limit
+    overflow: Totals,
+}
+
+impl Window {
+    fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            start,
+            keys: HashMap::new(),
+            overflow: Totals::default(),
+        }
+    }
+}
+
+impl RollupPublisher {
+    /// Keys rollups by `fields`, fields summed up or unique to every flow are rejected.
+    pub fn new(
+        fields: Vec<CompactField>,
+        inner: Box<dyn Publisher<Rollup> + Send + Sync>,
+    ) -> anyhow::Result<Self> {
+        for field in &fields {
+            if let CompactField::Time
+            | CompactField::Packets
+            | CompactField::Bytes
+            | CompactField::Duration = field
+            {
+                anyhow::bail!("{} can not be part of the rollup key", field.name());
+            }
+        }
+
+        Ok(Self {
+            fields,
+            max_keys: DEFAULT_MAX_KEYS,
+            normalize_sampling: false,
+            inner,
+            clock: Box::new(Utc::now),
+            window: Mutex::new(Window::new(Utc::now())),
+        })
+    }
+
+    /// Flows of further keys within a window are added to a single overflow rollup.
+    pub fn set_max_keys(&mut self, max_keys: usize) {
+        self.max_keys = max_keys;
+    }
+
+    /// Sums up the counters of sampled flows scaled up by the sampling interval.
+    pub fn set_normalize_sampling(&mut self, normalize_sampling: bool) {
+        self.normalize_sampling = normalize_sampling;
+    }
+
+    /// Replaces the system clock, e.g. to control windows in tests.
+    ///
+    /// The current window starts over at the time of the new clock.
+    pub fn set_clock(&mut self, clock: Clock) {
+        *self.window.get_mut() = Window::new(clock());
+        self.clock = clock;
+    }
+
+    /// Amount of distinct keys in the current window.
+    pub fn active_keys(&self) -> usize {
+        self.window.lock().keys.len()
+    }
+
+    /// Publishes the rollups of the current window and starts a new window.
+    ///
+    /// All rollups are published, the first error is returned.
+    pub async fn flush_window(&self) -> anyhow::Result<()> {
+        let end = (self.clock)();
+        let window = std::mem::replace(&mut *self.window.lock(), Window::new(end));
+
+        if window.overflow.flows > 0 {
+            tracing::warn!(
+                max_keys = self.max_keys,
+                flows = window.overflow.flows,
+                "rollup key limit reached, flows of further keys were added to the overflow rollup"
+            );
+        }
+
+        let start = window.start;
+        let overflow = Some((None, window.overflow)).filter(|(_, totals)| totals.flows > 0);
+        let rollups = window
+            .keys
+            .into_iter()
+            .map(|(key, totals)| (Some(key), totals))
+            .chain(overflow)
+            .map(|(key, totals)| Rollup {
+                overflow: key.is_none(),
+                key: key.map_or_else(BTreeMap::new, |key| {
+                    self.fields
+                        .iter()
+                        .map(|field| field.name())
+                        .zip(key)
+                        .collect()
+                }),
+                window_start: start,
+                window_end: end,
+                bytes: totals.bytes,
+                packets: totals.packets,
+                flows: totals.flows,
+            });
+
+        let mut result = Ok(());
+        for rollup in rollups {
+            if let Err(err) = self.inner.publish(&rollup).await {
+                result = result.and(Err(err));
+            }
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl Publisher for RollupPublisher {
+    async fn publish(&self, fluss: &Fluss) -> anyhow::Result<()> {
+        let key: Vec<_> = self
+            .fields
+            .iter()
+            .map(|&field| KeyValue::of(fluss, field))
+            .collect();
+
+        let (bytes, packets) = match self.normalize_sampling {
+            true => (fluss.normalized_bytes(), fluss.normalized_packets()),
+            false => (fluss.bytes, fluss.packets),
+        };
+        let totals = Totals {
+            bytes,
+            packets,
+            flows: 1,
+        };
+
+        let mut window = self.window.lock();
+        let window = &mut *window;
+        let full = window.keys.len() >= self.max_keys;
+        match window.keys.get_mut(&key) {
+            Some(sum) => sum.add(&totals),
+            None if full => window.overflow.add(&totals),
+            None => {
+                window.keys.insert(key, totals);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn health_check(&self) -> anyhow::Result<()> {
+        self.inner.health_check().await
+    }
+
+    /// Publishes the partial window.
+    async fn flush(&self) -> anyhow::Result<()> {
+        let result = self.flush_window().await;
+        result.and(self.inner.flush().await)
+    }
+}
+
+/// Value of a key field of a rollup.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(untagged)]
+pub enum KeyValue {
+    Number(u64),
+    Addr(IpAddr),
+    Text(String),
+    /// The flow has no value for the field.
+    None,
+}
+
+impl KeyValue {
+    fn of(fluss: &Fluss, field: CompactField) -> Self {
+        match field {
+            CompactField::Type => Self::Text(fluss.r#type.to_string()),
+            // ports are left out, they would make almost every flow a key of its own
+            CompactField::Addrs => Self::Text(format!("{} -> {}", fluss.src_addr, fluss.dst_addr)),
+            CompactField::Protocol => Self::Text(fluss.protocol.to_string()),
+            CompactField::Vlan => Self::Number(fluss.vlan_id.into()),
+            CompactField::Exporter => fluss.exporter.map_or(Self::None, Self::Addr),
+            CompactField::Service => fluss.service.clone().map_or(Self::None, Self::Text),
+            CompactField::Class => fluss
+                .flow_class
+                .map_or(Self::None, |class| Self::Text(class.to_string())),
+            CompactField::Tenant => fluss.tenant.clone().map_or(Self::None, Self::Text),
+            CompactField::Sampling => fluss
+                .sampling_interval
+                .map_or(Self::None, |interval| Self::Number(interval.into())),
+            CompactField::Direction => Self::Text(fluss.flow_direction.to_string()),
+            // rejected by `RollupPublisher::new`
+            CompactField::Time
+            | CompactField::Packets
+            | CompactField::Bytes
+            | CompactField::Duration => Self::None,
+        }
+    }
+}
+
+impl fmt::Display for KeyValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(number) => write!(f, "{}", number),
+            Self::Addr(addr) => write!(f, "{}", addr),
+            Self::Text(text) => write!(f, "{:?}", text),
+            Self::None => f.write_str("-"),
+        }
+    }
+}
+
+/// Bytes, packets and flows of a key within a window.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Rollup {
+    /// Values of the key fields by their name, empty for the overflow rollup.
+    pub key: BTreeMap<&'static str, KeyValue>,
+    /// Whether the rollup sums up all flows of keys beyond the key limit.
+    pub overflow: bool,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    pub bytes: u64,
+    pub packets: u64,
+    pub flows: u64,
+}
+
+// e.g. `2024-05-01T12:00:00Z/2024-05-01T12:01:00Z tenant="acme" vlan=120 14 pkts 9.1KB 3 flows`
+impl fmt::Display for Rollup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}",
+            self.window_start.to_rfc3339_opts(SecondsFormat::Secs, true),
+            self.window_end.to_rfc3339_opts(SecondsFormat::Secs, true)
+        )?;
+        if self.overflow {
+            f.write_str(" overflow")?;
+        }
+        for (name, value) in &self.key {
+            write!(f, " {}={}", name, value)?;
+        }
+        write!(
+            f,
+            " {} pkts {} {} flows",
+            self.packets,
+            HumanBytes(self.bytes),
+            self.flows
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use fluss_core::testing::flow;
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+
+    const A: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+    const B: Ipv4Addr = Ipv4Addr::new(198, 51, 100, 1);
+
+    #[derive(Default)]
+    struct Recorder {
+        rollups: Mutex<Vec<Rollup>>,
+        flushes: Mutex<usize>,
+    }
+
+    #[async_trait]
+    impl Publisher<Rollup> for Arc<Recorder> {
+        async fn publish(&self, rollup: &Rollup) -> anyhow::Result<()> {
+            self.rollups.lock().push(rollup.clone());
+            Ok(())
+        }
+
+        async fn flush(&self) -> anyhow::Result<()> {
+            *self.flushes.lock() += 1;
+            Ok(())
+        }
+    }
+
+    impl Recorder {
+        /// Takes the published rollups, ordered by their key.
+        fn take(&self) -> Vec<Rollup> {
+            let mut rollups = std::mem::take(&mut *self.rollups.lock());
+            rollups.sort_by_key(|rollup| (rollup.overflow, rollup.to_string()));
+            rollups
+        }
+    }
+
+    /// A clock which only moves when it is told to.
+    #[derive(Clone)]
+    struct MockClock(Arc<Mutex<DateTime<Utc>>>);
+
+    impl MockClock {
+        fn new() -> Self {
+            Self(Arc::new(Mutex::new(
+                Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+            )))
+        }
+
+        fn now(&self) -> DateTime<Utc> {
+            *self.0.lock()
+        }
+
+        fn advance(&self, secs: i64) {
+            *self.0.lock() += Duration::seconds(secs);
+        }
+    }
+
+    fn rollups(fields: Vec<CompactField>) -> (RollupPublisher, Arc<Recorder>, MockClock) {
+        let recorder = Arc::new(Recorder::default());
+        let clock = MockClock::new();
+        let mut publisher = RollupPublisher::new(fields, Box::new(recorder.clone())).unwrap();
+        let now = clock.clone();
+        publisher.set_clock(Box::new(move || now.now()));
+        (publisher, recorder, clock)
+    }
+
+    fn tenant_flow(tenant: &str, vlan_id: u16, bytes: u64, packets: u64) -> Fluss {
+        let mut fluss = flow(A, B, 443, bytes, packets);
+        fluss.tenant = Some(tenant.to_owned());
+        fluss.vlan_id = vlan_id;
+        fluss
+    }
+
+    async fn publish(publisher: &RollupPublisher, flows: &[Fluss]) {
+        for fluss in flows {
+            publisher.publish(fluss).await.unwrap();
+        }
+    }
+
+    fn totals(rollups: &[Rollup]) -> Vec<(String, u64, u64, u64)> {
+        rollups
+            .iter()
+            .map(|rollup| {
+                let key = rollup
+                    .key
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, value))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                (key, rollup.bytes, rollup.packets, rollup.flows)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn windows_of_the_clock() {
+        let (publisher, recorder, clock) = rollups(vec![CompactField::Tenant, CompactField::Vlan]);
+        let start = clock.now();
+
+        publish(
+            &publisher,
+            &[
+                tenant_flow("acme", 120, 1000, 10),
+                tenant_flow("acme", 120, 500, 5),
+                tenant_flow("acme", 130, 200, 2),
+                tenant_flow("initech", 120, 3000, 3),
+            ],
+        )
+        .await;
+        assert_eq!(publisher.active_keys(), 3);
+
+        clock.advance(60);
+        publisher.flush_window().await.unwrap();
+        let first = recorder.take();
+        assert_eq!(
+            totals(&first),
+            [
+                ("tenant=\"acme\" vlan=120".to_owned(), 1500, 15, 2),
+                ("tenant=\"acme\" vlan=130".to_owned(), 200, 2, 1),
+                ("tenant=\"initech\" vlan=120".to_owned(), 3000, 3, 1),
+            ]
+        );
+        for rollup in &first {
+            assert_eq!(rollup.window_start, start);
+            assert_eq!(rollup.window_end, start + Duration::seconds(60));
+            assert!(!rollup.overflow);
+        }
+        assert_eq!(
+            first[0].to_string(),
+            "2024-05-01T12:00:00Z/2024-05-01T12:01:00Z tenant=\"acme\" vlan=120 15 pkts 1.5KB 2 flows"
+        );
+        // the window is reset
+        assert_eq!(publisher.active_keys(), 0);
+
+        // the next window starts where the last one ended
+        publish(&publisher, &[tenant_flow("acme", 120, 700, 7)]).await;
+        clock.advance(60);
+        publisher.flush_window().await.unwrap();
+        let second = recorder.take();
+        assert_eq!(
+            totals(&second),
+            [("tenant=\"acme\" vlan=120".to_owned(), 700, 7, 1)]
+        );
+        assert_eq!(second[0].window_start, start + Duration::seconds(60));
+        assert_eq!(second[0].window_end, start + Duration::seconds(120));
+
+        // empty windows publish nothing
+        clock.advance(60);
+        publisher.flush_window().await.unwrap();
+        assert!(recorder.take().is_empty());
+    }
+
+    #[tokio::test]
+    async fn keys_beyond_the_limit_overflow() {
+        let (mut publisher, recorder, clock) = rollups(vec![CompactField::Tenant]);
+        publisher.set_max_keys(2);
+
+        publish(
+            &publisher,
+            &[
+                tenant_flow("acme", 0, 1000, 10),
+                tenant_flow("initech", 0, 2000, 20),
+                tenant_flow("globex", 0, 300, 3),
+                // known keys are still summed up with a full window
+                tenant_flow("acme", 0, 1000, 10),
+                tenant_flow("hooli", 0, 400, 4),
+            ],
+        )
+        .await;
+        assert_eq!(publisher.active_keys(), 2);
+
+        clock.advance(60);
+        publisher.flush_window().await.unwrap();
+        let rollups = recorder.take();
+        assert_eq!(
+            totals(&rollups),
+            [
+                ("tenant=\"acme\"".to_owned(), 2000, 20, 2),
+                ("tenant=\"initech\"".to_owned(), 2000, 20, 1),
+                (String::new(), 700, 7, 2),
+            ]
+        );
+        assert!(rollups[2].overflow);
+        assert_eq!(
+            rollups[2].to_string(),
+            "2024-05-01T12:00:00Z/2024-05-01T12:01:00Z overflow 7 pkts 700B 2 flows"
+        );
+
+        // the limit applies per window
+        publish(&publisher, &[tenant_flow("globex", 0, 300, 3)]).await;
+        assert_eq!(publisher.active_keys(), 1);
+    }
+
+    #[tokio::test]
+    async fn shutdown_flushes_the_partial_window() {
+        let (publisher, recorder, clock) = rollups(vec![CompactField::Tenant]);
+        let start = clock.now();
+
+        publish(&publisher, &[tenant_flow("acme", 0, 1000, 10)]).await;
+        clock.advance(25);
+        publisher.flush().await.unwrap();
+
+        let rollups = recorder.take();
+        assert_eq!(
+            totals(&rollups),
+            [("tenant=\"acme\"".to_owned(), 1000, 10, 1)]
+        );
+        assert_eq!(rollups[0].window_start, start);
+        assert_eq!(rollups[0].window_end, start + Duration::seconds(25));
+        assert_eq!(*recorder.flushes.lock(), 1);
+        assert_eq!(publisher.active_keys(), 0);
+    }
+
+    #[tokio::test]
+    async fn counters_saturate() {
+        let (publisher, recorder, _) = rollups(vec![CompactField::Tenant]);
+        publish(
+            &publisher,
+            &[
+                tenant_flow("acme", 0, u64::MAX - 10, 1),
+                tenant_flow("acme", 0, 1000, u64::MAX),
+            ],
+        )
+        .await;
+
+        publisher.flush_window().await.unwrap();
+        assert_eq!(
+            totals(&recorder.take()),
+            [("tenant=\"acme\"".to_owned(), u64::MAX, u64::MAX, 2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn sampled_counters_are_normalized() {
+        let (mut publisher, recorder, _) = rollups(vec![CompactField::Tenant]);
+        publisher.set_normalize_sampling(true);
+        let mut sampled = tenant_flow("acme", 0, 1000, 10);
+        sampled.sampling_interval = Some(100);
+        publish(&publisher, &[sampled, tenant_flow("acme", 0, 1000, 10)]).await;
+
+        publisher.flush_window().await.unwrap();
+        assert_eq!(
+            totals(&recorder.take()),
+            [("tenant=\"acme\"".to_owned(), 101_000, 1010, 2)]
+        );
+    }
+
+    #[test]
+    fn counters_can_not_be_keys() {
+        for field in [
+            CompactField::Time,
+            CompactField::Packets,
+            CompactField::Bytes,
+            CompactField::Duration,
+        ] {
+            let recorder = Arc::new(Recorder::default());
+            let result =
+                RollupPublisher::new(vec![CompactField::Tenant, field], Box::new(recorder));
+            assert!(result.is_err(), "{}", field.name());
+        }
+    }
+}
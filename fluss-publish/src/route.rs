@@ -0,0 +1,464 @@
+use crate::Publisher;
+use async_trait::async_trait;
+use fluss_core::fluss::Fluss;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Decides whether a flow takes a route.
+pub type Condition = dyn Fn(&Fluss) -> bool + Send + Sync;
+
+/// Field of a flow which is hashed to select a publisher.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HashField {
+    VlanId,
+    Exporter,
+    SrcAddr,
+    DstAddr,
+}
+
+impl HashField {
+    fn hash(self, fluss: &Fluss, hasher: &mut impl Hasher) {
+        match self {
+            Self::VlanId => fluss.vlan_id.hash(hasher),
+            Self::Exporter => fluss.exporter.hash(hasher),
+            Self::SrcAddr => fluss.src_addr.hash(hasher),
+            Self::DstAddr => fluss.dst_addr.hash(hasher),
+        }
+    }
+}
+
+impl std::str::FromStr for HashField {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vlan_id" => Ok(Self::VlanId),
+            "exporter" => Ok(Self::Exporter),
+            "src_addr" => Ok(Self::SrcAddr),
+            "dst_addr" => Ok(Self::DstAddr),
+            _ => anyhow::bail!("unknown hash field {}", s),
+        }
+    }
+}
+
+/// How a route selects one of its publishers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+    /// Always the same publisher.
+    Publisher(String),
+    /// The hash of a field modulo the amount of publishers, flows with the
+    /// same value always end up at the same publisher.
+    Hash(HashField, Vec<String>),
+    /// The publishers in turn.
+    RoundRobin(Vec<String>),
+}
+
+/// Counters of a route since the publisher was created.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteStats {
+    /// Index of the route, the default route is the last one.
+    pub route: usize,
+    /// Flows which took the route.
+    pub flows: u64,
+}
+
+/// Counters of an inner publisher since the publisher was created.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublisherStats {
+    pub name: String,
+    pub published: u64,
+    pub errors: u64,
+}
+
+/// Distributes flows across multiple named publishers, e.g. to split the
+/// storage of flows across Elasticsearch clusters.
+///
+/// Routes are tried in the order they were added, the first route whose
+/// condition matches selects the publisher of a flow. Flows which match no
+/// route take the default route.
+///
+/// Every inner publisher keeps its own batching, a flush or health check
+/// is forwarded to all of them.
+pub struct RoutingPublisher {
+    outputs: Vec<Output>,
+    routes: Vec<Route>,
+    default: Route,
+}
+
+struct Output {
+    name: String,
+    publisher: Arc<dyn Publisher + Send + Sync>,
+    published: AtomicU64,
+    errors: AtomicU64,
+}
+
+struct Route {
+    condition: Option<Box<Condition>>,
+    target: Target,
+    flows: AtomicU64,
+}
+
+enum Target {
+    Output(usize),
+    Hash(HashField, Vec<usize>),
+    RoundRobin(Vec<usize>, AtomicUsize),
+}
+
+impl RoutingPublisher {
+    /// Creates a router whose default route selects the publisher `default`,
+    /// which has to be one of `publishers`.
+    pub fn new(
+        publishers: Vec<(String, Arc<dyn Publisher + Send + Sync>)>,
+        default: Selector,
+    ) -> anyhow::Result<Self> {
+        let outputs = publishers
+            .into_iter()
+            .map(|(name, publisher)| Output {
+                name,
+                publisher,
+                published: AtomicU64::new(0),
+                errors: AtomicU64::new(0),
+            })
+            .collect::<Vec<_>>();
+
+        let default = Route {
+            condition: None,
+            target: Target::new(&outputs, default)?,
+            flows: AtomicU64::new(0),
+        };
+
+        Ok(Self {
+            outputs,
+            routes: Vec::new(),
+            default,
+        })
+    }
+
+    /// Appends a route taken by flows matching `condition`.
+    pub fn add_route<F>(&mut self, condition: F, selector: Selector) -> anyhow::Result<()>
+    where
+        F: Fn(&Fluss) -> bool + Send + Sync + 'static,
+    {
+        self.routes.push(Route {
+            condition: Some(Box::new(condition)),
+            target: Target::new(&self.outputs, selector)?,
+            flows: AtomicU64::new(0),
+        });
+        Ok(())
+    }
+
+    pub fn route_stats(&self) -> Vec<RouteStats> {
+        self.routes
+            .iter()
+            .chain(std::iter::once(&self.default))
+            .enumerate()
+            .map(|(route, r)| RouteStats {
+                route,
+                flows: r.flows.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    pub fn publisher_stats(&self) -> Vec<PublisherStats> {
+        self.outputs
+            .iter()
+            .map(|output| PublisherStats {
+                name: output.name.clone(),
+                published: output.published.load(Ordering::Relaxed),
+                errors: output.errors.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    /// Checks every inner publisher, the result of each publisher by its name.
+    pub async fn health(&self) -> Vec<(&str, anyhow::Result<()>)> {
+        let mut health = Vec::with_capacity(self.outputs.len());
+        for output in &self.outputs {
+            health.push((output.name.as_str(), output.publisher.health_check().await));
+        }
+        health
+    }
+
+    fn select(&self, fluss: &Fluss) -> &Output {
+        let route = self
+            .routes
+            .iter()
+            .find(|route| {
+                route
+                    .condition
+                    .as_ref()
+                    .is_some_and(|matches| matches(fluss))
+            })
+            .unwrap_or(&self.default);
+        route.flows.fetch_add(1, Ordering::Relaxed);
+
+        let index = match &route.target {
+            Target::Output(index) => *index,
+            Target::Hash(field, outputs) => {
+                let mut hasher = DefaultHasher::new();
+                field.hash(fluss, &mut hasher);
+                outputs[(hasher.finish() % outputs.len() as u64) as usize]
+            }
+            Target::RoundRobin(outputs, next) => {
+                outputs[next.fetch_add(1, Ordering::Relaxed) % outputs.len()]
+            }
+        };
+        &self.outputs[index]
+    }
+}
+
+impl Target {
+    fn new(outputs: &[Output], selector: Selector) -> anyhow::Result<Self> {
+        let index = |name: &str| {
+            outputs
+                .iter()
+                .position(|output| output.name == name)
+                .ok_or_else(|| anyhow::anyhow!("unknown publisher {}", name))
+        };
+        let indices = |names: Vec<String>| {
+            if names.is_empty() {
+                anyhow::bail!("a route needs at least one publisher");
+            }
+            names.iter().map(|name| index(name)).collect()
+        };
+
+        Ok(match selector {
+            Selector::Publisher(name) => Self::Output(index(&name)?),
+            Selector::Hash(field, names) => Self::Hash(field, indices(names)?),
+            Selector::RoundRobin(names) => Self::RoundRobin(indices(names)?, AtomicUsize::new(0)),
+        })
+    }
+}
+
+#[async_trait]
+impl Publisher for RoutingPublisher {
+    async fn publish(&self, fluss: &Fluss) -> anyhow::Result<()> {
+        let output = self.select(fluss);
+        let result = output.publisher.publish(fluss).await;
+        match &result {
+            Ok(()) => output.published.fetch_add(1, Ordering::Relaxed),
+            Err(_) => output.errors.fetch_add(1, Ordering::Relaxed),
+        };
+        result
+    }
+
+    async fn health_check(&self) -> anyhow::Result<()> {
+        let failed = self
+            .health()
+            .await
+            .into_iter()
+            .filter_map(|(name, result)| result.err().map(|err| format!("{}: {}", name, err)))
+            .collect::<Vec<_>>();
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            anyhow::bail!("unhealthy publishers: {}", failed.join(", "))
+        }
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        let mut result = Ok(());
+        // every publisher is flushed, even if a previous one failed
+        for output in &self.outputs {
+            if let Err(err) = output.publisher.flush().await {
+                tracing::warn!(publisher = output.name.as_str(), error = %err, "failed to flush");
+                result = Err(err);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fluss_core::testing::flow;
+    use parking_lot::Mutex;
+    use std::net::Ipv4Addr;
+
+    #[derive(Default)]
+    struct Recorder {
+        flows: Mutex<Vec<Fluss>>,
+        failing: bool,
+    }
+
+    impl Recorder {
+        fn failing() -> Self {
+            Self {
+                failing: true,
+                ..Self::default()
+            }
+        }
+
+        fn sources(&self) -> Vec<Ipv4Addr> {
+            self.flows
+                .lock()
+                .iter()
+                .map(|fluss| match fluss.src_addr {
+                    std::net::IpAddr::V4(addr) => addr,
+                    addr => panic!("unexpected source {}", addr),
+                })
+                .collect()
+        }
+    }
+
+    #[async_trait]
+    impl Publisher for Recorder {
+        async fn publish(&self, fluss: &Fluss) -> anyhow::Result<()> {
+            if self.failing {
+                anyhow::bail!("unavailable");
+            }
+            self.flows.lock().push(fluss.clone());
+            Ok(())
+        }
+
+        async fn health_check(&self) -> anyhow::Result<()> {
+            match self.failing {
+                true => anyhow::bail!("unavailable"),
+                false => Ok(()),
+            }
+        }
+
+        async fn flush(&self) -> anyhow::Result<()> {
+            self.health_check().await
+        }
+    }
+
+    fn source(host: u8) -> Fluss {
+        flow(
+            Ipv4Addr::new(192, 0, 2, host),
+            Ipv4Addr::new(198, 51, 100, 1),
+            443,
+            1500,
+            1,
+        )
+    }
+
+    fn router(recorders: &[&Arc<Recorder>], default: Selector) -> anyhow::Result<RoutingPublisher> {
+        let publishers = recorders
+            .iter()
+            .enumerate()
+            .map(|(i, recorder)| (format!("p{}", i), Arc::clone(recorder) as _))
+            .collect();
+        RoutingPublisher::new(publishers, default)
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn first_matching_route_wins() {
+        let (p0, p1, p2) = Default::default();
+        let mut router = router(&[&p0, &p1, &p2], Selector::Publisher("p2".to_owned())).unwrap();
+        router
+            .add_route(
+                |fluss| fluss.src_addr.to_string().ends_with(".1"),
+                Selector::Publisher("p0".to_owned()),
+            )
+            .unwrap();
+        router
+            .add_route(
+                |fluss| fluss.dst_port == 443,
+                Selector::Publisher("p1".to_owned()),
+            )
+            .unwrap();
+
+        for host in 1..=3 {
+            router.publish(&source(host)).await.unwrap();
+        }
+        assert_eq!(p0.sources(), [Ipv4Addr::new(192, 0, 2, 1)]);
+        assert_eq!(p1.sources().len(), 2);
+        assert!(p2.sources().is_empty());
+
+        let flows: Vec<_> = router
+            .route_stats()
+            .iter()
+            .map(|stats| stats.flows)
+            .collect();
+        assert_eq!(flows, [1, 2, 0]);
+    }
+
+    #[tokio::test]
+    async fn hashed_flows_stick_to_their_publisher() {
+        let (p0, p1) = Default::default();
+        let router = router(
+            &[&p0, &p1],
+            Selector::Hash(HashField::SrcAddr, names(&["p0", "p1"])),
+        )
+        .unwrap();
+
+        for _ in 0..3 {
+            for host in 1..=20 {
+                router.publish(&source(host)).await.unwrap();
+            }
+        }
+        let (first, second) = (p0.sources(), p1.sources());
+        assert_eq!(first.len() + second.len(), 60);
+        assert!(!first.is_empty() && !second.is_empty());
+        assert!(first.iter().all(|source| !second.contains(source)));
+    }
+
+    #[tokio::test]
+    async fn round_robin() {
+        let (p0, p1, p2) = Default::default();
+        let router = router(
+            &[&p0, &p1, &p2],
+            Selector::RoundRobin(names(&["p0", "p1", "p2", "p0"])),
+        )
+        .unwrap();
+
+        for host in 1..=8 {
+            router.publish(&source(host)).await.unwrap();
+        }
+        assert_eq!(p0.sources().len(), 4);
+        assert_eq!(p1.sources().len(), 2);
+        assert_eq!(p2.sources().len(), 2);
+        assert_eq!(p1.sources()[0], Ipv4Addr::new(192, 0, 2, 2));
+    }
+
+    #[test]
+    fn unknown_publishers_are_rejected() {
+        let p0 = Arc::default();
+        assert!(router(&[&p0], Selector::Publisher("p1".to_owned())).is_err());
+        assert!(router(&[&p0], Selector::RoundRobin(Vec::new())).is_err());
+        assert!(router(
+            &[&p0],
+            Selector::Hash(HashField::VlanId, names(&["p0", "p1"]))
+        )
+        .is_err());
+        assert!("protocol".parse::<HashField>().is_err());
+        assert_eq!(
+            "exporter".parse::<HashField>().unwrap(),
+            HashField::Exporter
+        );
+    }
+
+    #[tokio::test]
+    async fn failing_publishers() {
+        let healthy = Arc::default();
+        let failing = Arc::new(Recorder::failing());
+        let router = router(
+            &[&healthy, &failing],
+            Selector::RoundRobin(names(&["p0", "p1"])),
+        )
+        .unwrap();
+
+        router.publish(&source(1)).await.unwrap();
+        assert!(router.publish(&source(2)).await.is_err());
+        let stats = router.publisher_stats();
+        assert_eq!((stats[0].published, stats[0].errors), (1, 0));
+        assert_eq!((stats[1].published, stats[1].errors), (0, 1));
+
+        let health = router.health().await;
+        assert!(health[0].1.is_ok());
+        assert!(health[1].1.is_err());
+        let err = router.health_check().await.unwrap_err();
+        assert_eq!(err.to_string(), "unhealthy publishers: p1: unavailable");
+        assert!(router.flush().await.is_err());
+    }
+}
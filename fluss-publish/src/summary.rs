@@ -0,0 +1,364 @@
+use crate::Publisher;
+use async_trait::async_trait;
+use fluss_core::fluss::Fluss;
+use parking_lot::Mutex;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+
+/// Default amount of talkers shown in a summary.
+pub const DEFAULT_TOP: usize = 10;
+
+/// Aggregates flows by source and destination instead of printing every flow.
+///
+/// The aggregated talkers are retrieved with [`SummaryPublisher::take_summary`],
+/// which also starts a new window.
+pub struct SummaryPublisher {
+    top: usize,
+    by_port: bool,
+    normalize_sampling: bool,
+    talkers: Mutex<HashMap<TalkerKey, Totals>>,
+}
+
+impl SummaryPublisher {
+    pub fn new() -> Self {
+        Self {
+            top: DEFAULT_TOP,
+            by_port: false,
+            normalize_sampling: false,
+            talkers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Amount of talkers included in a summary.
+    pub fn set_top(&mut self, top: usize) {
+        self.top = top;
+    }
+
+    /// Additionally keys talkers by the destination port.
+    pub fn set_by_port(&mut self, by_port: bool) {
+        self.by_port = by_port;
+    }
+
+    /// Aggregates the counters of sampled flows scaled up by the sampling interval.
+    pub fn set_normalize_sampling(&mut self, normalize_sampling: bool) {
+        self.normalize_sampling = normalize_sampling;
+    }
+
+    /// Returns the top talkers of the current window and resets it.
+    pub fn take_summary(&self) -> Summary {
+        let talkers = std::mem::take(&mut *self.talkers.lock());
+
+        let total = talkers
+            .values()
+            .fold(Totals::default(), |mut total, totals| {
+                total.add(totals);
+                total
+            });
+        let talker_count = talkers.len();
+
+        let mut talkers: Vec<_> = talkers.into_iter().collect();
+        talkers.sort_unstable_by_key(|(_, totals)| Reverse(totals.bytes));
+        talkers.truncate(self.top);
+
+        Summary {
+            talkers,
+            talker_count,
+            total,
+        }
+    }
+}
+
+impl Default for SummaryPublisher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Publisher for SummaryPublisher {
+    async fn publish(&self, fluss: &Fluss) -> anyhow::Result<()> {
+        let key = TalkerKey {
+            src_addr: fluss.src_addr,
+            dst_addr: fluss.dst_addr,
+            dst_port: if self.by_port {
+                Some(fluss.dst_port)
+            } else {
+                None
+            },
+        };
+
+        let (bytes, packets) = match self.normalize_sampling {
+            true => (fluss.normalized_bytes(), fluss.normalized_packets()),
+            false => (fluss.bytes, fluss.packets),
+        };
+
+        self.talkers.lock().entry(key).or_default().add(&Totals {
+            bytes,
+            packets,
+            flows: 1,
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TalkerKey {
+    pub src_addr: IpAddr,
+    pub dst_addr: IpAddr,
+    pub dst_port: Option<u16>,
+}
+
+impl fmt::Display for TalkerKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} \u{2192} {}", self.src_addr, self.dst_addr)?;
+        if let Some(dst_port) = self.dst_port {
+            write!(f, ":{}", dst_port)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Totals {
+    pub bytes: u64,
+    pub packets: u64,
+    pub flows: u64,
+}
+
+impl Totals {
+    pub(crate) fn add(&mut self, other: &Totals) {
+        self.bytes = self.bytes.saturating_add(other.bytes);
+        self.packets = self.packets.saturating_add(other.packets);
+        self.flows = self.flows.saturating_add(other.flows);
+    }
+}
+
+/// Top talkers of a window, sorted by bytes descending.
+#[derive(Debug)]
+pub struct Summary {
+    pub talkers: Vec<(TalkerKey, Totals)>,
+    /// Amount of all talkers in the window, not only the top talkers.
+    pub talker_count: usize,
+    pub total: Totals,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys: Vec<_> = self
+            .talkers
+            .iter()
+            .map(|(key, _)| key.to_string())
+            .collect();
+        let total = format!("total ({} talkers)", self.talker_count);
+        let width = keys
+            .iter()
+            .chain(Some(&total))
+            .map(|key| key.chars().count())
+            .max()
+            .unwrap_or_default();
+
+        writeln!(
+            f,
+            "{:<width$} {:>15} {:>12} {:>8}",
+            "talker",
+            "bytes",
+            "packets",
+            "flows",
+            width = width
+        )?;
+
+        for (key, (_, totals)) in keys.iter().zip(&self.talkers) {
+            writeln!(
+                f,
+                "{:<width$} {:>15} {:>12} {:>8}",
+                key,
+                totals.bytes,
+                totals.packets,
+                totals.flows,
+                width = width
+            )?;
+        }
+
+        write!(
+            f,
+            "{:<width$} {:>15} {:>12} {:>8}",
+            total,
+            self.total.bytes,
+            self.total.packets,
+            self.total.flows,
+            width = width
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fluss_core::testing::flow;
+    use std::net::Ipv4Addr;
+
+    const A: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+    const B: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 2);
+    const C: Ipv4Addr = Ipv4Addr::new(198, 51, 100, 1);
+
+    async fn publish(publisher: &SummaryPublisher, flows: &[Fluss]) {
+        for fluss in flows {
+            publisher.publish(fluss).await.unwrap();
+        }
+    }
+
+    fn talkers(summary: &Summary) -> Vec<(String, u64, u64, u64)> {
+        summary
+            .talkers
+            .iter()
+            .map(|(key, totals)| (key.to_string(), totals.bytes, totals.packets, totals.flows))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn aggregates_by_talker() {
+        let publisher = SummaryPublisher::new();
+        publish(
+            &publisher,
+            &[
+                flow(A, C, 443, 1000, 10),
+                flow(A, C, 80, 500, 5),
+                flow(B, C, 443, 3000, 2),
+            ],
+        )
+        .await;
+
+        let summary = publisher.take_summary();
+        assert_eq!(
+            talkers(&summary),
+            [
+                ("192.0.2.2 \u{2192} 198.51.100.1".to_owned(), 3000, 2, 1),
+                ("192.0.2.1 \u{2192} 198.51.100.1".to_owned(), 1500, 15, 2),
+            ]
+        );
+        assert_eq!(summary.talker_count, 2);
+        assert_eq!(
+            summary.total,
+            Totals {
+                bytes: 4500,
+                packets: 17,
+                flows: 3
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn aggregates_by_port() {
+        let mut publisher = SummaryPublisher::new();
+        publisher.set_by_port(true);
+        publish(
+            &publisher,
+            &[
+                flow(A, C, 443, 1000, 10),
+                flow(A, C, 80, 500, 5),
+                flow(A, C, 443, 1000, 10),
+            ],
+        )
+        .await;
+
+        let summary = publisher.take_summary();
+        assert_eq!(
+            talkers(&summary),
+            [
+                (
+                    "192.0.2.1 \u{2192} 198.51.100.1:443".to_owned(),
+                    2000,
+                    20,
+                    2
+                ),
+                ("192.0.2.1 \u{2192} 198.51.100.1:80".to_owned(), 500, 5, 1),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn top_talkers_by_bytes() {
+        let mut publisher = SummaryPublisher::new();
+        publisher.set_top(2);
+        let flows: Vec<_> = (1..=5)
+            .map(|i| flow(Ipv4Addr::new(192, 0, 2, i), C, 443, u64::from(i) * 100, 1))
+            .collect();
+        publish(&publisher, &flows).await;
+
+        let summary = publisher.take_summary();
+        let bytes: Vec<_> = summary
+            .talkers
+            .iter()
+            .map(|(_, totals)| totals.bytes)
+            .collect();
+        assert_eq!(bytes, [500, 400]);
+        // the totals include the talkers beyond the top
+        assert_eq!(summary.talker_count, 5);
+        assert_eq!(summary.total.bytes, 1500);
+        assert_eq!(summary.total.flows, 5);
+    }
+
+    #[tokio::test]
+    async fn take_summary_resets_the_window() {
+        let publisher = SummaryPublisher::new();
+        publish(&publisher, &[flow(A, C, 443, 1000, 10)]).await;
+        assert_eq!(publisher.take_summary().talker_count, 1);
+
+        let summary = publisher.take_summary();
+        assert!(summary.talkers.is_empty());
+        assert_eq!(summary.total, Totals::default());
+    }
+
+    #[tokio::test]
+    async fn normalizes_sampled_flows() {
+        let mut publisher = SummaryPublisher::new();
+        publisher.set_normalize_sampling(true);
+        let mut sampled = flow(A, C, 443, 1000, 10);
+        sampled.sampling_interval = Some(100);
+        publish(&publisher, &[sampled]).await;
+
+        let summary = publisher.take_summary();
+        assert_eq!(summary.total.bytes, 100_000);
+        assert_eq!(summary.total.packets, 1000);
+    }
+
+    #[test]
+    fn columns_are_aligned() {
+        let summary = Summary {
+            talkers: vec![(
+                TalkerKey {
+                    src_addr: A.into(),
+                    dst_addr: C.into(),
+                    dst_port: None,
+                },
+                Totals {
+                    bytes: 1500,
+                    packets: 15,
+                    flows: 2,
+                },
+            )],
+            talker_count: 1,
+            total: Totals {
+                bytes: 1500,
+                packets: 15,
+                flows: 2,
+            },
+        };
+
+        let output = summary.to_string();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("192.0.2.1 \u{2192} 198.51.100.1"));
+        assert!(lines[2].starts_with("total (1 talkers)"));
+        // the counters end in the same column
+        let widths: Vec<_> = lines.iter().map(|line| line.chars().count()).collect();
+        assert!(
+            widths.iter().all(|&width| width == widths[0]),
+            "{:?}",
+            widths
+        );
+    }
+}
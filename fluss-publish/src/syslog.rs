@@ -0,0 +1,284 @@
+use crate::Publisher;
+use async_trait::async_trait;
+use fluss_core::fluss::Fluss;
+use serde::Deserialize;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::Mutex;
+
+/// Priority of facility local0 (16) and severity informational (6).
+const PRIORITY: u8 = 16 * 8 + 6;
+/// Structured data ID of the flow fields, 32473 is the enterprise number for documentation.
+const SD_ID: &str = "flow@32473";
+const NILVALUE: &str = "-";
+
+/// Transport of syslog messages.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyslogTransport {
+    /// One message per datagram, RFC 5426.
+    Udp,
+    /// Messages framed by octet counting, RFC 6587.
+    Tcp,
+}
+
+impl std::str::FromStr for SyslogTransport {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "udp" => Ok(Self::Udp),
+            "tcp" => Ok(Self::Tcp),
+            _ => anyhow::bail!("unknown syslog transport {}", s),
+        }
+    }
+}
+
+/// Sends flows as RFC 5424 syslog messages, e.g. to a SIEM.
+///
+/// Every flow becomes a message of facility local0 and severity informational
+/// with the MSGID `FLOW`. The serialized fields of the flow are the parameters
+/// of the `flow@32473` structured data element, fields without a value are
+/// left out. Messages have no free-form text.
+///
+/// The TCP connection is established on first use and again after it was lost.
+/// Messages of all fields can exceed the 2048 bytes every UDP receiver has to
+/// accept, receivers may truncate them.
+pub struct SyslogPublisher {
+    target: SocketAddr,
+    hostname: String,
+    connection: Connection,
+}
+
+enum Connection {
+    Udp(UdpSocket),
+    Tcp(Mutex<Option<TcpStream>>),
+}
+
+impl SyslogPublisher {
+    /// Sends the messages to `target`.
+    ///
+    /// Has to be called within a tokio runtime.
+    pub fn new(target: SocketAddr, transport: SyslogTransport) -> anyhow::Result<Self> {
+        let connection = match transport {
+            SyslogTransport::Udp => {
+                let local: SocketAddr = match target {
+                    SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+                    SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+                };
+                let socket = std::net::UdpSocket::bind(local)?;
+                socket.connect(target)?;
+                socket.set_nonblocking(true)?;
+                Connection::Udp(UdpSocket::from_std(socket)?)
+            }
+            SyslogTransport::Tcp => Connection::Tcp(Mutex::new(None)),
+        };
+
+        Ok(Self {
+            target,
+            hostname: NILVALUE.to_owned(),
+            connection,
+        })
+    }
+
+    /// Name of the sending host in the messages, left out by default.
+    pub fn set_hostname(&mut self, hostname: &str) {
+        self.hostname = match hostname.is_empty() {
+            true => NILVALUE.to_owned(),
+            false => hostname.to_owned(),
+        };
+    }
+
+    /// Formats `fluss` as an RFC 5424 message without framing.
+    pub fn format(&self, fluss: &Fluss) -> anyhow::Result<String> {
+        let mut message = format!(
+            "<{}>1 {} {} fluss {} FLOW [{}",
+            PRIORITY,
+            fluss.time_received.format("%Y-%m-%dT%H:%M:%S%.6fZ"),
+            self.hostname,
+            std::process::id(),
+            SD_ID
+        );
+
+        let fields = match serde_json::to_value(fluss)? {
+            serde_json::Value::Object(fields) => fields,
+            _ => unreachable!("flows are serialized as objects"),
+        };
+        for (name, value) in fields {
+            let value = match value {
+                serde_json::Value::Null => continue,
+                serde_json::Value::String(value) => value,
+                value => value.to_string(),
+            };
+            let _ = write!(message, " {}=\"", name);
+            // '"', '\' and ']' have to be escaped in parameter values
+            for c in value.chars() {
+                if matches!(c, '"' | '\\' | ']') {
+                    message.push('\\');
+                }
+                message.push(c);
+            }
+            message.push('"');
+        }
+        message.push(']');
+
+        Ok(message)
+    }
+
+    async fn send_tcp(
+        &self,
+        stream: &Mutex<Option<TcpStream>>,
+        frame: &[u8],
+    ) -> anyhow::Result<()> {
+        let mut stream = stream.lock().await;
+        if stream.is_none() {
+            tracing::info!(target = %self.target, "connecting to syslog");
+            *stream = Some(TcpStream::connect(self.target).await?);
+        }
+
+        let result = stream
+            .as_mut()
+            .expect("the stream was just connected")
+            .write_all(frame)
+            .await;
+        if result.is_err() {
+            // the next message reconnects
+            *stream = None;
+        }
+        Ok(result?)
+    }
+}
+
+#[async_trait]
+impl Publisher for SyslogPublisher {
+    async fn publish(&self, fluss: &Fluss) -> anyhow::Result<()> {
+        let message = self.format(fluss)?;
+
+        match &self.connection {
+            Connection::Udp(socket) => {
+                socket.send(message.as_bytes()).await?;
+            }
+            Connection::Tcp(stream) => {
+                let frame = format!("{} {}", message.len(), message);
+                self.send_tcp(stream, frame.as_bytes()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn health_check(&self) -> anyhow::Result<()> {
+        if let Connection::Tcp(stream) = &self.connection {
+            let mut stream = stream.lock().await;
+            if stream.is_none() {
+                *stream = Some(TcpStream::connect(self.target).await?);
+            }
+        }
+        Ok(())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        if let Connection::Tcp(stream) = &self.connection {
+            if let Some(stream) = &mut *stream.lock().await {
+                stream.flush().await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use fluss_core::testing::flow;
+    use std::net::Ipv4Addr;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    fn fluss(dst_port: u16) -> Fluss {
+        let mut fluss = flow(
+            Ipv4Addr::new(192, 0, 2, 1),
+            Ipv4Addr::new(198, 51, 100, 1),
+            dst_port,
+            1500,
+            3,
+        );
+        fluss.time_received = Utc.timestamp_millis_opt(1_600_000_000_123).unwrap();
+        fluss
+    }
+
+    #[tokio::test]
+    async fn messages_have_an_rfc5424_header_and_structured_data() {
+        let target = "127.0.0.1:514".parse().unwrap();
+        let mut publisher = SyslogPublisher::new(target, SyslogTransport::Udp).unwrap();
+        let mut fluss = fluss(443);
+        fluss.tls_sni = Some(r#"a"b]c\d"#.to_owned());
+
+        let message = publisher.format(&fluss).unwrap();
+        let header = format!(
+            "<134>1 2020-09-13T12:26:40.123000Z - fluss {} FLOW [flow@32473 ",
+            std::process::id()
+        );
+        assert!(message.starts_with(&header), "{}", message);
+        assert!(message.ends_with("\"]"), "{}", message);
+        assert!(message.contains(r#" src_addr="192.0.2.1""#), "{}", message);
+        assert!(message.contains(r#" dst_port="443""#), "{}", message);
+        assert!(message.contains(r#" bytes="1500""#), "{}", message);
+        assert!(message.contains(r#" tls_sni="a\"b\]c\\d""#), "{}", message);
+        // fields without a value are left out
+        assert!(!message.contains("icmp_type="), "{}", message);
+
+        publisher.set_hostname("collector-1");
+        let message = publisher.format(&fluss).unwrap();
+        assert!(message.contains("Z collector-1 fluss "), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn udp_datagrams_contain_a_single_message() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let target = receiver.local_addr().unwrap();
+        let publisher = SyslogPublisher::new(target, SyslogTransport::Udp).unwrap();
+
+        publisher.publish(&fluss(443)).await.unwrap();
+        let mut buf = vec![0; 65536];
+        let len = receiver.recv(&mut buf).await.unwrap();
+        assert_eq!(
+            std::str::from_utf8(&buf[..len]).unwrap(),
+            publisher.format(&fluss(443)).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn tcp_messages_are_framed_by_octet_counting() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = listener.local_addr().unwrap();
+        let publisher = SyslogPublisher::new(target, SyslogTransport::Tcp).unwrap();
+
+        let flows = [fluss(443), fluss(8080)];
+        for fluss in &flows {
+            publisher.publish(fluss).await.unwrap();
+        }
+        publisher.flush().await.unwrap();
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        for fluss in &flows {
+            // MSG-LEN SP SYSLOG-MSG, the length counts octets
+            let mut length = Vec::new();
+            loop {
+                match stream.read_u8().await.unwrap() {
+                    b' ' => break,
+                    digit => length.push(digit),
+                }
+            }
+            let length: usize = std::str::from_utf8(&length).unwrap().parse().unwrap();
+            let mut message = vec![0; length];
+            stream.read_exact(&mut message).await.unwrap();
+            assert_eq!(
+                String::from_utf8(message).unwrap(),
+                publisher.format(fluss).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Bindings for inspecting packets in the browser.
+//!
+//! ```text
+//! wasm-pack build fluss-wasm --target web
+//! ```
+//!
+//! ```ignore
+//! import init, { decode_packet, Session } from "./pkg/fluss_wasm.js";
+//!
+//! await init();
+//! console.log(decode_packet(templates));
+//!
+//! // templates are remembered by the session, like by a collector
+//! const session = new Session();
+//! session.decode(templates);
+//! console.log(session.decode(data));
+//! ```
+
+use fluss_core::ipfix::parser::{parse, Packet, Set};
+use fluss_core::ipfix::{FieldParser, Session as IpfixSession};
+use serde_json::json;
+use wasm_bindgen::prelude::*;
+
+/// Returns the structure of an IPFIX message: its header, the templates of
+/// template sets and the length of data sets.
+///
+/// Data sets can not be decoded without their templates, see [`Session`].
+#[wasm_bindgen]
+pub fn decode_packet(bytes: &[u8]) -> Result<JsValue, JsValue> {
+    let packet = parse(bytes).map_err(to_js_error)?;
+    to_js(&packet_json(&packet))
+}
+
+/// Decodes data sets of the templates announced by earlier messages.
+///
+/// The session is held by JavaScript, messages of a single exporter and
+/// observation domain should be decoded by the same session in order.
+#[wasm_bindgen]
+pub struct Session {
+    session: IpfixSession<FieldParser>,
+}
+
+#[wasm_bindgen]
+impl Session {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {
+            session: IpfixSession::new(FieldParser::builder().with_default_fields().build()),
+        }
+    }
+
+    /// Registers the templates of an IPFIX message and returns its data records
+    /// as objects of named fields, fields without a name are keyed by their id.
+    pub fn decode(&self, bytes: &[u8]) -> Result<JsValue, JsValue> {
+        let packet = parse(bytes).map_err(to_js_error)?;
+        let records = self
+            .session
+            .parse(&packet)
+            .map_err(to_js_error)?
+            .iter()
+            .map(|record_set| self.session.to_json(record_set))
+            .collect::<Vec<_>>();
+        to_js(&serde_json::Value::Array(records))
+    }
+
+    /// Number of known templates.
+    #[wasm_bindgen(js_name = templateCount)]
+    pub fn template_count(&self) -> usize {
+        self.session.templates().len()
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn packet_json(packet: &Packet) -> serde_json::Value {
+    let sets = packet
+        .sets
+        .iter()
+        .map(|set| match set {
+            Set::TemplateSet(templates) => json!({
+                "type": "template",
+                "templates": templates,
+            }),
+            Set::OptionsTemplateSet(templates) => json!({
+                "type": "options_template",
+                "templates": templates
+                    .iter()
+                    .map(|template| json!({
+                        "id": template.id,
+                        "scope_field_count": template.scope_field_count,
+                        "fields": template.fields,
+                    }))
+                    .collect::<Vec<_>>(),
+            }),
+            Set::DataSet(set) => json!({
+                "type": "data",
+                "template_id": set.id,
+                "length": set.data.len(),
+            }),
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "version": packet.version,
+        "export_time": packet.export_time,
+        "sequence_number": packet.sequence_number,
+        "observation_domain_id": packet.observation_domain_id,
+        "sets": sets,
+    })
+}
+
+fn to_js(value: &serde_json::Value) -> Result<JsValue, JsValue> {
+    js_sys::JSON::parse(&value.to_string())
+}
+
+fn to_js_error(err: impl std::fmt::Display) -> JsValue {
+    js_sys::Error::new(&err.to_string()).into()
+}
@@ -0,0 +1,90 @@
+//! Decoding of templates and data in a JavaScript runtime.
+//!
+//! ```text
+//! wasm-pack test --node fluss-wasm
+//! ```
+#![cfg(target_arch = "wasm32")]
+
+use fluss_core::ipfix::parser::TemplateRecord;
+use fluss_core::testing::{field, DataRecord, MessageBuilder};
+use fluss_wasm::{decode_packet, Session};
+use serde_json::json;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+fn template() -> TemplateRecord {
+    TemplateRecord {
+        id: 256,
+        fields: vec![field(8, 4), field(12, 4), field(7, 2), field(1, 8)],
+    }
+}
+
+fn record(bytes: u64) -> DataRecord {
+    DataRecord::new()
+        .addr([10, 0, 0, 1].into())
+        .addr([192, 0, 2, 1].into())
+        .u16(49152)
+        .u64(bytes)
+}
+
+fn to_json(value: JsValue) -> serde_json::Value {
+    let json = js_sys::JSON::stringify(&value).unwrap();
+    serde_json::from_str(&String::from(json)).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn packet_structure() {
+    let mut builder = MessageBuilder::new(7);
+    let templates = builder.templates(&[template()]);
+    let data = builder.data(256, &[record(1500), record(3000)]);
+
+    let packet = to_json(decode_packet(&templates).unwrap());
+    assert_eq!(packet["version"], 10);
+    assert_eq!(packet["observation_domain_id"], 7);
+    assert_eq!(packet["sets"][0]["type"], "template");
+    let template = &packet["sets"][0]["templates"][0];
+    assert_eq!(template["id"], 256);
+    assert_eq!(template["fields"].as_array().unwrap().len(), 4);
+
+    let packet = to_json(decode_packet(&data).unwrap());
+    assert_eq!(
+        packet["sets"],
+        json!([{ "type": "data", "template_id": 256, "length": 36 }])
+    );
+
+    assert!(decode_packet(&data[..10]).is_err());
+}
+
+#[wasm_bindgen_test]
+fn session_decodes_data_of_announced_templates() {
+    let mut builder = MessageBuilder::new(7);
+    let templates = builder.templates(&[template()]);
+    let data = builder.data(256, &[record(1500), record(3000)]);
+
+    let session = Session::new();
+    // data before its template is skipped
+    assert_eq!(to_json(session.decode(&data).unwrap()), json!([]));
+    assert_eq!(session.template_count(), 0);
+
+    assert_eq!(to_json(session.decode(&templates).unwrap()), json!([]));
+    assert_eq!(session.template_count(), 1);
+
+    let records = to_json(session.decode(&data).unwrap());
+    assert_eq!(
+        records,
+        json!([
+            {
+                "sourceIPv4Address": "10.0.0.1",
+                "destinationIPv4Address": "192.0.2.1",
+                "sourceTransportPort": 49152,
+                "octetDeltaCount": 1500,
+            },
+            {
+                "sourceIPv4Address": "10.0.0.1",
+                "destinationIPv4Address": "192.0.2.1",
+                "sourceTransportPort": 49152,
+                "octetDeltaCount": 3000,
+            },
+        ])
+    );
+}
@@ -0,0 +1,198 @@
+//! Access control of exporters by their source address.
+
+use crate::exporters::Cidr;
+use std::collections::{BTreeMap, HashSet};
+use std::net::IpAddr;
+
+/// Networks of exporters whose datagrams are accepted.
+///
+/// Datagrams of denied exporters are dropped before they are decoded, they
+/// neither produce flows nor teach the collector templates. Without allowed
+/// networks every exporter which is not denied is allowed, a denied network
+/// wins over an allowed network containing it.
+///
+/// IPv4 mapped IPv6 addresses and networks are matched as IPv4.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExporterAcl {
+    allow: PrefixSet,
+    deny: PrefixSet,
+}
+
+impl ExporterAcl {
+    /// Allows all exporters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn allow(&mut self, network: Cidr) {
+        self.allow.insert(network);
+    }
+
+    pub fn deny(&mut self, network: Cidr) {
+        self.deny.insert(network);
+    }
+
+    pub fn is_allowed(&self, addr: IpAddr) -> bool {
+        let addr = match addr {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(addr, IpAddr::V4),
+            addr => addr,
+        };
+        !self.deny.contains(addr) && (self.allow.is_empty() || self.allow.contains(addr))
+    }
+}
+
+/// Networks grouped by their prefix length.
+///
+/// A lookup takes a hash lookup per distinct prefix length, independent of
+/// the amount of networks.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct PrefixSet {
+    v4: BTreeMap<u8, HashSet<u32>>,
+    v6: BTreeMap<u8, HashSet<u128>>,
+}
+
+impl PrefixSet {
+    fn insert(&mut self, network: Cidr) {
+        let prefix_len = network.prefix_len();
+        match network.addr() {
+            IpAddr::V6(addr) if prefix_len >= 96 && addr.to_ipv4_mapped().is_some() => {
+                let addr = addr.to_ipv4_mapped().expect("the address is IPv4 mapped");
+                let masked = mask_v4(addr.into(), prefix_len - 96);
+                self.v4.entry(prefix_len - 96).or_default().insert(masked);
+            }
+            IpAddr::V4(addr) => {
+                let masked = mask_v4(addr.into(), prefix_len);
+                self.v4.entry(prefix_len).or_default().insert(masked);
+            }
+            IpAddr::V6(addr) => {
+                let masked = mask_v6(addr.into(), prefix_len);
+                self.v6.entry(prefix_len).or_default().insert(masked);
+            }
+        }
+    }
+
+    fn contains(&self, addr: IpAddr) -> bool {
+        match addr {
+            IpAddr::V4(addr) => self.v4.iter().any(|(&prefix_len, networks)| {
+                networks.contains(&mask_v4(addr.into(), prefix_len))
+            }),
+            IpAddr::V6(addr) => self.v6.iter().any(|(&prefix_len, networks)| {
+                networks.contains(&mask_v6(addr.into(), prefix_len))
+            }),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.v4.is_empty() && self.v6.is_empty()
+    }
+}
+
+fn mask_v4(addr: u32, prefix_len: u8) -> u32 {
+    addr & u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0)
+}
+
+fn mask_v6(addr: u128, prefix_len: u8) -> u128 {
+    addr & u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn networks(allow: &[&str], deny: &[&str]) -> ExporterAcl {
+        let mut acl = ExporterAcl::new();
+        for network in allow {
+            acl.allow(network.parse().unwrap());
+        }
+        for network in deny {
+            acl.deny(network.parse().unwrap());
+        }
+        acl
+    }
+
+    fn allowed(acl: &ExporterAcl, addr: &str) -> bool {
+        acl.is_allowed(addr.parse().unwrap())
+    }
+
+    #[test]
+    fn everything_is_allowed_by_default() {
+        let acl = ExporterAcl::new();
+        assert!(allowed(&acl, "192.0.2.1"));
+        assert!(allowed(&acl, "2001:db8::1"));
+    }
+
+    #[test]
+    fn v4_networks() {
+        let acl = networks(&["10.0.0.0/24", "192.0.2.7", "172.16.0.0/12"], &[]);
+        assert!(allowed(&acl, "10.0.0.0"));
+        assert!(allowed(&acl, "10.0.0.255"));
+        assert!(!allowed(&acl, "10.0.1.0"));
+        assert!(!allowed(&acl, "9.255.255.255"));
+        assert!(allowed(&acl, "192.0.2.7"));
+        assert!(!allowed(&acl, "192.0.2.8"));
+        assert!(allowed(&acl, "172.31.255.255"));
+        assert!(!allowed(&acl, "172.32.0.0"));
+        // only v4 networks are allowed
+        assert!(!allowed(&acl, "2001:db8::1"));
+    }
+
+    #[test]
+    fn v6_networks() {
+        let acl = networks(&["2001:db8::/32", "fe80::1/128"], &["2001:db8:ff::/48"]);
+        assert!(allowed(&acl, "2001:db8::1"));
+        assert!(allowed(&acl, "2001:db8:ffff::1"));
+        assert!(!allowed(&acl, "2001:db8:ff::1"));
+        assert!(!allowed(&acl, "2001:db9::1"));
+        assert!(allowed(&acl, "fe80::1"));
+        assert!(!allowed(&acl, "fe80::2"));
+        assert!(!allowed(&acl, "10.0.0.1"));
+    }
+
+    #[test]
+    fn every_address_of_the_default_routes() {
+        let acl = networks(&["0.0.0.0/0"], &["::/0"]);
+        assert!(allowed(&acl, "0.0.0.0"));
+        assert!(allowed(&acl, "255.255.255.255"));
+        assert!(!allowed(&acl, "::1"));
+    }
+
+    #[test]
+    fn v4_mapped_addresses_match_v4_networks() {
+        let acl = networks(&["10.0.0.0/8", "::ffff:192.0.2.0/120"], &[]);
+        assert!(allowed(&acl, "::ffff:10.1.2.3"));
+        assert!(allowed(&acl, "192.0.2.200"));
+        assert!(allowed(&acl, "::ffff:192.0.2.200"));
+        assert!(!allowed(&acl, "::ffff:192.0.3.1"));
+    }
+
+    #[test]
+    fn deny_wins_over_allow() {
+        let acl = networks(&["10.0.0.0/8"], &["10.1.0.0/16", "10.2.3.4"]);
+        assert!(allowed(&acl, "10.0.0.1"));
+        assert!(!allowed(&acl, "10.1.2.3"));
+        assert!(!allowed(&acl, "10.2.3.4"));
+        assert!(allowed(&acl, "10.2.3.5"));
+
+        // the same network allowed and denied
+        let acl = networks(&["10.0.0.0/8"], &["10.0.0.0/8"]);
+        assert!(!allowed(&acl, "10.0.0.1"));
+
+        // without allowed networks everything else is allowed
+        let acl = networks(&[], &["10.0.0.0/8"]);
+        assert!(!allowed(&acl, "10.0.0.1"));
+        assert!(allowed(&acl, "192.0.2.1"));
+    }
+
+    #[test]
+    fn many_networks() {
+        let mut acl = ExporterAcl::new();
+        for i in 0..10_000u32 {
+            let network = Cidr::new(IpAddr::from((10 << 24 | i << 8).to_be_bytes()), 24).unwrap();
+            acl.allow(network);
+        }
+        assert_eq!(acl.allow.v4.len(), 1);
+        assert!(allowed(&acl, "10.0.0.1"));
+        assert!(allowed(&acl, "10.39.15.1"));
+        assert!(!allowed(&acl, "10.39.16.1"));
+    }
+}
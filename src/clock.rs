@@ -0,0 +1,202 @@
+//! Correction of flow timestamps of exporters with drifting clocks.
+
+use crate::fluss::Fluss;
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Skews up to this threshold are not corrected by default.
+pub const DEFAULT_THRESHOLD: Duration = Duration::from_secs(5);
+
+/// Number of messages the skew is estimated from.
+const WINDOW: usize = 64;
+
+/// Estimates the clock skew of an exporter from the export times of its messages.
+///
+/// Every message yields the offset between the receive and the export time,
+/// which is the skew plus the network delay. Delays only ever increase the
+/// offset, the smallest offset of the last messages is the estimated skew.
+/// The export time has a resolution of a second, so is the estimate.
+#[derive(Debug)]
+pub struct ClockSkew {
+    threshold: Duration,
+    offsets: VecDeque<i64>,
+}
+
+impl ClockSkew {
+    /// Flows are corrected once the estimated skew exceeds `threshold`.
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            offsets: VecDeque::with_capacity(WINDOW),
+        }
+    }
+
+    /// Records a message exported at `export_time`, in seconds since the epoch,
+    /// and received at `received`.
+    pub fn record(&mut self, export_time: u32, received: DateTime<Utc>) {
+        if self.offsets.len() == WINDOW {
+            self.offsets.pop_front();
+        }
+        self.offsets
+            .push_back(received.timestamp_millis() - i64::from(export_time) * 1000);
+    }
+
+    /// Forgets all recorded messages, e.g. after the exporter restarted.
+    pub fn reset(&mut self) {
+        self.offsets.clear();
+    }
+
+    /// Milliseconds the exporter clock is behind the collector, negative if it is ahead.
+    pub fn estimate(&self) -> Option<i64> {
+        self.offsets.iter().copied().min()
+    }
+
+    /// Shifts the start and end of `fluss` by the estimated skew if it exceeds the threshold.
+    pub fn correct(&self, fluss: &mut Fluss) {
+        let skew = match self.estimate() {
+            Some(skew) if u128::from(skew.unsigned_abs()) > self.threshold.as_millis() => skew,
+            _ => return,
+        };
+        if fluss.flow_start.is_none() && fluss.flow_end.is_none() {
+            return;
+        }
+
+        let correction = chrono::Duration::milliseconds(skew);
+        fluss.flow_start = fluss.flow_start.map(|start| start + correction);
+        fluss.flow_end = fluss.flow_end.map(|end| end + correction);
+        fluss.clock_skew_ms = Some(skew);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::flow;
+    use chrono::TimeZone;
+    use std::net::Ipv4Addr;
+
+    const SKEW: i64 = 120;
+
+    fn time(secs: i64, millis: i64) -> DateTime<Utc> {
+        Utc.timestamp_millis_opt(secs * 1000 + millis).unwrap()
+    }
+
+    /// Network delays of up to 250ms, repeating.
+    fn jitter(i: i64) -> i64 {
+        (i * 37) % 250
+    }
+
+    /// A flow ending at `end` and lasting 10 seconds, by the exporter clock.
+    fn exported_flow(end: DateTime<Utc>) -> Fluss {
+        let mut fluss = flow(
+            Ipv4Addr::new(192, 0, 2, 1),
+            Ipv4Addr::new(192, 0, 2, 2),
+            443,
+            1,
+            1,
+        );
+        fluss.flow_start = Some(end - chrono::Duration::seconds(10));
+        fluss.flow_end = Some(end);
+        fluss
+    }
+
+    #[test]
+    fn exporter_ahead_is_corrected() {
+        let mut clock = ClockSkew::new(DEFAULT_THRESHOLD);
+        let start = 1_700_000_000;
+
+        // the exporter clock is 120s ahead, flows are exported when they end
+        for i in 0..100 {
+            let now = start + i;
+            let received = time(now, jitter(i));
+            let export_time = (now + SKEW) as u32;
+            clock.record(export_time, received);
+
+            let mut fluss = exported_flow(time(now + SKEW, 0));
+            clock.correct(&mut fluss);
+
+            let skew = fluss.clock_skew_ms.unwrap();
+            assert!(
+                (-SKEW * 1000..-SKEW * 1000 + 250).contains(&skew),
+                "{}",
+                skew
+            );
+            let delay = received - fluss.flow_end.unwrap();
+            assert!(
+                (0..250).contains(&delay.num_milliseconds()),
+                "{} is {}ms off",
+                i,
+                delay.num_milliseconds()
+            );
+            assert_eq!(
+                fluss.flow_end.unwrap() - fluss.flow_start.unwrap(),
+                chrono::Duration::seconds(10)
+            );
+        }
+
+        // the smallest delay of the window is the estimate
+        let estimate = clock.estimate().unwrap();
+        assert!(
+            (-SKEW * 1000..-SKEW * 1000 + 250).contains(&estimate),
+            "{}",
+            estimate
+        );
+    }
+
+    #[test]
+    fn exporter_behind_is_corrected() {
+        let mut clock = ClockSkew::new(DEFAULT_THRESHOLD);
+        clock.record(1_700_000_000 - SKEW as u32, time(1_700_000_000, 40));
+
+        let mut fluss = exported_flow(time(1_700_000_000 - SKEW, 0));
+        clock.correct(&mut fluss);
+        assert_eq!(fluss.clock_skew_ms, Some(SKEW * 1000 + 40));
+        assert_eq!(fluss.flow_end, Some(time(1_700_000_000, 40)));
+    }
+
+    #[test]
+    fn small_skews_are_not_corrected() {
+        let mut clock = ClockSkew::new(DEFAULT_THRESHOLD);
+        clock.record(1_700_000_003, time(1_700_000_000, 0));
+
+        let end = time(1_700_000_003, 0);
+        let mut fluss = exported_flow(end);
+        clock.correct(&mut fluss);
+        assert_eq!(fluss.flow_end, Some(end));
+        assert_eq!(fluss.clock_skew_ms, None);
+
+        // flows without timestamps are left alone
+        let mut clock = ClockSkew::new(DEFAULT_THRESHOLD);
+        clock.record(1_700_000_000 + SKEW as u32, time(1_700_000_000, 0));
+        let mut fluss = exported_flow(end);
+        fluss.flow_start = None;
+        fluss.flow_end = None;
+        clock.correct(&mut fluss);
+        assert_eq!(fluss.clock_skew_ms, None);
+    }
+
+    #[test]
+    fn reset_forgets_the_skew() {
+        let mut clock = ClockSkew::new(DEFAULT_THRESHOLD);
+        clock.record(1_700_000_000 + SKEW as u32, time(1_700_000_000, 0));
+        assert!(clock.estimate().is_some());
+
+        // the exporter restarted with its clock set
+        clock.reset();
+        assert_eq!(clock.estimate(), None);
+        clock.record(1_700_000_010, time(1_700_000_010, 20));
+        assert_eq!(clock.estimate(), Some(20));
+    }
+
+    #[test]
+    fn old_messages_leave_the_window() {
+        let mut clock = ClockSkew::new(DEFAULT_THRESHOLD);
+        // a single message with a short delay before the clock was set
+        clock.record(1_700_000_000 + SKEW as u32, time(1_700_000_000, 0));
+        for i in 1..=WINDOW as i64 {
+            clock.record((1_700_000_000 + i) as u32, time(1_700_000_000 + i, 100));
+        }
+        assert_eq!(clock.estimate(), Some(100));
+    }
+}
@@ -0,0 +1,685 @@
+use bytes::Bytes;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use fluss::fluss::Fluss;
+use fluss::ipfix::parser::{DataSet, FieldSpecifier, TemplateRecord};
+use fluss::ipfix::{FieldParser, OptionsContext, Parser, Session};
+use fluss::pool::BufferPool;
+use fluss::produce::IpfixParser;
+use fluss::protocol::{parse_ipv4, parse_ipv6, parse_mac6, parse_number};
+use fluss::publish::{NullPublisher, Publisher};
+use fluss::testing::{field, DataRecord, MessageBuilder};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::hint::black_box;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+
+const TEMPLATE_ID: u16 = 256;
+// same capacity as the channels of the collect decode workers
+const CHANNEL_CAPACITY: usize = 1024;
+/// Maximum time to wait for the decoder to catch up after a step.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+/// Minimum time every parser of `--parsers` is measured.
+const MEASURE_TIME: Duration = Duration::from_millis(500);
+/// Fixed seed of the records of `--parsers`, runs decode the same data.
+const SEED: u64 = 0x5eed_f1055;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("bench")
+        .about("measures decode throughput and latency with generated IPFIX traffic")
+        .arg(
+            Arg::with_name("bench-template")
+                .long("bench-template")
+                .possible_values(&["minimal", "fat"])
+                .default_value("minimal")
+                .help("template of the generated records, 6 or 30 fields"),
+        )
+        .arg(
+            Arg::with_name("bench-direct")
+                .long("bench-direct")
+                .takes_value(false)
+                .help("injects messages into the decode path instead of sending them over UDP"),
+        )
+        .arg(
+            Arg::with_name("start-rate")
+                .long("start-rate")
+                .default_value("10000")
+                .help("offered flows per second of the first step"),
+        )
+        .arg(
+            Arg::with_name("steps")
+                .long("steps")
+                .default_value("5")
+                .help("number of steps, the offered rate doubles every step"),
+        )
+        .arg(
+            Arg::with_name("step-duration")
+                .long("step-duration")
+                .default_value("5")
+                .help("seconds every step lasts"),
+        )
+        .arg(
+            Arg::with_name("records-per-message")
+                .long("records-per-message")
+                .default_value("10")
+                .help("data records per generated message"),
+        )
+        .arg(
+            Arg::with_name("parsers")
+                .long("parsers")
+                .takes_value(false)
+                .help("measures the field parsers and the decoding of a packet instead of generating traffic"),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .takes_value(false)
+                .help("prints the results as json"),
+        )
+}
+
+pub fn run(app: &ArgMatches) -> anyhow::Result<()> {
+    if app.is_present("parsers") {
+        return bench_parsers(app.is_present("json"));
+    }
+    tokio::runtime::Runtime::new()?.block_on(bench(app))
+}
+
+/// Results of a single step of the benchmark.
+#[derive(Debug, Serialize)]
+struct Step {
+    offered_flows_per_second: u64,
+    achieved_flows_per_second: u64,
+    p50_latency_us: u64,
+    p99_latency_us: u64,
+    /// Messages dropped because the decoder did not keep up.
+    channel_drops: u64,
+    /// Messages sent over UDP which were never received, always 0 with `--bench-direct`.
+    socket_drops: u64,
+    decode_errors: u64,
+}
+
+#[derive(Default)]
+struct Stats {
+    sent: AtomicU64,
+    received: AtomicU64,
+    channel_drops: AtomicU64,
+    decode_errors: AtomicU64,
+    // time to decode and publish each message
+    latencies: Mutex<Vec<Duration>>,
+}
+
+async fn bench(app: &ArgMatches<'_>) -> anyhow::Result<()> {
+    let start_rate: u64 = app.value_of("start-rate").unwrap().parse()?;
+    let steps: u32 = app.value_of("steps").unwrap().parse()?;
+    let step_duration = Duration::from_secs(app.value_of("step-duration").unwrap().parse()?);
+    let records: usize = app.value_of("records-per-message").unwrap().parse()?;
+    let template = match app.value_of("bench-template") {
+        Some("fat") => fat_template(),
+        _ => minimal_template(),
+    };
+
+    let stats = Arc::new(Stats::default());
+    let publisher = Arc::new(NullPublisher::new());
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    tokio::spawn(decode(rx, Arc::clone(&publisher), Arc::clone(&stats)));
+
+    let sink = match app.is_present("bench-direct") {
+        true => Sink::Direct(tx, Arc::clone(&stats)),
+        false => {
+            let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+            let collector = socket.local_addr()?;
+            tokio::spawn(receive(socket, tx, Arc::clone(&stats)));
+
+            let exporter = std::net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+            Sink::Udp(exporter, collector, Arc::clone(&stats))
+        }
+    };
+
+    let mut load = Load {
+        generator: Generator::new(template, records.max(1)),
+        sink,
+    };
+
+    let mut results = Vec::with_capacity(steps as usize);
+    for step in 0..steps {
+        let rate = start_rate.saturating_mul(1 << step.min(32));
+        let (returned, step) = run_step(load, rate, step_duration, &publisher, &stats).await?;
+        load = returned;
+
+        if !app.is_present("json") {
+            print_step(results.is_empty(), &step);
+        }
+        results.push(step);
+    }
+
+    if app.is_present("json") {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    }
+
+    Ok(())
+}
+
+async fn run_step(
+    mut load: Load,
+    rate: u64,
+    duration: Duration,
+    publisher: &NullPublisher,
+    stats: &Stats,
+) -> anyhow::Result<(Load, Step)> {
+    stats.latencies.lock().clear();
+    let published = publisher.published();
+    let sent = stats.sent.load(Ordering::Relaxed);
+    let received = stats.received.load(Ordering::Relaxed);
+    let channel_drops = stats.channel_drops.load(Ordering::Relaxed);
+    let decode_errors = stats.decode_errors.load(Ordering::Relaxed);
+
+    let start = Instant::now();
+    let (load, flows) = tokio::task::spawn_blocking(move || {
+        let flows = load.offer(rate, duration);
+        (load, flows)
+    })
+    .await?;
+    let offered = start.elapsed();
+
+    // waits until the decoder processed the backlog of the step
+    let deadline = Instant::now() + DRAIN_TIMEOUT;
+    let mut finished = Instant::now();
+    let mut last = publisher.published();
+    while Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let current = publisher.published();
+        if current == last {
+            break;
+        }
+        last = current;
+        finished = Instant::now();
+    }
+    let elapsed = finished - start;
+
+    let mut latencies = std::mem::take(&mut *stats.latencies.lock());
+    latencies.sort_unstable();
+
+    let sent = stats.sent.load(Ordering::Relaxed) - sent;
+    let received = stats.received.load(Ordering::Relaxed) - received;
+    let step = Step {
+        offered_flows_per_second: per_second(flows, offered),
+        achieved_flows_per_second: per_second(publisher.published() - published, elapsed),
+        p50_latency_us: percentile(&latencies, 0.5).as_micros() as u64,
+        p99_latency_us: percentile(&latencies, 0.99).as_micros() as u64,
+        channel_drops: stats.channel_drops.load(Ordering::Relaxed) - channel_drops,
+        socket_drops: sent.saturating_sub(received),
+        decode_errors: stats.decode_errors.load(Ordering::Relaxed) - decode_errors,
+    };
+
+    Ok((load, step))
+}
+
+fn print_step(header: bool, step: &Step) {
+    if header {
+        println!(
+            "{:>12} {:>12} {:>10} {:>10} {:>14} {:>13} {:>13}",
+            "offered/s",
+            "achieved/s",
+            "p50",
+            "p99",
+            "channel drops",
+            "socket drops",
+            "decode errors"
+        );
+    }
+
+    println!(
+        "{:>12} {:>12} {:>8}us {:>8}us {:>14} {:>13} {:>13}",
+        step.offered_flows_per_second,
+        step.achieved_flows_per_second,
+        step.p50_latency_us,
+        step.p99_latency_us,
+        step.channel_drops,
+        step.socket_drops,
+        step.decode_errors
+    );
+}
+
+fn per_second(count: u64, elapsed: Duration) -> u64 {
+    (count as f64 / elapsed.as_secs_f64().max(f64::EPSILON)) as u64
+}
+
+fn percentile(sorted: &[Duration], percentile: f64) -> Duration {
+    match sorted.len() {
+        0 => Duration::default(),
+        len => sorted[((len - 1) as f64 * percentile).round() as usize],
+    }
+}
+
+/// Time per run of a parser of `--parsers`.
+#[derive(Debug, Serialize)]
+struct Measurement {
+    name: &'static str,
+    ns_per_run: f64,
+    /// Decoded flows per second, only set for parsers producing flows.
+    flows_per_second: Option<u64>,
+}
+
+/// Measures the parsers of single fields, records and packets on fixed inputs.
+fn bench_parsers(json: bool) -> anyhow::Result<()> {
+    let mut rng = XorShift(SEED);
+    let record = |rng: &mut XorShift, template: &TemplateRecord| {
+        template
+            .fields
+            .iter()
+            .fold(DataRecord::new(), |record, field| {
+                random_value(rng, record, field)
+            })
+    };
+
+    let wide = wide_template();
+    let wide_record = record(&mut rng, &wide).into_bytes();
+    let fat = fat_template();
+    let fat_record = record(&mut rng, &fat).into_bytes();
+
+    // a template refresh and data sets of two templates, like routers send them
+    let minimal = TemplateRecord {
+        id: TEMPLATE_ID + 1,
+        ..minimal_template()
+    };
+    let mut builder = MessageBuilder::new(1);
+    let templates = builder.templates(&[fat.clone(), minimal.clone()]);
+    let fat_records = (0..10).map(|_| record(&mut rng, &fat)).collect::<Vec<_>>();
+    let minimal_records = (0..20)
+        .map(|_| record(&mut rng, &minimal))
+        .collect::<Vec<_>>();
+    let packet = concat_messages(&[
+        templates,
+        builder.data(fat.id, &fat_records),
+        builder.data(minimal.id, &minimal_records),
+    ]);
+    let packet = fluss::ipfix::parse_owned(Bytes::from(packet))?;
+    let flows_per_packet = (fat_records.len() + minimal_records.len()) as u64;
+
+    let field_parser = FieldParser::builder().with_default_fields().build();
+    let ipfix_parser = IpfixParser::new();
+    let options = OptionsContext::new();
+    let session =
+        Session::new(IpfixParser::new().with_options(options.clone())).with_options(options);
+    let decoded = session.parse(&packet)?.len() as u64;
+    anyhow::ensure!(
+        decoded == flows_per_packet,
+        "decoded {} of {} flows of the packet",
+        decoded,
+        flows_per_packet
+    );
+
+    let fields = |name, input: &'static [u8], parse: fn(&[u8]) -> fluss::protocol::Value| {
+        (name, measure(|| parse(black_box(input))), None)
+    };
+    let results = vec![
+        fields("parse_number/1", &[0x2a], parse_number),
+        fields("parse_number/2", &[0x01, 0xbb], parse_number),
+        fields("parse_number/4", &[0x00, 0x01, 0x86, 0xa0], parse_number),
+        fields(
+            "parse_number/8",
+            &[0, 0, 0, 0x02, 0x54, 0x0b, 0xe4, 0x00],
+            parse_number,
+        ),
+        fields("parse_ipv4", &[10, 0, 0, 1], parse_ipv4),
+        fields(
+            "parse_ipv6",
+            &[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+            parse_ipv6,
+        ),
+        fields(
+            "parse_mac6",
+            &[0x02, 0x42, 0xac, 0x11, 0x00, 0x02],
+            parse_mac6,
+        ),
+        (
+            "FieldParser::parse/40 fields",
+            measure(|| {
+                let set = DataSet {
+                    id: wide.id,
+                    data: &wide_record,
+                };
+                field_parser.parse(black_box(&wide.fields), black_box(&set))
+            }),
+            None,
+        ),
+        (
+            "IpfixParser::parse/30 fields",
+            measure(|| {
+                let set = DataSet {
+                    id: fat.id,
+                    data: &fat_record,
+                };
+                ipfix_parser.parse(black_box(&fat.fields), black_box(&set))
+            }),
+            Some(1),
+        ),
+        (
+            "Session::parse/3 sets",
+            // the same packet is decoded over and over, its sequence gaps are not logged
+            tracing::subscriber::with_default(tracing::subscriber::NoSubscriber::default(), || {
+                measure(|| {
+                    let flows = session.parse(black_box(&packet));
+                    session.events().for_each(drop);
+                    flows
+                })
+            }),
+            Some(flows_per_packet),
+        ),
+    ];
+
+    let results = results
+        .into_iter()
+        .map(|(name, ns_per_run, flows)| Measurement {
+            name,
+            ns_per_run,
+            flows_per_second: flows.map(|flows: u64| (flows as f64 * 1e9 / ns_per_run) as u64),
+        })
+        .collect::<Vec<_>>();
+
+    match json {
+        true => println!("{}", serde_json::to_string_pretty(&results)?),
+        false => {
+            println!("{:<30} {:>12} {:>14}", "parser", "time", "flows/s");
+            for result in &results {
+                let flows = result
+                    .flows_per_second
+                    .map_or_else(String::new, |flows| flows.to_string());
+                println!(
+                    "{:<30} {:>10.1}ns {:>14}",
+                    result.name, result.ns_per_run, flows
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs `f` until it took at least [`MEASURE_TIME`], returns the mean nanoseconds per run.
+fn measure<T>(mut f: impl FnMut() -> T) -> f64 {
+    // the amount of runs doubles until they take long enough, the shorter rounds warm up
+    let mut runs = 1_u64;
+    loop {
+        let start = Instant::now();
+        for _ in 0..runs {
+            black_box(f());
+        }
+        let elapsed = start.elapsed();
+        if elapsed >= MEASURE_TIME {
+            return elapsed.as_nanos() as f64 / runs as f64;
+        }
+        runs *= 2;
+    }
+}
+
+/// Joins the sets of messages into a single message with the header of the first message.
+fn concat_messages(messages: &[Vec<u8>]) -> Vec<u8> {
+    let mut packet = messages[0].clone();
+    for message in &messages[1..] {
+        packet.extend_from_slice(&message[16..]);
+    }
+    let length = packet.len() as u16;
+    packet[2..4].copy_from_slice(&length.to_be_bytes());
+    packet
+}
+
+/// Receives datagrams like the collector, messages are dropped if the decoder is busy.
+async fn receive(socket: UdpSocket, tx: mpsc::Sender<Bytes>, stats: Arc<Stats>) {
+    let mut pool = BufferPool::new(u16::MAX as usize);
+    loop {
+        let data = match pool.recv_from(&socket).await {
+            Ok((data, _)) => data,
+            Err(err) => {
+                tracing::error!(error = %err, "failed to receive datagram");
+                return;
+            }
+        };
+
+        stats.received.fetch_add(1, Ordering::Relaxed);
+        if tx.try_send(data).is_err() {
+            stats.channel_drops.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+async fn decode(mut rx: mpsc::Receiver<Bytes>, publisher: Arc<NullPublisher>, stats: Arc<Stats>) {
+    let options = OptionsContext::new();
+    let session =
+        Session::new(IpfixParser::new().with_options(options.clone())).with_options(options);
+
+    while let Some(data) = rx.recv().await {
+        let start = Instant::now();
+        match decode_message(&session, data) {
+            Ok(flows) => {
+                for flow in &flows {
+                    // the null publisher never fails
+                    let _ = publisher.publish(flow).await;
+                }
+            }
+            Err(err) => {
+                tracing::debug!(error = %err, "failed to decode message");
+                stats.decode_errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        stats.latencies.lock().push(start.elapsed());
+    }
+}
+
+fn decode_message(session: &Session<IpfixParser>, data: Bytes) -> anyhow::Result<Vec<Fluss>> {
+    let packet = fluss::ipfix::parse_owned(data)?;
+    Ok(session.parse(&packet)?)
+}
+
+/// Where generated messages are delivered to.
+enum Sink {
+    Udp(std::net::UdpSocket, SocketAddr, Arc<Stats>),
+    Direct(mpsc::Sender<Bytes>, Arc<Stats>),
+}
+
+impl Sink {
+    fn send(&self, message: Vec<u8>) {
+        match self {
+            Self::Udp(socket, collector, stats) => match socket.send_to(&message, collector) {
+                Ok(_) => {
+                    stats.sent.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(err) => tracing::debug!(error = %err, "failed to send message"),
+            },
+            Self::Direct(tx, stats) => {
+                stats.sent.fetch_add(1, Ordering::Relaxed);
+                stats.received.fetch_add(1, Ordering::Relaxed);
+                if tx.try_send(Bytes::from(message)).is_err() {
+                    stats.channel_drops.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+}
+
+struct Load {
+    generator: Generator,
+    sink: Sink,
+}
+
+impl Load {
+    /// Sends messages at `rate` flows per second, returns the number of sent flows.
+    fn offer(&mut self, rate: u64, duration: Duration) -> u64 {
+        // templates are refreshed every step, like exporters do periodically
+        self.sink.send(self.generator.templates());
+
+        let messages_per_second = rate as f64 / self.generator.records as f64;
+        let start = Instant::now();
+        let mut sent = 0;
+        while start.elapsed() < duration {
+            let due = (start.elapsed().as_secs_f64() * messages_per_second) as u64;
+            while sent < due {
+                self.sink.send(self.generator.data());
+                sent += 1;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        sent * self.generator.records as u64
+    }
+}
+
+/// Generates messages with random records of a template.
+struct Generator {
+    builder: MessageBuilder,
+    template: TemplateRecord,
+    records: usize,
+    rng: XorShift,
+}
+
+impl Generator {
+    fn new(template: TemplateRecord, records: usize) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+
+        Self {
+            builder: MessageBuilder::new(1),
+            template,
+            records,
+            rng: XorShift(seed | 1),
+        }
+    }
+
+    fn templates(&mut self) -> Vec<u8> {
+        self.set_export_time();
+        self.builder.templates(std::slice::from_ref(&self.template))
+    }
+
+    fn data(&mut self) -> Vec<u8> {
+        self.set_export_time();
+        let records = (0..self.records)
+            .map(|_| {
+                let rng = &mut self.rng;
+                self.template
+                    .fields
+                    .iter()
+                    .fold(DataRecord::new(), |record, field| {
+                        random_value(rng, record, field)
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        self.builder.data(self.template.id, &records)
+    }
+
+    fn set_export_time(&mut self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        self.builder.set_export_time(now.as_secs() as u32);
+    }
+}
+
+/// Appends a random but plausible value of the field.
+fn random_value(rng: &mut XorShift, record: DataRecord, field: &FieldSpecifier) -> DataRecord {
+    match field.id {
+        // protocol
+        4 => record.u8([1, 6, 17][rng.next() as usize % 3]),
+        // addresses in 10.0.0.0/8
+        8 | 12 | 15 | 225 | 226 => record.u32(0x0a00_0000 | (rng.next() as u32 & 0x00ff_ffff)),
+        _ => {
+            let bytes = (0..field.length)
+                .map(|_| rng.next() as u8)
+                .collect::<Vec<_>>();
+            record.bytes(&bytes)
+        }
+    }
+}
+
+/// A fast pseudo random number generator, good enough for synthetic traffic.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+}
+
+/// The 5-tuple and the byte counter.
+fn minimal_template() -> TemplateRecord {
+    TemplateRecord {
+        id: TEMPLATE_ID,
+        fields: vec![
+            field(8, 4),
+            field(12, 4),
+            field(7, 2),
+            field(11, 2),
+            field(4, 1),
+            field(1, 8),
+        ],
+    }
+}
+
+/// A template like the ones of routers exporting everything they know about a flow.
+fn fat_template() -> TemplateRecord {
+    TemplateRecord {
+        id: TEMPLATE_ID,
+        fields: vec![
+            field(1, 8),
+            field(2, 8),
+            field(4, 1),
+            field(5, 1),
+            field(6, 2),
+            field(7, 2),
+            field(8, 4),
+            field(9, 1),
+            field(10, 4),
+            field(11, 2),
+            field(12, 4),
+            field(13, 1),
+            field(14, 4),
+            field(15, 4),
+            field(16, 4),
+            field(17, 4),
+            field(21, 4),
+            field(22, 4),
+            field(23, 8),
+            field(24, 8),
+            field(56, 6),
+            field(58, 2),
+            field(59, 2),
+            field(61, 1),
+            field(81, 6),
+            field(136, 1),
+            field(225, 4),
+            field(226, 4),
+            field(227, 2),
+            field(228, 2),
+        ],
+    }
+}
+
+/// The fat template and ten more fields, IPv6 addresses, MACs and timestamps.
+fn wide_template() -> TemplateRecord {
+    let mut template = fat_template();
+    template.fields.extend([
+        field(27, 16),
+        field(28, 16),
+        field(29, 1),
+        field(30, 1),
+        field(32, 2),
+        field(80, 6),
+        field(150, 4),
+        field(151, 4),
+        field(152, 8),
+        field(153, 8),
+    ]);
+    template
+}
@@ -0,0 +1,2414 @@
+use anyhow::Context as _;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use fluss::clock::ClockSkew;
+use fluss::control::{ExporterTemplates, PipelineStats, Request, Response};
+use fluss::duplicates::DuplicateMessages;
+use fluss::enrich::{FlowClassifier, InterfaceNames};
+use fluss::exporters::ExporterSettings;
+use fluss::fluss::{CompactField, Fluss};
+use fluss::ipfix::{
+    parser::{DataSet, FieldSpecifier},
+    Compile, DebugParser, Decoded, OptionsContext, OptionsRecord, Parser, SanityChecks, Session,
+    SessionEvent,
+};
+use fluss::pool::BufferPool;
+use fluss::produce::IpfixParser;
+use fluss::proxy::IpfixProxy;
+use fluss::publish::elastic::IndexStrategy;
+use fluss::publish::rollup::Rollup;
+use fluss::publish::{
+    AmqpPublisher, ClickHousePublisher, DeduplicatingPublisher, ElasticPublisher, FlowMerger,
+    Publisher, RawPublisher, RedisPublisher, RollupPublisher, RoutingPublisher, SummaryPublisher,
+    SyslogPublisher,
+};
+use fluss::reload::{Reloaded, Reloader, Sources};
+use fluss::routes::{PublisherConfig, PublisherKind, Routes};
+use fluss::shutdown::ShutdownToken;
+use fluss::stats::{ExporterCounters, StatsRegistry, StatsReport};
+use fluss::transport::PacketSink;
+use parking_lot::{Mutex, RwLock};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
+use std::io::IsTerminal;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc;
+use tracing_futures::Instrument;
+
+/// Minimum time between two log messages about denied exporters.
+const DENIED_LOG_INTERVAL: Duration = Duration::from_secs(10);
+/// Default time after which a silent exporter is dropped by its decode worker.
+const DEFAULT_EXPORTER_IDLE_TIMEOUT: Duration = Duration::from_secs(3600);
+/// Time between two checks of a decode worker for idle exporters.
+const EXPORTER_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+enum Either<Left, Right> {
+    Left(Left),
+    Right(Right),
+}
+
+impl<L: Compile, R: Compile> Compile for Either<L, R> {
+    type Plan = Either<L::Plan, R::Plan>;
+
+    fn compile(&self, fields: &[FieldSpecifier]) -> Self::Plan {
+        match self {
+            Self::Left(left) => Either::Left(left.compile(fields)),
+            Self::Right(right) => Either::Right(right.compile(fields)),
+        }
+    }
+}
+
+impl<L, R> Parser for Either<L, R>
+where
+    L: Parser,
+    R: for<'a> Parser<Output<'a> = L::Output<'a>>,
+{
+    type Output<'a> = L::Output<'a>;
+
+    fn parse<'a>(&self, fields: &[FieldSpecifier], set: &DataSet<'a>) -> Option<Self::Output<'a>> {
+        match self {
+            Self::Left(left) => left.parse(fields, set),
+            Self::Right(right) => right.parse(fields, set),
+        }
+    }
+
+    fn parse_in_domain<'a>(
+        &self,
+        domain_id: u32,
+        fields: &[FieldSpecifier],
+        set: &DataSet<'a>,
+    ) -> Option<Self::Output<'a>> {
+        match self {
+            Self::Left(left) => left.parse_in_domain(domain_id, fields, set),
+            Self::Right(right) => right.parse_in_domain(domain_id, fields, set),
+        }
+    }
+
+    fn parse_planned<'a>(
+        &self,
+        plan: &Self::Plan,
+        domain_id: u32,
+        fields: &[FieldSpecifier],
+        set: &DataSet<'a>,
+    ) -> Option<Self::Output<'a>> {
+        match (self, plan) {
+            (Self::Left(left), Either::Left(plan)) => {
+                left.parse_planned(plan, domain_id, fields, set)
+            }
+            (Self::Right(right), Either::Right(plan)) => {
+                right.parse_planned(plan, domain_id, fields, set)
+            }
+            // plans are compiled by the same parser
+            _ => self.parse_in_domain(domain_id, fields, set),
+        }
+    }
+
+    fn check_sanity(
+        &self,
+        checks: &SanityChecks,
+        fields: &[FieldSpecifier],
+        output: &mut Self::Output<'_>,
+    ) -> bool {
+        match self {
+            Self::Left(left) => left.check_sanity(checks, fields, output),
+            Self::Right(right) => right.check_sanity(checks, fields, output),
+        }
+    }
+}
+
+type CollectParser = Either<DebugParser<IpfixParser>, IpfixParser>;
+
+/// Decoding state of a single exporter.
+struct Exporter {
+    session: Arc<Session<CollectParser>>,
+    stats: Arc<ExporterCounters>,
+    // `None` if clock skew correction is disabled
+    clock: Option<ClockSkew>,
+    // `None` if duplicate messages are not dropped
+    duplicates: Option<DuplicateMessages>,
+    // time of the last datagram, idle exporters are evicted by their worker
+    last_seen: Instant,
+}
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("collect")
+        .about("collects flows from the network and publishes them")
+        .arg(
+            Arg::with_name("debug")
+                .long("debug")
+                .short("d")
+                .takes_value(false)
+                .help("enables additional debug output, does not change verbosity"),
+        )
+        .arg(
+            Arg::with_name("pcap-capture")
+                .long("pcap-capture")
+                .takes_value(true)
+                .requires("debug")
+                .help("writes all received datagrams to this pcapng file"),
+        )
+        .arg(
+            Arg::with_name("raw-capture")
+                .long("raw-capture")
+                .takes_value(true)
+                .help("records the raw bytes of all received datagrams of IPv4 exporters to this file, for a later replay"),
+        )
+        .arg(
+            Arg::with_name("listen")
+                .long("listen")
+                .short("l")
+                .default_value("0.0.0.0:2055")
+                .help("listen/bind port for netflow traffic"),
+        )
+        .arg(
+            Arg::with_name("systemd-socket")
+                .long("systemd-socket")
+                .takes_value(false)
+                .help("uses the UDP socket passed by systemd socket activation, binds --listen if no socket was passed"),
+        )
+        .arg(
+            Arg::with_name("forward")
+                .long("forward")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("forwards all received datagrams unchanged to this collector, can be repeated"),
+        )
+        .arg(
+            Arg::with_name("publisher")
+                .long("publisher")
+                .short("p")
+                .possible_values(&["console", "elastic", "clickhouse", "redis", "amqp", "parquet", "syslog", "routes"])
+                .default_value("console")
+                .help("publisher for flow data"),
+        )
+        .arg(
+            Arg::with_name("routes")
+                .long("routes")
+                .takes_value(true)
+                .required_if("publisher", "routes")
+                .help("TOML file with the [[publisher]] and [[route]] sections of the routes publisher"),
+        )
+        .arg(
+            Arg::with_name("console-mode")
+                .long("console-mode")
+                .possible_values(&["flows", "summary"])
+                .default_value("flows")
+                .help("prints every flow or a periodic summary of the top talkers"),
+        )
+        .arg(
+            Arg::with_name("console-format")
+                .long("console-format")
+                .possible_values(&["compact", "line", "debug"])
+                .default_value("compact")
+                .help("format of the flows in the console, compact fields, all fields as key=value or the debug representation"),
+        )
+        .arg(
+            Arg::with_name("console-fields")
+                .long("console-fields")
+                .takes_value(true)
+                .help(
+                    "comma separated fields of the compact console format: time, type, addrs, proto, \
+                     packets, bytes, duration, vlan, exporter, service, class, tenant, sampling, direction",
+                ),
+        )
+        .arg(
+            Arg::with_name("summary-interval")
+                .long("summary-interval")
+                .default_value("5")
+                .help("seconds between two summaries"),
+        )
+        .arg(
+            Arg::with_name("summary-top")
+                .long("summary-top")
+                .takes_value(true)
+                .help("amount of talkers shown in the summary, defaults to 10"),
+        )
+        .arg(
+            Arg::with_name("summary-by-port")
+                .long("summary-by-port")
+                .takes_value(false)
+                .help("additionally groups talkers by destination port"),
+        )
+        .arg(
+            Arg::with_name("service-names")
+                .long("service-names")
+                .takes_value(false)
+                .help("shows the service names of ports in the console output"),
+        )
+        .arg(
+            Arg::with_name("normalize-sampling")
+                .long("normalize-sampling")
+                .takes_value(false)
+                .help("scales counters of sampled flows up in the console output"),
+        )
+        .arg(
+            Arg::with_name("rollup")
+                .long("rollup")
+                .takes_value(true)
+                .help(
+                    "publishes the bytes and packets of flows summed up by these comma separated \
+                     console fields instead of the flows, e.g. tenant,vlan,direction",
+                ),
+        )
+        .arg(
+            Arg::with_name("rollup-interval")
+                .long("rollup-interval")
+                .default_value("60")
+                .help("seconds between two rollups"),
+        )
+        .arg(
+            Arg::with_name("rollup-max-keys")
+                .long("rollup-max-keys")
+                .takes_value(true)
+                .help("distinct keys of a rollup window, flows of further keys are summed up in an overflow rollup, defaults to 100000"),
+        )
+        .arg(
+            Arg::with_name("dedup-window")
+                .long("dedup-window")
+                .takes_value(true)
+                .help("drops flows with the same 5-tuple seen within this many seconds"),
+        )
+        .arg(
+            Arg::with_name("merge-window")
+                .long("merge-window")
+                .takes_value(true)
+                .help("merges both directions of a connection seen within this many seconds"),
+        )
+        .arg(
+            Arg::with_name("exporters")
+                .long("exporters")
+                .takes_value(true)
+                .help("TOML file with per exporter settings, reloaded on SIGHUP"),
+        )
+        .arg(
+            Arg::with_name("allow-exporter")
+                .long("allow-exporter")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("only accepts datagrams of exporters in this network, can be repeated"),
+        )
+        .arg(
+            Arg::with_name("deny-exporter")
+                .long("deny-exporter")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("drops datagrams of exporters in this network, wins over --allow-exporter"),
+        )
+        .arg(
+            Arg::with_name("log-denied")
+                .long("log-denied")
+                .takes_value(false)
+                .help("logs dropped datagrams of denied exporters, at most every 10 seconds"),
+        )
+        .arg(
+            Arg::with_name("retry-budget")
+                .long("retry-budget")
+                .takes_value(true)
+                .help("seconds spent retrying a failed elastic request, defaults to 60"),
+        )
+        .arg(
+            Arg::with_name("dead-letter")
+                .long("dead-letter")
+                .takes_value(true)
+                .help("file for flows which could not be published to elastic"),
+        )
+        .arg(
+            Arg::with_name("elastic-index")
+                .long("elastic-index")
+                .takes_value(true)
+                .help("strftime pattern of the elastic index names, may contain {exporter} and {tenant}, defaults to fluss-%d.%m.%Y"),
+        )
+        .arg(
+            Arg::with_name("elastic-data-stream")
+                .long("elastic-data-stream")
+                .takes_value(true)
+                .conflicts_with("elastic-index")
+                .help("writes flows to an elastic data stream instead of pattern named indices"),
+        )
+        .arg(
+            Arg::with_name("elastic-setup")
+                .long("elastic-setup")
+                .takes_value(false)
+                .help("creates the ILM policy and index template at startup"),
+        )
+        .arg(
+            Arg::with_name("elastic-retention")
+                .long("elastic-retention")
+                .default_value("30")
+                .help("days after which the ILM policy created by --elastic-setup deletes indices"),
+        )
+        .arg(
+            Arg::with_name("clickhouse-url")
+                .long("clickhouse-url")
+                .default_value("http://localhost:8123")
+                .help("url of the clickhouse HTTP interface"),
+        )
+        .arg(
+            Arg::with_name("clickhouse-table")
+                .long("clickhouse-table")
+                .default_value("flows")
+                .help("clickhouse table flows are inserted into"),
+        )
+        .arg(
+            Arg::with_name("clickhouse-create-table")
+                .long("clickhouse-create-table")
+                .takes_value(false)
+                .help("creates the clickhouse table if it does not exist"),
+        )
+        .arg(
+            Arg::with_name("redis-url")
+                .long("redis-url")
+                .default_value("redis://127.0.0.1:6379")
+                .help("url of the redis server"),
+        )
+        .arg(
+            Arg::with_name("redis-stream")
+                .long("redis-stream")
+                .default_value("flows")
+                .help("redis stream flows are added to"),
+        )
+        .arg(
+            Arg::with_name("redis-max-len")
+                .long("redis-max-len")
+                .takes_value(true)
+                .help("trims the redis stream to about this many entries"),
+        )
+        .arg(
+            Arg::with_name("syslog-target")
+                .long("syslog-target")
+                .takes_value(true)
+                .required_if("publisher", "syslog")
+                .help("address of the syslog receiver, e.g. 192.168.1.100:514"),
+        )
+        .arg(
+            Arg::with_name("syslog-transport")
+                .long("syslog-transport")
+                .possible_values(&["udp", "tcp"])
+                .default_value("udp")
+                .help("transport of syslog messages"),
+        )
+        .arg(
+            Arg::with_name("syslog-hostname")
+                .long("syslog-hostname")
+                .takes_value(true)
+                .help("hostname in syslog messages, left out by default"),
+        )
+        .arg(
+            Arg::with_name("amqp-url")
+                .long("amqp-url")
+                .default_value("amqp://127.0.0.1:5672/%2f")
+                .help("url of the amqp broker"),
+        )
+        .arg(
+            Arg::with_name("amqp-exchange")
+                .long("amqp-exchange")
+                .default_value("flows")
+                .help("amqp exchange flows are published to, has to exist"),
+        )
+        .arg(
+            Arg::with_name("amqp-routing-key")
+                .long("amqp-routing-key")
+                .default_value("flows.{exporter}.{protocol}")
+                .help("routing key of published flows, supports {exporter}, {protocol} and {direction}"),
+        )
+        .arg(
+            Arg::with_name("amqp-max-in-flight")
+                .long("amqp-max-in-flight")
+                .takes_value(true)
+                .help("maximum amount of flows waiting for their confirmation, defaults to 1000"),
+        )
+        .arg(
+            Arg::with_name("parquet-dir")
+                .long("parquet-dir")
+                .default_value("flows")
+                .help("directory parquet files are written to"),
+        )
+        .arg(
+            Arg::with_name("parquet-rotate")
+                .long("parquet-rotate")
+                .default_value("1h")
+                .help("time after which a new parquet file is started, e.g. 15m"),
+        )
+        .arg(
+            Arg::with_name("parquet-max-file-size")
+                .long("parquet-max-file-size")
+                .takes_value(true)
+                .help("megabytes after which a new parquet file is started, defaults to 1024"),
+        )
+        .arg(
+            Arg::with_name("parquet-compression")
+                .long("parquet-compression")
+                .default_value("zstd(3)")
+                .help("compression of parquet files: uncompressed, snappy, lz4_raw or zstd(level)"),
+        )
+        .arg(
+            Arg::with_name("batch-size")
+                .long("batch-size")
+                .takes_value(true)
+                .help(
+                    "maximum amount of flows sent to elastic, clickhouse or redis at once, defaults to 1000, \
+                     10000 for parquet",
+                ),
+        )
+        .arg(
+            Arg::with_name("flush-interval")
+                .long("flush-interval")
+                .default_value("5")
+                .help("seconds after which a partial batch is sent"),
+        )
+        .arg(
+            Arg::with_name("max-clock-skew")
+                .long("max-clock-skew")
+                .takes_value(true)
+                .help("maximum allowed deviation of the export time in seconds"),
+        )
+        .arg(
+            Arg::with_name("correct-clock-skew")
+                .long("correct-clock-skew")
+                .takes_value(false)
+                .help("shifts flow_start and flow_end by the estimated clock skew of the exporter"),
+        )
+        .arg(
+            Arg::with_name("clock-skew-threshold")
+                .long("clock-skew-threshold")
+                .takes_value(true)
+                .help("seconds of clock skew from which on timestamps are corrected, defaults to 5"),
+        )
+        .arg(
+            Arg::with_name("dump-templates")
+                .long("dump-templates")
+                .takes_value(true)
+                .help("writes the templates of all exporters as JSON to this file on SIGUSR1"),
+        )
+        .arg(
+            Arg::with_name("message-dedup-window")
+                .long("message-dedup-window")
+                .takes_value(true)
+                .help("drops messages equal to one of the last this many messages of the observation domain"),
+        )
+        .arg(
+            Arg::with_name("message-dedup-age")
+                .long("message-dedup-age")
+                .takes_value(true)
+                .help("seconds messages are remembered for --message-dedup-window, defaults to 300"),
+        )
+        .arg(
+            Arg::with_name("exporter-idle-timeout")
+                .long("exporter-idle-timeout")
+                .takes_value(true)
+                .help("seconds after which the templates and state of a silent exporter are dropped, 0 keeps them forever, defaults to 3600"),
+        )
+        .arg(
+            Arg::with_name("max-template-fields")
+                .long("max-template-fields")
+                .takes_value(true)
+                .help("rejects templates with more fields, defaults to 128"),
+        )
+        .arg(
+            Arg::with_name("suspect-max-bytes")
+                .long("suspect-max-bytes")
+                .takes_value(true)
+                .help("flags flows with more bytes as suspect, e.g. 1125899906842624 (2^50)"),
+        )
+        .arg(
+            Arg::with_name("suspect-port-lengths")
+                .long("suspect-port-lengths")
+                .takes_value(false)
+                .help("flags flows of templates with ports which are not two bytes long as suspect"),
+        )
+        .arg(
+            Arg::with_name("suspect-time-reversal")
+                .long("suspect-time-reversal")
+                .takes_value(true)
+                .help("flags flows ending more than the seconds before they started as suspect"),
+        )
+        .arg(
+            Arg::with_name("suspect-threshold")
+                .long("suspect-threshold")
+                .takes_value(true)
+                .help("suspect records after which a template with a changed layout is reported, defaults to 10"),
+        )
+        .arg(
+            Arg::with_name("suspect-window")
+                .long("suspect-window")
+                .takes_value(true)
+                .help("seconds after a layout change in which templates are reported, defaults to 300"),
+        )
+        .arg(
+            Arg::with_name("quarantine-suspect-templates")
+                .long("quarantine-suspect-templates")
+                .takes_value(false)
+                .help("drops records of reported templates until the template is announced again"),
+        )
+        .arg(
+            Arg::with_name("tenant")
+                .long("tenant")
+                .alias("site")
+                .takes_value(true)
+                .help("tenant of all flows, exporters can override it in the exporters file"),
+        )
+        .arg(
+            Arg::with_name("elephant-bytes")
+                .long("elephant-bytes")
+                .takes_value(true)
+                .help("flows of at least this size, e.g. 100MB, are classified as elephants, defaults to 100MB"),
+        )
+        .arg(
+            Arg::with_name("elephant-duration")
+                .long("elephant-duration")
+                .takes_value(true)
+                .help(
+                    "smaller flows active for at least this duration, e.g. 60s, are classified as \
+                     long-lived-small, defaults to 60s",
+                ),
+        )
+        .arg(
+            Arg::with_name("service-map")
+                .long("service-map")
+                .takes_value(true)
+                .help("TOML file with service names, takes precedence over the built-in names, reloaded on SIGHUP"),
+        )
+        .arg(
+            Arg::with_name("networks")
+                .long("networks")
+                .takes_value(true)
+                .help("TOML or CSV file of network prefixes, names and labels of the source and destination networks, reloaded on SIGHUP"),
+        )
+        .arg(
+            Arg::with_name("custom-fields")
+                .long("custom-fields")
+                .takes_value(true)
+                .help(
+                    "TOML file with [[custom_field]] mappings of additional information elements",
+                ),
+        )
+        .arg(
+            Arg::with_name("prefer-inner")
+                .long("prefer-inner")
+                .takes_value(false)
+                .help("uses the inner header of tunneled flows as source and destination, the outer header is kept in the outer_* fields"),
+        )
+        .arg(
+            Arg::with_name("max-metadata-length")
+                .long("max-metadata-length")
+                .takes_value(true)
+                .help("truncates DNS, HTTP and TLS metadata strings to this many bytes, defaults to 512"),
+        )
+        .arg(
+            Arg::with_name("interface-name-ttl")
+                .long("interface-name-ttl")
+                .takes_value(true)
+                .help("seconds interface names announced by exporters are kept, defaults to 3600"),
+        )
+        .arg(
+            Arg::with_name("ephemeral-port-start")
+                .long("ephemeral-port-start")
+                .takes_value(true)
+                .help("first ephemeral port, used to decide which side of a flow is the service"),
+        )
+        .arg(
+            Arg::with_name("control-socket")
+                .long("control-socket")
+                .default_value(fluss::control::DEFAULT_SOCKET)
+                .help("unix domain socket for inspecting the running collector"),
+        )
+        .arg(
+            Arg::with_name("stats-report")
+                .long("stats-report")
+                .takes_value(true)
+                .help("writes the per exporter statistics as JSON to this file on shutdown"),
+        )
+        .arg(
+            Arg::with_name("decode-workers")
+                .long("decode-workers")
+                .takes_value(true)
+                .help("number of decode workers, defaults to half the available cpus"),
+        )
+        .arg(
+            Arg::with_name("otel-endpoint")
+                .long("otel-endpoint")
+                .takes_value(true)
+                .help("exports traces and pipeline metrics to this OTLP/gRPC endpoint, e.g. http://localhost:4317"),
+        )
+        .arg(
+            Arg::with_name("otel-sample-ratio")
+                .long("otel-sample-ratio")
+                .takes_value(true)
+                .requires("otel-endpoint")
+                .help("fraction of packets traced with --otel-endpoint, defaults to 0.001"),
+        )
+}
+
+pub fn run(app: &ArgMatches, verbose: bool) -> anyhow::Result<()> {
+    tokio::runtime::Runtime::new()?.block_on(collect(app, verbose))
+}
+
+/// Starts the export configured with `--otel-endpoint`.
+///
+/// Has to happen before logging is set up, the spans are exported by a layer of the subscriber.
+#[cfg(feature = "otel")]
+pub fn telemetry(app: &ArgMatches) -> anyhow::Result<Option<fluss::telemetry::Telemetry>> {
+    let endpoint = match app.value_of("otel-endpoint") {
+        Some(endpoint) => endpoint,
+        None => return Ok(None),
+    };
+    let sample_ratio = match app.value_of("otel-sample-ratio") {
+        Some(ratio) => ratio.parse()?,
+        None => fluss::telemetry::DEFAULT_SAMPLE_RATIO,
+    };
+    if !(0.0..=1.0).contains(&sample_ratio) {
+        anyhow::bail!("--otel-sample-ratio has to be between 0 and 1");
+    }
+
+    let telemetry = fluss::telemetry::Telemetry::new(endpoint, sample_ratio)?;
+    Ok(Some(telemetry))
+}
+
+/// Prints and resets the summary every `interval`.
+async fn print_summaries(publisher: Arc<SummaryPublisher>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    // the first tick completes immediately
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        // clears the terminal before drawing the new summary
+        println!("\x1b[2J\x1b[H{}", publisher.take_summary());
+    }
+}
+
+/// Publishes flows of the merger which did not find their counterpart.
+async fn flush_merger(merger: Arc<FlowMerger>, window: Duration) {
+    let mut interval = tokio::time::interval(window);
+
+    loop {
+        interval.tick().await;
+        if let Err(err) = merger.flush_expired().await {
+            tracing::warn!(error = %err, "failed to publish unmerged flows");
+        }
+    }
+}
+
+/// Publishes the rollups of the elapsed window.
+async fn flush_rollups(publisher: Arc<RollupPublisher>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    // the first tick completes immediately
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        if let Err(err) = publisher.flush_window().await {
+            tracing::warn!(error = %err, "failed to publish rollups");
+        }
+    }
+}
+
+/// Indexes partial batches which did not fill up within the flush interval.
+async fn flush_elastic(publisher: Arc<ElasticPublisher>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+        if let Err(err) = ElasticPublisher::flush(&publisher).await {
+            tracing::warn!(error = %err, "failed to index flows into elastic");
+        }
+    }
+}
+
+/// Inserts partial batches which did not fill up within the flush interval.
+async fn flush_clickhouse(publisher: Arc<ClickHousePublisher>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+        if let Err(err) = publisher.flush().await {
+            tracing::warn!(error = %err, "failed to insert flows into clickhouse");
+        }
+    }
+}
+
+/// Sends partial batches which did not fill up within the flush interval.
+async fn flush_redis(publisher: Arc<RedisPublisher>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+        if let Err(err) = publisher.flush().await {
+            tracing::warn!(error = %err, "failed to add flows to redis");
+        }
+    }
+}
+
+/// Removes interface names which were not announced again in time.
+#[cfg(feature = "parquet")]
+async fn flush_parquet(publisher: Arc<fluss::publish::ParquetPublisher>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+        if let Err(err) = fluss::publish::ParquetPublisher::flush(&publisher).await {
+            tracing::warn!(error = %err, "failed to write flows to parquet");
+        }
+    }
+}
+
+/// Parses a duration in seconds, minutes, hours or days, e.g. `90s`, `15m` or `1h`.
+///
+/// Durations without a unit are seconds.
+fn parse_duration(duration: &str) -> anyhow::Result<Duration> {
+    let split = duration
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(duration.len());
+    let value: u64 = duration[..split]
+        .parse()
+        .with_context(|| format!("invalid duration {}", duration))?;
+    let unit = match &duration[split..] {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        unit => anyhow::bail!("unknown unit {} of duration {}", unit, duration),
+    };
+    Ok(Duration::from_secs(value * unit))
+}
+
+/// Parses an amount of bytes with a decimal or binary unit, e.g. `100MB` or `1GiB`.
+///
+/// Sizes without a unit are bytes.
+fn parse_size(size: &str) -> anyhow::Result<u64> {
+    let split = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let value: u64 = size[..split]
+        .parse()
+        .with_context(|| format!("invalid size {}", size))?;
+    let unit: u64 = match &size[split..] {
+        "" | "B" => 1,
+        "KB" => 1000,
+        "MB" => 1000 * 1000,
+        "GB" => 1000 * 1000 * 1000,
+        "TB" => 1000 * 1000 * 1000 * 1000,
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        "TiB" => 1 << 40,
+        unit => anyhow::bail!("unknown unit {} of size {}", unit, size),
+    };
+    value
+        .checked_mul(unit)
+        .ok_or_else(|| anyhow::anyhow!("size {} is too large", size))
+}
+
+/// Removes interface names which were not announced again in time.
+async fn expire_interface_names(pipeline: Arc<Pipeline>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+        pipeline.interfaces.expire();
+    }
+}
+
+/// Keeps the systemd watchdog from restarting the collector.
+async fn systemd_watchdog(interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+        notify_systemd("WATCHDOG=1");
+    }
+}
+
+fn notify_systemd(state: &str) {
+    if let Err(err) = fluss::systemd::notify(state) {
+        tracing::warn!(error = %err, state, "failed to notify systemd");
+    }
+}
+
+/// A received datagram, the data is shared with the sets of the decoded packet.
+struct Datagram {
+    data: Bytes,
+    addr: SocketAddr,
+    received: DateTime<Utc>,
+    settings: Arc<ExporterSettings>,
+}
+
+/// Creates a publisher of the routes publisher, the flush interval applies to all of them.
+async fn routed_publisher(
+    app: &ArgMatches<'_>,
+    config: &PublisherConfig,
+) -> anyhow::Result<Arc<dyn Publisher + Send + Sync>> {
+    let interval: u64 = app.value_of("flush-interval").unwrap().parse()?;
+    let interval = Duration::from_secs(interval.max(1));
+
+    Ok(match &config.kind {
+        PublisherKind::Elastic {
+            url,
+            index,
+            data_stream,
+        } => {
+            let transport = elasticsearch::http::transport::Transport::single_node(url)?;
+            let mut publisher = ElasticPublisher::new(elasticsearch::Elasticsearch::new(transport));
+            if let Some(pattern) = index {
+                publisher.set_index_strategy(IndexStrategy::Pattern(pattern.parse()?));
+            }
+            if let Some(name) = data_stream {
+                publisher.set_index_strategy(IndexStrategy::DataStream(name.clone()));
+            }
+            if let Some(batch_size) = config.batch_size {
+                publisher.set_batch_size(batch_size);
+            }
+            publisher.set_flush_interval(interval);
+
+            let publisher = Arc::new(publisher);
+            tokio::spawn(flush_elastic(Arc::clone(&publisher), interval));
+            publisher
+        }
+        PublisherKind::ClickHouse { url, table } => {
+            let mut publisher = ClickHousePublisher::new(url, table);
+            if let Some(batch_size) = config.batch_size {
+                publisher.set_batch_size(batch_size);
+            }
+            publisher.set_flush_interval(interval);
+
+            let publisher = Arc::new(publisher);
+            tokio::spawn(flush_clickhouse(Arc::clone(&publisher), interval));
+            publisher
+        }
+        PublisherKind::Redis {
+            url,
+            stream,
+            max_len,
+        } => {
+            let mut publisher = RedisPublisher::new(url, stream)?;
+            if let Some(max_len) = max_len {
+                publisher.set_max_len(*max_len);
+            }
+            if let Some(batch_size) = config.batch_size {
+                publisher.set_batch_size(batch_size);
+            }
+            publisher.set_flush_interval(interval);
+
+            let publisher = Arc::new(publisher);
+            tokio::spawn(flush_redis(Arc::clone(&publisher), interval));
+            publisher
+        }
+        #[cfg(feature = "parquet")]
+        PublisherKind::Parquet { dir } => {
+            let mut publisher = fluss::publish::ParquetPublisher::new(dir);
+            if let Some(batch_size) = config.batch_size {
+                publisher.set_batch_size(batch_size);
+            }
+
+            let publisher = Arc::new(publisher);
+            tokio::spawn(flush_parquet(Arc::clone(&publisher), interval));
+            publisher
+        }
+        #[cfg(not(feature = "parquet"))]
+        PublisherKind::Parquet { .. } => {
+            anyhow::bail!("fluss was built without the parquet feature")
+        }
+        PublisherKind::Syslog { target, transport } => {
+            Arc::new(SyslogPublisher::new(*target, *transport)?)
+        }
+        PublisherKind::Console => Arc::new(fluss::publish::ConsolePublisher::new()),
+    })
+}
+
+async fn collect(app: &ArgMatches<'_>, verbose: bool) -> anyhow::Result<()> {
+    let mut router = None;
+    let mut elastic = None;
+    let publisher: Arc<dyn Publisher + Send + Sync> = match app.value_of("publisher") {
+        Some("elastic") => {
+            let mut publisher = ElasticPublisher::new(elasticsearch::Elasticsearch::default());
+            if let Some(pattern) = app.value_of("elastic-index") {
+                publisher.set_index_strategy(IndexStrategy::Pattern(pattern.parse()?));
+            }
+            if let Some(name) = app.value_of("elastic-data-stream") {
+                publisher.set_index_strategy(IndexStrategy::DataStream(name.to_owned()));
+            }
+            if let Some(budget) = app.value_of("retry-budget") {
+                publisher.set_retry_budget(Duration::from_secs(budget.parse()?));
+            }
+            if let Some(path) = app.value_of("dead-letter") {
+                publisher.set_dead_letter_file(path).await?;
+            }
+            if let Some(batch_size) = app.value_of("batch-size") {
+                publisher.set_batch_size(batch_size.parse()?);
+            }
+            let interval: u64 = app.value_of("flush-interval").unwrap().parse()?;
+            let interval = Duration::from_secs(interval.max(1));
+            publisher.set_flush_interval(interval);
+            if app.is_present("elastic-setup") {
+                let days: u64 = app.value_of("elastic-retention").unwrap().parse()?;
+                publisher
+                    .setup(Duration::from_secs(days.max(1) * 24 * 60 * 60))
+                    .await?;
+            }
+
+            let publisher = Arc::new(publisher);
+            tokio::spawn(flush_elastic(Arc::clone(&publisher), interval));
+            elastic = Some(Arc::clone(&publisher));
+            publisher
+        }
+        Some("clickhouse") => {
+            let mut publisher = ClickHousePublisher::new(
+                app.value_of("clickhouse-url").unwrap(),
+                app.value_of("clickhouse-table").unwrap(),
+            );
+            if let Some(batch_size) = app.value_of("batch-size") {
+                publisher.set_batch_size(batch_size.parse()?);
+            }
+            let interval: u64 = app.value_of("flush-interval").unwrap().parse()?;
+            let interval = Duration::from_secs(interval.max(1));
+            publisher.set_flush_interval(interval);
+            if app.is_present("clickhouse-create-table") {
+                publisher.ensure_table().await?;
+            }
+
+            let publisher = Arc::new(publisher);
+            tokio::spawn(flush_clickhouse(Arc::clone(&publisher), interval));
+            publisher
+        }
+        Some("redis") => {
+            let mut publisher = RedisPublisher::new(
+                app.value_of("redis-url").unwrap(),
+                app.value_of("redis-stream").unwrap(),
+            )?;
+            if let Some(max_len) = app.value_of("redis-max-len") {
+                publisher.set_max_len(max_len.parse()?);
+            }
+            if let Some(batch_size) = app.value_of("batch-size") {
+                publisher.set_batch_size(batch_size.parse()?);
+            }
+            let interval: u64 = app.value_of("flush-interval").unwrap().parse()?;
+            let interval = Duration::from_secs(interval.max(1));
+            publisher.set_flush_interval(interval);
+
+            let publisher = Arc::new(publisher);
+            tokio::spawn(flush_redis(Arc::clone(&publisher), interval));
+            publisher
+        }
+        Some("amqp") => {
+            let mut publisher = AmqpPublisher::new(
+                app.value_of("amqp-url").unwrap(),
+                app.value_of("amqp-exchange").unwrap(),
+                app.value_of("amqp-routing-key").unwrap().parse()?,
+            );
+            if let Some(max_in_flight) = app.value_of("amqp-max-in-flight") {
+                publisher.set_max_in_flight(max_in_flight.parse()?);
+            }
+            Arc::new(publisher)
+        }
+        Some("syslog") => {
+            let mut publisher = SyslogPublisher::new(
+                app.value_of("syslog-target").unwrap().parse()?,
+                app.value_of("syslog-transport").unwrap().parse()?,
+            )?;
+            if let Some(hostname) = app.value_of("syslog-hostname") {
+                publisher.set_hostname(hostname);
+            }
+            Arc::new(publisher)
+        }
+        #[cfg(feature = "parquet")]
+        Some("parquet") => {
+            let mut publisher =
+                fluss::publish::ParquetPublisher::new(app.value_of("parquet-dir").unwrap());
+            publisher.set_rotate(parse_duration(app.value_of("parquet-rotate").unwrap())?);
+            if let Some(megabytes) = app.value_of("parquet-max-file-size") {
+                publisher.set_max_file_size(megabytes.parse::<u64>()? * 1024 * 1024);
+            }
+            publisher.set_compression(app.value_of("parquet-compression").unwrap().parse()?);
+            if let Some(batch_size) = app.value_of("batch-size") {
+                publisher.set_batch_size(batch_size.parse()?);
+            }
+            let interval: u64 = app.value_of("flush-interval").unwrap().parse()?;
+            let interval = Duration::from_secs(interval.max(1));
+
+            let publisher = Arc::new(publisher);
+            tokio::spawn(flush_parquet(Arc::clone(&publisher), interval));
+            publisher
+        }
+        #[cfg(not(feature = "parquet"))]
+        Some("parquet") => anyhow::bail!("fluss was built without the parquet feature"),
+        Some("routes") => {
+            let routes = Routes::load(app.value_of("routes").unwrap())
+                .context("failed to load the routes")?;
+            let mut publishers = Vec::with_capacity(routes.publishers().len());
+            for config in routes.publishers() {
+                let publisher = routed_publisher(app, config)
+                    .await
+                    .with_context(|| format!("failed to create publisher {}", config.name))?;
+                publishers.push((config.name.clone(), publisher));
+            }
+
+            let publisher = Arc::new(routes.into_publisher(publishers)?);
+            router = Some(Arc::clone(&publisher));
+            publisher
+        }
+        Some("console") if app.value_of("console-mode") == Some("summary") => {
+            let mut publisher = SummaryPublisher::new();
+            if let Some(top) = app.value_of("summary-top") {
+                publisher.set_top(top.parse()?);
+            }
+            publisher.set_by_port(app.is_present("summary-by-port"));
+            publisher.set_normalize_sampling(app.is_present("normalize-sampling"));
+            let publisher = Arc::new(publisher);
+
+            let interval: u64 = app.value_of("summary-interval").unwrap().parse()?;
+            let interval = Duration::from_secs(interval.max(1));
+            tokio::spawn(print_summaries(Arc::clone(&publisher), interval));
+
+            publisher
+        }
+        Some("console") => {
+            let mut publisher = fluss::publish::ConsolePublisher::new();
+            publisher.set_format(app.value_of("console-format").unwrap().parse()?);
+            if let Some(fields) = app.value_of("console-fields") {
+                publisher.set_fields(CompactField::parse_list(fields)?);
+            }
+            // https://no-color.org
+            let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+            publisher.set_color(std::io::stdout().is_terminal() && !no_color);
+            publisher.set_verbose(verbose);
+            publisher.set_service_names(app.is_present("service-names"));
+            publisher.set_normalize_sampling(app.is_present("normalize-sampling"));
+            Arc::new(publisher)
+        }
+        _ => panic!("unknown or no publisher"),
+    };
+
+    // rollups are published instead of the flows
+    let mut rollup = None;
+    let publisher: Arc<dyn Publisher + Send + Sync> = match app.value_of("rollup") {
+        Some(fields) => {
+            let inner: Box<dyn Publisher<Rollup> + Send + Sync> = match &elastic {
+                Some(elastic) => Box::new(Arc::clone(elastic)),
+                None if app.value_of("publisher") == Some("console")
+                    && app.value_of("console-mode") == Some("flows") =>
+                {
+                    let mut console = fluss::publish::ConsolePublisher::new();
+                    console.set_format(app.value_of("console-format").unwrap().parse()?);
+                    Box::new(console)
+                }
+                None => anyhow::bail!(
+                    "rollups can only be published by the console publisher printing flows or the elastic publisher"
+                ),
+            };
+
+            let mut publisher = RollupPublisher::new(CompactField::parse_list(fields)?, inner)?;
+            if let Some(max_keys) = app.value_of("rollup-max-keys") {
+                publisher.set_max_keys(max_keys.parse()?);
+            }
+            publisher.set_normalize_sampling(app.is_present("normalize-sampling"));
+            let publisher = Arc::new(publisher);
+
+            let interval: u64 = app.value_of("rollup-interval").unwrap().parse()?;
+            let interval = Duration::from_secs(interval.max(1));
+            tokio::spawn(flush_rollups(Arc::clone(&publisher), interval));
+            rollup = Some(Arc::clone(&publisher));
+            publisher
+        }
+        None => publisher,
+    };
+
+    let publisher: Arc<dyn Publisher + Send + Sync> = match app.value_of("dedup-window") {
+        Some(window) => Arc::new(DeduplicatingPublisher::new(
+            publisher,
+            Duration::from_secs(window.parse()?),
+        )),
+        None => publisher,
+    };
+
+    let publisher: Arc<dyn Publisher + Send + Sync> = match app.value_of("merge-window") {
+        Some(window) => {
+            let window = Duration::from_secs(window.parse::<u64>()?.max(1));
+            let merger = Arc::new(FlowMerger::new(window, Box::new(publisher)));
+            tokio::spawn(flush_merger(Arc::clone(&merger), window));
+            merger
+        }
+        None => publisher,
+    };
+
+    // fails fast instead of queueing flows for an unreachable backend
+    publisher
+        .health_check()
+        .await
+        .context("publisher failed the health check")?;
+
+    let workers = match app.value_of("decode-workers") {
+        Some(workers) => workers.parse()?,
+        None => std::thread::available_parallelism().map_or(1, |n| n.get() / 2),
+    }
+    .max(1);
+
+    let activated = match app.is_present("systemd-socket") {
+        true => fluss::systemd::udp_socket()?,
+        false => None,
+    };
+    let socket = match activated {
+        Some(socket) => {
+            let socket = UdpSocket::from_std(socket)?;
+            tracing::info!(listen = %socket.local_addr()?, "listening on socket passed by systemd");
+            socket
+        }
+        None => {
+            let listen = app.value_of("listen").unwrap();
+            let socket = UdpSocket::bind(listen).await?;
+            tracing::info!(listen, "listening for netflow traffic");
+            socket
+        }
+    };
+
+    let targets = app
+        .values_of("forward")
+        .into_iter()
+        .flatten()
+        .map(str::parse)
+        .collect::<Result<Vec<SocketAddr>, _>>()?;
+    if !targets.is_empty() {
+        tracing::info!(?targets, "forwarding datagrams");
+    }
+    let mut proxy = IpfixProxy::from_socket(socket, targets);
+
+    let max_clock_skew = match app.value_of("max-clock-skew") {
+        Some(skew) => Duration::from_secs(skew.parse()?),
+        None => Duration::MAX,
+    };
+
+    let clock_skew_threshold = match app.value_of("clock-skew-threshold") {
+        Some(threshold) => Duration::from_secs(threshold.parse()?),
+        None => fluss::clock::DEFAULT_THRESHOLD,
+    };
+    let clock_skew_threshold = app
+        .is_present("correct-clock-skew")
+        .then_some(clock_skew_threshold);
+
+    let message_dedup_window = app
+        .value_of("message-dedup-window")
+        .map(str::parse)
+        .transpose()?;
+    let message_dedup_age = match app.value_of("message-dedup-age") {
+        Some(age) => Duration::from_secs(age.parse()?),
+        None => fluss::duplicates::DEFAULT_MAX_AGE,
+    };
+
+    let max_template_fields = match app.value_of("max-template-fields") {
+        Some(count) => count.parse()?,
+        None => fluss::ipfix::session::DEFAULT_MAX_TEMPLATE_FIELDS,
+    };
+
+    let exporter_idle_timeout = match app.value_of("exporter-idle-timeout") {
+        Some(secs) => Some(Duration::from_secs(secs.parse()?)),
+        None => Some(DEFAULT_EXPORTER_IDLE_TIMEOUT),
+    }
+    .filter(|timeout| !timeout.is_zero());
+
+    let max_metadata_length = match app.value_of("max-metadata-length") {
+        Some(length) => length.parse()?,
+        None => fluss::produce::DEFAULT_MAX_METADATA_LENGTH,
+    };
+
+    let mut sanity = SanityChecks::new();
+    if let Some(bytes) = app.value_of("suspect-max-bytes") {
+        sanity = sanity.with_max_bytes(bytes.parse()?);
+    }
+    if app.is_present("suspect-port-lengths") {
+        sanity = sanity.with_port_lengths();
+    }
+    if let Some(secs) = app.value_of("suspect-time-reversal") {
+        sanity = sanity.with_max_time_reversal(Duration::from_secs(secs.parse()?));
+    }
+    if let Some(count) = app.value_of("suspect-threshold") {
+        sanity = sanity.with_threshold(count.parse()?);
+    }
+    if let Some(secs) = app.value_of("suspect-window") {
+        sanity = sanity.with_window(Duration::from_secs(secs.parse()?));
+    }
+    if app.is_present("quarantine-suspect-templates") {
+        sanity = sanity.with_quarantine();
+    }
+
+    let reloader = Reloader::new(Sources {
+        exporters: app.value_of("exporters").map(Into::into),
+        allow_exporters: app
+            .values_of("allow-exporter")
+            .into_iter()
+            .flatten()
+            .map(str::parse)
+            .collect::<anyhow::Result<_>>()?,
+        deny_exporters: app
+            .values_of("deny-exporter")
+            .into_iter()
+            .flatten()
+            .map(str::parse)
+            .collect::<anyhow::Result<_>>()?,
+        service_map: app.value_of("service-map").map(Into::into),
+        ephemeral_port_start: app
+            .value_of("ephemeral-port-start")
+            .map(str::parse)
+            .transpose()?,
+        networks: app.value_of("networks").map(Into::into),
+        custom_fields: app.value_of("custom-fields").map(Into::into),
+        tenant: app.value_of("tenant").map(Into::into),
+    })?;
+
+    let mut interfaces = InterfaceNames::new();
+    let interface_name_ttl = match app.value_of("interface-name-ttl") {
+        Some(ttl) => Duration::from_secs(ttl.parse::<u64>()?.max(1)),
+        None => fluss::enrich::interface::DEFAULT_MAX_AGE,
+    };
+    interfaces.set_max_age(interface_name_ttl);
+
+    let mut classifier = FlowClassifier::new();
+    if let Some(bytes) = app.value_of("elephant-bytes") {
+        classifier.set_elephant_bytes(parse_size(bytes)?);
+    }
+    if let Some(duration) = app.value_of("elephant-duration") {
+        classifier.set_elephant_duration(parse_duration(duration)?);
+    }
+
+    let pipeline = Arc::new(Pipeline {
+        publisher,
+        router,
+        elastic,
+        rollup,
+        reloader,
+        interfaces,
+        classifier,
+        prefer_inner: app.is_present("prefer-inner"),
+        max_metadata_length,
+        exporter_idle_timeout,
+        debug: app.is_present("debug"),
+        log_denied: app.is_present("log-denied"),
+        denied_logged: Mutex::new(None),
+        max_clock_skew,
+        clock_skew_threshold,
+        message_dedup_window,
+        message_dedup_age,
+        max_template_fields,
+        sanity,
+        sessions: RwLock::new(HashMap::new()),
+        counters: Counters::default(),
+        stats: StatsRegistry::new(),
+        started: Utc::now(),
+    });
+
+    tokio::spawn(expire_interface_names(
+        Arc::clone(&pipeline),
+        interface_name_ttl,
+    ));
+
+    // the export was started with the subscriber, see `telemetry`
+    #[cfg(feature = "otel")]
+    if fluss::telemetry::enabled() {
+        let observed = Arc::clone(&pipeline);
+        fluss::telemetry::observe_pipeline(move || observed.pipeline_stats());
+        let observed = Arc::clone(&pipeline);
+        fluss::telemetry::observe_flow_histograms(move || observed.classifier.histograms());
+    }
+    #[cfg(not(feature = "otel"))]
+    if app.is_present("otel-endpoint") {
+        anyhow::bail!("fluss was built without the otel feature");
+    }
+
+    let control_socket = app.value_of("control-socket").unwrap().to_owned();
+    let control = Arc::clone(&pipeline);
+    tokio::spawn(async move {
+        let result = fluss::control::serve(&control_socket, move |request| {
+            control.handle_request(request)
+        });
+        if let Err(err) = result.await {
+            tracing::warn!(error = %err, path = control_socket.as_str(), "control socket failed");
+        }
+    });
+
+    let mut senders = Vec::with_capacity(workers);
+    let mut handles = Vec::with_capacity(workers);
+    for _ in 0..workers {
+        let (tx, rx) = mpsc::channel(1024);
+        handles.push(tokio::spawn(decode(rx, Arc::clone(&pipeline))));
+        senders.push(tx);
+    }
+    tracing::info!(workers, "started decode workers");
+
+    // the publisher was set up and the socket is bound, the collector is ready
+    notify_systemd("READY=1");
+    if let Some(interval) = fluss::systemd::watchdog_interval() {
+        tokio::spawn(systemd_watchdog(interval));
+    }
+
+    let shutdown = ShutdownToken::new();
+    shutdown.shutdown_on_signal()?;
+    let mut hangup = signal(SignalKind::hangup())?;
+    let mut user1 = signal(SignalKind::user_defined1())?;
+    let dump_templates = app.value_of("dump-templates");
+
+    #[cfg(feature = "pcap-capture")]
+    let (mut capture, local_addr) = (
+        app.value_of("pcap-capture")
+            .map(fluss::debug::PcapCapture::new)
+            .transpose()?,
+        proxy.local_addr()?,
+    );
+    #[cfg(not(feature = "pcap-capture"))]
+    if app.is_present("pcap-capture") {
+        anyhow::bail!("fluss was built without the pcap-capture feature");
+    }
+
+    let raw_capture = app
+        .value_of("raw-capture")
+        .map(RawPublisher::new)
+        .transpose()?;
+
+    let mut pool = BufferPool::new(u16::MAX as usize);
+    loop {
+        let (data, addr) = tokio::select! {
+            received = proxy.recv_from(&mut pool) => received?,
+            _ = shutdown.wait() => break,
+            _ = hangup.recv() => {
+                // errors are logged, the collector keeps the previous settings
+                let _ = pipeline.reload();
+                continue;
+            }
+            _ = user1.recv() => {
+                match dump_templates {
+                    Some(path) => match pipeline.dump_templates(path) {
+                        Ok(()) => tracing::info!(path, "dumped templates"),
+                        Err(err) => tracing::error!(error = %err, path, "failed to dump templates"),
+                    },
+                    None => tracing::warn!("received SIGUSR1 without --dump-templates"),
+                }
+                continue;
+            }
+        };
+        tracing::debug!(len = data.len(), exporter = %addr, "datagram received");
+        pipeline.counters.datagrams.fetch_add(1, Ordering::Relaxed);
+        let received = Utc::now();
+
+        #[cfg(feature = "pcap-capture")]
+        if let Some(capture) = &mut capture {
+            if let Err(err) = capture.write(addr, local_addr, received, &data) {
+                tracing::warn!(error = %err, "failed to capture datagram");
+            }
+        }
+
+        if let Some(raw_capture) = &raw_capture {
+            if let Err(err) = raw_capture.raw_packet(addr, &data) {
+                tracing::warn!(error = %err, "failed to record datagram");
+            }
+        }
+
+        let (worker, datagram) = match pipeline.dispatch(data, addr, received, senders.len()) {
+            Some(dispatched) => dispatched,
+            None => continue,
+        };
+        senders[worker]
+            .send(datagram)
+            .await
+            .map_err(|_| anyhow::anyhow!("decode worker {} stopped", worker))?;
+    }
+
+    // the workers stop once all queued datagrams are decoded
+    tracing::info!("shutting down");
+    notify_systemd("STOPPING=1");
+    drop(senders);
+    for handle in handles {
+        handle.await?;
+    }
+
+    #[cfg(feature = "pcap-capture")]
+    if let Some(capture) = &mut capture {
+        capture.flush()?;
+    }
+    if let Some(raw_capture) = &raw_capture {
+        raw_capture.flush()?;
+    }
+
+    // batching publishers still hold flows of the last datagrams
+    if let Err(err) = pipeline.publisher.flush().await {
+        tracing::error!(error = %err, "failed to flush the publisher, flows were lost");
+    }
+
+    let report = pipeline.report();
+    tracing::info!(
+        "flow accounting since {}\n{}",
+        report.started,
+        fluss::stats::format_table(&report.exporters)
+    );
+    if let Some(path) = app.value_of("stats-report") {
+        report.write(path)?;
+        tracing::info!(path, "wrote statistics report");
+    }
+
+    Ok(())
+}
+
+/// Counters of the pipeline, exposed through the control socket.
+#[derive(Default)]
+struct Counters {
+    datagrams: AtomicU64,
+    decode_errors: AtomicU64,
+    flows: AtomicU64,
+    options_records: AtomicU64,
+    sequence_gaps: AtomicU64,
+    duplicate_messages: AtomicU64,
+    denied_datagrams: AtomicU64,
+    rejected_templates: AtomicU64,
+    flapping_templates: AtomicU64,
+    published: AtomicU64,
+    publish_errors: AtomicU64,
+}
+
+impl Counters {
+    fn snapshot(&self) -> PipelineStats {
+        PipelineStats {
+            datagrams: self.datagrams.load(Ordering::Relaxed),
+            decode_errors: self.decode_errors.load(Ordering::Relaxed),
+            flows: self.flows.load(Ordering::Relaxed),
+            options_records: self.options_records.load(Ordering::Relaxed),
+            sequence_gaps: self.sequence_gaps.load(Ordering::Relaxed),
+            duplicate_messages: self.duplicate_messages.load(Ordering::Relaxed),
+            denied_datagrams: self.denied_datagrams.load(Ordering::Relaxed),
+            rejected_templates: self.rejected_templates.load(Ordering::Relaxed),
+            flapping_templates: self.flapping_templates.load(Ordering::Relaxed),
+            // not a counter, see `Pipeline::pipeline_stats`
+            rollup_keys: 0,
+            published: self.published.load(Ordering::Relaxed),
+            publish_errors: self.publish_errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// State shared by the decode workers and the control socket.
+struct Pipeline {
+    publisher: Arc<dyn Publisher + Send + Sync>,
+    // the inner publisher if flows are routed, for its counters
+    router: Option<Arc<RoutingPublisher>>,
+    // the elastic publisher, for its documents per tenant
+    elastic: Option<Arc<ElasticPublisher>>,
+    // the rollup publisher, for its active keys
+    rollup: Option<Arc<RollupPublisher>>,
+    // exporter settings and service names, replaced on SIGHUP
+    reloader: Reloader,
+    // interface names learned from options records of all exporters
+    interfaces: InterfaceNames,
+    // size classes of flows and the distributions of their sizes
+    classifier: FlowClassifier,
+    prefer_inner: bool,
+    max_metadata_length: usize,
+    // `None` if exporters are never evicted
+    exporter_idle_timeout: Option<Duration>,
+    debug: bool,
+    // datagrams of denied exporters are logged at most every `DENIED_LOG_INTERVAL`
+    log_denied: bool,
+    denied_logged: Mutex<Option<Instant>>,
+    max_clock_skew: Duration,
+    // flow timestamps are corrected if set
+    clock_skew_threshold: Option<Duration>,
+    // duplicate messages are dropped if set
+    message_dedup_window: Option<usize>,
+    message_dedup_age: Duration,
+    max_template_fields: usize,
+    // heuristics flagging suspect flows
+    sanity: SanityChecks,
+    // sessions of all exporters, each session is only decoded by a single worker
+    sessions: RwLock<HashMap<SocketAddr, Arc<Session<CollectParser>>>>,
+    counters: Counters,
+    stats: StatsRegistry,
+    started: DateTime<Utc>,
+}
+
+impl Pipeline {
+    /// Selects one of `workers` decode workers for a datagram, `None` if the
+    /// exporter is denied or its datagrams are dropped.
+    fn dispatch(
+        &self,
+        data: Bytes,
+        addr: SocketAddr,
+        received: DateTime<Utc>,
+        workers: usize,
+    ) -> Option<(usize, Datagram)> {
+        let current = self.reloader.settings();
+        if !current.exporters.is_allowed(addr.ip()) {
+            self.deny(addr);
+            return None;
+        }
+
+        let settings = current.exporters.lookup(addr.ip());
+        if settings.drop {
+            tracing::trace!(exporter = %addr, "dropping datagram of ignored exporter");
+            return None;
+        }
+
+        // all datagrams of an exporter need to end up at the same worker,
+        // the worker owns the templates of the exporter
+        let mut hasher = DefaultHasher::new();
+        addr.hash(&mut hasher);
+        let worker = hasher.finish() as usize % workers;
+
+        Some((
+            worker,
+            Datagram {
+                data,
+                addr,
+                received,
+                settings,
+            },
+        ))
+    }
+
+    /// Counts a dropped datagram of a denied exporter.
+    fn deny(&self, addr: SocketAddr) {
+        let denied = self
+            .counters
+            .denied_datagrams
+            .fetch_add(1, Ordering::Relaxed)
+            + 1;
+        if !self.log_denied {
+            return;
+        }
+
+        let mut logged = self.denied_logged.lock();
+        if logged.is_none_or(|logged| logged.elapsed() >= DENIED_LOG_INTERVAL) {
+            tracing::warn!(exporter = %addr, denied, "dropping datagrams of denied exporters");
+            *logged = Some(Instant::now());
+        }
+    }
+
+    fn new_exporter(&self, addr: SocketAddr, settings: &ExporterSettings) -> Exporter {
+        // the parser completes flows with the options recorded by the session
+        let options = OptionsContext::new();
+        let parser = IpfixParser::with_custom_fields(self.reloader.custom_fields())
+            .with_options(options.clone())
+            .with_prefer_inner(self.prefer_inner)
+            .with_max_metadata_length(self.max_metadata_length);
+        let mut session = Session::new(match self.debug {
+            true => Either::Left(DebugParser::new(parser)),
+            false => Either::Right(parser),
+        })
+        .with_max_clock_skew(self.max_clock_skew)
+        .with_max_template_fields(self.max_template_fields)
+        .with_sanity_checks(self.sanity.clone())
+        .with_options(options);
+        if let Some(templates) = &settings.templates {
+            session = session.with_allowed_templates(templates.iter().copied());
+        }
+
+        let session = Arc::new(session);
+        self.sessions.write().insert(addr, Arc::clone(&session));
+
+        Exporter {
+            session,
+            stats: self.stats.exporter(addr),
+            clock: self.clock_skew_threshold.map(ClockSkew::new),
+            duplicates: self.message_dedup_window.map(|window| {
+                let mut duplicates = DuplicateMessages::new(window);
+                duplicates.set_max_age(self.message_dedup_age);
+                duplicates
+            }),
+            last_seen: Instant::now(),
+        }
+    }
+
+    /// Drops the exporters of a worker which sent nothing within the idle
+    /// timeout, e.g. spoofed sources or exporters restarted on a new port.
+    fn evict_idle(&self, exporters: &mut HashMap<SocketAddr, Exporter>, now: Instant) {
+        let timeout = match self.exporter_idle_timeout {
+            Some(timeout) => timeout,
+            None => return,
+        };
+
+        for addr in idle_exporters(exporters, timeout, now) {
+            tracing::debug!(exporter = %addr, "dropping idle exporter");
+            exporters.remove(&addr);
+            self.sessions.write().remove(&addr);
+            self.stats.remove(addr);
+        }
+    }
+
+    fn exporter_stats(&self) -> Vec<fluss::control::ExporterStats> {
+        let sessions = self.sessions.read();
+        self.stats.snapshot(|exporter| {
+            sessions
+                .get(&exporter)
+                .map_or(0, |session| session.templates().len())
+        })
+    }
+
+    fn pipeline_stats(&self) -> PipelineStats {
+        PipelineStats {
+            rollup_keys: self
+                .rollup
+                .as_ref()
+                .map_or(0, |rollup| rollup.active_keys() as u64),
+            ..self.counters.snapshot()
+        }
+    }
+
+    fn report(&self) -> StatsReport {
+        StatsReport {
+            started: self.started,
+            finished: Utc::now(),
+            pipeline: self.pipeline_stats(),
+            exporters: self.exporter_stats(),
+        }
+    }
+
+    fn handle_request(&self, request: Request) -> Response {
+        match request {
+            Request::Templates => Response::Templates {
+                exporters: self
+                    .sessions
+                    .read()
+                    .iter()
+                    .map(|(&exporter, session)| ExporterTemplates {
+                        exporter,
+                        templates: session.template_stats(),
+                    })
+                    .collect(),
+            },
+            Request::Stats => Response::Stats {
+                stats: self.pipeline_stats(),
+                exporters: self.exporter_stats(),
+                routes: self
+                    .router
+                    .as_ref()
+                    .map_or_else(Vec::new, |router| router.route_stats()),
+                publishers: self
+                    .router
+                    .as_ref()
+                    .map_or_else(Vec::new, |router| router.publisher_stats()),
+                histograms: self.classifier.histograms(),
+                tenants: self
+                    .elastic
+                    .as_ref()
+                    .map_or_else(Vec::new, |elastic| elastic.tenant_stats()),
+            },
+            Request::Reload => match self.reload() {
+                Ok(reloaded) => Response::Reloaded {
+                    applied: reloaded
+                        .applied
+                        .iter()
+                        .map(|&name| name.to_owned())
+                        .collect(),
+                    restart_required: reloaded
+                        .restart_required
+                        .iter()
+                        .map(|&name| name.to_owned())
+                        .collect(),
+                },
+                Err(err) => Response::Error {
+                    message: err.to_string(),
+                },
+            },
+        }
+    }
+
+    /// Reloads the settings files and logs the outcome.
+    fn reload(&self) -> anyhow::Result<Reloaded> {
+        let result = self.reloader.reload();
+        match &result {
+            Ok(reloaded) => {
+                tracing::info!(applied = ?reloaded.applied, "reloaded settings");
+                if !reloaded.restart_required.is_empty() {
+                    tracing::warn!(
+                        changed = ?reloaded.restart_required,
+                        "changed settings require a restart"
+                    );
+                }
+            }
+            Err(err) => {
+                tracing::error!(error = %err, "reload failed, keeping the previous settings");
+            }
+        }
+        result
+    }
+
+    /// Writes the templates of all exporters as JSON to `path`.
+    fn dump_templates(&self, path: &str) -> anyhow::Result<()> {
+        let templates = self
+            .sessions
+            .read()
+            .iter()
+            .map(|(exporter, session)| (exporter.to_string(), session.dump_templates()))
+            .collect::<BTreeMap<_, _>>();
+
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(file), &templates)?;
+        Ok(())
+    }
+}
+
+/// Addresses of the exporters which sent nothing for `timeout`.
+fn idle_exporters(
+    exporters: &HashMap<SocketAddr, Exporter>,
+    timeout: Duration,
+    now: Instant,
+) -> Vec<SocketAddr> {
+    exporters
+        .iter()
+        .filter(|(_, exporter)| now.saturating_duration_since(exporter.last_seen) >= timeout)
+        .map(|(&addr, _)| addr)
+        .collect()
+}
+
+async fn decode(mut rx: mpsc::Receiver<Datagram>, pipeline: Arc<Pipeline>) {
+    let mut exporters = HashMap::new();
+    let mut sweep = tokio::time::interval(EXPORTER_SWEEP_INTERVAL);
+    sweep.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        let datagram = tokio::select! {
+            datagram = rx.recv() => match datagram {
+                Some(datagram) => datagram,
+                None => break,
+            },
+            _ = sweep.tick() => {
+                pipeline.evict_idle(&mut exporters, Instant::now());
+                continue;
+            }
+        };
+
+        let exporter = exporters
+            .entry(datagram.addr)
+            .or_insert_with(|| pipeline.new_exporter(datagram.addr, &datagram.settings));
+        exporter.last_seen = Instant::now();
+
+        let span = tracing::debug_span!(
+            "packet",
+            exporter = %datagram.addr,
+            odid = tracing::field::Empty,
+            seq = tracing::field::Empty
+        );
+
+        let counters = &pipeline.counters;
+        exporter.stats.record_packet(datagram.data.len());
+        let (export_time, mut flows) = span.in_scope(|| {
+            let data = datagram.data.clone();
+            let addr = datagram.addr.ip();
+            match decode_datagram(&span, &pipeline, exporter, addr, &datagram.settings, data) {
+                Ok(Some((export_time, flows))) => (Some(export_time), flows),
+                Ok(None) => (None, Vec::new()),
+                Err(err) => {
+                    tracing::warn!(error = %err, "failed to decode packet");
+                    counters.decode_errors.fetch_add(1, Ordering::Relaxed);
+                    (None, Vec::new())
+                }
+            }
+        });
+
+        for event in exporter.session.events() {
+            match event {
+                SessionEvent::SequenceGap { expected, got, .. } => {
+                    counters.sequence_gaps.fetch_add(1, Ordering::Relaxed);
+                    // the sequence number went back, the exporter most likely restarted
+                    // and its clock may have been set since
+                    if expected.wrapping_sub(got) <= u32::MAX / 2 {
+                        if let Some(clock) = &mut exporter.clock {
+                            clock.reset();
+                        }
+                    }
+                }
+                SessionEvent::TemplateRejected { .. } => {
+                    counters.rejected_templates.fetch_add(1, Ordering::Relaxed);
+                }
+                SessionEvent::TemplateFlapping {
+                    domain_id,
+                    template_id,
+                    version,
+                    suspect,
+                    quarantined,
+                } => {
+                    counters.flapping_templates.fetch_add(1, Ordering::Relaxed);
+                    tracing::warn!(
+                        exporter = %datagram.addr,
+                        domain_id,
+                        template = template_id,
+                        version,
+                        suspect,
+                        quarantined,
+                        "template flapping, records after a layout change look implausible \
+                         and flows of the template may be garbage"
+                    );
+                }
+            }
+        }
+
+        counters
+            .flows
+            .fetch_add(flows.len() as u64, Ordering::Relaxed);
+        exporter
+            .stats
+            .flows
+            .fetch_add(flows.len() as u64, Ordering::Relaxed);
+        if let (Some(clock), Some(export_time)) = (&mut exporter.clock, export_time) {
+            clock.record(export_time, datagram.received);
+        }
+
+        let settings = pipeline.reloader.settings();
+        flows.iter_mut().for_each(|flow| {
+            flow.exporter = Some(datagram.addr.ip());
+            flow.time_received = datagram.received;
+            if let Some(clock) = &exporter.clock {
+                clock.correct(flow);
+            }
+            datagram.settings.apply(flow);
+            settings.enricher.enrich(flow);
+            settings.networks.enrich(flow);
+            pipeline.interfaces.enrich(datagram.addr.ip(), flow);
+            pipeline.classifier.enrich(flow);
+        });
+
+        let stats = &exporter.stats;
+        async {
+            for flow in flows {
+                #[cfg(feature = "otel")]
+                let started = fluss::telemetry::enabled().then(Instant::now);
+                let result = pipeline.publisher.publish(&flow).await;
+                #[cfg(feature = "otel")]
+                if let Some(started) = started {
+                    fluss::telemetry::record_publish_duration(started.elapsed());
+                }
+
+                match result {
+                    Ok(()) => {
+                        counters.published.fetch_add(1, Ordering::Relaxed);
+                        stats.published.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(err) => {
+                        tracing::error!(error = %err, "failed to publish flow");
+                        counters.publish_errors.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+        .instrument(span)
+        .await;
+    }
+}
+
+fn decode_datagram(
+    span: &tracing::Span,
+    pipeline: &Pipeline,
+    exporter: &mut Exporter,
+    addr: IpAddr,
+    settings: &ExporterSettings,
+    data: Bytes,
+) -> anyhow::Result<Option<(u32, Vec<Fluss>)>> {
+    let packet = fluss::ipfix::parse_owned(data.clone()).inspect_err(|_| {
+        exporter.stats.parse_errors.fetch_add(1, Ordering::Relaxed);
+    })?;
+    exporter.stats.messages.fetch_add(1, Ordering::Relaxed);
+    span.record("odid", packet.observation_domain_id);
+    span.record("seq", packet.sequence_number);
+
+    // dropped before the session sees them, they would be reported as sequence gaps
+    if let Some(duplicates) = &mut exporter.duplicates {
+        if duplicates.is_duplicate(
+            packet.observation_domain_id,
+            packet.sequence_number,
+            packet.export_time,
+            &data,
+        ) {
+            tracing::debug!("dropping duplicate message");
+            pipeline
+                .counters
+                .duplicate_messages
+                .fetch_add(1, Ordering::Relaxed);
+            return Ok(None);
+        }
+    }
+
+    let mut flows = Vec::new();
+    for decoded in exporter.session.parse_with_options(&packet)? {
+        match decoded {
+            Decoded::Flow(flow) => flows.push(flow),
+            Decoded::Options(record) => {
+                pipeline
+                    .counters
+                    .options_records
+                    .fetch_add(1, Ordering::Relaxed);
+                pipeline.interfaces.update(addr, &record);
+                log_options(&exporter.session, &record);
+            }
+        }
+    }
+
+    // a configured sampling rate takes precedence over the one of the record,
+    // the parser falls back to the one announced through options records
+    if let Some(interval) = settings.sampling_rate {
+        let interval = u32::try_from(interval).unwrap_or(u32::MAX);
+        for flow in &mut flows {
+            flow.sampling_interval = Some(interval);
+        }
+    }
+
+    Ok(Some((packet.export_time, flows)))
+}
+
+fn log_options(session: &Session<CollectParser>, record: &OptionsRecord) {
+    let fields = |records: &[fluss::protocol::Record]| {
+        records
+            .iter()
+            .map(|record| {
+                let name = session.field_name(record.id).unwrap_or("<???>");
+                format!("{}={}", name, record.value)
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    tracing::info!(
+        template = record.template_id,
+        scope = %fields(&record.scope),
+        options = %fields(&record.options),
+        "options record"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fluss::ipfix::parser::TemplateRecord;
+    use fluss::publish::CapturingPublisher;
+    use fluss::testing::{field, DataRecord, ExporterSimulator};
+    use std::net::Ipv4Addr;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    struct Collector {
+        addr: SocketAddr,
+        pipeline: Arc<Pipeline>,
+        flows: Arc<CapturingPublisher<Fluss>>,
+    }
+
+    /// Runs the receive loop and a decode worker with the default settings on
+    /// an ephemeral port of the loopback interface.
+    async fn collector() -> Collector {
+        collector_with(|_| ()).await
+    }
+
+    /// Like [`collector`] with the settings changed by `configure`.
+    async fn collector_with(configure: impl FnOnce(&mut Pipeline)) -> Collector {
+        let flows = Arc::new(CapturingPublisher::new());
+        let mut pipeline = Pipeline {
+            publisher: Arc::clone(&flows) as Arc<dyn Publisher + Send + Sync>,
+            router: None,
+            elastic: None,
+            rollup: None,
+            reloader: Reloader::new(Sources::default()).unwrap(),
+            interfaces: InterfaceNames::new(),
+            classifier: FlowClassifier::new(),
+            prefer_inner: false,
+            max_metadata_length: usize::MAX,
+            exporter_idle_timeout: None,
+            debug: false,
+            log_denied: false,
+            denied_logged: Mutex::new(None),
+            max_clock_skew: Duration::MAX,
+            clock_skew_threshold: None,
+            message_dedup_window: None,
+            message_dedup_age: fluss::duplicates::DEFAULT_MAX_AGE,
+            max_template_fields: fluss::ipfix::session::DEFAULT_MAX_TEMPLATE_FIELDS,
+            sanity: SanityChecks::new(),
+            sessions: RwLock::new(HashMap::new()),
+            counters: Counters::default(),
+            stats: StatsRegistry::new(),
+            started: Utc::now(),
+        };
+        configure(&mut pipeline);
+        let pipeline = Arc::new(pipeline);
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut proxy = IpfixProxy::from_socket(socket, Vec::new());
+        let addr = proxy.local_addr().unwrap();
+
+        let (tx, rx) = mpsc::channel(64);
+        tokio::spawn(decode(rx, Arc::clone(&pipeline)));
+        let receiver = Arc::clone(&pipeline);
+        tokio::spawn(async move {
+            let mut pool = BufferPool::new(u16::MAX as usize);
+            while let Ok((data, addr)) = proxy.recv_from(&mut pool).await {
+                receiver.counters.datagrams.fetch_add(1, Ordering::Relaxed);
+                if let Some((_, datagram)) = receiver.dispatch(data, addr, Utc::now(), 1) {
+                    if tx.send(datagram).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Collector {
+            addr,
+            pipeline,
+            flows,
+        }
+    }
+
+    fn template(id: u16) -> TemplateRecord {
+        TemplateRecord {
+            id,
+            fields: vec![
+                field(8, 4),
+                field(12, 4),
+                field(4, 1),
+                field(11, 2),
+                field(1, 8),
+                field(2, 8),
+            ],
+        }
+    }
+
+    fn record(src: Ipv4Addr, dst_port: u16, bytes: u64) -> DataRecord {
+        DataRecord::new()
+            .addr(src.into())
+            .addr(Ipv4Addr::new(198, 51, 100, 1).into())
+            .u8(6)
+            .u16(dst_port)
+            .u64(bytes)
+            .u64(1)
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("0").unwrap(), 0);
+        assert_eq!(parse_size("1500B").unwrap(), 1500);
+        assert_eq!(parse_size("100MB").unwrap(), 100_000_000);
+        assert_eq!(parse_size("1GiB").unwrap(), 1 << 30);
+        for size in ["", "MB", "1.5GB", "1 GB", "1mb", "20000000TB"] {
+            assert!(parse_size(size).is_err(), "{:?}", size);
+        }
+    }
+
+    #[tokio::test]
+    async fn templates_then_data() {
+        let collector = collector().await;
+        let mut exporter = ExporterSimulator::new(collector.addr, 1).unwrap();
+        exporter.send_templates(&[template(256)]).unwrap();
+        exporter
+            .send_data(
+                256,
+                &[
+                    record(Ipv4Addr::new(192, 0, 2, 1), 443, 1500),
+                    record(Ipv4Addr::new(192, 0, 2, 2), 53, 80),
+                ],
+            )
+            .unwrap();
+
+        assert!(collector.flows.wait_for(2, TIMEOUT).await);
+        let flows = collector.flows.items();
+        assert_eq!(flows.len(), 2);
+        assert_eq!(flows[0].src_addr, Ipv4Addr::new(192, 0, 2, 1));
+        assert_eq!(flows[0].dst_port, 443);
+        assert_eq!(flows[0].bytes, 1500);
+        assert_eq!(flows[1].src_addr, Ipv4Addr::new(192, 0, 2, 2));
+        assert_eq!(flows[1].dst_port, 53);
+        assert_eq!(flows[1].bytes, 80);
+        for flow in &flows {
+            assert_eq!(flow.exporter, Some(IpAddr::from([127, 0, 0, 1])));
+        }
+    }
+
+    #[tokio::test]
+    async fn resent_messages_are_published_once() {
+        let collector = collector_with(|pipeline| pipeline.message_dedup_window = Some(1000)).await;
+        let mut exporter = ExporterSimulator::new(collector.addr, 1).unwrap();
+        exporter.send_templates(&[template(256)]).unwrap();
+        let message = exporter.builder().data(
+            256,
+            &[
+                record(Ipv4Addr::new(192, 0, 2, 1), 443, 1500),
+                record(Ipv4Addr::new(192, 0, 2, 2), 53, 80),
+            ],
+        );
+        exporter.send_raw(&message).unwrap();
+        exporter.send_raw(&message).unwrap();
+        exporter
+            .send_data(256, &[record(Ipv4Addr::new(192, 0, 2, 3), 22, 700)])
+            .unwrap();
+
+        // the worker decodes the messages in order, the duplicate came before the last flow
+        assert!(collector.flows.wait_for(3, TIMEOUT).await);
+        let flows = collector.flows.items();
+        let sources: Vec<_> = flows.iter().map(|flow| flow.src_addr).collect();
+        assert_eq!(
+            sources,
+            [
+                Ipv4Addr::new(192, 0, 2, 1),
+                Ipv4Addr::new(192, 0, 2, 2),
+                Ipv4Addr::new(192, 0, 2, 3),
+            ]
+        );
+        let counters = &collector.pipeline.counters;
+        assert_eq!(counters.duplicate_messages.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn denied_exporters_teach_no_templates() {
+        let collector = collector_with(|pipeline| {
+            let sources = Sources {
+                allow_exporters: vec!["127.0.0.0/8".parse().unwrap()],
+                deny_exporters: vec!["127.0.0.2/32".parse().unwrap()],
+                ..Sources::default()
+            };
+            pipeline.reloader = Reloader::new(sources).unwrap();
+        })
+        .await;
+        let mut denied =
+            ExporterSimulator::bind(([127, 0, 0, 2], 0).into(), collector.addr, 1).unwrap();
+        let mut allowed = ExporterSimulator::new(collector.addr, 1).unwrap();
+
+        denied.send_templates(&[template(256)]).unwrap();
+        denied
+            .send_data(256, &[record(Ipv4Addr::new(192, 0, 2, 1), 443, 1500)])
+            .unwrap();
+        allowed.send_templates(&[template(256)]).unwrap();
+        allowed
+            .send_data(256, &[record(Ipv4Addr::new(192, 0, 2, 2), 53, 80)])
+            .unwrap();
+
+        assert!(collector.flows.wait_for(1, TIMEOUT).await);
+        let flows = collector.flows.items();
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].exporter, Some(IpAddr::from([127, 0, 0, 1])));
+
+        let pipeline = &collector.pipeline;
+        let exporters: Vec<_> = pipeline.sessions.read().keys().copied().collect();
+        assert_eq!(exporters, [allowed.local_addr().unwrap()]);
+        assert_eq!(
+            pipeline.counters.denied_datagrams.load(Ordering::Relaxed),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn data_before_template() {
+        let collector = collector().await;
+        let mut exporter = ExporterSimulator::new(collector.addr, 1).unwrap();
+        let message = exporter.builder().data_before_template(
+            &template(256),
+            &[record(Ipv4Addr::new(192, 0, 2, 1), 443, 1500)],
+        );
+        exporter.send_raw(&message).unwrap();
+        exporter
+            .send_data(256, &[record(Ipv4Addr::new(192, 0, 2, 2), 80, 700)])
+            .unwrap();
+
+        assert!(collector.flows.wait_for(2, TIMEOUT).await);
+        let flows = collector.flows.items();
+        assert_eq!(flows[0].src_addr, Ipv4Addr::new(192, 0, 2, 1));
+        assert_eq!(flows[0].bytes, 1500);
+        assert_eq!(flows[1].src_addr, Ipv4Addr::new(192, 0, 2, 2));
+        assert_eq!(flows[1].bytes, 700);
+    }
+
+    #[tokio::test]
+    async fn conflicting_template_ids_of_exporters() {
+        let collector = collector().await;
+        let mut first = ExporterSimulator::new(collector.addr, 1).unwrap();
+        let mut second = ExporterSimulator::new(collector.addr, 1).unwrap();
+
+        // the same template id with a different layout, addresses and counters swapped
+        let swapped = TemplateRecord {
+            id: 256,
+            fields: vec![
+                field(1, 8),
+                field(2, 8),
+                field(11, 2),
+                field(4, 1),
+                field(12, 4),
+                field(8, 4),
+            ],
+        };
+        first.send_templates(&[template(256)]).unwrap();
+        second.send_templates(&[swapped]).unwrap();
+        first
+            .send_data(256, &[record(Ipv4Addr::new(192, 0, 2, 1), 443, 1500)])
+            .unwrap();
+        second
+            .send_data(
+                256,
+                &[DataRecord::new()
+                    .u64(900)
+                    .u64(3)
+                    .u16(22)
+                    .u8(6)
+                    .addr(Ipv4Addr::new(198, 51, 100, 1).into())
+                    .addr(Ipv4Addr::new(192, 0, 2, 2).into())],
+            )
+            .unwrap();
+
+        assert!(collector.flows.wait_for(2, TIMEOUT).await);
+        let mut flows = collector.flows.items();
+        flows.sort_by_key(|flow| flow.src_addr);
+        assert_eq!(flows[0].src_addr, Ipv4Addr::new(192, 0, 2, 1));
+        assert_eq!(flows[0].dst_port, 443);
+        assert_eq!(flows[0].bytes, 1500);
+        assert_eq!(flows[1].src_addr, Ipv4Addr::new(192, 0, 2, 2));
+        assert_eq!(flows[1].dst_port, 22);
+        assert_eq!(flows[1].bytes, 900);
+        assert_eq!(flows[1].packets, 3);
+        assert_eq!(collector.pipeline.sessions.read().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn malformed_datagrams_are_skipped() {
+        let collector = collector().await;
+        let mut exporter = ExporterSimulator::new(collector.addr, 1).unwrap();
+        exporter.send_templates(&[template(256)]).unwrap();
+        exporter
+            .send_data(256, &[record(Ipv4Addr::new(192, 0, 2, 1), 443, 1500)])
+            .unwrap();
+        // a message header claiming more bytes than the datagram contains
+        exporter
+            .send_raw(&[0, 10, 0, 200, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1])
+            .unwrap();
+        exporter
+            .send_data(256, &[record(Ipv4Addr::new(192, 0, 2, 2), 80, 700)])
+            .unwrap();
+
+        assert!(collector.flows.wait_for(2, TIMEOUT).await);
+        let flows = collector.flows.items();
+        assert_eq!(flows[0].src_addr, Ipv4Addr::new(192, 0, 2, 1));
+        assert_eq!(flows[1].src_addr, Ipv4Addr::new(192, 0, 2, 2));
+        assert_eq!(flows[1].bytes, 700);
+
+        let counters = &collector.pipeline.counters;
+        assert_eq!(counters.decode_errors.load(Ordering::Relaxed), 1);
+        assert_eq!(counters.sequence_gaps.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn report_of_two_exporters() {
+        let collector = collector().await;
+        let mut first = ExporterSimulator::new(collector.addr, 1).unwrap();
+        let mut second = ExporterSimulator::new(collector.addr, 2).unwrap();
+
+        let sent = |exporter: &mut ExporterSimulator, messages: Vec<Vec<u8>>| {
+            for message in &messages {
+                exporter.send_raw(message).unwrap();
+            }
+            messages.iter().map(Vec::len).sum::<usize>() as u64
+        };
+        let flow = |port| record(Ipv4Addr::new(192, 0, 2, 1), port, 100);
+        let first_bytes = {
+            let builder = first.builder();
+            let messages = vec![
+                builder.templates(&[template(256)]),
+                builder.data(256, &[flow(80), flow(443)]),
+                builder.data(256, &[flow(22)]),
+            ];
+            sent(&mut first, messages)
+        };
+        let second_bytes = {
+            let builder = second.builder();
+            let messages = vec![
+                builder.templates(&[template(256), template(257)]),
+                builder.data(257, &[flow(53)]),
+                vec![0, 10, 0, 200],
+            ];
+            sent(&mut second, messages)
+        };
+
+        // the counters are updated after publishing
+        let published = || {
+            collector
+                .pipeline
+                .counters
+                .published
+                .load(Ordering::Relaxed)
+        };
+        assert!(collector.flows.wait_for(4, TIMEOUT).await);
+        let deadline = Instant::now() + TIMEOUT;
+        while published() < 4 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.json");
+        collector.pipeline.report().write(&path).unwrap();
+        let report: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+
+        let pipeline = &report["pipeline"];
+        assert_eq!(pipeline["datagrams"], 6);
+        assert_eq!(pipeline["decode_errors"], 1);
+        assert_eq!(pipeline["flows"], 4);
+        assert_eq!(pipeline["published"], 4);
+
+        let exporters = report["exporters"].as_array().unwrap();
+        assert_eq!(exporters.len(), 2);
+        let stats = |exporter: &ExporterSimulator| {
+            let addr = exporter.local_addr().unwrap().to_string();
+            exporters
+                .iter()
+                .find(|stats| stats["exporter"] == addr.as_str())
+                .unwrap()
+                .clone()
+        };
+
+        let stats_first = stats(&first);
+        assert_eq!(stats_first["packets"], 3);
+        assert_eq!(stats_first["bytes"], first_bytes);
+        assert_eq!(stats_first["messages"], 3);
+        assert_eq!(stats_first["parse_errors"], 0);
+        assert_eq!(stats_first["flows"], 3);
+        assert_eq!(stats_first["published"], 3);
+        assert_eq!(stats_first["templates"], 1);
+        assert!(stats_first["first_packet"].is_string());
+        assert!(stats_first["last_packet"].is_string());
+
+        let stats_second = stats(&second);
+        assert_eq!(stats_second["packets"], 3);
+        assert_eq!(stats_second["bytes"], second_bytes);
+        assert_eq!(stats_second["messages"], 2);
+        assert_eq!(stats_second["parse_errors"], 1);
+        assert_eq!(stats_second["flows"], 1);
+        assert_eq!(stats_second["published"], 1);
+        assert_eq!(stats_second["templates"], 2);
+
+        let table = fluss::stats::format_table(&collector.pipeline.exporter_stats());
+        assert_eq!(table.lines().count(), 3, "{}", table);
+    }
+
+    fn exporter(last_seen: Instant) -> Exporter {
+        Exporter {
+            session: Arc::new(Session::new(Either::Right(IpfixParser::new()))),
+            stats: Arc::default(),
+            clock: None,
+            duplicates: None,
+            last_seen,
+        }
+    }
+
+    #[test]
+    fn idle_exporters_are_selected_by_last_datagram() {
+        let start = Instant::now();
+        let timeout = Duration::from_secs(600);
+        let active: SocketAddr = "192.0.2.1:4739".parse().unwrap();
+        let restarted: SocketAddr = "192.0.2.1:50123".parse().unwrap();
+        let idle: SocketAddr = "192.0.2.2:4739".parse().unwrap();
+
+        let mut exporters = HashMap::new();
+        exporters.insert(active, exporter(start + Duration::from_secs(590)));
+        exporters.insert(restarted, exporter(start));
+        exporters.insert(idle, exporter(start + Duration::from_secs(1)));
+
+        let mut evicted = idle_exporters(&exporters, timeout, start + Duration::from_secs(601));
+        evicted.sort();
+        assert_eq!(evicted, vec![restarted, idle]);
+        assert!(idle_exporters(&exporters, timeout, start + Duration::from_secs(599)).is_empty());
+    }
+}
@@ -0,0 +1,322 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use fluss::ipfix::parser::{FieldSpecifier, Set};
+use fluss::ipfix::session::FieldParserBuilder;
+use fluss::ipfix::{Decoded, FieldParser, Packet, Session};
+use fluss::protocol::{Record, Value};
+use serde::Serialize;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("decode")
+        .about("decodes IPFIX messages from a file for troubleshooting")
+        .arg(
+            Arg::with_name("file")
+                .required(true)
+                .help("file containing one or more IPFIX messages"),
+        )
+        .arg(
+            Arg::with_name("hex")
+                .long("hex")
+                .takes_value(false)
+                .help("the file contains a hexdump instead of raw binary data"),
+        )
+        .arg(
+            Arg::with_name("fields")
+                .long("fields")
+                .takes_value(true)
+                .help("JSON file with additional field definitions"),
+        )
+        .arg(
+            Arg::with_name("plugin")
+                .long("plugin")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("ID=LIBRARY:SYMBOL")
+                .help("decodes a field with a function of a shared library, can be repeated"),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .takes_value(false)
+                .help("prints the decoded messages as json"),
+        )
+}
+
+pub fn run(app: &ArgMatches) -> anyhow::Result<()> {
+    let content = std::fs::read(app.value_of("file").unwrap())?;
+    let data = match app.is_present("hex") {
+        true => decode_hex(&content)?,
+        false => content,
+    };
+
+    let packets = fluss::ipfix::parse_all(&data)?;
+    let mut parser = FieldParser::builder().with_default_fields();
+    if let Some(fields) = app.value_of("fields") {
+        parser = parser.with_json(fields)?;
+    }
+    for plugin in app.values_of("plugin").into_iter().flatten() {
+        parser = with_plugin(parser, plugin)?;
+    }
+    let session = Session::new(parser.build());
+
+    // first pass only learns templates, data sets may precede the templates they reference
+    for packet in &packets {
+        session.parse(packet)?;
+    }
+
+    let messages = packets
+        .iter()
+        .map(|packet| Message::new(&session, packet))
+        .collect::<Vec<_>>();
+
+    match app.is_present("json") {
+        true => println!("{}", serde_json::to_string_pretty(&messages)?),
+        false => messages.iter().for_each(Message::print),
+    }
+
+    Ok(())
+}
+
+/// Registers a plugin given as `ID=LIBRARY:SYMBOL`.
+#[cfg(feature = "plugins")]
+fn with_plugin(parser: FieldParserBuilder, plugin: &str) -> anyhow::Result<FieldParserBuilder> {
+    let (id, function) = plugin
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("plugin {} is not ID=LIBRARY:SYMBOL", plugin))?;
+    let (path, symbol) = function
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow::anyhow!("plugin {} is not ID=LIBRARY:SYMBOL", plugin))?;
+    parser.with_plugin(id.parse()?, path, symbol)
+}
+
+#[cfg(not(feature = "plugins"))]
+fn with_plugin(_: FieldParserBuilder, _: &str) -> anyhow::Result<FieldParserBuilder> {
+    anyhow::bail!("fluss was built without the plugins feature")
+}
+
+fn decode_hex(input: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let digits = input
+        .iter()
+        .filter(|c| !c.is_ascii_whitespace())
+        .map(|&c| match c {
+            b'0'..=b'9' => Ok(c - b'0'),
+            b'a'..=b'f' => Ok(c - b'a' + 10),
+            b'A'..=b'F' => Ok(c - b'A' + 10),
+            _ => Err(anyhow::anyhow!("invalid hex character: {:?}", c as char)),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    if digits.len() % 2 != 0 {
+        anyhow::bail!("hexdump contains an odd number of digits");
+    }
+
+    Ok(digits.chunks(2).map(|c| c[0] << 4 | c[1]).collect())
+}
+
+#[derive(Serialize)]
+struct Message<'a> {
+    version: u16,
+    export_time: u32,
+    sequence_number: u32,
+    observation_domain_id: u32,
+    sets: Vec<DecodedSet<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum DecodedSet<'a> {
+    Template {
+        templates: Vec<Template<'a>>,
+    },
+    Options {
+        templates: Vec<Template<'a>>,
+    },
+    Data {
+        id: u16,
+        records: Option<Vec<Vec<Field<'a>>>>,
+    },
+}
+
+#[derive(Serialize)]
+struct Template<'a> {
+    id: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope_field_count: Option<u16>,
+    fields: Vec<TemplateField<'a>>,
+}
+
+#[derive(Serialize)]
+struct TemplateField<'a> {
+    id: u16,
+    name: Option<&'a str>,
+    length: u16,
+    enterprise_id: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct Field<'a> {
+    id: u16,
+    name: Option<&'a str>,
+    value: Value<'a>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    scope: bool,
+}
+
+impl<'a> Message<'a> {
+    fn new(session: &'a Session<FieldParser>, packet: &'a Packet) -> Self {
+        let parser = session.get_parser();
+
+        let sets = packet
+            .sets
+            .iter()
+            .map(|set| match set {
+                Set::TemplateSet(records) => DecodedSet::Template {
+                    templates: records
+                        .iter()
+                        .map(|record| Template {
+                            id: record.id,
+                            scope_field_count: None,
+                            fields: template_fields(parser, &record.fields),
+                        })
+                        .collect(),
+                },
+                Set::OptionsTemplateSet(records) => DecodedSet::Options {
+                    templates: records
+                        .iter()
+                        .map(|record| Template {
+                            id: record.id,
+                            scope_field_count: Some(record.scope_field_count),
+                            fields: template_fields(parser, &record.fields),
+                        })
+                        .collect(),
+                },
+                Set::DataSet(data) => {
+                    let records = session.parse_data_set(packet.observation_domain_id, data);
+                    DecodedSet::Data {
+                        id: data.id,
+                        records: match records.is_empty() {
+                            true => None,
+                            false => Some(
+                                records
+                                    .into_iter()
+                                    .map(|decoded| match decoded {
+                                        Decoded::Flow(record_set) => {
+                                            fields(parser, record_set.records, false)
+                                        }
+                                        Decoded::Options(record) => {
+                                            let mut scope = fields(parser, record.scope, true);
+                                            scope.extend(fields(parser, record.options, false));
+                                            scope
+                                        }
+                                    })
+                                    .collect(),
+                            ),
+                        },
+                    }
+                }
+            })
+            .collect();
+
+        Self {
+            version: packet.version,
+            export_time: packet.export_time,
+            sequence_number: packet.sequence_number,
+            observation_domain_id: packet.observation_domain_id,
+            sets,
+        }
+    }
+
+    fn print(&self) {
+        println!(
+            "message version={} export_time={} sequence_number={} observation_domain_id={}",
+            self.version, self.export_time, self.sequence_number, self.observation_domain_id
+        );
+
+        for set in &self.sets {
+            match set {
+                DecodedSet::Template { templates } => {
+                    println!("  template set");
+                    print_templates(templates);
+                }
+                DecodedSet::Options { templates } => {
+                    println!("  options template set");
+                    print_templates(templates);
+                }
+                DecodedSet::Data { id, records: None } => {
+                    println!("  data set {} (no template)", id)
+                }
+                DecodedSet::Data {
+                    id,
+                    records: Some(records),
+                } => {
+                    println!("  data set {} ({} records)", id, records.len());
+                    for (i, record) in records.iter().enumerate() {
+                        println!("    record {}", i);
+                        for field in record {
+                            println!(
+                                "      {}:{} = {}{}",
+                                field.id,
+                                field.name.unwrap_or("<???>"),
+                                field.value,
+                                if field.scope { " (scope)" } else { "" }
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn print_templates(templates: &[Template]) {
+    for template in templates {
+        print!(
+            "    template {} ({} fields",
+            template.id,
+            template.fields.len()
+        );
+        match template.scope_field_count {
+            Some(scope_field_count) => println!(", {} scope fields)", scope_field_count),
+            None => println!(")"),
+        }
+        for field in &template.fields {
+            print!(
+                "      {}:{} length={}",
+                field.id,
+                field.name.unwrap_or("<???>"),
+                field.length
+            );
+            match field.enterprise_id {
+                Some(enterprise_id) => println!(" enterprise_id={}", enterprise_id),
+                None => println!(),
+            }
+        }
+    }
+}
+
+fn template_fields<'a>(
+    parser: &'a FieldParser,
+    fields: &[FieldSpecifier],
+) -> Vec<TemplateField<'a>> {
+    fields
+        .iter()
+        .map(|field| TemplateField {
+            id: field.id,
+            name: parser.field_name(field.id),
+            length: field.length,
+            enterprise_id: field.enterprise_id,
+        })
+        .collect()
+}
+
+fn fields<'a>(parser: &'a FieldParser, records: Vec<Record<'a>>, scope: bool) -> Vec<Field<'a>> {
+    records
+        .into_iter()
+        .map(|record| Field {
+            id: record.id,
+            name: parser.field_name(record.id),
+            value: record.value,
+            scope,
+        })
+        .collect()
+}
@@ -0,0 +1,6 @@
+pub mod bench;
+pub mod collect;
+pub mod decode;
+pub mod replay_dlq;
+pub mod stats;
+pub mod templates;
@@ -0,0 +1,68 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use fluss::publish::elastic::DeadLetter;
+use fluss::publish::ElasticPublisher;
+use std::io::{BufRead, BufReader, Write};
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("replay-dlq")
+        .about("publishes the flows of a dead-letter file to elastic again")
+        .arg(
+            Arg::with_name("file")
+                .required(true)
+                .help("dead-letter file written by the elastic publisher"),
+        )
+        .arg(
+            Arg::with_name("failed")
+                .long("failed")
+                .takes_value(true)
+                .help("file for flows which failed again, defaults to <file>.failed"),
+        )
+}
+
+pub fn run(app: &ArgMatches) -> anyhow::Result<()> {
+    tokio::runtime::Runtime::new()?.block_on(replay(app))
+}
+
+async fn replay(app: &ArgMatches<'_>) -> anyhow::Result<()> {
+    let path = app.value_of("file").unwrap();
+    let failed_path = match app.value_of("failed") {
+        Some(failed) => failed.to_owned(),
+        None => format!("{}.failed", path),
+    };
+
+    let publisher = ElasticPublisher::new(elasticsearch::Elasticsearch::default());
+    let reader = BufReader::new(std::fs::File::open(path)?);
+
+    let mut replayed = 0;
+    let mut failed = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let dead_letter: DeadLetter = serde_json::from_str(&line)?;
+        match publisher.replay(&dead_letter).await {
+            Ok(()) => replayed += 1,
+            Err(err) => {
+                tracing::warn!(error = %err, index = dead_letter.index.as_str(), "replay failed");
+                failed.push(line);
+            }
+        }
+    }
+
+    tracing::info!(replayed, failed = failed.len(), "replayed dead-letter file");
+    if !failed.is_empty() {
+        let mut file = std::fs::File::create(&failed_path)?;
+        failed
+            .iter()
+            .try_for_each(|line| writeln!(file, "{}", line))?;
+        anyhow::bail!(
+            "{} flows could not be replayed, written to {}",
+            failed.len(),
+            failed_path
+        );
+    }
+
+    Ok(())
+}
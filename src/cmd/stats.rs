@@ -0,0 +1,124 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use fluss::control::{Request, Response};
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("stats")
+        .about("prints the pipeline counters of a running collector")
+        .arg(
+            Arg::with_name("control-socket")
+                .long("control-socket")
+                .default_value(fluss::control::DEFAULT_SOCKET)
+                .help("control socket of the collector"),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .takes_value(false)
+                .help("prints the counters as json"),
+        )
+}
+
+pub fn run(app: &ArgMatches) -> anyhow::Result<()> {
+    let path = app.value_of("control-socket").unwrap();
+    let response =
+        tokio::runtime::Runtime::new()?.block_on(fluss::control::request(path, &Request::Stats))?;
+
+    let (stats, exporters, routes, publishers, histograms, tenants) = match response {
+        Response::Stats {
+            stats,
+            exporters,
+            routes,
+            publishers,
+            histograms,
+            tenants,
+        } => (stats, exporters, routes, publishers, histograms, tenants),
+        Response::Error { message } => anyhow::bail!("collector returned an error: {}", message),
+        response => anyhow::bail!("unexpected response: {:?}", response),
+    };
+
+    match app.is_present("json") {
+        true => {
+            // the exporters are added to the pipeline counters for compatibility
+            let mut output = serde_json::to_value(&stats)?;
+            output["exporters"] = serde_json::to_value(&exporters)?;
+            if !routes.is_empty() {
+                output["routes"] = serde_json::to_value(&routes)?;
+                output["publishers"] = serde_json::to_value(&publishers)?;
+            }
+            output["histograms"] = serde_json::to_value(&histograms)?;
+            if !tenants.is_empty() {
+                output["tenants"] = serde_json::to_value(&tenants)?;
+            }
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+        false => {
+            println!("datagrams           {}", stats.datagrams);
+            println!("decode_errors       {}", stats.decode_errors);
+            println!("flows               {}", stats.flows);
+            println!("options_records     {}", stats.options_records);
+            println!("sequence_gaps       {}", stats.sequence_gaps);
+            println!("duplicate_messages  {}", stats.duplicate_messages);
+            println!("denied_datagrams    {}", stats.denied_datagrams);
+            println!("rejected_templates  {}", stats.rejected_templates);
+            println!("flapping_templates  {}", stats.flapping_templates);
+            println!("rollup_keys         {}", stats.rollup_keys);
+            println!("published           {}", stats.published);
+            println!("publish_errors      {}", stats.publish_errors);
+
+            if !exporters.is_empty() {
+                println!();
+                println!("{}", fluss::stats::format_table(&exporters));
+            }
+
+            if !routes.is_empty() {
+                println!();
+                println!("{:<10} {:>12}", "route", "flows");
+                for route in &routes {
+                    // the default route is always the last one
+                    let name = match route.route + 1 == routes.len() {
+                        true => "default".to_owned(),
+                        false => route.route.to_string(),
+                    };
+                    println!("{:<10} {:>12}", name, route.flows);
+                }
+
+                println!();
+                println!("{:<20} {:>12} {:>8}", "publisher", "published", "errors");
+                for publisher in &publishers {
+                    println!(
+                        "{:<20} {:>12} {:>8}",
+                        publisher.name, publisher.published, publisher.errors
+                    );
+                }
+            }
+
+            if !tenants.is_empty() {
+                println!();
+                println!("{:<20} {:>12}", "tenant", "documents");
+                for tenant in &tenants {
+                    println!(
+                        "{:<20} {:>12}",
+                        tenant.tenant.as_deref().unwrap_or("-"),
+                        tenant.documents
+                    );
+                }
+            }
+
+            for (name, buckets) in [
+                ("bytes", &histograms.bytes),
+                ("duration_ms", &histograms.duration_ms),
+            ] {
+                if buckets.is_empty() {
+                    continue;
+                }
+                println!();
+                println!("{:>20} {:>12}", format!("{} <=", name), "flows");
+                for bucket in buckets.iter().filter(|bucket| bucket.count > 0) {
+                    println!("{:>20} {:>12}", bucket.le, bucket.count);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use fluss::control::{Request, Response};
+use fluss::ipfix::FieldParser;
+use std::time::SystemTime;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("templates")
+        .about("lists the templates learned by a running collector")
+        .arg(
+            Arg::with_name("control-socket")
+                .long("control-socket")
+                .default_value(fluss::control::DEFAULT_SOCKET)
+                .help("control socket of the collector"),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .takes_value(false)
+                .help("prints the templates as json"),
+        )
+}
+
+pub fn run(app: &ArgMatches) -> anyhow::Result<()> {
+    let path = app.value_of("control-socket").unwrap();
+    let response = tokio::runtime::Runtime::new()?
+        .block_on(fluss::control::request(path, &Request::Templates))?;
+
+    let mut exporters = match response {
+        Response::Templates { exporters } => exporters,
+        Response::Error { message } => anyhow::bail!("collector returned an error: {}", message),
+        response => anyhow::bail!("unexpected response: {:?}", response),
+    };
+
+    if app.is_present("json") {
+        println!("{}", serde_json::to_string_pretty(&exporters)?);
+        return Ok(());
+    }
+
+    let parser = FieldParser::builder().with_default_fields().build();
+    let now = SystemTime::now();
+
+    exporters.sort_by_key(|exporter| exporter.exporter);
+    for exporter in &mut exporters {
+        println!("exporter {}", exporter.exporter);
+
+        exporter
+            .templates
+            .sort_by_key(|template| (template.domain_id, template.id));
+        for template in &exporter.templates {
+            let last_seen = now
+                .duration_since(template.last_seen)
+                .unwrap_or_default()
+                .as_secs();
+
+            print!(
+                "  odid {} template {} ({} fields",
+                template.domain_id,
+                template.id,
+                template.fields.len()
+            );
+            if template.scope_field_count > 0 {
+                print!(", {} scope fields", template.scope_field_count);
+            }
+            print!(
+                ") version={} records={} last_seen={}s ago",
+                template.version, template.records, last_seen
+            );
+            match template.suspect_records {
+                0 => println!(),
+                suspect => println!(" suspect={}", suspect),
+            }
+
+            for field in &template.fields {
+                print!(
+                    "    {}:{} length={}",
+                    field.id,
+                    parser.field_name(field.id).unwrap_or("<???>"),
+                    field.length
+                );
+                match field.enterprise_id {
+                    Some(enterprise_id) => println!(" enterprise_id={}", enterprise_id),
+                    None => println!(),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
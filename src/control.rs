@@ -0,0 +1,328 @@
+//! Control interface of a running collector.
+//!
+//! Requests and responses are exchanged as newline delimited JSON over a unix domain socket.
+
+use crate::enrich::class::FlowHistograms;
+use crate::ipfix::TemplateStats;
+use crate::publish::elastic::TenantStats;
+use crate::publish::route::{PublisherStats, RouteStats};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+pub const DEFAULT_SOCKET: &str = "/tmp/fluss.sock";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum Request {
+    Templates,
+    Stats,
+    /// Reloads the settings files, like SIGHUP.
+    Reload,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Response {
+    Templates {
+        exporters: Vec<ExporterTemplates>,
+    },
+    Stats {
+        stats: PipelineStats,
+        #[serde(default)]
+        exporters: Vec<ExporterStats>,
+        /// Counters of the routes of the routes publisher, the default route is the last one.
+        #[serde(default)]
+        routes: Vec<RouteStats>,
+        #[serde(default)]
+        publishers: Vec<PublisherStats>,
+        /// Distributions of the sizes and durations of all flows.
+        #[serde(default)]
+        histograms: FlowHistograms,
+        /// Documents indexed per tenant by the elastic publisher.
+        #[serde(default)]
+        tenants: Vec<TenantStats>,
+    },
+    Reloaded {
+        applied: Vec<String>,
+        restart_required: Vec<String>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// All templates learned from a single exporter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExporterTemplates {
+    pub exporter: SocketAddr,
+    pub templates: Vec<TemplateStats>,
+}
+
+/// Counters of the collect pipeline since the collector was started.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PipelineStats {
+    pub datagrams: u64,
+    pub decode_errors: u64,
+    pub flows: u64,
+    pub options_records: u64,
+    pub sequence_gaps: u64,
+    #[serde(default)]
+    pub duplicate_messages: u64,
+    #[serde(default)]
+    pub denied_datagrams: u64,
+    #[serde(default)]
+    pub rejected_templates: u64,
+    /// Templates producing suspect records shortly after their layout changed.
+    #[serde(default)]
+    pub flapping_templates: u64,
+    /// Distinct keys of the current rollup window.
+    #[serde(default)]
+    pub rollup_keys: u64,
+    #[serde(default)]
+    pub published: u64,
+    pub publish_errors: u64,
+}
+
+/// Counters of a single exporter since the collector was started.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExporterStats {
+    pub exporter: SocketAddr,
+    /// Datagrams received from the exporter.
+    pub packets: u64,
+    /// Bytes of all received datagrams.
+    pub bytes: u64,
+    /// Datagrams successfully parsed as IPFIX messages.
+    pub messages: u64,
+    pub parse_errors: u64,
+    pub flows: u64,
+    pub published: u64,
+    pub first_packet: Option<DateTime<Utc>>,
+    pub last_packet: Option<DateTime<Utc>>,
+    /// Number of currently known templates.
+    pub templates: usize,
+}
+
+/// Answers requests on the unix domain socket `path` with `handler`.
+///
+/// A stale socket left behind by a previous instance is replaced.
+pub async fn serve<F>(path: impl AsRef<Path>, handler: F) -> anyhow::Result<()>
+where
+    F: Fn(Request) -> Response + Send + Sync + 'static,
+{
+    let path = path.as_ref();
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            anyhow::bail!(
+                "control socket {} exists and is not a socket",
+                path.display()
+            );
+        }
+        std::fs::remove_file(path)?;
+    }
+
+    let listener = UnixListener::bind(path)?;
+    let handler = Arc::new(handler);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let handler = Arc::clone(&handler);
+        tokio::spawn(async move {
+            if let Err(err) = handle(stream, &*handler).await {
+                tracing::warn!(error = %err, "control connection failed");
+            }
+        });
+    }
+}
+
+async fn handle<F>(stream: UnixStream, handler: &F) -> anyhow::Result<()>
+where
+    F: Fn(Request) -> Response,
+{
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let response = match serde_json::from_str(&line) {
+            Ok(request) => handler(request),
+            Err(err) => Response::Error {
+                message: err.to_string(),
+            },
+        };
+
+        let mut response = serde_json::to_vec(&response)?;
+        response.push(b'\n');
+        writer.write_all(&response).await?;
+    }
+
+    Ok(())
+}
+
+/// Sends a single request to the collector listening on `path`.
+pub async fn request(path: impl AsRef<Path>, request: &Request) -> anyhow::Result<Response> {
+    let stream = UnixStream::connect(path).await?;
+    let (reader, mut writer) = stream.into_split();
+
+    let mut line = serde_json::to_vec(request)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+
+    match BufReader::new(reader).lines().next_line().await? {
+        Some(line) => Ok(serde_json::from_str(&line)?),
+        None => anyhow::bail!("control socket closed without a response"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipfix::parser::{parse, TemplateRecord};
+    use crate::ipfix::Session;
+    use crate::produce::IpfixParser;
+    use crate::testing::{field, DataRecord, MessageBuilder};
+    use std::time::Duration;
+
+    const EXPORTER: &str = "192.0.2.1:4739";
+
+    /// Serves the templates of a session which decoded two records.
+    fn templates() -> Vec<ExporterTemplates> {
+        let template = TemplateRecord {
+            id: 256,
+            fields: vec![field(8, 4), field(12, 4), field(1, 8)],
+        };
+        let record = DataRecord::new()
+            .addr([10, 0, 0, 1].into())
+            .addr([10, 0, 0, 2].into())
+            .u64(1500);
+
+        let session = Session::new(IpfixParser::new());
+        let mut builder = MessageBuilder::new(7);
+        let templates = builder.templates(std::slice::from_ref(&template));
+        session.parse(&parse(&templates).unwrap()).unwrap();
+        let data = builder.data(256, &[record.clone(), record]);
+        session.parse(&parse(&data).unwrap()).unwrap();
+
+        vec![ExporterTemplates {
+            exporter: EXPORTER.parse().unwrap(),
+            templates: session.template_stats(),
+        }]
+    }
+
+    async fn start(path: &Path) {
+        let exporters = templates();
+        let server = serve(path.to_owned(), move |request| match request {
+            Request::Templates => Response::Templates {
+                exporters: exporters.clone(),
+            },
+            Request::Stats => Response::Stats {
+                stats: PipelineStats {
+                    datagrams: 2,
+                    flows: 2,
+                    ..PipelineStats::default()
+                },
+                exporters: Vec::new(),
+                routes: Vec::new(),
+                publishers: Vec::new(),
+                histograms: FlowHistograms::default(),
+                tenants: Vec::new(),
+            },
+            Request::Reload => Response::Error {
+                message: "not supported".to_owned(),
+            },
+        });
+        tokio::spawn(async move { server.await.unwrap() });
+
+        // the socket is bound once the server task ran
+        for _ in 0..100 {
+            if UnixStream::connect(path).await.is_ok() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("control socket {} is not served", path.display());
+    }
+
+    #[tokio::test]
+    async fn templates_request() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fluss.sock");
+        start(&path).await;
+
+        let exporters = match request(&path, &Request::Templates).await.unwrap() {
+            Response::Templates { exporters } => exporters,
+            response => panic!("unexpected response {:?}", response),
+        };
+        assert_eq!(exporters.len(), 1);
+        assert_eq!(exporters[0].exporter, EXPORTER.parse().unwrap());
+
+        let templates = &exporters[0].templates;
+        assert_eq!(templates.len(), 1);
+        assert_eq!((templates[0].domain_id, templates[0].id), (7, 256));
+        assert_eq!(templates[0].fields.len(), 3);
+        assert_eq!(templates[0].records, 2);
+    }
+
+    #[tokio::test]
+    async fn stats_request() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fluss.sock");
+        start(&path).await;
+
+        match request(&path, &Request::Stats).await.unwrap() {
+            Response::Stats { stats, .. } => assert_eq!((stats.datagrams, stats.flows), (2, 2)),
+            response => panic!("unexpected response {:?}", response),
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_request() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fluss.sock");
+        start(&path).await;
+
+        let stream = UnixStream::connect(&path).await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        writer.write_all(b"{\"command\":\"nope\"}\n").await.unwrap();
+        let line = BufReader::new(reader)
+            .lines()
+            .next_line()
+            .await
+            .unwrap()
+            .unwrap();
+
+        match serde_json::from_str(&line).unwrap() {
+            Response::Error { message } => assert!(message.contains("nope"), "{}", message),
+            response => panic!("unexpected response {:?}", response),
+        }
+    }
+
+    #[tokio::test]
+    async fn stale_socket_is_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fluss.sock");
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        start(&path).await;
+
+        assert!(request(&path, &Request::Stats).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn other_files_are_not_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fluss.sock");
+        std::fs::write(&path, "data").unwrap();
+
+        let err = serve(&path, |_| Response::Error {
+            message: String::new(),
+        })
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("not a socket"), "{}", err);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "data");
+    }
+}
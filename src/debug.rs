@@ -0,0 +1,186 @@
+//! Capture of received datagrams for analysis with Wireshark.
+
+use anyhow::Context as _;
+use chrono::{DateTime, Utc};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const LINKTYPE_ETHERNET: u16 = 1;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86DD;
+const IPPROTO_UDP: u8 = 17;
+
+/// Writes received datagrams to a pcapng file.
+///
+/// Only the UDP payload is received, every datagram is wrapped into made up
+/// Ethernet, IP and UDP headers with the addresses of the exporter and the
+/// collector, so Wireshark dissects the IPFIX messages. IPv4 mapped addresses
+/// are written as IPv4.
+///
+/// Writes are buffered, call [`PcapCapture::flush`] before exiting.
+pub struct PcapCapture {
+    writer: BufWriter<File>,
+}
+
+impl PcapCapture {
+    /// Creates the file, an existing file is truncated.
+    pub fn new(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .with_context(|| format!("failed to create capture {}", path.display()))?;
+
+        let mut capture = Self {
+            writer: BufWriter::new(file),
+        };
+        capture.write_header()?;
+        Ok(capture)
+    }
+
+    /// Writes the payload of a datagram sent from `source` to `destination`.
+    pub fn write(
+        &mut self,
+        source: SocketAddr,
+        destination: SocketAddr,
+        received: DateTime<Utc>,
+        payload: &[u8],
+    ) -> io::Result<()> {
+        let packet = encapsulate(source, destination, payload);
+        let padding = (4 - packet.len() % 4) % 4;
+        let micros = received.timestamp_micros() as u64;
+
+        self.write_u32(ENHANCED_PACKET_BLOCK)?;
+        self.write_u32((32 + packet.len() + padding) as u32)?;
+        self.write_u32(0)?; // interface
+        self.write_u32((micros >> 32) as u32)?;
+        self.write_u32(micros as u32)?;
+        self.write_u32(packet.len() as u32)?; // captured length
+        self.write_u32(packet.len() as u32)?; // original length
+        self.writer.write_all(&packet)?;
+        self.writer.write_all(&[0; 3][..padding])?;
+        self.write_u32((32 + packet.len() + padding) as u32)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        self.write_u32(SECTION_HEADER_BLOCK)?;
+        self.write_u32(28)?;
+        self.write_u32(BYTE_ORDER_MAGIC)?;
+        self.write_u16(1)?; // major version
+        self.write_u16(0)?; // minor version
+        self.writer.write_all(&(-1i64).to_le_bytes())?; // unknown section length
+        self.write_u32(28)?;
+
+        // timestamps are in microseconds, the default resolution
+        self.write_u32(INTERFACE_DESCRIPTION_BLOCK)?;
+        self.write_u32(20)?;
+        self.write_u16(LINKTYPE_ETHERNET)?;
+        self.write_u16(0)?; // reserved
+        self.write_u32(0)?; // no snapshot length
+        self.write_u32(20)
+    }
+
+    fn write_u16(&mut self, value: u16) -> io::Result<()> {
+        self.writer.write_all(&value.to_le_bytes())
+    }
+
+    fn write_u32(&mut self, value: u32) -> io::Result<()> {
+        self.writer.write_all(&value.to_le_bytes())
+    }
+}
+
+/// Builds an Ethernet frame with an IP and UDP header around `payload`.
+fn encapsulate(source: SocketAddr, destination: SocketAddr, payload: &[u8]) -> Vec<u8> {
+    let udp_length = 8 + payload.len();
+    let mut packet = Vec::with_capacity(14 + 40 + udp_length);
+
+    // zero MAC addresses, only the type matters
+    packet.extend_from_slice(&[0; 12]);
+
+    let (src, dst) = (canonical(source.ip()), canonical(destination.ip()));
+    let pseudo_header = match (src, dst) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            packet.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+            let mut header = [0; 20];
+            header[0] = 0x45; // version 4, 5 words
+            header[2..4].copy_from_slice(&((20 + udp_length) as u16).to_be_bytes());
+            header[8] = 64; // time to live
+            header[9] = IPPROTO_UDP;
+            header[12..16].copy_from_slice(&src.octets());
+            header[16..20].copy_from_slice(&dst.octets());
+            let checksum = !fold(sum(&header));
+            header[10..12].copy_from_slice(&checksum.to_be_bytes());
+            packet.extend_from_slice(&header);
+
+            sum(&src.octets()) + sum(&dst.octets())
+        }
+        (src, dst) => {
+            let src = to_ipv6(src);
+            let dst = to_ipv6(dst);
+            packet.extend_from_slice(&ETHERTYPE_IPV6.to_be_bytes());
+            let mut header = [0; 40];
+            header[0] = 0x60; // version 6
+            header[4..6].copy_from_slice(&(udp_length as u16).to_be_bytes());
+            header[6] = IPPROTO_UDP;
+            header[7] = 64; // hop limit
+            header[8..24].copy_from_slice(&src.octets());
+            header[24..40].copy_from_slice(&dst.octets());
+            packet.extend_from_slice(&header);
+
+            sum(&src.octets()) + sum(&dst.octets())
+        }
+    };
+
+    let mut header = [0; 8];
+    header[0..2].copy_from_slice(&source.port().to_be_bytes());
+    header[2..4].copy_from_slice(&destination.port().to_be_bytes());
+    header[4..6].copy_from_slice(&(udp_length as u16).to_be_bytes());
+    let checksum = !fold(
+        pseudo_header + u64::from(IPPROTO_UDP) + udp_length as u64 + sum(&header) + sum(payload),
+    );
+    // a zero checksum means no checksum, it is sent as all ones
+    let checksum = if checksum == 0 { 0xFFFF } else { checksum };
+    header[6..8].copy_from_slice(&checksum.to_be_bytes());
+    packet.extend_from_slice(&header);
+    packet.extend_from_slice(payload);
+
+    packet
+}
+
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(ip) => ip.to_ipv4_mapped().map_or(IpAddr::V6(ip), IpAddr::V4),
+        ip => ip,
+    }
+}
+
+fn to_ipv6(ip: IpAddr) -> std::net::Ipv6Addr {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    }
+}
+
+/// Sums the big endian 16 bit words of `data` for the internet checksum.
+fn sum(data: &[u8]) -> u64 {
+    data.chunks(2)
+        .map(|word| u64::from(u16::from_be_bytes([word[0], *word.get(1).unwrap_or(&0)])))
+        .sum()
+}
+
+/// Folds the carries of a sum into 16 bits.
+fn fold(mut sum: u64) -> u16 {
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    sum as u16
+}
@@ -0,0 +1,187 @@
+//! Detection of IPFIX messages which were received more than once.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+/// Messages are forgotten after this age by default.
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(300);
+
+/// Remembers the last messages of an exporter to detect duplicates,
+/// e.g. of exporters sending to the collector twice or replayed captures.
+///
+/// A message is identified by its observation domain, sequence number,
+/// export time and a hash of the whole message. The sequence number alone
+/// counts data records and repeats for messages without data records and
+/// after it wrapped around, the export time and hash tell those apart.
+///
+/// At most `window` messages per observation domain are remembered, each for
+/// at most the maximum age, lookups take constant time.
+#[derive(Debug)]
+pub struct DuplicateMessages {
+    window: usize,
+    max_age: Duration,
+    domains: HashMap<u32, Window>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+struct MessageId {
+    sequence_number: u32,
+    export_time: u32,
+    digest: u64,
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    // oldest message first, evicted from the set in the same order
+    order: VecDeque<(Instant, MessageId)>,
+    seen: HashSet<MessageId>,
+}
+
+impl DuplicateMessages {
+    /// Remembers the last `window` messages of every observation domain.
+    pub fn new(window: usize) -> Self {
+        Self {
+            window: window.max(1),
+            max_age: DEFAULT_MAX_AGE,
+            domains: HashMap::new(),
+        }
+    }
+
+    /// Forgets messages after `max_age`, even if the window is not full.
+    pub fn set_max_age(&mut self, max_age: Duration) {
+        self.max_age = max_age;
+    }
+
+    /// Records a message and returns whether it was seen before.
+    ///
+    /// `message` are the bytes of the whole message.
+    pub fn is_duplicate(
+        &mut self,
+        domain_id: u32,
+        sequence_number: u32,
+        export_time: u32,
+        message: &[u8],
+    ) -> bool {
+        let mut hasher = DefaultHasher::new();
+        message.hash(&mut hasher);
+        let id = MessageId {
+            sequence_number,
+            export_time,
+            digest: hasher.finish(),
+        };
+
+        let now = Instant::now();
+        let window = self.domains.entry(domain_id).or_default();
+        while let Some(&(seen, oldest)) = window.order.front() {
+            if now.duration_since(seen) < self.max_age {
+                break;
+            }
+            window.order.pop_front();
+            window.seen.remove(&oldest);
+        }
+
+        // a full window still knows its oldest message, only new messages evict it
+        if window.seen.contains(&id) {
+            return true;
+        }
+        if window.order.len() >= self.window {
+            if let Some((_, oldest)) = window.order.pop_front() {
+                window.seen.remove(&oldest);
+            }
+        }
+        window.seen.insert(id);
+        window.order.push_back((now, id));
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(sequence_number: u32, export_time: u32) -> Vec<u8> {
+        let mut message = vec![0, 10, 0, 32];
+        message.extend_from_slice(&export_time.to_be_bytes());
+        message.extend_from_slice(&sequence_number.to_be_bytes());
+        message.extend_from_slice(&[0, 0, 0, 1]);
+        message.extend_from_slice(&[1; 16]);
+        message
+    }
+
+    fn check(duplicates: &mut DuplicateMessages, sequence_number: u32, export_time: u32) -> bool {
+        let message = message(sequence_number, export_time);
+        duplicates.is_duplicate(1, sequence_number, export_time, &message)
+    }
+
+    #[test]
+    fn resent_messages_are_duplicates() {
+        let mut duplicates = DuplicateMessages::new(16);
+        assert!(!check(&mut duplicates, 10, 1_700_000_000));
+        assert!(check(&mut duplicates, 10, 1_700_000_000));
+        assert!(check(&mut duplicates, 10, 1_700_000_000));
+        assert!(!check(&mut duplicates, 11, 1_700_000_000));
+
+        // other observation domains have their own sequence numbers
+        let message = message(10, 1_700_000_000);
+        assert!(!duplicates.is_duplicate(2, 10, 1_700_000_000, &message));
+    }
+
+    #[test]
+    fn oldest_message_of_a_full_window_is_a_duplicate() {
+        let mut duplicates = DuplicateMessages::new(4);
+        for sequence_number in 0..4 {
+            assert!(!check(&mut duplicates, sequence_number, 1_700_000_000));
+        }
+        assert!(check(&mut duplicates, 0, 1_700_000_000));
+        assert!(check(&mut duplicates, 3, 1_700_000_000));
+
+        // a new message evicts the oldest one
+        assert!(!check(&mut duplicates, 4, 1_700_000_000));
+        assert!(!check(&mut duplicates, 0, 1_700_000_000));
+        assert!(check(&mut duplicates, 4, 1_700_000_000));
+    }
+
+    #[test]
+    fn same_sequence_number_of_other_messages() {
+        let mut duplicates = DuplicateMessages::new(16);
+        assert!(!check(&mut duplicates, 10, 1_700_000_000));
+        // messages without data records do not advance the sequence number
+        assert!(!check(&mut duplicates, 10, 1_700_000_001));
+
+        let mut other = message(10, 1_700_000_000);
+        other[20] = 2;
+        assert!(!duplicates.is_duplicate(1, 10, 1_700_000_000, &other));
+    }
+
+    #[test]
+    fn sequence_numbers_wrap_around() {
+        let mut duplicates = DuplicateMessages::new(1000);
+        let mut sequence_number = u32::MAX - 500;
+        for i in 0..1000 {
+            assert!(
+                !check(&mut duplicates, sequence_number, 1_700_000_000 + i / 10),
+                "{} is no duplicate",
+                sequence_number
+            );
+            sequence_number = sequence_number.wrapping_add(1);
+        }
+        assert_eq!(sequence_number, 499);
+
+        // a wrapped sequence number is seen again with a later export time
+        let mut duplicates = DuplicateMessages::new(1000);
+        assert!(!check(&mut duplicates, 7, 1_700_000_000));
+        assert!(!check(&mut duplicates, u32::MAX, 1_700_000_100));
+        assert!(!check(&mut duplicates, 7, 1_700_000_200));
+        assert!(check(&mut duplicates, u32::MAX, 1_700_000_100));
+    }
+
+    #[test]
+    fn messages_are_forgotten_after_max_age() {
+        let mut duplicates = DuplicateMessages::new(16);
+        duplicates.set_max_age(Duration::ZERO);
+        assert!(!check(&mut duplicates, 10, 1_700_000_000));
+        assert!(!check(&mut duplicates, 10, 1_700_000_000));
+    }
+}
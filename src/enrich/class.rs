@@ -0,0 +1,271 @@
+use crate::fluss::{FlowClass, Fluss};
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Flows of at least 100 MB are elephants by default.
+pub const DEFAULT_ELEPHANT_BYTES: u64 = 100_000_000;
+/// Smaller flows active for at least a minute are long-lived by default.
+pub const DEFAULT_ELEPHANT_DURATION: Duration = Duration::from_secs(60);
+
+/// Approximate distribution of values in power of two buckets.
+///
+/// Recording a value is a single relaxed atomic increment, the histogram
+/// can be updated by all decode workers without locking.
+#[derive(Debug)]
+pub struct Log2Histogram {
+    // bucket 0 counts zeros, bucket i the values in [2^(i-1), 2^i)
+    buckets: [AtomicU64; 65],
+}
+
+/// Values of at most `le` since the previous bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistogramBucket {
+    pub le: u64,
+    pub count: u64,
+}
+
+impl Log2Histogram {
+    pub fn new() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+
+    pub fn record(&self, value: u64) {
+        let bucket = (u64::BITS - value.leading_zeros()) as usize;
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The buckets from the smallest up to the largest recorded value.
+    pub fn snapshot(&self) -> Vec<HistogramBucket> {
+        let mut buckets = self
+            .buckets
+            .iter()
+            .enumerate()
+            .map(|(i, count)| HistogramBucket {
+                le: match i {
+                    0 => 0,
+                    i => u64::MAX >> (u64::BITS as usize - i),
+                },
+                count: count.load(Ordering::Relaxed),
+            })
+            .collect::<Vec<_>>();
+        let len = buckets.iter().rposition(|bucket| bucket.count > 0);
+        buckets.truncate(len.map_or(0, |len| len + 1));
+        buckets
+    }
+}
+
+impl Default for Log2Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Distributions of the sizes and durations of classified flows.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlowHistograms {
+    /// Bytes per flow, scaled up by the sampling interval.
+    pub bytes: Vec<HistogramBucket>,
+    pub duration_ms: Vec<HistogramBucket>,
+}
+
+/// Sets the [`FlowClass`] of flows and records the distributions of flow
+/// sizes and durations, to tune the thresholds from observed traffic.
+#[derive(Debug)]
+pub struct FlowClassifier {
+    elephant_bytes: u64,
+    elephant_duration: Duration,
+    bytes: Log2Histogram,
+    durations: Log2Histogram,
+}
+
+impl FlowClassifier {
+    pub fn new() -> Self {
+        Self {
+            elephant_bytes: DEFAULT_ELEPHANT_BYTES,
+            elephant_duration: DEFAULT_ELEPHANT_DURATION,
+            bytes: Log2Histogram::new(),
+            durations: Log2Histogram::new(),
+        }
+    }
+
+    pub fn set_elephant_bytes(&mut self, bytes: u64) {
+        self.elephant_bytes = bytes;
+    }
+
+    pub fn set_elephant_duration(&mut self, duration: Duration) {
+        self.elephant_duration = duration;
+    }
+
+    pub fn enrich(&self, fluss: &mut Fluss) {
+        let bytes = fluss.normalized_bytes();
+        fluss.flow_class = FlowClass::classify(
+            bytes,
+            fluss.packets,
+            fluss.flow_age,
+            self.elephant_bytes,
+            self.elephant_duration,
+        );
+
+        if fluss.flow_class.is_some() {
+            self.bytes.record(bytes);
+            self.durations
+                .record(fluss.flow_age.as_millis().try_into().unwrap_or(u64::MAX));
+        }
+    }
+
+    pub fn histograms(&self) -> FlowHistograms {
+        FlowHistograms {
+            bytes: self.bytes.snapshot(),
+            duration_ms: self.durations.snapshot(),
+        }
+    }
+}
+
+impl Default for FlowClassifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::flow;
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+
+    fn counts(buckets: &[HistogramBucket]) -> Vec<(u64, u64)> {
+        buckets
+            .iter()
+            .map(|bucket| (bucket.le, bucket.count))
+            .collect()
+    }
+
+    fn fluss(bytes: u64, packets: u64, age: Duration) -> Fluss {
+        let mut fluss = flow(
+            Ipv4Addr::new(192, 0, 2, 1),
+            Ipv4Addr::new(198, 51, 100, 1),
+            443,
+            bytes,
+            packets,
+        );
+        fluss.flow_age = age;
+        fluss
+    }
+
+    #[test]
+    fn log2_buckets() {
+        let histogram = Log2Histogram::new();
+        assert!(histogram.snapshot().is_empty());
+
+        for value in [0, 1, 2, 3, 4, 7, 8, 1000] {
+            histogram.record(value);
+        }
+        assert_eq!(
+            counts(&histogram.snapshot()),
+            [
+                (0, 1),
+                (1, 1),
+                (3, 2),
+                (7, 2),
+                (15, 1),
+                (31, 0),
+                (63, 0),
+                (127, 0),
+                (255, 0),
+                (511, 0),
+                (1023, 1),
+            ]
+        );
+
+        histogram.record(u64::MAX);
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.len(), 65);
+        assert_eq!(
+            snapshot[64],
+            HistogramBucket {
+                le: u64::MAX,
+                count: 1
+            }
+        );
+    }
+
+    #[test]
+    fn histograms_are_shared_between_threads() {
+        let histogram = Arc::new(Log2Histogram::new());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let histogram = Arc::clone(&histogram);
+                std::thread::spawn(move || {
+                    for value in 0..1000 {
+                        histogram.record(value);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let total: u64 = histogram.snapshot().iter().map(|bucket| bucket.count).sum();
+        assert_eq!(total, 4000);
+    }
+
+    #[test]
+    fn flows_are_classified() {
+        let mut classifier = FlowClassifier::new();
+        classifier.set_elephant_bytes(10_000);
+        classifier.set_elephant_duration(Duration::from_secs(30));
+
+        let mut mouse = fluss(1500, 3, Duration::from_millis(200));
+        let mut elephant = fluss(20_000, 20, Duration::from_secs(5));
+        let mut long_lived = fluss(9000, 90, Duration::from_secs(30));
+        // sampled flows are classified by their estimated size
+        let mut sampled = fluss(1000, 1, Duration::ZERO);
+        sampled.sampling_interval = Some(10);
+        let mut empty = fluss(0, 0, Duration::from_secs(60));
+        for fluss in [
+            &mut mouse,
+            &mut elephant,
+            &mut long_lived,
+            &mut sampled,
+            &mut empty,
+        ] {
+            classifier.enrich(fluss);
+        }
+
+        assert_eq!(mouse.flow_class, Some(FlowClass::Mouse));
+        assert_eq!(elephant.flow_class, Some(FlowClass::Elephant));
+        assert_eq!(long_lived.flow_class, Some(FlowClass::LongLivedSmall));
+        assert_eq!(sampled.flow_class, Some(FlowClass::Elephant));
+        assert_eq!(empty.flow_class, None);
+
+        let json = serde_json::to_value(&long_lived).unwrap();
+        assert_eq!(json["flow_class"], "long-lived-small");
+
+        // empty flows are not recorded
+        let histograms = classifier.histograms();
+        let total = |buckets: &[HistogramBucket]| buckets.iter().map(|b| b.count).sum::<u64>();
+        assert_eq!(total(&histograms.bytes), 4);
+        assert_eq!(total(&histograms.duration_ms), 4);
+        // 1500 bytes and the mouse of 200ms
+        assert_eq!(histograms.bytes[11].count, 1);
+        assert_eq!(
+            histograms.duration_ms[8],
+            HistogramBucket { le: 255, count: 1 }
+        );
+        // the sampled flow of 10 KB and the long-lived flow of 9000 bytes
+        assert_eq!(
+            histograms.bytes[14],
+            HistogramBucket {
+                le: 16383,
+                count: 2
+            }
+        );
+        assert_eq!(histograms.bytes.last().unwrap().le, 32767);
+    }
+}
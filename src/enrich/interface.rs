@@ -0,0 +1,199 @@
+use crate::fluss::Fluss;
+use crate::ipfix::OptionsRecord;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+const IPFIX_INGRESS_INTERFACE: u16 = 10;
+const IPFIX_EGRESS_INTERFACE: u16 = 14;
+const IPFIX_INTERFACE_NAME: u16 = 82;
+const IPFIX_INTERFACE_DESCRIPTION: u16 = 83;
+
+/// Mappings which were not refreshed for this long are dropped by default.
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+struct Entry {
+    name: String,
+    last_seen: Instant,
+}
+
+/// Resolves interface indexes to the interface names announced by the exporters.
+///
+/// Exporters like Cisco and Juniper routers periodically send options records
+/// scoped by the interface index with the `interfaceName` or `interfaceDescription`
+/// of the interface. The names are learned per exporter from these records.
+pub struct InterfaceNames {
+    names: RwLock<HashMap<(IpAddr, u32), Entry>>,
+    max_age: Duration,
+}
+
+impl InterfaceNames {
+    pub fn new() -> Self {
+        Self {
+            names: RwLock::new(HashMap::new()),
+            max_age: DEFAULT_MAX_AGE,
+        }
+    }
+
+    /// Names which were not announced again within `max_age` are no longer used.
+    pub fn set_max_age(&mut self, max_age: Duration) {
+        self.max_age = max_age;
+    }
+
+    /// Learns the interface name of an options record of `exporter`.
+    ///
+    /// Returns whether the record contained an interface name.
+    pub fn update(&self, exporter: IpAddr, record: &OptionsRecord) -> bool {
+        let index = record
+            .scope
+            .iter()
+            .filter(|scope| {
+                scope.id == IPFIX_INGRESS_INTERFACE || scope.id == IPFIX_EGRESS_INTERFACE
+            })
+            .find_map(|scope| scope.value.as_u32());
+
+        // the name is preferred, some exporters only send a description,
+        // fixed length fields are padded with zeros
+        let name = [IPFIX_INTERFACE_NAME, IPFIX_INTERFACE_DESCRIPTION]
+            .iter()
+            .filter_map(|&id| record.options.iter().find(|option| option.id == id))
+            .map(|option| {
+                option
+                    .value
+                    .to_string()
+                    .trim_end_matches('\0')
+                    .trim()
+                    .to_owned()
+            })
+            .find(|name| !name.is_empty());
+
+        let (index, name) = match (index, name) {
+            (Some(index), Some(name)) => (index, name),
+            _ => return false,
+        };
+
+        tracing::debug!(%exporter, index, name = name.as_str(), "learned interface name");
+        self.names.write().insert(
+            (exporter, index),
+            Entry {
+                name,
+                last_seen: Instant::now(),
+            },
+        );
+
+        true
+    }
+
+    /// Returns the name of the interface `index` of `exporter`.
+    pub fn lookup(&self, exporter: IpAddr, index: u32) -> Option<String> {
+        self.names
+            .read()
+            .get(&(exporter, index))
+            .filter(|entry| entry.last_seen.elapsed() < self.max_age)
+            .map(|entry| entry.name.clone())
+    }
+
+    pub fn enrich(&self, exporter: IpAddr, fluss: &mut Fluss) {
+        fluss.ingress_interface_name = self.lookup(exporter, fluss.ingress_interface);
+        fluss.egress_interface_name = self.lookup(exporter, fluss.egress_interface);
+    }
+
+    /// Removes all names which were not refreshed within the maximum age.
+    pub fn expire(&self) {
+        let max_age = self.max_age;
+        self.names
+            .write()
+            .retain(|_, entry| entry.last_seen.elapsed() < max_age);
+    }
+}
+
+impl Default for InterfaceNames {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipfix::{parse_all, Decoded, Session};
+    use crate::produce::IpfixParser;
+
+    /// Learns the interface names of the Cisco fixture and returns its enriched flows.
+    fn cisco(interfaces: &InterfaceNames, exporter: IpAddr) -> Vec<Fluss> {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/fluss-core/tests/fixtures/cisco.hex"
+        );
+        let messages: Vec<Vec<u8>> = std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                (0..line.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&line[i..i + 2], 16).unwrap())
+                    .collect()
+            })
+            .collect();
+
+        let session = Session::new(IpfixParser::new());
+        let mut flows = Vec::new();
+        for message in &messages {
+            for packet in parse_all(message).unwrap() {
+                for decoded in session.parse_with_options(&packet).unwrap() {
+                    match decoded {
+                        Decoded::Options(record) => assert!(interfaces.update(exporter, &record)),
+                        Decoded::Flow(flow) => flows.push(flow),
+                    }
+                }
+            }
+        }
+
+        flows
+            .iter_mut()
+            .for_each(|flow| interfaces.enrich(exporter, flow));
+        flows
+    }
+
+    fn names(flow: &Fluss) -> (Option<&str>, Option<&str>) {
+        (
+            flow.ingress_interface_name.as_deref(),
+            flow.egress_interface_name.as_deref(),
+        )
+    }
+
+    #[test]
+    fn names_of_options_records() {
+        let exporter = IpAddr::from([192, 0, 2, 1]);
+        let interfaces = InterfaceNames::new();
+        let flows = cisco(&interfaces, exporter);
+
+        assert_eq!(
+            names(&flows[0]),
+            (Some("GigabitEthernet0/0/1"), Some("GigabitEthernet0/0/0"))
+        );
+        assert_eq!(
+            names(&flows[1]),
+            (Some("GigabitEthernet0/0/0"), Some("GigabitEthernet0/0/1"))
+        );
+        // interface 3 was never announced
+        assert_eq!(names(&flows[2]), (Some("Tunnel100"), None));
+
+        // names are learned per exporter
+        assert_eq!(interfaces.lookup(IpAddr::from([192, 0, 2, 2]), 1), None);
+    }
+
+    #[test]
+    fn names_age_out() {
+        let exporter = IpAddr::from([192, 0, 2, 1]);
+        let mut interfaces = InterfaceNames::new();
+        interfaces.set_max_age(Duration::ZERO);
+        let flows = cisco(&interfaces, exporter);
+
+        assert_eq!(names(&flows[0]), (None, None));
+        assert_eq!(interfaces.names.read().len(), 3);
+        interfaces.expire();
+        assert!(interfaces.names.read().is_empty());
+    }
+}
@@ -0,0 +1,9 @@
+pub mod class;
+pub mod interface;
+pub mod prefix;
+pub mod service;
+
+pub use self::class::FlowClassifier;
+pub use self::interface::InterfaceNames;
+pub use self::prefix::PrefixEnricher;
+pub use self::service::ServiceEnricher;
@@ -0,0 +1,488 @@
+use crate::exporters::Cidr;
+use crate::fluss::Fluss;
+use anyhow::Context as _;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// A named network of the prefix table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Network {
+    pub name: Option<String>,
+    /// Labels added to flows of the network, prefixed with `src_` or `dst_`.
+    pub labels: BTreeMap<String, String>,
+}
+
+/// Labels flows with the networks containing their source and destination address.
+///
+/// Networks are looked up by longest prefix match, a flow gets the most
+/// specific network of each address. Addresses without a network are left
+/// unlabeled. IPv4 mapped IPv6 addresses and networks are matched as IPv4.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PrefixEnricher {
+    networks: Vec<Network>,
+    v4: PrefixTrie,
+    v6: PrefixTrie,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    #[serde(default)]
+    network: Vec<NetworkEntry>,
+}
+
+#[derive(Deserialize)]
+struct NetworkEntry {
+    prefix: String,
+    name: Option<String>,
+    #[serde(flatten)]
+    labels: BTreeMap<String, String>,
+}
+
+impl PrefixEnricher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the network `prefix`, replacing a network added before with the same prefix.
+    pub fn insert(&mut self, prefix: Cidr, network: Network) -> anyhow::Result<()> {
+        for label in network.labels.keys() {
+            let (src, dst) = (format!("src_{}", label), format!("dst_{}", label));
+            if Fluss::FIELDS.contains(&src.as_str()) || Fluss::FIELDS.contains(&dst.as_str()) {
+                anyhow::bail!(
+                    "label {} of network {} clashes with a flow field",
+                    label,
+                    prefix
+                );
+            }
+        }
+
+        let index = self.networks.len();
+        let replaced = match prefix.addr() {
+            IpAddr::V6(addr) if prefix.prefix_len() >= 96 && addr.to_ipv4_mapped().is_some() => {
+                let addr = addr.to_ipv4_mapped().expect("the address is IPv4 mapped");
+                self.v4
+                    .insert(v4_key(addr.into()), prefix.prefix_len() - 96, index)
+            }
+            IpAddr::V4(addr) => self
+                .v4
+                .insert(v4_key(addr.into()), prefix.prefix_len(), index),
+            IpAddr::V6(addr) => self.v6.insert(addr.into(), prefix.prefix_len(), index),
+        };
+
+        match replaced {
+            Some(replaced) => self.networks[replaced] = network,
+            None => self.networks.push(network),
+        }
+        Ok(())
+    }
+
+    /// Loads networks from a TOML file or, if the file name ends with `.csv`, a CSV file.
+    ///
+    /// The TOML file lists networks with their prefix, name and any labels:
+    ///
+    /// ```toml
+    /// [[network]]
+    /// prefix = "10.20.0.0/16"
+    /// name = "dmz-berlin"
+    /// zone = "dmz"
+    /// criticality = "high"
+    /// ```
+    ///
+    /// The first row of a CSV file names the columns, it needs a `prefix`
+    /// column, `name` and all other columns are optional. Empty cells are skipped.
+    pub fn load_networks(&mut self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+
+        let entries = match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => {
+                parse_networks_csv(&content)?
+            }
+            _ => toml::from_str::<Config>(&content)?.network,
+        };
+
+        for entry in entries {
+            let prefix = entry
+                .prefix
+                .parse()
+                .with_context(|| format!("invalid network prefix {:?}", entry.prefix))?;
+            let network = Network {
+                name: entry.name,
+                labels: entry.labels,
+            };
+            self.insert(prefix, network)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the most specific network containing `addr`.
+    pub fn lookup(&self, addr: IpAddr) -> Option<&Network> {
+        let index = match addr {
+            IpAddr::V4(addr) => self.v4.lookup(v4_key(addr.into()), 32),
+            IpAddr::V6(addr) => match addr.to_ipv4_mapped() {
+                Some(addr) => self.v4.lookup(v4_key(addr.into()), 32),
+                None => self.v6.lookup(addr.into(), 128),
+            },
+        };
+        index.map(|index| &self.networks[index])
+    }
+
+    pub fn enrich(&self, fluss: &mut Fluss) {
+        if let Some(network) = self.lookup(fluss.src_addr) {
+            fluss.src_net_name = network.name.clone();
+            for (name, value) in &network.labels {
+                fluss.labels.insert(format!("src_{}", name), value.clone());
+            }
+        }
+        if let Some(network) = self.lookup(fluss.dst_addr) {
+            fluss.dst_net_name = network.name.clone();
+            for (name, value) in &network.labels {
+                fluss.labels.insert(format!("dst_{}", name), value.clone());
+            }
+        }
+    }
+
+    /// Amount of networks.
+    pub fn len(&self) -> usize {
+        self.networks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty()
+    }
+}
+
+/// Left aligns an IPv4 address, the trie is keyed on the most significant bits.
+fn v4_key(addr: u32) -> u128 {
+    u128::from(addr) << 96
+}
+
+/// Binary trie of prefixes, one level per address bit.
+///
+/// A lookup visits at most one node per bit of the address, independent of
+/// the amount of prefixes.
+#[derive(Debug, Clone, PartialEq)]
+struct PrefixTrie {
+    // the root is the first node, nodes are never removed
+    nodes: Vec<TrieNode>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct TrieNode {
+    children: [Option<u32>; 2],
+    // index of the network of the prefix ending at this node
+    value: Option<usize>,
+}
+
+impl Default for PrefixTrie {
+    fn default() -> Self {
+        Self {
+            nodes: vec![TrieNode::default()],
+        }
+    }
+}
+
+impl PrefixTrie {
+    /// Inserts the first `prefix_len` bits of `key`, returns the value replaced.
+    fn insert(&mut self, key: u128, prefix_len: u8, value: usize) -> Option<usize> {
+        let mut node = 0;
+        for bit in 0..prefix_len {
+            let branch = key_bit(key, bit);
+            node = match self.nodes[node].children[branch] {
+                Some(child) => child as usize,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(TrieNode::default());
+                    self.nodes[node].children[branch] = Some(child as u32);
+                    child
+                }
+            };
+        }
+
+        let replaced = self.nodes[node].value.take();
+        self.nodes[node].value = Some(replaced.unwrap_or(value));
+        replaced
+    }
+
+    /// Returns the value of the longest prefix of the first `bits` bits of `key`.
+    fn lookup(&self, key: u128, bits: u8) -> Option<usize> {
+        let mut node = &self.nodes[0];
+        let mut longest = node.value;
+        for bit in 0..bits {
+            node = match node.children[key_bit(key, bit)] {
+                Some(child) => &self.nodes[child as usize],
+                None => break,
+            };
+            longest = node.value.or(longest);
+        }
+        longest
+    }
+}
+
+fn key_bit(key: u128, bit: u8) -> usize {
+    (key >> (127 - bit) & 1) as usize
+}
+
+fn parse_networks_csv(content: &str) -> anyhow::Result<Vec<NetworkEntry>> {
+    let mut records = parse_csv(content).into_iter();
+    let header = records.next().unwrap_or_default();
+    let prefix_column = header
+        .iter()
+        .position(|column| column == "prefix")
+        .ok_or_else(|| anyhow::anyhow!("the network table has no prefix column"))?;
+
+    let mut entries = Vec::new();
+    for record in records {
+        // blank lines
+        if record.iter().all(String::is_empty) {
+            continue;
+        }
+
+        let mut entry = NetworkEntry {
+            prefix: record.get(prefix_column).cloned().unwrap_or_default(),
+            name: None,
+            labels: BTreeMap::new(),
+        };
+        for (column, value) in header.iter().zip(record) {
+            match column.as_str() {
+                _ if value.is_empty() => (),
+                "prefix" => (),
+                "name" => entry.name = Some(value),
+                column => {
+                    entry.labels.insert(column.to_owned(), value);
+                }
+            }
+        }
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// Minimal RFC 4180 parser, quoted fields may contain separators and line breaks.
+fn parse_csv(input: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => record.push(std::mem::take(&mut field)),
+            ('\r', false) => (),
+            ('\n', false) => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (c, _) => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::flow;
+    use std::io::Write;
+
+    fn network(name: &str, labels: &[(&str, &str)]) -> Network {
+        Network {
+            name: Some(name.to_owned()),
+            labels: labels
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    fn enricher(networks: &[(&str, &str)]) -> PrefixEnricher {
+        let mut enricher = PrefixEnricher::new();
+        for (prefix, name) in networks {
+            enricher
+                .insert(prefix.parse().unwrap(), network(name, &[]))
+                .unwrap();
+        }
+        enricher
+    }
+
+    fn name(enricher: &PrefixEnricher, addr: &str) -> Option<String> {
+        enricher.lookup(addr.parse().unwrap())?.name.clone()
+    }
+
+    #[test]
+    fn overlapping_prefixes_match_the_longest() {
+        // inserted from the most to the least specific, the order does not matter
+        let enricher = enricher(&[
+            ("10.20.30.40/32", "host"),
+            ("10.20.30.0/24", "office"),
+            ("10.20.0.0/16", "berlin"),
+            ("10.0.0.0/8", "corp"),
+            ("0.0.0.0/0", "internet"),
+        ]);
+        assert_eq!(name(&enricher, "10.20.30.40").as_deref(), Some("host"));
+        assert_eq!(name(&enricher, "10.20.30.41").as_deref(), Some("office"));
+        assert_eq!(name(&enricher, "10.20.31.1").as_deref(), Some("berlin"));
+        assert_eq!(name(&enricher, "10.21.0.1").as_deref(), Some("corp"));
+        assert_eq!(name(&enricher, "192.0.2.1").as_deref(), Some("internet"));
+        // the default route of IPv4 does not contain IPv6 addresses
+        assert_eq!(name(&enricher, "2001:db8::1"), None);
+    }
+
+    #[test]
+    fn ipv6_prefixes() {
+        let enricher = enricher(&[
+            ("2001:db8::/32", "documentation"),
+            ("2001:db8:1::/48", "lab"),
+            ("2001:db8:1:2::/64", "lab-servers"),
+            ("::ffff:192.0.2.0/120", "mapped"),
+        ]);
+        assert_eq!(
+            name(&enricher, "2001:db8:1:2::1").as_deref(),
+            Some("lab-servers")
+        );
+        assert_eq!(name(&enricher, "2001:db8:1:3::1").as_deref(), Some("lab"));
+        assert_eq!(
+            name(&enricher, "2001:db8:ffff::1").as_deref(),
+            Some("documentation")
+        );
+        assert_eq!(name(&enricher, "2001:db9::1"), None);
+
+        // IPv4 mapped networks and addresses are matched as IPv4
+        assert_eq!(name(&enricher, "192.0.2.7").as_deref(), Some("mapped"));
+        assert_eq!(
+            name(&enricher, "::ffff:192.0.2.7").as_deref(),
+            Some("mapped")
+        );
+        assert_eq!(name(&enricher, "192.0.3.7"), None);
+    }
+
+    #[test]
+    fn same_prefix_replaces_the_network() {
+        let enricher = enricher(&[("10.0.0.0/8", "old"), ("10.0.0.0/8", "new")]);
+        assert_eq!(name(&enricher, "10.1.1.1").as_deref(), Some("new"));
+        assert_eq!(enricher.len(), 1);
+    }
+
+    #[test]
+    fn flows_are_labeled() {
+        let mut enricher = PrefixEnricher::new();
+        let dmz = network("dmz-berlin", &[("zone", "dmz"), ("criticality", "high")]);
+        enricher
+            .insert("10.20.0.0/16".parse().unwrap(), dmz)
+            .unwrap();
+        let office = network("office", &[("zone", "office")]);
+        enricher
+            .insert("10.30.0.0/16".parse().unwrap(), office)
+            .unwrap();
+
+        let mut fluss = flow([10, 30, 0, 1].into(), [10, 20, 0, 1].into(), 443, 1000, 10);
+        enricher.enrich(&mut fluss);
+        assert_eq!(fluss.src_net_name.as_deref(), Some("office"));
+        assert_eq!(fluss.dst_net_name.as_deref(), Some("dmz-berlin"));
+        let labels: Vec<_> = fluss
+            .labels
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                ("dst_criticality", "high"),
+                ("dst_zone", "dmz"),
+                ("src_zone", "office"),
+            ]
+        );
+
+        // addresses without a network leave the fields unset
+        let mut fluss = flow([192, 0, 2, 1].into(), [10, 20, 0, 1].into(), 443, 1000, 10);
+        enricher.enrich(&mut fluss);
+        assert_eq!(fluss.src_net_name, None);
+        assert_eq!(fluss.dst_net_name.as_deref(), Some("dmz-berlin"));
+        assert!(fluss.labels.keys().all(|name| name.starts_with("dst_")));
+    }
+
+    #[test]
+    fn labels_clashing_with_flow_fields_are_rejected() {
+        let mut enricher = PrefixEnricher::new();
+        let err = enricher
+            .insert(
+                "10.0.0.0/8".parse().unwrap(),
+                network("corp", &[("port", "1")]),
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("clashes"), "{}", err);
+        assert!(enricher.is_empty());
+    }
+
+    fn load(extension: &str, content: &str) -> anyhow::Result<PrefixEnricher> {
+        let mut file = tempfile::Builder::new()
+            .suffix(extension)
+            .tempfile()
+            .unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        let mut enricher = PrefixEnricher::new();
+        enricher.load_networks(file.path())?;
+        Ok(enricher)
+    }
+
+    #[test]
+    fn toml_and_csv_tables() {
+        let toml = r#"
+            [[network]]
+            prefix = "10.20.0.0/16"
+            name = "dmz-berlin"
+            zone = "dmz"
+
+            [[network]]
+            prefix = "2001:db8::/32"
+            zone = "lab"
+        "#;
+        let csv = "name,prefix,zone\r\n\
+                   dmz-berlin,10.20.0.0/16,dmz\r\n\
+                   \r\n\
+                   ,2001:db8::/32,lab\r\n";
+
+        for enricher in [load(".toml", toml).unwrap(), load(".csv", csv).unwrap()] {
+            assert_eq!(enricher.len(), 2);
+            let dmz = enricher.lookup([10, 20, 1, 1].into()).unwrap();
+            assert_eq!(*dmz, network("dmz-berlin", &[("zone", "dmz")]));
+            let lab = enricher.lookup("2001:db8::1".parse().unwrap()).unwrap();
+            assert_eq!(lab.name, None);
+            assert_eq!(lab.labels["zone"], "lab");
+        }
+
+        let csv = "\"name\",\"prefix\"\n\"dmz, berlin\",10.20.0.0/16\n";
+        let enricher = load(".csv", csv).unwrap();
+        assert_eq!(name(&enricher, "10.20.0.1").as_deref(), Some("dmz, berlin"));
+    }
+
+    #[test]
+    fn invalid_tables_are_rejected() {
+        let err = load(".csv", "name,network\ndmz,10.0.0.0/8\n").unwrap_err();
+        assert!(err.to_string().contains("no prefix column"), "{}", err);
+        let err = load(".csv", "prefix\n10.0.0.0/33\n").unwrap_err();
+        assert!(
+            err.to_string().contains("invalid network prefix"),
+            "{}",
+            err
+        );
+        assert!(load(".toml", "[[networks]]\nprefix = \"10.0.0.0/8\"\n").is_err());
+    }
+}
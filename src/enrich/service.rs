@@ -0,0 +1,181 @@
+use crate::fluss::{Fluss, Protocol};
+use crate::services::service_name;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Ports from this port on are considered ephemeral by default,
+/// the start of the IANA dynamic port range.
+pub const DEFAULT_EPHEMERAL_PORT_START: u16 = 32768;
+
+/// Maps the protocol and port of a flow to the name of the service.
+#[derive(Debug, PartialEq)]
+pub struct ServiceEnricher {
+    overrides: HashMap<(u8, u16), String>,
+    ephemeral_port_start: u16,
+}
+
+impl ServiceEnricher {
+    pub fn new() -> Self {
+        Self {
+            overrides: HashMap::new(),
+            ephemeral_port_start: DEFAULT_EPHEMERAL_PORT_START,
+        }
+    }
+
+    /// Ports greater or equal to `port` are considered client ports.
+    pub fn set_ephemeral_port_start(&mut self, port: u16) {
+        self.ephemeral_port_start = port;
+    }
+
+    /// Adds a service name which takes precedence over the built-in service names.
+    pub fn set_service(&mut self, protocol: Protocol, port: u16, name: impl Into<String>) {
+        self.overrides.insert((protocol.into(), port), name.into());
+    }
+
+    /// Loads service name overrides from a TOML file.
+    ///
+    /// Tables are named after the protocol and map ports to service names:
+    ///
+    /// ```toml
+    /// [tcp]
+    /// 8443 = "internal-admin-ui"
+    /// ```
+    pub fn load_services(&mut self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let content = std::fs::read_to_string(path)?;
+        let services: HashMap<String, HashMap<String, String>> = toml::from_str(&content)?;
+
+        for (protocol, ports) in services {
+            let protocol = parse_protocol(&protocol)?;
+            for (port, name) in ports {
+                let port = port
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("invalid port: {:?}", port))?;
+                self.set_service(protocol, port, name);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the name of the service listening on `port`.
+    pub fn service(&self, protocol: Protocol, port: u16) -> Option<&str> {
+        if let Some(name) = self.overrides.get(&(protocol.into(), port)) {
+            return Some(name.as_str());
+        }
+
+        service_name(port, protocol)
+    }
+
+    /// Returns the name of the service of a connection between `src_port` and `dst_port`.
+    ///
+    /// The service is usually on the destination port, unless the destination port
+    /// is ephemeral and the source port is not, e.g. for a flow of a response.
+    pub fn lookup(&self, protocol: Protocol, src_port: u16, dst_port: u16) -> Option<&str> {
+        let port = match (self.is_ephemeral(src_port), self.is_ephemeral(dst_port)) {
+            (false, true) => src_port,
+            _ => dst_port,
+        };
+
+        self.service(protocol, port)
+    }
+
+    pub fn enrich(&self, fluss: &mut Fluss) {
+        fluss.service = self
+            .lookup(fluss.protocol, fluss.src_port, fluss.dst_port)
+            .map(str::to_owned);
+    }
+
+    fn is_ephemeral(&self, port: u16) -> bool {
+        port >= self.ephemeral_port_start
+    }
+}
+
+impl Default for ServiceEnricher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parse_protocol(name: &str) -> anyhow::Result<Protocol> {
+    match name {
+        "tcp" => Ok(Protocol::Tcp),
+        "udp" => Ok(Protocol::Udp),
+        "dccp" => Ok(Protocol::from(33)),
+        "sctp" => Ok(Protocol::from(132)),
+        name => name
+            .parse::<u8>()
+            .map(Protocol::from)
+            .map_err(|_| anyhow::anyhow!("unknown protocol: {:?}", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_services() {
+        let enricher = ServiceEnricher::new();
+        assert_eq!(enricher.service(Protocol::Tcp, 443), Some("https"));
+        assert_eq!(enricher.service(Protocol::Tcp, 8443), Some("pcsync-https"));
+        assert_eq!(enricher.service(Protocol::Tcp, 1), None);
+    }
+
+    #[test]
+    fn overrides_take_precedence() {
+        let mut enricher = ServiceEnricher::new();
+        enricher.set_service(Protocol::Tcp, 8443, "internal-admin-ui");
+
+        assert_eq!(
+            enricher.service(Protocol::Tcp, 8443),
+            Some("internal-admin-ui")
+        );
+        // only the overridden protocol changes
+        assert_eq!(enricher.service(Protocol::Udp, 8443), Some("pcsync-https"));
+        assert_eq!(enricher.service(Protocol::Tcp, 443), Some("https"));
+    }
+
+    #[test]
+    fn load_services() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(
+            &mut file,
+            b"[tcp]\n8443 = \"internal-admin-ui\"\n\n[udp]\n9999 = \"telemetry\"\n",
+        )
+        .unwrap();
+
+        let mut enricher = ServiceEnricher::new();
+        enricher.load_services(file.path()).unwrap();
+
+        assert_eq!(
+            enricher.service(Protocol::Tcp, 8443),
+            Some("internal-admin-ui")
+        );
+        assert_eq!(enricher.service(Protocol::Udp, 9999), Some("telemetry"));
+    }
+
+    #[test]
+    fn load_services_rejects_invalid_entries() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, b"[tcp]\nhttps = \"web\"\n").unwrap();
+        assert!(ServiceEnricher::new().load_services(file.path()).is_err());
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, b"[quic]\n443 = \"web\"\n").unwrap();
+        assert!(ServiceEnricher::new().load_services(file.path()).is_err());
+    }
+
+    #[test]
+    fn service_side() {
+        let mut enricher = ServiceEnricher::new();
+        // request to the service
+        assert_eq!(enricher.lookup(Protocol::Tcp, 51514, 443), Some("https"));
+        // response from the service
+        assert_eq!(enricher.lookup(Protocol::Tcp, 443, 51514), Some("https"));
+        // both ports are well-known, the destination is the service
+        assert_eq!(enricher.lookup(Protocol::Udp, 53, 123), Some("ntp"));
+
+        enricher.set_ephemeral_port_start(1024);
+        assert_eq!(enricher.lookup(Protocol::Tcp, 443, 8443), Some("https"));
+    }
+}
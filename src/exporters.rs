@@ -0,0 +1,378 @@
+use crate::acl::ExporterAcl;
+use crate::fluss::Fluss;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// An IP network, e.g. `10.1.0.0/16`, a plain address matches only itself.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Option<Self> {
+        let max = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix_len > max {
+            return None;
+        }
+        Some(Self { addr, prefix_len })
+    }
+
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, addr) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(addr)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr.parse::<IpAddr>()?, Some(prefix_len.parse()?)),
+            None => (s.parse()?, None),
+        };
+
+        let prefix_len = prefix_len.unwrap_or(match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        });
+
+        Self::new(addr, prefix_len).ok_or_else(|| anyhow::anyhow!("invalid prefix length: {}", s))
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// Settings applied to all datagrams of an exporter.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExporterSettings {
+    /// Sampling interval used instead of the one announced by the exporter.
+    pub sampling_rate: Option<u64>,
+    /// Ignores all datagrams of the exporter.
+    #[serde(default)]
+    pub drop: bool,
+    /// Labels added to every flow of the exporter.
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    /// Site or tenant of the exporter, the `tenant` of every flow.
+    pub tenant: Option<String>,
+    /// Only data sets of these templates are decoded.
+    pub templates: Option<Vec<u16>>,
+}
+
+impl ExporterSettings {
+    /// Adds the labels and the tenant of the exporter to `fluss`.
+    pub fn apply(&self, fluss: &mut Fluss) {
+        fluss.labels.extend(
+            self.labels
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+        if let Some(tenant) = &self.tenant {
+            fluss.tenant = Some(tenant.clone());
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Config {
+    #[serde(default)]
+    allow: Vec<String>,
+    #[serde(default)]
+    deny: Vec<String>,
+    #[serde(default)]
+    exporter: Vec<ExporterEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExporterEntry {
+    address: String,
+    #[serde(flatten)]
+    settings: ExporterSettings,
+    // `deny_unknown_fields` has no effect on flattened structs
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+/// Per exporter settings, selected by the address of the exporter.
+///
+/// When multiple networks contain the address the longest prefix wins.
+/// Datagrams of exporters which are not allowed by the [`ExporterAcl`] are
+/// dropped before they are decoded.
+#[derive(Debug, Default, PartialEq)]
+pub struct Exporters {
+    exporters: Vec<(Cidr, Arc<ExporterSettings>)>,
+    default: Arc<ExporterSettings>,
+    acl: ExporterAcl,
+}
+
+impl Exporters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the allowed and denied networks and `[[exporter]]` sections from a TOML file.
+    ///
+    /// ```toml
+    /// allow = ["10.0.0.0/8"]
+    /// deny = ["10.66.0.0/16"]
+    ///
+    /// [[exporter]]
+    /// address = "10.1.0.0/16"
+    /// sampling_rate = 1000
+    /// labels = { site = "berlin" }
+    /// tenant = "berlin"
+    /// ```
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let config: Config = toml::from_str(&content)?;
+
+        let mut exporters = Self::new();
+        for network in config.allow {
+            exporters.allow(network.parse()?);
+        }
+        for network in config.deny {
+            exporters.deny(network.parse()?);
+        }
+        for entry in config.exporter {
+            if let Some(name) = entry.unknown.keys().next() {
+                anyhow::bail!("unknown setting {:?} of exporter {}", name, entry.address);
+            }
+            exporters.add(entry.address.parse()?, entry.settings)?;
+        }
+
+        Ok(exporters)
+    }
+
+    pub fn add(&mut self, network: Cidr, settings: ExporterSettings) -> anyhow::Result<()> {
+        if let Some(name) = settings
+            .labels
+            .keys()
+            .find(|name| Fluss::FIELDS.contains(&name.as_str()))
+        {
+            anyhow::bail!("label {:?} of {} collides with a flow field", name, network);
+        }
+        if settings.tenant.as_deref() == Some("") {
+            anyhow::bail!("empty tenant of {}", network);
+        }
+        if self.exporters.iter().any(|(other, _)| *other == network) {
+            anyhow::bail!("duplicate exporter: {}", network);
+        }
+
+        self.exporters.push((network, Arc::new(settings)));
+        Ok(())
+    }
+
+    /// Sets the tenant of all exporters without a tenant of their own.
+    pub fn set_default_tenant(&mut self, tenant: &str) {
+        let settings = self
+            .exporters
+            .iter_mut()
+            .map(|(_, settings)| settings)
+            .chain(std::iter::once(&mut self.default));
+        for settings in settings {
+            if settings.tenant.is_none() {
+                Arc::make_mut(settings).tenant = Some(tenant.to_owned());
+            }
+        }
+    }
+
+    /// Allows exporters of `network`, all other exporters are denied once a network is allowed.
+    pub fn allow(&mut self, network: Cidr) {
+        self.acl.allow(network);
+    }
+
+    /// Denies exporters of `network`, even if they are in an allowed network.
+    pub fn deny(&mut self, network: Cidr) {
+        self.acl.deny(network);
+    }
+
+    pub fn is_allowed(&self, addr: IpAddr) -> bool {
+        self.acl.is_allowed(addr)
+    }
+
+    /// Returns the settings of the exporter with the address `addr`.
+    pub fn lookup(&self, addr: IpAddr) -> Arc<ExporterSettings> {
+        self.exporters
+            .iter()
+            .filter(|(network, _)| network.contains(addr))
+            .max_by_key(|(network, _)| network.prefix_len())
+            .map_or_else(
+                || Arc::clone(&self.default),
+                |(_, settings)| Arc::clone(settings),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::flow;
+    use std::io::Write;
+
+    const CONFIG: &str = r#"
+        [[exporter]]
+        address = "10.0.0.0/8"
+        labels = { site = "hq" }
+
+        [[exporter]]
+        address = "10.1.0.0/16"
+        sampling_rate = 1000
+        labels = { site = "berlin", role = "core" }
+
+        [[exporter]]
+        address = "10.1.2.3"
+        sampling_rate = 1
+
+        [[exporter]]
+        address = "10.99.0.0/16"
+        drop = true
+
+        [[exporter]]
+        address = "10.99.0.1"
+        templates = [256, 257]
+    "#;
+
+    fn load(config: &str) -> anyhow::Result<Exporters> {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+        Exporters::load(file.path())
+    }
+
+    fn addr(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn longest_prefix_wins() {
+        let exporters = load(CONFIG).unwrap();
+
+        let site = |exporter| exporters.lookup(addr(exporter)).labels.get("site").cloned();
+        assert_eq!(site("10.200.0.1").as_deref(), Some("hq"));
+        assert_eq!(site("10.1.7.7").as_deref(), Some("berlin"));
+        // the settings of the longest prefix replace the others, they are not merged
+        assert_eq!(site("10.1.2.3"), None);
+
+        assert_eq!(exporters.lookup(addr("10.1.7.7")).sampling_rate, Some(1000));
+        assert_eq!(exporters.lookup(addr("10.1.2.3")).sampling_rate, Some(1));
+        assert_eq!(exporters.lookup(addr("10.200.0.1")).sampling_rate, None);
+    }
+
+    #[test]
+    fn unknown_exporters_get_the_defaults() {
+        let exporters = load(CONFIG).unwrap();
+        assert_eq!(
+            *exporters.lookup(addr("192.0.2.1")),
+            ExporterSettings::default()
+        );
+    }
+
+    #[test]
+    fn dropped_exporters() {
+        let exporters = load(CONFIG).unwrap();
+        assert!(exporters.lookup(addr("10.99.4.4")).drop);
+        // a more specific entry of a dropped network is not dropped
+        let settings = exporters.lookup(addr("10.99.0.1"));
+        assert!(!settings.drop);
+        assert_eq!(settings.templates.as_deref(), Some(&[256, 257][..]));
+        assert!(!exporters.lookup(addr("10.1.7.7")).drop);
+    }
+
+    #[test]
+    fn labels_are_serialized_flat() {
+        let exporters = load(CONFIG).unwrap();
+        let mut fluss = flow([10, 1, 7, 7].into(), [192, 0, 2, 1].into(), 443, 1000, 10);
+        exporters.lookup(addr("10.1.7.7")).apply(&mut fluss);
+
+        let json = serde_json::to_value(&fluss).unwrap();
+        assert_eq!(json["site"], "berlin");
+        assert_eq!(json["role"], "core");
+        assert!(json.get("labels").is_none());
+    }
+
+    #[test]
+    fn tenants_of_exporters() {
+        let config = r#"
+            [[exporter]]
+            address = "10.1.0.0/16"
+            tenant = "berlin"
+
+            [[exporter]]
+            address = "10.2.0.0/16"
+            tenant = "lab"
+
+            [[exporter]]
+            address = "10.3.0.0/16"
+            sampling_rate = 10
+        "#;
+        let mut exporters = load(config).unwrap();
+        exporters.set_default_tenant("hq");
+
+        let tenant = |exporter| {
+            let mut fluss = flow([10, 0, 0, 1].into(), [192, 0, 2, 1].into(), 443, 1000, 10);
+            exporters.lookup(addr(exporter)).apply(&mut fluss);
+            fluss.tenant
+        };
+        assert_eq!(tenant("10.1.0.1").as_deref(), Some("berlin"));
+        assert_eq!(tenant("10.2.0.1").as_deref(), Some("lab"));
+        // exporters without a tenant of their own and unknown exporters get the default
+        assert_eq!(tenant("10.3.0.1").as_deref(), Some("hq"));
+        assert_eq!(tenant("192.0.2.1").as_deref(), Some("hq"));
+
+        let err = load("[[exporter]]\naddress = \"10.0.0.0/8\"\ntenant = \"\"\n").unwrap_err();
+        assert!(err.to_string().contains("empty tenant"), "{}", err);
+    }
+
+    #[test]
+    fn invalid_entries_are_rejected() {
+        let err = load("[[exporter]]\naddress = \"10.0.0.0/8\"\nlabels = { bytes = \"1\" }\n")
+            .unwrap_err();
+        assert!(err.to_string().contains("collides"), "{}", err);
+
+        let err = load(
+            "[[exporter]]\naddress = \"10.0.0.0/8\"\n\n[[exporter]]\naddress = \"10.0.0.0/8\"\n",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("duplicate"), "{}", err);
+
+        assert!(load("[[exporter]]\naddress = \"10.0.0.0/33\"\n").is_err());
+        let err = load("[[exporter]]\naddress = \"10.0.0.0/8\"\nsampling = 10\n").unwrap_err();
+        assert!(err.to_string().contains("unknown setting"), "{}", err);
+    }
+}
@@ -1,5 +0,0 @@
-pub mod parser;
-pub mod session;
-
-pub use parser::{parse, Packet};
-pub use session::{DebugParser, FieldParser, Parser, Session};
@@ -1,5 +1,25 @@
-pub mod fluss;
-pub mod ipfix;
-pub mod produce;
-pub mod protocol;
-pub mod publish;
+pub mod acl;
+pub mod clock;
+pub mod control;
+#[cfg(feature = "pcap-capture")]
+pub mod debug;
+pub mod duplicates;
+pub mod enrich;
+pub mod exporters;
+pub mod matrix;
+pub mod pool;
+pub mod proxy;
+pub mod reload;
+pub mod routes;
+pub mod shutdown;
+pub mod stats;
+pub mod store;
+pub mod systemd;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+
+#[cfg(feature = "arrow")]
+pub use fluss_core::arrow;
+pub use fluss_core::testing;
+pub use fluss_core::{flow_key, fluss, icmp, ipfix, produce, protocol, services, transport};
+pub use fluss_publish as publish;
@@ -1,100 +1,116 @@
-use clap::{App, Arg};
-use fluss::ipfix::{
-    parser::{DataSet, FieldSpecifier},
-    Parser,
-};
-use tokio::net::UdpSocket;
+use clap::{App, AppSettings, Arg};
+use tracing_subscriber::layer::SubscriberExt as _;
+use tracing_subscriber::util::SubscriberInitExt as _;
+use tracing_subscriber::{EnvFilter, Layer as _};
 
-enum Either<Left, Right> {
-    Left(Left),
-    Right(Right),
-}
-
-impl<'a, L, R, T> Parser<'a> for Either<L, R>
-where
-    L: Parser<'a, Output = T>,
-    R: Parser<'a, Output = T>,
-{
-    type Output = T;
-
-    fn parse(&self, fields: &[FieldSpecifier], set: &DataSet<'a>) -> Option<Self::Output> {
-        match self {
-            Self::Left(left) => left.parse(fields, set),
-            Self::Right(right) => right.parse(fields, set),
-        }
-    }
-}
+mod cmd;
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<()> {
     let app = App::new("fluss")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
         .arg(
             Arg::with_name("verbosity")
                 .long("verbose")
                 .short("v")
                 .multiple(true)
+                .global(true)
                 .help("verbosity level"),
         )
         .arg(
-            Arg::with_name("debug")
-                .long("debug")
-                .short("d")
-                .takes_value(false)
-                .help("enables additional debug output, does not change verbosity"),
-        )
-        .arg(
-            Arg::with_name("listen")
-                .long("listen")
-                .short("l")
-                .default_value("0.0.0.0:2055")
-                .help("listen/bind port for netflow traffic"),
+            Arg::with_name("log-level")
+                .long("log-level")
+                .takes_value(true)
+                .global(true)
+                .possible_values(&["trace", "debug", "info", "warn", "error"])
+                .help("log level, takes precedence over the verbosity level"),
         )
         .arg(
-            Arg::with_name("publisher")
-                .long("publisher")
-                .short("p")
-                .possible_values(&["console", "elastic"])
-                .default_value("console")
-                .help("publisher for flow data"),
+            Arg::with_name("log-format")
+                .long("log-format")
+                .takes_value(true)
+                .global(true)
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .help("output format of logs"),
         )
+        .subcommand(cmd::collect::subcommand())
+        .subcommand(cmd::decode::subcommand())
+        .subcommand(cmd::templates::subcommand())
+        .subcommand(cmd::stats::subcommand())
+        .subcommand(cmd::replay_dlq::subcommand())
+        .subcommand(cmd::bench::subcommand())
         .get_matches();
 
-    tracing_subscriber::fmt()
-        .with_max_level(match app.occurrences_of("verbosity") {
-            0 => tracing::Level::INFO,
-            1 => tracing::Level::DEBUG,
-            _ => tracing::Level::TRACE,
-        })
-        .init();
-
-    let publisher: Box<dyn fluss::publish::Publisher> = match app.value_of("publisher") {
-        Some("elastic") => Box::new(fluss::publish::ElasticPublisher::new(
-            elasticsearch::Elasticsearch::default(),
-        )),
-        Some("console") => Box::new(fluss::publish::ConsolePublisher::new()),
-        _ => panic!("unknown or no publisher"),
+    // global arguments are only propagated to the subcommand
+    let matches = match app.subcommand() {
+        (_, Some(matches)) => matches,
+        _ => &app,
     };
 
-    let listen = app.value_of("listen").unwrap();
-    let socket = UdpSocket::bind(listen).await?;
-    tracing::info!("listening for netflow traffic on: {}", listen);
+    let verbosity = matches.occurrences_of("verbosity");
+    let level = log_level(matches.value_of("log-level"), verbosity);
 
-    let parser = fluss::produce::IpfixParser::new();
-    let parser = match app.is_present("debug") {
-        true => Either::Left(fluss::ipfix::DebugParser::new(parser)),
-        false => Either::Right(parser),
+    // RUST_LOG style filters take precedence over the level
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
+    let fmt = match matches.value_of("log-format") {
+        Some("json") => tracing_subscriber::fmt::layer().json().boxed(),
+        _ => tracing_subscriber::fmt::layer().boxed(),
     };
-    let session = fluss::ipfix::Session::new(parser);
+    let registry = tracing_subscriber::registry().with(fmt.with_filter(filter));
 
-    let mut buf = vec![0; u16::MAX as usize];
-    loop {
-        let (len, addr) = socket.recv_from(&mut buf).await?;
-        tracing::info!("{:?} bytes received from {:?}", len, addr);
+    // spans are exported independent of the log level
+    #[cfg(feature = "otel")]
+    let telemetry = cmd::collect::telemetry(matches)?;
+    #[cfg(feature = "otel")]
+    let registry = registry.with(telemetry.as_ref().map(|telemetry| {
+        let spans =
+            tracing_subscriber::filter::Targets::new().with_target("fluss", tracing::Level::DEBUG);
+        telemetry.layer().with_filter(spans)
+    }));
 
-        let packet = fluss::ipfix::parse(&buf[0..len])?;
+    registry.init();
 
-        for flow in session.parse(&packet) {
-            publisher.publish(&flow).await?;
+    let result = match app.subcommand() {
+        ("collect", Some(matches)) => cmd::collect::run(matches, verbosity > 0),
+        ("decode", Some(matches)) => cmd::decode::run(matches),
+        ("templates", Some(matches)) => cmd::templates::run(matches),
+        ("stats", Some(matches)) => cmd::stats::run(matches),
+        ("replay-dlq", Some(matches)) => cmd::replay_dlq::run(matches),
+        ("bench", Some(matches)) => cmd::bench::run(matches),
+        _ => unreachable!("subcommand is required"),
+    };
+
+    #[cfg(feature = "otel")]
+    if let Some(telemetry) = telemetry {
+        if let Err(err) = telemetry.shutdown() {
+            tracing::warn!(error = %err, "failed to export pending telemetry");
         }
     }
+
+    result
+}
+
+/// The explicit `--log-level` takes precedence over the number of `-v`.
+fn log_level(level: Option<&str>, verbosity: u64) -> &str {
+    match level {
+        Some(level) => level,
+        None => match verbosity {
+            0 => "info",
+            1 => "debug",
+            _ => "trace",
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_level_takes_precedence_over_verbosity() {
+        assert_eq!(log_level(None, 0), "info");
+        assert_eq!(log_level(None, 1), "debug");
+        assert_eq!(log_level(None, 3), "trace");
+        assert_eq!(log_level(Some("warn"), 2), "warn");
+    }
 }
@@ -0,0 +1,197 @@
+//! Bytes exchanged between autonomous systems.
+
+use crate::fluss::Fluss;
+use crate::publish::Publisher;
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::Write as _;
+
+/// Field of [`Fluss::extra`] holding the source AS number.
+pub const SRC_ASN_FIELD: &str = "src_asn";
+/// Field of [`Fluss::extra`] holding the destination AS number.
+pub const DST_ASN_FIELD: &str = "dst_asn";
+
+/// Sums the bytes of flows per pair of source and destination AS, e.g. to
+/// answer how many bytes flowed from AS1234 to AS5678 in the last hour.
+///
+/// The AS numbers are read from the custom fields `src_asn` and `dst_asn`,
+/// e.g. the BGP AS numbers reported by the exporter:
+///
+/// ```toml
+/// [[custom_field]]
+/// id = 16
+/// name = "src_asn"
+/// type = "number"
+///
+/// [[custom_field]]
+/// id = 17
+/// name = "dst_asn"
+/// type = "number"
+/// ```
+///
+/// Flows without an AS number are counted as AS 0. Bytes are scaled up by
+/// the sampling interval. All flows are passed on to the inner publisher,
+/// [`TrafficMatrix::flush`] has to be called at the end of every window.
+pub struct TrafficMatrix {
+    inner: Box<dyn Publisher + Send + Sync>,
+    matrix: Mutex<HashMap<(u32, u32), u64>>,
+}
+
+impl TrafficMatrix {
+    pub fn new(inner: Box<dyn Publisher + Send + Sync>) -> Self {
+        Self {
+            inner,
+            matrix: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the bytes per `(src_asn, dst_asn)` of the current window and starts a new one.
+    pub fn flush(&self) -> HashMap<(u32, u32), u64> {
+        std::mem::take(&mut *self.matrix.lock())
+    }
+
+    /// Formats the current window as CSV with the columns `src_asn`, `dst_asn`
+    /// and `bytes`, ordered by the AS numbers.
+    pub fn render_csv(&self) -> String {
+        let mut rows = self
+            .matrix
+            .lock()
+            .iter()
+            .map(|(&asns, &bytes)| (asns, bytes))
+            .collect::<Vec<_>>();
+        rows.sort_unstable();
+
+        let mut csv = String::from("src_asn,dst_asn,bytes\n");
+        for ((src_asn, dst_asn), bytes) in rows {
+            let _ = writeln!(csv, "{},{},{}", src_asn, dst_asn, bytes);
+        }
+        csv
+    }
+
+    fn record(&self, fluss: &Fluss) {
+        let asn = |field| {
+            fluss
+                .extra
+                .get(field)
+                .and_then(serde_json::Value::as_u64)
+                .and_then(|asn| u32::try_from(asn).ok())
+                .unwrap_or(0)
+        };
+        let key = (asn(SRC_ASN_FIELD), asn(DST_ASN_FIELD));
+
+        let mut matrix = self.matrix.lock();
+        let bytes = matrix.entry(key).or_default();
+        *bytes = bytes.saturating_add(fluss.normalized_bytes());
+    }
+}
+
+#[async_trait]
+impl Publisher for TrafficMatrix {
+    async fn publish(&self, fluss: &Fluss) -> anyhow::Result<()> {
+        self.record(fluss);
+        self.inner.publish(fluss).await
+    }
+
+    async fn health_check(&self) -> anyhow::Result<()> {
+        self.inner.health_check().await
+    }
+
+    /// Flushes the inner publisher, the matrix is kept.
+    async fn flush(&self) -> anyhow::Result<()> {
+        self.inner.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::publish::CapturingPublisher;
+    use crate::testing::flow;
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+
+    fn fluss(asns: Option<(u64, u64)>, bytes: u64) -> Fluss {
+        let mut fluss = flow(
+            Ipv4Addr::new(192, 0, 2, 1),
+            Ipv4Addr::new(198, 51, 100, 1),
+            443,
+            bytes,
+            1,
+        );
+        if let Some((src_asn, dst_asn)) = asns {
+            fluss.extra.insert(SRC_ASN_FIELD.to_owned(), src_asn.into());
+            fluss.extra.insert(DST_ASN_FIELD.to_owned(), dst_asn.into());
+        }
+        fluss
+    }
+
+    fn matrix() -> (TrafficMatrix, Arc<CapturingPublisher<Fluss>>) {
+        let inner = Arc::new(CapturingPublisher::new());
+        (TrafficMatrix::new(Box::new(Arc::clone(&inner))), inner)
+    }
+
+    #[tokio::test]
+    async fn bytes_are_summed_per_as_pair() {
+        let (matrix, inner) = matrix();
+        let mut sampled = fluss(Some((1234, 5678)), 10);
+        sampled.sampling_interval = Some(100);
+        let flows = [
+            fluss(Some((1234, 5678)), 1500),
+            sampled,
+            fluss(Some((5678, 1234)), 700),
+            fluss(None, 300),
+            fluss(None, 200),
+        ];
+        for fluss in &flows {
+            matrix.publish(fluss).await.unwrap();
+        }
+
+        let window = matrix.flush();
+        assert_eq!(window.len(), 3);
+        assert_eq!(window[&(1234, 5678)], 1500 + 10 * 100);
+        assert_eq!(window[&(5678, 1234)], 700);
+        // flows without AS numbers are counted as AS 0
+        assert_eq!(window[&(0, 0)], 500);
+
+        // all flows are passed on unchanged
+        assert_eq!(inner.items(), flows);
+    }
+
+    #[tokio::test]
+    async fn flush_starts_a_new_window() {
+        let (matrix, _) = matrix();
+        matrix.publish(&fluss(Some((1, 2)), 100)).await.unwrap();
+        assert_eq!(matrix.flush()[&(1, 2)], 100);
+        assert!(matrix.flush().is_empty());
+
+        matrix.publish(&fluss(Some((1, 2)), 50)).await.unwrap();
+        matrix.publish(&fluss(Some((3, 4)), 25)).await.unwrap();
+        let window = matrix.flush();
+        assert_eq!(window.len(), 2);
+        assert_eq!(window[&(1, 2)], 50);
+        assert_eq!(window[&(3, 4)], 25);
+
+        // flushing the publisher keeps the window
+        matrix.publish(&fluss(Some((1, 2)), 10)).await.unwrap();
+        Publisher::flush(&matrix).await.unwrap();
+        assert_eq!(matrix.flush()[&(1, 2)], 10);
+    }
+
+    #[tokio::test]
+    async fn csv_is_ordered_by_as_numbers() {
+        let (matrix, _) = matrix();
+        for (asns, bytes) in [((5678, 1234), 700), ((1234, 5678), 1500), ((1234, 80), 5)] {
+            matrix.publish(&fluss(Some(asns), bytes)).await.unwrap();
+        }
+
+        assert_eq!(
+            matrix.render_csv(),
+            "src_asn,dst_asn,bytes\n1234,80,5\n1234,5678,1500\n5678,1234,700\n"
+        );
+        // rendering does not reset the window
+        assert_eq!(matrix.flush().len(), 3);
+        assert_eq!(matrix.render_csv(), "src_asn,dst_asn,bytes\n");
+    }
+}
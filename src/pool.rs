@@ -0,0 +1,37 @@
+use bytes::{Bytes, BytesMut};
+use std::io;
+use std::net::SocketAddr;
+use tokio::net::UdpSocket;
+
+/// Datagrams received into a chunk before another chunk is allocated.
+const DATAGRAMS_PER_CHUNK: usize = 16;
+
+/// A receive buffer handing out datagrams as reference counted [`Bytes`].
+///
+/// Datagrams are received into a large chunk and split off sized to the
+/// actual datagram, decoders can hold on to cheap clones instead of copying.
+/// The chunk is reused once all datagrams split off from it were dropped,
+/// otherwise a new chunk is allocated.
+pub struct BufferPool {
+    buf: BytesMut,
+    datagram_size: usize,
+}
+
+impl BufferPool {
+    /// Creates a pool for datagrams of up to `datagram_size` bytes.
+    pub fn new(datagram_size: usize) -> Self {
+        Self {
+            buf: BytesMut::with_capacity(datagram_size * DATAGRAMS_PER_CHUNK),
+            datagram_size,
+        }
+    }
+
+    /// Receives the next datagram of the socket.
+    pub async fn recv_from(&mut self, socket: &UdpSocket) -> io::Result<(Bytes, SocketAddr)> {
+        // the datagram is truncated if the remaining capacity is too small
+        self.buf.reserve(self.datagram_size);
+        let (_, addr) = socket.recv_buf_from(&mut self.buf).await?;
+
+        Ok((self.buf.split().freeze(), addr))
+    }
+}
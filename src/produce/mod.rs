@@ -1,3 +0,0 @@
-mod ipfix;
-
-pub use self::ipfix::IpfixParser;
@@ -0,0 +1,219 @@
+//! Forwarding of received IPFIX messages to other collectors.
+
+use crate::pool::BufferPool;
+use anyhow::Context as _;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket as StdUdpSocket};
+use tokio::net::UdpSocket;
+
+/// Exporters which get a forwarding socket, datagrams of further exporters are not forwarded.
+const MAX_EXPORTERS: usize = 1024;
+
+/// Receives IPFIX messages and forwards the original bytes to upstream collectors.
+///
+/// Forwarding is best effort, datagrams which can not be sent right away are
+/// dropped and errors are only logged. Datagrams of every exporter are sent
+/// from a socket of their own, so upstream collectors, which key templates by
+/// the source address, keep the templates of different exporters apart.
+pub struct IpfixProxy {
+    socket: UdpSocket,
+    targets: Vec<SocketAddr>,
+    forwarders: HashMap<SocketAddr, Forwarder>,
+}
+
+impl IpfixProxy {
+    /// Binds `listen` and forwards all received datagrams to `targets`.
+    ///
+    /// Has to be called within a tokio runtime.
+    pub fn new(listen: SocketAddr, targets: Vec<SocketAddr>) -> anyhow::Result<Self> {
+        let socket =
+            StdUdpSocket::bind(listen).with_context(|| format!("failed to bind {}", listen))?;
+        socket.set_nonblocking(true)?;
+        Ok(Self::from_socket(UdpSocket::from_std(socket)?, targets))
+    }
+
+    /// Forwards the datagrams received on an already bound `socket`.
+    pub fn from_socket(socket: UdpSocket, targets: Vec<SocketAddr>) -> Self {
+        Self {
+            socket,
+            targets,
+            forwarders: HashMap::new(),
+        }
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    pub fn targets(&self) -> &[SocketAddr] {
+        &self.targets
+    }
+
+    /// Receives the next datagram and forwards it before it is returned for decoding.
+    pub async fn recv_from(&mut self, pool: &mut BufferPool) -> io::Result<(Bytes, SocketAddr)> {
+        let (data, exporter) = pool.recv_from(&self.socket).await?;
+        self.forward(exporter, &data);
+        Ok((data, exporter))
+    }
+
+    /// Sends `data` received from `exporter` to all targets.
+    pub fn forward(&mut self, exporter: SocketAddr, data: &[u8]) {
+        if self.targets.is_empty() {
+            return;
+        }
+
+        if !self.forwarders.contains_key(&exporter) {
+            if self.forwarders.len() >= MAX_EXPORTERS {
+                tracing::debug!(%exporter, "too many exporters, not forwarding datagram");
+                return;
+            }
+            match Forwarder::bind(&self.targets) {
+                Ok(forwarder) => self.forwarders.insert(exporter, forwarder),
+                Err(err) => {
+                    tracing::warn!(error = %err, %exporter, "failed to bind forwarding socket");
+                    return;
+                }
+            };
+        }
+
+        let forwarder = &self.forwarders[&exporter];
+        for target in &self.targets {
+            if let Err(err) = forwarder.send_to(data, *target) {
+                tracing::debug!(error = %err, %exporter, %target, "failed to forward datagram");
+            }
+        }
+    }
+}
+
+/// Non-blocking sockets of an exporter, one for each address family of the targets.
+struct Forwarder {
+    v4: Option<StdUdpSocket>,
+    v6: Option<StdUdpSocket>,
+}
+
+impl Forwarder {
+    fn bind(targets: &[SocketAddr]) -> io::Result<Self> {
+        let bind = |addr: SocketAddr| -> io::Result<StdUdpSocket> {
+            let socket = StdUdpSocket::bind(addr)?;
+            socket.set_nonblocking(true)?;
+            Ok(socket)
+        };
+
+        Ok(Self {
+            v4: targets
+                .iter()
+                .any(SocketAddr::is_ipv4)
+                .then(|| bind((Ipv4Addr::UNSPECIFIED, 0).into()))
+                .transpose()?,
+            v6: targets
+                .iter()
+                .any(SocketAddr::is_ipv6)
+                .then(|| bind((Ipv6Addr::UNSPECIFIED, 0).into()))
+                .transpose()?,
+        })
+    }
+
+    fn send_to(&self, data: &[u8], target: SocketAddr) -> io::Result<usize> {
+        let socket = match target {
+            SocketAddr::V4(_) => &self.v4,
+            SocketAddr::V6(_) => &self.v6,
+        };
+        socket
+            .as_ref()
+            .expect("a socket is bound for every family of the targets")
+            .send_to(data, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipfix::parser::{parse, TemplateRecord};
+    use crate::testing::{field, MessageBuilder};
+    use std::time::Duration;
+
+    async fn socket() -> UdpSocket {
+        UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap()
+    }
+
+    async fn proxy(targets: Vec<SocketAddr>) -> IpfixProxy {
+        IpfixProxy::from_socket(socket().await, targets)
+    }
+
+    /// An IPFIX message with a template set.
+    fn message() -> Vec<u8> {
+        let template = TemplateRecord {
+            id: 256,
+            fields: vec![field(8, 4), field(12, 4), field(1, 8)],
+        };
+        MessageBuilder::new(0).templates(&[template])
+    }
+
+    /// Returns the next datagram received on `socket`, fails after a second.
+    async fn recv(socket: &UdpSocket) -> (Vec<u8>, SocketAddr) {
+        let mut buf = vec![0; 65536];
+        let (len, from) = tokio::time::timeout(Duration::from_secs(1), socket.recv_from(&mut buf))
+            .await
+            .expect("no datagram was forwarded")
+            .unwrap();
+        buf.truncate(len);
+        (buf, from)
+    }
+
+    #[tokio::test]
+    async fn datagrams_reach_every_target() {
+        let targets = [socket().await, socket().await];
+        let addrs = targets
+            .iter()
+            .map(|target| target.local_addr().unwrap())
+            .collect();
+        let mut proxy = proxy(addrs).await;
+        let mut pool = BufferPool::new(65535);
+
+        let exporters = [socket().await, socket().await];
+        let message = message();
+        for exporter in &exporters {
+            exporter
+                .send_to(&message, proxy.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (data, from) = proxy.recv_from(&mut pool).await.unwrap();
+            assert_eq!(data, message);
+            assert_eq!(from, exporter.local_addr().unwrap());
+        }
+
+        for target in &targets {
+            let (first, first_from) = recv(target).await;
+            let (second, second_from) = recv(target).await;
+            assert_eq!(first, message);
+            assert_eq!(second, message);
+            // every exporter is forwarded from a socket of its own
+            assert_ne!(first_from, second_from);
+        }
+    }
+
+    #[tokio::test]
+    async fn unreachable_targets_do_not_stop_parsing() {
+        let target = socket().await;
+        // sending to the broadcast address fails without SO_BROADCAST
+        let unreachable = (Ipv4Addr::BROADCAST, 4739).into();
+        let mut proxy = proxy(vec![unreachable, target.local_addr().unwrap()]).await;
+        let mut pool = BufferPool::new(65535);
+
+        let exporter = socket().await;
+        let message = message();
+        for _ in 0..2 {
+            exporter
+                .send_to(&message, proxy.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (data, _) = proxy.recv_from(&mut pool).await.unwrap();
+            assert_eq!(parse(&data).unwrap().sets.len(), 1);
+
+            let (forwarded, _) = recv(&target).await;
+            assert_eq!(forwarded, message);
+        }
+    }
+}
@@ -0,0 +1,322 @@
+//! Settings of the collector which are reloaded while it is running.
+//!
+//! The settings are read from the files passed on the command line and handed
+//! to the pipeline through a watch channel, a reload replaces them as a whole.
+
+use crate::enrich::{PrefixEnricher, ServiceEnricher};
+use crate::exporters::{Cidr, Exporters};
+use crate::produce::CustomFields;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Settings applied to every datagram, the next datagram after a reload uses
+/// the new settings.
+#[derive(Debug, PartialEq)]
+pub struct Settings {
+    /// Allowed exporters, sampling overrides, labels and dropped exporters.
+    ///
+    /// Allowed templates only apply to exporters which were not seen before.
+    pub exporters: Exporters,
+    pub enricher: ServiceEnricher,
+    /// Names and labels of the networks of source and destination addresses.
+    pub networks: PrefixEnricher,
+}
+
+/// Files the settings are read from.
+#[derive(Debug, Clone, Default)]
+pub struct Sources {
+    pub exporters: Option<PathBuf>,
+    /// Networks allowed and denied in addition to the ones of the exporters file.
+    pub allow_exporters: Vec<Cidr>,
+    pub deny_exporters: Vec<Cidr>,
+    pub service_map: Option<PathBuf>,
+    pub ephemeral_port_start: Option<u16>,
+    pub networks: Option<PathBuf>,
+    /// Tenant of all exporters without a tenant in the exporters file.
+    pub tenant: Option<String>,
+    /// Custom fields are compiled into the parsers of the exporters, changes
+    /// are only reported and require a restart.
+    pub custom_fields: Option<PathBuf>,
+}
+
+impl Sources {
+    pub fn load(&self) -> anyhow::Result<Settings> {
+        let mut exporters = match &self.exporters {
+            Some(path) => Exporters::load(path)?,
+            None => Exporters::new(),
+        };
+        for network in &self.allow_exporters {
+            exporters.allow(*network);
+        }
+        for network in &self.deny_exporters {
+            exporters.deny(*network);
+        }
+        if let Some(tenant) = &self.tenant {
+            exporters.set_default_tenant(tenant);
+        }
+
+        let mut enricher = ServiceEnricher::new();
+        if let Some(path) = &self.service_map {
+            enricher.load_services(path)?;
+        }
+        if let Some(port) = self.ephemeral_port_start {
+            enricher.set_ephemeral_port_start(port);
+        }
+
+        let mut networks = PrefixEnricher::new();
+        if let Some(path) = &self.networks {
+            networks.load_networks(path)?;
+        }
+
+        Ok(Settings {
+            exporters,
+            enricher,
+            networks,
+        })
+    }
+
+    pub fn load_custom_fields(&self) -> anyhow::Result<CustomFields> {
+        match &self.custom_fields {
+            Some(path) => CustomFields::load(path),
+            None => Ok(CustomFields::new()),
+        }
+    }
+}
+
+/// Outcome of a successful reload.
+#[derive(Debug, Clone, Default)]
+pub struct Reloaded {
+    /// Settings which changed and are in effect now.
+    pub applied: Vec<&'static str>,
+    /// Settings which changed but are only used after a restart.
+    pub restart_required: Vec<&'static str>,
+}
+
+/// Owns the current settings and replaces them on reload.
+pub struct Reloader {
+    sources: Sources,
+    custom_fields: Arc<CustomFields>,
+    settings: watch::Sender<Arc<Settings>>,
+}
+
+impl Reloader {
+    /// Loads the initial settings.
+    pub fn new(sources: Sources) -> anyhow::Result<Self> {
+        let settings = sources.load()?;
+        let custom_fields = sources.load_custom_fields()?;
+        let (settings, _) = watch::channel(Arc::new(settings));
+
+        Ok(Self {
+            sources,
+            custom_fields: Arc::new(custom_fields),
+            settings,
+        })
+    }
+
+    /// Returns the settings currently in effect.
+    pub fn settings(&self) -> Arc<Settings> {
+        Arc::clone(&self.settings.borrow())
+    }
+
+    /// Returns a receiver which is notified about every reload.
+    pub fn subscribe(&self) -> watch::Receiver<Arc<Settings>> {
+        self.settings.subscribe()
+    }
+
+    /// Custom fields the collector was started with.
+    pub fn custom_fields(&self) -> Arc<CustomFields> {
+        Arc::clone(&self.custom_fields)
+    }
+
+    /// Reads all files again and applies the changed settings.
+    ///
+    /// If any file is invalid the current settings stay in effect.
+    pub fn reload(&self) -> anyhow::Result<Reloaded> {
+        let settings = self.sources.load()?;
+        let custom_fields = self.sources.load_custom_fields()?;
+
+        let mut reloaded = Reloaded::default();
+        let current = self.settings();
+        if settings.exporters != current.exporters {
+            reloaded.applied.push("exporters");
+        }
+        if settings.enricher != current.enricher {
+            reloaded.applied.push("service names");
+        }
+        if settings.networks != current.networks {
+            reloaded.applied.push("networks");
+        }
+        if custom_fields != *self.custom_fields {
+            reloaded.restart_required.push("custom fields");
+        }
+
+        if !reloaded.applied.is_empty() {
+            self.settings.send_replace(Arc::new(settings));
+        }
+
+        Ok(reloaded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::flow;
+    use fluss_core::fluss::Fluss;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::path::Path;
+
+    const SERVICES: &str = "[tcp]\n8443 = \"admin\"\n";
+    const NETWORKS: &str = "[[network]]\nprefix = \"198.51.100.0/24\"\nname = \"dc\"\n";
+    const EXPORTERS: &str = "allow = [\"10.0.0.0/8\"]\n";
+    const CUSTOM_FIELDS: &str = r#"
+        [[custom_field]]
+        pen = 25461
+        id = 5671
+        name = "rule_name"
+        type = "string"
+    "#;
+
+    fn write(path: &Path, content: &str) {
+        std::fs::write(path, content).unwrap();
+    }
+
+    /// Writes the initial files to `dir` and returns the sources reading them.
+    fn sources(dir: &Path) -> Sources {
+        let sources = Sources {
+            exporters: Some(dir.join("exporters.toml")),
+            service_map: Some(dir.join("services.toml")),
+            networks: Some(dir.join("networks.toml")),
+            custom_fields: Some(dir.join("custom.toml")),
+            ..Sources::default()
+        };
+        write(sources.exporters.as_ref().unwrap(), EXPORTERS);
+        write(sources.service_map.as_ref().unwrap(), SERVICES);
+        write(sources.networks.as_ref().unwrap(), NETWORKS);
+        write(sources.custom_fields.as_ref().unwrap(), CUSTOM_FIELDS);
+        sources
+    }
+
+    /// Enriches a flow to 198.51.100.1:8443 like the pipeline does.
+    fn enriched(settings: &Settings) -> Fluss {
+        let mut flow = flow(
+            Ipv4Addr::new(192, 0, 2, 1),
+            Ipv4Addr::new(198, 51, 100, 1),
+            8443,
+            1500,
+            3,
+        );
+        settings.enricher.enrich(&mut flow);
+        settings.networks.enrich(&mut flow);
+        flow
+    }
+
+    fn exporter(a: u8, b: u8, c: u8, d: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(a, b, c, d))
+    }
+
+    #[test]
+    fn reload_applies_to_the_next_flow() {
+        let dir = tempfile::tempdir().unwrap();
+        let sources = sources(dir.path());
+        let reloader = Reloader::new(sources.clone()).unwrap();
+        let receiver = reloader.subscribe();
+
+        let before = reloader.settings();
+        let flow = enriched(&before);
+        assert_eq!(flow.service.as_deref(), Some("admin"));
+        assert_eq!(flow.dst_net_name.as_deref(), Some("dc"));
+        assert!(before.exporters.is_allowed(exporter(10, 1, 1, 1)));
+        assert!(!before.exporters.is_allowed(exporter(192, 0, 2, 1)));
+
+        write(
+            sources.service_map.as_ref().unwrap(),
+            "[tcp]\n8443 = \"metrics\"\n",
+        );
+        write(
+            sources.networks.as_ref().unwrap(),
+            &NETWORKS.replace("dc", "lab"),
+        );
+        write(
+            sources.exporters.as_ref().unwrap(),
+            "allow = [\"192.0.2.0/24\"]\n",
+        );
+        let reloaded = reloader.reload().unwrap();
+        assert_eq!(reloaded.applied, ["exporters", "service names", "networks"]);
+        assert!(reloaded.restart_required.is_empty());
+        assert!(receiver.has_changed().unwrap());
+
+        let after = reloader.settings();
+        let flow = enriched(&after);
+        assert_eq!(flow.service.as_deref(), Some("metrics"));
+        assert_eq!(flow.dst_net_name.as_deref(), Some("lab"));
+        assert!(!after.exporters.is_allowed(exporter(10, 1, 1, 1)));
+        assert!(after.exporters.is_allowed(exporter(192, 0, 2, 1)));
+
+        // a flow in progress keeps the settings it started with
+        assert_eq!(enriched(&before).service.as_deref(), Some("admin"));
+    }
+
+    #[test]
+    fn unchanged_files_are_not_applied() {
+        let dir = tempfile::tempdir().unwrap();
+        let reloader = Reloader::new(sources(dir.path())).unwrap();
+        let receiver = reloader.subscribe();
+        let before = reloader.settings();
+
+        let reloaded = reloader.reload().unwrap();
+        assert!(reloaded.applied.is_empty());
+        assert!(reloaded.restart_required.is_empty());
+        assert!(!receiver.has_changed().unwrap());
+        assert!(Arc::ptr_eq(&before, &reloader.settings()));
+    }
+
+    #[test]
+    fn custom_field_changes_require_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let sources = sources(dir.path());
+        let reloader = Reloader::new(sources.clone()).unwrap();
+        let before = reloader.settings();
+        let custom_fields = reloader.custom_fields();
+
+        write(
+            sources.custom_fields.as_ref().unwrap(),
+            &CUSTOM_FIELDS.replace("rule_name", "policy"),
+        );
+        let reloaded = reloader.reload().unwrap();
+        assert!(reloaded.applied.is_empty());
+        assert_eq!(reloaded.restart_required, ["custom fields"]);
+
+        // neither the custom fields nor the other settings are replaced
+        assert!(Arc::ptr_eq(&custom_fields, &reloader.custom_fields()));
+        assert!(Arc::ptr_eq(&before, &reloader.settings()));
+    }
+
+    #[test]
+    fn failed_reload_keeps_the_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let sources = sources(dir.path());
+        let reloader = Reloader::new(sources.clone()).unwrap();
+        let receiver = reloader.subscribe();
+        let before = reloader.settings();
+
+        // the networks are valid, the service map is not
+        write(
+            sources.networks.as_ref().unwrap(),
+            &NETWORKS.replace("dc", "lab"),
+        );
+        write(
+            sources.service_map.as_ref().unwrap(),
+            "[tcp]\nhttps = \"web\"\n",
+        );
+        assert!(reloader.reload().is_err());
+        assert!(!receiver.has_changed().unwrap());
+
+        let settings = reloader.settings();
+        assert!(Arc::ptr_eq(&before, &settings));
+        let flow = enriched(&settings);
+        assert_eq!(flow.service.as_deref(), Some("admin"));
+        assert_eq!(flow.dst_net_name.as_deref(), Some("dc"));
+    }
+}
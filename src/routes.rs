@@ -0,0 +1,625 @@
+//! Routing of flows to multiple publishers, configured in a TOML file.
+
+use crate::exporters::Cidr;
+use crate::fluss::Fluss;
+use crate::publish::route::Selector;
+use crate::publish::{Publisher, RoutingPublisher, SyslogTransport};
+use crate::store::FlowFilter;
+use serde::Deserialize;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    publisher: Vec<PublisherConfig>,
+    route: Vec<RouteConfig>,
+}
+
+/// A named publisher of the `[[publisher]]` sections.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PublisherConfig {
+    pub name: String,
+    /// Flows sent to the backend at once, the default of the publisher if not set.
+    pub batch_size: Option<usize>,
+    #[serde(flatten)]
+    pub kind: PublisherKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PublisherKind {
+    Elastic {
+        url: String,
+        index: Option<String>,
+        data_stream: Option<String>,
+    },
+    ClickHouse {
+        url: String,
+        table: String,
+    },
+    Redis {
+        url: String,
+        stream: String,
+        max_len: Option<usize>,
+    },
+    Parquet {
+        dir: String,
+    },
+    Syslog {
+        target: SocketAddr,
+        #[serde(default = "default_syslog_transport")]
+        transport: SyslogTransport,
+    },
+    Console,
+}
+
+fn default_syslog_transport() -> SyslogTransport {
+    SyslogTransport::Udp
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RouteConfig {
+    #[serde(rename = "match")]
+    condition: Option<String>,
+    publisher: Option<String>,
+    publishers: Option<Vec<String>>,
+    hash: Option<String>,
+}
+
+impl RouteConfig {
+    fn selector(self) -> anyhow::Result<Selector> {
+        match (self.publisher, self.publishers, self.hash) {
+            (Some(name), None, None) => Ok(Selector::Publisher(name)),
+            (None, Some(names), Some(field)) => Ok(Selector::Hash(field.parse()?, names)),
+            (None, Some(names), None) => Ok(Selector::RoundRobin(names)),
+            (Some(_), _, Some(_)) => anyhow::bail!("hash requires publishers, not publisher"),
+            _ => anyhow::bail!("a route needs either publisher or publishers"),
+        }
+    }
+}
+
+/// Publishers and the routes selecting them, see [`RoutingPublisher`].
+#[derive(Debug)]
+pub struct Routes {
+    publishers: Vec<PublisherConfig>,
+    routes: Vec<(Expr, Selector)>,
+    default: Selector,
+}
+
+impl Routes {
+    /// Loads the `[[publisher]]` and `[[route]]` sections from a TOML file.
+    ///
+    /// Routes are tried in order. The last route has no `match` and takes
+    /// all flows which did not match any other route, it is required.
+    ///
+    /// ```toml
+    /// [[publisher]]
+    /// name = "cluster-a"
+    /// type = "elastic"
+    /// url = "http://cluster-a:9200"
+    ///
+    /// [[publisher]]
+    /// name = "cluster-b"
+    /// type = "elastic"
+    /// url = "http://cluster-b:9200"
+    ///
+    /// [[route]]
+    /// match = "vlan_id >= 100 && vlan_id < 200"
+    /// publisher = "cluster-a"
+    ///
+    /// [[route]]
+    /// publishers = ["cluster-a", "cluster-b"]
+    /// hash = "exporter"
+    /// ```
+    ///
+    /// A route with `publishers` and without `hash` selects them round-robin.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let config: Config = toml::from_str(&content)?;
+
+        for (i, publisher) in config.publisher.iter().enumerate() {
+            if config.publisher[..i]
+                .iter()
+                .any(|other| other.name == publisher.name)
+            {
+                anyhow::bail!("duplicate publisher: {}", publisher.name);
+            }
+        }
+
+        let mut routes = Vec::with_capacity(config.route.len());
+        let mut default = None;
+        for route in config.route {
+            if default.is_some() {
+                anyhow::bail!("the route without match has to be the last route");
+            }
+            match route.condition.clone() {
+                Some(condition) => {
+                    let expr = condition
+                        .parse()
+                        .map_err(|err| anyhow::anyhow!("invalid match {:?}: {}", condition, err))?;
+                    routes.push((expr, route.selector()?));
+                }
+                None => default = Some(route.selector()?),
+            }
+        }
+        let default =
+            default.ok_or_else(|| anyhow::anyhow!("a default route without match is required"))?;
+
+        Ok(Self {
+            publishers: config.publisher,
+            routes,
+            default,
+        })
+    }
+
+    /// The publishers, which have to be created by the caller.
+    pub fn publishers(&self) -> &[PublisherConfig] {
+        &self.publishers
+    }
+
+    /// Creates the router from the created `publishers`, named like the configured ones.
+    pub fn into_publisher(
+        self,
+        publishers: Vec<(String, Arc<dyn Publisher + Send + Sync>)>,
+    ) -> anyhow::Result<RoutingPublisher> {
+        let mut router = RoutingPublisher::new(publishers, self.default)?;
+        for (expr, selector) in self.routes {
+            router.add_route(move |fluss| expr.matches(fluss), selector)?;
+        }
+        Ok(router)
+    }
+}
+
+/// A condition of a route, e.g. `vlan_id >= 100 && vlan_id < 200`.
+///
+/// Numeric fields are compared with `==`, `!=`, `<`, `<=`, `>` and `>=`,
+/// addresses with `==` and `!=` to an address or network and the tenant with
+/// `==` and `!=` to a name, e.g. `tenant == "lab"`. Conditions are combined
+/// with `&&`, `||` and parentheses, `&&` binds stronger.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Number(NumberField, Op, u64),
+    Addr(AddrField, bool, Cidr),
+    Tenant(bool, String),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NumberField {
+    VlanId,
+    PostVlanId,
+    IngressInterface,
+    EgressInterface,
+    SrcPort,
+    DstPort,
+    Protocol,
+    Dscp,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AddrField {
+    Exporter,
+    SrcAddr,
+    DstAddr,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Expr {
+    pub fn matches(&self, fluss: &Fluss) -> bool {
+        match self {
+            Self::And(exprs) => exprs.iter().all(|expr| expr.matches(fluss)),
+            Self::Or(exprs) => exprs.iter().any(|expr| expr.matches(fluss)),
+            Self::Number(field, op, value) => {
+                let actual = field.get(fluss);
+                match op {
+                    Op::Eq => actual == *value,
+                    Op::Ne => actual != *value,
+                    Op::Lt => actual < *value,
+                    Op::Le => actual <= *value,
+                    Op::Gt => actual > *value,
+                    Op::Ge => actual >= *value,
+                }
+            }
+            Self::Addr(field, negate, network) => {
+                let contains = field.get(fluss).is_some_and(|addr| network.contains(addr));
+                contains != *negate
+            }
+            Self::Tenant(negate, tenant) => (fluss.tenant.as_ref() == Some(tenant)) != *negate,
+        }
+    }
+}
+
+impl FlowFilter for Expr {
+    fn matches(&self, fluss: &Fluss) -> bool {
+        Expr::matches(self, fluss)
+    }
+}
+
+impl NumberField {
+    fn get(self, fluss: &Fluss) -> u64 {
+        match self {
+            Self::VlanId => fluss.vlan_id.into(),
+            Self::PostVlanId => fluss.post_vlan_id.into(),
+            Self::IngressInterface => fluss.ingress_interface.into(),
+            Self::EgressInterface => fluss.egress_interface.into(),
+            Self::SrcPort => fluss.src_port.into(),
+            Self::DstPort => fluss.dst_port.into(),
+            Self::Protocol => u8::from(fluss.protocol).into(),
+            Self::Dscp => fluss.dscp.into(),
+        }
+    }
+}
+
+impl AddrField {
+    fn get(self, fluss: &Fluss) -> Option<IpAddr> {
+        match self {
+            Self::Exporter => fluss.exporter,
+            Self::SrcAddr => Some(fluss.src_addr),
+            Self::DstAddr => Some(fluss.dst_addr),
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+        };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => anyhow::bail!("unexpected {:?}", token),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> anyhow::Result<Vec<&str>> {
+    const SYMBOLS: &[&str] = &["&&", "||", "==", "!=", "<=", ">=", "<", ">", "(", ")"];
+
+    let mut tokens = Vec::new();
+    let mut rest = s.trim_start();
+    while !rest.is_empty() {
+        let len = match SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+            Some(symbol) => symbol.len(),
+            None => rest
+                .find(|c: char| c.is_whitespace() || "&|=!<>()".contains(c))
+                .unwrap_or(rest.len()),
+        };
+        if len == 0 {
+            anyhow::bail!("unexpected {:?}", &rest[..1]);
+        }
+        tokens.push(&rest[..len]);
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [&'a str],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> anyhow::Result<&'a str> {
+        let token = self
+            .peek()
+            .ok_or_else(|| anyhow::anyhow!("unexpected end of the condition"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn or(&mut self) -> anyhow::Result<Expr> {
+        let mut exprs = vec![self.and()?];
+        while self.peek() == Some("||") {
+            self.pos += 1;
+            exprs.push(self.and()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::Or(exprs)
+        })
+    }
+
+    fn and(&mut self) -> anyhow::Result<Expr> {
+        let mut exprs = vec![self.comparison()?];
+        while self.peek() == Some("&&") {
+            self.pos += 1;
+            exprs.push(self.comparison()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::And(exprs)
+        })
+    }
+
+    fn comparison(&mut self) -> anyhow::Result<Expr> {
+        let field = self.next()?;
+        if field == "(" {
+            let expr = self.or()?;
+            return match self.next()? {
+                ")" => Ok(expr),
+                token => anyhow::bail!("expected ) instead of {:?}", token),
+            };
+        }
+
+        let op = match self.next()? {
+            "==" => Op::Eq,
+            "!=" => Op::Ne,
+            "<" => Op::Lt,
+            "<=" => Op::Le,
+            ">" => Op::Gt,
+            ">=" => Op::Ge,
+            token => anyhow::bail!("expected a comparison instead of {:?}", token),
+        };
+        let value = self.next()?;
+
+        let number = match field {
+            "vlan_id" => Some(NumberField::VlanId),
+            "post_vlan_id" => Some(NumberField::PostVlanId),
+            "ingress_interface" => Some(NumberField::IngressInterface),
+            "egress_interface" => Some(NumberField::EgressInterface),
+            "src_port" => Some(NumberField::SrcPort),
+            "dst_port" => Some(NumberField::DstPort),
+            "protocol" => Some(NumberField::Protocol),
+            "dscp" => Some(NumberField::Dscp),
+            _ => None,
+        };
+        if let Some(number) = number {
+            let value = value.parse().map_err(|_| {
+                anyhow::anyhow!("{} is compared to a number, not {:?}", field, value)
+            })?;
+            return Ok(Expr::Number(number, op, value));
+        }
+
+        if field == "tenant" {
+            let negate = match op {
+                Op::Eq => false,
+                Op::Ne => true,
+                _ => anyhow::bail!("the tenant is only compared with == and !="),
+            };
+            let tenant = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+            return Ok(Expr::Tenant(negate, tenant.to_owned()));
+        }
+
+        let addr = match field {
+            "exporter" => AddrField::Exporter,
+            "src_addr" => AddrField::SrcAddr,
+            "dst_addr" => AddrField::DstAddr,
+            _ => anyhow::bail!("unknown field {:?}", field),
+        };
+        let negate = match op {
+            Op::Eq => false,
+            Op::Ne => true,
+            _ => anyhow::bail!("addresses are only compared with == and !="),
+        };
+        Ok(Expr::Addr(addr, negate, value.parse()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::publish::CapturingPublisher;
+    use crate::testing::flow;
+    use std::io::Write;
+    use std::net::Ipv4Addr;
+
+    fn load(config: &str) -> anyhow::Result<Routes> {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(config.as_bytes()).unwrap();
+        Routes::load(file.path())
+    }
+
+    fn vlan(vlan_id: u16) -> Fluss {
+        let mut fluss = flow(
+            Ipv4Addr::new(192, 0, 2, 1),
+            Ipv4Addr::new(198, 51, 100, 1),
+            443,
+            1500,
+            1,
+        );
+        fluss.vlan_id = vlan_id;
+        fluss
+    }
+
+    fn matches(condition: &str, fluss: &Fluss) -> bool {
+        condition.parse::<Expr>().unwrap().matches(fluss)
+    }
+
+    const CLUSTERS: &str = r#"
+        [[publisher]]
+        name = "cluster-a"
+        type = "elastic"
+        url = "http://cluster-a:9200"
+
+        [[publisher]]
+        name = "cluster-b"
+        type = "elastic"
+        url = "http://cluster-b:9200"
+        batch_size = 100
+    "#;
+
+    #[tokio::test]
+    async fn flows_are_routed_by_vlan() {
+        let config = format!(
+            r#"{}
+            [[route]]
+            match = "vlan_id >= 100 && vlan_id < 200"
+            publisher = "cluster-a"
+
+            [[route]]
+            publisher = "cluster-b"
+            "#,
+            CLUSTERS
+        );
+        let routes = load(&config).unwrap();
+        let names: Vec<_> = routes
+            .publishers()
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(names, ["cluster-a", "cluster-b"]);
+        assert_eq!(routes.publishers()[1].batch_size, Some(100));
+
+        let a = Arc::new(CapturingPublisher::new());
+        let b = Arc::new(CapturingPublisher::new());
+        let router = routes
+            .into_publisher(vec![
+                ("cluster-a".to_owned(), Arc::clone(&a) as _),
+                ("cluster-b".to_owned(), Arc::clone(&b) as _),
+            ])
+            .unwrap();
+
+        for vlan_id in [50, 150, 250] {
+            router.publish(&vlan(vlan_id)).await.unwrap();
+        }
+        let vlans = |publisher: &CapturingPublisher<Fluss>| {
+            publisher
+                .items()
+                .iter()
+                .map(|fluss| fluss.vlan_id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vlans(&a), [150]);
+        assert_eq!(vlans(&b), [50, 250]);
+
+        let flows: Vec<_> = router
+            .route_stats()
+            .iter()
+            .map(|stats| stats.flows)
+            .collect();
+        assert_eq!(flows, [1, 2]);
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        let route = |routes: &str| format!("{}\n{}", CLUSTERS, routes);
+
+        // no default route
+        let config = route("[[route]]\nmatch = \"vlan_id == 1\"\npublisher = \"cluster-a\"");
+        assert!(load(&config).is_err());
+        // the default route is not the last route
+        let config = route(
+            "[[route]]\npublisher = \"cluster-a\"\n\
+             [[route]]\nmatch = \"vlan_id == 1\"\npublisher = \"cluster-b\"",
+        );
+        assert!(load(&config).is_err());
+        // hash without publishers
+        let config = route("[[route]]\npublisher = \"cluster-a\"\nhash = \"exporter\"");
+        assert!(load(&config).is_err());
+        let config = route("[[route]]\nmatch = \"vlan\"\npublisher = \"cluster-a\"");
+        assert!(load(&config).is_err());
+        // duplicate publisher
+        let config = format!(
+            "{}{}[[route]]\npublisher = \"cluster-a\"",
+            CLUSTERS, CLUSTERS
+        );
+        assert!(load(&config).is_err());
+
+        // unknown publishers are only detected with the created publishers
+        let routes = load(&route("[[route]]\npublisher = \"cluster-c\"")).unwrap();
+        let a = Arc::new(CapturingPublisher::<Fluss>::new());
+        assert!(routes
+            .into_publisher(vec![("cluster-a".to_owned(), a as _)])
+            .is_err());
+    }
+
+    #[test]
+    fn selectors() {
+        let config = format!(
+            "{}[[route]]\nmatch = \"dscp == 46\"\npublishers = [\"cluster-a\", \"cluster-b\"]\n\
+             [[route]]\npublishers = [\"cluster-a\", \"cluster-b\"]\nhash = \"src_addr\"",
+            CLUSTERS
+        );
+        let routes = load(&config).unwrap();
+        let names = || vec!["cluster-a".to_owned(), "cluster-b".to_owned()];
+        assert_eq!(routes.routes[0].1, Selector::RoundRobin(names()));
+        assert_eq!(
+            routes.default,
+            Selector::Hash(crate::publish::route::HashField::SrcAddr, names())
+        );
+    }
+
+    #[test]
+    fn conditions() {
+        let mut fluss = vlan(150);
+        fluss.exporter = Some(Ipv4Addr::new(10, 0, 0, 1).into());
+        fluss.tenant = Some("lab".to_owned());
+
+        assert!(matches("vlan_id == 150", &fluss));
+        assert!(matches("vlan_id != 151", &fluss));
+        assert!(matches("vlan_id > 149 && vlan_id <= 150", &fluss));
+        assert!(!matches("vlan_id < 150", &fluss));
+        assert!(matches("dst_port == 443 && protocol == 6", &fluss));
+        assert!(matches("exporter == 10.0.0.0/8", &fluss));
+        assert!(!matches("exporter != 10.0.0.1", &fluss));
+        assert!(matches("src_addr == 192.0.2.0/24", &fluss));
+        assert!(!matches("dst_addr == 2001:db8::/32", &fluss));
+        assert!(matches("tenant == \"lab\"", &fluss));
+        assert!(matches("tenant != other", &fluss));
+
+        // && binds stronger than ||
+        assert!(matches(
+            "vlan_id == 1 && dscp == 1 || vlan_id == 150",
+            &fluss
+        ));
+        assert!(!matches(
+            "vlan_id == 1 && (dscp == 1 || vlan_id == 150)",
+            &fluss
+        ));
+        assert!(matches("(vlan_id==1||vlan_id==150)&&dst_port==443", &fluss));
+
+        // flows without an exporter never match an exporter network
+        fluss.exporter = None;
+        assert!(!matches("exporter == 0.0.0.0/0", &fluss));
+        assert!(matches("exporter != 0.0.0.0/0", &fluss));
+    }
+
+    #[test]
+    fn invalid_conditions() {
+        for condition in [
+            "",
+            "vlan_id",
+            "vlan_id ==",
+            "vlan_id == lab",
+            "vlan_id = 1",
+            "(vlan_id == 1",
+            "vlan_id == 1)",
+            "vlan_id == 1 &&",
+            "src_addr < 10.0.0.0/8",
+            "src_addr == 10.0.0.0/33",
+            "tenant > lab",
+            "bytes == 1",
+            "vlan_id == 1 # comment",
+        ] {
+            assert!(condition.parse::<Expr>().is_err(), "{:?}", condition);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Graceful shutdown of the collector.
+//!
+//! The receive loop stops on shutdown, the queued datagrams are decoded and
+//! the publisher is flushed before the collector exits.
+
+use tokio::signal::unix::{signal, SignalKind};
+use tokio_util::sync::CancellationToken;
+
+/// Notifies all clones of the token once the shutdown started.
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownToken(CancellationToken);
+
+impl ShutdownToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts the shutdown, starting it again does nothing.
+    pub fn shutdown(&self) {
+        self.0.cancel();
+    }
+
+    pub fn is_shutdown(&self) -> bool {
+        self.0.is_cancelled()
+    }
+
+    /// Completes once the shutdown started.
+    pub async fn wait(&self) {
+        self.0.cancelled().await
+    }
+
+    /// Starts the shutdown on SIGINT or SIGTERM.
+    ///
+    /// Has to be called within a tokio runtime.
+    pub fn shutdown_on_signal(&self) -> std::io::Result<()> {
+        let mut interrupt = signal(SignalKind::interrupt())?;
+        let mut terminate = signal(SignalKind::terminate())?;
+
+        let token = self.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = interrupt.recv() => tracing::debug!("received SIGINT"),
+                _ = terminate.recv() => tracing::debug!("received SIGTERM"),
+            }
+            token.shutdown();
+        });
+
+        Ok(())
+    }
+}
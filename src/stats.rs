@@ -0,0 +1,297 @@
+//! Per exporter statistics of the collect pipeline.
+
+use crate::control::{ExporterStats, PipelineStats};
+use crate::flow_key::FlowKey;
+use crate::fluss::Fluss;
+use crate::publish::Publisher;
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use std::cmp::{Ordering as CmpOrdering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Counters of a single exporter.
+///
+/// Decode workers keep the counters of their exporters, updating them is a
+/// few relaxed atomic operations.
+#[derive(Debug, Default)]
+pub struct ExporterCounters {
+    pub packets: AtomicU64,
+    pub bytes: AtomicU64,
+    pub messages: AtomicU64,
+    pub parse_errors: AtomicU64,
+    pub flows: AtomicU64,
+    pub published: AtomicU64,
+    // milliseconds since the epoch, zero until the first packet
+    first_packet: AtomicI64,
+    last_packet: AtomicI64,
+}
+
+impl ExporterCounters {
+    /// Counts a received datagram of `len` bytes.
+    pub fn record_packet(&self, len: usize) {
+        let now = Utc::now().timestamp_millis();
+        let _ = self
+            .first_packet
+            .compare_exchange(0, now, Ordering::Relaxed, Ordering::Relaxed);
+        self.last_packet.store(now, Ordering::Relaxed);
+
+        self.packets.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(len as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self, exporter: SocketAddr, templates: usize) -> ExporterStats {
+        let time = |millis: &AtomicI64| match millis.load(Ordering::Relaxed) {
+            0 => None,
+            millis => Utc.timestamp_millis_opt(millis).single(),
+        };
+
+        ExporterStats {
+            exporter,
+            packets: self.packets.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            messages: self.messages.load(Ordering::Relaxed),
+            parse_errors: self.parse_errors.load(Ordering::Relaxed),
+            flows: self.flows.load(Ordering::Relaxed),
+            published: self.published.load(Ordering::Relaxed),
+            first_packet: time(&self.first_packet),
+            last_packet: time(&self.last_packet),
+            templates,
+        }
+    }
+}
+
+/// The counters of all exporters seen since the collector was started.
+#[derive(Debug, Default)]
+pub struct StatsRegistry {
+    exporters: RwLock<HashMap<SocketAddr, Arc<ExporterCounters>>>,
+}
+
+impl StatsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the counters of `exporter`, they are created on first use.
+    pub fn exporter(&self, exporter: SocketAddr) -> Arc<ExporterCounters> {
+        if let Some(counters) = self.exporters.read().get(&exporter) {
+            return Arc::clone(counters);
+        }
+
+        Arc::clone(self.exporters.write().entry(exporter).or_default())
+    }
+
+    /// Drops the counters of `exporter`, e.g. after it was idle for too long.
+    pub fn remove(&self, exporter: SocketAddr) {
+        self.exporters.write().remove(&exporter);
+    }
+
+    /// Returns the statistics of all exporters ordered by address,
+    /// `templates` returns the number of known templates of an exporter.
+    pub fn snapshot(&self, templates: impl Fn(SocketAddr) -> usize) -> Vec<ExporterStats> {
+        let mut exporters = self
+            .exporters
+            .read()
+            .iter()
+            .map(|(&exporter, counters)| counters.snapshot(exporter, templates(exporter)))
+            .collect::<Vec<_>>();
+        exporters.sort_by_key(|stats| stats.exporter);
+        exporters
+    }
+}
+
+/// Flow accounting of a whole run, written when the collector shuts down.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsReport {
+    pub started: DateTime<Utc>,
+    pub finished: DateTime<Utc>,
+    pub pipeline: PipelineStats,
+    pub exporters: Vec<ExporterStats>,
+}
+
+impl StatsReport {
+    /// Writes the report as JSON to `path`.
+    pub fn write(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+}
+
+/// Formats the statistics as a table with one exporter per row.
+pub fn format_table(exporters: &[ExporterStats]) -> String {
+    let time = |time: Option<DateTime<Utc>>| match time {
+        Some(time) => time.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        None => "-".to_owned(),
+    };
+
+    let mut table = format!(
+        "{:<40} {:>10} {:>12} {:>10} {:>8} {:>10} {:>10} {:>9}  {:<20}  {}",
+        "exporter",
+        "packets",
+        "bytes",
+        "messages",
+        "errors",
+        "flows",
+        "published",
+        "templates",
+        "first packet",
+        "last packet"
+    );
+    for stats in exporters {
+        let _ = write!(
+            table,
+            "\n{:<40} {:>10} {:>12} {:>10} {:>8} {:>10} {:>10} {:>9}  {:<20}  {}",
+            stats.exporter.to_string(),
+            stats.packets,
+            stats.bytes,
+            stats.messages,
+            stats.parse_errors,
+            stats.flows,
+            stats.published,
+            stats.templates,
+            time(stats.first_packet),
+            time(stats.last_packet)
+        );
+    }
+
+    table
+}
+
+/// Bytes of a flow key, ordered by the bytes only.
+#[derive(Debug, Copy, Clone)]
+struct FlowStats {
+    key: FlowKey,
+    bytes: u64,
+}
+
+impl PartialEq for FlowStats {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl Eq for FlowStats {}
+
+impl PartialOrd for FlowStats {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FlowStats {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.bytes.cmp(&other.bytes)
+    }
+}
+
+struct TopNWindow {
+    started: Instant,
+    bytes: HashMap<FlowKey, u64>,
+}
+
+/// The flows which transferred the most bytes in a time window, e.g. the
+/// src/dst pairs using the most bandwidth in the last 5 minutes.
+///
+/// Bytes are summed per [`FlowKey`] and scaled up by the sampling interval.
+/// A flow pushed after the window elapsed starts a new window.
+///
+/// As a [`Publisher`] the top flows are logged with `tracing::info!` whenever
+/// a window ends and on flush.
+pub struct TopNStats {
+    n: usize,
+    window: Duration,
+    state: Mutex<TopNWindow>,
+}
+
+impl TopNStats {
+    pub fn new(n: usize, window: Duration) -> Self {
+        Self {
+            n,
+            window,
+            state: Mutex::new(TopNWindow {
+                started: Instant::now(),
+                bytes: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Adds the bytes of `flow` to its key in the current window.
+    pub fn push(&mut self, flow: &Fluss) {
+        let n = self.n;
+        let window = self.window;
+        Self::record(n, window, self.state.get_mut(), flow);
+    }
+
+    /// The top flow keys of the current window, sorted by bytes descending.
+    pub fn top(&self) -> Vec<(FlowKey, u64)> {
+        Self::top_of(self.n, &self.state.lock().bytes)
+    }
+
+    fn record(n: usize, window: Duration, state: &mut TopNWindow, flow: &Fluss) {
+        if state.started.elapsed() >= window {
+            log_top(&Self::top_of(n, &state.bytes), window);
+            state.started = Instant::now();
+            state.bytes.clear();
+        }
+        *state.bytes.entry(FlowKey::from(flow)).or_default() += flow.normalized_bytes();
+    }
+
+    fn top_of(n: usize, bytes: &HashMap<FlowKey, u64>) -> Vec<(FlowKey, u64)> {
+        // a min-heap of the n largest entries, the smallest one is replaced first
+        let mut heap = BinaryHeap::with_capacity(n + 1);
+        for (&key, &bytes) in bytes {
+            heap.push(Reverse(FlowStats { key, bytes }));
+            if heap.len() > n {
+                heap.pop();
+            }
+        }
+
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(stats)| (stats.key, stats.bytes))
+            .collect()
+    }
+}
+
+fn log_top(top: &[(FlowKey, u64)], window: Duration) {
+    if top.is_empty() {
+        return;
+    }
+
+    let mut table = format!(
+        "{:<40} {:<40} {:>8} {:>12}",
+        "source", "destination", "protocol", "bytes"
+    );
+    for (key, bytes) in top {
+        let _ = write!(
+            table,
+            "\n{:<40} {:<40} {:>8} {:>12}",
+            SocketAddr::new(key.src_addr, key.src_port).to_string(),
+            SocketAddr::new(key.dst_addr, key.dst_port).to_string(),
+            key.protocol.to_string(),
+            bytes
+        );
+    }
+    tracing::info!("top {} flows of {:?}\n{}", top.len(), window, table);
+}
+
+#[async_trait]
+impl Publisher for TopNStats {
+    async fn publish(&self, fluss: &Fluss) -> anyhow::Result<()> {
+        Self::record(self.n, self.window, &mut self.state.lock(), fluss);
+        Ok(())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        log_top(&self.top(), self.window);
+        Ok(())
+    }
+}
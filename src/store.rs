@@ -0,0 +1,193 @@
+//! Recently seen flows kept in memory.
+
+use crate::flow_key::FlowKey;
+use crate::fluss::Fluss;
+use crate::publish::Publisher;
+use async_trait::async_trait;
+use lru::LruCache;
+use parking_lot::Mutex;
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
+
+/// Selects the flows returned by [`InMemoryStore::query`].
+pub trait FlowFilter {
+    fn matches(&self, fluss: &Fluss) -> bool;
+}
+
+impl<F> FlowFilter for F
+where
+    F: Fn(&Fluss) -> bool,
+{
+    fn matches(&self, fluss: &Fluss) -> bool {
+        self(fluss)
+    }
+}
+
+struct Entry {
+    fluss: Fluss,
+    updated: Instant,
+}
+
+/// Keeps the latest flow of up to `capacity` connections.
+///
+/// A flow replaces the stored flow of the same [`FlowKey`], the least recently
+/// updated connection is evicted once the store is full. Flows which were not
+/// updated within the ttl are dropped.
+pub struct InMemoryStore {
+    ttl: Duration,
+    flows: Mutex<LruCache<FlowKey, Entry>>,
+}
+
+impl InMemoryStore {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+
+        Self {
+            ttl,
+            flows: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Stores `fluss`, replacing the previous flow of the connection.
+    pub fn insert(&self, fluss: Fluss) {
+        let mut flows = self.flows.lock();
+        self.expire_locked(&mut flows);
+        flows.put(
+            FlowKey::from(&fluss),
+            Entry {
+                fluss,
+                updated: Instant::now(),
+            },
+        );
+    }
+
+    /// Returns all flows matching `filter`, the most recently updated first.
+    pub fn query(&self, filter: &dyn FlowFilter) -> Vec<Fluss> {
+        let mut flows = self.flows.lock();
+        self.expire_locked(&mut flows);
+        flows
+            .iter()
+            .map(|(_, entry)| &entry.fluss)
+            .filter(|fluss| filter.matches(fluss))
+            .cloned()
+            .collect()
+    }
+
+    /// Removes all flows which were not updated within the ttl.
+    pub fn expire(&self) {
+        self.expire_locked(&mut self.flows.lock());
+    }
+
+    fn expire_locked(&self, flows: &mut LruCache<FlowKey, Entry>) {
+        // every update moves the entry to the front, the least recently used
+        // entry is always the oldest one
+        while let Some((_, entry)) = flows.peek_lru() {
+            if entry.updated.elapsed() < self.ttl {
+                break;
+            }
+            flows.pop_lru();
+        }
+    }
+
+    /// Number of stored flows, including expired flows which were not removed yet.
+    pub fn len(&self) -> usize {
+        self.flows.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flows.lock().is_empty()
+    }
+}
+
+#[async_trait]
+impl Publisher for InMemoryStore {
+    async fn publish(&self, fluss: &Fluss) -> anyhow::Result<()> {
+        self.insert(fluss.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::Expr;
+    use crate::testing::flow;
+    use std::net::Ipv4Addr;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn fluss(src: u8, dst_port: u16, bytes: u64) -> Fluss {
+        flow(
+            Ipv4Addr::new(192, 0, 2, src),
+            Ipv4Addr::new(198, 51, 100, 1),
+            dst_port,
+            bytes,
+            1,
+        )
+    }
+
+    /// Sources and bytes of the flows, the most recently updated first.
+    fn flows(store: &InMemoryStore) -> Vec<(String, u64)> {
+        store
+            .query(&|_: &Fluss| true)
+            .iter()
+            .map(|fluss| (fluss.src_addr.to_string(), fluss.bytes))
+            .collect()
+    }
+
+    #[test]
+    fn least_recently_updated_connection_is_evicted() {
+        let store = InMemoryStore::new(3, TTL);
+        store.insert(fluss(1, 443, 100));
+        store.insert(fluss(2, 443, 200));
+        store.insert(fluss(3, 443, 300));
+        // an update replaces the flow and makes it the most recent one
+        store.insert(fluss(1, 443, 150));
+        assert_eq!(store.len(), 3);
+
+        store.insert(fluss(4, 443, 400));
+        assert_eq!(store.len(), 3);
+        assert_eq!(
+            flows(&store),
+            [
+                ("192.0.2.4".to_owned(), 400),
+                ("192.0.2.1".to_owned(), 150),
+                ("192.0.2.3".to_owned(), 300),
+            ]
+        );
+    }
+
+    #[test]
+    fn expired_flows_are_dropped() {
+        let store = InMemoryStore::new(3, Duration::ZERO);
+        store.insert(fluss(1, 443, 100));
+        assert!(flows(&store).is_empty());
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn query_returns_the_matching_flows() {
+        let store = InMemoryStore::new(10, TTL);
+        store.insert(fluss(1, 443, 100));
+        store.insert(fluss(2, 80, 2000));
+        store.insert(fluss(3, 443, 3000));
+
+        let https = store.query(&|fluss: &Fluss| fluss.dst_port == 443);
+        let sources: Vec<_> = https.iter().map(|fluss| fluss.src_addr).collect();
+        assert_eq!(
+            sources,
+            [Ipv4Addr::new(192, 0, 2, 3), Ipv4Addr::new(192, 0, 2, 1)]
+        );
+
+        let expr: Expr = "dst_port == 443 && src_addr != 192.0.2.1".parse().unwrap();
+        let matching = store.query(&expr);
+        assert_eq!(matching.len(), 1);
+        assert_eq!(matching[0].src_addr, Ipv4Addr::new(192, 0, 2, 3));
+
+        assert!(store
+            .query(&|fluss: &Fluss| fluss.dst_port == 22)
+            .is_empty());
+        // querying does not remove flows
+        assert_eq!(store.len(), 3);
+    }
+}
@@ -0,0 +1,272 @@
+//! Socket activation and readiness notification of the systemd service manager.
+//!
+//! Implements the `sd_listen_fds` and `sd_notify` protocols without linking
+//! against libsystemd, all functions do nothing outside of systemd.
+//! Announcing the collector through mDNS or other service discovery is not supported.
+
+use anyhow::Context as _;
+use std::env;
+use std::io;
+use std::net::UdpSocket;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::os::unix::net::UnixDatagram;
+use std::time::Duration;
+
+/// First file descriptor passed by socket activation.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Returns the file descriptors passed by socket activation.
+///
+/// The variables are removed from the environment, so they are not inherited
+/// by child processes. Without socket activation no descriptors are returned.
+pub fn listen_fds() -> anyhow::Result<Vec<RawFd>> {
+    let pid = env::var("LISTEN_PID").ok();
+    let fds = env::var("LISTEN_FDS").ok();
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+
+    parse_listen_fds(pid.as_deref(), fds.as_deref(), std::process::id())
+}
+
+fn parse_listen_fds(
+    pid: Option<&str>,
+    fds: Option<&str>,
+    own_pid: u32,
+) -> anyhow::Result<Vec<RawFd>> {
+    let (pid, fds) = match (pid, fds) {
+        (Some(pid), Some(fds)) => (pid, fds),
+        _ => return Ok(Vec::new()),
+    };
+
+    let pid: u32 = pid
+        .parse()
+        .map_err(|_| anyhow::anyhow!("invalid LISTEN_PID {:?}", pid))?;
+    // the sockets were passed to the parent process
+    if pid != own_pid {
+        return Ok(Vec::new());
+    }
+
+    let fds: RawFd = fds
+        .parse()
+        .ok()
+        .filter(|&fds| fds >= 0)
+        .ok_or_else(|| anyhow::anyhow!("invalid LISTEN_FDS {:?}", fds))?;
+
+    Ok((LISTEN_FDS_START..LISTEN_FDS_START + fds).collect())
+}
+
+/// Returns the UDP socket passed by socket activation, `None` if no socket was passed.
+///
+/// Fails if more than one socket or a socket which is not a datagram socket was passed.
+pub fn udp_socket() -> anyhow::Result<Option<UdpSocket>> {
+    let fd = match listen_fds()?.as_slice() {
+        [] => return Ok(None),
+        &[fd] => fd,
+        fds => anyhow::bail!("expected a single socket from systemd, got {}", fds.len()),
+    };
+
+    let mut kind: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: `kind` and `len` are valid for the size passed to getsockopt
+    let result = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_TYPE,
+            &mut kind as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error())
+            .with_context(|| format!("file descriptor {} passed by systemd is not a socket", fd));
+    }
+    if kind != libc::SOCK_DGRAM {
+        anyhow::bail!(
+            "socket passed by systemd is not a datagram socket, type {}",
+            kind
+        );
+    }
+
+    // SAFETY: the descriptor was passed to this process and is owned by the socket from now on
+    let socket = unsafe { UdpSocket::from_raw_fd(fd) };
+    // tokio requires non-blocking sockets
+    socket.set_nonblocking(true)?;
+
+    Ok(Some(socket))
+}
+
+/// Sends a state like `READY=1` to the service manager.
+///
+/// Returns `false` if the process is not supervised by systemd.
+pub fn notify(state: &str) -> io::Result<bool> {
+    let path = match env::var_os("NOTIFY_SOCKET") {
+        Some(path) => path,
+        None => return Ok(false),
+    };
+
+    let socket = UnixDatagram::unbound()?;
+    let path = path.to_string_lossy();
+    match path.strip_prefix('@') {
+        // sockets in the abstract namespace are prefixed with an @
+        Some(name) => send_abstract(&socket, name, state)?,
+        None => {
+            socket.send_to(state.as_bytes(), path.as_ref())?;
+        }
+    }
+
+    Ok(true)
+}
+
+#[cfg(target_os = "linux")]
+fn send_abstract(socket: &UnixDatagram, name: &str, state: &str) -> io::Result<()> {
+    use std::os::linux::net::SocketAddrExt;
+
+    let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+    socket.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn send_abstract(_socket: &UnixDatagram, _name: &str, _state: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "abstract unix sockets are not supported",
+    ))
+}
+
+/// Returns how often `WATCHDOG=1` has to be sent, `None` if the watchdog is disabled.
+///
+/// The interval is half of the configured `WatchdogSec`, as recommended by systemd.
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(std::process::id()) {
+            return None;
+        }
+    }
+
+    let usec: u64 = env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+    match usec {
+        0 => None,
+        usec => Some(Duration::from_micros(usec / 2)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+
+    // the environment is shared by all tests
+    static ENV: Mutex<()> = parking_lot::const_mutex(());
+
+    #[test]
+    fn listen_fds_are_parsed() {
+        assert!(parse_listen_fds(None, None, 100).unwrap().is_empty());
+        assert!(parse_listen_fds(Some("100"), None, 100).unwrap().is_empty());
+        assert!(parse_listen_fds(None, Some("1"), 100).unwrap().is_empty());
+        assert!(parse_listen_fds(Some("100"), Some("0"), 100)
+            .unwrap()
+            .is_empty());
+        assert_eq!(parse_listen_fds(Some("100"), Some("1"), 100).unwrap(), [3]);
+        assert_eq!(
+            parse_listen_fds(Some("100"), Some("3"), 100).unwrap(),
+            [3, 4, 5]
+        );
+    }
+
+    #[test]
+    fn listen_fds_of_other_processes_are_ignored() {
+        assert!(parse_listen_fds(Some("99"), Some("1"), 100)
+            .unwrap()
+            .is_empty());
+        // the variables of the parent are checked before the count
+        assert!(parse_listen_fds(Some("99"), Some("x"), 100)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn invalid_listen_fds_are_rejected() {
+        assert!(parse_listen_fds(Some("pid"), Some("1"), 100).is_err());
+        assert!(parse_listen_fds(Some("-1"), Some("1"), 100).is_err());
+        assert!(parse_listen_fds(Some("100"), Some("many"), 100).is_err());
+        assert!(parse_listen_fds(Some("100"), Some("-1"), 100).is_err());
+    }
+
+    #[test]
+    fn listen_fds_are_removed_from_the_environment() {
+        let _env = ENV.lock();
+        env::set_var("LISTEN_PID", std::process::id().to_string());
+        env::set_var("LISTEN_FDS", "2");
+        env::set_var("LISTEN_FDNAMES", "ipfix:netflow");
+
+        assert_eq!(listen_fds().unwrap(), [3, 4]);
+        assert!(env::var_os("LISTEN_PID").is_none());
+        assert!(env::var_os("LISTEN_FDS").is_none());
+        assert!(env::var_os("LISTEN_FDNAMES").is_none());
+        // a second call does not see the sockets again
+        assert!(listen_fds().unwrap().is_empty());
+    }
+
+    #[test]
+    fn without_socket_activation_the_socket_is_bound() {
+        let _env = ENV.lock();
+        env::remove_var("LISTEN_PID");
+        env::remove_var("LISTEN_FDS");
+
+        assert!(udp_socket().unwrap().is_none());
+    }
+
+    #[test]
+    fn more_than_one_socket_is_rejected() {
+        let _env = ENV.lock();
+        env::set_var("LISTEN_PID", std::process::id().to_string());
+        env::set_var("LISTEN_FDS", "2");
+
+        let err = udp_socket().unwrap_err();
+        assert!(err.to_string().contains("single socket"), "{}", err);
+    }
+
+    #[test]
+    fn states_are_sent_to_the_notify_socket() {
+        let _env = ENV.lock();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify");
+        let socket = UnixDatagram::bind(&path).unwrap();
+
+        env::set_var("NOTIFY_SOCKET", &path);
+        let notified = notify("READY=1");
+        env::remove_var("NOTIFY_SOCKET");
+        assert!(notified.unwrap());
+
+        let mut buf = [0; 64];
+        let len = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+
+        assert!(!notify("READY=1").unwrap());
+    }
+
+    #[test]
+    fn watchdog_interval_is_half_of_the_timeout() {
+        let _env = ENV.lock();
+        env::remove_var("WATCHDOG_PID");
+        env::remove_var("WATCHDOG_USEC");
+        assert_eq!(watchdog_interval(), None);
+
+        env::set_var("WATCHDOG_USEC", "30000000");
+        assert_eq!(watchdog_interval(), Some(Duration::from_secs(15)));
+
+        env::set_var("WATCHDOG_PID", std::process::id().to_string());
+        assert_eq!(watchdog_interval(), Some(Duration::from_secs(15)));
+        // the watchdog of the parent process
+        env::set_var("WATCHDOG_PID", "1");
+        assert_eq!(watchdog_interval(), None);
+
+        env::remove_var("WATCHDOG_PID");
+        env::set_var("WATCHDOG_USEC", "0");
+        assert_eq!(watchdog_interval(), None);
+        env::remove_var("WATCHDOG_USEC");
+    }
+}
@@ -0,0 +1,339 @@
+//! Export of traces and pipeline metrics through OTLP.
+
+use crate::control::PipelineStats;
+use crate::enrich::class::{FlowHistograms, HistogramBucket};
+use opentelemetry::metrics::{AsyncInstrument, Histogram, MeterProvider as _};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::{MetricExporter, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tracing::Subscriber;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Fraction of packets traced by default.
+pub const DEFAULT_SAMPLE_RATIO: f64 = 0.001;
+
+const SCOPE: &str = "fluss";
+
+// checked before anything is measured, costs a relaxed load if the export is disabled
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static PUBLISH_DURATION: OnceLock<Histogram<f64>> = OnceLock::new();
+
+/// Exports sampled traces of the `packet` spans and the pipeline metrics
+/// to an OpenTelemetry collector through OTLP/gRPC.
+///
+/// The exporters run on their own runtime, so exports do not compete with
+/// the receiving of datagrams. Call [`Telemetry::shutdown`] before exiting
+/// to export the pending spans and metrics.
+pub struct Telemetry {
+    tracer_provider: SdkTracerProvider,
+    meter_provider: SdkMeterProvider,
+    // drives the connections of the exporters
+    _runtime: tokio::runtime::Runtime,
+}
+
+impl Telemetry {
+    /// Creates the exporters for the collector at `endpoint`, e.g. `http://collector:4317`.
+    ///
+    /// `sample_ratio` of the traces are exported, child spans follow the
+    /// decision of their root span.
+    pub fn new(endpoint: &str, sample_ratio: f64) -> anyhow::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("otel")
+            .enable_all()
+            .build()?;
+        let _guard = runtime.enter();
+
+        let resource = Resource::builder().with_service_name(SCOPE).build();
+
+        let spans = SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()?;
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_batch_exporter(spans)
+            .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                sample_ratio,
+            ))))
+            .with_resource(resource.clone())
+            .build();
+
+        let metrics = MetricExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()?;
+        let meter_provider = SdkMeterProvider::builder()
+            .with_periodic_exporter(metrics)
+            .with_resource(resource)
+            .build();
+        global::set_meter_provider(meter_provider.clone());
+
+        let publish_duration = meter_provider
+            .meter(SCOPE)
+            .f64_histogram("fluss.publish.duration")
+            .with_unit("s")
+            .with_description("Time it took to publish a flow")
+            .build();
+        // only the first instance records, there is a single one per process
+        let _ = PUBLISH_DURATION.set(publish_duration);
+        ENABLED.store(true, Ordering::Relaxed);
+
+        Ok(Self {
+            tracer_provider,
+            meter_provider,
+            _runtime: runtime,
+        })
+    }
+
+    /// Layer exporting the spans of fluss.
+    ///
+    /// Spans have to be enabled for the layer to see them, e.g. with a
+    /// per-layer filter independent of the log level.
+    pub fn layer<S>(&self) -> impl Layer<S>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        tracing_opentelemetry::layer().with_tracer(self.tracer_provider.tracer(SCOPE))
+    }
+
+    /// Exports all pending spans and metrics and stops the exporters.
+    pub fn shutdown(&self) -> anyhow::Result<()> {
+        ENABLED.store(false, Ordering::Relaxed);
+        self.tracer_provider.shutdown()?;
+        self.meter_provider.shutdown()?;
+        Ok(())
+    }
+}
+
+/// Whether metrics are exported, measurements on the hot path are skipped otherwise.
+#[inline]
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn record_publish_duration(duration: Duration) {
+    if let Some(histogram) = PUBLISH_DURATION.get() {
+        histogram.record(duration.as_secs_f64(), &[]);
+    }
+}
+
+/// Exports the pipeline counters, `stats` is called on every export.
+///
+/// Datagrams and flows which were dropped are counted by the `reason` of the drop.
+pub fn observe_pipeline(stats: impl Fn() -> PipelineStats + Send + Sync + 'static) {
+    let stats = Arc::new(stats);
+    let meter = global::meter(SCOPE);
+
+    let received = Arc::clone(&stats);
+    meter
+        .u64_observable_counter("fluss.datagrams.received")
+        .with_description("Datagrams received from exporters")
+        .with_callback(move |observer| observer.observe(received().datagrams, &[]))
+        .build();
+
+    let dropped = Arc::clone(&stats);
+    meter
+        .u64_observable_counter("fluss.datagrams.dropped")
+        .with_description("Datagrams which did not produce flows")
+        .with_callback(move |observer| {
+            let stats = dropped();
+            for (reason, count) in [
+                ("decode_error", stats.decode_errors),
+                ("denied", stats.denied_datagrams),
+                ("duplicate", stats.duplicate_messages),
+            ] {
+                observer.observe(count, &[KeyValue::new("reason", reason)]);
+            }
+        })
+        .build();
+
+    let decoded = Arc::clone(&stats);
+    meter
+        .u64_observable_counter("fluss.flows.decoded")
+        .with_description("Flows decoded from data records")
+        .with_callback(move |observer| observer.observe(decoded().flows, &[]))
+        .build();
+
+    let published = Arc::clone(&stats);
+    meter
+        .u64_observable_counter("fluss.flows.published")
+        .with_description("Flows handed to the publisher")
+        .with_callback(move |observer| observer.observe(published().published, &[]))
+        .build();
+
+    let rollup = Arc::clone(&stats);
+    meter
+        .u64_observable_gauge("fluss.rollup.keys")
+        .with_description("Distinct keys of the current rollup window")
+        .with_callback(move |observer| observer.observe(rollup().rollup_keys, &[]))
+        .build();
+
+    meter
+        .u64_observable_counter("fluss.flows.dropped")
+        .with_description("Flows which could not be published")
+        .with_callback(move |observer| {
+            observer.observe(
+                stats().publish_errors,
+                &[KeyValue::new("reason", "publish_error")],
+            )
+        })
+        .build();
+}
+
+/// Exports the distributions of flow sizes and durations, `histograms` is
+/// called on every export.
+///
+/// Every bucket is a counter of the flows of at most `le`, like the
+/// cumulative buckets of a Prometheus histogram.
+pub fn observe_flow_histograms(histograms: impl Fn() -> FlowHistograms + Send + Sync + 'static) {
+    fn observe(observer: &dyn AsyncInstrument<u64>, buckets: &[HistogramBucket]) {
+        let mut count = 0;
+        for bucket in buckets {
+            count += bucket.count;
+            observer.observe(count, &[KeyValue::new("le", bucket.le.to_string())]);
+        }
+    }
+
+    let histograms = Arc::new(histograms);
+    let meter = global::meter(SCOPE);
+
+    let bytes = Arc::clone(&histograms);
+    meter
+        .u64_observable_counter("fluss.flows.bytes")
+        .with_description("Flows by their size in bytes, scaled up by the sampling interval")
+        .with_callback(move |observer| observe(observer, &bytes().bytes))
+        .build();
+
+    meter
+        .u64_observable_counter("fluss.flows.duration")
+        .with_description("Flows by their duration in milliseconds")
+        .with_unit("ms")
+        .with_callback(move |observer| observe(observer, &histograms().duration_ms))
+        .build();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use parking_lot::Mutex;
+    use std::net::SocketAddr;
+    use tokio::net::TcpListener;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    type Requests = Arc<Mutex<Vec<(String, Vec<u8>)>>>;
+
+    /// Starts an OTLP collector on its own thread which accepts every export,
+    /// returns its address and the path and body of all requests.
+    fn collector() -> (SocketAddr, Requests) {
+        let requests = Requests::default();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        listener.set_nonblocking(true).unwrap();
+
+        let received = Arc::clone(&requests);
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            runtime.block_on(async move {
+                let listener = TcpListener::from_std(listener).unwrap();
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    tokio::spawn(serve(stream, Arc::clone(&received)));
+                }
+            });
+        });
+
+        (addr, requests)
+    }
+
+    async fn serve(stream: tokio::net::TcpStream, requests: Requests) {
+        let mut connection = match h2::server::handshake(stream).await {
+            Ok(connection) => connection,
+            Err(_) => return,
+        };
+        while let Some(Ok((request, mut respond))) = connection.accept().await {
+            let requests = Arc::clone(&requests);
+            tokio::spawn(async move {
+                let path = request.uri().path().to_owned();
+                let mut body = request.into_body();
+                let mut data = Vec::new();
+                while let Some(Ok(chunk)) = body.data().await {
+                    let _ = body.flow_control().release_capacity(chunk.len());
+                    data.extend_from_slice(&chunk);
+                }
+                requests.lock().push((path, data));
+
+                let response = http::Response::builder()
+                    .header("content-type", "application/grpc")
+                    .body(())
+                    .unwrap();
+                let mut send = respond.send_response(response, false).unwrap();
+                // an empty response message
+                send.send_data(Bytes::from_static(&[0; 5]), false).unwrap();
+                let mut trailers = http::HeaderMap::new();
+                trailers.insert("grpc-status", http::HeaderValue::from_static("0"));
+                send.send_trailers(trailers).unwrap();
+            });
+        }
+    }
+
+    #[test]
+    fn spans_and_metrics_are_exported() {
+        let (addr, requests) = collector();
+        let telemetry = Telemetry::new(&format!("http://{}", addr), 1.0).unwrap();
+        assert!(enabled());
+
+        observe_pipeline(|| PipelineStats {
+            datagrams: 7,
+            denied_datagrams: 1,
+            ..PipelineStats::default()
+        });
+        record_publish_duration(Duration::from_millis(3));
+        let subscriber = tracing_subscriber::registry().with(telemetry.layer());
+        tracing::subscriber::with_default(subscriber, || {
+            let _packet = tracing::info_span!("packet", exporter = "192.0.2.1").entered();
+            tracing::info_span!("decode").in_scope(|| ());
+        });
+
+        telemetry.shutdown().unwrap();
+        assert!(!enabled());
+
+        let requests = requests.lock();
+        let body = |service: &str| {
+            let path = format!("/opentelemetry.proto.collector.{}/Export", service);
+            requests
+                .iter()
+                .filter(|(request, _)| *request == path)
+                .flat_map(|(_, body)| body.iter().copied())
+                .collect::<Vec<_>>()
+        };
+        let contains = |body: &[u8], needle: &str| {
+            body.windows(needle.len())
+                .any(|window| window == needle.as_bytes())
+        };
+
+        let spans = body("trace.v1.TraceService");
+        for needle in ["packet", "decode", "192.0.2.1", "fluss"] {
+            assert!(contains(&spans, needle), "{} was not exported", needle);
+        }
+        let metrics = body("metrics.v1.MetricsService");
+        for needle in [
+            "fluss.datagrams.received",
+            "fluss.datagrams.dropped",
+            "denied",
+            "fluss.publish.duration",
+        ] {
+            assert!(contains(&metrics, needle), "{} was not exported", needle);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Allocations of the receive path, counted by a global allocator.
+//!
+//! Allocations are counted per thread, tests running in parallel do not
+//! see the allocations of each other.
+
+use fluss::ipfix::parser::TemplateRecord;
+use fluss::pool::BufferPool;
+use fluss::testing::{field, DataRecord, MessageBuilder};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::net::{Ipv4Addr, UdpSocket};
+
+const DATAGRAM_SIZE: usize = 1400;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    static ALLOCATED: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        record(layout.size());
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn record(size: usize) {
+    // the thread locals are gone while the thread is torn down
+    let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+    let _ = ALLOCATED.try_with(|bytes| bytes.set(bytes.get() + size));
+}
+
+/// Returns the number of allocations and the allocated bytes of the thread so far.
+fn counters() -> (usize, usize) {
+    (ALLOCATIONS.with(Cell::get), ALLOCATED.with(Cell::get))
+}
+
+/// A message with a single data set filling a typical datagram.
+fn message() -> Vec<u8> {
+    let template = TemplateRecord {
+        id: 256,
+        fields: vec![field(8, 4), field(12, 4), field(4, 1), field(1, 8)],
+    };
+    let record = DataRecord::new()
+        .addr(Ipv4Addr::new(192, 0, 2, 1).into())
+        .addr(Ipv4Addr::new(198, 51, 100, 1).into())
+        .u8(6)
+        .u64(1500);
+    let records = vec![record; (DATAGRAM_SIZE - 20) / 17];
+
+    let mut builder = MessageBuilder::new(0);
+    builder.templates(&[template]);
+    builder.data(256, &records)
+}
+
+#[test]
+fn received_datagrams_reuse_the_buffer() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()
+        .unwrap();
+
+    runtime.block_on(async {
+        let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let exporter = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let collector = socket.local_addr().unwrap();
+        let datagram = message();
+        let mut pool = BufferPool::new(u16::MAX as usize);
+
+        let mut received = 0;
+        let mut allocations = 0;
+        let mut allocated = 0;
+        for _ in 0..64 {
+            exporter.send_to(&datagram, collector).unwrap();
+            let (before, before_bytes) = counters();
+            let result = pool.recv_from(&socket).await;
+            let (after, after_bytes) = counters();
+            let (data, _) = result.unwrap();
+            received += data.len();
+            allocations += after - before;
+            allocated += after_bytes - before_bytes;
+        }
+
+        assert_eq!(received, 64 * datagram.len());
+        // the chunk is reused, datagrams are dropped before the next one is received,
+        // only the runtime allocates once in a while
+        assert!(allocations < 4, "{} allocations", allocations);
+        assert!(allocated < DATAGRAM_SIZE, "{} bytes allocated", allocated);
+    });
+}
+
+#[test]
+fn data_sets_are_not_copied() {
+    let datagram = bytes::Bytes::from(message());
+    assert!(datagram.len() > DATAGRAM_SIZE - 20);
+
+    let (before, before_bytes) = counters();
+    let packet = fluss::ipfix::parse_owned(datagram.clone()).unwrap();
+    let (allocations, allocated) = {
+        let (after, after_bytes) = counters();
+        (after - before, after_bytes - before_bytes)
+    };
+    assert_eq!(packet.sets().len(), 1);
+
+    // the sets of the packet collected into a shared slice, the data is not copied
+    assert!(allocations <= 3, "{} allocations", allocations);
+    assert!(allocated < 512, "{} bytes allocated", allocated);
+}